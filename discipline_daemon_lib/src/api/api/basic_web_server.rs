@@ -2,18 +2,19 @@
 // TODO: limit the server request payload size
 
 use std::collections::HashMap;
-use std::net::SocketAddrV4;
+use std::net::{SocketAddr, SocketAddrV4};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use url::Url;
 use crate::GenericError;
+use crate::web_regulation_intrusive::proxy_auto_config::PROXY_AUTO_CONFIG_MEDIA_TYPE;
 
 pub struct WebServer {
   actual_server: tiny_http::Server,
 }
 
 impl WebServer {
-  pub fn new<Handler>(
+  pub fn new(
     address: SocketAddrV4, 
   ) 
     -> Result<Self, GenericError> 
@@ -62,7 +63,9 @@ pub struct Request {
 
 impl Request {
   pub fn wrap(request: tiny_http::Request) -> Result<Self, RecieveError> {
-    let url = match request.url().parse::<Url>() {
+    // The request target is usually just a path and a query, so it's resolved
+    // against a placeholder origin.
+    let url = match Url::parse("http://localhost").and_then(|base| base.join(request.url())) {
       Ok(url) => {
         url
       }
@@ -91,6 +94,19 @@ impl Request {
     &self.query_parameters
   }
 
+  pub fn remote_address(&self) -> Option<SocketAddr> {
+    self.req.remote_addr().copied()
+  }
+
+  pub fn header(&self, name: &str) -> Option<&str> {
+    self
+      .req
+      .headers()
+      .iter()
+      .find(|header| header.field.as_str().as_str().eq_ignore_ascii_case(name))
+      .map(|header| header.value.as_str())
+  }
+
   pub fn deserialize_body<T>(&mut self) -> Result<T, DeserializeBodyError>
   where
     T: DeserializeOwned
@@ -131,6 +147,58 @@ impl Request {
     }
   }
 
+  pub fn respond_with_html(self, status_code: u16, html: String) {
+    let Ok(content_type_header) = tiny_http::Header::from_bytes(
+      b"Content-Type", 
+      b"text/html; charset=utf-8",
+    ) else {
+      self.respond_with_http_internal_server_error();
+      return;
+    };
+    let Ok(cache_control_header) = tiny_http::Header::from_bytes(
+      b"Cache-Control", 
+      b"no-store",
+    ) else {
+      self.respond_with_http_internal_server_error();
+      return;
+    };
+
+    let response = tiny_http::Response::from_string(html)
+      .with_status_code(status_code)
+      .with_header(content_type_header)
+      .with_header(cache_control_header);
+
+    if let Err(error) = self.req.respond(response) {
+      eprintln!("Discipline.Server.RespondWithHtml: {error}");
+    }
+  }
+
+  pub fn respond_with_proxy_auto_config(self, script: String) {
+    let Ok(content_type_header) = tiny_http::Header::from_bytes(
+      b"Content-Type", 
+      PROXY_AUTO_CONFIG_MEDIA_TYPE.as_bytes(),
+    ) else {
+      self.respond_with_http_internal_server_error();
+      return;
+    };
+    let Ok(cache_control_header) = tiny_http::Header::from_bytes(
+      b"Cache-Control", 
+      b"no-store",
+    ) else {
+      self.respond_with_http_internal_server_error();
+      return;
+    };
+
+    let response = tiny_http::Response::from_string(script)
+      .with_status_code(200)
+      .with_header(content_type_header)
+      .with_header(cache_control_header);
+
+    if let Err(error) = self.req.respond(response) {
+      eprintln!("Discipline.Server.RespondWithProxyAutoConfig: {error}");
+    }
+  }
+
   pub fn respond_with_http_not_found(self) {
     if let Err(error) = self.req.respond(tiny_http::Response::empty(404)) {
       eprintln!("Discipline.Server.RespondWithNotFound: {error}");
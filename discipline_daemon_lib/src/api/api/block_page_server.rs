@@ -0,0 +1,422 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::sync::Arc;
+use std::thread::{spawn, JoinHandle};
+use url::form_urlencoded;
+use crate::operating_system_integration::retrieve_tcp_socket_owner;
+use crate::web_regulation_intrusive::proxy_auto_config::PROXY_AUTO_CONFIG_PATH;
+use crate::web_regulation_intrusive::RuleActivator;
+use crate::{Daemon, DateTime, GenericError, UserId, Uuid};
+use super::basic_web_server::{RecieveError, Request, WebServer};
+use super::translations::Language;
+use super::view::{BlockCause, BlockPage, Resumption, WebRuleFeature};
+
+/// The path blocking features redirect users to, with a `BlockReason` in
+/// the query. Requests for any other path, except the proxy auto-config
+/// file's, are connections to blocked sites that were pointed at the block
+/// page server, by the firewall or by DNS.
+pub const BLOCK_PAGE_PATH: &str = "/blocked";
+
+/// Why a blocking path sent the user to the block page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockReason {
+  InternetAccessRegulation,
+  /// A service without a safe address was opened while SafeSearch is
+  /// enforced on the user. Settings are per user, so the user the
+  /// connection belongs to tells which one.
+  SafeSearch,
+  WebRule {
+    feature: WebRuleFeature,
+    rule_id: Uuid,
+  },
+}
+
+impl BlockReason {
+  /// Returns the url of the block page explaining why `blocked_url` was
+  /// blocked, to redirect the user to.
+  pub fn block_page_url(&self, block_page_port: u16, blocked_url: &str) -> String {
+    let mut query = form_urlencoded::Serializer::new(String::new());
+
+    match self {
+      BlockReason::InternetAccessRegulation => {
+        query.append_pair("reason", "internet-access-regulation");
+      }
+      BlockReason::SafeSearch => {
+        query.append_pair("reason", "safe-search");
+      }
+      BlockReason::WebRule { feature, rule_id } => {
+        query
+          .append_pair("reason", "web-rule")
+          .append_pair("feature", feature.identifier())
+          .append_pair("rule", &rule_id.to_string());
+      }
+    }
+
+    query.append_pair("url", blocked_url);
+    format!("http://127.0.0.1:{block_page_port}{BLOCK_PAGE_PATH}?{}", query.finish())
+  }
+
+  pub fn from_query_parameters(parameters: &HashMap<String, String>) -> Option<BlockReason> {
+    match parameters.get("reason")?.as_str() {
+      "internet-access-regulation" => {
+        Some(BlockReason::InternetAccessRegulation)
+      }
+      "safe-search" => {
+        Some(BlockReason::SafeSearch)
+      }
+      "web-rule" => {
+        Some(BlockReason::WebRule {
+          feature: WebRuleFeature::from_identifier(parameters.get("feature")?)?,
+          rule_id: Uuid::parse_str(parameters.get("rule")?).ok()?,
+        })
+      }
+      _ => {
+        None
+      }
+    }
+  }
+}
+
+/// Serves block pages, and the proxy auto-config file, on `127.0.0.1` at the
+/// configured block page port.
+pub fn launch_thread(daemon: Arc<Daemon>) -> Result<JoinHandle<()>, GenericError> {
+  let address = SocketAddrV4::new(
+    Ipv4Addr::LOCALHOST,
+    daemon.configuration().block_page_port(),
+  );
+
+  let server = WebServer::new(address).map_err(|error|
+    error.change_context("launching the block page server")
+  )?;
+
+  Ok(spawn(move || {
+    loop {
+      match server.recieve() {
+        Ok(request) => {
+          respond(&daemon, request);
+        }
+        Err(RecieveError::MalformedUrl { .. }) => {
+          continue;
+        }
+        Err(RecieveError::NetworkError { .. }) => {
+          // The server was shut down.
+          return;
+        }
+      }
+    }
+  }))
+}
+
+fn respond(daemon: &Daemon, request: Request) {
+  if request.url().path() == PROXY_AUTO_CONFIG_PATH {
+    let script = daemon
+      .web_regulation_intrusive()
+      .proxy_auto_config()
+      .script(daemon.configuration().proxy_auto_config_proxy_address());
+
+    request.respond_with_proxy_auto_config(script);
+    return;
+  }
+
+  let now = DateTime::now();
+  let language = Language::negotiate(request.header("Accept-Language"));
+
+  let (blocked_url, reason) = if request.url().path() == BLOCK_PAGE_PATH {
+    let parameters = request.query_parameters();
+    (
+      parameters.get("url").cloned(),
+      BlockReason::from_query_parameters(parameters),
+    )
+  } else {
+    // The request was meant for the blocked site, so it tells what the user
+    // tried to open.
+    let blocked_url = request.header("Host").map(|host| {
+      let url = request.url();
+      match url.query() {
+        Some(query) => format!("http://{host}{}?{query}", url.path()),
+        None => format!("http://{host}{}", url.path()),
+      }
+    });
+
+    (blocked_url, None)
+  };
+
+  let user_id = || request
+    .remote_address()
+    .and_then(retrieve_tcp_socket_owner);
+
+  let (causes, resumption) = match reason {
+    Some(BlockReason::WebRule { feature, rule_id }) => {
+      web_rule_block(daemon, user_id(), feature, &rule_id, now)
+    }
+    Some(BlockReason::SafeSearch) => {
+      safe_search_block(daemon, user_id(), now)
+    }
+    Some(BlockReason::InternetAccessRegulation) | None => {
+      internet_access_block(daemon, user_id(), now)
+    }
+  };
+
+  let page = BlockPage {
+    language,
+    blocked_url,
+    causes,
+    resumption,
+  };
+
+  request.respond_with_html(403, page.render());
+}
+
+fn internet_access_block(
+  daemon: &Daemon,
+  user_id: Option<UserId>,
+  now: DateTime,
+) -> (Vec<BlockCause>, Resumption) {
+  let Some(user_id) = user_id else {
+    return (Vec::new(), Resumption::Unknown);
+  };
+
+  let Ok(integration) = daemon.operating_system_integration().lock_data() else {
+    return (Vec::new(), Resumption::Unknown);
+  };
+
+  let Some(user) = integration.users.get(&user_id) else {
+    return (Vec::new(), Resumption::Unknown);
+  };
+
+  let regulation = &user.user_internet_access_regulation_logic;
+
+  let causes: Vec<BlockCause> = regulation
+    .blocking_rules(now)
+    .into_iter()
+    .map(|(policy, rule)| BlockCause::PolicyRule {
+      policy_name: policy.name().as_ref().clone(),
+      activator: rule.activator().into(),
+    })
+    .collect();
+
+  if causes.is_empty() {
+    return (causes, Resumption::NoLongerBlocked);
+  }
+
+  let resumption = match regulation.blocks_until(now) {
+    Some(time) => Resumption::At(time),
+    None => Resumption::Never,
+  };
+
+  (causes, resumption)
+}
+
+fn safe_search_block(
+  daemon: &Daemon,
+  user_id: Option<UserId>,
+  now: DateTime,
+) -> (Vec<BlockCause>, Resumption) {
+  let Some(user_id) = user_id else {
+    return (Vec::new(), Resumption::Unknown);
+  };
+
+  let activator = daemon
+    .web_regulation_intrusive()
+    .safe_search()
+    .settings()
+    .iter()
+    .find(|setting| setting.user_id() == user_id && setting.activator().is_effective(now))
+    .map(|setting| setting.activator().clone());
+
+  let Some(activator) = activator else {
+    return (Vec::new(), Resumption::NoLongerBlocked);
+  };
+
+  let cause = BlockCause::SafeSearch {
+    activator: (&activator).into(),
+  };
+
+  (vec![cause], activator_resumption(&activator, now))
+}
+
+/// How a web rule blocks, as far as the block page is concerned.
+enum WebRuleState {
+  /// The rule blocks while its activator is effective.
+  Activated(RuleActivator),
+  /// The rule has no activator and blocks until `Resumption`, which is
+  /// `NoLongerBlocked` once it stopped.
+  Unscheduled(Resumption),
+}
+
+fn web_rule_block(
+  daemon: &Daemon,
+  user_id: Option<UserId>,
+  feature: WebRuleFeature,
+  rule_id: &Uuid,
+  now: DateTime,
+) -> (Vec<BlockCause>, Resumption) {
+  let Some(user_id) = user_id else {
+    return (Vec::new(), Resumption::Unknown);
+  };
+
+  match find_web_rule(daemon, user_id, feature, rule_id, now) {
+    None => {
+      (Vec::new(), Resumption::Unknown)
+    }
+    Some(WebRuleState::Activated(activator)) => {
+      if !activator.is_effective(now) {
+        return (Vec::new(), Resumption::NoLongerBlocked);
+      }
+
+      let cause = BlockCause::WebRule {
+        feature,
+        activator: Some((&activator).into()),
+      };
+
+      (vec![cause], activator_resumption(&activator, now))
+    }
+    Some(WebRuleState::Unscheduled(Resumption::NoLongerBlocked)) => {
+      (Vec::new(), Resumption::NoLongerBlocked)
+    }
+    Some(WebRuleState::Unscheduled(resumption)) => {
+      let cause = BlockCause::WebRule {
+        feature,
+        activator: None,
+      };
+
+      (vec![cause], resumption)
+    }
+  }
+}
+
+/// When an effective activator stops being effective.
+fn activator_resumption(activator: &RuleActivator, now: DateTime) -> Resumption {
+  match activator.effective_until(now) {
+    Some(time) => Resumption::At(time),
+    None => Resumption::Never,
+  }
+}
+
+/// The state of the rule `rule_id` of `feature`, if it's one of `user_id`'s,
+/// so users can't learn about each other's rules from the block page.
+fn find_web_rule(
+  daemon: &Daemon,
+  user_id: UserId,
+  feature: WebRuleFeature,
+  rule_id: &Uuid,
+  now: DateTime,
+) -> Option<WebRuleState> {
+  let proxy = daemon.web_regulation_intrusive();
+
+  match feature {
+    WebRuleFeature::ContentFilter => {
+      proxy
+        .content_filter()
+        .rules()
+        .iter()
+        .find(|rule| rule.user_id() == user_id && rule.id() == rule_id)
+        .map(|rule| WebRuleState::Activated(rule.activator().clone()))
+    }
+    WebRuleFeature::ImageRule => {
+      proxy
+        .image_regulation()
+        .rules()
+        .iter()
+        .find(|rule| rule.user_id() == user_id && rule.id() == rule_id)
+        .map(|rule| WebRuleState::Activated(rule.activator().clone()))
+    }
+    WebRuleFeature::MediaTypeRule => {
+      proxy
+        .media_type_blocker()
+        .rules()
+        .iter()
+        .find(|rule| rule.user_id() == user_id && rule.id() == rule_id)
+        .map(|rule| WebRuleState::Activated(rule.activator().clone()))
+    }
+    WebRuleFeature::ProtobufRule => {
+      proxy
+        .protobuf_regulation()
+        .rules()
+        .iter()
+        .find(|rule| rule.user_id() == user_id && rule.id() == rule_id)
+        .map(|rule| WebRuleState::Activated(rule.activator().clone()))
+    }
+    WebRuleFeature::SearchQueryRule => {
+      proxy
+        .search_query_blocker()
+        .rules()
+        .iter()
+        .find(|rule| rule.user_id() == user_id && rule.id() == rule_id)
+        .map(|rule| WebRuleState::Activated(rule.activator().clone()))
+    }
+    WebRuleFeature::YoutubeRule => {
+      proxy
+        .youtube()
+        .rules()
+        .iter()
+        .any(|rule| rule.user_id() == user_id && rule.id() == rule_id)
+        .then_some(WebRuleState::Unscheduled(Resumption::Never))
+    }
+    WebRuleFeature::TwitterFilter => {
+      proxy
+        .twitter()
+        .filters()
+        .iter()
+        .any(|filter| filter.user_id() == user_id && filter.id() == rule_id)
+        .then_some(WebRuleState::Unscheduled(Resumption::Never))
+    }
+    WebRuleFeature::ElementRule => {
+      proxy
+        .element_regulation()
+        .sheets()
+        .iter()
+        .any(|sheet| sheet.user_id() == user_id && sheet.id() == rule_id)
+        .then_some(WebRuleState::Unscheduled(Resumption::Never))
+    }
+    WebRuleFeature::WebsiteVisitsLimiter => {
+      let mut limiter = proxy
+        .website_visits_limiter()
+        .limiters()
+        .iter()
+        .find(|limiter| limiter.user_id() == user_id && limiter.id() == rule_id)?
+        .clone();
+
+      limiter.forget_expired_visits(now);
+      let resumption = match limiter.next_visit_available_at() {
+        Some(time) => Resumption::At(time),
+        None => Resumption::NoLongerBlocked,
+      };
+
+      Some(WebRuleState::Unscheduled(resumption))
+    }
+    WebRuleFeature::WebsiteVisitDelayer => {
+      let mut delayer = proxy
+        .website_visit_delayer()
+        .delayers()
+        .iter()
+        .find(|delayer| delayer.user_id() == user_id && delayer.id() == rule_id)?
+        .clone();
+
+      delayer.synchronize(now);
+      let resumption = match delayer.remaining_block_duration() {
+        Some(remaining) => Resumption::In(remaining),
+        None => Resumption::NoLongerBlocked,
+      };
+
+      Some(WebRuleState::Unscheduled(resumption))
+    }
+    WebRuleFeature::ViewTimeAllowance => {
+      let mut allowance = proxy
+        .view_time_allowances()
+        .allowances()
+        .iter()
+        .find(|allowance| allowance.user_id() == user_id && allowance.id() == rule_id)?
+        .clone();
+
+      allowance.synchronize(now);
+      let resumption = if allowance.is_used_up() {
+        Resumption::At(allowance.renews_at())
+      } else {
+        Resumption::NoLongerBlocked
+      };
+
+      Some(WebRuleState::Unscheduled(resumption))
+    }
+  }
+}
@@ -15,6 +15,16 @@ pub use operations::*;
 
 mod server;
 
+mod web_view;
+mod translations;
+pub use translations::{Language, Text};
+
+mod view;
+pub use view::{ActivatorDescription, BlockCause, BlockPage, Resumption, WebRuleFeature};
+
+pub mod block_page_server;
+pub use block_page_server::{BlockReason, BLOCK_PAGE_PATH};
+
 // pub use server::launch_thread;
 // pub struct Api {
 //   daemon_mutex: DaemonMutex,
@@ -23,4 +33,6 @@ mod server;
 
 // impl Api {
 
-// }
\ No newline at end of file
+// }
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use url::Url;
+use crate::internet_access_regulation::{self, Policy, PolicyName, Regulation, Rule};
+use crate::web_regulation_intrusive::RuleActivator;
+use crate::{DateTime, Duration, TimeRange, Uuid, WeekdayRange};
+use super::translations::Language;
+use super::view::{ActivatorDescription, BlockCause, BlockPage, Resumption, WebRuleFeature};
+use super::web_view::UI;
+use super::BlockReason;
+
+/// Monday, January 1, 2024 0:00 UTC.
+const MONDAY: i64 = 1_704_067_200_000;
+
+fn at(days: i64, hours: i64, minutes: i64) -> DateTime {
+  DateTime::from_timestamp(MONDAY + ((days * 24 + hours) * 60 + minutes) * 60_000).unwrap()
+}
+
+fn time_range(from_hours: u32, till_hours: u32) -> TimeRange {
+  TimeRange::from_timestamps(from_hours * 3_600_000, till_hours * 3_600_000).unwrap()
+}
+
+fn policy(rules: Vec<internet_access_regulation::RuleActivator>, is_enabled: bool) -> Policy {
+  Policy::from_fields(
+    Uuid::from_u128(1),
+    PolicyName::new("Work".into()).unwrap(),
+    rules
+      .into_iter()
+      .enumerate()
+      .map(|(index, activator)| Rule::new(Uuid::from_u128(index as u128 + 10), activator))
+      .collect(),
+    is_enabled,
+    Duration::ZERO,
+    Duration::ZERO,
+    at(0, 0, 0),
+  )
+}
+
+#[test]
+fn time_range_activator_is_effective_until_the_minute_after_its_end() {
+  let activator = RuleActivator::InTimeRange(time_range(9, 17));
+
+  assert_eq!(activator.effective_until(at(0, 10, 30)), Some(at(0, 17, 1)));
+  assert_eq!(activator.effective_until(at(0, 18, 0)), Some(at(0, 18, 0)));
+}
+
+#[test]
+fn weekday_activators_are_effective_until_midnight() {
+  // Monday through Friday, counted from Sunday.
+  let weekdays = RuleActivator::InWeekdayRange(WeekdayRange::from_timestamps(1, 5).unwrap());
+  assert_eq!(weekdays.effective_until(at(2, 12, 0)), Some(at(5, 0, 0)));
+
+  let monday = RuleActivator::OnWeekday(crate::Weekday::Monday);
+  assert_eq!(monday.effective_until(at(0, 10, 0)), Some(at(1, 0, 0)));
+  assert_eq!(monday.effective_until(at(1, 10, 0)), Some(at(1, 10, 0)));
+
+  assert_eq!(RuleActivator::AllTheTime.effective_until(at(0, 0, 0)), None);
+}
+
+#[test]
+fn regulation_blocks_until_overlapping_rules_all_end() {
+  use internet_access_regulation::RuleActivator;
+
+  let regulation = Regulation::new(vec![
+    policy(vec![
+      RuleActivator::InTimeRange(time_range(9, 12)),
+      RuleActivator::InTimeRange(time_range(11, 14)),
+    ], true),
+    policy(vec![RuleActivator::AllTheTime], false),
+  ]);
+
+  assert_eq!(regulation.blocking_rules(at(0, 10, 0)).len(), 1);
+  assert_eq!(regulation.blocks_until(at(0, 10, 0)), Some(at(0, 14, 1)));
+  assert_eq!(regulation.blocks_until(at(0, 15, 0)), Some(at(0, 15, 0)));
+
+  let regulation = Regulation::new(vec![
+    policy(vec![RuleActivator::AllTheTime], true),
+  ]);
+
+  assert_eq!(regulation.blocks_until(at(0, 10, 0)), None);
+}
+
+#[test]
+fn language_is_negotiated_from_accept_language() {
+  assert_eq!(Language::negotiate(None), Language::English);
+  assert_eq!(Language::negotiate(Some("ja, ko;q=0.8")), Language::English);
+  assert_eq!(Language::negotiate(Some("de-CH, fr;q=0.9")), Language::German);
+  assert_eq!(Language::negotiate(Some("fr;q=0.5, es-MX;q=0.8")), Language::Spanish);
+  assert_eq!(Language::negotiate(Some("en;q=0, fr;q=0.1")), Language::French);
+}
+
+#[test]
+fn ui_escapes_text_and_attributes() {
+  let mut ui = UI::new();
+  ui
+    .open("p").attr("title", "\"quoted\"")
+      .template("Hello {name} & bye", |ui, _| {
+        ui.open("b").text("<you>").close();
+      });
+
+  assert_eq!(
+    ui.finish(),
+    "<!DOCTYPE html><p title=\"&quot;quoted&quot;\">Hello <b>&lt;you&gt;</b> &amp; bye</p>",
+  );
+}
+
+#[test]
+fn block_page_shows_the_policy_rule_and_resumption_time() {
+  let page = BlockPage {
+    language: Language::French,
+    blocked_url: Some("http://example.com/<script>".into()),
+    causes: vec![BlockCause::PolicyRule {
+      policy_name: "Travail".into(),
+      activator: ActivatorDescription::AllTheTime,
+    }],
+    resumption: Resumption::At(at(0, 17, 1)),
+  }.render();
+
+  assert!(page.contains("<html lang=\"fr\">"));
+  assert!(page.contains("<title>Accès bloqué</title>"));
+  assert!(page.contains("http://example.com/&lt;script&gt;"));
+  assert!(page.contains("« <strong>Travail</strong> »"));
+  assert!(page.contains("en permanence"));
+  assert!(page.contains(&format!("data-timestamp=\"{}\"", at(0, 17, 1).timestamp())));
+  assert!(page.ends_with("</html>"));
+}
+
+#[test]
+fn block_page_says_when_nothing_blocks_anymore() {
+  let page = BlockPage {
+    language: Language::English,
+    blocked_url: None,
+    causes: Vec::new(),
+    resumption: Resumption::NoLongerBlocked,
+  }.render();
+
+  assert!(page.contains("It&#39;s no longer blocked."));
+  assert!(!page.contains("Access resumes"));
+}
+
+#[test]
+fn block_reason_round_trips_through_the_block_page_url() {
+  let reason = BlockReason::WebRule {
+    feature: WebRuleFeature::SearchQueryRule,
+    rule_id: Uuid::from_u128(7),
+  };
+
+  let url = reason.block_page_url(9120, "https://example.com/?q=a&b");
+  let url = Url::parse(&url).unwrap();
+  let parameters: HashMap<String, String> = url.query_pairs().into_owned().collect();
+
+  assert_eq!(url.path(), "/blocked");
+  assert_eq!(url.port(), Some(9120));
+  assert_eq!(parameters.get("url").map(String::as_str), Some("https://example.com/?q=a&b"));
+  assert_eq!(BlockReason::from_query_parameters(&parameters), Some(reason));
+}
+
+#[test]
+fn every_block_reason_round_trips_through_the_block_page_url() {
+  let reasons = WebRuleFeature::ALL
+    .into_iter()
+    .map(|feature| BlockReason::WebRule { feature, rule_id: Uuid::from_u128(7) })
+    .chain([BlockReason::InternetAccessRegulation, BlockReason::SafeSearch]);
+
+  for reason in reasons {
+    let url = Url::parse(&reason.block_page_url(9120, "https://example.com/")).unwrap();
+    let parameters: HashMap<String, String> = url.query_pairs().into_owned().collect();
+    assert_eq!(BlockReason::from_query_parameters(&parameters), Some(reason));
+  }
+}
+
+#[test]
+fn block_page_links_back_to_the_blocked_page_until_access_resumes() {
+  let page = BlockPage {
+    language: Language::English,
+    blocked_url: Some("https://example.com/watch".into()),
+    causes: vec![BlockCause::WebRule {
+      feature: WebRuleFeature::WebsiteVisitDelayer,
+      activator: None,
+    }],
+    resumption: Resumption::At(at(0, 12, 2)),
+  }.render();
+
+  assert!(page.contains("It&#39;s blocked by a website visit delayer rule."));
+  assert!(page.contains(&format!(
+    "<a href=\"https://example.com/watch\" data-resume-at=\"{}\">",
+    at(0, 12, 2).timestamp(),
+  )));
+
+  // Only web pages are linked.
+  let page = BlockPage {
+    language: Language::English,
+    blocked_url: Some("javascript:alert(1)".into()),
+    causes: Vec::new(),
+    resumption: Resumption::Unknown,
+  }.render();
+
+  assert!(!page.contains("<a "));
+}
+
+#[test]
+fn block_page_counts_down_delays_and_reopens_the_blocked_page_after() {
+  let page = BlockPage {
+    language: Language::English,
+    blocked_url: Some("https://example.com/watch".into()),
+    causes: vec![BlockCause::WebRule {
+      feature: WebRuleFeature::WebsiteVisitDelayer,
+      activator: None,
+    }],
+    resumption: Resumption::In(Duration::from_milliseconds(90_500)),
+  }.render();
+
+  assert!(page.contains("Access resumes in <span data-countdown=\"90500\">1:31</span>."));
+  assert!(page.contains("<a href=\"https://example.com/watch\" data-resume-in=\"90500\">"));
+}
@@ -0,0 +1,278 @@
+use crate::Weekday;
+use super::view::WebRuleFeature;
+
+/// A language the pages served by the daemon are translated to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+  English,
+  French,
+  German,
+  Spanish,
+}
+
+impl Language {
+  pub const ALL: [Language; 4] = [
+    Language::English,
+    Language::French,
+    Language::German,
+    Language::Spanish,
+  ];
+
+  /// The ISO 639-1 code of the language.
+  pub fn code(&self) -> &'static str {
+    match self {
+      Language::English => "en",
+      Language::French => "fr",
+      Language::German => "de",
+      Language::Spanish => "es",
+    }
+  }
+
+  /// Returns the language of a language tag like `fr` or `fr-CH`.
+  pub fn from_tag(tag: &str) -> Option<Language> {
+    let primary = tag.split('-').next()?.trim();
+    Language::ALL
+      .into_iter()
+      .find(|language| language.code().eq_ignore_ascii_case(primary))
+  }
+
+  /// Picks the language the browser prefers most from the value of an
+  /// `Accept-Language` header, falling back to English.
+  pub fn negotiate(accept_language: Option<&str>) -> Language {
+    let Some(accept_language) = accept_language else {
+      return Language::English;
+    };
+
+    let mut best: Option<(Language, f32)> = None;
+
+    for entry in accept_language.split(',') {
+      let mut parts = entry.split(';');
+      let tag = parts.next().unwrap_or_default();
+
+      let quality = parts
+        .find_map(|parameter| parameter.trim().strip_prefix("q="))
+        .map(|quality| quality.trim().parse::<f32>().unwrap_or(0.0))
+        .unwrap_or(1.0);
+
+      let Some(language) = Language::from_tag(tag) else {
+        continue;
+      };
+
+      if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+        best = Some((language, quality));
+      }
+    }
+
+    best.map_or(Language::English, |(language, _)| language)
+  }
+
+  pub fn translate(&self, text: Text) -> &'static str {
+    match self {
+      Language::English => english(text),
+      Language::French => french(text),
+      Language::German => german(text),
+      Language::Spanish => spanish(text),
+    }
+  }
+}
+
+/// A piece of text on a page served by the daemon. Words in braces are
+/// placeholders the page fills in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Text {
+  BlockPageTitle,
+  /// `{url}`
+  BlockedUrl,
+  /// `{policy}` and `{activator}`
+  BlockedByPolicyRule,
+  /// `{feature}` and `{activator}`
+  BlockedByWebRule,
+  /// `{feature}`
+  BlockedByUnscheduledWebRule,
+  /// `{activator}`
+  BlockedBySafeSearch,
+  UnknownCause,
+  /// `{time}`
+  ResumesAt,
+  /// `{countdown}`
+  ResumesIn,
+  NeverResumes,
+  NoLongerBlocked,
+  ActivatorAllTheTime,
+  /// `{weekday}`
+  ActivatorOnWeekday,
+  /// `{from}` and `{till}`, both times.
+  ActivatorInTimeRange,
+  /// `{from}` and `{till}`, both weekdays.
+  ActivatorInWeekdayRange,
+  Weekday(Weekday),
+  WebRuleFeature(WebRuleFeature),
+}
+
+fn english(text: Text) -> &'static str {
+  match text {
+    Text::BlockPageTitle => "Access blocked",
+    Text::BlockedUrl => "You can't open {url} right now.",
+    Text::BlockedByPolicyRule => "It's blocked by a rule of the “{policy}” internet access policy that is in effect {activator}.",
+    Text::BlockedByWebRule => "It's blocked by a {feature} rule that is in effect {activator}.",
+    Text::BlockedByUnscheduledWebRule => "It's blocked by a {feature} rule.",
+    Text::BlockedBySafeSearch => "It doesn't filter its results, so it's blocked while SafeSearch is enforced, which is {activator}.",
+    Text::UnknownCause => "The rule that blocked it no longer exists.",
+    Text::ResumesAt => "Access resumes at {time}.",
+    Text::ResumesIn => "Access resumes in {countdown}.",
+    Text::NeverResumes => "Access doesn't resume on its own.",
+    Text::NoLongerBlocked => "It's no longer blocked. Open it again to continue.",
+    Text::ActivatorAllTheTime => "all the time",
+    Text::ActivatorOnWeekday => "on {weekday}",
+    Text::ActivatorInTimeRange => "from {from} to {till}",
+    Text::ActivatorInWeekdayRange => "from {from} to {till}",
+    Text::Weekday(weekday) => match weekday {
+      Weekday::Monday => "Monday",
+      Weekday::Tuesday => "Tuesday",
+      Weekday::Wednesday => "Wednesday",
+      Weekday::Thursday => "Thursday",
+      Weekday::Friday => "Friday",
+      Weekday::Saturday => "Saturday",
+      Weekday::Sunday => "Sunday",
+    },
+    Text::WebRuleFeature(feature) => match feature {
+      WebRuleFeature::ContentFilter => "content filter",
+      WebRuleFeature::ImageRule => "image",
+      WebRuleFeature::MediaTypeRule => "media type",
+      WebRuleFeature::ProtobufRule => "gRPC",
+      WebRuleFeature::SearchQueryRule => "search query",
+      WebRuleFeature::YoutubeRule => "YouTube",
+      WebRuleFeature::TwitterFilter => "Twitter filter",
+      WebRuleFeature::ElementRule => "page element",
+      WebRuleFeature::WebsiteVisitsLimiter => "website visits limiter",
+      WebRuleFeature::WebsiteVisitDelayer => "website visit delayer",
+      WebRuleFeature::ViewTimeAllowance => "view time allowance",
+    },
+  }
+}
+
+fn french(text: Text) -> &'static str {
+  match text {
+    Text::BlockPageTitle => "Accès bloqué",
+    Text::BlockedUrl => "Vous ne pouvez pas ouvrir {url} pour le moment.",
+    Text::BlockedByPolicyRule => "Une règle de la politique d'accès à Internet « {policy} » la bloque ; elle s'applique {activator}.",
+    Text::BlockedByWebRule => "Une règle de type {feature} la bloque ; elle s'applique {activator}.",
+    Text::BlockedByUnscheduledWebRule => "Une règle de type {feature} la bloque.",
+    Text::BlockedBySafeSearch => "Elle ne filtre pas ses résultats, elle est donc bloquée tant que SafeSearch est imposé, c'est-à-dire {activator}.",
+    Text::UnknownCause => "La règle qui l'a bloquée n'existe plus.",
+    Text::ResumesAt => "L'accès reprendra le {time}.",
+    Text::ResumesIn => "L'accès reprendra dans {countdown}.",
+    Text::NeverResumes => "L'accès ne reprendra pas de lui-même.",
+    Text::NoLongerBlocked => "Elle n'est plus bloquée. Ouvrez-la de nouveau pour continuer.",
+    Text::ActivatorAllTheTime => "en permanence",
+    Text::ActivatorOnWeekday => "le {weekday}",
+    Text::ActivatorInTimeRange => "de {from} à {till}",
+    Text::ActivatorInWeekdayRange => "du {from} au {till}",
+    Text::Weekday(weekday) => match weekday {
+      Weekday::Monday => "lundi",
+      Weekday::Tuesday => "mardi",
+      Weekday::Wednesday => "mercredi",
+      Weekday::Thursday => "jeudi",
+      Weekday::Friday => "vendredi",
+      Weekday::Saturday => "samedi",
+      Weekday::Sunday => "dimanche",
+    },
+    Text::WebRuleFeature(feature) => match feature {
+      WebRuleFeature::ContentFilter => "filtre de contenu",
+      WebRuleFeature::ImageRule => "image",
+      WebRuleFeature::MediaTypeRule => "type de média",
+      WebRuleFeature::ProtobufRule => "gRPC",
+      WebRuleFeature::SearchQueryRule => "recherche",
+      WebRuleFeature::YoutubeRule => "YouTube",
+      WebRuleFeature::TwitterFilter => "filtre Twitter",
+      WebRuleFeature::ElementRule => "élément",
+      WebRuleFeature::WebsiteVisitsLimiter => "limite de visites",
+      WebRuleFeature::WebsiteVisitDelayer => "délai de visite",
+      WebRuleFeature::ViewTimeAllowance => "temps de consultation",
+    },
+  }
+}
+
+fn german(text: Text) -> &'static str {
+  match text {
+    Text::BlockPageTitle => "Zugriff blockiert",
+    Text::BlockedUrl => "Sie können {url} gerade nicht öffnen.",
+    Text::BlockedByPolicyRule => "Eine Regel der Internetzugangsrichtlinie „{policy}“ blockiert die Seite; sie gilt {activator}.",
+    Text::BlockedByWebRule => "Eine Regel vom Typ {feature} blockiert die Seite; sie gilt {activator}.",
+    Text::BlockedByUnscheduledWebRule => "Eine Regel vom Typ {feature} blockiert die Seite.",
+    Text::BlockedBySafeSearch => "Die Seite filtert ihre Ergebnisse nicht und ist daher blockiert, solange SafeSearch erzwungen wird, also {activator}.",
+    Text::UnknownCause => "Die Regel, die die Seite blockiert hat, existiert nicht mehr.",
+    Text::ResumesAt => "Der Zugriff ist ab {time} wieder möglich.",
+    Text::ResumesIn => "Der Zugriff ist in {countdown} wieder möglich.",
+    Text::NeverResumes => "Der Zugriff wird nicht von selbst wieder freigegeben.",
+    Text::NoLongerBlocked => "Die Seite ist nicht mehr blockiert. Öffnen Sie sie erneut, um fortzufahren.",
+    Text::ActivatorAllTheTime => "immer",
+    Text::ActivatorOnWeekday => "am {weekday}",
+    Text::ActivatorInTimeRange => "von {from} bis {till}",
+    Text::ActivatorInWeekdayRange => "von {from} bis {till}",
+    Text::Weekday(weekday) => match weekday {
+      Weekday::Monday => "Montag",
+      Weekday::Tuesday => "Dienstag",
+      Weekday::Wednesday => "Mittwoch",
+      Weekday::Thursday => "Donnerstag",
+      Weekday::Friday => "Freitag",
+      Weekday::Saturday => "Samstag",
+      Weekday::Sunday => "Sonntag",
+    },
+    Text::WebRuleFeature(feature) => match feature {
+      WebRuleFeature::ContentFilter => "Inhaltsfilter",
+      WebRuleFeature::ImageRule => "Bild",
+      WebRuleFeature::MediaTypeRule => "Medientyp",
+      WebRuleFeature::ProtobufRule => "gRPC",
+      WebRuleFeature::SearchQueryRule => "Suchanfrage",
+      WebRuleFeature::YoutubeRule => "YouTube",
+      WebRuleFeature::TwitterFilter => "Twitter-Filter",
+      WebRuleFeature::ElementRule => "Element",
+      WebRuleFeature::WebsiteVisitsLimiter => "Besuchslimit",
+      WebRuleFeature::WebsiteVisitDelayer => "Besuchsverzögerung",
+      WebRuleFeature::ViewTimeAllowance => "Ansichtszeit",
+    },
+  }
+}
+
+fn spanish(text: Text) -> &'static str {
+  match text {
+    Text::BlockPageTitle => "Acceso bloqueado",
+    Text::BlockedUrl => "No puedes abrir {url} en este momento.",
+    Text::BlockedByPolicyRule => "La bloquea una regla de la política de acceso a Internet «{policy}» que se aplica {activator}.",
+    Text::BlockedByWebRule => "La bloquea una regla de tipo {feature} que se aplica {activator}.",
+    Text::BlockedByUnscheduledWebRule => "La bloquea una regla de tipo {feature}.",
+    Text::BlockedBySafeSearch => "No filtra sus resultados, así que está bloqueada mientras se impone SafeSearch, es decir, {activator}.",
+    Text::UnknownCause => "La regla que la bloqueó ya no existe.",
+    Text::ResumesAt => "El acceso se restablecerá el {time}.",
+    Text::ResumesIn => "El acceso se restablecerá en {countdown}.",
+    Text::NeverResumes => "El acceso no se restablecerá por sí solo.",
+    Text::NoLongerBlocked => "Ya no está bloqueada. Vuelve a abrirla para continuar.",
+    Text::ActivatorAllTheTime => "siempre",
+    Text::ActivatorOnWeekday => "los {weekday}",
+    Text::ActivatorInTimeRange => "de {from} a {till}",
+    Text::ActivatorInWeekdayRange => "de {from} a {till}",
+    Text::Weekday(weekday) => match weekday {
+      Weekday::Monday => "lunes",
+      Weekday::Tuesday => "martes",
+      Weekday::Wednesday => "miércoles",
+      Weekday::Thursday => "jueves",
+      Weekday::Friday => "viernes",
+      Weekday::Saturday => "sábado",
+      Weekday::Sunday => "domingo",
+    },
+    Text::WebRuleFeature(feature) => match feature {
+      WebRuleFeature::ContentFilter => "filtro de contenido",
+      WebRuleFeature::ImageRule => "imagen",
+      WebRuleFeature::MediaTypeRule => "tipo de medio",
+      WebRuleFeature::ProtobufRule => "gRPC",
+      WebRuleFeature::SearchQueryRule => "búsqueda",
+      WebRuleFeature::YoutubeRule => "YouTube",
+      WebRuleFeature::TwitterFilter => "filtro de Twitter",
+      WebRuleFeature::ElementRule => "elemento",
+      WebRuleFeature::WebsiteVisitsLimiter => "límite de visitas",
+      WebRuleFeature::WebsiteVisitDelayer => "retraso de visitas",
+      WebRuleFeature::ViewTimeAllowance => "tiempo de visualización",
+    },
+  }
+}
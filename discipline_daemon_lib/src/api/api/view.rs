@@ -1,314 +1,295 @@
-// use std::fmt::Write;
-// use crate::{Condition, DateTime, NetworkAccessConfig, Rule, ScreenAccessConfig, UserAccessConfig};
-
-// enum RuleConditionLocation {
-//   ActiveCondition,
-//   GuardedCondition,
-// }
-
-// fn view_condition(rule: &mut Rule, condition_location: RuleConditionLocation, into: &mut impl Write) {
-//   writeln!(into, "<div>").unwrap();
-
-//   let now = DateTime::now();
-
-//   let condition = match condition_location {
-//     RuleConditionLocation::ActiveCondition => &mut rule.active_condition,
-//     RuleConditionLocation::GuardedCondition => &mut rule.guarded_condition,
-//   };
-
-//   match condition {
-//     Condition::Never(_) => {
-//       writeln!(into, "<p>status: inactive</p>").unwrap();
-//       writeln!(into, "<p>condition: never active</p>").unwrap();
-//     }
-//     Condition::Always(_) => {
-//       writeln!(into, "<p>status: active</p>").unwrap();
-//       writeln!(into, "<p>condition: always active</p>").unwrap();
-//     }
-//     Condition::ForDuration(condition) => {
-//       condition.synchronize(&now);
-      
-//       let status = if condition.evaluate() {
-//         "active"
-//       } else {
-//         "inactive"
-//       };
-
-//       writeln!(into, "<p>status: {status}</p>").unwrap();
-
-//       let remaining_duration = condition.remaining_duration.to_string();
-//       writeln!(into, "<p>condition: active for {remaining_duration}</p>").unwrap();
-
-//       if rule.is_fully_blocker() {
-//         writeln!(into, "<p><a href=\"\">Increase by five minutes</a></p>").unwrap();
-//       } else if rule.is_fully_allower() {
-//         writeln!(into, "<p><a href=\"\">Decrease by five minutes</a></p>").unwrap();
-//       }
-//     }
-//     Condition::ForTimeRange(condition) => {
-//       let status = if condition.evaluate(&now) {
-//         "active"
-//       } else {
-//         "inactive"
-//       };
-
-//       writeln!(into, "<p>status: {status}</p>").unwrap();
-
-//       let min = condition.min.to_12_hour_based_string_with_period();
-//       let max = condition.max.to_12_hour_based_string_with_period();
-//       writeln!(into, "<p>condition: active within {min} .. {max}</p>").unwrap();
-      
-//       // TODO: Create actions make wider and make narrower.
-//     }
-//     Condition::ForHourRange(condition) => {
-//       let status = if condition.evaluate(&now) {
-//         "active"
-//       } else {
-//         "inactive"
-//       };
-
-//       writeln!(into, "<p>status: {status}</p>").unwrap();
-
-//       let min = condition.min.to_12_based_string_with_period();
-//       let max = condition.max.to_12_based_string_with_period();
-//       writeln!(into, "<p>condition: active within hours {min} .. {max}</p>").unwrap();
-      
-//       // TODO: Create actions make wider and make narrower.
-//     }
-//     Condition::ForMinuteRange(condition) => {
-//       let status = if condition.evaluate(&now) {
-//         "active"
-//       } else {
-//         "inactive"
-//       };
-
-//       writeln!(into, "<p>status: {status}</p>").unwrap();
-
-//       let min = condition.min.value0();
-//       let max = condition.max.value0();
-//       writeln!(into, "<p>condition: active within minutes {min} .. {max}</p>").unwrap();
-      
-//       // TODO: Create actions make wider and make narrower.
-//     }
-//     Condition::ForWeekdayRange(condition) => {
-//       let status = if condition.evaluate(&now) {
-//         "active"
-//       } else {
-//         "inactive"
-//       };
-
-//       writeln!(into, "<p>status: {status}</p>").unwrap();
-
-//       let min = condition.min.to_string();
-//       let max = condition.max.to_string();
-//       writeln!(into, "<p>condition: active within weekdays {min} .. {max}</p>").unwrap();
-      
-//       // TODO: Create actions make wider and make narrower.
-//     }
-//     Condition::ByPasswordAuthentication(condition) => {
-//       let status = if condition.evaluate() {
-//         "active"
-//       } else {
-//         "inactive"
-//       };
-
-//       writeln!(into, "<p>status: {status}</p>").unwrap();
-//       writeln!(into, "<p>condition: always active until password is enterd.</p>").unwrap();
-//       if condition.evaluate() {
-//         writeln!(into, "<p><a href=\"\"></a>activate</p>").unwrap();
-//         writeln!(into, "
-//           <p>
-//             <form action=\"/change-password\" method=\"post\">
-//               <label for=\"password\">Change Password:</label>
-//               <input type=\"password\" id=\"password\" name=\"password\">
-//               <button type=\"submit\">Submit</button>
-//             </form>
-//           </p>
-//         ").unwrap();
-//       } else {
-//         writeln!(into, "
-//           <p>
-//             <form action=\"/inactivate\" method=\"POST\">
-//               <label for=\"password\">deactivate:</label>
-//               <input type=\"password\" id=\"password\" name=\"password\">
-//               <button type=\"submit\">Submit</button>
-//             </form>
-//           </p>
-//         ").unwrap();
-//       }
-//     }
-//   }
-
-//   writeln!(into, "</div>").unwrap();
-// }
-
-// fn view_rule_users(rule: &mut Rule, into: &mut impl Write) {
-//   writeln!(into, "<div>").unwrap();
-
-//   if rule.users.wildcard {
-//     writeln!(into, "<p>rule applies to all users</p>").unwrap();
-    
-//     if rule.is_fully_allower() {
-//       writeln!(into, "<p><a href=\"\">apply only to specific users</a></p>").unwrap();
-//     }
-
-//     return;
-//   }
-
-//   writeln!(into, "<p>rule applies to these users:</p>").unwrap();
-
-//   writeln!(into, "<ul>").unwrap();
-//   for user in &rule.users.users {
-//     if rule.is_fully_allower() {
-//       writeln!(into, "
-//         <li>
-//           <p>{user}</p>
-//           <p><a href=\"\">remove</a></p>
-//         </li>
-//       ").unwrap();
-//     } else {
-//       writeln!(into, "<li>{user}</li>").unwrap();
-//     }
-//   }
-//   writeln!(into, "</ul>").unwrap();
-
-//   if rule.is_fully_blocker() {
-//     writeln!(into, "<p><a href=\"\">apply only to all users</a></p>").unwrap();
-//   }
-
-//   writeln!(into, "</div>").unwrap();
-// }
-
-// fn view_user_access_config(rule: &mut Rule, into: &mut impl Write) {
-//   let now = DateTime::now();
-
-//   writeln!(into, "<div>").unwrap();
-
-//   match rule.user_access_config {
-//     None => {
-//       writeln!(into, "<p>action: none</p>").unwrap();
-//       writeln!(into, "<p>description: this rule neither allow nor block access to effected users</p>").unwrap();
-//       writeln!(into, "<p><a href=\"\">change to block</a></p>").unwrap();
-//       if !rule.guarded(&now) {
-//         writeln!(into, "<p><a href=\"\">change to allow</a></p>").unwrap();
-//       }
-//     }
-//     Some(UserAccessConfig::Allow) => {
-//       writeln!(into, "<p>action: allow</p>").unwrap();
-//       writeln!(into, "<p>description: when this rule is active, accessing the effected users is allowed</p>").unwrap();
-//       writeln!(into, "<p><a href=\"\">change to block</a></p>").unwrap();
-//     }
-//     Some(UserAccessConfig::Block) => {
-//       writeln!(into, "<p>action: block</p>").unwrap();
-//       writeln!(into, "<p>description: when this rule is active, accessing the effected users is blocked</p>").unwrap();
-//       if !rule.guarded(&now) {
-//         writeln!(into, "<p><a href=\"\">change to allow</a></p>").unwrap();
-//       }
-//     }
-//   }
-// }
-
-// fn view_screen_access_config(rule: &mut Rule, into: &mut impl Write) {
-//   let now = DateTime::now();
-
-//   writeln!(into, "<div>").unwrap();
-
-//   match rule.screen_access_config {
-//     None => {
-//       writeln!(into, "<p>action: none</p>").unwrap();
-//       writeln!(into, "<p>description: this rule neither allow nor block access the device</p>").unwrap();
-//       writeln!(into, "<p><a href=\"\">change to shutdown</a></p>").unwrap();
-//       if !rule.guarded(&now) {
-//         writeln!(into, "<p><a href=\"\">change to allow</a></p>").unwrap();
-//       }
-//     }
-//     Some(ScreenAccessConfig::Allow) => {
-//       writeln!(into, "<p>action: allow</p>").unwrap();
-//       writeln!(into, "<p>description: when this rule is active, effected users are allowed to access the device</p>").unwrap();
-//       writeln!(into, "<p><a href=\"\">change to shutdown</a></p>").unwrap();
-//     }
-//     Some(ScreenAccessConfig::Shutdown) => {
-//       writeln!(into, "<p>action: shutdown</p>").unwrap();
-//       writeln!(into, "<p>description: when this rule is active, effected users are blocked from accesing the device, that is done by shutting the device down when one of them is signed in</p>").unwrap();
-//       if !rule.guarded(&now) {
-//         writeln!(into, "<p><a href=\"\">change to allow</a></p>").unwrap();
-//       }
-//     }
-//   }
-// }
-
-// fn view_network_access_config(rule: &mut Rule, into: &mut impl Write) {
-//   let now = DateTime::now();
-
-//   writeln!(into, "<div>").unwrap();
-
-//   match rule.network_access_config {
-//     None => {
-//       writeln!(into, "<p>action: none</p>").unwrap();
-//       writeln!(into, "<p>description: this rule neither allow nor block effected users from accessing the internet</p>").unwrap();
-//       writeln!(into, "<p><a href=\"\">change to block</a></p>").unwrap();
-//       if !rule.guarded(&now) {
-//         writeln!(into, "<p><a href=\"\">change to allow</a></p>").unwrap();
-//       }
-//     }
-//     Some(NetworkAccessConfig::Allow) => {
-//       writeln!(into, "<p>action: allow</p>").unwrap();
-//       writeln!(into, "<p>description: when this rule is active, effected users are allowed to access the internet</p>").unwrap();
-//       writeln!(into, "<p><a href=\"\">change to block</a></p>").unwrap();
-//     }
-//     Some(NetworkAccessConfig::Block) => {
-//       writeln!(into, "<p>action: block</p>").unwrap();
-//       writeln!(into, "<p>description: when this rule is active, effected users are blocked from accessing the internet</p>").unwrap();
-//       if !rule.guarded(&now) {
-//         writeln!(into, "<p><a href=\"\">change to allow</a></p>").unwrap();
-//       }
-//     }
-//   }
-// }
-
-// pub fn view_rule(rule: &mut Rule, into: &mut impl Write) {
-//   writeln!(into, "<div>").unwrap();
-
-//   writeln!(into, "<p>name: {}</p>", rule.name).unwrap();
-//   writeln!(into, "<p>description: {}</p>", rule.description).unwrap();
-//   writeln!(into, "<p>created at: {}</p>", rule.created_at.to_iso_8601_like()).unwrap();
-
-//   writeln!(into, "<p>active condition</p>").unwrap();
-//   writeln!(into, "<section>").unwrap();
-//   view_condition(rule, RuleConditionLocation::ActiveCondition, into);
-//   writeln!(into, "</section>").unwrap();
-
-//   writeln!(into, "<p>guarded condition</p>").unwrap();
-//   writeln!(into, "<section>").unwrap();
-//   view_condition(rule, RuleConditionLocation::GuardedCondition, into);
-//   writeln!(into, "</section>").unwrap();
-
-//   writeln!(into, "<p>users</p>").unwrap();
-//   writeln!(into, "<section>").unwrap();
-//   view_rule_users(rule, into);
-//   writeln!(into, "</section>").unwrap();
-
-//   writeln!(into, "<p>user access</p>").unwrap();
-//   writeln!(into, "<section>").unwrap();
-//   view_user_access_config(rule, into);
-//   writeln!(into, "</section>").unwrap();
-
-//   writeln!(into, "<p>screen access</p>").unwrap();
-//   writeln!(into, "<section>").unwrap();
-//   view_screen_access_config(rule, into);
-//   writeln!(into, "</section>").unwrap();
-
-//   writeln!(into, "<p>network access</p>").unwrap();
-//   writeln!(into, "<section>").unwrap();
-//   view_network_access_config(rule, into);
-//   writeln!(into, "</section>").unwrap();
-
-//   writeln!(into, "</div>").unwrap();
-// }
-
-// pub fn view_create_rule(into: &mut impl Write) {
-//   write
-// }
-// pub fn view_rules(rules: &mut Vec<Rule>, into: &mut impl Write) {
-  
-// }
\ No newline at end of file
+use crate::{internet_access_regulation, web_regulation_intrusive, DateTime, Duration, Time, Weekday};
+use crate::web_regulation_intrusive::block_page::{write_countdown, write_time, SCRIPT, STYLE};
+use super::translations::{Language, Text};
+use super::web_view::UI;
+
+/// A web regulation feature whose rules may send the user to the block page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebRuleFeature {
+  ContentFilter,
+  ImageRule,
+  MediaTypeRule,
+  ProtobufRule,
+  SearchQueryRule,
+  YoutubeRule,
+  TwitterFilter,
+  ElementRule,
+  WebsiteVisitsLimiter,
+  WebsiteVisitDelayer,
+  ViewTimeAllowance,
+}
+
+impl WebRuleFeature {
+  pub const ALL: [WebRuleFeature; 11] = [
+    WebRuleFeature::ContentFilter,
+    WebRuleFeature::ImageRule,
+    WebRuleFeature::MediaTypeRule,
+    WebRuleFeature::ProtobufRule,
+    WebRuleFeature::SearchQueryRule,
+    WebRuleFeature::YoutubeRule,
+    WebRuleFeature::TwitterFilter,
+    WebRuleFeature::ElementRule,
+    WebRuleFeature::WebsiteVisitsLimiter,
+    WebRuleFeature::WebsiteVisitDelayer,
+    WebRuleFeature::ViewTimeAllowance,
+  ];
+
+  /// How the feature is named in block page urls.
+  pub fn identifier(&self) -> &'static str {
+    match self {
+      WebRuleFeature::ContentFilter => "content-filter",
+      WebRuleFeature::ImageRule => "image-rule",
+      WebRuleFeature::MediaTypeRule => "media-type-rule",
+      WebRuleFeature::ProtobufRule => "protobuf-rule",
+      WebRuleFeature::SearchQueryRule => "search-query-rule",
+      WebRuleFeature::YoutubeRule => "youtube-rule",
+      WebRuleFeature::TwitterFilter => "twitter-filter",
+      WebRuleFeature::ElementRule => "element-rule",
+      WebRuleFeature::WebsiteVisitsLimiter => "website-visits-limiter",
+      WebRuleFeature::WebsiteVisitDelayer => "website-visit-delayer",
+      WebRuleFeature::ViewTimeAllowance => "view-time-allowance",
+    }
+  }
+
+  pub fn from_identifier(identifier: &str) -> Option<WebRuleFeature> {
+    WebRuleFeature::ALL
+      .into_iter()
+      .find(|feature| feature.identifier() == identifier)
+  }
+}
+
+/// When a rule is in effect, whichever feature it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActivatorDescription {
+  AllTheTime,
+  OnWeekday(Weekday),
+  InTimeRange(Time, Time),
+  InWeekdayRange(Weekday, Weekday),
+}
+
+impl From<&internet_access_regulation::RuleActivator> for ActivatorDescription {
+  fn from(activator: &internet_access_regulation::RuleActivator) -> Self {
+    use internet_access_regulation::RuleActivator;
+
+    match activator {
+      RuleActivator::AllTheTime => {
+        ActivatorDescription::AllTheTime
+      }
+      RuleActivator::OnWeekday(weekday) => {
+        ActivatorDescription::OnWeekday(*weekday)
+      }
+      RuleActivator::InTimeRange(time_range) => {
+        ActivatorDescription::InTimeRange(time_range.from(), time_range.till())
+      }
+      RuleActivator::InWeekdayRange(weekday_range) => {
+        ActivatorDescription::InWeekdayRange(weekday_range.from(), weekday_range.till())
+      }
+    }
+  }
+}
+
+impl From<&web_regulation_intrusive::RuleActivator> for ActivatorDescription {
+  fn from(activator: &web_regulation_intrusive::RuleActivator) -> Self {
+    use web_regulation_intrusive::RuleActivator;
+
+    match activator {
+      RuleActivator::AllTheTime => {
+        ActivatorDescription::AllTheTime
+      }
+      RuleActivator::OnWeekday(weekday) => {
+        ActivatorDescription::OnWeekday(*weekday)
+      }
+      RuleActivator::InTimeRange(time_range) => {
+        ActivatorDescription::InTimeRange(time_range.from(), time_range.till())
+      }
+      RuleActivator::InWeekdayRange(weekday_range) => {
+        ActivatorDescription::InWeekdayRange(weekday_range.from(), weekday_range.till())
+      }
+    }
+  }
+}
+
+/// What blocks the page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockCause {
+  PolicyRule {
+    policy_name: String,
+    activator: ActivatorDescription,
+  },
+  /// Rules of features without activators block whenever they apply.
+  WebRule {
+    feature: WebRuleFeature,
+    activator: Option<ActivatorDescription>,
+  },
+  SafeSearch {
+    activator: ActivatorDescription,
+  },
+}
+
+/// When the user can open the page again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resumption {
+  NoLongerBlocked,
+  At(DateTime),
+  /// After the duration from when the page is shown. The page counts it
+  /// down on its own, so it doesn't depend on the device's clock.
+  In(Duration),
+  Never,
+  /// What blocked the page couldn't be found.
+  Unknown,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockPage {
+  pub language: Language,
+  pub blocked_url: Option<String>,
+  pub causes: Vec<BlockCause>,
+  pub resumption: Resumption,
+}
+
+impl BlockPage {
+  pub fn render(&self) -> String {
+    let language = self.language;
+    let title = language.translate(Text::BlockPageTitle);
+
+    let mut ui = UI::new();
+    ui
+      .open("html").attr("lang", language.code())
+        .open("head")
+          .void("meta").attr("charset", "utf-8")
+          .void("meta").attr("name", "viewport").attr("content", "width=device-width, initial-scale=1")
+          .open("title").text(title).close()
+          .open("style").raw(STYLE).close()
+        .close()
+        .open("body")
+          .open("main")
+            .open("h1").text(title).close();
+
+    if let Some(blocked_url) = &self.blocked_url {
+      ui.open("p").template(language.translate(Text::BlockedUrl), |ui, _| {
+        write_blocked_url(ui, blocked_url, &self.resumption);
+      }).close();
+    }
+
+    if self.resumption == Resumption::NoLongerBlocked {
+      ui.open("p").text(language.translate(Text::NoLongerBlocked)).close();
+    } else {
+      if self.causes.is_empty() {
+        ui.open("p").text(language.translate(Text::UnknownCause)).close();
+      }
+
+      for cause in &self.causes {
+        ui.open("p");
+        write_cause(&mut ui, language, cause);
+        ui.close();
+      }
+
+      match self.resumption {
+        Resumption::At(time) => {
+          ui.open("p").template(language.translate(Text::ResumesAt), |ui, _| {
+            let mut html = String::new();
+            write_time(time, &mut html);
+            ui.raw(&html);
+          }).close();
+        }
+        Resumption::In(remaining) => {
+          ui.open("p").template(language.translate(Text::ResumesIn), |ui, _| {
+            let mut html = String::new();
+            write_countdown(remaining, &mut html);
+            ui.raw(&html);
+          }).close();
+        }
+        Resumption::Never => {
+          ui.open("p").text(language.translate(Text::NeverResumes)).close();
+        }
+        Resumption::NoLongerBlocked | Resumption::Unknown => {}
+      }
+    }
+
+    ui
+      .close()
+      .open("script").raw(SCRIPT).close();
+
+    ui.finish()
+  }
+}
+
+fn write_cause(ui: &mut UI, language: Language, cause: &BlockCause) {
+  match cause {
+    BlockCause::PolicyRule { policy_name, activator } => {
+      ui.template(language.translate(Text::BlockedByPolicyRule), |ui, placeholder| {
+        if placeholder == "policy" {
+          ui.open("strong").text(policy_name).close();
+        } else {
+          write_activator(ui, language, activator);
+        }
+      });
+    }
+    BlockCause::WebRule { feature, activator: Some(activator) } => {
+      ui.template(language.translate(Text::BlockedByWebRule), |ui, placeholder| {
+        if placeholder == "feature" {
+          ui.text(language.translate(Text::WebRuleFeature(*feature)));
+        } else {
+          write_activator(ui, language, activator);
+        }
+      });
+    }
+    BlockCause::WebRule { feature, activator: None } => {
+      ui.template(language.translate(Text::BlockedByUnscheduledWebRule), |ui, _| {
+        ui.text(language.translate(Text::WebRuleFeature(*feature)));
+      });
+    }
+    BlockCause::SafeSearch { activator } => {
+      ui.template(language.translate(Text::BlockedBySafeSearch), |ui, _| {
+        write_activator(ui, language, activator);
+      });
+    }
+  }
+}
+
+/// Links the blocked url, when it's a web page, so the user can go back to
+/// it. The page script follows the link on its own once access resumes.
+fn write_blocked_url(ui: &mut UI, blocked_url: &str, resumption: &Resumption) {
+  let is_web_page = blocked_url.starts_with("http://") || blocked_url.starts_with("https://");
+  if !is_web_page {
+    ui.open("strong").text(blocked_url).close();
+    return;
+  }
+
+  ui.open("a").attr("href", blocked_url);
+  match resumption {
+    Resumption::At(time) => {
+      ui.attr("data-resume-at", &time.timestamp().to_string());
+    }
+    Resumption::In(remaining) => {
+      ui.attr("data-resume-in", &remaining.total_milliseconds().to_string());
+    }
+    Resumption::NoLongerBlocked | Resumption::Never | Resumption::Unknown => {}
+  }
+  ui.open("strong").text(blocked_url).close().close();
+}
+
+fn write_activator(ui: &mut UI, language: Language, activator: &ActivatorDescription) {
+  match activator {
+    ActivatorDescription::AllTheTime => {
+      ui.text(language.translate(Text::ActivatorAllTheTime));
+    }
+    ActivatorDescription::OnWeekday(weekday) => {
+      ui.template(language.translate(Text::ActivatorOnWeekday), |ui, _| {
+        ui.text(language.translate(Text::Weekday(*weekday)));
+      });
+    }
+    ActivatorDescription::InTimeRange(from, till) => {
+      ui.template(language.translate(Text::ActivatorInTimeRange), |ui, placeholder| {
+        let time = if placeholder == "from" { from } else { till };
+        ui.text(&format!("{:02}:{:02}", time.hour(), time.minute()));
+      });
+    }
+    ActivatorDescription::InWeekdayRange(from, till) => {
+      ui.template(language.translate(Text::ActivatorInWeekdayRange), |ui, placeholder| {
+        let weekday = if placeholder == "from" { from } else { till };
+        ui.text(language.translate(Text::Weekday(*weekday)));
+      });
+    }
+  }
+}
@@ -0,0 +1,95 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+use crate::browser_extension::{decide, CheckInStatus, TabDecision, TabReport};
+use crate::browser_extension::native_host::REPORT_TABS_OPERATION_ID;
+use crate::operating_system_integration::{retrieve_tcp_socket_owner, UserId};
+use crate::{Daemon, DateTime};
+
+/// Sent by the native messaging host with the tabs of a user's browser,
+/// which also checks the extension in. Returns what to do with each tab.
+///
+/// The user is the owner of the connection the operation came over, as
+/// browsers run the host as the user the extension belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportTabs {
+  tabs: Vec<TabReport>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ReportTabsReturn {
+  NoSuchUser,
+  Success(Vec<TabDecision>),
+  InternalError,
+}
+
+impl ReportTabs {
+  pub const HUMAN_READABLE_ID: &'static str = REPORT_TABS_OPERATION_ID;
+
+  pub fn execute(self, daemon: Arc<Daemon>, caller_address: Option<SocketAddr>) -> ReportTabsReturn {
+    let Some(user_id) = caller_address.and_then(retrieve_tcp_socket_owner) else {
+      return ReportTabsReturn::NoSuchUser;
+    };
+
+    match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        if !data.users.contains_key(&user_id) {
+          return ReportTabsReturn::NoSuchUser;
+        }
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return ReportTabsReturn::InternalError;
+      }
+    }
+
+    let now = DateTime::now();
+    daemon.browser_extension().check_in(user_id, now);
+
+    let rules = daemon.web_regulation_intrusive().content_filter().rules().clone();
+    let decisions = self
+      .tabs
+      .iter()
+      .map(|tab| TabDecision {
+        tab_id: tab.tab_id,
+        decision: decide(&rules, user_id, tab, now),
+      })
+      .collect();
+
+    ReportTabsReturn::Success(decisions)
+  }
+}
+
+/// Whether the extension of each managed user is still checking in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListExtensionCheckIns {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ListExtensionCheckInsReturn {
+  Success(Vec<CheckInStatus>),
+  InternalError,
+}
+
+impl ListExtensionCheckIns {
+  pub const HUMAN_READABLE_ID: &'static str = "BrowserExtensionListExtensionCheckIns";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> ListExtensionCheckInsReturn {
+    let user_ids: Vec<UserId> = match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        data.users.keys().copied().collect()
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return ListExtensionCheckInsReturn::InternalError;
+      }
+    };
+
+    let now = DateTime::now();
+    let statuses = user_ids
+      .into_iter()
+      .map(|user_id| daemon.browser_extension().status(user_id, now))
+      .collect();
+
+    ListExtensionCheckInsReturn::Success(statuses)
+  }
+}
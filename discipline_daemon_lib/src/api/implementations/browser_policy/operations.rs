@@ -0,0 +1,90 @@
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+use crate::browser_policy::{SettingCreator, SettingCreatorError};
+use crate::database::browser_policy_setting as browser_policy_db;
+use crate::Daemon;
+
+/// Enables browser policies for the device, or replaces the enabled ones,
+/// and writes the policy files right away.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetBrowserPolicy {
+  setting_creator: SettingCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SetBrowserPolicyReturn {
+  InvalidSetting(SettingCreatorError),
+  Success,
+  InternalError,
+}
+
+impl SetBrowserPolicy {
+  pub const HUMAN_READABLE_ID: &'static str = "BrowserPolicySetBrowserPolicy";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> SetBrowserPolicyReturn {
+    let setting = match self.setting_creator.create() {
+      Ok(setting) => {
+        setting
+      }
+      Err(error) => {
+        return SetBrowserPolicyReturn::InvalidSetting(error);
+      }
+    };
+
+    let browser_policy = daemon.browser_policy();
+    {
+      let mut current_setting = browser_policy.setting();
+      if let Err(error) = browser_policy_db::set_setting(daemon.database(), &setting) {
+        daemon.internal_logger().log_error(error);
+        return SetBrowserPolicyReturn::InternalError;
+      }
+
+      *current_setting = Some(setting);
+    }
+
+    // The files are checked again shortly, so they're only late if this
+    // fails.
+    if let Err(error) = browser_policy.apply() {
+      daemon.internal_logger().log_error(error);
+    }
+
+    SetBrowserPolicyReturn::Success
+  }
+}
+
+/// Disables browser policies for the device and removes the policy files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisableBrowserPolicy {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DisableBrowserPolicyReturn {
+  NotEnabled,
+  Success,
+  InternalError,
+}
+
+impl DisableBrowserPolicy {
+  pub const HUMAN_READABLE_ID: &'static str = "BrowserPolicyDisableBrowserPolicy";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> DisableBrowserPolicyReturn {
+    let browser_policy = daemon.browser_policy();
+    let mut current_setting = browser_policy.setting();
+    let Some(setting) = current_setting.clone() else {
+      return DisableBrowserPolicyReturn::NotEnabled;
+    };
+
+    if let Err(error) = browser_policy_db::delete_setting(daemon.database()) {
+      daemon.internal_logger().log_error(error);
+      return DisableBrowserPolicyReturn::InternalError;
+    }
+
+    *current_setting = None;
+    drop(current_setting);
+
+    if let Err(error) = browser_policy.remove(&setting) {
+      daemon.internal_logger().log_error(error);
+    }
+
+    DisableBrowserPolicyReturn::Success
+  }
+}
@@ -0,0 +1,2 @@
+pub mod operations;
+pub use operations::*;
\ No newline at end of file
@@ -0,0 +1,199 @@
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+use crate::circumvention_detection::{Event, SettingCreator, SettingCreatorError};
+use crate::database::circumvention_detection as circumvention_db;
+use crate::operating_system_integration::UserId;
+use crate::{Daemon, DateTime};
+
+/// Enables circumvention detection for a managed user, or changes the
+/// response of an enabled one. A running screen blocking penalty carries
+/// over.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetCircumventionDetection {
+  user_id: UserId,
+  setting_creator: SettingCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SetCircumventionDetectionReturn {
+  NoSuchUser { user_id: UserId },
+  InvalidSetting(SettingCreatorError),
+  Success,
+  InternalError,
+}
+
+impl SetCircumventionDetection {
+  pub const HUMAN_READABLE_ID: &'static str = "CircumventionDetectionSetCircumventionDetection";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> SetCircumventionDetectionReturn {
+    match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        if !data.users.contains_key(&self.user_id) {
+          return SetCircumventionDetectionReturn::NoSuchUser { user_id: self.user_id };
+        }
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return SetCircumventionDetectionReturn::InternalError;
+      }
+    }
+
+    let setting = match self.setting_creator.create(self.user_id, DateTime::now()) {
+      Ok(setting) => {
+        setting
+      }
+      Err(error) => {
+        return SetCircumventionDetectionReturn::InvalidSetting(error);
+      }
+    };
+
+    let detection = daemon.circumvention_detection();
+    let mut settings = detection.settings();
+    let index = settings.iter().position(|other| other.user_id() == self.user_id);
+    let previous_response = index.map(|index| settings[index].response());
+
+    if let Err(error) = detection.apply_firewall_rules(self.user_id, Some(setting.response())) {
+      daemon.internal_logger().log_error(error);
+      if let Err(error) = detection.apply_firewall_rules(self.user_id, previous_response) {
+        daemon.internal_logger().log_error(error);
+      }
+      return SetCircumventionDetectionReturn::InternalError;
+    }
+
+    let result = match index {
+      Some(_) => {
+        circumvention_db::update_response(daemon.database(), self.user_id, setting.response())
+      }
+      None => {
+        circumvention_db::add_setting(daemon.database(), &setting)
+      }
+    };
+
+    if let Err(error) = result {
+      daemon.internal_logger().log_error(error);
+      if let Err(error) = detection.apply_firewall_rules(self.user_id, previous_response) {
+        daemon.internal_logger().log_error(error);
+      }
+      return SetCircumventionDetectionReturn::InternalError;
+    }
+
+    match index {
+      Some(index) => {
+        let penalty = settings[index].penalty().clone();
+        settings[index] = setting.with_penalty(penalty);
+      }
+      None => {
+        settings.push(setting);
+      }
+    }
+
+    SetCircumventionDetectionReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisableCircumventionDetection {
+  user_id: UserId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DisableCircumventionDetectionReturn {
+  NotEnabled,
+  /// The user's screen is blocked for a violation, which disabling
+  /// detection would lift.
+  PenaltyIsRunning,
+  Success,
+  InternalError,
+}
+
+impl DisableCircumventionDetection {
+  pub const HUMAN_READABLE_ID: &'static str = "CircumventionDetectionDisableCircumventionDetection";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> DisableCircumventionDetectionReturn {
+    let detection = daemon.circumvention_detection();
+    if detection.is_screen_blocked(self.user_id, DateTime::now()) {
+      return DisableCircumventionDetectionReturn::PenaltyIsRunning;
+    }
+
+    let mut settings = detection.settings();
+    let Some(index) = settings.iter().position(|setting| setting.user_id() == self.user_id) else {
+      return DisableCircumventionDetectionReturn::NotEnabled;
+    };
+
+    let response = settings[index].response();
+    if let Err(error) = detection.apply_firewall_rules(self.user_id, None) {
+      daemon.internal_logger().log_error(error);
+      if let Err(error) = detection.apply_firewall_rules(self.user_id, Some(response)) {
+        daemon.internal_logger().log_error(error);
+      }
+      return DisableCircumventionDetectionReturn::InternalError;
+    }
+
+    if let Err(error) = circumvention_db::delete_setting(daemon.database(), self.user_id) {
+      daemon.internal_logger().log_error(error);
+      if let Err(error) = detection.apply_firewall_rules(self.user_id, Some(response)) {
+        daemon.internal_logger().log_error(error);
+      }
+      return DisableCircumventionDetectionReturn::InternalError;
+    }
+
+    settings.remove(index);
+    DisableCircumventionDetectionReturn::Success
+  }
+}
+
+/// Lists the recorded events of a user, or of every user, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListCircumventionEvents {
+  user_id: Option<UserId>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ListCircumventionEventsReturn {
+  Success(Vec<Event>),
+  InternalError,
+}
+
+impl ListCircumventionEvents {
+  pub const HUMAN_READABLE_ID: &'static str = "CircumventionDetectionListCircumventionEvents";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> ListCircumventionEventsReturn {
+    match circumvention_db::retrieve_events(daemon.database(), self.user_id) {
+      Ok(events) => {
+        ListCircumventionEventsReturn::Success(events)
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        ListCircumventionEventsReturn::InternalError
+      }
+    }
+  }
+}
+
+/// Deletes the recorded events of a user, or of every user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClearCircumventionEvents {
+  user_id: Option<UserId>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ClearCircumventionEventsReturn {
+  Success,
+  InternalError,
+}
+
+impl ClearCircumventionEvents {
+  pub const HUMAN_READABLE_ID: &'static str = "CircumventionDetectionClearCircumventionEvents";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> ClearCircumventionEventsReturn {
+    match circumvention_db::delete_events(daemon.database(), self.user_id) {
+      Ok(()) => {
+        ClearCircumventionEventsReturn::Success
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        ClearCircumventionEventsReturn::InternalError
+      }
+    }
+  }
+}
@@ -1,6 +1,10 @@
 mod screen_access_regulation;
 mod internet_access_regulation;
 mod operating_system_integration_linux;
+mod web_regulation_intrusive;
+mod circumvention_detection;
+mod browser_policy;
+mod browser_extension;
 
 pub mod operations {
   pub use super::screen_access_regulation::{
@@ -51,4 +55,67 @@ pub mod operations {
     ManageUser as OperatingSystemIntegrationManageUser,
     UnmanageUser as OperatingSystemIntegrationUnmanageUser,
   };
-}
\ No newline at end of file
+
+  pub use super::web_regulation_intrusive::{
+    AddNoInterceptHost as WebRegulationIntrusiveAddNoInterceptHost,
+    RemoveNoInterceptHost as WebRegulationIntrusiveRemoveNoInterceptHost,
+    AddProxiedHost as WebRegulationIntrusiveAddProxiedHost,
+    RemoveProxiedHost as WebRegulationIntrusiveRemoveProxiedHost,
+    CreateWebsiteVisitsLimiter as WebRegulationIntrusiveCreateWebsiteVisitsLimiter,
+    DeleteWebsiteVisitsLimiter as WebRegulationIntrusiveDeleteWebsiteVisitsLimiter,
+    CreateWebsiteVisitDelayer as WebRegulationIntrusiveCreateWebsiteVisitDelayer,
+    DeleteWebsiteVisitDelayer as WebRegulationIntrusiveDeleteWebsiteVisitDelayer,
+    CreateYoutubeRule as WebRegulationIntrusiveCreateYoutubeRule,
+    DeleteYoutubeRule as WebRegulationIntrusiveDeleteYoutubeRule,
+    CreateTwitterFilter as WebRegulationIntrusiveCreateTwitterFilter,
+    DeleteTwitterFilter as WebRegulationIntrusiveDeleteTwitterFilter,
+    CreateMediaTypeRule as WebRegulationIntrusiveCreateMediaTypeRule,
+    DeleteMediaTypeRule as WebRegulationIntrusiveDeleteMediaTypeRule,
+    CreateElementRuleSheet as WebRegulationIntrusiveCreateElementRuleSheet,
+    DeleteElementRuleSheet as WebRegulationIntrusiveDeleteElementRuleSheet,
+    ValidateElementRules as WebRegulationIntrusiveValidateElementRules,
+    CreateContentFilterRule as WebRegulationIntrusiveCreateContentFilterRule,
+    DeleteContentFilterRule as WebRegulationIntrusiveDeleteContentFilterRule,
+    EnableSafeSearch as WebRegulationIntrusiveEnableSafeSearch,
+    DisableSafeSearch as WebRegulationIntrusiveDisableSafeSearch,
+    IncreaseSafeSearchProtection as WebRegulationIntrusiveIncreaseSafeSearchProtection,
+    CreateSearchQueryRule as WebRegulationIntrusiveCreateSearchQueryRule,
+    DeleteSearchQueryRule as WebRegulationIntrusiveDeleteSearchQueryRule,
+    CreateViewTimeAllowance as WebRegulationIntrusiveCreateViewTimeAllowance,
+    DeleteViewTimeAllowance as WebRegulationIntrusiveDeleteViewTimeAllowance,
+    CreateImageRule as WebRegulationIntrusiveCreateImageRule,
+    DeleteImageRule as WebRegulationIntrusiveDeleteImageRule,
+    AddImageClassifier as WebRegulationIntrusiveAddImageClassifier,
+    RemoveImageClassifier as WebRegulationIntrusiveRemoveImageClassifier,
+    CreateProtobufRule as WebRegulationIntrusiveCreateProtobufRule,
+    DeleteProtobufRule as WebRegulationIntrusiveDeleteProtobufRule,
+    AddProtobufFieldName as WebRegulationIntrusiveAddProtobufFieldName,
+    RemoveProtobufFieldName as WebRegulationIntrusiveRemoveProtobufFieldName,
+    InspectProtobufBody as WebRegulationIntrusiveInspectProtobufBody,
+    SetEncryptedDnsPrevention as WebRegulationIntrusiveSetEncryptedDnsPrevention,
+    DisableEncryptedDnsPrevention as WebRegulationIntrusiveDisableEncryptedDnsPrevention,
+    SetAuditLog as WebRegulationIntrusiveSetAuditLog,
+    DisableAuditLog as WebRegulationIntrusiveDisableAuditLog,
+    IncreaseAuditLogProtection as WebRegulationIntrusiveIncreaseAuditLogProtection,
+    QueryAuditLog as WebRegulationIntrusiveQueryAuditLog,
+    ExportAuditLog as WebRegulationIntrusiveExportAuditLog,
+    ClearAuditLog as WebRegulationIntrusiveClearAuditLog,
+  };
+
+  pub use super::circumvention_detection::{
+    SetCircumventionDetection as CircumventionDetectionSetCircumventionDetection,
+    DisableCircumventionDetection as CircumventionDetectionDisableCircumventionDetection,
+    ListCircumventionEvents as CircumventionDetectionListCircumventionEvents,
+    ClearCircumventionEvents as CircumventionDetectionClearCircumventionEvents,
+  };
+
+  pub use super::browser_policy::{
+    SetBrowserPolicy as BrowserPolicySetBrowserPolicy,
+    DisableBrowserPolicy as BrowserPolicyDisableBrowserPolicy,
+  };
+
+  pub use super::browser_extension::{
+    ReportTabs as BrowserExtensionReportTabs,
+    ListExtensionCheckIns as BrowserExtensionListExtensionCheckIns,
+  };
+}
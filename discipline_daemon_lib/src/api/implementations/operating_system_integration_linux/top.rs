@@ -2,6 +2,8 @@ use std::sync::Arc;
 use serde::{Deserialize, Serialize};
 use crate::Daemon;
 use crate::logic;
+use crate::web_regulation_intrusive::ProxyAutoConfig;
+use crate::browser_extension::{chromium_host_manifest, firefox_host_manifest, NATIVE_MESSAGING_HOST_NAME};
 use crate::api::IntoPublic;
 use crate::operating_system_integration::{
   UserId, UserName, UserPassword, User,
@@ -99,6 +101,53 @@ impl ManageUser {
       }
     };
 
+    let proxy = daemon.web_regulation_intrusive();
+
+    if let Err(error) = os::redirect_web_traffic_of_user_to_proxy(
+      &user_info.user_id,
+      proxy.port(),
+    ) {
+      daemon.internal_logger().log_error(error);
+      return ManageUserReturn::InternalError;
+    }
+
+    // Browsers keep their own certificate stores rather than using the system's.
+    if let Err(error) = os::install_certificate_into_user_nss_databases(
+      &user_info.user_name,
+      &user_info.user_home_directory,
+      proxy.certificate_authority().certificate_path(),
+    ) {
+      daemon.internal_logger().log_error(error);
+    }
+
+    // For programs that go around the redirection, and where it isn't
+    // possible at all.
+    if let Err(error) = os::install_system_proxy_settings(
+      &user_info.user_name,
+      &user_info.user_home_directory,
+      &ProxyAutoConfig::url(daemon.configuration().block_page_port()),
+      daemon.configuration().proxy_auto_config_proxy_address(),
+    ) {
+      daemon.internal_logger().log_error(error);
+    }
+
+    let configuration = daemon.configuration();
+    if let Err(error) = os::install_native_messaging_host_manifests(
+      &user_info.user_name,
+      &user_info.user_home_directory,
+      NATIVE_MESSAGING_HOST_NAME,
+      &chromium_host_manifest(
+        configuration.native_messaging_host_path(),
+        configuration.chromium_extension_ids(),
+      ),
+      &firefox_host_manifest(
+        configuration.native_messaging_host_path(),
+        configuration.firefox_extension_ids(),
+      ),
+    ) {
+      daemon.internal_logger().log_error(error);
+    }
+
     let user = User {
       user_id: user_info.user_id,
       user_name: user_info.user_name,
@@ -142,6 +191,8 @@ pub enum UnmanageUserReturn {
   InternalError,
   SomeScreenAccessRegulationPoliciesAreStillEnabled,
   SomeInternetAccessRegulationPoliciesAreStillEnabled,
+  EncryptedDnsPreventionIsStillEnabled,
+  CircumventionDetectionIsStillEnabled,
   Success,
 }
 
@@ -169,6 +220,28 @@ impl UnmanageUser {
       return UnmanageUserReturn::SomeInternetAccessRegulationPoliciesAreStillEnabled;
     }
 
+    // Its firewall rules are only removed along with the setting.
+    let has_encrypted_dns_setting = daemon
+      .web_regulation_intrusive()
+      .encrypted_dns_prevention()
+      .settings()
+      .iter()
+      .any(|setting| setting.user_id() == self.user_id);
+
+    if has_encrypted_dns_setting {
+      return UnmanageUserReturn::EncryptedDnsPreventionIsStillEnabled;
+    }
+
+    let has_circumvention_detection_setting = daemon
+      .circumvention_detection()
+      .settings()
+      .iter()
+      .any(|setting| setting.user_id() == self.user_id);
+
+    if has_circumvention_detection_setting {
+      return UnmanageUserReturn::CircumventionDetectionIsStillEnabled;
+    }
+
     if let Err(error) = user_db::delete_user(
       daemon.database(), 
       self.user_id
@@ -177,6 +250,41 @@ impl UnmanageUser {
       return UnmanageUserReturn::InternalError;
     }
 
+    if let Err(error) = os::stop_redirecting_web_traffic_of_user_to_proxy(
+      &self.user_id,
+      daemon.web_regulation_intrusive().port(),
+    ) {
+      daemon.internal_logger().log_error(error);
+    }
+
+    if let RetrieveUserInfoReturn::Success(user_info) = retrieve_user_info(
+      UserIdentificationMethod::Id(self.user_id)
+    ) {
+      if let Err(error) = os::remove_certificate_from_user_nss_databases(
+        &user_info.user_name,
+        &user_info.user_home_directory,
+      ) {
+        daemon.internal_logger().log_error(error);
+      }
+
+      if let Err(error) = os::remove_system_proxy_settings(
+        &user_info.user_name,
+        &user_info.user_home_directory,
+        &ProxyAutoConfig::url(daemon.configuration().block_page_port()),
+      ) {
+        daemon.internal_logger().log_error(error);
+      }
+
+      if let Err(error) = os::remove_native_messaging_host_manifests(
+        &user_info.user_name,
+        &user_info.user_home_directory,
+        NATIVE_MESSAGING_HOST_NAME,
+      ) {
+        daemon.internal_logger().log_error(error);
+      }
+    }
+
+    daemon.browser_extension().forget(self.user_id);
     data.users.remove(&self.user_id);
     UnmanageUserReturn::Success
   }
@@ -0,0 +1,2205 @@
+use std::sync::Arc;
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use serde::{Serialize, Deserialize};
+use crate::operating_system_integration::UserId;
+use crate::web_regulation_intrusive::HostPattern;
+use crate::web_regulation_intrusive::website_visits_limiter::{
+  LimiterCreator,
+  LimiterCreatorError,
+  MAXIMUM_LIMITERS_PER_USER,
+};
+use crate::web_regulation_intrusive::website_visit_delayer::{
+  DelayerCreator,
+  DelayerCreatorError,
+  MAXIMUM_DELAYERS_PER_USER,
+};
+use crate::web_regulation_intrusive::twitter::{
+  FilterCreator as TwitterFilterCreator,
+  MAXIMUM_FILTERS_PER_USER as MAXIMUM_TWITTER_FILTERS_PER_USER,
+};
+use crate::web_regulation_intrusive::youtube::{
+  RuleCreator as YoutubeRuleCreator,
+  MAXIMUM_RULES_PER_USER as MAXIMUM_YOUTUBE_RULES_PER_USER,
+};
+use crate::web_regulation_intrusive::media_type_blocker::{
+  RuleCreator as MediaTypeRuleCreator,
+  RuleCreatorError as MediaTypeRuleCreatorError,
+  MAXIMUM_RULES_PER_USER as MAXIMUM_MEDIA_TYPE_RULES_PER_USER,
+};
+use crate::web_regulation_intrusive::element_rules::{
+  RuleSheetCreator as ElementRuleSheetCreator,
+  Source as ElementRulesSource,
+  SourceError as ElementRulesSourceError,
+  MAXIMUM_SHEETS_PER_USER as MAXIMUM_ELEMENT_RULE_SHEETS_PER_USER,
+};
+use crate::web_regulation_intrusive::content_filter::{
+  RuleCreator as ContentFilterRuleCreator,
+  RuleCreatorError as ContentFilterRuleCreatorError,
+  MAXIMUM_RULES_PER_USER as MAXIMUM_CONTENT_FILTER_RULES_PER_USER,
+};
+use crate::web_regulation_intrusive::safe_search::{
+  SettingCreator as SafeSearchSettingCreator,
+  SettingCreatorError as SafeSearchSettingCreatorError,
+  MAXIMUM_PROTECTION_DURATION as MAXIMUM_SAFE_SEARCH_PROTECTION_DURATION,
+};
+use crate::web_regulation_intrusive::search_queries::{
+  RuleCreator as SearchQueryRuleCreator,
+  RuleCreatorError as SearchQueryRuleCreatorError,
+  MAXIMUM_RULES_PER_USER as MAXIMUM_SEARCH_QUERY_RULES_PER_USER,
+};
+use crate::web_regulation_intrusive::view_time_allowance::{
+  AllowanceCreator as ViewTimeAllowanceCreator,
+  AllowanceCreatorError as ViewTimeAllowanceCreatorError,
+  MAXIMUM_ALLOWANCES_PER_USER as MAXIMUM_VIEW_TIME_ALLOWANCES_PER_USER,
+};
+use crate::web_regulation_intrusive::image_rules::{
+  Classifier as ImageClassifier,
+  ClassifierModelCreator as ImageClassifierModelCreator,
+  ClassifierModelCreatorError as ImageClassifierModelCreatorError,
+  RuleCreator as ImageRuleCreator,
+  RuleCreatorError as ImageRuleCreatorError,
+  MAXIMUM_CLASSIFIERS as MAXIMUM_IMAGE_CLASSIFIERS,
+  MAXIMUM_RULES_PER_USER as MAXIMUM_IMAGE_RULES_PER_USER,
+};
+use crate::web_regulation_intrusive::protobuf::{
+  self,
+  Direction as ProtobufDirection,
+  FieldNameCreator as ProtobufFieldNameCreator,
+  FieldNameCreatorError as ProtobufFieldNameCreatorError,
+  Framing as ProtobufFraming,
+  InspectedField as InspectedProtobufField,
+  RuleCreator as ProtobufRuleCreator,
+  RuleCreatorError as ProtobufRuleCreatorError,
+  MAXIMUM_FIELD_NAMES_PER_USER as MAXIMUM_PROTOBUF_FIELD_NAMES_PER_USER,
+  MAXIMUM_RULES_PER_USER as MAXIMUM_PROTOBUF_RULES_PER_USER,
+};
+use crate::web_regulation_intrusive::encrypted_dns::{
+  Measures as EncryptedDnsMeasures,
+  SettingCreator as EncryptedDnsSettingCreator,
+  SettingCreatorError as EncryptedDnsSettingCreatorError,
+};
+use crate::web_regulation_intrusive::audit_log::{
+  Entry as AuditLogEntry,
+  ExportFormat as AuditLogExportFormat,
+  Privacy as AuditLogPrivacy,
+  Query as AuditLogQuery,
+  SettingCreator as AuditLogSettingCreator,
+  SettingCreatorError as AuditLogSettingCreatorError,
+  MAXIMUM_PROTECTION_DURATION as MAXIMUM_AUDIT_LOG_PROTECTION_DURATION,
+};
+use crate::{Daemon, DateTime, Duration, Uuid};
+use crate::database::web_regulation_intrusive_no_intercept_host as no_intercept_db;
+use crate::database::web_regulation_intrusive_proxied_host as proxied_host_db;
+use crate::database::web_regulation_intrusive_website_visits_limiter as visits_limiter_db;
+use crate::database::web_regulation_intrusive_website_visit_delayer as visit_delayer_db;
+use crate::database::web_regulation_intrusive_youtube_rule as youtube_rule_db;
+use crate::database::web_regulation_intrusive_twitter_filter as twitter_filter_db;
+use crate::database::web_regulation_intrusive_media_type_rule as media_type_rule_db;
+use crate::database::web_regulation_intrusive_element_rule_sheet as element_rule_sheet_db;
+use crate::database::web_regulation_intrusive_content_filter_rule as content_filter_rule_db;
+use crate::database::web_regulation_intrusive_safe_search_setting as safe_search_setting_db;
+use crate::database::web_regulation_intrusive_search_query_rule as search_query_rule_db;
+use crate::database::web_regulation_intrusive_view_time_allowance as view_time_allowance_db;
+use crate::database::web_regulation_intrusive_image_rule as image_rule_db;
+use crate::database::web_regulation_intrusive_image_classifier as image_classifier_db;
+use crate::database::web_regulation_intrusive_protobuf_rule as protobuf_rule_db;
+use crate::database::web_regulation_intrusive_protobuf_field_name as protobuf_field_name_db;
+use crate::database::web_regulation_intrusive_encrypted_dns_setting as encrypted_dns_setting_db;
+use crate::database::web_regulation_intrusive_audit_log as audit_log_db;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddNoInterceptHost {
+  host_pattern: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AddNoInterceptHostReturn {
+  InvalidHostPattern,
+  AlreadyAdded,
+  Success,
+  InternalError,
+}
+
+impl AddNoInterceptHost {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveAddNoInterceptHost";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> AddNoInterceptHostReturn {
+    let Ok(host_pattern) = HostPattern::parse(&self.host_pattern) else {
+      return AddNoInterceptHostReturn::InvalidHostPattern;
+    };
+
+    let mut no_intercept_hosts = daemon.web_regulation_intrusive().no_intercept_hosts_mut();
+    if no_intercept_hosts.contains(&host_pattern) {
+      return AddNoInterceptHostReturn::AlreadyAdded;
+    }
+
+    if let Err(error) = no_intercept_db::add_host_pattern(
+      daemon.database(),
+      &host_pattern,
+    ) {
+      daemon.internal_logger().log_error(error);
+      return AddNoInterceptHostReturn::InternalError;
+    }
+
+    no_intercept_hosts.add(host_pattern);
+    AddNoInterceptHostReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoveNoInterceptHost {
+  host_pattern: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RemoveNoInterceptHostReturn {
+  NoSuchHostPattern,
+  Success,
+  InternalError,
+}
+
+impl RemoveNoInterceptHost {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveRemoveNoInterceptHost";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> RemoveNoInterceptHostReturn {
+    let Ok(host_pattern) = HostPattern::parse(&self.host_pattern) else {
+      return RemoveNoInterceptHostReturn::NoSuchHostPattern;
+    };
+
+    let mut no_intercept_hosts = daemon.web_regulation_intrusive().no_intercept_hosts_mut();
+    if !no_intercept_hosts.contains(&host_pattern) {
+      return RemoveNoInterceptHostReturn::NoSuchHostPattern;
+    }
+
+    if let Err(error) = no_intercept_db::delete_host_pattern(
+      daemon.database(),
+      &host_pattern,
+    ) {
+      daemon.internal_logger().log_error(error);
+      return RemoveNoInterceptHostReturn::InternalError;
+    }
+
+    no_intercept_hosts.remove(&host_pattern);
+    RemoveNoInterceptHostReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddProxiedHost {
+  host_pattern: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AddProxiedHostReturn {
+  InvalidHostPattern,
+  AlreadyAdded,
+  Success,
+  InternalError,
+}
+
+impl AddProxiedHost {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveAddProxiedHost";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> AddProxiedHostReturn {
+    let Ok(host_pattern) = HostPattern::parse(&self.host_pattern) else {
+      return AddProxiedHostReturn::InvalidHostPattern;
+    };
+
+    let mut proxy_auto_config = daemon.web_regulation_intrusive().proxy_auto_config_mut();
+    if proxy_auto_config.contains(&host_pattern) {
+      return AddProxiedHostReturn::AlreadyAdded;
+    }
+
+    if let Err(error) = proxied_host_db::add_host_pattern(
+      daemon.database(),
+      &host_pattern,
+    ) {
+      daemon.internal_logger().log_error(error);
+      return AddProxiedHostReturn::InternalError;
+    }
+
+    proxy_auto_config.add(host_pattern);
+    AddProxiedHostReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoveProxiedHost {
+  host_pattern: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RemoveProxiedHostReturn {
+  NoSuchHostPattern,
+  Success,
+  InternalError,
+}
+
+impl RemoveProxiedHost {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveRemoveProxiedHost";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> RemoveProxiedHostReturn {
+    let Ok(host_pattern) = HostPattern::parse(&self.host_pattern) else {
+      return RemoveProxiedHostReturn::NoSuchHostPattern;
+    };
+
+    let mut proxy_auto_config = daemon.web_regulation_intrusive().proxy_auto_config_mut();
+    if !proxy_auto_config.contains(&host_pattern) {
+      return RemoveProxiedHostReturn::NoSuchHostPattern;
+    }
+
+    if let Err(error) = proxied_host_db::delete_host_pattern(
+      daemon.database(),
+      &host_pattern,
+    ) {
+      daemon.internal_logger().log_error(error);
+      return RemoveProxiedHostReturn::InternalError;
+    }
+
+    proxy_auto_config.remove(&host_pattern);
+    RemoveProxiedHostReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWebsiteVisitsLimiter {
+  user_id: UserId,
+  limiter_creator: LimiterCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CreateWebsiteVisitsLimiterReturn {
+  NoSuchUser { user_id: UserId },
+  InvalidLimiter(LimiterCreatorError),
+  ReachedMaximumLimitersAllowed,
+  DuplicateId,
+  Success,
+  InternalError,
+}
+
+impl CreateWebsiteVisitsLimiter {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveCreateWebsiteVisitsLimiter";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> CreateWebsiteVisitsLimiterReturn {
+    match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        if !data.users.contains_key(&self.user_id) {
+          return CreateWebsiteVisitsLimiterReturn::NoSuchUser { user_id: self.user_id };
+        }
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return CreateWebsiteVisitsLimiterReturn::InternalError;
+      }
+    }
+
+    let limiter = match self.limiter_creator.create(self.user_id) {
+      Ok(limiter) => {
+        limiter
+      }
+      Err(error) => {
+        return CreateWebsiteVisitsLimiterReturn::InvalidLimiter(error);
+      }
+    };
+
+    let mut limiters = daemon.web_regulation_intrusive().website_visits_limiter().limiters();
+    if limiters.iter().any(|other| other.id() == limiter.id()) {
+      return CreateWebsiteVisitsLimiterReturn::DuplicateId;
+    }
+
+    let limiters_of_user = limiters
+      .iter()
+      .filter(|other| other.user_id() == self.user_id)
+      .count();
+
+    if limiters_of_user >= MAXIMUM_LIMITERS_PER_USER {
+      return CreateWebsiteVisitsLimiterReturn::ReachedMaximumLimitersAllowed;
+    }
+
+    if let Err(error) = visits_limiter_db::add_limiter(daemon.database(), &limiter) {
+      daemon.internal_logger().log_error(error);
+      return CreateWebsiteVisitsLimiterReturn::InternalError;
+    }
+
+    limiters.push(limiter);
+    CreateWebsiteVisitsLimiterReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteWebsiteVisitsLimiter {
+  limiter_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DeleteWebsiteVisitsLimiterReturn {
+  NoSuchLimiter,
+  Success,
+  InternalError,
+}
+
+impl DeleteWebsiteVisitsLimiter {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveDeleteWebsiteVisitsLimiter";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> DeleteWebsiteVisitsLimiterReturn {
+    let mut limiters = daemon.web_regulation_intrusive().website_visits_limiter().limiters();
+    let Some(index) = limiters.iter().position(|limiter| *limiter.id() == self.limiter_id) else {
+      return DeleteWebsiteVisitsLimiterReturn::NoSuchLimiter;
+    };
+
+    if let Err(error) = visits_limiter_db::delete_limiter(daemon.database(), &self.limiter_id) {
+      daemon.internal_logger().log_error(error);
+      return DeleteWebsiteVisitsLimiterReturn::InternalError;
+    }
+
+    limiters.remove(index);
+    DeleteWebsiteVisitsLimiterReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWebsiteVisitDelayer {
+  user_id: UserId,
+  delayer_creator: DelayerCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CreateWebsiteVisitDelayerReturn {
+  NoSuchUser { user_id: UserId },
+  InvalidDelayer(DelayerCreatorError),
+  ReachedMaximumDelayersAllowed,
+  DuplicateId,
+  Success,
+  InternalError,
+}
+
+impl CreateWebsiteVisitDelayer {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveCreateWebsiteVisitDelayer";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> CreateWebsiteVisitDelayerReturn {
+    match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        if !data.users.contains_key(&self.user_id) {
+          return CreateWebsiteVisitDelayerReturn::NoSuchUser { user_id: self.user_id };
+        }
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return CreateWebsiteVisitDelayerReturn::InternalError;
+      }
+    }
+
+    let delayer = match self.delayer_creator.create(self.user_id) {
+      Ok(delayer) => {
+        delayer
+      }
+      Err(error) => {
+        return CreateWebsiteVisitDelayerReturn::InvalidDelayer(error);
+      }
+    };
+
+    let mut delayers = daemon.web_regulation_intrusive().website_visit_delayer().delayers();
+    if delayers.iter().any(|other| other.id() == delayer.id()) {
+      return CreateWebsiteVisitDelayerReturn::DuplicateId;
+    }
+
+    let delayers_of_user = delayers
+      .iter()
+      .filter(|other| other.user_id() == self.user_id)
+      .count();
+
+    if delayers_of_user >= MAXIMUM_DELAYERS_PER_USER {
+      return CreateWebsiteVisitDelayerReturn::ReachedMaximumDelayersAllowed;
+    }
+
+    if let Err(error) = visit_delayer_db::add_delayer(daemon.database(), &delayer) {
+      daemon.internal_logger().log_error(error);
+      return CreateWebsiteVisitDelayerReturn::InternalError;
+    }
+
+    delayers.push(delayer);
+    CreateWebsiteVisitDelayerReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteWebsiteVisitDelayer {
+  delayer_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DeleteWebsiteVisitDelayerReturn {
+  NoSuchDelayer,
+  Success,
+  InternalError,
+}
+
+impl DeleteWebsiteVisitDelayer {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveDeleteWebsiteVisitDelayer";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> DeleteWebsiteVisitDelayerReturn {
+    let mut delayers = daemon.web_regulation_intrusive().website_visit_delayer().delayers();
+    let Some(index) = delayers.iter().position(|delayer| *delayer.id() == self.delayer_id) else {
+      return DeleteWebsiteVisitDelayerReturn::NoSuchDelayer;
+    };
+
+    if let Err(error) = visit_delayer_db::delete_delayer(daemon.database(), &self.delayer_id) {
+      daemon.internal_logger().log_error(error);
+      return DeleteWebsiteVisitDelayerReturn::InternalError;
+    }
+
+    delayers.remove(index);
+    DeleteWebsiteVisitDelayerReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateYoutubeRule {
+  user_id: UserId,
+  rule_creator: YoutubeRuleCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CreateYoutubeRuleReturn {
+  NoSuchUser { user_id: UserId },
+  ReachedMaximumRulesAllowed,
+  DuplicateId,
+  Success,
+  InternalError,
+}
+
+impl CreateYoutubeRule {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveCreateYoutubeRule";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> CreateYoutubeRuleReturn {
+    match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        if !data.users.contains_key(&self.user_id) {
+          return CreateYoutubeRuleReturn::NoSuchUser { user_id: self.user_id };
+        }
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return CreateYoutubeRuleReturn::InternalError;
+      }
+    }
+
+    let rule = self.rule_creator.create(self.user_id);
+
+    let mut rules = daemon.web_regulation_intrusive().youtube().rules();
+    if rules.iter().any(|other| other.id() == rule.id()) {
+      return CreateYoutubeRuleReturn::DuplicateId;
+    }
+
+    let rules_of_user = rules
+      .iter()
+      .filter(|other| other.user_id() == self.user_id)
+      .count();
+
+    if rules_of_user >= MAXIMUM_YOUTUBE_RULES_PER_USER {
+      return CreateYoutubeRuleReturn::ReachedMaximumRulesAllowed;
+    }
+
+    if let Err(error) = youtube_rule_db::add_rule(daemon.database(), &rule) {
+      daemon.internal_logger().log_error(error);
+      return CreateYoutubeRuleReturn::InternalError;
+    }
+
+    rules.push(rule);
+    CreateYoutubeRuleReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteYoutubeRule {
+  rule_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DeleteYoutubeRuleReturn {
+  NoSuchRule,
+  Success,
+  InternalError,
+}
+
+impl DeleteYoutubeRule {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveDeleteYoutubeRule";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> DeleteYoutubeRuleReturn {
+    let mut rules = daemon.web_regulation_intrusive().youtube().rules();
+    let Some(index) = rules.iter().position(|rule| *rule.id() == self.rule_id) else {
+      return DeleteYoutubeRuleReturn::NoSuchRule;
+    };
+
+    if let Err(error) = youtube_rule_db::delete_rule(daemon.database(), &self.rule_id) {
+      daemon.internal_logger().log_error(error);
+      return DeleteYoutubeRuleReturn::InternalError;
+    }
+
+    rules.remove(index);
+    DeleteYoutubeRuleReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateTwitterFilter {
+  user_id: UserId,
+  filter_creator: TwitterFilterCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CreateTwitterFilterReturn {
+  NoSuchUser { user_id: UserId },
+  ReachedMaximumFiltersAllowed,
+  DuplicateId,
+  Success,
+  InternalError,
+}
+
+impl CreateTwitterFilter {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveCreateTwitterFilter";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> CreateTwitterFilterReturn {
+    match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        if !data.users.contains_key(&self.user_id) {
+          return CreateTwitterFilterReturn::NoSuchUser { user_id: self.user_id };
+        }
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return CreateTwitterFilterReturn::InternalError;
+      }
+    }
+
+    let filter = self.filter_creator.create(self.user_id);
+
+    let mut filters = daemon.web_regulation_intrusive().twitter().filters();
+    if filters.iter().any(|other| other.id() == filter.id()) {
+      return CreateTwitterFilterReturn::DuplicateId;
+    }
+
+    let filters_of_user = filters
+      .iter()
+      .filter(|other| other.user_id() == self.user_id)
+      .count();
+
+    if filters_of_user >= MAXIMUM_TWITTER_FILTERS_PER_USER {
+      return CreateTwitterFilterReturn::ReachedMaximumFiltersAllowed;
+    }
+
+    if let Err(error) = twitter_filter_db::add_filter(daemon.database(), &filter) {
+      daemon.internal_logger().log_error(error);
+      return CreateTwitterFilterReturn::InternalError;
+    }
+
+    filters.push(filter);
+    CreateTwitterFilterReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteTwitterFilter {
+  filter_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DeleteTwitterFilterReturn {
+  NoSuchFilter,
+  Success,
+  InternalError,
+}
+
+impl DeleteTwitterFilter {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveDeleteTwitterFilter";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> DeleteTwitterFilterReturn {
+    let mut filters = daemon.web_regulation_intrusive().twitter().filters();
+    let Some(index) = filters.iter().position(|filter| *filter.id() == self.filter_id) else {
+      return DeleteTwitterFilterReturn::NoSuchFilter;
+    };
+
+    if let Err(error) = twitter_filter_db::delete_filter(daemon.database(), &self.filter_id) {
+      daemon.internal_logger().log_error(error);
+      return DeleteTwitterFilterReturn::InternalError;
+    }
+
+    filters.remove(index);
+    DeleteTwitterFilterReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateMediaTypeRule {
+  user_id: UserId,
+  rule_creator: MediaTypeRuleCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CreateMediaTypeRuleReturn {
+  NoSuchUser { user_id: UserId },
+  InvalidRule(MediaTypeRuleCreatorError),
+  ReachedMaximumRulesAllowed,
+  DuplicateId,
+  Success,
+  InternalError,
+}
+
+impl CreateMediaTypeRule {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveCreateMediaTypeRule";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> CreateMediaTypeRuleReturn {
+    match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        if !data.users.contains_key(&self.user_id) {
+          return CreateMediaTypeRuleReturn::NoSuchUser { user_id: self.user_id };
+        }
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return CreateMediaTypeRuleReturn::InternalError;
+      }
+    }
+
+    let rule = match self.rule_creator.create(self.user_id) {
+      Ok(rule) => {
+        rule
+      }
+      Err(error) => {
+        return CreateMediaTypeRuleReturn::InvalidRule(error);
+      }
+    };
+
+    let mut rules = daemon.web_regulation_intrusive().media_type_blocker().rules();
+    if rules.iter().any(|other| other.id() == rule.id()) {
+      return CreateMediaTypeRuleReturn::DuplicateId;
+    }
+
+    let rules_of_user = rules
+      .iter()
+      .filter(|other| other.user_id() == self.user_id)
+      .count();
+
+    if rules_of_user >= MAXIMUM_MEDIA_TYPE_RULES_PER_USER {
+      return CreateMediaTypeRuleReturn::ReachedMaximumRulesAllowed;
+    }
+
+    if let Err(error) = media_type_rule_db::add_rule(daemon.database(), &rule) {
+      daemon.internal_logger().log_error(error);
+      return CreateMediaTypeRuleReturn::InternalError;
+    }
+
+    rules.push(rule);
+    CreateMediaTypeRuleReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteMediaTypeRule {
+  rule_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DeleteMediaTypeRuleReturn {
+  NoSuchRule,
+  Success,
+  InternalError,
+}
+
+impl DeleteMediaTypeRule {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveDeleteMediaTypeRule";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> DeleteMediaTypeRuleReturn {
+    let mut rules = daemon.web_regulation_intrusive().media_type_blocker().rules();
+    let Some(index) = rules.iter().position(|rule| *rule.id() == self.rule_id) else {
+      return DeleteMediaTypeRuleReturn::NoSuchRule;
+    };
+
+    if let Err(error) = media_type_rule_db::delete_rule(daemon.database(), &self.rule_id) {
+      daemon.internal_logger().log_error(error);
+      return DeleteMediaTypeRuleReturn::InternalError;
+    }
+
+    rules.remove(index);
+    DeleteMediaTypeRuleReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateElementRuleSheet {
+  user_id: UserId,
+  sheet_creator: ElementRuleSheetCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CreateElementRuleSheetReturn {
+  NoSuchUser { user_id: UserId },
+  InvalidSource(ElementRulesSourceError),
+  ReachedMaximumSheetsAllowed,
+  DuplicateId,
+  Success,
+  InternalError,
+}
+
+impl CreateElementRuleSheet {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveCreateElementRuleSheet";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> CreateElementRuleSheetReturn {
+    match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        if !data.users.contains_key(&self.user_id) {
+          return CreateElementRuleSheetReturn::NoSuchUser { user_id: self.user_id };
+        }
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return CreateElementRuleSheetReturn::InternalError;
+      }
+    }
+
+    let sheet = match self.sheet_creator.create(self.user_id) {
+      Ok(sheet) => {
+        sheet
+      }
+      Err(error) => {
+        return CreateElementRuleSheetReturn::InvalidSource(error);
+      }
+    };
+
+    let mut sheets = daemon.web_regulation_intrusive().element_regulation().sheets();
+    if sheets.iter().any(|other| other.id() == sheet.id()) {
+      return CreateElementRuleSheetReturn::DuplicateId;
+    }
+
+    let sheets_of_user = sheets
+      .iter()
+      .filter(|other| other.user_id() == self.user_id)
+      .count();
+
+    if sheets_of_user >= MAXIMUM_ELEMENT_RULE_SHEETS_PER_USER {
+      return CreateElementRuleSheetReturn::ReachedMaximumSheetsAllowed;
+    }
+
+    if let Err(error) = element_rule_sheet_db::add_sheet(daemon.database(), &sheet) {
+      daemon.internal_logger().log_error(error);
+      return CreateElementRuleSheetReturn::InternalError;
+    }
+
+    sheets.push(sheet);
+    CreateElementRuleSheetReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteElementRuleSheet {
+  sheet_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DeleteElementRuleSheetReturn {
+  NoSuchSheet,
+  Success,
+  InternalError,
+}
+
+impl DeleteElementRuleSheet {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveDeleteElementRuleSheet";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> DeleteElementRuleSheetReturn {
+    let mut sheets = daemon.web_regulation_intrusive().element_regulation().sheets();
+    let Some(index) = sheets.iter().position(|sheet| *sheet.id() == self.sheet_id) else {
+      return DeleteElementRuleSheetReturn::NoSuchSheet;
+    };
+
+    if let Err(error) = element_rule_sheet_db::delete_sheet(daemon.database(), &self.sheet_id) {
+      daemon.internal_logger().log_error(error);
+      return DeleteElementRuleSheetReturn::InternalError;
+    }
+
+    sheets.remove(index);
+    DeleteElementRuleSheetReturn::Success
+  }
+}
+
+/// Checks element rules without saving them, so clients can show errors
+/// while the user is still writing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidateElementRules {
+  source: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ValidateElementRulesReturn {
+  Valid { rule_count: usize },
+  Invalid(ElementRulesSourceError),
+}
+
+impl ValidateElementRules {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveValidateElementRules";
+
+  pub fn execute(self, _daemon: Arc<Daemon>) -> ValidateElementRulesReturn {
+    match ElementRulesSource::parse(self.source) {
+      Ok(source) => {
+        ValidateElementRulesReturn::Valid { rule_count: source.rules().len() }
+      }
+      Err(error) => {
+        ValidateElementRulesReturn::Invalid(error)
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateContentFilterRule {
+  user_id: UserId,
+  rule_creator: ContentFilterRuleCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CreateContentFilterRuleReturn {
+  NoSuchUser { user_id: UserId },
+  InvalidRule(ContentFilterRuleCreatorError),
+  ReachedMaximumRulesAllowed,
+  DuplicateId,
+  Success,
+  InternalError,
+}
+
+impl CreateContentFilterRule {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveCreateContentFilterRule";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> CreateContentFilterRuleReturn {
+    match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        if !data.users.contains_key(&self.user_id) {
+          return CreateContentFilterRuleReturn::NoSuchUser { user_id: self.user_id };
+        }
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return CreateContentFilterRuleReturn::InternalError;
+      }
+    }
+
+    let rule = match self.rule_creator.create(self.user_id) {
+      Ok(rule) => {
+        rule
+      }
+      Err(error) => {
+        return CreateContentFilterRuleReturn::InvalidRule(error);
+      }
+    };
+
+    let mut rules = daemon.web_regulation_intrusive().content_filter().rules();
+    if rules.iter().any(|other| other.id() == rule.id()) {
+      return CreateContentFilterRuleReturn::DuplicateId;
+    }
+
+    let rules_of_user = rules
+      .iter()
+      .filter(|other| other.user_id() == self.user_id)
+      .count();
+
+    if rules_of_user >= MAXIMUM_CONTENT_FILTER_RULES_PER_USER {
+      return CreateContentFilterRuleReturn::ReachedMaximumRulesAllowed;
+    }
+
+    if let Err(error) = content_filter_rule_db::add_rule(daemon.database(), &rule) {
+      daemon.internal_logger().log_error(error);
+      return CreateContentFilterRuleReturn::InternalError;
+    }
+
+    rules.push(rule);
+    CreateContentFilterRuleReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteContentFilterRule {
+  rule_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DeleteContentFilterRuleReturn {
+  NoSuchRule,
+  Success,
+  InternalError,
+}
+
+impl DeleteContentFilterRule {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveDeleteContentFilterRule";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> DeleteContentFilterRuleReturn {
+    let mut rules = daemon.web_regulation_intrusive().content_filter().rules();
+    let Some(index) = rules.iter().position(|rule| *rule.id() == self.rule_id) else {
+      return DeleteContentFilterRuleReturn::NoSuchRule;
+    };
+
+    if let Err(error) = content_filter_rule_db::delete_rule(daemon.database(), &self.rule_id) {
+      daemon.internal_logger().log_error(error);
+      return DeleteContentFilterRuleReturn::InternalError;
+    }
+
+    rules.remove(index);
+    DeleteContentFilterRuleReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnableSafeSearch {
+  user_id: UserId,
+  setting_creator: SafeSearchSettingCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum EnableSafeSearchReturn {
+  NoSuchUser { user_id: UserId },
+  InvalidSetting(SafeSearchSettingCreatorError),
+  AlreadyEnabled,
+  Success,
+  InternalError,
+}
+
+impl EnableSafeSearch {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveEnableSafeSearch";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> EnableSafeSearchReturn {
+    match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        if !data.users.contains_key(&self.user_id) {
+          return EnableSafeSearchReturn::NoSuchUser { user_id: self.user_id };
+        }
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return EnableSafeSearchReturn::InternalError;
+      }
+    }
+
+    let setting = match self.setting_creator.create(self.user_id, DateTime::now()) {
+      Ok(setting) => {
+        setting
+      }
+      Err(error) => {
+        return EnableSafeSearchReturn::InvalidSetting(error);
+      }
+    };
+
+    let safe_search = daemon.web_regulation_intrusive().safe_search();
+    let mut settings = safe_search.settings();
+    if settings.iter().any(|other| other.user_id() == self.user_id) {
+      return EnableSafeSearchReturn::AlreadyEnabled;
+    }
+
+    if let Err(error) = safe_search.apply_firewall_rules(self.user_id, true) {
+      daemon.internal_logger().log_error(error);
+      if let Err(error) = safe_search.apply_firewall_rules(self.user_id, false) {
+        daemon.internal_logger().log_error(error);
+      }
+      return EnableSafeSearchReturn::InternalError;
+    }
+
+    if let Err(error) = safe_search_setting_db::add_setting(daemon.database(), &setting) {
+      daemon.internal_logger().log_error(error);
+      if let Err(error) = safe_search.apply_firewall_rules(self.user_id, false) {
+        daemon.internal_logger().log_error(error);
+      }
+      return EnableSafeSearchReturn::InternalError;
+    }
+
+    settings.push(setting);
+    EnableSafeSearchReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisableSafeSearch {
+  user_id: UserId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DisableSafeSearchReturn {
+  NotEnabled,
+  IsProtected,
+  Success,
+  InternalError,
+}
+
+impl DisableSafeSearch {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveDisableSafeSearch";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> DisableSafeSearchReturn {
+    let safe_search = daemon.web_regulation_intrusive().safe_search();
+    let mut settings = safe_search.settings();
+    let Some(index) = settings.iter().position(|setting| setting.user_id() == self.user_id) else {
+      return DisableSafeSearchReturn::NotEnabled;
+    };
+
+    if settings[index].is_protected(DateTime::now()) {
+      return DisableSafeSearchReturn::IsProtected;
+    }
+
+    if let Err(error) = safe_search.apply_firewall_rules(self.user_id, false) {
+      daemon.internal_logger().log_error(error);
+      if let Err(error) = safe_search.apply_firewall_rules(self.user_id, true) {
+        daemon.internal_logger().log_error(error);
+      }
+      return DisableSafeSearchReturn::InternalError;
+    }
+
+    if let Err(error) = safe_search_setting_db::delete_setting(daemon.database(), self.user_id) {
+      daemon.internal_logger().log_error(error);
+      if let Err(error) = safe_search.apply_firewall_rules(self.user_id, true) {
+        daemon.internal_logger().log_error(error);
+      }
+      return DisableSafeSearchReturn::InternalError;
+    }
+
+    settings.remove(index);
+    DisableSafeSearchReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncreaseSafeSearchProtection {
+  user_id: UserId,
+  increment: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum IncreaseSafeSearchProtectionReturn {
+  NotEnabled,
+  WouldBeEffectiveForTooLong,
+  Success,
+  InternalError,
+}
+
+impl IncreaseSafeSearchProtection {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveIncreaseSafeSearchProtection";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> IncreaseSafeSearchProtectionReturn {
+    let mut settings = daemon.web_regulation_intrusive().safe_search().settings();
+    let Some(setting) = settings.iter_mut().find(|setting| setting.user_id() == self.user_id) else {
+      return IncreaseSafeSearchProtectionReturn::NotEnabled;
+    };
+
+    let now = DateTime::now();
+    setting.synchronize(now);
+
+    let Some(new_remaining_duration) = setting
+      .protector()
+      .remaining_duration()
+      .checked_add(&self.increment) else
+    {
+      return IncreaseSafeSearchProtectionReturn::WouldBeEffectiveForTooLong;
+    };
+
+    if new_remaining_duration > MAXIMUM_SAFE_SEARCH_PROTECTION_DURATION {
+      return IncreaseSafeSearchProtectionReturn::WouldBeEffectiveForTooLong;
+    }
+
+    let mut protected = setting.clone();
+    protected.protect_for(new_remaining_duration, now);
+
+    if let Err(error) = safe_search_setting_db::update_protector(daemon.database(), &protected) {
+      daemon.internal_logger().log_error(error);
+      return IncreaseSafeSearchProtectionReturn::InternalError;
+    }
+
+    *setting = protected;
+    IncreaseSafeSearchProtectionReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateSearchQueryRule {
+  user_id: UserId,
+  rule_creator: SearchQueryRuleCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CreateSearchQueryRuleReturn {
+  NoSuchUser { user_id: UserId },
+  InvalidRule(SearchQueryRuleCreatorError),
+  ReachedMaximumRulesAllowed,
+  DuplicateId,
+  Success,
+  InternalError,
+}
+
+impl CreateSearchQueryRule {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveCreateSearchQueryRule";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> CreateSearchQueryRuleReturn {
+    match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        if !data.users.contains_key(&self.user_id) {
+          return CreateSearchQueryRuleReturn::NoSuchUser { user_id: self.user_id };
+        }
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return CreateSearchQueryRuleReturn::InternalError;
+      }
+    }
+
+    let rule = match self.rule_creator.create(self.user_id) {
+      Ok(rule) => {
+        rule
+      }
+      Err(error) => {
+        return CreateSearchQueryRuleReturn::InvalidRule(error);
+      }
+    };
+
+    let mut rules = daemon.web_regulation_intrusive().search_query_blocker().rules();
+    if rules.iter().any(|other| other.id() == rule.id()) {
+      return CreateSearchQueryRuleReturn::DuplicateId;
+    }
+
+    let rules_of_user = rules
+      .iter()
+      .filter(|other| other.user_id() == self.user_id)
+      .count();
+
+    if rules_of_user >= MAXIMUM_SEARCH_QUERY_RULES_PER_USER {
+      return CreateSearchQueryRuleReturn::ReachedMaximumRulesAllowed;
+    }
+
+    if let Err(error) = search_query_rule_db::add_rule(daemon.database(), &rule) {
+      daemon.internal_logger().log_error(error);
+      return CreateSearchQueryRuleReturn::InternalError;
+    }
+
+    rules.push(rule);
+    CreateSearchQueryRuleReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteSearchQueryRule {
+  rule_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DeleteSearchQueryRuleReturn {
+  NoSuchRule,
+  Success,
+  InternalError,
+}
+
+impl DeleteSearchQueryRule {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveDeleteSearchQueryRule";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> DeleteSearchQueryRuleReturn {
+    let mut rules = daemon.web_regulation_intrusive().search_query_blocker().rules();
+    let Some(index) = rules.iter().position(|rule| *rule.id() == self.rule_id) else {
+      return DeleteSearchQueryRuleReturn::NoSuchRule;
+    };
+
+    if let Err(error) = search_query_rule_db::delete_rule(daemon.database(), &self.rule_id) {
+      daemon.internal_logger().log_error(error);
+      return DeleteSearchQueryRuleReturn::InternalError;
+    }
+
+    rules.remove(index);
+    DeleteSearchQueryRuleReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateViewTimeAllowance {
+  user_id: UserId,
+  allowance_creator: ViewTimeAllowanceCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CreateViewTimeAllowanceReturn {
+  NoSuchUser { user_id: UserId },
+  InvalidAllowance(ViewTimeAllowanceCreatorError),
+  ReachedMaximumAllowancesAllowed,
+  DuplicateId,
+  Success,
+  InternalError,
+}
+
+impl CreateViewTimeAllowance {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveCreateViewTimeAllowance";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> CreateViewTimeAllowanceReturn {
+    match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        if !data.users.contains_key(&self.user_id) {
+          return CreateViewTimeAllowanceReturn::NoSuchUser { user_id: self.user_id };
+        }
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return CreateViewTimeAllowanceReturn::InternalError;
+      }
+    }
+
+    let allowance = match self.allowance_creator.create(self.user_id, DateTime::now()) {
+      Ok(allowance) => {
+        allowance
+      }
+      Err(error) => {
+        return CreateViewTimeAllowanceReturn::InvalidAllowance(error);
+      }
+    };
+
+    let mut allowances = daemon.web_regulation_intrusive().view_time_allowances().allowances();
+    if allowances.iter().any(|other| other.id() == allowance.id()) {
+      return CreateViewTimeAllowanceReturn::DuplicateId;
+    }
+
+    let allowances_of_user = allowances
+      .iter()
+      .filter(|other| other.user_id() == self.user_id)
+      .count();
+
+    if allowances_of_user >= MAXIMUM_VIEW_TIME_ALLOWANCES_PER_USER {
+      return CreateViewTimeAllowanceReturn::ReachedMaximumAllowancesAllowed;
+    }
+
+    if let Err(error) = view_time_allowance_db::add_allowance(daemon.database(), &allowance) {
+      daemon.internal_logger().log_error(error);
+      return CreateViewTimeAllowanceReturn::InternalError;
+    }
+
+    allowances.push(allowance);
+    CreateViewTimeAllowanceReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteViewTimeAllowance {
+  allowance_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DeleteViewTimeAllowanceReturn {
+  NoSuchAllowance,
+  Success,
+  InternalError,
+}
+
+impl DeleteViewTimeAllowance {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveDeleteViewTimeAllowance";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> DeleteViewTimeAllowanceReturn {
+    let mut allowances = daemon.web_regulation_intrusive().view_time_allowances().allowances();
+    let Some(index) = allowances.iter().position(|allowance| *allowance.id() == self.allowance_id) else {
+      return DeleteViewTimeAllowanceReturn::NoSuchAllowance;
+    };
+
+    if let Err(error) = view_time_allowance_db::delete_allowance(daemon.database(), &self.allowance_id) {
+      daemon.internal_logger().log_error(error);
+      return DeleteViewTimeAllowanceReturn::InternalError;
+    }
+
+    allowances.remove(index);
+    DeleteViewTimeAllowanceReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateImageRule {
+  user_id: UserId,
+  rule_creator: ImageRuleCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CreateImageRuleReturn {
+  NoSuchUser { user_id: UserId },
+  InvalidRule(ImageRuleCreatorError),
+  NoSuchClassifier,
+  NoSuchClass,
+  ReachedMaximumRulesAllowed,
+  DuplicateId,
+  Success,
+  InternalError,
+}
+
+impl CreateImageRule {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveCreateImageRule";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> CreateImageRuleReturn {
+    match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        if !data.users.contains_key(&self.user_id) {
+          return CreateImageRuleReturn::NoSuchUser { user_id: self.user_id };
+        }
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return CreateImageRuleReturn::InternalError;
+      }
+    }
+
+    let rule = match self.rule_creator.create(self.user_id) {
+      Ok(rule) => {
+        rule
+      }
+      Err(error) => {
+        return CreateImageRuleReturn::InvalidRule(error);
+      }
+    };
+
+    let image_regulation = daemon.web_regulation_intrusive().image_regulation();
+    let mut rules = image_regulation.rules();
+    if rules.iter().any(|other| other.id() == rule.id()) {
+      return CreateImageRuleReturn::DuplicateId;
+    }
+
+    if let Some(condition) = rule.classification() {
+      let classifiers = image_regulation.classifiers();
+      let Some(classifier) = classifiers
+        .iter()
+        .find(|classifier| *classifier.model().id() == condition.classifier_id)
+      else {
+        return CreateImageRuleReturn::NoSuchClassifier;
+      };
+
+      if !classifier.model().classes().contains(&condition.class) {
+        return CreateImageRuleReturn::NoSuchClass;
+      }
+    }
+
+    let rules_of_user = rules
+      .iter()
+      .filter(|other| other.user_id() == self.user_id)
+      .count();
+
+    if rules_of_user >= MAXIMUM_IMAGE_RULES_PER_USER {
+      return CreateImageRuleReturn::ReachedMaximumRulesAllowed;
+    }
+
+    if let Err(error) = image_rule_db::add_rule(daemon.database(), &rule) {
+      daemon.internal_logger().log_error(error);
+      return CreateImageRuleReturn::InternalError;
+    }
+
+    rules.push(rule);
+    CreateImageRuleReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteImageRule {
+  rule_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DeleteImageRuleReturn {
+  NoSuchRule,
+  Success,
+  InternalError,
+}
+
+impl DeleteImageRule {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveDeleteImageRule";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> DeleteImageRuleReturn {
+    let mut rules = daemon.web_regulation_intrusive().image_regulation().rules();
+    let Some(index) = rules.iter().position(|rule| *rule.id() == self.rule_id) else {
+      return DeleteImageRuleReturn::NoSuchRule;
+    };
+
+    if let Err(error) = image_rule_db::delete_rule(daemon.database(), &self.rule_id) {
+      daemon.internal_logger().log_error(error);
+      return DeleteImageRuleReturn::InternalError;
+    }
+
+    rules.remove(index);
+    DeleteImageRuleReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddImageClassifier {
+  model_creator: ImageClassifierModelCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AddImageClassifierReturn {
+  InvalidModel(ImageClassifierModelCreatorError),
+  /// The model file couldn't be opened, or isn't an image classifier.
+  CouldNotOpenModel,
+  ReachedMaximumClassifiersAllowed,
+  DuplicateId,
+  Success,
+  InternalError,
+}
+
+impl AddImageClassifier {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveAddImageClassifier";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> AddImageClassifierReturn {
+    let model = match self.model_creator.create() {
+      Ok(model) => {
+        model
+      }
+      Err(error) => {
+        return AddImageClassifierReturn::InvalidModel(error);
+      }
+    };
+
+    let mut classifiers = daemon.web_regulation_intrusive().image_regulation().classifiers();
+    if classifiers.iter().any(|other| other.model().id() == model.id()) {
+      return AddImageClassifierReturn::DuplicateId;
+    }
+
+    if classifiers.len() >= MAXIMUM_IMAGE_CLASSIFIERS {
+      return AddImageClassifierReturn::ReachedMaximumClassifiersAllowed;
+    }
+
+    // Opened now so a bad model is reported rather than silently skipped
+    // when rules use it.
+    let classifier = match model.open() {
+      Ok(classifier) => {
+        ImageClassifier::loaded(model, Arc::new(classifier))
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return AddImageClassifierReturn::CouldNotOpenModel;
+      }
+    };
+
+    if let Err(error) = image_classifier_db::add_model(daemon.database(), classifier.model()) {
+      daemon.internal_logger().log_error(error);
+      return AddImageClassifierReturn::InternalError;
+    }
+
+    classifiers.push(classifier);
+    AddImageClassifierReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoveImageClassifier {
+  classifier_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RemoveImageClassifierReturn {
+  NoSuchClassifier,
+  /// Image rules still classify images with it.
+  InUse,
+  Success,
+  InternalError,
+}
+
+impl RemoveImageClassifier {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveRemoveImageClassifier";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> RemoveImageClassifierReturn {
+    let image_regulation = daemon.web_regulation_intrusive().image_regulation();
+    let rules = image_regulation.rules();
+    let mut classifiers = image_regulation.classifiers();
+
+    let Some(index) = classifiers
+      .iter()
+      .position(|classifier| *classifier.model().id() == self.classifier_id)
+    else {
+      return RemoveImageClassifierReturn::NoSuchClassifier;
+    };
+
+    let is_in_use = rules
+      .iter()
+      .filter_map(|rule| rule.classification())
+      .any(|condition| condition.classifier_id == self.classifier_id);
+
+    if is_in_use {
+      return RemoveImageClassifierReturn::InUse;
+    }
+
+    if let Err(error) = image_classifier_db::delete_model(daemon.database(), &self.classifier_id) {
+      daemon.internal_logger().log_error(error);
+      return RemoveImageClassifierReturn::InternalError;
+    }
+
+    classifiers.remove(index);
+    RemoveImageClassifierReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateProtobufRule {
+  user_id: UserId,
+  rule_creator: ProtobufRuleCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CreateProtobufRuleReturn {
+  NoSuchUser { user_id: UserId },
+  InvalidRule(ProtobufRuleCreatorError),
+  ReachedMaximumRulesAllowed,
+  DuplicateId,
+  Success,
+  InternalError,
+}
+
+impl CreateProtobufRule {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveCreateProtobufRule";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> CreateProtobufRuleReturn {
+    match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        if !data.users.contains_key(&self.user_id) {
+          return CreateProtobufRuleReturn::NoSuchUser { user_id: self.user_id };
+        }
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return CreateProtobufRuleReturn::InternalError;
+      }
+    }
+
+    let protobuf_regulation = daemon.web_regulation_intrusive().protobuf_regulation();
+    let field_names = protobuf_regulation.field_names().clone();
+    let rule = match self.rule_creator.create(self.user_id, &field_names) {
+      Ok(rule) => {
+        rule
+      }
+      Err(error) => {
+        return CreateProtobufRuleReturn::InvalidRule(error);
+      }
+    };
+
+    let mut rules = protobuf_regulation.rules();
+    if rules.iter().any(|other| other.id() == rule.id()) {
+      return CreateProtobufRuleReturn::DuplicateId;
+    }
+
+    let rules_of_user = rules
+      .iter()
+      .filter(|other| other.user_id() == self.user_id)
+      .count();
+
+    if rules_of_user >= MAXIMUM_PROTOBUF_RULES_PER_USER {
+      return CreateProtobufRuleReturn::ReachedMaximumRulesAllowed;
+    }
+
+    if let Err(error) = protobuf_rule_db::add_rule(daemon.database(), &rule) {
+      daemon.internal_logger().log_error(error);
+      return CreateProtobufRuleReturn::InternalError;
+    }
+
+    rules.push(rule);
+    CreateProtobufRuleReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteProtobufRule {
+  rule_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DeleteProtobufRuleReturn {
+  NoSuchRule,
+  Success,
+  InternalError,
+}
+
+impl DeleteProtobufRule {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveDeleteProtobufRule";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> DeleteProtobufRuleReturn {
+    let mut rules = daemon.web_regulation_intrusive().protobuf_regulation().rules();
+    let Some(index) = rules.iter().position(|rule| *rule.id() == self.rule_id) else {
+      return DeleteProtobufRuleReturn::NoSuchRule;
+    };
+
+    if let Err(error) = protobuf_rule_db::delete_rule(daemon.database(), &self.rule_id) {
+      daemon.internal_logger().log_error(error);
+      return DeleteProtobufRuleReturn::InternalError;
+    }
+
+    rules.remove(index);
+    DeleteProtobufRuleReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddProtobufFieldName {
+  user_id: UserId,
+  field_name_creator: ProtobufFieldNameCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AddProtobufFieldNameReturn {
+  NoSuchUser { user_id: UserId },
+  InvalidFieldName(ProtobufFieldNameCreatorError),
+  /// The field, or another field of the method's messages, already has
+  /// the name.
+  AlreadyNamed,
+  ReachedMaximumFieldNamesAllowed,
+  DuplicateId,
+  Success,
+  InternalError,
+}
+
+impl AddProtobufFieldName {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveAddProtobufFieldName";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> AddProtobufFieldNameReturn {
+    match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        if !data.users.contains_key(&self.user_id) {
+          return AddProtobufFieldNameReturn::NoSuchUser { user_id: self.user_id };
+        }
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return AddProtobufFieldNameReturn::InternalError;
+      }
+    }
+
+    let field_name = match self.field_name_creator.create(self.user_id) {
+      Ok(field_name) => {
+        field_name
+      }
+      Err(error) => {
+        return AddProtobufFieldNameReturn::InvalidFieldName(error);
+      }
+    };
+
+    let mut field_names = daemon.web_regulation_intrusive().protobuf_regulation().field_names();
+    if field_names.iter().any(|other| other.id() == field_name.id()) {
+      return AddProtobufFieldNameReturn::DuplicateId;
+    }
+
+    let is_already_named = field_names.iter().any(|other| {
+      other.user_id() == self.user_id
+        && other.method() == field_name.method()
+        && other.direction() == field_name.direction()
+        && (other.path() == field_name.path() || other.name() == field_name.name())
+    });
+
+    if is_already_named {
+      return AddProtobufFieldNameReturn::AlreadyNamed;
+    }
+
+    let field_names_of_user = field_names
+      .iter()
+      .filter(|other| other.user_id() == self.user_id)
+      .count();
+
+    if field_names_of_user >= MAXIMUM_PROTOBUF_FIELD_NAMES_PER_USER {
+      return AddProtobufFieldNameReturn::ReachedMaximumFieldNamesAllowed;
+    }
+
+    if let Err(error) = protobuf_field_name_db::add_field_name(daemon.database(), &field_name) {
+      daemon.internal_logger().log_error(error);
+      return AddProtobufFieldNameReturn::InternalError;
+    }
+
+    field_names.push(field_name);
+    AddProtobufFieldNameReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoveProtobufFieldName {
+  field_name_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RemoveProtobufFieldNameReturn {
+  NoSuchFieldName,
+  Success,
+  InternalError,
+}
+
+impl RemoveProtobufFieldName {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveRemoveProtobufFieldName";
+
+  /// Rules created with the name keep the field it named.
+  pub fn execute(self, daemon: Arc<Daemon>) -> RemoveProtobufFieldNameReturn {
+    let mut field_names = daemon.web_regulation_intrusive().protobuf_regulation().field_names();
+    let Some(index) = field_names.iter().position(|field_name| *field_name.id() == self.field_name_id) else {
+      return RemoveProtobufFieldNameReturn::NoSuchFieldName;
+    };
+
+    if let Err(error) = protobuf_field_name_db::delete_field_name(daemon.database(), &self.field_name_id) {
+      daemon.internal_logger().log_error(error);
+      return RemoveProtobufFieldNameReturn::InternalError;
+    }
+
+    field_names.remove(index);
+    RemoveProtobufFieldNameReturn::Success
+  }
+}
+
+/// Lists the fields of the messages of a body, with what each might be and
+/// the names the user gave them, to help writing rules.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InspectProtobufBody {
+  user_id: UserId,
+  /// The path of the request, like `/package.Service/Method`.
+  method: String,
+  direction: ProtobufDirection,
+  content_type: String,
+  /// The body in base64.
+  body: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum InspectProtobufBodyReturn {
+  UnknownContentType,
+  InvalidBase64,
+  Success { messages: Vec<Vec<InspectedProtobufField>> },
+}
+
+impl InspectProtobufBody {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveInspectProtobufBody";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> InspectProtobufBodyReturn {
+    let content_type = self
+      .content_type
+      .split(';')
+      .next()
+      .unwrap_or_default()
+      .trim()
+      .to_ascii_lowercase();
+
+    let Some(framing) = ProtobufFraming::of(&content_type) else {
+      return InspectProtobufBodyReturn::UnknownContentType;
+    };
+
+    let Ok(body) = BASE64_STANDARD.decode(self.body.trim()) else {
+      return InspectProtobufBodyReturn::InvalidBase64;
+    };
+
+    let field_names = daemon.web_regulation_intrusive().protobuf_regulation().field_names().clone();
+    let name_of = |path: &protobuf::FieldPath| {
+      protobuf::name_of(&field_names, self.user_id, &self.method, self.direction, path)
+    };
+
+    let messages = protobuf::wire::messages(framing, &body)
+      .iter()
+      .map(|message| protobuf::wire::inspect(message, &name_of))
+      .collect();
+
+    InspectProtobufBodyReturn::Success { messages }
+  }
+}
+
+/// Sets the measures taken against a managed user's encrypted DNS,
+/// replacing the previous ones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetEncryptedDnsPrevention {
+  user_id: UserId,
+  setting_creator: EncryptedDnsSettingCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SetEncryptedDnsPreventionReturn {
+  NoSuchUser { user_id: UserId },
+  InvalidSetting(EncryptedDnsSettingCreatorError),
+  Success,
+  InternalError,
+}
+
+impl SetEncryptedDnsPrevention {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveSetEncryptedDnsPrevention";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> SetEncryptedDnsPreventionReturn {
+    match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        if !data.users.contains_key(&self.user_id) {
+          return SetEncryptedDnsPreventionReturn::NoSuchUser { user_id: self.user_id };
+        }
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return SetEncryptedDnsPreventionReturn::InternalError;
+      }
+    }
+
+    let setting = match self.setting_creator.create(self.user_id) {
+      Ok(setting) => {
+        setting
+      }
+      Err(error) => {
+        return SetEncryptedDnsPreventionReturn::InvalidSetting(error);
+      }
+    };
+
+    let prevention = daemon.web_regulation_intrusive().encrypted_dns_prevention();
+    let mut settings = prevention.settings();
+    let index = settings.iter().position(|other| other.user_id() == self.user_id);
+    let previous_measures = index
+      .map(|index| settings[index].measures())
+      .unwrap_or(EncryptedDnsMeasures::NONE);
+
+    if let Err(error) = prevention.apply_firewall_rules(self.user_id, setting.measures()) {
+      daemon.internal_logger().log_error(error);
+      if let Err(error) = prevention.apply_firewall_rules(self.user_id, previous_measures) {
+        daemon.internal_logger().log_error(error);
+      }
+      return SetEncryptedDnsPreventionReturn::InternalError;
+    }
+
+    let result = match index {
+      Some(_) => {
+        encrypted_dns_setting_db::update_measures(daemon.database(), &setting)
+      }
+      None => {
+        encrypted_dns_setting_db::add_setting(daemon.database(), &setting)
+      }
+    };
+
+    if let Err(error) = result {
+      daemon.internal_logger().log_error(error);
+      if let Err(error) = prevention.apply_firewall_rules(self.user_id, previous_measures) {
+        daemon.internal_logger().log_error(error);
+      }
+      return SetEncryptedDnsPreventionReturn::InternalError;
+    }
+
+    match index {
+      Some(index) => {
+        settings[index] = setting;
+      }
+      None => {
+        settings.push(setting);
+      }
+    }
+
+    SetEncryptedDnsPreventionReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisableEncryptedDnsPrevention {
+  user_id: UserId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DisableEncryptedDnsPreventionReturn {
+  NotEnabled,
+  Success,
+  InternalError,
+}
+
+impl DisableEncryptedDnsPrevention {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveDisableEncryptedDnsPrevention";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> DisableEncryptedDnsPreventionReturn {
+    let prevention = daemon.web_regulation_intrusive().encrypted_dns_prevention();
+    let mut settings = prevention.settings();
+    let Some(index) = settings.iter().position(|setting| setting.user_id() == self.user_id) else {
+      return DisableEncryptedDnsPreventionReturn::NotEnabled;
+    };
+
+    if let Err(error) = prevention.apply_firewall_rules(self.user_id, EncryptedDnsMeasures::NONE) {
+      daemon.internal_logger().log_error(error);
+      if let Err(error) = prevention.apply_firewall_rules(self.user_id, settings[index].measures()) {
+        daemon.internal_logger().log_error(error);
+      }
+      return DisableEncryptedDnsPreventionReturn::InternalError;
+    }
+
+    if let Err(error) = encrypted_dns_setting_db::delete_setting(daemon.database(), self.user_id) {
+      daemon.internal_logger().log_error(error);
+      if let Err(error) = prevention.apply_firewall_rules(self.user_id, settings[index].measures()) {
+        daemon.internal_logger().log_error(error);
+      }
+      return DisableEncryptedDnsPreventionReturn::InternalError;
+    }
+
+    settings.remove(index);
+    DisableEncryptedDnsPreventionReturn::Success
+  }
+}
+
+/// Opts a managed user into having their navigations recorded, or changes
+/// how they're recorded while the setting isn't protected. Switching to
+/// domains only forgets the paths already recorded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetAuditLog {
+  user_id: UserId,
+  setting_creator: AuditLogSettingCreator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SetAuditLogReturn {
+  NoSuchUser { user_id: UserId },
+  InvalidSetting(AuditLogSettingCreatorError),
+  IsProtected,
+  Success,
+  InternalError,
+}
+
+impl SetAuditLog {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveSetAuditLog";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> SetAuditLogReturn {
+    match daemon.operating_system_integration().lock_data() {
+      Ok(data) => {
+        if !data.users.contains_key(&self.user_id) {
+          return SetAuditLogReturn::NoSuchUser { user_id: self.user_id };
+        }
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return SetAuditLogReturn::InternalError;
+      }
+    }
+
+    let now = DateTime::now();
+    let setting = match self.setting_creator.create(self.user_id, now) {
+      Ok(setting) => {
+        setting
+      }
+      Err(error) => {
+        return SetAuditLogReturn::InvalidSetting(error);
+      }
+    };
+
+    let audit_log = daemon.web_regulation_intrusive().audit_log();
+    let mut settings = audit_log.settings();
+    let index = settings.iter().position(|other| other.user_id() == self.user_id);
+    if index.is_some_and(|index| settings[index].is_protected(now)) {
+      return SetAuditLogReturn::IsProtected;
+    }
+
+    let result = match index {
+      Some(_) => {
+        audit_log_db::update_setting(daemon.database(), &setting)
+      }
+      None => {
+        audit_log_db::add_setting(daemon.database(), &setting)
+      }
+    };
+
+    if let Err(error) = result {
+      daemon.internal_logger().log_error(error);
+      return SetAuditLogReturn::InternalError;
+    }
+
+    if setting.privacy() == AuditLogPrivacy::DomainsOnly {
+      if let Err(error) = audit_log_db::delete_entry_paths(daemon.database(), self.user_id) {
+        daemon.internal_logger().log_error(error);
+        return SetAuditLogReturn::InternalError;
+      }
+    }
+
+    match index {
+      Some(index) => {
+        settings[index] = setting;
+      }
+      None => {
+        settings.push(setting);
+      }
+    }
+
+    SetAuditLogReturn::Success
+  }
+}
+
+/// Stops recording a managed user's navigations once the setting isn't
+/// protected. The entries already recorded are kept until cleared.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisableAuditLog {
+  user_id: UserId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DisableAuditLogReturn {
+  NotEnabled,
+  IsProtected,
+  Success,
+  InternalError,
+}
+
+impl DisableAuditLog {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveDisableAuditLog";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> DisableAuditLogReturn {
+    let audit_log = daemon.web_regulation_intrusive().audit_log();
+    let mut settings = audit_log.settings();
+    let Some(index) = settings.iter().position(|setting| setting.user_id() == self.user_id) else {
+      return DisableAuditLogReturn::NotEnabled;
+    };
+
+    if settings[index].is_protected(DateTime::now()) {
+      return DisableAuditLogReturn::IsProtected;
+    }
+
+    if let Err(error) = audit_log_db::delete_setting(daemon.database(), self.user_id) {
+      daemon.internal_logger().log_error(error);
+      return DisableAuditLogReturn::InternalError;
+    }
+
+    settings.remove(index);
+    DisableAuditLogReturn::Success
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncreaseAuditLogProtection {
+  user_id: UserId,
+  increment: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum IncreaseAuditLogProtectionReturn {
+  NotEnabled,
+  WouldBeEffectiveForTooLong,
+  Success,
+  InternalError,
+}
+
+impl IncreaseAuditLogProtection {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveIncreaseAuditLogProtection";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> IncreaseAuditLogProtectionReturn {
+    let audit_log = daemon.web_regulation_intrusive().audit_log();
+    let mut settings = audit_log.settings();
+    let Some(setting) = settings.iter_mut().find(|setting| setting.user_id() == self.user_id) else {
+      return IncreaseAuditLogProtectionReturn::NotEnabled;
+    };
+
+    let now = DateTime::now();
+    setting.synchronize(now);
+
+    let Some(new_remaining_duration) = setting
+      .protector()
+      .remaining_duration()
+      .checked_add(&self.increment) else
+    {
+      return IncreaseAuditLogProtectionReturn::WouldBeEffectiveForTooLong;
+    };
+
+    if new_remaining_duration > MAXIMUM_AUDIT_LOG_PROTECTION_DURATION {
+      return IncreaseAuditLogProtectionReturn::WouldBeEffectiveForTooLong;
+    }
+
+    let mut protected = setting.clone();
+    protected.protect_for(new_remaining_duration, now);
+
+    if let Err(error) = audit_log_db::update_protector(daemon.database(), &protected) {
+      daemon.internal_logger().log_error(error);
+      return IncreaseAuditLogProtectionReturn::InternalError;
+    }
+
+    *setting = protected;
+    IncreaseAuditLogProtectionReturn::Success
+  }
+}
+
+/// Lists the recorded navigations of a user the query matches, oldest
+/// first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryAuditLog {
+  query: AuditLogQuery,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum QueryAuditLogReturn {
+  Success(Vec<AuditLogEntry>),
+  InternalError,
+}
+
+impl QueryAuditLog {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveQueryAuditLog";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> QueryAuditLogReturn {
+    match audit_log_db::retrieve_entries(daemon.database(), &self.query) {
+      Ok(entries) => {
+        QueryAuditLogReturn::Success(entries)
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        QueryAuditLogReturn::InternalError
+      }
+    }
+  }
+}
+
+/// Exports the recorded navigations of a user the query matches as a csv
+/// or json document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportAuditLog {
+  query: AuditLogQuery,
+  format: AuditLogExportFormat,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ExportAuditLogReturn {
+  Success(String),
+  InternalError,
+}
+
+impl ExportAuditLog {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveExportAuditLog";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> ExportAuditLogReturn {
+    let entries = match audit_log_db::retrieve_entries(daemon.database(), &self.query) {
+      Ok(entries) => {
+        entries
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        return ExportAuditLogReturn::InternalError;
+      }
+    };
+
+    match self.format.export(&entries) {
+      Ok(document) => {
+        ExportAuditLogReturn::Success(document)
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        ExportAuditLogReturn::InternalError
+      }
+    }
+  }
+}
+
+/// Deletes the recorded navigations of a user whose navigations are no
+/// longer recorded, so a protected setting can't be worked around.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClearAuditLog {
+  user_id: UserId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ClearAuditLogReturn {
+  IsEnabled,
+  IsProtected,
+  Success,
+  InternalError,
+}
+
+impl ClearAuditLog {
+  pub const HUMAN_READABLE_ID: &'static str = "WebRegulationIntrusiveClearAuditLog";
+
+  pub fn execute(self, daemon: Arc<Daemon>) -> ClearAuditLogReturn {
+    let audit_log = daemon.web_regulation_intrusive().audit_log();
+    // Held until the entries are deleted, so the setting can't be enabled
+    // in between.
+    let mut settings = audit_log.settings();
+    if let Some(setting) = settings.iter_mut().find(|setting| setting.user_id() == self.user_id) {
+      if setting.is_protected(DateTime::now()) {
+        return ClearAuditLogReturn::IsProtected;
+      }
+
+      return ClearAuditLogReturn::IsEnabled;
+    }
+
+    match audit_log_db::delete_entries(daemon.database(), self.user_id) {
+      Ok(()) => {
+        ClearAuditLogReturn::Success
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        ClearAuditLogReturn::InternalError
+      }
+    }
+  }
+}
@@ -2,6 +2,21 @@ pub mod internal {
   pub use super::implementations::*;
 }
 
+pub mod block_pages {
+  pub use super::api::{
+    block_page_server::launch_thread,
+    BlockReason,
+    BLOCK_PAGE_PATH,
+    BlockPage,
+    BlockCause,
+    Resumption,
+    ActivatorDescription,
+    WebRuleFeature,
+    Language,
+    Text,
+  };
+}
+
 mod api;
 pub use api::IntoPublic;
 
@@ -0,0 +1,34 @@
+use std::env;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::process::ExitCode;
+use discipline_daemon_lib::browser_extension::native_host;
+
+/// The port of the daemon's api, unless the "DISCIPLINE_API_TCP_PORT"
+/// environment variable says otherwise. Browsers run the host without
+/// arguments of ours, so it can't be told on the command line.
+const DEFAULT_API_TCP_PORT: u16 = 9118;
+
+fn main() -> ExitCode {
+  let api_tcp_port = env::var("DISCIPLINE_API_TCP_PORT")
+    .ok()
+    .and_then(|port| port.parse().ok())
+    .unwrap_or(DEFAULT_API_TCP_PORT);
+
+  let result = native_host::run(
+    &mut io::stdin().lock(),
+    &mut io::stdout().lock(),
+    SocketAddr::new(Ipv4Addr::LOCALHOST.into(), api_tcp_port),
+  );
+
+  match result {
+    Ok(()) => {
+      ExitCode::SUCCESS
+    }
+    Err(error) => {
+      // Browsers show what hosts write to stderr in their console.
+      eprintln!("discipline-native-host: {error}");
+      ExitCode::FAILURE
+    }
+  }
+}
@@ -66,14 +66,21 @@ impl CountdownTimer {
     self.remaining_duration = new_value;
   }
 
+  /// When the timer finishes, or finished, assuming it keeps running.
+  pub fn finish_time(&self) -> Option<DateTime> {
+    self.previous_synchronization_time.checked_add(&self.remaining_duration)
+  }
+
   pub fn synchronize(&mut self, now: DateTime) {
     let interval = now
       .since_or_zero(&self.previous_synchronization_time);
 
-    if let Some(remaining_duration) = self.remaining_duration.checked_sub(&interval) {
-      self.remaining_duration = remaining_duration;
-      self.previous_synchronization_time = now;
-    }
+    self.remaining_duration = self
+      .remaining_duration
+      .checked_sub(&interval)
+      .unwrap_or(Duration::ZERO);
+
+    self.previous_synchronization_time = now;
   }
 
   // pub fn synchronize_and_write_updates(&mut self, now: DateTime) {
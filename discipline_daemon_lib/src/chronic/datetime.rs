@@ -84,6 +84,12 @@ impl DateTime {
     )
   }
 
+  pub fn next_midnight(&self) -> Option<DateTime> {
+    Duration::unchecked_from_days(1)
+      .checked_sub(&self.duration_since_midnight())
+      .and_then(|remaining| self.checked_add(&remaining))
+  }
+
   pub fn duration_since_midnight(&self) -> Duration {
     self.since(&self.midnight()).unwrap()
   }
@@ -96,6 +102,12 @@ impl DateTime {
       .map(|timestamp| Duration::from_milliseconds(timestamp as u64))
   }
 
+  pub fn checked_add(&self, duration: &Duration) -> Option<DateTime> {
+    self.0
+      .checked_add_signed(duration.to_chrono()?)
+      .map(DateTime)
+  }
+
   pub fn since_or_zero(&self, other: &DateTime) -> Duration {
     match self.timestamp().checked_sub(other.timestamp()) {
       None => Duration::from_milliseconds(0),
@@ -104,6 +116,12 @@ impl DateTime {
     }
   }
 
+  /// Returns the date in RFC 3339 format with milliseconds, like this:
+  /// 2025-03-19T08:00:00.000Z
+  pub fn to_rfc_3339(&self) -> String {
+    self.0.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+  }
+
   /** 
    * Returns the date in a format like this:
    * 2025-03-19 08:00 AM
@@ -29,4 +29,7 @@ mod time_tracker;
 pub use time_tracker::TimeTracker;
 
 // mod countdown;
-mod daily_device_uptime_tracker;
\ No newline at end of file
+mod daily_device_uptime_tracker;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,31 @@
+use crate::{CountdownTimer, DateTime, Duration};
+
+fn at_minute(minute: i64) -> DateTime {
+  DateTime::from_timestamp(1_700_000_000_000 + minute * 60_000).unwrap()
+}
+
+#[test]
+fn countdown_timer_counts_down_and_runs_out() {
+  let mut timer = CountdownTimer::new(Duration::unchecked_from_minutes(30), at_minute(0));
+
+  timer.synchronize(at_minute(10));
+  assert_eq!(timer.remaining_duration(), Duration::unchecked_from_minutes(20));
+  assert!(timer.is_running());
+
+  timer.synchronize(at_minute(45));
+  assert!(timer.is_finished());
+  assert_eq!(timer.previous_synchronization_time(), at_minute(45));
+
+  timer.reinitialize();
+  timer.synchronize(at_minute(60));
+  assert_eq!(timer.remaining_duration(), Duration::unchecked_from_minutes(15));
+}
+
+#[test]
+fn countdown_timer_first_synchronized_long_after_it_ran_out_is_finished() {
+  let mut timer = CountdownTimer::new(Duration::unchecked_from_minutes(30), at_minute(0));
+
+  timer.synchronize(at_minute(24 * 60));
+  assert!(timer.is_finished());
+  assert_eq!(timer.previous_synchronization_time(), at_minute(24 * 60));
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
-use crate::{time, Duration, GenericError, Time};
+use crate::{time, DateTime, Duration, GenericError, Time};
+use super::duration::MS_PER_MINUTE;
 
 // #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 // pub enum CreateFromNumbersError {
@@ -124,6 +125,21 @@ impl TimeRange {
     self.from <= time && time <= self.till
   }
 
+  /// Returns the first minute after `now` at which `contains_time` stops
+  /// returning true, or `None` if `now` is not within this range.
+  pub fn occurrence_end(&self, now: DateTime) -> Option<DateTime> {
+    if !self.contains_time(now.time()) {
+      return None;
+    }
+
+    let minute = MS_PER_MINUTE as u32;
+    let end = (self.till - self.till % minute + minute).min(MILLISECONDS_PER_DAY);
+    let remaining = Duration::from_milliseconds(end as u64)
+      .checked_sub(&now.duration_since_midnight())?;
+    
+    now.checked_add(&remaining)
+  }
+
   pub fn is_wider_than_or_equal_to(&self, other: &TimeRange) -> bool {
     self.from <= other.from
     &&
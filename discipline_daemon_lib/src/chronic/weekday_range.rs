@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use crate::{Duration, GenericError, Weekday};
+use crate::{DateTime, Duration, GenericError, Weekday};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CreateFromNumbersError {
@@ -104,6 +104,21 @@ impl WeekdayRange {
     weekday >= self.from && weekday <= self.till
   }
 
+  /// Returns the midnight at which `contains_weekday` stops returning true
+  /// for the days following `now`, or `None` if `now` is not within this range.
+  pub fn occurrence_end(&self, now: DateTime) -> Option<DateTime> {
+    let weekday = now.weekday().days_since_sunday();
+    if !self.contains_weekday(now.weekday()) {
+      return None;
+    }
+
+    let days = self.till.min(FROM_MAX_VALUE) - weekday + 1;
+    let remaining = Duration::unchecked_from_days_u32(days)
+      .checked_sub(&now.duration_since_midnight())?;
+
+    now.checked_add(&remaining)
+  }
+
   /// Returns true if this range contains the `other` range.
   pub fn is_wider_than_or_equal_to(&self, other: &WeekdayRange) -> bool {
     self.from <= other.from 
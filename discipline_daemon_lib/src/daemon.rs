@@ -1,24 +1,51 @@
 use clap::{Parser, command};
 use std::fmt::Debug;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
-use crate::{GenericError, InternalErrorLogger};
+use crate::{DateTime, GenericError, InternalErrorLogger};
 use crate::database::Database;
 use crate::operating_system_integration::OperatingSystemIntegration;
+use crate::web_regulation_intrusive;
+use crate::circumvention_detection::CircumventionDetection;
+use crate::browser_policy::BrowserPolicy;
+use crate::browser_extension::BrowserExtension;
 
 pub struct Configuration {
   database_directory_path: PathBuf,
   api_tcp_port: u16,
+  web_regulation_intrusive_proxy_port: u16,
+  web_regulation_intrusive_dns_forwarder_port: u16,
+  block_page_port: u16,
+  proxy_auto_config_proxy_address: Option<SocketAddr>,
+  native_messaging_host_path: PathBuf,
+  chromium_extension_ids: Vec<String>,
+  firefox_extension_ids: Vec<String>,
 }
 
 impl Configuration {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     database_directory_path: PathBuf,
     api_tcp_port: u16,
+    web_regulation_intrusive_proxy_port: u16,
+    web_regulation_intrusive_dns_forwarder_port: u16,
+    block_page_port: u16,
+    proxy_auto_config_proxy_address: Option<SocketAddr>,
+    native_messaging_host_path: PathBuf,
+    chromium_extension_ids: Vec<String>,
+    firefox_extension_ids: Vec<String>,
   ) -> Self {
     Self {
       database_directory_path,
       api_tcp_port,
+      web_regulation_intrusive_proxy_port,
+      web_regulation_intrusive_dns_forwarder_port,
+      block_page_port,
+      proxy_auto_config_proxy_address,
+      native_messaging_host_path,
+      chromium_extension_ids,
+      firefox_extension_ids,
     }
   }
 
@@ -26,6 +53,43 @@ impl Configuration {
     self.api_tcp_port
   }
 
+  pub fn web_regulation_intrusive_proxy_port(&self) -> u16 {
+    self.web_regulation_intrusive_proxy_port
+  }
+
+  pub fn web_regulation_intrusive_dns_forwarder_port(&self) -> u16 {
+    self.web_regulation_intrusive_dns_forwarder_port
+  }
+
+  pub fn block_page_port(&self) -> u16 {
+    self.block_page_port
+  }
+
+  /// Where the proxy auto-config file sends proxied hosts: our own proxy
+  /// unless another one was configured.
+  pub fn proxy_auto_config_proxy_address(&self) -> SocketAddr {
+    self.proxy_auto_config_proxy_address.unwrap_or(SocketAddr::new(
+      IpAddr::V4(Ipv4Addr::LOCALHOST),
+      self.web_regulation_intrusive_proxy_port,
+    ))
+  }
+
+  pub fn native_messaging_host_path(&self) -> &PathBuf {
+    &self.native_messaging_host_path
+  }
+
+  /// The ids of the extension allowed to run the native messaging host in
+  /// Chromium based browsers.
+  pub fn chromium_extension_ids(&self) -> &Vec<String> {
+    &self.chromium_extension_ids
+  }
+
+  /// The ids of the extension allowed to run the native messaging host in
+  /// Firefox.
+  pub fn firefox_extension_ids(&self) -> &Vec<String> {
+    &self.firefox_extension_ids
+  }
+
   pub fn database_directory_path(&self) -> &PathBuf {
     &self.database_directory_path
   }
@@ -40,6 +104,10 @@ pub struct Daemon {
   configuration: Configuration,
   internal_error_logger: InternalErrorLogger,
   operating_system_integration: OperatingSystemIntegration,
+  web_regulation_intrusive: web_regulation_intrusive::Proxy,
+  circumvention_detection: CircumventionDetection,
+  browser_policy: BrowserPolicy,
+  browser_extension: BrowserExtension,
 }
 
 impl Daemon {
@@ -52,6 +120,23 @@ impl Daemon {
 
     let operating_system_integration = OperatingSystemIntegration::open(&database)?;
 
+    let web_regulation_intrusive = web_regulation_intrusive::Proxy::open(
+      &database,
+      configuration.database_directory_path(),
+      configuration.web_regulation_intrusive_proxy_port(),
+      configuration.web_regulation_intrusive_dns_forwarder_port(),
+    ).map_err(|error|
+      error.change_context("creating daemon")
+    )?;
+
+    let circumvention_detection = CircumventionDetection::open(&database).map_err(|error|
+      error.change_context("creating daemon")
+    )?;
+
+    let browser_policy = BrowserPolicy::open(&database).map_err(|error|
+      error.change_context("creating daemon")
+    )?;
+
     // TODO: Run operating_system_integration and api server.
 
     Ok(Daemon {
@@ -60,6 +145,10 @@ impl Daemon {
       configuration,
       internal_error_logger: InternalErrorLogger::new(),
       operating_system_integration,
+      web_regulation_intrusive,
+      circumvention_detection,
+      browser_policy,
+      browser_extension: BrowserExtension::new(DateTime::now()),
     })
   }
 
@@ -75,6 +164,22 @@ impl Daemon {
     &self.operating_system_integration
   }
 
+  pub fn web_regulation_intrusive(&self) -> &web_regulation_intrusive::Proxy {
+    &self.web_regulation_intrusive
+  }
+
+  pub fn circumvention_detection(&self) -> &CircumventionDetection {
+    &self.circumvention_detection
+  }
+
+  pub fn browser_policy(&self) -> &BrowserPolicy {
+    &self.browser_policy
+  }
+
+  pub fn browser_extension(&self) -> &BrowserExtension {
+    &self.browser_extension
+  }
+
   pub fn internal_logger(&self) -> InternalErrorLogger {
     self.internal_error_logger
   }
@@ -94,6 +199,37 @@ struct CommandLineArguments {
 
   /// Age of the Supreme Emperor (optional)
   api_tcp_port: u16,
+
+  /// The port the web regulation proxy listens on.
+  #[arg(long, default_value_t = 9119)]
+  web_regulation_intrusive_proxy_port: u16,
+
+  /// The port the DNS forwarder of managed users listens on.
+  #[arg(long, default_value_t = 9153)]
+  web_regulation_intrusive_dns_forwarder_port: u16,
+
+  /// The port the block page server listens on.
+  #[arg(long, default_value_t = 9120)]
+  block_page_port: u16,
+
+  /// The proxy the proxy auto-config file sends proxied hosts to. Defaults
+  /// to the web regulation proxy.
+  #[arg(long)]
+  proxy_auto_config_proxy_address: Option<SocketAddr>,
+
+  /// The native messaging host the browser extension runs.
+  #[arg(long, default_value = "/usr/bin/discipline-native-host")]
+  native_messaging_host_path: PathBuf,
+
+  /// The id of the browser extension in Chromium based browsers. May be
+  /// given more than once.
+  #[arg(long)]
+  chromium_extension_id: Vec<String>,
+
+  /// The id of the browser extension in Firefox. May be given more than
+  /// once.
+  #[arg(long)]
+  firefox_extension_id: Vec<String>,
 }
 
 impl Daemon {
@@ -110,6 +246,13 @@ impl Daemon {
     let configuration = Configuration::new(
       arguments.database_directory_path, 
       arguments.api_tcp_port,
+      arguments.web_regulation_intrusive_proxy_port,
+      arguments.web_regulation_intrusive_dns_forwarder_port,
+      arguments.block_page_port,
+      arguments.proxy_auto_config_proxy_address,
+      arguments.native_messaging_host_path,
+      arguments.chromium_extension_id,
+      arguments.firefox_extension_id,
     );
 
     Daemon::open_with_configuration(configuration)
@@ -7,4 +7,24 @@ pub use v3::{
   screen_access_regulation_rule,
   internet_access_regulation_policy,
   internet_access_regulation_rule,
+  web_regulation_intrusive_no_intercept_host,
+  web_regulation_intrusive_website_visits_limiter,
+  web_regulation_intrusive_website_visit_delayer,
+  web_regulation_intrusive_youtube_rule,
+  web_regulation_intrusive_twitter_filter,
+  web_regulation_intrusive_media_type_rule,
+  web_regulation_intrusive_element_rule_sheet,
+  web_regulation_intrusive_content_filter_rule,
+  web_regulation_intrusive_safe_search_setting,
+  web_regulation_intrusive_search_query_rule,
+  web_regulation_intrusive_view_time_allowance,
+  web_regulation_intrusive_image_rule,
+  web_regulation_intrusive_image_classifier,
+  web_regulation_intrusive_protobuf_field_name,
+  web_regulation_intrusive_encrypted_dns_setting,
+  circumvention_detection,
+  web_regulation_intrusive_protobuf_rule,
+  web_regulation_intrusive_audit_log,
+  web_regulation_intrusive_proxied_host,
+  browser_policy_setting,
 };
\ No newline at end of file
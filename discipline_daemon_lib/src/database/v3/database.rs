@@ -26,6 +26,66 @@ pub struct Database {
   pub internet_access_regulation_policy: implementation
     ::internet_access_regulation_policy
     ::PolicyCollection,
+  pub web_regulation_intrusive_no_intercept_host: implementation
+    ::web_regulation_intrusive_no_intercept_host
+    ::HostPatternCollection,
+  pub web_regulation_intrusive_website_visits_limiter: implementation
+    ::web_regulation_intrusive_website_visits_limiter
+    ::LimiterCollection,
+  pub web_regulation_intrusive_website_visit_delayer: implementation
+    ::web_regulation_intrusive_website_visit_delayer
+    ::DelayerCollection,
+  pub web_regulation_intrusive_youtube_rule: implementation
+    ::web_regulation_intrusive_youtube_rule
+    ::RuleCollection,
+  pub web_regulation_intrusive_twitter_filter: implementation
+    ::web_regulation_intrusive_twitter_filter
+    ::FilterCollection,
+  pub web_regulation_intrusive_media_type_rule: implementation
+    ::web_regulation_intrusive_media_type_rule
+    ::RuleCollection,
+  pub web_regulation_intrusive_element_rule_sheet: implementation
+    ::web_regulation_intrusive_element_rule_sheet
+    ::SheetCollection,
+  pub web_regulation_intrusive_content_filter_rule: implementation
+    ::web_regulation_intrusive_content_filter_rule
+    ::RuleCollection,
+  pub web_regulation_intrusive_safe_search_setting: implementation
+    ::web_regulation_intrusive_safe_search_setting
+    ::SettingCollection,
+  pub web_regulation_intrusive_search_query_rule: implementation
+    ::web_regulation_intrusive_search_query_rule
+    ::RuleCollection,
+  pub web_regulation_intrusive_view_time_allowance: implementation
+    ::web_regulation_intrusive_view_time_allowance
+    ::AllowanceCollection,
+  pub web_regulation_intrusive_image_rule: implementation
+    ::web_regulation_intrusive_image_rule
+    ::RuleCollection,
+  pub web_regulation_intrusive_image_classifier: implementation
+    ::web_regulation_intrusive_image_classifier
+    ::ModelCollection,
+  pub web_regulation_intrusive_protobuf_field_name: implementation
+    ::web_regulation_intrusive_protobuf_field_name
+    ::FieldNameCollection,
+  pub web_regulation_intrusive_encrypted_dns_setting: implementation
+    ::web_regulation_intrusive_encrypted_dns_setting
+    ::SettingCollection,
+  pub circumvention_detection: implementation
+    ::circumvention_detection
+    ::SettingCollection,
+  pub web_regulation_intrusive_protobuf_rule: implementation
+    ::web_regulation_intrusive_protobuf_rule
+    ::RuleCollection,
+  pub web_regulation_intrusive_audit_log: implementation
+    ::web_regulation_intrusive_audit_log
+    ::SettingCollection,
+  pub web_regulation_intrusive_proxied_host: implementation
+    ::web_regulation_intrusive_proxied_host
+    ::ProxiedHostCollection,
+  pub browser_policy_setting: implementation
+    ::browser_policy_setting
+    ::SettingCollection,
 }
 
 impl Database {
@@ -84,6 +144,115 @@ impl Database {
         ::internet_access_regulation_rule
         ::RuleCollection
         ::new("InternetAccessRegulationRules".into()),
+
+      web_regulation_intrusive_no_intercept_host: implementation
+        ::web_regulation_intrusive_no_intercept_host
+        ::HostPatternCollection
+        ::new("WebRegulationIntrusiveNoInterceptHosts".into()),
+
+      web_regulation_intrusive_website_visits_limiter: implementation
+        ::web_regulation_intrusive_website_visits_limiter
+        ::LimiterCollection
+        ::new(
+          "WebRegulationIntrusiveWebsiteVisitsLimiters".into(),
+          "WebRegulationIntrusiveWebsiteVisits".into(),
+        ),
+
+      web_regulation_intrusive_website_visit_delayer: implementation
+        ::web_regulation_intrusive_website_visit_delayer
+        ::DelayerCollection
+        ::new("WebRegulationIntrusiveWebsiteVisitDelayers".into()),
+
+      web_regulation_intrusive_youtube_rule: implementation
+        ::web_regulation_intrusive_youtube_rule
+        ::RuleCollection
+        ::new("WebRegulationIntrusiveYoutubeRules".into()),
+
+      web_regulation_intrusive_twitter_filter: implementation
+        ::web_regulation_intrusive_twitter_filter
+        ::FilterCollection
+        ::new("WebRegulationIntrusiveTwitterFilters".into()),
+
+      web_regulation_intrusive_media_type_rule: implementation
+        ::web_regulation_intrusive_media_type_rule
+        ::RuleCollection
+        ::new("WebRegulationIntrusiveMediaTypeRules".into()),
+
+      web_regulation_intrusive_element_rule_sheet: implementation
+        ::web_regulation_intrusive_element_rule_sheet
+        ::SheetCollection
+        ::new("WebRegulationIntrusiveElementRuleSheets".into()),
+
+      web_regulation_intrusive_content_filter_rule: implementation
+        ::web_regulation_intrusive_content_filter_rule
+        ::RuleCollection
+        ::new("WebRegulationIntrusiveContentFilterRules".into()),
+
+      web_regulation_intrusive_safe_search_setting: implementation
+        ::web_regulation_intrusive_safe_search_setting
+        ::SettingCollection
+        ::new("WebRegulationIntrusiveSafeSearchSettings".into()),
+
+      web_regulation_intrusive_search_query_rule: implementation
+        ::web_regulation_intrusive_search_query_rule
+        ::RuleCollection
+        ::new("WebRegulationIntrusiveSearchQueryRules".into()),
+
+      web_regulation_intrusive_view_time_allowance: implementation
+        ::web_regulation_intrusive_view_time_allowance
+        ::AllowanceCollection
+        ::new("WebRegulationIntrusiveViewTimeAllowances".into()),
+
+      web_regulation_intrusive_image_rule: implementation
+        ::web_regulation_intrusive_image_rule
+        ::RuleCollection
+        ::new("WebRegulationIntrusiveImageRules".into()),
+
+      web_regulation_intrusive_image_classifier: implementation
+        ::web_regulation_intrusive_image_classifier
+        ::ModelCollection
+        ::new("WebRegulationIntrusiveImageClassifiers".into()),
+
+      web_regulation_intrusive_protobuf_field_name: implementation
+        ::web_regulation_intrusive_protobuf_field_name
+        ::FieldNameCollection
+        ::new("WebRegulationIntrusiveProtobufFieldNames".into()),
+
+      web_regulation_intrusive_encrypted_dns_setting: implementation
+        ::web_regulation_intrusive_encrypted_dns_setting
+        ::SettingCollection
+        ::new("WebRegulationIntrusiveEncryptedDnsSettings".into()),
+
+      circumvention_detection: implementation
+        ::circumvention_detection
+        ::SettingCollection
+        ::new(
+          "CircumventionDetectionSettings".into(),
+          "CircumventionDetectionEvents".into(),
+        ),
+
+      web_regulation_intrusive_protobuf_rule: implementation
+        ::web_regulation_intrusive_protobuf_rule
+        ::RuleCollection
+        ::new("WebRegulationIntrusiveProtobufRules".into()),
+
+      web_regulation_intrusive_audit_log: implementation
+        ::web_regulation_intrusive_audit_log
+        ::SettingCollection
+        ::new(
+          "WebRegulationIntrusiveAuditLogSettings".into(),
+          "WebRegulationIntrusiveAuditLogEntries".into(),
+        ),
+
+      web_regulation_intrusive_proxied_host: implementation
+        ::web_regulation_intrusive_proxied_host
+        ::ProxiedHostCollection
+        ::new("WebRegulationIntrusiveProxiedHosts".into()),
+
+      browser_policy_setting: implementation
+        ::browser_policy_setting
+        ::SettingCollection
+        ::new("BrowserPolicySettings".into()),
     };
 
     let mut definitions = DatabaseCode::new();
@@ -111,6 +280,86 @@ impl Database {
       ::internet_access_regulation_rule
       ::write_define(&database, &mut definitions);
 
+    implementation
+      ::web_regulation_intrusive_no_intercept_host
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::web_regulation_intrusive_website_visits_limiter
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::web_regulation_intrusive_website_visit_delayer
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::web_regulation_intrusive_youtube_rule
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::web_regulation_intrusive_twitter_filter
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::web_regulation_intrusive_media_type_rule
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::web_regulation_intrusive_element_rule_sheet
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::web_regulation_intrusive_content_filter_rule
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::web_regulation_intrusive_safe_search_setting
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::web_regulation_intrusive_search_query_rule
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::web_regulation_intrusive_view_time_allowance
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::web_regulation_intrusive_image_rule
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::web_regulation_intrusive_image_classifier
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::web_regulation_intrusive_protobuf_field_name
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::web_regulation_intrusive_encrypted_dns_setting
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::circumvention_detection
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::web_regulation_intrusive_protobuf_rule
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::web_regulation_intrusive_audit_log
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::web_regulation_intrusive_proxied_host
+      ::write_define(&database, &mut definitions);
+
+    implementation
+      ::browser_policy_setting
+      ::write_define(&database, &mut definitions);
+
     database.execute(definitions.as_str())?;
 
     Ok(database)
@@ -0,0 +1,207 @@
+use std::path::PathBuf;
+use crate::browser_policy::{Extension, Setting};
+use crate::*;
+use super::*;
+
+/// The device has a single setting, stored in a single row with this id.
+static ID_FIELD_VALUE: u8 = 0;
+
+/// Lists are stored one item per line, as items can't have line breaks.
+fn serialize_certificate_paths(paths: &[PathBuf]) -> String {
+  paths
+    .iter()
+    .map(|path| path.to_string_lossy())
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+fn deserialize_certificate_paths(paths: String) -> Vec<PathBuf> {
+  paths
+    .lines()
+    .filter(|path| !path.is_empty())
+    .map(PathBuf::from)
+    .collect()
+}
+
+/// Extensions are stored as an id and a source separated by a semicolon,
+/// which neither can contain.
+fn serialize_extensions(extensions: &[Extension]) -> String {
+  extensions
+    .iter()
+    .map(|extension| format!("{};{}", extension.id, extension.source))
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+fn deserialize_extensions(extensions: String) -> Result<Vec<Extension>, GenericError> {
+  extensions
+    .lines()
+    .filter(|extension| !extension.is_empty())
+    .map(|extension| {
+      let Some((id, source)) = extension.split_once(';') else {
+        return Err(
+          GenericError::new("deserializing a browser policy Extension")
+            .add_error("missing the semicolon separating the id and source")
+            .add_attachment("extension", extension)
+        );
+      };
+
+      Ok(Extension {
+        id: id.into(),
+        source: source.into(),
+      })
+    })
+    .collect()
+}
+
+pub struct SettingFields {
+  id: String,
+  disable_private_browsing: String,
+  block_about_config: String,
+  force_safe_search: String,
+  disable_dns_over_https: String,
+  certificate_paths: String,
+  firefox_extensions: String,
+  chromium_extensions: String,
+}
+
+pub struct SettingCollection {
+  name: String,
+  fields: SettingFields,
+}
+
+impl SettingCollection {
+  pub fn new(collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: SettingFields {
+        id: "Id".into(),
+        disable_private_browsing: "DisablePrivateBrowsing".into(),
+        block_about_config: "BlockAboutConfig".into(),
+        force_safe_search: "ForceSafeSearch".into(),
+        disable_dns_over_https: "DisableDnsOverHttps".into(),
+        certificate_paths: "CertificatePaths".into(),
+        firefox_extensions: "FirefoxExtensions".into(),
+        chromium_extensions: "ChromiumExtensions".into(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &SettingCollection {
+  &database.browser_policy_setting
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.id);
+  code.write(" INTEGER PRIMARY KEY, ");
+  code.write(&collection.fields.disable_private_browsing);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.block_about_config);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.force_safe_search);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.disable_dns_over_https);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.certificate_paths);
+  code.write(" TEXT NOT NULL, ");
+  code.write(&collection.fields.firefox_extensions);
+  code.write(" TEXT NOT NULL, ");
+  code.write(&collection.fields.chromium_extensions);
+  code.write(" TEXT NOT NULL) WITHOUT ROWID;");
+}
+
+fn deserialize_setting(context: &DeserializeCompoundValueContext, fields: &SettingFields) -> Result<Setting, GenericError> {
+  Ok(Setting::from_fields(
+    context.deserializable_scalar(&fields.disable_private_browsing)?,
+    context.deserializable_scalar(&fields.block_about_config)?,
+    context.deserializable_scalar(&fields.force_safe_search)?,
+    context.deserializable_scalar(&fields.disable_dns_over_https)?,
+    deserialize_certificate_paths(context.deserializable_scalar(&fields.certificate_paths)?),
+    deserialize_extensions(context.deserializable_scalar(&fields.firefox_extensions)?)?,
+    deserialize_extensions(context.deserializable_scalar(&fields.chromium_extensions)?)?,
+  ))
+}
+
+/// Stores the device's setting, replacing the one stored before.
+pub fn set_setting(database: &Database, setting: &Setting) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+
+  let mut context = SerializeCompoundValueContext::new();
+  context.write_u8(&fields.id, ID_FIELD_VALUE);
+  context.write_scalar(&fields.disable_private_browsing, &setting.disable_private_browsing());
+  context.write_scalar(&fields.block_about_config, &setting.block_about_config());
+  context.write_scalar(&fields.force_safe_search, &setting.force_safe_search());
+  context.write_scalar(&fields.disable_dns_over_https, &setting.disable_dns_over_https());
+  context.write_scalar(&fields.certificate_paths, &serialize_certificate_paths(setting.certificate_paths()));
+  context.write_scalar(&fields.firefox_extensions, &serialize_extensions(setting.firefox_extensions()));
+  context.write_scalar(&fields.chromium_extensions, &serialize_extensions(setting.chromium_extensions()));
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT OR REPLACE INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_setting(database: &Database) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn retrieve_setting(database: &Database) -> Result<Option<Setting>, GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.id);
+  code.write(" = ");
+  serialize_scalar_value_into(&ID_FIELD_VALUE, code.as_mut());
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving the browser policy setting")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving the browser policy setting")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let item = iterator.next().map_err(|error|
+    GenericError::new("retrieving the browser policy setting")
+      .add_error("retreiving the next item of sqlite iterator")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let Some(item) = item else {
+    return Ok(None);
+  };
+
+  let context = DeserializeCompoundValueContext(item);
+  deserialize_setting(&context, &collection.fields).map(Some)
+}
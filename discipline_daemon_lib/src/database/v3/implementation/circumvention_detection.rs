@@ -0,0 +1,460 @@
+use crate::circumvention_detection::{Circumvention, Detection, DetectionKind, Event, Response, Setting};
+use crate::operating_system_integration::UserId;
+use crate::*;
+use super::*;
+
+impl SerializableScalarValue for Circumvention {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    match self {
+      Circumvention::Tor => context.write_u8(0),
+      Circumvention::Vpn => context.write_u8(1),
+      Circumvention::Proxy => context.write_u8(2),
+    }
+  }
+}
+
+impl DeserializableScalarValue for Circumvention {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing a Circumvention"))?;
+
+    match number {
+      0 => Ok(Circumvention::Tor),
+      1 => Ok(Circumvention::Vpn),
+      2 => Ok(Circumvention::Proxy),
+      _ => {
+        Err(
+          GenericError::new("deserializing a Circumvention")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0, 1 and 2")
+        )
+      }
+    }
+  }
+}
+
+impl SerializableScalarValue for DetectionKind {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    match self {
+      DetectionKind::Interface => context.write_u8(0),
+      DetectionKind::Process => context.write_u8(1),
+      DetectionKind::Connection => context.write_u8(2),
+    }
+  }
+}
+
+impl DeserializableScalarValue for DetectionKind {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing a DetectionKind"))?;
+
+    match number {
+      0 => Ok(DetectionKind::Interface),
+      1 => Ok(DetectionKind::Process),
+      2 => Ok(DetectionKind::Connection),
+      _ => {
+        Err(
+          GenericError::new("deserializing a DetectionKind")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0, 1 and 2")
+        )
+      }
+    }
+  }
+}
+
+/// A response is stored as its variant number and, for violations, the
+/// duration the screen is blocked for.
+pub struct ResponseFields {
+  kind: String,
+  screen_blocking_duration: String,
+}
+
+impl ResponseFields {
+  fn new() -> Self {
+    Self {
+      kind: "Response".into(),
+      screen_blocking_duration: "ScreenBlockingDuration".into(),
+    }
+  }
+
+  fn write_define(&self, code: &mut DatabaseCode) {
+    code.write(&self.kind);
+    code.write(" INTEGER NOT NULL, ");
+    code.write(&self.screen_blocking_duration);
+    code.write(" INTEGER");
+  }
+
+  fn serialize(&self, context: &mut SerializeCompoundValueContext, response: Response) {
+    match response {
+      Response::Log => {
+        context.write_u8(&self.kind, 0);
+        context.write_scalar(&self.screen_blocking_duration, &None::<Duration>);
+      }
+      Response::Block => {
+        context.write_u8(&self.kind, 1);
+        context.write_scalar(&self.screen_blocking_duration, &None::<Duration>);
+      }
+      Response::Violation { screen_blocking_duration } => {
+        context.write_u8(&self.kind, 2);
+        context.write_scalar(&self.screen_blocking_duration, &Some(screen_blocking_duration));
+      }
+    }
+  }
+
+  fn deserialize(&self, context: &DeserializeCompoundValueContext) -> Result<Response, GenericError> {
+    let number: u8 = context.deserializable_scalar(&self.kind)?;
+    match number {
+      0 => Ok(Response::Log),
+      1 => Ok(Response::Block),
+      2 => {
+        Ok(Response::Violation {
+          screen_blocking_duration: context.deserializable_scalar(&self.screen_blocking_duration)?,
+        })
+      }
+      _ => {
+        Err(
+          GenericError::new("deserializing a circumvention Response")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0, 1 and 2")
+        )
+      }
+    }
+  }
+}
+
+pub struct SettingFields {
+  user_id: String,
+  response: ResponseFields,
+  penalty_duration: String,
+  penalty_remaining_duration: String,
+  penalty_previous_synchronization_time: String,
+}
+
+pub struct EventFields {
+  id: String,
+  user_id: String,
+  time: String,
+  circumvention: String,
+  detection_kind: String,
+  detection_subject: String,
+  response: ResponseFields,
+}
+
+/// Settings live in one table and the events detected for them in another,
+/// so recording an event is a single insert.
+pub struct SettingCollection {
+  name: String,
+  fields: SettingFields,
+  events_name: String,
+  event_fields: EventFields,
+}
+
+impl SettingCollection {
+  pub fn new(collection_name: String, events_collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: SettingFields {
+        user_id: "UserId".into(),
+        response: ResponseFields::new(),
+        penalty_duration: "PenaltyDuration".into(),
+        penalty_remaining_duration: "PenaltyRemainingDuration".into(),
+        penalty_previous_synchronization_time: "PenaltyPreviousSynchronizationTime".into(),
+      },
+      events_name: events_collection_name,
+      event_fields: EventFields {
+        id: "Id".into(),
+        user_id: "UserId".into(),
+        time: "Time".into(),
+        circumvention: "Circumvention".into(),
+        detection_kind: "DetectionKind".into(),
+        detection_subject: "DetectionSubject".into(),
+        response: ResponseFields::new(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &SettingCollection {
+  &database.circumvention_detection
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.user_id);
+  code.write(" INTEGER PRIMARY KEY, ");
+  collection.fields.response.write_define(code);
+  code.write(", ");
+  code.write(&collection.fields.penalty_duration);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.penalty_remaining_duration);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.penalty_previous_synchronization_time);
+  code.write(" INTEGER NOT NULL) WITHOUT ROWID;");
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.events_name);
+  code.write(" (");
+  code.write(&collection.event_fields.id);
+  code.write(" TEXT PRIMARY KEY, ");
+  code.write(&collection.event_fields.user_id);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.event_fields.time);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.event_fields.circumvention);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.event_fields.detection_kind);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.event_fields.detection_subject);
+  code.write(" TEXT NOT NULL, ");
+  collection.event_fields.response.write_define(code);
+  code.write(") WITHOUT ROWID;");
+}
+
+fn serialize_penalty(context: &mut SerializeCompoundValueContext, penalty: &CountdownTimer, fields: &SettingFields) {
+  context.write_scalar(&fields.penalty_duration, &penalty.duration());
+  context.write_scalar(&fields.penalty_remaining_duration, &penalty.remaining_duration());
+  context.write_scalar(&fields.penalty_previous_synchronization_time, &penalty.previous_synchronization_time());
+}
+
+pub fn add_setting(database: &Database, setting: &Setting) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+
+  let mut context = SerializeCompoundValueContext::new();
+  context.write_scalar(&fields.user_id, &setting.user_id());
+  fields.response.serialize(&mut context, setting.response());
+  serialize_penalty(&mut context, setting.penalty(), fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_setting(database: &Database, user_id: UserId) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.user_id);
+  code.write(" = ");
+  serialize_scalar_value_into(&user_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+fn update_setting(
+  database: &Database,
+  user_id: UserId,
+  context: SerializeCompoundValueContext,
+) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("UPDATE ");
+  code.write(&collection.name);
+  code.write(" SET (");
+  code.write(&context.column_names);
+  code.write(") = (");
+  code.write(&context.column_values);
+  code.write(") WHERE ");
+  code.write(&collection.fields.user_id);
+  code.write(" = ");
+  serialize_scalar_value_into(&user_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn update_response(database: &Database, user_id: UserId, response: Response) -> Result<(), GenericError> {
+  let mut context = SerializeCompoundValueContext::new();
+  collection(database).fields.response.serialize(&mut context, response);
+  update_setting(database, user_id, context)
+}
+
+pub fn update_penalty(database: &Database, user_id: UserId, penalty: &CountdownTimer) -> Result<(), GenericError> {
+  let mut context = SerializeCompoundValueContext::new();
+  serialize_penalty(&mut context, penalty, &collection(database).fields);
+  update_setting(database, user_id, context)
+}
+
+pub fn retrieve_all_settings(database: &Database) -> Result<Vec<Setting>, GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all circumvention detection settings")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all circumvention detection settings")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut settings = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all circumvention detection settings")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(settings);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    let penalty = CountdownTimer::from_fields(
+      context.deserializable_scalar(&fields.penalty_duration)?,
+      context.deserializable_scalar(&fields.penalty_remaining_duration)?,
+      context.deserializable_scalar(&fields.penalty_previous_synchronization_time)?,
+    );
+    settings.push(Setting::from_fields(
+      context.deserializable_scalar(&fields.user_id)?,
+      fields.response.deserialize(&context)?,
+      penalty,
+    ));
+  }
+}
+
+/// Stores `event` and forgets the events older than `retention`.
+pub fn add_event(database: &Database, event: &Event, retention: Duration) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.event_fields;
+  let oldest_kept_time = DateTime::from_timestamp(event.time().timestamp() - retention.total_milliseconds() as i64)
+    .unwrap_or(event.time());
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.events_name);
+  code.write(" WHERE ");
+  code.write(&fields.time);
+  code.write(" < ");
+  serialize_scalar_value_into(&oldest_kept_time, code.as_mut());
+  code.write(";");
+
+  let mut context = SerializeCompoundValueContext::new();
+  context.write_scalar(&fields.id, event.id());
+  context.write_scalar(&fields.user_id, &event.user_id());
+  context.write_scalar(&fields.time, &event.time());
+  context.write_scalar(&fields.circumvention, &event.circumvention());
+  context.write_scalar(&fields.detection_kind, &event.detection().kind);
+  context.write_scalar(&fields.detection_subject, &event.detection().subject);
+  fields.response.serialize(&mut context, event.response());
+
+  code.write("INSERT INTO ");
+  code.write(&collection.events_name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+/// Deletes the events of `user_id`, or of every user if it's `None`.
+pub fn delete_events(database: &Database, user_id: Option<UserId>) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.events_name);
+  if let Some(user_id) = user_id {
+    code.write(" WHERE ");
+    code.write(&collection.event_fields.user_id);
+    code.write(" = ");
+    serialize_scalar_value_into(&user_id, code.as_mut());
+  }
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+/// Retrieves the events of `user_id`, or of every user if it's `None`,
+/// oldest first.
+pub fn retrieve_events(database: &Database, user_id: Option<UserId>) -> Result<Vec<Event>, GenericError> {
+  let collection = collection(database);
+  let fields = &collection.event_fields;
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.events_name);
+  if let Some(user_id) = user_id {
+    code.write(" WHERE ");
+    code.write(&fields.user_id);
+    code.write(" = ");
+    serialize_scalar_value_into(&user_id, code.as_mut());
+  }
+  code.write(" ORDER BY ");
+  code.write(&fields.time);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving circumvention events")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving circumvention events")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut events = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving circumvention events")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(events);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    let detection = Detection {
+      kind: context.deserializable_scalar(&fields.detection_kind)?,
+      subject: context.deserializable_scalar(&fields.detection_subject)?,
+    };
+    events.push(Event::from_fields(
+      context.deserializable_scalar(&fields.id)?,
+      context.deserializable_scalar(&fields.user_id)?,
+      context.deserializable_scalar(&fields.time)?,
+      context.deserializable_scalar(&fields.circumvention)?,
+      detection,
+      fields.response.deserialize(&context)?,
+    ));
+  }
+}
@@ -11,4 +11,26 @@ pub mod internet_access_regulation_policy;
 pub mod internet_access_regulation_rule;
 pub mod operating_system_integration_linux_data;
 pub mod operating_system_integration_linux_user;
+pub mod web_regulation_intrusive_url_pattern;
+pub mod web_regulation_intrusive_no_intercept_host;
+pub mod web_regulation_intrusive_website_visits_limiter;
+pub mod web_regulation_intrusive_website_visit_delayer;
+pub mod web_regulation_intrusive_youtube_rule;
+pub mod web_regulation_intrusive_twitter_filter;
+pub mod web_regulation_intrusive_rule_activator;
+pub mod web_regulation_intrusive_media_type_rule;
+pub mod web_regulation_intrusive_element_rule_sheet;
+pub mod web_regulation_intrusive_content_filter_rule;
+pub mod web_regulation_intrusive_safe_search_setting;
+pub mod web_regulation_intrusive_search_query_rule;
+pub mod web_regulation_intrusive_view_time_allowance;
+pub mod web_regulation_intrusive_image_rule;
+pub mod web_regulation_intrusive_image_classifier;
+pub mod web_regulation_intrusive_protobuf_field_name;
+pub mod web_regulation_intrusive_encrypted_dns_setting;
+pub mod circumvention_detection;
+pub mod web_regulation_intrusive_protobuf_rule;
+pub mod web_regulation_intrusive_audit_log;
+pub mod web_regulation_intrusive_proxied_host;
+pub mod browser_policy_setting;
 // pub mod shadow_vault;
\ No newline at end of file
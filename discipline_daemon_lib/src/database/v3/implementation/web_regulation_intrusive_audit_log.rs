@@ -0,0 +1,459 @@
+use crate::web_regulation_intrusive::audit_log::{Decision, Entry, Privacy, Query, Setting};
+use crate::operating_system_integration::UserId;
+use crate::*;
+use super::*;
+
+impl SerializableScalarValue for Privacy {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    match self {
+      Privacy::FullUrls => context.write_u8(0),
+      Privacy::DomainsOnly => context.write_u8(1),
+    }
+  }
+}
+
+impl DeserializableScalarValue for Privacy {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing an audit log Privacy"))?;
+
+    match number {
+      0 => Ok(Privacy::FullUrls),
+      1 => Ok(Privacy::DomainsOnly),
+      _ => {
+        Err(
+          GenericError::new("deserializing an audit log Privacy")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0 and 1")
+        )
+      }
+    }
+  }
+}
+
+impl SerializableScalarValue for Decision {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    match self {
+      Decision::Allowed => context.write_u8(0),
+      Decision::Blocked => context.write_u8(1),
+    }
+  }
+}
+
+impl DeserializableScalarValue for Decision {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing an audit log Decision"))?;
+
+    match number {
+      0 => Ok(Decision::Allowed),
+      1 => Ok(Decision::Blocked),
+      _ => {
+        Err(
+          GenericError::new("deserializing an audit log Decision")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0 and 1")
+        )
+      }
+    }
+  }
+}
+
+pub struct SettingFields {
+  user_id: String,
+  privacy: String,
+  retention: String,
+  protection_duration: String,
+  protection_remaining_duration: String,
+  protection_previous_synchronization_time: String,
+}
+
+pub struct EntryFields {
+  id: String,
+  user_id: String,
+  time: String,
+  domain: String,
+  path: String,
+  decision: String,
+}
+
+/// Settings live in one table and the entries recorded for them in another,
+/// which is indexed by time for retention and time range queries.
+pub struct SettingCollection {
+  name: String,
+  fields: SettingFields,
+  entries_name: String,
+  entry_fields: EntryFields,
+}
+
+impl SettingCollection {
+  pub fn new(collection_name: String, entries_collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: SettingFields {
+        user_id: "UserId".into(),
+        privacy: "Privacy".into(),
+        retention: "Retention".into(),
+        protection_duration: "ProtectionDuration".into(),
+        protection_remaining_duration: "ProtectionRemainingDuration".into(),
+        protection_previous_synchronization_time: "ProtectionPreviousSynchronizationTime".into(),
+      },
+      entries_name: entries_collection_name,
+      entry_fields: EntryFields {
+        id: "Id".into(),
+        user_id: "UserId".into(),
+        time: "Time".into(),
+        domain: "Domain".into(),
+        path: "Path".into(),
+        decision: "Decision".into(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &SettingCollection {
+  &database.web_regulation_intrusive_audit_log
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.user_id);
+  code.write(" INTEGER PRIMARY KEY, ");
+  code.write(&collection.fields.privacy);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.retention);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.protection_duration);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.protection_remaining_duration);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.protection_previous_synchronization_time);
+  code.write(" INTEGER NOT NULL) WITHOUT ROWID;");
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.entries_name);
+  code.write(" (");
+  code.write(&collection.entry_fields.id);
+  code.write(" TEXT PRIMARY KEY, ");
+  code.write(&collection.entry_fields.user_id);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.entry_fields.time);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.entry_fields.domain);
+  code.write(" TEXT NOT NULL, ");
+  code.write(&collection.entry_fields.path);
+  code.write(" TEXT, ");
+  code.write(&collection.entry_fields.decision);
+  code.write(" INTEGER NOT NULL) WITHOUT ROWID;");
+
+  code.write("CREATE INDEX IF NOT EXISTS ");
+  code.write(&collection.entries_name);
+  code.write("By");
+  code.write(&collection.entry_fields.time);
+  code.write(" ON ");
+  code.write(&collection.entries_name);
+  code.write(" (");
+  code.write(&collection.entry_fields.time);
+  code.write(");");
+}
+
+fn serialize_protector(context: &mut SerializeCompoundValueContext, setting: &Setting, fields: &SettingFields) {
+  context.write_scalar(&fields.protection_duration, &setting.protector().duration());
+  context.write_scalar(&fields.protection_remaining_duration, &setting.protector().remaining_duration());
+  context.write_scalar(&fields.protection_previous_synchronization_time, &setting.protector().previous_synchronization_time());
+}
+
+pub fn add_setting(database: &Database, setting: &Setting) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+
+  let mut context = SerializeCompoundValueContext::new();
+  context.write_scalar(&fields.user_id, &setting.user_id());
+  context.write_scalar(&fields.privacy, &setting.privacy());
+  context.write_scalar(&fields.retention, &setting.retention());
+  serialize_protector(&mut context, setting, fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn update_setting(database: &Database, setting: &Setting) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+
+  let mut context = SerializeCompoundValueContext::new();
+  context.write_scalar(&fields.privacy, &setting.privacy());
+  context.write_scalar(&fields.retention, &setting.retention());
+  serialize_protector(&mut context, setting, fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("UPDATE ");
+  code.write(&collection.name);
+  code.write(" SET (");
+  code.write(&context.column_names);
+  code.write(") = (");
+  code.write(&context.column_values);
+  code.write(") WHERE ");
+  code.write(&fields.user_id);
+  code.write(" = ");
+  serialize_scalar_value_into(&setting.user_id(), code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn update_protector(database: &Database, setting: &Setting) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+
+  let mut context = SerializeCompoundValueContext::new();
+  serialize_protector(&mut context, setting, fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("UPDATE ");
+  code.write(&collection.name);
+  code.write(" SET (");
+  code.write(&context.column_names);
+  code.write(") = (");
+  code.write(&context.column_values);
+  code.write(") WHERE ");
+  code.write(&fields.user_id);
+  code.write(" = ");
+  serialize_scalar_value_into(&setting.user_id(), code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_setting(database: &Database, user_id: UserId) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.user_id);
+  code.write(" = ");
+  serialize_scalar_value_into(&user_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn retrieve_all_settings(database: &Database) -> Result<Vec<Setting>, GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all audit log settings")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all audit log settings")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut settings = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all audit log settings")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(settings);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    settings.push(Setting::from_fields(
+      context.deserializable_scalar(&fields.user_id)?,
+      context.deserializable_scalar(&fields.privacy)?,
+      context.deserializable_scalar(&fields.retention)?,
+      CountdownTimer::from_fields(
+        context.deserializable_scalar(&fields.protection_duration)?,
+        context.deserializable_scalar(&fields.protection_remaining_duration)?,
+        context.deserializable_scalar(&fields.protection_previous_synchronization_time)?,
+      ),
+    ));
+  }
+}
+
+/// Stores `entry` and forgets the entries of its user older than
+/// `retention`.
+pub fn add_entry(database: &Database, entry: &Entry, retention: Duration) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.entry_fields;
+  let oldest_kept_time = DateTime::from_timestamp(entry.time().timestamp() - retention.total_milliseconds() as i64)
+    .unwrap_or(entry.time());
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.entries_name);
+  code.write(" WHERE ");
+  code.write(&fields.user_id);
+  code.write(" = ");
+  serialize_scalar_value_into(&entry.user_id(), code.as_mut());
+  code.write(" AND ");
+  code.write(&fields.time);
+  code.write(" < ");
+  serialize_scalar_value_into(&oldest_kept_time, code.as_mut());
+  code.write(";");
+
+  let mut context = SerializeCompoundValueContext::new();
+  context.write_scalar(&fields.id, entry.id());
+  context.write_scalar(&fields.user_id, &entry.user_id());
+  context.write_scalar(&fields.time, &entry.time());
+  context.write_scalar(&fields.domain, &entry.domain().to_string());
+  context.write_scalar(&fields.path, &entry.path().map(str::to_string));
+  context.write_scalar(&fields.decision, &entry.decision());
+
+  code.write("INSERT INTO ");
+  code.write(&collection.entries_name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+/// Deletes the entries of `user_id`.
+pub fn delete_entries(database: &Database, user_id: UserId) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.entries_name);
+  code.write(" WHERE ");
+  code.write(&collection.entry_fields.user_id);
+  code.write(" = ");
+  serialize_scalar_value_into(&user_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+/// Forgets the paths of the entries of `user_id`, keeping their domains.
+pub fn delete_entry_paths(database: &Database, user_id: UserId) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("UPDATE ");
+  code.write(&collection.entries_name);
+  code.write(" SET ");
+  code.write(&collection.entry_fields.path);
+  code.write(" = NULL WHERE ");
+  code.write(&collection.entry_fields.user_id);
+  code.write(" = ");
+  serialize_scalar_value_into(&user_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+/// Retrieves the entries `query` matches, oldest first.
+pub fn retrieve_entries(database: &Database, query: &Query) -> Result<Vec<Entry>, GenericError> {
+  let collection = collection(database);
+  let fields = &collection.entry_fields;
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.entries_name);
+  code.write(" WHERE ");
+  code.write(&fields.user_id);
+  code.write(" = ");
+  serialize_scalar_value_into(&query.user_id, code.as_mut());
+  if let Some(domain) = query.normalized_domain() {
+    // The domain itself or any of its subdomains.
+    let suffix = format!(".{domain}");
+    code.write(" AND (");
+    code.write(&fields.domain);
+    code.write(" = ");
+    serialize_scalar_value_into(&domain, code.as_mut());
+    code.write(" OR substr(");
+    code.write(&fields.domain);
+    code.write(", -");
+    code.write(&suffix.chars().count().to_string());
+    code.write(") = ");
+    serialize_scalar_value_into(&suffix, code.as_mut());
+    code.write(")");
+  }
+  if let Some(from) = query.from {
+    code.write(" AND ");
+    code.write(&fields.time);
+    code.write(" >= ");
+    serialize_scalar_value_into(&from, code.as_mut());
+  }
+  if let Some(till) = query.till {
+    code.write(" AND ");
+    code.write(&fields.time);
+    code.write(" <= ");
+    serialize_scalar_value_into(&till, code.as_mut());
+  }
+  code.write(" ORDER BY ");
+  code.write(&fields.time);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving audit log entries")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving audit log entries")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut entries = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving audit log entries")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(entries);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    entries.push(Entry::from_fields(
+      context.deserializable_scalar(&fields.id)?,
+      context.deserializable_scalar(&fields.user_id)?,
+      context.deserializable_scalar(&fields.time)?,
+      context.deserializable_scalar(&fields.domain)?,
+      context.deserializable_scalar(&fields.path)?,
+      context.deserializable_scalar(&fields.decision)?,
+    ));
+  }
+}
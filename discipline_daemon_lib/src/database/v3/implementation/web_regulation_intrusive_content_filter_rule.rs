@@ -0,0 +1,270 @@
+use crate::operating_system_integration::UserId;
+use crate::web_regulation_intrusive::content_filter::{Action, Language, Rule};
+use crate::*;
+use super::web_regulation_intrusive_rule_activator::RuleActivatorFields;
+use super::*;
+
+impl SerializableScalarValue for Action {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    match self {
+      Action::MaskWords => context.write_u8(0),
+      Action::BlockPage => context.write_u8(1),
+    }
+  }
+}
+
+impl DeserializableScalarValue for Action {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing a content filter Action"))?;
+
+    match number {
+      0 => Ok(Action::MaskWords),
+      1 => Ok(Action::BlockPage),
+      _ => {
+        Err(
+          GenericError::new("deserializing a content filter Action")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0 and 1")
+        )
+      }
+    }
+  }
+}
+
+/// The bit of a language in the `Languages` column. These mustn't change
+/// once rules are stored with them.
+fn language_bit(language: Language) -> u32 {
+  let number = match language {
+    Language::Arabic => 0,
+    Language::Chinese => 1,
+    Language::Czech => 2,
+    Language::Danish => 3,
+    Language::Dutch => 4,
+    Language::English => 5,
+    Language::Esperanto => 6,
+    Language::Filipino => 7,
+    Language::Finnish => 8,
+    Language::French => 9,
+    Language::German => 10,
+    Language::Hindi => 11,
+    Language::Hungarian => 12,
+    Language::Indonesian => 13,
+    Language::Italian => 14,
+    Language::Japanese => 15,
+    Language::Kabyle => 16,
+    Language::Korean => 17,
+    Language::Norwegian => 18,
+    Language::Persian => 19,
+    Language::Polish => 20,
+    Language::Portuguese => 21,
+    Language::Russian => 22,
+    Language::Spanish => 23,
+    Language::Swedish => 24,
+    Language::Thai => 25,
+    Language::Turkish => 26,
+  };
+
+  1 << number
+}
+
+pub(super) fn serialize_languages(languages: &[Language]) -> u32 {
+  languages
+    .iter()
+    .fold(0, |bits, language| bits | language_bit(*language))
+}
+
+pub(super) fn deserialize_languages(bits: u32) -> Result<Vec<Language>, GenericError> {
+  let known_bits = serialize_languages(&Language::ALL);
+  if bits & !known_bits != 0 {
+    return Err(
+      GenericError::new("deserializing content filter languages")
+        .add_error("unknown language bits are set")
+        .add_attachment("bits", bits.to_string())
+    );
+  }
+
+  Ok(
+    Language::ALL
+      .into_iter()
+      .filter(|language| bits & language_bit(*language) != 0)
+      .collect()
+  )
+}
+
+/// Keywords are stored one per line, as they can't have line breaks.
+pub(super) fn serialize_keywords(keywords: &[String]) -> String {
+  keywords.join("\n")
+}
+
+pub(super) fn deserialize_keywords(keywords: String) -> Vec<String> {
+  keywords
+    .lines()
+    .filter(|keyword| !keyword.is_empty())
+    .map(str::to_string)
+    .collect()
+}
+
+pub struct RuleFields {
+  id: String,
+  user_id: String,
+  languages: String,
+  keywords: String,
+  threshold: String,
+  action: String,
+  url_pattern: String,
+  activator: RuleActivatorFields,
+}
+
+pub struct RuleCollection {
+  name: String,
+  fields: RuleFields,
+}
+
+impl RuleCollection {
+  pub fn new(collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: RuleFields {
+        id: "Id".into(),
+        user_id: "UserId".into(),
+        languages: "Languages".into(),
+        keywords: "Keywords".into(),
+        threshold: "Threshold".into(),
+        action: "Action".into(),
+        url_pattern: "UrlPattern".into(),
+        activator: RuleActivatorFields::new(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &RuleCollection {
+  &database.web_regulation_intrusive_content_filter_rule
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.id);
+  code.write(" TEXT PRIMARY KEY, ");
+  code.write(&collection.fields.user_id);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.languages);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.keywords);
+  code.write(" TEXT NOT NULL, ");
+  code.write(&collection.fields.threshold);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.action);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.url_pattern);
+  code.write(" TEXT, ");
+  collection.fields.activator.write_define(code);
+  code.write(") WITHOUT ROWID;");
+}
+
+fn serialize_rule(context: &mut SerializeCompoundValueContext, rule: &Rule, fields: &RuleFields) {
+  context.write_scalar(&fields.id, rule.id());
+  context.write_scalar(&fields.user_id, &rule.user_id());
+  context.write_u32(&fields.languages, serialize_languages(rule.languages()));
+  context.write_string(&fields.keywords, &serialize_keywords(rule.keywords()));
+  context.write_u32(&fields.threshold, rule.threshold());
+  context.write_scalar(&fields.action, &rule.action());
+  context.write_scalar(&fields.url_pattern, &rule.url_pattern().cloned());
+  fields.activator.serialize(context, rule.activator());
+}
+
+fn deserialize_rule(context: &DeserializeCompoundValueContext, fields: &RuleFields) -> Result<Rule, GenericError> {
+  let id: Uuid = context.deserializable_scalar(&fields.id)?;
+  let user_id: UserId = context.deserializable_scalar(&fields.user_id)?;
+  let languages = deserialize_languages(context.deserializable_scalar(&fields.languages)?)?;
+  let keywords = deserialize_keywords(context.deserializable_scalar(&fields.keywords)?);
+
+  Ok(Rule::from_fields(
+    id,
+    user_id,
+    languages,
+    keywords,
+    context.deserializable_scalar(&fields.threshold)?,
+    context.deserializable_scalar(&fields.action)?,
+    context.deserializable_scalar(&fields.url_pattern)?,
+    fields.activator.deserialize(context)?,
+  ))
+}
+
+pub fn add_rule(database: &Database, rule: &Rule) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut context = SerializeCompoundValueContext::new();
+  serialize_rule(&mut context, rule, &collection.fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_rule(database: &Database, rule_id: &Uuid) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.id);
+  code.write(" = ");
+  serialize_scalar_value_into(rule_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn retrieve_all_rules(database: &Database) -> Result<Vec<Rule>, GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all content filter rules")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all content filter rules")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut rules = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all content filter rules")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(rules);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    rules.push(deserialize_rule(&context, &collection.fields)?);
+  }
+}
@@ -0,0 +1,149 @@
+use crate::operating_system_integration::UserId;
+use crate::web_regulation_intrusive::element_rules::{RuleSheet, Source};
+use crate::*;
+use super::*;
+
+impl SerializableScalarValue for Source {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    context.write_string(self.text());
+  }
+}
+
+impl DeserializableScalarValue for Source {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    value
+      .as_string()
+      .and_then(|text| {
+        Source::parse(text).map_err(|error|
+          GenericError::new("parsing element rules")
+            .add_error("the stored element rules no longer parse")
+            .add_attachment("error", format!("{error:?}"))
+        )
+      })
+      .map_err(|error| error.change_context("deserializing an element rules Source"))
+  }
+}
+
+pub struct SheetFields {
+  id: String,
+  user_id: String,
+  source: String,
+}
+
+pub struct SheetCollection {
+  name: String,
+  fields: SheetFields,
+}
+
+impl SheetCollection {
+  pub fn new(collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: SheetFields {
+        id: "Id".into(),
+        user_id: "UserId".into(),
+        source: "Source".into(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &SheetCollection {
+  &database.web_regulation_intrusive_element_rule_sheet
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.id);
+  code.write(" TEXT PRIMARY KEY, ");
+  code.write(&collection.fields.user_id);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.source);
+  code.write(" TEXT NOT NULL) WITHOUT ROWID;");
+}
+
+fn deserialize_sheet(context: &DeserializeCompoundValueContext, fields: &SheetFields) -> Result<RuleSheet, GenericError> {
+  Ok(RuleSheet::from_fields(
+    context.deserializable_scalar(&fields.id)?,
+    context.deserializable_scalar::<UserId>(&fields.user_id)?,
+    context.deserializable_scalar(&fields.source)?,
+  ))
+}
+
+pub fn add_sheet(database: &Database, sheet: &RuleSheet) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+
+  let mut context = SerializeCompoundValueContext::new();
+  context.write_scalar(&fields.id, sheet.id());
+  context.write_scalar(&fields.user_id, &sheet.user_id());
+  context.write_scalar(&fields.source, sheet.source());
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_sheet(database: &Database, sheet_id: &Uuid) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.id);
+  code.write(" = ");
+  serialize_scalar_value_into(sheet_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn retrieve_all_sheets(database: &Database) -> Result<Vec<RuleSheet>, GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all element rule sheets")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all element rule sheets")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut sheets = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all element rule sheets")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(sheets);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    sheets.push(deserialize_sheet(&context, &collection.fields)?);
+  }
+}
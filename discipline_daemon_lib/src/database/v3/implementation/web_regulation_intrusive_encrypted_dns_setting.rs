@@ -0,0 +1,168 @@
+use crate::operating_system_integration::UserId;
+use crate::web_regulation_intrusive::encrypted_dns::{Measures, Setting};
+use crate::*;
+use super::*;
+
+pub struct SettingFields {
+  user_id: String,
+  block_dns_over_tls: String,
+  block_known_endpoints: String,
+  answer_canary: String,
+  strip_service_bindings: String,
+}
+
+pub struct SettingCollection {
+  name: String,
+  fields: SettingFields,
+}
+
+impl SettingCollection {
+  pub fn new(collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: SettingFields {
+        user_id: "UserId".into(),
+        block_dns_over_tls: "BlockDnsOverTls".into(),
+        block_known_endpoints: "BlockKnownEndpoints".into(),
+        answer_canary: "AnswerCanary".into(),
+        strip_service_bindings: "StripServiceBindings".into(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &SettingCollection {
+  &database.web_regulation_intrusive_encrypted_dns_setting
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.user_id);
+  code.write(" INTEGER PRIMARY KEY, ");
+  code.write(&collection.fields.block_dns_over_tls);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.block_known_endpoints);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.answer_canary);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.strip_service_bindings);
+  code.write(" INTEGER NOT NULL) WITHOUT ROWID;");
+}
+
+fn serialize_measures(context: &mut SerializeCompoundValueContext, measures: &Measures, fields: &SettingFields) {
+  context.write_scalar(&fields.block_dns_over_tls, &measures.block_dns_over_tls);
+  context.write_scalar(&fields.block_known_endpoints, &measures.block_known_endpoints);
+  context.write_scalar(&fields.answer_canary, &measures.answer_canary);
+  context.write_scalar(&fields.strip_service_bindings, &measures.strip_service_bindings);
+}
+
+fn deserialize_setting(context: &DeserializeCompoundValueContext, fields: &SettingFields) -> Result<Setting, GenericError> {
+  Ok(Setting::from_fields(
+    context.deserializable_scalar::<UserId>(&fields.user_id)?,
+    Measures {
+      block_dns_over_tls: context.deserializable_scalar(&fields.block_dns_over_tls)?,
+      block_known_endpoints: context.deserializable_scalar(&fields.block_known_endpoints)?,
+      answer_canary: context.deserializable_scalar(&fields.answer_canary)?,
+      strip_service_bindings: context.deserializable_scalar(&fields.strip_service_bindings)?,
+    },
+  ))
+}
+
+pub fn add_setting(database: &Database, setting: &Setting) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut context = SerializeCompoundValueContext::new();
+  context.write_scalar(&collection.fields.user_id, &setting.user_id());
+  serialize_measures(&mut context, &setting.measures(), &collection.fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_setting(database: &Database, user_id: UserId) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.user_id);
+  code.write(" = ");
+  serialize_scalar_value_into(&user_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn update_measures(database: &Database, setting: &Setting) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+
+  let mut context = SerializeCompoundValueContext::new();
+  serialize_measures(&mut context, &setting.measures(), fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("UPDATE ");
+  code.write(&collection.name);
+  code.write(" SET (");
+  code.write(&context.column_names);
+  code.write(") = (");
+  code.write(&context.column_values);
+  code.write(") WHERE ");
+  code.write(&fields.user_id);
+  code.write(" = ");
+  serialize_scalar_value_into(&setting.user_id(), code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn retrieve_all_settings(database: &Database) -> Result<Vec<Setting>, GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all encrypted DNS settings")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all encrypted DNS settings")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut settings = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all encrypted DNS settings")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(settings);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    settings.push(deserialize_setting(&context, &collection.fields)?);
+  }
+}
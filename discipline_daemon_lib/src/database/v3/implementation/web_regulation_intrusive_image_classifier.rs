@@ -0,0 +1,217 @@
+use std::path::PathBuf;
+use crate::web_regulation_intrusive::image_rules::{ClassifierModel, Normalization, OutputKind};
+use crate::*;
+use super::*;
+
+impl SerializableScalarValue for Normalization {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    match self {
+      Normalization::ZeroTo255 => context.write_u8(0),
+      Normalization::ZeroToOne => context.write_u8(1),
+      Normalization::MinusOneToOne => context.write_u8(2),
+      Normalization::ImageNet => context.write_u8(3),
+    }
+  }
+}
+
+impl DeserializableScalarValue for Normalization {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing an image classifier Normalization"))?;
+
+    match number {
+      0 => Ok(Normalization::ZeroTo255),
+      1 => Ok(Normalization::ZeroToOne),
+      2 => Ok(Normalization::MinusOneToOne),
+      3 => Ok(Normalization::ImageNet),
+      _ => {
+        Err(
+          GenericError::new("deserializing an image classifier Normalization")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0, 1, 2 and 3")
+        )
+      }
+    }
+  }
+}
+
+impl SerializableScalarValue for OutputKind {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    match self {
+      OutputKind::Probabilities => context.write_u8(0),
+      OutputKind::Logits => context.write_u8(1),
+    }
+  }
+}
+
+impl DeserializableScalarValue for OutputKind {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing an image classifier OutputKind"))?;
+
+    match number {
+      0 => Ok(OutputKind::Probabilities),
+      1 => Ok(OutputKind::Logits),
+      _ => {
+        Err(
+          GenericError::new("deserializing an image classifier OutputKind")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0 and 1")
+        )
+      }
+    }
+  }
+}
+
+pub struct ModelFields {
+  id: String,
+  name: String,
+  model_path: String,
+  classes: String,
+  normalization: String,
+  output_kind: String,
+}
+
+pub struct ModelCollection {
+  name: String,
+  fields: ModelFields,
+}
+
+impl ModelCollection {
+  pub fn new(collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: ModelFields {
+        id: "Id".into(),
+        name: "Name".into(),
+        model_path: "ModelPath".into(),
+        classes: "Classes".into(),
+        normalization: "Normalization".into(),
+        output_kind: "OutputKind".into(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &ModelCollection {
+  &database.web_regulation_intrusive_image_classifier
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.id);
+  code.write(" TEXT PRIMARY KEY, ");
+  code.write(&collection.fields.name);
+  code.write(" TEXT NOT NULL, ");
+  code.write(&collection.fields.model_path);
+  code.write(" TEXT NOT NULL, ");
+  code.write(&collection.fields.classes);
+  code.write(" TEXT NOT NULL, ");
+  code.write(&collection.fields.normalization);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.output_kind);
+  code.write(" INTEGER NOT NULL) WITHOUT ROWID;");
+}
+
+fn serialize_model(context: &mut SerializeCompoundValueContext, model: &ClassifierModel, fields: &ModelFields) {
+  context.write_scalar(&fields.id, model.id());
+  context.write_scalar(&fields.name, &model.name().to_string());
+  context.write_scalar(&fields.model_path, &model.model_path().to_string_lossy().into_owned());
+  context.write_scalar(&fields.classes, &model.classes().join("\n"));
+  context.write_scalar(&fields.normalization, &model.normalization());
+  context.write_scalar(&fields.output_kind, &model.output_kind());
+}
+
+fn deserialize_model(context: &DeserializeCompoundValueContext, fields: &ModelFields) -> Result<ClassifierModel, GenericError> {
+  Ok(ClassifierModel::from_fields(
+    context.deserializable_scalar(&fields.id)?,
+    context.deserializable_scalar(&fields.name)?,
+    PathBuf::from(context.deserializable_scalar::<String>(&fields.model_path)?),
+    context
+      .deserializable_scalar::<String>(&fields.classes)?
+      .lines()
+      .map(String::from)
+      .collect(),
+    context.deserializable_scalar(&fields.normalization)?,
+    context.deserializable_scalar(&fields.output_kind)?,
+  ))
+}
+
+pub fn add_model(database: &Database, model: &ClassifierModel) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut context = SerializeCompoundValueContext::new();
+  serialize_model(&mut context, model, &collection.fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_model(database: &Database, model_id: &Uuid) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.id);
+  code.write(" = ");
+  serialize_scalar_value_into(model_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn retrieve_all_models(database: &Database) -> Result<Vec<ClassifierModel>, GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all image classifier models")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all image classifier models")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut models = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all image classifier models")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(models);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    models.push(deserialize_model(&context, &collection.fields)?);
+  }
+}
@@ -0,0 +1,221 @@
+use crate::operating_system_integration::UserId;
+use crate::web_regulation_intrusive::image_rules::{Action, ClassificationCondition, Rule};
+use crate::*;
+use super::web_regulation_intrusive_rule_activator::RuleActivatorFields;
+use super::*;
+
+impl SerializableScalarValue for Action {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    match self {
+      Action::Blur => context.write_u8(0),
+      Action::Replace => context.write_u8(1),
+    }
+  }
+}
+
+impl DeserializableScalarValue for Action {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing an image rule Action"))?;
+
+    match number {
+      0 => Ok(Action::Blur),
+      1 => Ok(Action::Replace),
+      _ => {
+        Err(
+          GenericError::new("deserializing an image rule Action")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0 and 1")
+        )
+      }
+    }
+  }
+}
+
+pub struct RuleFields {
+  id: String,
+  user_id: String,
+  image_url_pattern: String,
+  page_url_pattern: String,
+  classifier_id: String,
+  classifier_class: String,
+  classifier_threshold: String,
+  action: String,
+  activator: RuleActivatorFields,
+}
+
+pub struct RuleCollection {
+  name: String,
+  fields: RuleFields,
+}
+
+impl RuleCollection {
+  pub fn new(collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: RuleFields {
+        id: "Id".into(),
+        user_id: "UserId".into(),
+        image_url_pattern: "ImageUrlPattern".into(),
+        page_url_pattern: "PageUrlPattern".into(),
+        classifier_id: "ClassifierId".into(),
+        classifier_class: "ClassifierClass".into(),
+        classifier_threshold: "ClassifierThreshold".into(),
+        action: "Action".into(),
+        activator: RuleActivatorFields::new(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &RuleCollection {
+  &database.web_regulation_intrusive_image_rule
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.id);
+  code.write(" TEXT PRIMARY KEY, ");
+  code.write(&collection.fields.user_id);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.image_url_pattern);
+  code.write(" TEXT, ");
+  code.write(&collection.fields.page_url_pattern);
+  code.write(" TEXT, ");
+  code.write(&collection.fields.classifier_id);
+  code.write(" TEXT, ");
+  code.write(&collection.fields.classifier_class);
+  code.write(" TEXT, ");
+  code.write(&collection.fields.classifier_threshold);
+  code.write(" REAL, ");
+  code.write(&collection.fields.action);
+  code.write(" INTEGER NOT NULL, ");
+  collection.fields.activator.write_define(code);
+  code.write(") WITHOUT ROWID;");
+}
+
+fn serialize_rule(context: &mut SerializeCompoundValueContext, rule: &Rule, fields: &RuleFields) {
+  context.write_scalar(&fields.id, rule.id());
+  context.write_scalar(&fields.user_id, &rule.user_id());
+  context.write_scalar(&fields.image_url_pattern, &rule.image_url_pattern().cloned());
+  context.write_scalar(&fields.page_url_pattern, &rule.page_url_pattern().cloned());
+  let classification = rule.classification();
+  context.write_scalar(&fields.classifier_id, &classification.map(|condition| condition.classifier_id));
+  context.write_scalar(&fields.classifier_class, &classification.map(|condition| condition.class.clone()));
+  context.write_scalar(&fields.classifier_threshold, &classification.map(|condition| condition.threshold));
+  context.write_scalar(&fields.action, &rule.action());
+  fields.activator.serialize(context, rule.activator());
+}
+
+fn deserialize_classification(
+  context: &DeserializeCompoundValueContext,
+  fields: &RuleFields,
+) -> Result<Option<ClassificationCondition>, GenericError> {
+  let classifier_id = context.deserializable_scalar::<Option<Uuid>>(&fields.classifier_id)?;
+  let class = context.deserializable_scalar::<Option<String>>(&fields.classifier_class)?;
+  let threshold = context.deserializable_scalar::<Option<f32>>(&fields.classifier_threshold)?;
+
+  match (classifier_id, class, threshold) {
+    (Some(classifier_id), Some(class), Some(threshold)) => {
+      Ok(Some(ClassificationCondition { classifier_id, class, threshold }))
+    }
+    (None, None, None) => {
+      Ok(None)
+    }
+    _ => {
+      Err(
+        GenericError::new("deserializing an image rule classification condition")
+          .add_error("some of the classifier columns are null, but not all")
+      )
+    }
+  }
+}
+
+fn deserialize_rule(context: &DeserializeCompoundValueContext, fields: &RuleFields) -> Result<Rule, GenericError> {
+  Ok(Rule::from_fields(
+    context.deserializable_scalar(&fields.id)?,
+    context.deserializable_scalar::<UserId>(&fields.user_id)?,
+    context.deserializable_scalar(&fields.image_url_pattern)?,
+    context.deserializable_scalar(&fields.page_url_pattern)?,
+    deserialize_classification(context, fields)?,
+    context.deserializable_scalar(&fields.action)?,
+    fields.activator.deserialize(context)?,
+  ))
+}
+
+pub fn add_rule(database: &Database, rule: &Rule) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut context = SerializeCompoundValueContext::new();
+  serialize_rule(&mut context, rule, &collection.fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_rule(database: &Database, rule_id: &Uuid) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.id);
+  code.write(" = ");
+  serialize_scalar_value_into(rule_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn retrieve_all_rules(database: &Database) -> Result<Vec<Rule>, GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all image rules")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all image rules")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut rules = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all image rules")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(rules);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    rules.push(deserialize_rule(&context, &collection.fields)?);
+  }
+}
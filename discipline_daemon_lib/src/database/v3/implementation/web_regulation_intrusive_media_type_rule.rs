@@ -0,0 +1,201 @@
+use crate::operating_system_integration::UserId;
+use crate::web_regulation_intrusive::media_type_blocker::Rule;
+use crate::web_regulation_intrusive::MediaKind;
+use crate::*;
+use super::web_regulation_intrusive_rule_activator::RuleActivatorFields;
+use super::*;
+
+/// The bit of a media kind in the `MediaKinds` column. These mustn't change
+/// once rules are stored with them.
+fn media_kind_bit(media_kind: MediaKind) -> u32 {
+  let number = match media_kind {
+    MediaKind::Image => 0,
+    MediaKind::Video => 1,
+    MediaKind::Audio => 2,
+    MediaKind::Font => 3,
+    MediaKind::Archive => 4,
+    MediaKind::WebPage => 5,
+    MediaKind::WebStyle => 6,
+    MediaKind::WebScript => 7,
+    MediaKind::JavaScript => 8,
+    MediaKind::TypeScript => 9,
+    MediaKind::Binary => 10,
+    MediaKind::ShellScript => 11,
+    MediaKind::Text => 12,
+    MediaKind::Document => 13,
+    MediaKind::GifLike => 14,
+    MediaKind::Icon => 15,
+    MediaKind::Pdf => 16,
+    MediaKind::AndroidPackage => 17,
+    MediaKind::Executable => 18,
+  };
+
+  1 << number
+}
+
+fn serialize_media_kinds(media_kinds: &[MediaKind]) -> u32 {
+  media_kinds
+    .iter()
+    .fold(0, |bits, media_kind| bits | media_kind_bit(*media_kind))
+}
+
+fn deserialize_media_kinds(bits: u32) -> Result<Vec<MediaKind>, GenericError> {
+  let known_bits = serialize_media_kinds(&MediaKind::ALL);
+  if bits & !known_bits != 0 {
+    return Err(
+      GenericError::new("deserializing media kinds")
+        .add_error("unknown media kind bits are set")
+        .add_attachment("bits", bits.to_string())
+    );
+  }
+
+  Ok(
+    MediaKind::ALL
+      .into_iter()
+      .filter(|media_kind| bits & media_kind_bit(*media_kind) != 0)
+      .collect()
+  )
+}
+
+pub struct RuleFields {
+  id: String,
+  user_id: String,
+  media_kinds: String,
+  url_pattern: String,
+  activator: RuleActivatorFields,
+}
+
+pub struct RuleCollection {
+  name: String,
+  fields: RuleFields,
+}
+
+impl RuleCollection {
+  pub fn new(collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: RuleFields {
+        id: "Id".into(),
+        user_id: "UserId".into(),
+        media_kinds: "MediaKinds".into(),
+        url_pattern: "UrlPattern".into(),
+        activator: RuleActivatorFields::new(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &RuleCollection {
+  &database.web_regulation_intrusive_media_type_rule
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.id);
+  code.write(" TEXT PRIMARY KEY, ");
+  code.write(&collection.fields.user_id);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.media_kinds);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.url_pattern);
+  code.write(" TEXT, ");
+  collection.fields.activator.write_define(code);
+  code.write(") WITHOUT ROWID;");
+}
+
+fn serialize_rule(context: &mut SerializeCompoundValueContext, rule: &Rule, fields: &RuleFields) {
+  context.write_scalar(&fields.id, rule.id());
+  context.write_scalar(&fields.user_id, &rule.user_id());
+  context.write_u32(&fields.media_kinds, serialize_media_kinds(rule.media_kinds()));
+  context.write_scalar(&fields.url_pattern, &rule.url_pattern().cloned());
+  fields.activator.serialize(context, rule.activator());
+}
+
+fn deserialize_rule(context: &DeserializeCompoundValueContext, fields: &RuleFields) -> Result<Rule, GenericError> {
+  let id: Uuid = context.deserializable_scalar(&fields.id)?;
+  let user_id: UserId = context.deserializable_scalar(&fields.user_id)?;
+  let media_kinds = deserialize_media_kinds(context.deserializable_scalar(&fields.media_kinds)?)?;
+
+  Ok(Rule::from_fields(
+    id,
+    user_id,
+    media_kinds,
+    context.deserializable_scalar(&fields.url_pattern)?,
+    fields.activator.deserialize(context)?,
+  ))
+}
+
+pub fn add_rule(database: &Database, rule: &Rule) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut context = SerializeCompoundValueContext::new();
+  serialize_rule(&mut context, rule, &collection.fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_rule(database: &Database, rule_id: &Uuid) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.id);
+  code.write(" = ");
+  serialize_scalar_value_into(rule_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn retrieve_all_rules(database: &Database) -> Result<Vec<Rule>, GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all media type rules")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all media type rules")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut rules = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all media type rules")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(rules);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    rules.push(deserialize_rule(&context, &collection.fields)?);
+  }
+}
@@ -0,0 +1,242 @@
+use crate::operating_system_integration::UserId;
+use crate::web_regulation_intrusive::protobuf::{Direction, FieldName, FieldPath, Interpretation};
+use crate::*;
+use super::*;
+
+impl SerializableScalarValue for Direction {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    match self {
+      Direction::Request => context.write_u8(0),
+      Direction::Response => context.write_u8(1),
+    }
+  }
+}
+
+impl DeserializableScalarValue for Direction {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing a protobuf Direction"))?;
+
+    match number {
+      0 => Ok(Direction::Request),
+      1 => Ok(Direction::Response),
+      _ => {
+        Err(
+          GenericError::new("deserializing a protobuf Direction")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0 and 1")
+        )
+      }
+    }
+  }
+}
+
+impl SerializableScalarValue for Interpretation {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    match self {
+      Interpretation::Unsigned => context.write_u8(0),
+      Interpretation::Signed => context.write_u8(1),
+      Interpretation::ZigZag => context.write_u8(2),
+      Interpretation::Boolean => context.write_u8(3),
+      Interpretation::Float => context.write_u8(4),
+      Interpretation::Double => context.write_u8(5),
+      Interpretation::Text => context.write_u8(6),
+      Interpretation::Bytes => context.write_u8(7),
+    }
+  }
+}
+
+impl DeserializableScalarValue for Interpretation {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing a protobuf Interpretation"))?;
+
+    match number {
+      0 => Ok(Interpretation::Unsigned),
+      1 => Ok(Interpretation::Signed),
+      2 => Ok(Interpretation::ZigZag),
+      3 => Ok(Interpretation::Boolean),
+      4 => Ok(Interpretation::Float),
+      5 => Ok(Interpretation::Double),
+      6 => Ok(Interpretation::Text),
+      7 => Ok(Interpretation::Bytes),
+      _ => {
+        Err(
+          GenericError::new("deserializing a protobuf Interpretation")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0 through 7")
+        )
+      }
+    }
+  }
+}
+
+impl SerializableScalarValue for FieldPath {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    context.write_string(&self.to_string());
+  }
+}
+
+impl DeserializableScalarValue for FieldPath {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    value
+      .as_string()
+      .and_then(|string| FieldPath::parse(&string))
+      .map_err(|error| error.change_context("deserializing a protobuf FieldPath"))
+  }
+}
+
+pub struct FieldNameFields {
+  id: String,
+  user_id: String,
+  method: String,
+  direction: String,
+  path: String,
+  name: String,
+  interpretation: String,
+}
+
+pub struct FieldNameCollection {
+  name: String,
+  fields: FieldNameFields,
+}
+
+impl FieldNameCollection {
+  pub fn new(collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: FieldNameFields {
+        id: "Id".into(),
+        user_id: "UserId".into(),
+        method: "Method".into(),
+        direction: "Direction".into(),
+        path: "Path".into(),
+        name: "Name".into(),
+        interpretation: "Interpretation".into(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &FieldNameCollection {
+  &database.web_regulation_intrusive_protobuf_field_name
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.id);
+  code.write(" TEXT PRIMARY KEY, ");
+  code.write(&collection.fields.user_id);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.method);
+  code.write(" TEXT NOT NULL, ");
+  code.write(&collection.fields.direction);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.path);
+  code.write(" TEXT NOT NULL, ");
+  code.write(&collection.fields.name);
+  code.write(" TEXT NOT NULL, ");
+  code.write(&collection.fields.interpretation);
+  code.write(" INTEGER NOT NULL) WITHOUT ROWID;");
+}
+
+fn serialize_field_name(context: &mut SerializeCompoundValueContext, field_name: &FieldName, fields: &FieldNameFields) {
+  context.write_scalar(&fields.id, field_name.id());
+  context.write_scalar(&fields.user_id, &field_name.user_id());
+  context.write_scalar(&fields.method, field_name.method());
+  context.write_scalar(&fields.direction, &field_name.direction());
+  context.write_scalar(&fields.path, field_name.path());
+  context.write_scalar(&fields.name, &field_name.name().to_string());
+  context.write_scalar(&fields.interpretation, &field_name.interpretation());
+}
+
+fn deserialize_field_name(context: &DeserializeCompoundValueContext, fields: &FieldNameFields) -> Result<FieldName, GenericError> {
+  Ok(FieldName::from_fields(
+    context.deserializable_scalar(&fields.id)?,
+    context.deserializable_scalar::<UserId>(&fields.user_id)?,
+    context.deserializable_scalar(&fields.method)?,
+    context.deserializable_scalar(&fields.direction)?,
+    context.deserializable_scalar(&fields.path)?,
+    context.deserializable_scalar(&fields.name)?,
+    context.deserializable_scalar(&fields.interpretation)?,
+  ))
+}
+
+pub fn add_field_name(database: &Database, field_name: &FieldName) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut context = SerializeCompoundValueContext::new();
+  serialize_field_name(&mut context, field_name, &collection.fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_field_name(database: &Database, field_name_id: &Uuid) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.id);
+  code.write(" = ");
+  serialize_scalar_value_into(field_name_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn retrieve_all_field_names(database: &Database) -> Result<Vec<FieldName>, GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all protobuf field names")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all protobuf field names")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut field_names = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all protobuf field names")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(field_names);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    field_names.push(deserialize_field_name(&context, &collection.fields)?);
+  }
+}
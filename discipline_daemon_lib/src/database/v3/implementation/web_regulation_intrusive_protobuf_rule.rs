@@ -0,0 +1,204 @@
+use crate::operating_system_integration::UserId;
+use crate::web_regulation_intrusive::protobuf::{Operator, Rule};
+use crate::*;
+use super::web_regulation_intrusive_rule_activator::RuleActivatorFields;
+use super::*;
+
+impl SerializableScalarValue for Operator {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    match self {
+      Operator::Present => context.write_u8(0),
+      Operator::Equals => context.write_u8(1),
+      Operator::Contains => context.write_u8(2),
+      Operator::AtLeast => context.write_u8(3),
+      Operator::AtMost => context.write_u8(4),
+    }
+  }
+}
+
+impl DeserializableScalarValue for Operator {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing a protobuf rule Operator"))?;
+
+    match number {
+      0 => Ok(Operator::Present),
+      1 => Ok(Operator::Equals),
+      2 => Ok(Operator::Contains),
+      3 => Ok(Operator::AtLeast),
+      4 => Ok(Operator::AtMost),
+      _ => {
+        Err(
+          GenericError::new("deserializing a protobuf rule Operator")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0 through 4")
+        )
+      }
+    }
+  }
+}
+
+pub struct RuleFields {
+  id: String,
+  user_id: String,
+  url_pattern: String,
+  direction: String,
+  path: String,
+  interpretation: String,
+  operator: String,
+  operand: String,
+  activator: RuleActivatorFields,
+}
+
+pub struct RuleCollection {
+  name: String,
+  fields: RuleFields,
+}
+
+impl RuleCollection {
+  pub fn new(collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: RuleFields {
+        id: "Id".into(),
+        user_id: "UserId".into(),
+        url_pattern: "UrlPattern".into(),
+        direction: "Direction".into(),
+        path: "Path".into(),
+        interpretation: "Interpretation".into(),
+        operator: "Operator".into(),
+        operand: "Operand".into(),
+        activator: RuleActivatorFields::new(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &RuleCollection {
+  &database.web_regulation_intrusive_protobuf_rule
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.id);
+  code.write(" TEXT PRIMARY KEY, ");
+  code.write(&collection.fields.user_id);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.url_pattern);
+  code.write(" TEXT NOT NULL, ");
+  code.write(&collection.fields.direction);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.path);
+  code.write(" TEXT NOT NULL, ");
+  code.write(&collection.fields.interpretation);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.operator);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.operand);
+  code.write(" TEXT NOT NULL, ");
+  collection.fields.activator.write_define(code);
+  code.write(") WITHOUT ROWID;");
+}
+
+fn serialize_rule(context: &mut SerializeCompoundValueContext, rule: &Rule, fields: &RuleFields) {
+  context.write_scalar(&fields.id, rule.id());
+  context.write_scalar(&fields.user_id, &rule.user_id());
+  context.write_scalar(&fields.url_pattern, rule.url_pattern());
+  context.write_scalar(&fields.direction, &rule.direction());
+  context.write_scalar(&fields.path, rule.path());
+  context.write_scalar(&fields.interpretation, &rule.interpretation());
+  context.write_scalar(&fields.operator, &rule.operator());
+  context.write_scalar(&fields.operand, &rule.operand().to_string());
+  fields.activator.serialize(context, rule.activator());
+}
+
+fn deserialize_rule(context: &DeserializeCompoundValueContext, fields: &RuleFields) -> Result<Rule, GenericError> {
+  Ok(Rule::from_fields(
+    context.deserializable_scalar(&fields.id)?,
+    context.deserializable_scalar::<UserId>(&fields.user_id)?,
+    context.deserializable_scalar(&fields.url_pattern)?,
+    context.deserializable_scalar(&fields.direction)?,
+    context.deserializable_scalar(&fields.path)?,
+    context.deserializable_scalar(&fields.interpretation)?,
+    context.deserializable_scalar(&fields.operator)?,
+    context.deserializable_scalar(&fields.operand)?,
+    fields.activator.deserialize(context)?,
+  ))
+}
+
+pub fn add_rule(database: &Database, rule: &Rule) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut context = SerializeCompoundValueContext::new();
+  serialize_rule(&mut context, rule, &collection.fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_rule(database: &Database, rule_id: &Uuid) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.id);
+  code.write(" = ");
+  serialize_scalar_value_into(rule_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn retrieve_all_rules(database: &Database) -> Result<Vec<Rule>, GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all protobuf rules")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all protobuf rules")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut rules = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all protobuf rules")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(rules);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    rules.push(deserialize_rule(&context, &collection.fields)?);
+  }
+}
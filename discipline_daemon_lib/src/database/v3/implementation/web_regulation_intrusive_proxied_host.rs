@@ -0,0 +1,108 @@
+use crate::web_regulation_intrusive::HostPattern;
+use crate::*;
+use super::*;
+
+pub struct ProxiedHostFields {
+  host_pattern: String,
+}
+
+pub struct ProxiedHostCollection {
+  name: String,
+  fields: ProxiedHostFields,
+}
+
+impl ProxiedHostCollection {
+  pub fn new(collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: ProxiedHostFields {
+        host_pattern: "HostPattern".into(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &ProxiedHostCollection {
+  &database.web_regulation_intrusive_proxied_host
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.host_pattern);
+  code.write(" TEXT PRIMARY KEY) WITHOUT ROWID;");
+}
+
+pub fn add_host_pattern(database: &Database, host_pattern: &HostPattern) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut context = SerializeCompoundValueContext::new();
+  context.write_scalar(&collection.fields.host_pattern, host_pattern);
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT OR IGNORE INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_host_pattern(database: &Database, host_pattern: &HostPattern) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.host_pattern);
+  code.write(" = ");
+  serialize_scalar_value_into(host_pattern, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn retrieve_all_host_patterns(database: &Database) -> Result<Vec<HostPattern>, GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all web regulation proxied host patterns")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all web regulation proxied host patterns")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut host_patterns = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all web regulation proxied host patterns")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(host_patterns);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    host_patterns.push(context.deserializable_scalar(&collection.fields.host_pattern)?);
+  }
+}
@@ -0,0 +1,119 @@
+use crate::web_regulation_intrusive::RuleActivator;
+use crate::*;
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleActivatorType {
+  AllTheTime,
+  OnWeekday,
+  InTimeRange,
+  InWeekdayRange,
+}
+
+impl SerializableScalarValue for RuleActivatorType {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    match self {
+      RuleActivatorType::AllTheTime => context.write_u8(0),
+      RuleActivatorType::OnWeekday => context.write_u8(1),
+      RuleActivatorType::InTimeRange => context.write_u8(2),
+      RuleActivatorType::InWeekdayRange => context.write_u8(3),
+    }
+  }
+}
+
+impl DeserializableScalarValue for RuleActivatorType {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing a web rule RuleActivatorType"))?;
+
+    match number {
+      0 => Ok(RuleActivatorType::AllTheTime),
+      1 => Ok(RuleActivatorType::OnWeekday),
+      2 => Ok(RuleActivatorType::InTimeRange),
+      3 => Ok(RuleActivatorType::InWeekdayRange),
+      _ => {
+        Err(
+          GenericError::new("deserializing a web rule RuleActivatorType")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0, 1, 2, and 3")
+        )
+      }
+    }
+  }
+}
+
+/// The three columns a `RuleActivator` is stored in.
+pub struct RuleActivatorFields {
+  enum_type: String,
+  enum_data_1: String,
+  enum_data_2: String,
+}
+
+impl RuleActivatorFields {
+  pub fn new() -> Self {
+    Self {
+      enum_type: "ActivatorEnumType".into(),
+      enum_data_1: "ActivatorEnumData1".into(),
+      enum_data_2: "ActivatorEnumData2".into(),
+    }
+  }
+
+  pub fn write_define(&self, code: &mut DatabaseCode) {
+    code.write(&self.enum_type);
+    code.write(" INTEGER NOT NULL, ");
+    code.write(&self.enum_data_1);
+    code.write(" INTEGER, ");
+    code.write(&self.enum_data_2);
+    code.write(" INTEGER");
+  }
+
+  pub fn serialize(&self, context: &mut SerializeCompoundValueContext, activator: &RuleActivator) {
+    match activator {
+      RuleActivator::AllTheTime => {
+        context.write_scalar(&self.enum_type, &RuleActivatorType::AllTheTime);
+        context.write_null(&self.enum_data_1);
+        context.write_null(&self.enum_data_2);
+      }
+      RuleActivator::OnWeekday(weekday) => {
+        context.write_scalar(&self.enum_type, &RuleActivatorType::OnWeekday);
+        context.write_scalar(&self.enum_data_1, weekday);
+        context.write_null(&self.enum_data_2);
+      }
+      RuleActivator::InTimeRange(range) => {
+        context.write_scalar(&self.enum_type, &RuleActivatorType::InTimeRange);
+        context.write_u32(&self.enum_data_1, range.from_as_timestamp());
+        context.write_u32(&self.enum_data_2, range.till_as_timestamp());
+      }
+      RuleActivator::InWeekdayRange(range) => {
+        context.write_scalar(&self.enum_type, &RuleActivatorType::InWeekdayRange);
+        context.write_u32(&self.enum_data_1, range.from_as_timestamp());
+        context.write_u32(&self.enum_data_2, range.till_as_timestamp());
+      }
+    }
+  }
+
+  pub fn deserialize(&self, context: &DeserializeCompoundValueContext) -> Result<RuleActivator, GenericError> {
+    let activator_type = context.deserializable_scalar(&self.enum_type)?;
+
+    Ok(match activator_type {
+      RuleActivatorType::AllTheTime => {
+        RuleActivator::AllTheTime
+      }
+      RuleActivatorType::OnWeekday => {
+        RuleActivator::OnWeekday(context.deserializable_scalar(&self.enum_data_1)?)
+      }
+      RuleActivatorType::InTimeRange => {
+        let from = context.deserializable_scalar(&self.enum_data_1)?;
+        let till = context.deserializable_scalar(&self.enum_data_2)?;
+        RuleActivator::InTimeRange(TimeRange::from_timestamps(from, till)?)
+      }
+      RuleActivatorType::InWeekdayRange => {
+        let from = context.deserializable_scalar(&self.enum_data_1)?;
+        let till = context.deserializable_scalar(&self.enum_data_2)?;
+        RuleActivator::InWeekdayRange(WeekdayRange::from_timestamps(from, till)?)
+      }
+    })
+  }
+}
@@ -0,0 +1,219 @@
+use crate::operating_system_integration::UserId;
+use crate::web_regulation_intrusive::safe_search::{Service, Setting};
+use crate::*;
+use super::web_regulation_intrusive_rule_activator::RuleActivatorFields;
+use super::*;
+
+/// The bit of a service in the `Services` column. These mustn't change
+/// once settings are stored with them.
+fn service_bit(service: Service) -> u32 {
+  let number = match service {
+    Service::Google => 0,
+    Service::Bing => 1,
+    Service::Youtube => 2,
+  };
+
+  1 << number
+}
+
+fn serialize_services(services: &[Service]) -> u32 {
+  services
+    .iter()
+    .fold(0, |bits, service| bits | service_bit(*service))
+}
+
+fn deserialize_services(bits: u32) -> Result<Vec<Service>, GenericError> {
+  let known_bits = serialize_services(&Service::ALL);
+  if bits & !known_bits != 0 {
+    return Err(
+      GenericError::new("deserializing SafeSearch services")
+        .add_error("unknown service bits are set")
+        .add_attachment("bits", bits.to_string())
+    );
+  }
+
+  Ok(
+    Service::ALL
+      .into_iter()
+      .filter(|service| bits & service_bit(*service) != 0)
+      .collect()
+  )
+}
+
+pub struct SettingFields {
+  user_id: String,
+  services: String,
+  activator: RuleActivatorFields,
+  protection_duration: String,
+  protection_remaining_duration: String,
+  protection_previous_synchronization_time: String,
+}
+
+pub struct SettingCollection {
+  name: String,
+  fields: SettingFields,
+}
+
+impl SettingCollection {
+  pub fn new(collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: SettingFields {
+        user_id: "UserId".into(),
+        services: "Services".into(),
+        activator: RuleActivatorFields::new(),
+        protection_duration: "ProtectionDuration".into(),
+        protection_remaining_duration: "ProtectionRemainingDuration".into(),
+        protection_previous_synchronization_time: "ProtectionPreviousSynchronizationTime".into(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &SettingCollection {
+  &database.web_regulation_intrusive_safe_search_setting
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.user_id);
+  code.write(" INTEGER PRIMARY KEY, ");
+  code.write(&collection.fields.services);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.protection_duration);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.protection_remaining_duration);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.protection_previous_synchronization_time);
+  code.write(" INTEGER NOT NULL, ");
+  collection.fields.activator.write_define(code);
+  code.write(") WITHOUT ROWID;");
+}
+
+fn serialize_protector(context: &mut SerializeCompoundValueContext, setting: &Setting, fields: &SettingFields) {
+  context.write_scalar(&fields.protection_duration, &setting.protector().duration());
+  context.write_scalar(&fields.protection_remaining_duration, &setting.protector().remaining_duration());
+  context.write_scalar(&fields.protection_previous_synchronization_time, &setting.protector().previous_synchronization_time());
+}
+
+fn deserialize_setting(
+  context: &DeserializeCompoundValueContext,
+  fields: &SettingFields,
+) -> Result<Setting, GenericError> {
+  let user_id: UserId = context.deserializable_scalar(&fields.user_id)?;
+  let services = deserialize_services(context.deserializable_scalar(&fields.services)?)?;
+  let protector = CountdownTimer::from_fields(
+    context.deserializable_scalar(&fields.protection_duration)?,
+    context.deserializable_scalar(&fields.protection_remaining_duration)?,
+    context.deserializable_scalar(&fields.protection_previous_synchronization_time)?,
+  );
+
+  Ok(Setting::from_fields(
+    user_id,
+    services,
+    fields.activator.deserialize(context)?,
+    protector,
+  ))
+}
+
+pub fn add_setting(database: &Database, setting: &Setting) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+
+  let mut context = SerializeCompoundValueContext::new();
+  context.write_scalar(&fields.user_id, &setting.user_id());
+  context.write_u32(&fields.services, serialize_services(setting.services()));
+  fields.activator.serialize(&mut context, setting.activator());
+  serialize_protector(&mut context, setting, fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_setting(database: &Database, user_id: UserId) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.user_id);
+  code.write(" = ");
+  serialize_scalar_value_into(&user_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn update_protector(database: &Database, setting: &Setting) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+
+  let mut context = SerializeCompoundValueContext::new();
+  serialize_protector(&mut context, setting, fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("UPDATE ");
+  code.write(&collection.name);
+  code.write(" SET (");
+  code.write(&context.column_names);
+  code.write(") = (");
+  code.write(&context.column_values);
+  code.write(") WHERE ");
+  code.write(&fields.user_id);
+  code.write(" = ");
+  serialize_scalar_value_into(&setting.user_id(), code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn retrieve_all_settings(database: &Database) -> Result<Vec<Setting>, GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all SafeSearch settings")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all SafeSearch settings")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut settings = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all SafeSearch settings")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(settings);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    settings.push(deserialize_setting(&context, &collection.fields)?);
+  }
+}
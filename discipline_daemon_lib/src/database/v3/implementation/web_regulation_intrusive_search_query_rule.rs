@@ -0,0 +1,303 @@
+use crate::operating_system_integration::UserId;
+use crate::web_regulation_intrusive::search_queries::{Action, CustomEngine, Engine, Rule};
+use crate::*;
+use super::web_regulation_intrusive_content_filter_rule::{
+  deserialize_keywords,
+  deserialize_languages,
+  serialize_keywords,
+  serialize_languages,
+};
+use super::web_regulation_intrusive_rule_activator::RuleActivatorFields;
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionType {
+  Block,
+  Redirect,
+}
+
+impl SerializableScalarValue for ActionType {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    match self {
+      ActionType::Block => context.write_u8(0),
+      ActionType::Redirect => context.write_u8(1),
+    }
+  }
+}
+
+impl DeserializableScalarValue for ActionType {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing a search query rule ActionType"))?;
+
+    match number {
+      0 => Ok(ActionType::Block),
+      1 => Ok(ActionType::Redirect),
+      _ => {
+        Err(
+          GenericError::new("deserializing a search query rule ActionType")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0 and 1")
+        )
+      }
+    }
+  }
+}
+
+/// The bit of an engine in the `Engines` column. These mustn't change once
+/// rules are stored with them.
+fn engine_bit(engine: Engine) -> u32 {
+  let number = match engine {
+    Engine::Google => 0,
+    Engine::Bing => 1,
+    Engine::DuckDuckGo => 2,
+    Engine::Youtube => 3,
+  };
+
+  1 << number
+}
+
+fn serialize_engines(engines: &[Engine]) -> u32 {
+  engines
+    .iter()
+    .fold(0, |bits, engine| bits | engine_bit(*engine))
+}
+
+fn deserialize_engines(bits: u32) -> Result<Vec<Engine>, GenericError> {
+  let known_bits = serialize_engines(&Engine::ALL);
+  if bits & !known_bits != 0 {
+    return Err(
+      GenericError::new("deserializing search query rule engines")
+        .add_error("unknown engine bits are set")
+        .add_attachment("bits", bits.to_string())
+    );
+  }
+
+  Ok(
+    Engine::ALL
+      .into_iter()
+      .filter(|engine| bits & engine_bit(*engine) != 0)
+      .collect()
+  )
+}
+
+pub struct RuleFields {
+  id: String,
+  user_id: String,
+  engines: String,
+  custom_engine_url_pattern: String,
+  custom_engine_query_parameter: String,
+  languages: String,
+  keywords: String,
+  action_enum_type: String,
+  action_redirect_url: String,
+  activator: RuleActivatorFields,
+}
+
+pub struct RuleCollection {
+  name: String,
+  fields: RuleFields,
+}
+
+impl RuleCollection {
+  pub fn new(collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: RuleFields {
+        id: "Id".into(),
+        user_id: "UserId".into(),
+        engines: "Engines".into(),
+        custom_engine_url_pattern: "CustomEngineUrlPattern".into(),
+        custom_engine_query_parameter: "CustomEngineQueryParameter".into(),
+        languages: "Languages".into(),
+        keywords: "Keywords".into(),
+        action_enum_type: "ActionEnumType".into(),
+        action_redirect_url: "ActionRedirectUrl".into(),
+        activator: RuleActivatorFields::new(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &RuleCollection {
+  &database.web_regulation_intrusive_search_query_rule
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.id);
+  code.write(" TEXT PRIMARY KEY, ");
+  code.write(&collection.fields.user_id);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.engines);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.custom_engine_url_pattern);
+  code.write(" TEXT, ");
+  code.write(&collection.fields.custom_engine_query_parameter);
+  code.write(" TEXT, ");
+  code.write(&collection.fields.languages);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.keywords);
+  code.write(" TEXT NOT NULL, ");
+  code.write(&collection.fields.action_enum_type);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.action_redirect_url);
+  code.write(" TEXT, ");
+  collection.fields.activator.write_define(code);
+  code.write(") WITHOUT ROWID;");
+}
+
+fn serialize_rule(context: &mut SerializeCompoundValueContext, rule: &Rule, fields: &RuleFields) {
+  context.write_scalar(&fields.id, rule.id());
+  context.write_scalar(&fields.user_id, &rule.user_id());
+  context.write_u32(&fields.engines, serialize_engines(rule.engines()));
+
+  match rule.custom_engine() {
+    Some(custom_engine) => {
+      context.write_scalar(&fields.custom_engine_url_pattern, custom_engine.url_pattern());
+      context.write_string(&fields.custom_engine_query_parameter, &custom_engine.query_parameter().to_string());
+    }
+    None => {
+      context.write_null(&fields.custom_engine_url_pattern);
+      context.write_null(&fields.custom_engine_query_parameter);
+    }
+  }
+
+  context.write_u32(&fields.languages, serialize_languages(rule.languages()));
+  context.write_string(&fields.keywords, &serialize_keywords(rule.keywords()));
+
+  match rule.action() {
+    Action::Block => {
+      context.write_scalar(&fields.action_enum_type, &ActionType::Block);
+      context.write_null(&fields.action_redirect_url);
+    }
+    Action::Redirect { url } => {
+      context.write_scalar(&fields.action_enum_type, &ActionType::Redirect);
+      context.write_string(&fields.action_redirect_url, url);
+    }
+  }
+
+  fields.activator.serialize(context, rule.activator());
+}
+
+fn deserialize_rule(context: &DeserializeCompoundValueContext, fields: &RuleFields) -> Result<Rule, GenericError> {
+  let id: Uuid = context.deserializable_scalar(&fields.id)?;
+  let user_id: UserId = context.deserializable_scalar(&fields.user_id)?;
+  let engines = deserialize_engines(context.deserializable_scalar(&fields.engines)?)?;
+
+  let custom_engine_url_pattern = context.deserializable_scalar(&fields.custom_engine_url_pattern)?;
+  let custom_engine = match custom_engine_url_pattern {
+    Some(url_pattern) => {
+      let custom_engine = CustomEngine::new(
+        url_pattern,
+        context.deserializable_scalar(&fields.custom_engine_query_parameter)?,
+      ).map_err(|error| error.change_context("deserializing a search query rule"))?;
+
+      Some(custom_engine)
+    }
+    None => {
+      None
+    }
+  };
+
+  let languages = deserialize_languages(context.deserializable_scalar(&fields.languages)?)?;
+  let keywords = deserialize_keywords(context.deserializable_scalar(&fields.keywords)?);
+
+  let action_type = context.deserializable_scalar(&fields.action_enum_type)?;
+  let action = match action_type {
+    ActionType::Block => {
+      Action::Block
+    }
+    ActionType::Redirect => {
+      Action::Redirect { url: context.deserializable_scalar(&fields.action_redirect_url)? }
+    }
+  };
+
+  Ok(Rule::from_fields(
+    id,
+    user_id,
+    engines,
+    custom_engine,
+    languages,
+    keywords,
+    action,
+    fields.activator.deserialize(context)?,
+  ))
+}
+
+pub fn add_rule(database: &Database, rule: &Rule) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut context = SerializeCompoundValueContext::new();
+  serialize_rule(&mut context, rule, &collection.fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_rule(database: &Database, rule_id: &Uuid) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.id);
+  code.write(" = ");
+  serialize_scalar_value_into(rule_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn retrieve_all_rules(database: &Database) -> Result<Vec<Rule>, GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all search query rules")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all search query rules")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut rules = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all search query rules")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(rules);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    rules.push(deserialize_rule(&context, &collection.fields)?);
+  }
+}
@@ -0,0 +1,185 @@
+use crate::operating_system_integration::UserId;
+use crate::web_regulation_intrusive::twitter::{Filter, FilterKind, PostId, ScreenName};
+use crate::*;
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterKindType {
+  BlockedUser,
+  BlockedPost,
+  AllowedUser,
+}
+
+impl SerializableScalarValue for FilterKindType {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    match self {
+      FilterKindType::BlockedUser => context.write_u8(0),
+      FilterKindType::BlockedPost => context.write_u8(1),
+      FilterKindType::AllowedUser => context.write_u8(2),
+    }
+  }
+}
+
+impl DeserializableScalarValue for FilterKindType {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing a Twitter FilterKindType"))?;
+
+    match number {
+      0 => Ok(FilterKindType::BlockedUser),
+      1 => Ok(FilterKindType::BlockedPost),
+      2 => Ok(FilterKindType::AllowedUser),
+      _ => {
+        Err(
+          GenericError::new("deserializing a Twitter FilterKindType")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0, 1, and 2")
+        )
+      }
+    }
+  }
+}
+
+pub struct FilterFields {
+  id: String,
+  user_id: String,
+  kind_enum_type: String,
+  kind_enum_data: String,
+}
+
+pub struct FilterCollection {
+  name: String,
+  fields: FilterFields,
+}
+
+impl FilterCollection {
+  pub fn new(collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: FilterFields {
+        id: "Id".into(),
+        user_id: "UserId".into(),
+        kind_enum_type: "KindEnumType".into(),
+        kind_enum_data: "KindEnumData".into(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &FilterCollection {
+  &database.web_regulation_intrusive_twitter_filter
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.id);
+  code.write(" TEXT PRIMARY KEY, ");
+  code.write(&collection.fields.user_id);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.kind_enum_type);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.kind_enum_data);
+  code.write(" TEXT NOT NULL) WITHOUT ROWID;");
+}
+
+fn deserialize_filter(context: &DeserializeCompoundValueContext, fields: &FilterFields) -> Result<Filter, GenericError> {
+  let id: Uuid = context.deserializable_scalar(&fields.id)?;
+  let user_id: UserId = context.deserializable_scalar(&fields.user_id)?;
+  let kind_type = context.deserializable_scalar(&fields.kind_enum_type)?;
+  let data: String = context.deserializable_scalar(&fields.kind_enum_data)?;
+
+  let kind = match kind_type {
+    FilterKindType::BlockedUser => FilterKind::BlockedUser(ScreenName::new(data)?),
+    FilterKindType::BlockedPost => FilterKind::BlockedPost(PostId::new(data)?),
+    FilterKindType::AllowedUser => FilterKind::AllowedUser(ScreenName::new(data)?),
+  };
+
+  Ok(Filter::from_fields(id, user_id, kind))
+}
+
+pub fn add_filter(database: &Database, filter: &Filter) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+
+  let (kind_type, data) = match filter.kind() {
+    FilterKind::BlockedUser(screen_name) => (FilterKindType::BlockedUser, screen_name.as_str()),
+    FilterKind::BlockedPost(post_id) => (FilterKindType::BlockedPost, post_id.as_str()),
+    FilterKind::AllowedUser(screen_name) => (FilterKindType::AllowedUser, screen_name.as_str()),
+  };
+
+  let mut context = SerializeCompoundValueContext::new();
+  context.write_scalar(&fields.id, filter.id());
+  context.write_scalar(&fields.user_id, &filter.user_id());
+  context.write_scalar(&fields.kind_enum_type, &kind_type);
+  context.write_string(&fields.kind_enum_data, &data.to_string());
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_filter(database: &Database, filter_id: &Uuid) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.id);
+  code.write(" = ");
+  serialize_scalar_value_into(filter_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn retrieve_all_filters(database: &Database) -> Result<Vec<Filter>, GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all Twitter filters")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all Twitter filters")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut filters = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all Twitter filters")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(filters);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    filters.push(deserialize_filter(&context, &collection.fields)?);
+  }
+}
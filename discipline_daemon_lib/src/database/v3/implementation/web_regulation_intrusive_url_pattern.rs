@@ -0,0 +1,48 @@
+use crate::web_regulation_intrusive::{HostPattern, PathPrefix, UrlPattern};
+use crate::*;
+use super::*;
+
+impl SerializableScalarValue for HostPattern {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    context.write_string(&self.as_str().to_string());
+  }
+}
+
+impl DeserializableScalarValue for HostPattern {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    value
+      .as_string()
+      .and_then(|string| HostPattern::parse(&string))
+      .map_err(|error| error.change_context("deserializing a HostPattern"))
+  }
+}
+
+impl SerializableScalarValue for PathPrefix {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    context.write_string(&self.as_str().to_string());
+  }
+}
+
+impl DeserializableScalarValue for PathPrefix {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    value
+      .as_string()
+      .and_then(PathPrefix::new)
+      .map_err(|error| error.change_context("deserializing a PathPrefix"))
+  }
+}
+
+impl SerializableScalarValue for UrlPattern {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    context.write_string(&self.as_str().to_string());
+  }
+}
+
+impl DeserializableScalarValue for UrlPattern {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    value
+      .as_string()
+      .and_then(|string| UrlPattern::parse(&string))
+      .map_err(|error| error.change_context("deserializing a UrlPattern"))
+  }
+}
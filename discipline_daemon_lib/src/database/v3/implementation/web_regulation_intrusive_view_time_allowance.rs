@@ -0,0 +1,192 @@
+use crate::operating_system_integration::UserId;
+use crate::web_regulation_intrusive::view_time_allowance::Allowance;
+use crate::web_regulation_intrusive::UrlPattern;
+use crate::*;
+use super::*;
+
+fn serialize_url_patterns(url_patterns: &[UrlPattern]) -> String {
+  url_patterns
+    .iter()
+    .map(UrlPattern::as_str)
+    .collect::<Vec<&str>>()
+    .join("\n")
+}
+
+fn deserialize_url_patterns(url_patterns: String) -> Result<Vec<UrlPattern>, GenericError> {
+  url_patterns
+    .lines()
+    .filter(|url_pattern| !url_pattern.is_empty())
+    .map(UrlPattern::parse)
+    .collect::<Result<Vec<UrlPattern>, GenericError>>()
+    .map_err(|error| error.change_context("deserializing view time allowance url patterns"))
+}
+
+pub struct AllowanceFields {
+  id: String,
+  user_id: String,
+  url_patterns: String,
+  daily_allowance: String,
+  used_today: String,
+  day: String,
+}
+
+pub struct AllowanceCollection {
+  name: String,
+  fields: AllowanceFields,
+}
+
+impl AllowanceCollection {
+  pub fn new(collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: AllowanceFields {
+        id: "Id".into(),
+        user_id: "UserId".into(),
+        url_patterns: "UrlPatterns".into(),
+        daily_allowance: "DailyAllowance".into(),
+        used_today: "UsedToday".into(),
+        day: "Day".into(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &AllowanceCollection {
+  &database.web_regulation_intrusive_view_time_allowance
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.id);
+  code.write(" TEXT PRIMARY KEY, ");
+  code.write(&collection.fields.user_id);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.url_patterns);
+  code.write(" TEXT NOT NULL, ");
+  code.write(&collection.fields.daily_allowance);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.used_today);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.day);
+  code.write(" INTEGER NOT NULL) WITHOUT ROWID;");
+}
+
+fn serialize_usage(context: &mut SerializeCompoundValueContext, allowance: &Allowance, fields: &AllowanceFields) {
+  context.write_scalar(&fields.used_today, &allowance.used_today());
+  context.write_scalar(&fields.day, &allowance.day());
+}
+
+pub fn add_allowance(database: &Database, allowance: &Allowance) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+
+  let mut context = SerializeCompoundValueContext::new();
+  context.write_scalar(&fields.id, allowance.id());
+  context.write_scalar(&fields.user_id, &allowance.user_id());
+  context.write_string(&fields.url_patterns, &serialize_url_patterns(allowance.url_patterns()));
+  context.write_scalar(&fields.daily_allowance, &allowance.daily_allowance());
+  serialize_usage(&mut context, allowance, fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_allowance(database: &Database, allowance_id: &Uuid) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.id);
+  code.write(" = ");
+  serialize_scalar_value_into(allowance_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+/// Stores how much of `allowance` was used and on which day.
+pub fn update_usage(database: &Database, allowance: &Allowance) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+
+  let mut context = SerializeCompoundValueContext::new();
+  serialize_usage(&mut context, allowance, fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("UPDATE ");
+  code.write(&collection.name);
+  code.write(" SET (");
+  code.write(&context.column_names);
+  code.write(") = (");
+  code.write(&context.column_values);
+  code.write(") WHERE ");
+  code.write(&fields.id);
+  code.write(" = ");
+  serialize_scalar_value_into(allowance.id(), code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+fn deserialize_allowance(context: &DeserializeCompoundValueContext, fields: &AllowanceFields) -> Result<Allowance, GenericError> {
+  Ok(Allowance::from_fields(
+    context.deserializable_scalar(&fields.id)?,
+    context.deserializable_scalar::<UserId>(&fields.user_id)?,
+    deserialize_url_patterns(context.deserializable_scalar(&fields.url_patterns)?)?,
+    context.deserializable_scalar(&fields.daily_allowance)?,
+    context.deserializable_scalar(&fields.used_today)?,
+    context.deserializable_scalar(&fields.day)?,
+  ))
+}
+
+pub fn retrieve_all_allowances(database: &Database) -> Result<Vec<Allowance>, GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all view time allowances")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all view time allowances")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut allowances = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all view time allowances")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(allowances);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    allowances.push(deserialize_allowance(&context, &collection.fields)?);
+  }
+}
@@ -0,0 +1,264 @@
+use crate::operating_system_integration::UserId;
+use crate::web_regulation_intrusive::website_visit_delayer::{Delayer, Phase};
+use crate::*;
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhaseType {
+  Idle,
+  Blocking,
+  Allowing,
+}
+
+impl SerializableScalarValue for PhaseType {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    match self {
+      PhaseType::Idle => context.write_u8(0),
+      PhaseType::Blocking => context.write_u8(1),
+      PhaseType::Allowing => context.write_u8(2),
+    }
+  }
+}
+
+impl DeserializableScalarValue for PhaseType {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing a website visit delayer PhaseType"))?;
+
+    match number {
+      0 => Ok(PhaseType::Idle),
+      1 => Ok(PhaseType::Blocking),
+      2 => Ok(PhaseType::Allowing),
+      _ => {
+        Err(
+          GenericError::new("deserializing a website visit delayer PhaseType")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0, 1, and 2")
+        )
+      }
+    }
+  }
+}
+
+pub struct DelayerFields {
+  id: String,
+  user_id: String,
+  url_pattern: String,
+  block_for: String,
+  allow_for: String,
+  phase_enum_type: String,
+  phase_timer_remaining_duration: String,
+  phase_timer_previous_synchronization_time: String,
+}
+
+pub struct DelayerCollection {
+  name: String,
+  fields: DelayerFields,
+}
+
+impl DelayerCollection {
+  pub fn new(collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: DelayerFields {
+        id: "Id".into(),
+        user_id: "UserId".into(),
+        url_pattern: "UrlPattern".into(),
+        block_for: "BlockFor".into(),
+        allow_for: "AllowFor".into(),
+        phase_enum_type: "PhaseEnumType".into(),
+        phase_timer_remaining_duration: "PhaseTimerRemainingDuration".into(),
+        phase_timer_previous_synchronization_time: "PhaseTimerPreviousSynchronizationTime".into(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &DelayerCollection {
+  &database.web_regulation_intrusive_website_visit_delayer
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.id);
+  code.write(" TEXT PRIMARY KEY, ");
+  code.write(&collection.fields.user_id);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.url_pattern);
+  code.write(" TEXT NOT NULL, ");
+  code.write(&collection.fields.block_for);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.allow_for);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.phase_enum_type);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.phase_timer_remaining_duration);
+  code.write(", ");
+  code.write(&collection.fields.phase_timer_previous_synchronization_time);
+  code.write(") WITHOUT ROWID;");
+}
+
+fn serialize_phase(context: &mut SerializeCompoundValueContext, phase: &Phase, fields: &DelayerFields) {
+  let (phase_type, timer) = match phase {
+    Phase::Idle => (PhaseType::Idle, None),
+    Phase::Blocking(timer) => (PhaseType::Blocking, Some(timer)),
+    Phase::Allowing(timer) => (PhaseType::Allowing, Some(timer)),
+  };
+
+  context.write_scalar(&fields.phase_enum_type, &phase_type);
+  match timer {
+    Some(timer) => {
+      context.write_scalar(&fields.phase_timer_remaining_duration, &timer.remaining_duration());
+      context.write_scalar(&fields.phase_timer_previous_synchronization_time, &timer.previous_synchronization_time());
+    }
+    None => {
+      context.write_null(&fields.phase_timer_remaining_duration);
+      context.write_null(&fields.phase_timer_previous_synchronization_time);
+    }
+  }
+}
+
+fn deserialize_delayer(
+  context: &DeserializeCompoundValueContext,
+  fields: &DelayerFields,
+) -> Result<Delayer, GenericError> {
+  let id: Uuid = context.deserializable_scalar(&fields.id)?;
+  let user_id: UserId = context.deserializable_scalar(&fields.user_id)?;
+  let block_for = context.deserializable_scalar(&fields.block_for)?;
+  let allow_for = context.deserializable_scalar(&fields.allow_for)?;
+  let phase_type = context.deserializable_scalar(&fields.phase_enum_type)?;
+
+  let phase = match phase_type {
+    PhaseType::Idle => {
+      Phase::Idle
+    }
+    PhaseType::Blocking | PhaseType::Allowing => {
+      let timer = CountdownTimer::from_fields(
+        if phase_type == PhaseType::Blocking { block_for } else { allow_for },
+        context.deserializable_scalar(&fields.phase_timer_remaining_duration)?,
+        context.deserializable_scalar(&fields.phase_timer_previous_synchronization_time)?,
+      );
+
+      if phase_type == PhaseType::Blocking {
+        Phase::Blocking(timer)
+      } else {
+        Phase::Allowing(timer)
+      }
+    }
+  };
+
+  Ok(Delayer::from_fields(
+    id,
+    user_id,
+    context.deserializable_scalar(&fields.url_pattern)?,
+    block_for,
+    allow_for,
+    phase,
+  ))
+}
+
+pub fn add_delayer(database: &Database, delayer: &Delayer) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+
+  let mut context = SerializeCompoundValueContext::new();
+  context.write_scalar(&fields.id, delayer.id());
+  context.write_scalar(&fields.user_id, &delayer.user_id());
+  context.write_scalar(&fields.url_pattern, delayer.url_pattern());
+  context.write_scalar(&fields.block_for, &delayer.block_for());
+  context.write_scalar(&fields.allow_for, &delayer.allow_for());
+  serialize_phase(&mut context, delayer.phase(), fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_delayer(database: &Database, delayer_id: &Uuid) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.id);
+  code.write(" = ");
+  serialize_scalar_value_into(delayer_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn update_phase(database: &Database, delayer: &Delayer) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+
+  let mut context = SerializeCompoundValueContext::new();
+  serialize_phase(&mut context, delayer.phase(), fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("UPDATE ");
+  code.write(&collection.name);
+  code.write(" SET (");
+  code.write(&context.column_names);
+  code.write(") = (");
+  code.write(&context.column_values);
+  code.write(") WHERE ");
+  code.write(&fields.id);
+  code.write(" = ");
+  serialize_scalar_value_into(delayer.id(), code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn retrieve_all_delayers(database: &Database) -> Result<Vec<Delayer>, GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all website visit delayers")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all website visit delayers")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut delayers = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all website visit delayers")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(delayers);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    delayers.push(deserialize_delayer(&context, &collection.fields)?);
+  }
+}
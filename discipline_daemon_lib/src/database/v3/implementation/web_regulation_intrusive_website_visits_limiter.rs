@@ -0,0 +1,285 @@
+use std::collections::{HashMap, VecDeque};
+use crate::operating_system_integration::UserId;
+use crate::web_regulation_intrusive::website_visits_limiter::{Limiter, WindowKind};
+use crate::*;
+use super::*;
+
+impl SerializableScalarValue for WindowKind {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    match self {
+      WindowKind::Rolling => context.write_u8(0),
+      WindowKind::Fixed => context.write_u8(1),
+    }
+  }
+}
+
+impl DeserializableScalarValue for WindowKind {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing a WindowKind"))?;
+
+    match number {
+      0 => Ok(WindowKind::Rolling),
+      1 => Ok(WindowKind::Fixed),
+      _ => {
+        Err(
+          GenericError::new("deserializing a WindowKind")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0 and 1")
+        )
+      }
+    }
+  }
+}
+
+pub struct LimiterFields {
+  id: String,
+  user_id: String,
+  url_pattern: String,
+  maximum_visits: String,
+  window: String,
+  window_kind: String,
+}
+
+pub struct VisitFields {
+  limiter_id: String,
+  time: String,
+}
+
+/// Limiters live in one table and the visits counted against them in
+/// another, so recording a visit is a single insert.
+pub struct LimiterCollection {
+  name: String,
+  fields: LimiterFields,
+  visits_name: String,
+  visit_fields: VisitFields,
+}
+
+impl LimiterCollection {
+  pub fn new(collection_name: String, visits_collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: LimiterFields {
+        id: "Id".into(),
+        user_id: "UserId".into(),
+        url_pattern: "UrlPattern".into(),
+        maximum_visits: "MaximumVisits".into(),
+        window: "Window".into(),
+        window_kind: "WindowKind".into(),
+      },
+      visits_name: visits_collection_name,
+      visit_fields: VisitFields {
+        limiter_id: "LimiterId".into(),
+        time: "Time".into(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &LimiterCollection {
+  &database.web_regulation_intrusive_website_visits_limiter
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.id);
+  code.write(" TEXT PRIMARY KEY, ");
+  code.write(&collection.fields.user_id);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.url_pattern);
+  code.write(" TEXT NOT NULL, ");
+  code.write(&collection.fields.maximum_visits);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.window);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.window_kind);
+  code.write(" INTEGER NOT NULL) WITHOUT ROWID;");
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.visits_name);
+  code.write(" (");
+  code.write(&collection.visit_fields.limiter_id);
+  code.write(" TEXT NOT NULL, ");
+  code.write(&collection.visit_fields.time);
+  code.write(" INTEGER NOT NULL);");
+}
+
+pub fn add_limiter(database: &Database, limiter: &Limiter) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+
+  let mut context = SerializeCompoundValueContext::new();
+  context.write_scalar(&fields.id, limiter.id());
+  context.write_scalar(&fields.user_id, &limiter.user_id());
+  context.write_scalar(&fields.url_pattern, limiter.url_pattern());
+  context.write_u32(&fields.maximum_visits, limiter.maximum_visits());
+  context.write_scalar(&fields.window, &limiter.window());
+  context.write_scalar(&fields.window_kind, &limiter.window_kind());
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_limiter(database: &Database, limiter_id: &Uuid) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.id);
+  code.write(" = ");
+  serialize_scalar_value_into(limiter_id, code.as_mut());
+  code.write(";");
+
+  code.write("DELETE FROM ");
+  code.write(&collection.visits_name);
+  code.write(" WHERE ");
+  code.write(&collection.visit_fields.limiter_id);
+  code.write(" = ");
+  serialize_scalar_value_into(limiter_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+/// Stores a visit `limiter` just recorded and forgets the visits it no
+/// longer counts.
+pub fn record_visit(database: &Database, limiter: &Limiter, now: DateTime) -> Result<(), GenericError> {
+  let collection = collection(database);
+  let fields = &collection.visit_fields;
+  let oldest_counted_visit = limiter.visits().front().copied().unwrap_or(now);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.visits_name);
+  code.write(" WHERE ");
+  code.write(&fields.limiter_id);
+  code.write(" = ");
+  serialize_scalar_value_into(limiter.id(), code.as_mut());
+  code.write(" AND ");
+  code.write(&fields.time);
+  code.write(" < ");
+  serialize_scalar_value_into(&oldest_counted_visit, code.as_mut());
+  code.write(";");
+
+  let mut context = SerializeCompoundValueContext::new();
+  context.write_scalar(&fields.limiter_id, limiter.id());
+  context.write_scalar(&fields.time, &now);
+
+  code.write("INSERT INTO ");
+  code.write(&collection.visits_name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+fn retrieve_all_visits(database: &Database) -> Result<HashMap<Uuid, VecDeque<DateTime>>, GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.visits_name);
+  code.write(" ORDER BY ");
+  code.write(&collection.visit_fields.time);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all website visits")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all website visits")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut visits: HashMap<Uuid, VecDeque<DateTime>> = HashMap::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all website visits")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(visits);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    let limiter_id = context.deserializable_scalar(&collection.visit_fields.limiter_id)?;
+    let time = context.deserializable_scalar(&collection.visit_fields.time)?;
+    visits.entry(limiter_id).or_default().push_back(time);
+  }
+}
+
+pub fn retrieve_all_limiters(database: &Database) -> Result<Vec<Limiter>, GenericError> {
+  let collection = collection(database);
+  let fields = &collection.fields;
+  let mut visits = retrieve_all_visits(database)?;
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all website visits limiters")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all website visits limiters")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut limiters = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all website visits limiters")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(limiters);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    let id: Uuid = context.deserializable_scalar(&fields.id)?;
+    let user_id: UserId = context.deserializable_scalar(&fields.user_id)?;
+    limiters.push(Limiter::from_fields(
+      id,
+      user_id,
+      context.deserializable_scalar(&fields.url_pattern)?,
+      context.deserializable_scalar(&fields.maximum_visits)?,
+      context.deserializable_scalar(&fields.window)?,
+      context.deserializable_scalar(&fields.window_kind)?,
+      visits.remove(&id).unwrap_or_default(),
+    ));
+  }
+}
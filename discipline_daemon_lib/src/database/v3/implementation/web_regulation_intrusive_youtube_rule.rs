@@ -0,0 +1,252 @@
+use crate::operating_system_integration::UserId;
+use crate::web_regulation_intrusive::youtube::conditions::*;
+use crate::web_regulation_intrusive::youtube::{CategoryID, Condition, Rule};
+use crate::*;
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConditionType {
+  VideoId,
+  VideoName,
+  ChannelId,
+  ChannelName,
+  VideoCategory,
+  ChannelCategory,
+}
+
+impl SerializableScalarValue for ConditionType {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    match self {
+      ConditionType::VideoId => context.write_u8(0),
+      ConditionType::VideoName => context.write_u8(1),
+      ConditionType::ChannelId => context.write_u8(2),
+      ConditionType::ChannelName => context.write_u8(3),
+      ConditionType::VideoCategory => context.write_u8(4),
+      ConditionType::ChannelCategory => context.write_u8(5),
+    }
+  }
+}
+
+impl DeserializableScalarValue for ConditionType {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing a YouTube ConditionType"))?;
+
+    match number {
+      0 => Ok(ConditionType::VideoId),
+      1 => Ok(ConditionType::VideoName),
+      2 => Ok(ConditionType::ChannelId),
+      3 => Ok(ConditionType::ChannelName),
+      4 => Ok(ConditionType::VideoCategory),
+      5 => Ok(ConditionType::ChannelCategory),
+      _ => {
+        Err(
+          GenericError::new("deserializing a YouTube ConditionType")
+            .add_error("unknown variant number")
+            .add_attachment("variant", number.to_string())
+            .add_attachment("known variant numbers", "0, 1, 2, 3, 4, and 5")
+        )
+      }
+    }
+  }
+}
+
+impl SerializableScalarValue for CategoryID {
+  fn serialize(&self, context: &mut SerializeScalarValueContext) {
+    context.write_u8(self.number());
+  }
+}
+
+impl DeserializableScalarValue for CategoryID {
+  fn deserialize(value: ScalarValue) -> Result<Self, GenericError> {
+    let number = value
+      .as_u8()
+      .map_err(|error| error.change_context("deserializing a YouTube CategoryID"))?;
+
+    CategoryID::from_number(number).ok_or_else(||
+      GenericError::new("deserializing a YouTube CategoryID")
+        .add_error("number isn't a known category id")
+        .add_attachment("number", number.to_string())
+    )
+  }
+}
+
+pub struct RuleFields {
+  id: String,
+  user_id: String,
+  condition_enum_type: String,
+  condition_enum_data: String,
+}
+
+pub struct RuleCollection {
+  name: String,
+  fields: RuleFields,
+}
+
+impl RuleCollection {
+  pub fn new(collection_name: String) -> Self {
+    Self {
+      name: collection_name,
+      fields: RuleFields {
+        id: "Id".into(),
+        user_id: "UserId".into(),
+        condition_enum_type: "ConditionEnumType".into(),
+        condition_enum_data: "ConditionEnumData".into(),
+      },
+    }
+  }
+}
+
+fn collection(database: &Database) -> &RuleCollection {
+  &database.web_regulation_intrusive_youtube_rule
+}
+
+pub fn write_define(database: &Database, code: &mut DatabaseCode) {
+  let collection = collection(database);
+
+  code.write("CREATE TABLE IF NOT EXISTS ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&collection.fields.id);
+  code.write(" TEXT PRIMARY KEY, ");
+  code.write(&collection.fields.user_id);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.condition_enum_type);
+  code.write(" INTEGER NOT NULL, ");
+  code.write(&collection.fields.condition_enum_data);
+  code.write(" NOT NULL) WITHOUT ROWID;");
+}
+
+fn serialize_rule(context: &mut SerializeCompoundValueContext, rule: &Rule, fields: &RuleFields) {
+  context.write_scalar(&fields.id, rule.id());
+  context.write_scalar(&fields.user_id, &rule.user_id());
+
+  match rule.condition() {
+    Condition::VideoId(condition) => {
+      context.write_scalar(&fields.condition_enum_type, &ConditionType::VideoId);
+      context.write_string(&fields.condition_enum_data, condition.video_id());
+    }
+    Condition::VideoName(condition) => {
+      context.write_scalar(&fields.condition_enum_type, &ConditionType::VideoName);
+      context.write_string(&fields.condition_enum_data, condition.text());
+    }
+    Condition::ChannelId(condition) => {
+      context.write_scalar(&fields.condition_enum_type, &ConditionType::ChannelId);
+      context.write_string(&fields.condition_enum_data, condition.channel_id());
+    }
+    Condition::ChannelName(condition) => {
+      context.write_scalar(&fields.condition_enum_type, &ConditionType::ChannelName);
+      context.write_string(&fields.condition_enum_data, condition.name());
+    }
+    Condition::VideoCategory(condition) => {
+      context.write_scalar(&fields.condition_enum_type, &ConditionType::VideoCategory);
+      context.write_scalar(&fields.condition_enum_data, &condition.category());
+    }
+    Condition::ChannelCategory(condition) => {
+      context.write_scalar(&fields.condition_enum_type, &ConditionType::ChannelCategory);
+      context.write_scalar(&fields.condition_enum_data, &condition.category());
+    }
+  }
+}
+
+fn deserialize_rule(context: &DeserializeCompoundValueContext, fields: &RuleFields) -> Result<Rule, GenericError> {
+  let id: Uuid = context.deserializable_scalar(&fields.id)?;
+  let user_id: UserId = context.deserializable_scalar(&fields.user_id)?;
+  let condition_type = context.deserializable_scalar(&fields.condition_enum_type)?;
+  let data = &fields.condition_enum_data;
+
+  let condition = match condition_type {
+    ConditionType::VideoId => {
+      Condition::VideoId(video_id::Condition::new(context.deserializable_scalar(data)?)?)
+    }
+    ConditionType::VideoName => {
+      Condition::VideoName(video_name::Condition::new(context.deserializable_scalar(data)?)?)
+    }
+    ConditionType::ChannelId => {
+      Condition::ChannelId(channel_id::Condition::new(context.deserializable_scalar(data)?)?)
+    }
+    ConditionType::ChannelName => {
+      Condition::ChannelName(channel_name::Condition::new(context.deserializable_scalar(data)?)?)
+    }
+    ConditionType::VideoCategory => {
+      Condition::VideoCategory(video_category::Condition::new(context.deserializable_scalar(data)?))
+    }
+    ConditionType::ChannelCategory => {
+      Condition::ChannelCategory(channel_category::Condition::new(context.deserializable_scalar(data)?))
+    }
+  };
+
+  Ok(Rule::from_fields(id, user_id, condition))
+}
+
+pub fn add_rule(database: &Database, rule: &Rule) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut context = SerializeCompoundValueContext::new();
+  serialize_rule(&mut context, rule, &collection.fields);
+
+  let mut code = DatabaseCode::new();
+  code.write("INSERT INTO ");
+  code.write(&collection.name);
+  code.write(" (");
+  code.write(&context.column_names);
+  code.write(") VALUES (");
+  code.write(&context.column_values);
+  code.write(");");
+
+  database.execute(code.as_str())
+}
+
+pub fn delete_rule(database: &Database, rule_id: &Uuid) -> Result<(), GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("DELETE FROM ");
+  code.write(&collection.name);
+  code.write(" WHERE ");
+  code.write(&collection.fields.id);
+  code.write(" = ");
+  serialize_scalar_value_into(rule_id, code.as_mut());
+  code.write(";");
+
+  database.execute(code.as_str())
+}
+
+pub fn retrieve_all_rules(database: &Database) -> Result<Vec<Rule>, GenericError> {
+  let collection = collection(database);
+
+  let mut code = DatabaseCode::new();
+  code.write("SELECT * FROM ");
+  code.write(&collection.name);
+  code.write(";");
+
+  let connection = database.connection.lock().unwrap();
+  let mut statement = connection.prepare(code.as_str()).map_err(|error|
+    GenericError::new("retrieving all YouTube rules")
+      .add_error("failed to prepare statement")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+  let mut iterator = statement.query(()).map_err(|error|
+    GenericError::new("retrieving all YouTube rules")
+      .add_error("failed to run query code")
+      .add_attachment("code", code.as_str())
+      .add_attachment("sqlite error", error.to_string())
+  )?;
+
+  let mut rules = Vec::new();
+  loop {
+    let item = iterator.next().map_err(|error|
+      GenericError::new("retrieving all YouTube rules")
+        .add_error("retreiving the next item of sqlite iterator")
+        .add_attachment("code", code.as_str())
+        .add_attachment("sqlite error", error.to_string())
+    )?;
+    let Some(item) = item else {
+      return Ok(rules);
+    };
+    let context = DeserializeCompoundValueContext(item);
+    rules.push(deserialize_rule(&context, &collection.fields)?);
+  }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use url::Url;
+use crate::operating_system_integration::UserId;
+use crate::web_regulation_intrusive::content_filter::{Action, NormalizedText, Rule};
+use crate::web_regulation_intrusive::traffic::{Exchange, Headers, RequestHead, Scheme};
+use crate::web_regulation_intrusive::UrlScopedList;
+use crate::{DateTime, Duration};
+
+/// The name browsers know the native messaging host by.
+pub const NATIVE_MESSAGING_HOST_NAME: &str = "com.discipline.native_host";
+
+/// The extension checks in at least every 30 seconds, so one that hasn't
+/// for this long was disabled, removed or its browser closed.
+pub const CHECK_IN_TIMEOUT: Duration = Duration::unchecked_from_minutes(2);
+
+/// A tab of the user's browser, as the extension sees it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TabReport {
+  /// The browser's id of the tab.
+  pub tab_id: u64,
+  pub url: String,
+  pub title: String,
+  /// Whether the tab is the selected one of a window that isn't minimized.
+  pub is_visible: bool,
+}
+
+/// What the extension does with a tab.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Decision {
+  Allow,
+  /// Replace the tab with a block page.
+  Block,
+  /// Blur the tab's contents.
+  Blur,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TabDecision {
+  pub tab_id: u64,
+  pub decision: Decision,
+}
+
+/// A message from the extension to the native messaging host. Messages
+/// without tabs only check in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtensionMessage {
+  pub tabs: Vec<TabReport>,
+}
+
+/// The native messaging host's answer to each `ExtensionMessage`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HostMessage {
+  Decisions(Vec<TabDecision>),
+  /// The daemon couldn't be reached or failed, so the extension should
+  /// keep its previous decisions.
+  DaemonUnavailable,
+}
+
+/// The exchange loading `url` would be, for matching rules written for the
+/// proxy. Only http and https urls have one.
+fn exchange_of(user_id: UserId, url: &str) -> Option<Exchange> {
+  let url = Url::parse(url).ok()?;
+  let scheme = match url.scheme() {
+    "http" => Scheme::Http,
+    "https" => Scheme::Https,
+    _ => return None,
+  };
+
+  let mut target = url.path().to_string();
+  if let Some(query) = url.query() {
+    target.push('?');
+    target.push_str(query);
+  }
+
+  Some(Exchange {
+    user_id: Some(user_id),
+    scheme,
+    host: url.host_str()?.to_lowercase(),
+    port: url.port_or_known_default()?,
+    request: RequestHead {
+      method: "GET".into(),
+      target,
+      version: 1,
+      headers: Headers::new(),
+    },
+  })
+}
+
+/// Decides on a tab by the user's content filter rules, which are looked
+/// for in its title: tabs rules would block are blocked and tabs whose
+/// words rules would mask are blurred. Blocking wins, as it does for
+/// bodies.
+pub fn decide(rules: &UrlScopedList<Rule>, user_id: UserId, tab: &TabReport, now: DateTime) -> Decision {
+  let Some(exchange) = exchange_of(user_id, &tab.url) else {
+    return Decision::Allow;
+  };
+
+  let title = NormalizedText::normalize(&tab.title);
+  let mut decision = Decision::Allow;
+  for index in rules.matching(&exchange) {
+    let rule = &rules[index];
+    if !rule.applies_to(&exchange, now) {
+      continue;
+    }
+
+    if rule.find(&title).len() < rule.threshold() as usize {
+      continue;
+    }
+
+    match rule.action() {
+      Action::BlockPage => {
+        return Decision::Block;
+      }
+      Action::MaskWords => {
+        decision = Decision::Blur;
+      }
+    }
+  }
+
+  decision
+}
+
+// SECTION: Manifests.
+/// The manifest Chromium based browsers find the host by, allowing the
+/// extensions of `extension_ids` to run it.
+pub fn chromium_host_manifest(host_path: &Path, extension_ids: &[String]) -> String {
+  let allowed_origins: Vec<String> = extension_ids
+    .iter()
+    .map(|id| format!("chrome-extension://{id}/"))
+    .collect();
+
+  let manifest = json!({
+    "name": NATIVE_MESSAGING_HOST_NAME,
+    "description": "Discipline",
+    "path": host_path,
+    "type": "stdio",
+    "allowed_origins": allowed_origins,
+  });
+
+  serde_json::to_string_pretty(&manifest).unwrap()
+}
+
+/// The manifest Firefox finds the host by, allowing the extensions of
+/// `extension_ids` to run it.
+pub fn firefox_host_manifest(host_path: &Path, extension_ids: &[String]) -> String {
+  let manifest = json!({
+    "name": NATIVE_MESSAGING_HOST_NAME,
+    "description": "Discipline",
+    "path": host_path,
+    "type": "stdio",
+    "allowed_extensions": extension_ids,
+  });
+
+  serde_json::to_string_pretty(&manifest).unwrap()
+}
+
+// SECTION: Check ins.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckInStatus {
+  pub user_id: UserId,
+  pub last_check_in: Option<DateTime>,
+  /// Whether the extension checked in within `CHECK_IN_TIMEOUT`. Users
+  /// get that long after the daemon starts before they count as stopped.
+  pub is_checking_in: bool,
+}
+
+/// Keeps track of when the extension of each managed user last checked in,
+/// so we know when it stops.
+pub struct BrowserExtension {
+  check_ins: Mutex<HashMap<UserId, DateTime>>,
+  started_at: DateTime,
+}
+
+impl BrowserExtension {
+  pub fn new(now: DateTime) -> Self {
+    Self {
+      check_ins: Mutex::new(HashMap::new()),
+      started_at: now,
+    }
+  }
+
+  fn check_ins(&self) -> MutexGuard<'_, HashMap<UserId, DateTime>> {
+    self.check_ins.lock().unwrap()
+  }
+
+  pub fn check_in(&self, user_id: UserId, now: DateTime) {
+    self.check_ins().insert(user_id, now);
+  }
+
+  pub fn forget(&self, user_id: UserId) {
+    self.check_ins().remove(&user_id);
+  }
+
+  pub fn status(&self, user_id: UserId, now: DateTime) -> CheckInStatus {
+    let last_check_in = self.check_ins().get(&user_id).copied();
+    let since = last_check_in.unwrap_or(self.started_at);
+
+    CheckInStatus {
+      user_id,
+      last_check_in,
+      is_checking_in: now.since_or_zero(&since) < CHECK_IN_TIMEOUT,
+    }
+  }
+}
@@ -0,0 +1,23 @@
+pub mod native_messaging;
+pub use native_messaging::{read_message, write_message};
+
+pub mod feature;
+pub use feature::{
+  chromium_host_manifest,
+  decide,
+  firefox_host_manifest,
+  BrowserExtension,
+  CheckInStatus,
+  Decision,
+  ExtensionMessage,
+  HostMessage,
+  TabDecision,
+  TabReport,
+  CHECK_IN_TIMEOUT,
+  NATIVE_MESSAGING_HOST_NAME,
+};
+
+pub mod native_host;
+
+#[cfg(test)]
+mod tests;
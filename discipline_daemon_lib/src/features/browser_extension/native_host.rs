@@ -0,0 +1,113 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration as StandardDuration;
+use serde::Deserialize;
+use serde_json::json;
+use super::feature::{ExtensionMessage, HostMessage, TabDecision, TabReport};
+use super::native_messaging::{read_message, write_message};
+
+/// The id the daemon's api knows the operation reporting tabs by.
+pub const REPORT_TABS_OPERATION_ID: &str = "BrowserExtensionReportTabs";
+
+/// How long the daemon has to answer, after which the extension is told
+/// it's unavailable rather than left waiting.
+const API_TIMEOUT: StandardDuration = StandardDuration::from_secs(5);
+
+const MAXIMUM_RESPONSE_LENGTH: u64 = 1024 * 1024;
+
+/// What the daemon's api answers the operation with.
+#[derive(Deserialize)]
+enum ReportTabsReturn {
+  NoSuchUser,
+  Success(Vec<TabDecision>),
+  InternalError,
+}
+
+/// Sends an operation to the daemon's api and returns the body of its
+/// answer.
+fn execute_operation(api_address: SocketAddr, operation_id: &str, operation: &[u8]) -> io::Result<Vec<u8>> {
+  let mut stream = TcpStream::connect_timeout(&api_address, API_TIMEOUT)?;
+  stream.set_read_timeout(Some(API_TIMEOUT))?;
+  stream.set_write_timeout(Some(API_TIMEOUT))?;
+
+  let head = format!(
+    "POST /Api/ExecuteOperation?operation_id={operation_id} HTTP/1.1\r\n\
+     Host: {api_address}\r\n\
+     Content-Type: application/json\r\n\
+     Content-Length: {}\r\n\
+     Connection: close\r\n\r\n",
+    operation.len(),
+  );
+  stream.write_all(head.as_bytes())?;
+  stream.write_all(operation)?;
+
+  let mut response = Vec::new();
+  stream.take(MAXIMUM_RESPONSE_LENGTH).read_to_end(&mut response)?;
+
+  let Some(head_length) = response.windows(4).position(|window| window == b"\r\n\r\n") else {
+    return Err(io::Error::new(ErrorKind::InvalidData, "the api's response has no end of head"));
+  };
+
+  let head = String::from_utf8_lossy(&response[..head_length]);
+  let status_code = head.split(' ').nth(1);
+  if status_code != Some("200") {
+    return Err(io::Error::new(ErrorKind::InvalidData, format!("the api responded with: {head}")));
+  }
+
+  if head
+    .lines()
+    .filter_map(|line| line.split_once(':'))
+    .any(|(name, value)| name.eq_ignore_ascii_case("Transfer-Encoding") && value.trim() != "identity")
+  {
+    return Err(io::Error::new(ErrorKind::InvalidData, "the api's response has a transfer coding"));
+  }
+
+  Ok(response.split_off(head_length + 4))
+}
+
+/// Reports `tabs` to the daemon, which tells whose tabs they are by the
+/// user the connection comes from.
+fn report_tabs(api_address: SocketAddr, tabs: &[TabReport]) -> io::Result<Vec<TabDecision>> {
+  let operation = json!({
+    "tabs": tabs,
+  });
+
+  let body = execute_operation(api_address, REPORT_TABS_OPERATION_ID, operation.to_string().as_bytes())?;
+  let operation_return = serde_json::from_slice(&body)
+    .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+
+  match operation_return {
+    ReportTabsReturn::Success(decisions) => {
+      Ok(decisions)
+    }
+    ReportTabsReturn::NoSuchUser => {
+      Err(io::Error::new(ErrorKind::NotFound, "the user isn't managed"))
+    }
+    ReportTabsReturn::InternalError => {
+      Err(io::Error::other("the daemon failed"))
+    }
+  }
+}
+
+/// Relays the tabs the extension reports over `input` to the daemon, and
+/// the daemon's decisions back over `output`, until the browser closes
+/// `input`.
+///
+/// Failing to reach the daemon isn't an error here: the extension is told
+/// and keeps asking.
+pub fn run(
+  input: &mut impl Read,
+  output: &mut impl Write,
+  api_address: SocketAddr,
+) -> io::Result<()> {
+  while let Some(message) = read_message::<ExtensionMessage>(input)? {
+    let reply = match report_tabs(api_address, &message.tabs) {
+      Ok(decisions) => HostMessage::Decisions(decisions),
+      Err(_) => HostMessage::DaemonUnavailable,
+    };
+
+    write_message(output, &reply)?;
+  }
+
+  Ok(())
+}
@@ -0,0 +1,57 @@
+use std::io::{self, ErrorKind, Read, Write};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Browsers refuse messages from hosts longer than this.
+pub const MAXIMUM_OUTGOING_MESSAGE_LENGTH: usize = 1024 * 1024;
+
+/// Browsers send messages of up to 4 GiB, though the extension never sends
+/// anything near this long.
+pub const MAXIMUM_INCOMING_MESSAGE_LENGTH: usize = 8 * 1024 * 1024;
+
+/// Reads a message, which is json preceded by its length as a 32 bit
+/// integer in native byte order. Returns `None` once the browser closed
+/// the stream between messages.
+pub fn read_message<T: DeserializeOwned>(reader: &mut impl Read) -> io::Result<Option<T>> {
+  let mut length = [0; 4];
+  let mut filled = 0;
+  while filled < length.len() {
+    match reader.read(&mut length[filled..]) {
+      Ok(0) if filled == 0 => {
+        return Ok(None);
+      }
+      Ok(0) => {
+        return Err(io::Error::new(ErrorKind::UnexpectedEof, "the stream ended within a message length"));
+      }
+      Ok(read) => {
+        filled += read;
+      }
+      Err(error) if error.kind() == ErrorKind::Interrupted => {}
+      Err(error) => {
+        return Err(error);
+      }
+    }
+  }
+
+  let length = u32::from_ne_bytes(length) as usize;
+  if length > MAXIMUM_INCOMING_MESSAGE_LENGTH {
+    return Err(io::Error::new(ErrorKind::InvalidData, "message is too long"));
+  }
+
+  let mut message = vec![0; length];
+  reader.read_exact(&mut message)?;
+  serde_json::from_slice(&message)
+    .map(Some)
+    .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))
+}
+
+pub fn write_message<T: Serialize>(writer: &mut impl Write, message: &T) -> io::Result<()> {
+  let message = serde_json::to_vec(message).map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+  if message.len() > MAXIMUM_OUTGOING_MESSAGE_LENGTH {
+    return Err(io::Error::new(ErrorKind::InvalidData, "message is too long"));
+  }
+
+  writer.write_all(&(message.len() as u32).to_ne_bytes())?;
+  writer.write_all(&message)?;
+  writer.flush()
+}
@@ -0,0 +1,193 @@
+use std::io::{Cursor, Read, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::thread;
+use serde_json::{json, Value};
+use crate::operating_system_integration::UserId;
+use crate::web_regulation_intrusive::content_filter::{Action, Rule, RuleCreator};
+use crate::web_regulation_intrusive::{RuleActivator, UrlScopedList};
+use crate::{DateTime, Duration};
+use super::*;
+
+fn tab(tab_id: u64, url: &str, title: &str) -> TabReport {
+  TabReport {
+    tab_id,
+    url: url.into(),
+    title: title.into(),
+    is_visible: true,
+  }
+}
+
+fn rule(user_id: UserId, keywords: &[&str], action: Action, url_pattern: Option<&str>) -> Rule {
+  RuleCreator {
+    id: None,
+    languages: Vec::new(),
+    keywords: keywords.iter().map(|keyword| keyword.to_string()).collect(),
+    threshold: 1,
+    action,
+    url_pattern: url_pattern.map(String::from),
+    activator: RuleActivator::AllTheTime,
+  }.create(user_id).unwrap()
+}
+
+fn framed(messages: &[Value]) -> Vec<u8> {
+  let mut bytes = Vec::new();
+  for message in messages {
+    write_message(&mut bytes, message).unwrap();
+  }
+  bytes
+}
+
+#[test]
+fn frames_messages() {
+  let bytes = framed(&[json!({ "tabs": [] }), json!("second")]);
+  assert_eq!(&bytes[..4], &11u32.to_ne_bytes());
+
+  let mut reader = Cursor::new(bytes);
+  assert_eq!(read_message::<Value>(&mut reader).unwrap(), Some(json!({ "tabs": [] })));
+  assert_eq!(read_message::<Value>(&mut reader).unwrap(), Some(json!("second")));
+  assert_eq!(read_message::<Value>(&mut reader).unwrap(), None);
+
+  let mut cut_length = Cursor::new(vec![1, 0]);
+  assert!(read_message::<Value>(&mut cut_length).is_err());
+
+  let mut cut_message = Cursor::new(framed(&[json!("message")])[..6].to_vec());
+  assert!(read_message::<Value>(&mut cut_message).is_err());
+
+  let mut too_long = Cursor::new(u32::MAX.to_ne_bytes().to_vec());
+  assert!(read_message::<Value>(&mut too_long).is_err());
+}
+
+#[test]
+fn decides_on_tabs_by_their_titles() {
+  let user_id = UserId::new(1000);
+  let now = DateTime::now();
+  let rules = UrlScopedList::new(vec![
+    rule(user_id, &["gambling"], Action::BlockPage, None),
+    rule(user_id, &["spoiler"], Action::MaskWords, Some("*.example.com")),
+    rule(UserId::new(1001), &["news"], Action::BlockPage, None),
+  ]);
+
+  let decide = |tab: &TabReport| decide(&rules, user_id, tab, now);
+  assert_eq!(decide(&tab(1, "https://example.com/", "Gambling tonight")), Decision::Block);
+  assert_eq!(decide(&tab(2, "https://www.example.com/", "A spoiler")), Decision::Blur);
+  assert_eq!(decide(&tab(3, "https://other.org/", "A spoiler")), Decision::Allow);
+  assert_eq!(decide(&tab(4, "https://www.example.com/", "A gambling spoiler")), Decision::Block);
+  assert_eq!(decide(&tab(5, "https://example.com/", "The news")), Decision::Allow);
+  assert_eq!(decide(&tab(6, "about:blank", "Gambling")), Decision::Allow);
+}
+
+#[test]
+fn notices_extensions_that_stop_checking_in() {
+  let user_id = UserId::new(1000);
+  let started_at = DateTime::from_timestamp(1_000_000).unwrap();
+  let later = |duration: Duration| started_at.checked_add(&duration).unwrap();
+  let extension = BrowserExtension::new(started_at);
+
+  // Users have a while to check in after the daemon starts.
+  assert!(extension.status(user_id, later(Duration::from_milliseconds(1000))).is_checking_in);
+  assert!(!extension.status(user_id, later(CHECK_IN_TIMEOUT)).is_checking_in);
+
+  extension.check_in(user_id, later(CHECK_IN_TIMEOUT));
+  let status = extension.status(user_id, later(CHECK_IN_TIMEOUT.checked_add(&Duration::from_milliseconds(1000)).unwrap()));
+  assert!(status.is_checking_in);
+  assert_eq!(status.last_check_in, Some(later(CHECK_IN_TIMEOUT)));
+
+  let status = extension.status(user_id, later(CHECK_IN_TIMEOUT.checked_add(&CHECK_IN_TIMEOUT).unwrap()));
+  assert!(!status.is_checking_in);
+}
+
+#[test]
+fn renders_host_manifests() {
+  let host_path = Path::new("/usr/bin/discipline-native-host");
+
+  let chromium: Value = serde_json::from_str(&chromium_host_manifest(host_path, &["abcdefghijklmnop".into()])).unwrap();
+  assert_eq!(chromium, json!({
+    "name": NATIVE_MESSAGING_HOST_NAME,
+    "description": "Discipline",
+    "path": "/usr/bin/discipline-native-host",
+    "type": "stdio",
+    "allowed_origins": ["chrome-extension://abcdefghijklmnop/"],
+  }));
+
+  let firefox: Value = serde_json::from_str(&firefox_host_manifest(host_path, &["guard@example.com".into()])).unwrap();
+  assert_eq!(firefox["allowed_extensions"], json!(["guard@example.com"]));
+  assert_eq!(firefox["path"], json!("/usr/bin/discipline-native-host"));
+}
+
+/// Answers one api request with `body`, returning the request it got.
+fn serve_api_once(listener: TcpListener, body: &'static str) -> thread::JoinHandle<String> {
+  thread::spawn(move || {
+    let (mut stream, _) = listener.accept().unwrap();
+    let mut request = Vec::new();
+    let mut buffer = [0; 4096];
+    loop {
+      let read = stream.read(&mut buffer).unwrap();
+      request.extend_from_slice(&buffer[..read]);
+      let request = String::from_utf8_lossy(&request);
+      if let Some((head, body)) = request.split_once("\r\n\r\n") {
+        let length: usize = head
+          .lines()
+          .find_map(|line| line.strip_prefix("Content-Length: "))
+          .unwrap()
+          .parse()
+          .unwrap();
+        if body.len() >= length {
+          break;
+        }
+      }
+    }
+
+    write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+    String::from_utf8(request).unwrap()
+  })
+}
+
+#[test]
+fn relays_tabs_to_the_daemon() {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let api_address = listener.local_addr().unwrap();
+  let api = serve_api_once(listener, r#"{ "Success": [{ "tab_id": 7, "decision": "Blur" }] }"#);
+
+  let mut input = Cursor::new(framed(&[json!({
+    "tabs": [{ "tab_id": 7, "url": "https://example.com/", "title": "Example", "is_visible": false }],
+  })]));
+  let mut output = Vec::new();
+  native_host::run(&mut input, &mut output, api_address).unwrap();
+
+  let request = api.join().unwrap();
+  assert!(request.starts_with("POST /Api/ExecuteOperation?operation_id=BrowserExtensionReportTabs HTTP/1.1\r\n"));
+  let operation: Value = serde_json::from_str(request.split_once("\r\n\r\n").unwrap().1).unwrap();
+  assert_eq!(operation, json!({
+    "tabs": [{ "tab_id": 7, "url": "https://example.com/", "title": "Example", "is_visible": false }],
+  }));
+
+  let mut output = Cursor::new(output);
+  assert_eq!(
+    read_message::<HostMessage>(&mut output).unwrap(),
+    Some(HostMessage::Decisions(vec![TabDecision { tab_id: 7, decision: Decision::Blur }])),
+  );
+}
+
+#[test]
+fn tells_the_extension_when_the_daemon_is_unavailable() {
+  // Nothing listens on a port that was just freed.
+  let api_address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+  let mut input = Cursor::new(framed(&[json!({ "tabs": [] })]));
+  let mut output = Vec::new();
+  native_host::run(&mut input, &mut output, api_address).unwrap();
+
+  let mut output = Cursor::new(output);
+  assert_eq!(read_message::<HostMessage>(&mut output).unwrap(), Some(HostMessage::DaemonUnavailable));
+
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let api_address = listener.local_addr().unwrap();
+  let api = serve_api_once(listener, r#""NoSuchUser""#);
+  let mut input = Cursor::new(framed(&[json!({ "tabs": [] })]));
+  let mut output = Vec::new();
+  native_host::run(&mut input, &mut output, api_address).unwrap();
+  api.join().unwrap();
+
+  let mut output = Cursor::new(output);
+  assert_eq!(read_message::<HostMessage>(&mut output).unwrap(), Some(HostMessage::DaemonUnavailable));
+}
@@ -0,0 +1,29 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration as StandardDuration;
+use crate::{Daemon, GenericError};
+
+/// How often the policy files are checked for changes.
+const CHECK_INTERVAL: StandardDuration = StandardDuration::from_secs(10);
+
+/// Applies the browser policies, then keeps checking them every few seconds
+/// so files that are changed or removed are put back.
+pub fn run(daemon: Arc<Daemon>) -> Result<(), GenericError> {
+  daemon
+    .browser_policy()
+    .apply()
+    .map_err(|error| error.change_context("running browser policy enforcement"))?;
+
+  thread::spawn(move || enforce(daemon));
+  Ok(())
+}
+
+fn enforce(daemon: Arc<Daemon>) {
+  loop {
+    thread::sleep(CHECK_INTERVAL);
+
+    if let Err(error) = daemon.browser_policy().apply() {
+      daemon.internal_logger().log_error(error);
+    }
+  }
+}
@@ -0,0 +1,460 @@
+use std::fs::{self, Permissions};
+use std::io::ErrorKind;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use url::Url;
+use crate::database::browser_policy_setting as browser_policy_db;
+use crate::{Database, GenericError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BrowserFamily {
+  Firefox,
+  /// Chromium and the browsers built on it, which read the same policies.
+  Chromium,
+}
+
+/// A policy file a browser reads, relative to the root directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PolicyFile {
+  pub browser_family: BrowserFamily,
+  pub path: &'static str,
+}
+
+/// Firefox reads a single policies file, which we write whole. Chromium
+/// based browsers merge every file of their managed policies directory, so
+/// there we only own one of them.
+pub const POLICY_FILES: [PolicyFile; 4] = [
+  PolicyFile {
+    browser_family: BrowserFamily::Firefox,
+    path: "etc/firefox/policies/policies.json",
+  },
+  PolicyFile {
+    browser_family: BrowserFamily::Chromium,
+    path: "etc/chromium/policies/managed/discipline.json",
+  },
+  PolicyFile {
+    browser_family: BrowserFamily::Chromium,
+    path: "etc/opt/chrome/policies/managed/discipline.json",
+  },
+  PolicyFile {
+    browser_family: BrowserFamily::Chromium,
+    path: "etc/brave/policies/managed/discipline.json",
+  },
+];
+
+/// An extension a browser is made to install and keep.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Extension {
+  pub id: String,
+  /// Where it's installed from: the install url of the extension file for
+  /// Firefox, and the update manifest url for Chromium.
+  pub source: String,
+}
+
+impl Extension {
+  fn is_valid(&self) -> bool {
+    !self.id.is_empty()
+      && !self.id.contains([';', '\n'])
+      && Url::parse(&self.source).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+      && !self.source.contains([';', '\n'])
+  }
+}
+
+/// The policies applied to every browser of the device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Setting {
+  disable_private_browsing: bool,
+  block_about_config: bool,
+  force_safe_search: bool,
+  disable_dns_over_https: bool,
+  /// PEM files of the certificate authorities browsers are made to trust.
+  certificate_paths: Vec<PathBuf>,
+  firefox_extensions: Vec<Extension>,
+  chromium_extensions: Vec<Extension>,
+}
+
+impl Setting {
+  pub fn from_fields(
+    disable_private_browsing: bool,
+    block_about_config: bool,
+    force_safe_search: bool,
+    disable_dns_over_https: bool,
+    certificate_paths: Vec<PathBuf>,
+    firefox_extensions: Vec<Extension>,
+    chromium_extensions: Vec<Extension>,
+  ) -> Self {
+    Self {
+      disable_private_browsing,
+      block_about_config,
+      force_safe_search,
+      disable_dns_over_https,
+      certificate_paths,
+      firefox_extensions,
+      chromium_extensions,
+    }
+  }
+
+  pub fn disable_private_browsing(&self) -> bool {
+    self.disable_private_browsing
+  }
+
+  pub fn block_about_config(&self) -> bool {
+    self.block_about_config
+  }
+
+  pub fn force_safe_search(&self) -> bool {
+    self.force_safe_search
+  }
+
+  pub fn disable_dns_over_https(&self) -> bool {
+    self.disable_dns_over_https
+  }
+
+  pub fn certificate_paths(&self) -> &Vec<PathBuf> {
+    &self.certificate_paths
+  }
+
+  pub fn firefox_extensions(&self) -> &Vec<Extension> {
+    &self.firefox_extensions
+  }
+
+  pub fn chromium_extensions(&self) -> &Vec<Extension> {
+    &self.chromium_extensions
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingCreator {
+  pub disable_private_browsing: bool,
+  pub block_about_config: bool,
+  pub force_safe_search: bool,
+  pub disable_dns_over_https: bool,
+  pub certificate_paths: Vec<PathBuf>,
+  pub firefox_extensions: Vec<Extension>,
+  pub chromium_extensions: Vec<Extension>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SettingCreatorError {
+  CertificatePathIsNotAbsolute { path: PathBuf },
+  InvalidExtension { extension: Extension },
+}
+
+impl SettingCreator {
+  pub fn create(self) -> Result<Setting, SettingCreatorError> {
+    for path in &self.certificate_paths {
+      if !path.is_absolute() || path.to_string_lossy().contains('\n') {
+        return Err(SettingCreatorError::CertificatePathIsNotAbsolute { path: path.clone() });
+      }
+    }
+
+    for extension in self.firefox_extensions.iter().chain(&self.chromium_extensions) {
+      if !extension.is_valid() {
+        return Err(SettingCreatorError::InvalidExtension { extension: extension.clone() });
+      }
+    }
+
+    Ok(Setting::from_fields(
+      self.disable_private_browsing,
+      self.block_about_config,
+      self.force_safe_search,
+      self.disable_dns_over_https,
+      self.certificate_paths,
+      self.firefox_extensions,
+      self.chromium_extensions,
+    ))
+  }
+}
+
+// SECTION: Policies.
+/// The base64 encoded DER of each certificate of a PEM file, which is what
+/// Chromium takes.
+pub fn pem_certificates(pem: &str) -> Vec<String> {
+  let mut certificates = Vec::new();
+  let mut certificate = None;
+  for line in pem.lines().map(str::trim) {
+    match (line, &mut certificate) {
+      ("-----BEGIN CERTIFICATE-----", _) => {
+        certificate = Some(String::new());
+      }
+      ("-----END CERTIFICATE-----", Some(_)) => {
+        certificates.extend(certificate.take());
+      }
+      (line, Some(certificate)) => {
+        certificate.push_str(line);
+      }
+      _ => {}
+    }
+  }
+
+  certificates
+}
+
+/// The contents of Firefox's policies file.
+///
+/// Firefox has no SafeSearch policy, so for it that's left to the proxy.
+pub fn firefox_policies(setting: &Setting) -> String {
+  let mut policies = Map::new();
+
+  if setting.disable_private_browsing {
+    policies.insert("DisablePrivateBrowsing".into(), json!(true));
+  }
+
+  if setting.block_about_config {
+    policies.insert("BlockAboutConfig".into(), json!(true));
+  }
+
+  if setting.disable_dns_over_https {
+    policies.insert("DNSOverHTTPS".into(), json!({ "Enabled": false, "Locked": true }));
+  }
+
+  if !setting.certificate_paths.is_empty() {
+    policies.insert("Certificates".into(), json!({ "Install": setting.certificate_paths }));
+  }
+
+  if !setting.firefox_extensions.is_empty() {
+    let extensions: Map<String, Value> = setting
+      .firefox_extensions
+      .iter()
+      .map(|extension| {
+        (extension.id.clone(), json!({
+          "installation_mode": "force_installed",
+          "install_url": extension.source,
+        }))
+      })
+      .collect();
+
+    policies.insert("ExtensionSettings".into(), Value::Object(extensions));
+  }
+
+  let mut contents = serde_json::to_string_pretty(&json!({ "policies": policies })).unwrap();
+  contents.push('\n');
+  contents
+}
+
+/// The contents of the managed policies file of Chromium based browsers,
+/// given the certificates of the setting as `pem_certificates` returns.
+///
+/// Chromium has no `about:config`; its counterpart is the flags page.
+pub fn chromium_policies(setting: &Setting, certificates: &[String]) -> String {
+  let mut policies = Map::new();
+
+  if setting.disable_private_browsing {
+    // Incognito mode is unavailable when this is 1.
+    policies.insert("IncognitoModeAvailability".into(), json!(1));
+    policies.insert("BrowserGuestModeEnabled".into(), json!(false));
+  }
+
+  if setting.block_about_config {
+    policies.insert("URLBlocklist".into(), json!(["chrome://flags"]));
+  }
+
+  if setting.force_safe_search {
+    policies.insert("ForceGoogleSafeSearch".into(), json!(true));
+    // Moderate restricted mode on YouTube, and strict SafeSearch on Bing.
+    policies.insert("ForceYouTubeRestrict".into(), json!(1));
+    policies.insert("ForceBingSafeSearch".into(), json!(2));
+  }
+
+  if setting.disable_dns_over_https {
+    policies.insert("DnsOverHttpsMode".into(), json!("off"));
+  }
+
+  if !certificates.is_empty() {
+    policies.insert("CACertificates".into(), json!(certificates));
+  }
+
+  if !setting.chromium_extensions.is_empty() {
+    let extensions: Vec<String> = setting
+      .chromium_extensions
+      .iter()
+      .map(|extension| format!("{};{}", extension.id, extension.source))
+      .collect();
+
+    policies.insert("ExtensionInstallForcelist".into(), json!(extensions));
+  }
+
+  let mut contents = serde_json::to_string_pretty(&Value::Object(policies)).unwrap();
+  contents.push('\n');
+  contents
+}
+
+// SECTION: Enforcement.
+fn read_policy_file(path: &Path) -> Result<Option<String>, GenericError> {
+  match fs::read_to_string(path) {
+    Ok(contents) => {
+      Ok(Some(contents))
+    }
+    Err(error) if error.kind() == ErrorKind::NotFound => {
+      Ok(None)
+    }
+    Err(error) => {
+      Err(
+        GenericError::new("reading a browser policy file")
+          .add_error("failed to read file")
+          .add_attachment("path", path.to_string_lossy())
+          .add_attachment("io error", error.to_string())
+      )
+    }
+  }
+}
+
+/// Writes a policy file through a temporary one, so browsers never read it
+/// half written. Everyone may read it and only root may change it.
+fn write_policy_file(path: &Path, contents: &str) -> Result<(), GenericError> {
+  let error = |action: &str, error: std::io::Error| {
+    GenericError::new("writing a browser policy file")
+      .add_error(action)
+      .add_attachment("path", path.to_string_lossy())
+      .add_attachment("io error", error.to_string())
+  };
+
+  if let Some(directory) = path.parent() {
+    fs::create_dir_all(directory).map_err(|e| error("failed to create its directory", e))?;
+  }
+
+  let temporary_path = path.with_extension("json.tmp");
+  fs::write(&temporary_path, contents).map_err(|e| error("failed to write temporary file", e))?;
+  fs::set_permissions(&temporary_path, Permissions::from_mode(0o644))
+    .map_err(|e| error("failed to set permissions of temporary file", e))?;
+  fs::rename(&temporary_path, path).map_err(|e| error("failed to replace file", e))
+}
+
+/// Keeps the enterprise policy files of Firefox, Chromium, Chrome and Brave
+/// as the device's setting says, rewriting them when they're changed or
+/// removed by anyone else.
+pub struct BrowserPolicy {
+  setting: Mutex<Option<Setting>>,
+  /// Where the policy file paths are relative to, which is "/" but for
+  /// tests.
+  root: PathBuf,
+}
+
+impl BrowserPolicy {
+  pub fn open(database: &Database) -> Result<Self, GenericError> {
+    let setting = browser_policy_db::retrieve_setting(database)
+      .map_err(|error| error.change_context("opening browser policy"))?;
+
+    Ok(Self::new(setting, PathBuf::from("/")))
+  }
+
+  pub fn new(setting: Option<Setting>, root: PathBuf) -> Self {
+    Self {
+      setting: Mutex::new(setting),
+      root,
+    }
+  }
+
+  pub fn setting(&self) -> MutexGuard<'_, Option<Setting>> {
+    self.setting.lock().unwrap()
+  }
+
+  /// The contents of each policy file for a setting. Certificates that
+  /// can't be read are left out, along with an error saying so.
+  fn policies(setting: &Setting) -> (Vec<(PolicyFile, String)>, Option<GenericError>) {
+    let mut certificates = Vec::new();
+    let mut error_accumulator = None;
+    for path in &setting.certificate_paths {
+      match fs::read_to_string(path) {
+        Ok(pem) => {
+          certificates.extend(pem_certificates(&pem));
+        }
+        Err(error) => {
+          error_accumulator = Some(
+            GenericError::new("reading a certificate for browser policies")
+              .add_error("failed to read file")
+              .add_attachment("path", path.to_string_lossy())
+              .add_attachment("io error", error.to_string())
+          );
+        }
+      }
+    }
+
+    let firefox = firefox_policies(setting);
+    let chromium = chromium_policies(setting, &certificates);
+    let policies = POLICY_FILES
+      .into_iter()
+      .map(|file| {
+        let contents = match file.browser_family {
+          BrowserFamily::Firefox => firefox.clone(),
+          BrowserFamily::Chromium => chromium.clone(),
+        };
+        (file, contents)
+      })
+      .collect();
+
+    (policies, error_accumulator)
+  }
+
+  /// Rewrites the policy files whose contents aren't what the setting
+  /// makes, returning the paths of those rewritten. Does nothing without a
+  /// setting.
+  pub fn apply(&self) -> Result<Vec<PathBuf>, GenericError> {
+    let Some(setting) = self.setting().clone() else {
+      return Ok(Vec::new());
+    };
+
+    let (policies, mut error_accumulator) = Self::policies(&setting);
+    let mut rewritten = Vec::new();
+    for (file, contents) in policies {
+      let path = self.root.join(file.path);
+      let result = read_policy_file(&path).and_then(|current| {
+        if current.as_deref() == Some(contents.as_str()) {
+          return Ok(());
+        }
+
+        write_policy_file(&path, &contents)?;
+        rewritten.push(path.clone());
+        Ok(())
+      });
+
+      if let Err(error) = result {
+        error_accumulator = Some(error);
+      }
+    }
+
+    match error_accumulator {
+      None => Ok(rewritten),
+      Some(error) => Err(error.change_context("applying browser policies")),
+    }
+  }
+
+  /// Removes the policy files written for `setting`. Firefox's file is
+  /// shared with whoever else administers the device, so it's only
+  /// removed when it's still what we wrote.
+  pub fn remove(&self, setting: &Setting) -> Result<(), GenericError> {
+    let (policies, _) = Self::policies(setting);
+    let mut error_accumulator = None;
+    for (file, contents) in policies {
+      let path = self.root.join(file.path);
+      let result = read_policy_file(&path).and_then(|current| {
+        let Some(current) = current else {
+          return Ok(());
+        };
+
+        if file.browser_family == BrowserFamily::Firefox && current != contents {
+          return Ok(());
+        }
+
+        fs::remove_file(&path).map_err(|error|
+          GenericError::new("removing a browser policy file")
+            .add_error("failed to remove file")
+            .add_attachment("path", path.to_string_lossy())
+            .add_attachment("io error", error.to_string())
+        )
+      });
+
+      if let Err(error) = result {
+        error_accumulator = Some(error);
+      }
+    }
+
+    match error_accumulator {
+      None => Ok(()),
+      Some(error) => Err(error.change_context("removing browser policies")),
+    }
+  }
+}
@@ -0,0 +1,16 @@
+pub mod feature;
+pub use feature::{
+  BrowserFamily,
+  BrowserPolicy,
+  Extension,
+  PolicyFile,
+  Setting,
+  SettingCreator,
+  SettingCreatorError,
+  POLICY_FILES,
+};
+
+pub mod enforcer;
+
+#[cfg(test)]
+mod tests;
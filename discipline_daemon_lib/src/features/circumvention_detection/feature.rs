@@ -0,0 +1,529 @@
+use std::collections::HashSet;
+use std::sync::{Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+use crate::database::circumvention_detection as circumvention_db;
+use crate::operating_system_integration::screen_access_regulation::AsyncTask;
+use crate::operating_system_integration::{self as os, ConnectedSocket, ProcessInfo, TransportProtocol, UserId};
+use crate::{CountdownTimer, Daemon, Database, DateTime, Duration, GenericError, Uuid};
+use super::signatures::{self, Circumvention, TCP_PORTS, UDP_PORTS};
+
+/// The longest a single violation may block a user's screen for.
+pub const MAXIMUM_SCREEN_BLOCKING_DURATION: Duration = Duration::unchecked_from_days(1);
+
+/// How long events are kept for.
+pub const EVENT_RETENTION: Duration = Duration::unchecked_from_days(30);
+
+/// What is done when a user is caught tunneling their traffic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Response {
+  /// Only record an event.
+  Log,
+  /// Record an event and stop the tunnel: the user's traffic through new
+  /// tunnel interfaces is dropped, tunneling processes are killed, and
+  /// the user's traffic to the known ports is dropped all along.
+  Block,
+  /// Record an event and block the user's screen.
+  Violation {
+    screen_blocking_duration: Duration,
+  },
+}
+
+/// Enables circumvention detection for a managed user.
+#[derive(Debug, Clone)]
+pub struct Setting {
+  user_id: UserId,
+  response: Response,
+  /// Runs while the user's screen is blocked for a violation.
+  penalty: CountdownTimer,
+}
+
+impl Setting {
+  pub fn from_fields(user_id: UserId, response: Response, penalty: CountdownTimer) -> Self {
+    Self {
+      user_id,
+      response,
+      penalty,
+    }
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn response(&self) -> Response {
+    self.response
+  }
+
+  pub fn penalty(&self) -> &CountdownTimer {
+    &self.penalty
+  }
+
+  /// The setting with the penalty of the one it replaces.
+  pub fn with_penalty(self, penalty: CountdownTimer) -> Self {
+    Self {
+      penalty,
+      ..self
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingCreator {
+  pub response: Response,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SettingCreatorError {
+  ScreenBlockingDurationIsZero,
+  ScreenBlockingDurationTooLong,
+}
+
+impl SettingCreator {
+  pub fn create(self, user_id: UserId, now: DateTime) -> Result<Setting, SettingCreatorError> {
+    if let Response::Violation { screen_blocking_duration } = self.response {
+      if screen_blocking_duration.is_zero() {
+        return Err(SettingCreatorError::ScreenBlockingDurationIsZero);
+      }
+
+      if screen_blocking_duration > MAXIMUM_SCREEN_BLOCKING_DURATION {
+        return Err(SettingCreatorError::ScreenBlockingDurationTooLong);
+      }
+    }
+
+    Ok(Setting::from_fields(
+      user_id,
+      self.response,
+      CountdownTimer::new(Duration::ZERO, now),
+    ))
+  }
+}
+
+// SECTION: Events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DetectionKind {
+  /// A tunnel network interface was created.
+  Interface,
+  /// A tunneling program was running as the user.
+  Process,
+  /// The user connected to a port tunneled traffic is known to go through.
+  Connection,
+}
+
+/// Something that gave a tunnel away.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Detection {
+  pub kind: DetectionKind,
+  /// The name of the interface, the command line of the process, or the
+  /// protocol and remote address of the connection.
+  pub subject: String,
+}
+
+/// A record of a user caught tunneling their traffic.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+  id: Uuid,
+  user_id: UserId,
+  time: DateTime,
+  circumvention: Circumvention,
+  detection: Detection,
+  /// The response in effect when it was detected.
+  response: Response,
+}
+
+impl Event {
+  pub fn from_fields(
+    id: Uuid,
+    user_id: UserId,
+    time: DateTime,
+    circumvention: Circumvention,
+    detection: Detection,
+    response: Response,
+  ) -> Self {
+    Self {
+      id,
+      user_id,
+      time,
+      circumvention,
+      detection,
+      response,
+    }
+  }
+
+  pub fn id(&self) -> &Uuid {
+    &self.id
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn time(&self) -> DateTime {
+    self.time
+  }
+
+  pub fn circumvention(&self) -> Circumvention {
+    self.circumvention
+  }
+
+  pub fn detection(&self) -> &Detection {
+    &self.detection
+  }
+
+  pub fn response(&self) -> Response {
+    self.response
+  }
+}
+
+/// A detection of a user's tunnel that hasn't been responded to yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+  pub user_id: UserId,
+  pub circumvention: Circumvention,
+  pub detection: Detection,
+  /// The process to kill, when it's a process that gave the tunnel away.
+  pub process_id: Option<u32>,
+}
+
+// SECTION: Detection.
+#[derive(Default)]
+struct State {
+  /// Keys of what was reported and is still around, so running processes,
+  /// open connections and interfaces are reported once.
+  reported: HashSet<(UserId, String)>,
+  /// The interfaces that existed when monitoring started. Only interfaces
+  /// created afterwards are reported.
+  preexisting_interfaces: HashSet<String>,
+  /// The interfaces the traffic of users is being dropped through.
+  blocked_interfaces: HashSet<(UserId, String)>,
+}
+
+/// Watches for managed users getting around the firewall rules applied to
+/// their user ids with a VPN, Tor or a proxy tunnel, and responds as each
+/// user's setting says.
+///
+/// Network interfaces belong to the whole machine rather than a user, so a
+/// new tunnel interface counts against every user with a setting.
+pub struct CircumventionDetection {
+  settings: Mutex<Vec<Setting>>,
+  state: Mutex<State>,
+}
+
+impl CircumventionDetection {
+  pub fn open(database: &Database) -> Result<Self, GenericError> {
+    let settings = circumvention_db::retrieve_all_settings(database)
+      .map_err(|error| error.change_context("opening circumvention detection"))?;
+
+    Ok(Self::new(settings))
+  }
+
+  pub fn new(settings: Vec<Setting>) -> Self {
+    Self {
+      settings: Mutex::new(settings),
+      state: Mutex::new(State::default()),
+    }
+  }
+
+  pub fn settings(&self) -> MutexGuard<'_, Vec<Setting>> {
+    self.settings.lock().unwrap()
+  }
+
+  fn state(&self) -> MutexGuard<'_, State> {
+    self.state.lock().unwrap()
+  }
+
+  pub fn response_of(&self, user_id: UserId) -> Option<Response> {
+    self
+      .settings()
+      .iter()
+      .find(|setting| setting.user_id == user_id)
+      .map(|setting| setting.response)
+  }
+
+  /// Whether the user's screen is to stay blocked for a violation.
+  pub fn is_screen_blocked(&self, user_id: UserId, now: DateTime) -> bool {
+    let mut settings = self.settings();
+    let Some(setting) = settings.iter_mut().find(|setting| setting.user_id == user_id) else {
+      return false;
+    };
+
+    setting.penalty.synchronize(now);
+    setting.penalty.is_running()
+  }
+
+  /// Reports no interfaces that exist now, since they weren't created by
+  /// anyone we could have stopped.
+  pub fn ignore_preexisting_interfaces(&self, interface_names: Vec<String>) {
+    self.state().preexisting_interfaces.extend(interface_names);
+  }
+
+  /// The findings a new or changed network interface makes.
+  pub fn on_interface_added(&self, name: &str, kind: Option<&str>) -> Vec<Finding> {
+    let Some(circumvention) = signatures::classify_interface(name, kind) else {
+      return Vec::new();
+    };
+
+    let user_ids: Vec<UserId> = self.settings().iter().map(|setting| setting.user_id).collect();
+    let mut state = self.state();
+    if state.preexisting_interfaces.contains(name) {
+      return Vec::new();
+    }
+
+    let key = format!("interface:{name}");
+    user_ids
+      .into_iter()
+      .filter(|user_id| state.reported.insert((*user_id, key.clone())))
+      .map(|user_id| Finding {
+        user_id,
+        circumvention,
+        detection: Detection {
+          kind: DetectionKind::Interface,
+          subject: name.to_string(),
+        },
+        process_id: None,
+      })
+      .collect()
+  }
+
+  /// Forgets an interface that was removed and stops dropping traffic
+  /// through it, since the next one with its name may be another.
+  pub fn on_interface_removed(&self, name: &str) -> Result<(), GenericError> {
+    let mut state = self.state();
+    state.preexisting_interfaces.remove(name);
+
+    let key = format!("interface:{name}");
+    state.reported.retain(|(_, reported_key)| *reported_key != key);
+
+    let blocked: Vec<(UserId, String)> = state
+      .blocked_interfaces
+      .iter()
+      .filter(|(_, interface_name)| interface_name == name)
+      .cloned()
+      .collect();
+
+    for (user_id, interface_name) in blocked {
+      os::unblock_traffic_of_user_through_interface(&user_id, &interface_name)?;
+      state.blocked_interfaces.remove(&(user_id, interface_name));
+    }
+
+    Ok(())
+  }
+
+  /// The findings the processes running now make. Processes that are
+  /// reported stay reported for as long as they run.
+  pub fn on_processes(&self, processes: &[ProcessInfo]) -> Vec<Finding> {
+    let user_ids: HashSet<UserId> = self.settings().iter().map(|setting| setting.user_id).collect();
+    let mut findings = Vec::new();
+    let mut present = HashSet::new();
+
+    let mut state = self.state();
+    for process in processes {
+      if !user_ids.contains(&process.user_id) {
+        continue;
+      }
+
+      let Some(circumvention) = signatures::classify_process(process) else {
+        continue;
+      };
+
+      let key = (process.user_id, format!("process:{}", process.process_id));
+      present.insert(key.clone());
+      if !state.reported.insert(key) {
+        continue;
+      }
+
+      let subject = if process.command_line.is_empty() {
+        process.name.clone()
+      } else {
+        process.command_line.join(" ")
+      };
+
+      findings.push(Finding {
+        user_id: process.user_id,
+        circumvention,
+        detection: Detection {
+          kind: DetectionKind::Process,
+          subject,
+        },
+        process_id: Some(process.process_id),
+      });
+    }
+
+    state.reported.retain(|key| !key.1.starts_with("process:") || present.contains(key));
+    findings
+  }
+
+  /// The findings the connections open now make. Connections that are
+  /// reported stay reported for as long as they're open.
+  pub fn on_connections(&self, sockets: &[ConnectedSocket]) -> Vec<Finding> {
+    let user_ids: HashSet<UserId> = self.settings().iter().map(|setting| setting.user_id).collect();
+    let mut findings = Vec::new();
+    let mut present = HashSet::new();
+
+    let mut state = self.state();
+    for socket in sockets {
+      if !user_ids.contains(&socket.user_id) {
+        continue;
+      }
+
+      let port = socket.remote_address.port();
+      let Some(circumvention) = signatures::classify_connection(socket.protocol, port) else {
+        continue;
+      };
+
+      let protocol = match socket.protocol {
+        TransportProtocol::Tcp => "tcp",
+        TransportProtocol::Udp => "udp",
+      };
+
+      let subject = format!("{protocol} {}", socket.remote_address);
+      let key = (socket.user_id, format!("connection:{subject}"));
+      present.insert(key.clone());
+      if !state.reported.insert(key) {
+        continue;
+      }
+
+      findings.push(Finding {
+        user_id: socket.user_id,
+        circumvention,
+        detection: Detection {
+          kind: DetectionKind::Connection,
+          subject,
+        },
+        process_id: None,
+      });
+    }
+
+    state.reported.retain(|key| !key.1.starts_with("connection:") || present.contains(key));
+    findings
+  }
+
+  /// Records `finding` and responds to it as the user's setting says.
+  pub fn respond(&self, daemon: &Daemon, finding: Finding, now: DateTime) -> Result<(), GenericError> {
+    let Some(response) = self.response_of(finding.user_id) else {
+      return Ok(());
+    };
+
+    let event = Event::from_fields(
+      Uuid::new_v4(),
+      finding.user_id,
+      now,
+      finding.circumvention,
+      finding.detection.clone(),
+      response,
+    );
+
+    circumvention_db::add_event(daemon.database(), &event, EVENT_RETENTION)
+      .map_err(|error| error.change_context("responding to a circumvention"))?;
+
+    match response {
+      Response::Log => {}
+      Response::Block => {
+        self
+          .block(finding)
+          .map_err(|error| error.change_context("responding to a circumvention"))?;
+      }
+      Response::Violation { screen_blocking_duration } => {
+        self
+          .penalize(daemon, finding.user_id, screen_blocking_duration, now)
+          .map_err(|error| error.change_context("responding to a circumvention"))?;
+      }
+    }
+
+    Ok(())
+  }
+
+  fn block(&self, finding: Finding) -> Result<(), GenericError> {
+    match finding.detection.kind {
+      DetectionKind::Interface => {
+        let interface_name = finding.detection.subject;
+        os::block_traffic_of_user_through_interface(&finding.user_id, &interface_name)?;
+        self.state().blocked_interfaces.insert((finding.user_id, interface_name));
+      }
+      DetectionKind::Process => {
+        if let Some(process_id) = finding.process_id {
+          os::kill_process(process_id)?;
+        }
+      }
+      DetectionKind::Connection => {
+        // The known ports are already dropped for users with this response,
+        // so only connections that were open before it was set get here.
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Blocks the user's screen for `duration`, or for longer if it already
+  /// is.
+  fn penalize(
+    &self,
+    daemon: &Daemon,
+    user_id: UserId,
+    duration: Duration,
+    now: DateTime,
+  ) -> Result<(), GenericError> {
+    {
+      let mut settings = self.settings();
+      let Some(setting) = settings.iter_mut().find(|setting| setting.user_id == user_id) else {
+        return Ok(());
+      };
+
+      setting.penalty.synchronize(now);
+      if setting.penalty.remaining_duration() < duration {
+        setting.penalty = CountdownTimer::new(duration, now);
+        circumvention_db::update_penalty(daemon.database(), user_id, &setting.penalty)?;
+      }
+    }
+
+    daemon
+      .operating_system_integration()
+      .async_scheduler()
+      .add_immediate_operation(AsyncTask::ApplyRegulationForUser(user_id));
+
+    Ok(())
+  }
+
+  /// Adds and removes the firewall rules of `user_id` so they match
+  /// `response`, `None` meaning detection is off for the user.
+  pub fn apply_firewall_rules(&self, user_id: UserId, response: Option<Response>) -> Result<(), GenericError> {
+    let tcp_ports = signatures::port_list(TCP_PORTS);
+    let udp_ports = signatures::port_list(UDP_PORTS);
+
+    if response == Some(Response::Block) {
+      os::block_traffic_of_user_to_ports(&user_id, "tcp", &tcp_ports)?;
+      os::block_traffic_of_user_to_ports(&user_id, "udp", &udp_ports)?;
+      return Ok(());
+    }
+
+    os::unblock_traffic_of_user_to_ports(&user_id, "tcp", &tcp_ports)?;
+    os::unblock_traffic_of_user_to_ports(&user_id, "udp", &udp_ports)?;
+
+    let mut state = self.state();
+    let blocked: Vec<(UserId, String)> = state
+      .blocked_interfaces
+      .iter()
+      .filter(|(blocked_user_id, _)| *blocked_user_id == user_id)
+      .cloned()
+      .collect();
+
+    for (user_id, interface_name) in blocked {
+      os::unblock_traffic_of_user_through_interface(&user_id, &interface_name)?;
+      state.blocked_interfaces.remove(&(user_id, interface_name));
+    }
+
+    Ok(())
+  }
+
+  /// Restores the firewall rules of every setting, which don't survive
+  /// restarts of the machine.
+  pub fn apply_all_firewall_rules(&self) -> Result<(), GenericError> {
+    let settings = self.settings().clone();
+    for setting in settings {
+      self
+        .apply_firewall_rules(setting.user_id, Some(setting.response))
+        .map_err(|error| error.change_context("applying the firewall rules of all circumvention detection settings"))?;
+    }
+
+    Ok(())
+  }
+}
@@ -0,0 +1,22 @@
+pub mod signatures;
+pub use signatures::Circumvention;
+
+pub mod feature;
+pub use feature::{
+  CircumventionDetection,
+  Detection,
+  DetectionKind,
+  Event,
+  Finding,
+  Response,
+  Setting,
+  SettingCreator,
+  SettingCreatorError,
+  EVENT_RETENTION,
+  MAXIMUM_SCREEN_BLOCKING_DURATION,
+};
+
+pub mod monitor;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration as StandardDuration;
+use crate::operating_system_integration::{self as os, InterfaceChange, InterfaceMonitor};
+use crate::{Daemon, DateTime, GenericError};
+use super::feature::Finding;
+
+/// How often running processes and open connections are looked through.
+const SCAN_INTERVAL: StandardDuration = StandardDuration::from_secs(5);
+
+/// Starts watching for new network interfaces, and looking through the
+/// processes and connections of managed users every few seconds.
+pub fn run(daemon: Arc<Daemon>) -> Result<(), GenericError> {
+  let detection = daemon.circumvention_detection();
+
+  let monitor = InterfaceMonitor::open()
+    .map_err(|error| error.change_context("running circumvention detection"))?;
+
+  let interface_names = os::retrieve_network_interface_names()
+    .map_err(|error| error.change_context("running circumvention detection"))?;
+
+  detection.ignore_preexisting_interfaces(interface_names);
+  detection
+    .apply_all_firewall_rules()
+    .map_err(|error| error.change_context("running circumvention detection"))?;
+
+  let interfaces_daemon = Arc::clone(&daemon);
+  thread::spawn(move || watch_interfaces(interfaces_daemon, monitor));
+  thread::spawn(move || scan(daemon));
+
+  Ok(())
+}
+
+fn respond_to_all(daemon: &Daemon, findings: Vec<Finding>) {
+  let now = DateTime::now();
+  for finding in findings {
+    if let Err(error) = daemon.circumvention_detection().respond(daemon, finding, now) {
+      daemon.internal_logger().log_error(error);
+    }
+  }
+}
+
+fn watch_interfaces(daemon: Arc<Daemon>, monitor: InterfaceMonitor) {
+  loop {
+    let changes = match monitor.receive() {
+      Ok(changes) => {
+        changes
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+        thread::sleep(SCAN_INTERVAL);
+        continue;
+      }
+    };
+
+    for change in changes {
+      match change {
+        InterfaceChange::AddedOrChanged { name, kind } => {
+          let findings = daemon
+            .circumvention_detection()
+            .on_interface_added(&name, kind.as_deref());
+
+          respond_to_all(&daemon, findings);
+        }
+        InterfaceChange::Removed { name } => {
+          if let Err(error) = daemon.circumvention_detection().on_interface_removed(&name) {
+            daemon.internal_logger().log_error(error);
+          }
+        }
+      }
+    }
+  }
+}
+
+fn scan(daemon: Arc<Daemon>) {
+  loop {
+    match os::retrieve_processes() {
+      Ok(processes) => {
+        let findings = daemon.circumvention_detection().on_processes(&processes);
+        respond_to_all(&daemon, findings);
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error);
+      }
+    }
+
+    let sockets = os::retrieve_connected_sockets();
+    let findings = daemon.circumvention_detection().on_connections(&sockets);
+    respond_to_all(&daemon, findings);
+
+    thread::sleep(SCAN_INTERVAL);
+  }
+}
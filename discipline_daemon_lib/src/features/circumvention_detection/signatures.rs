@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+use crate::operating_system_integration::{ProcessInfo, TransportProtocol};
+
+/// The kind of tunnel a user may route their traffic through to get around
+/// the firewall rules we apply to their user id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Circumvention {
+  Tor,
+  Vpn,
+  Proxy,
+}
+
+/// Link kinds, as netlink reports them, of interfaces that tunnel traffic.
+const TUNNEL_INTERFACE_KINDS: &[&str] = &["tun", "wireguard"];
+
+/// Name prefixes of the interfaces VPN clients create. Some create plain
+/// tun devices, which are caught by their kind too.
+const VPN_INTERFACE_NAME_PREFIXES: &[&str] = &[
+  "tun",
+  "tap",
+  "wg",
+  "ppp",
+  "tailscale",
+  "nordlynx",
+  "proton",
+  "mullvad",
+  "zt",
+];
+
+/// Executable names, as "/proc/<pid>/comm" has them, of tunneling programs.
+const PROCESS_NAMES: &[(&str, Circumvention)] = &[
+  ("tor", Circumvention::Tor),
+  ("obfs4proxy", Circumvention::Tor),
+  ("snowflake-clien", Circumvention::Tor),
+  ("lyrebird", Circumvention::Tor),
+  ("openvpn", Circumvention::Vpn),
+  ("wireguard-go", Circumvention::Vpn),
+  ("wg-quick", Circumvention::Vpn),
+  ("boringtun", Circumvention::Vpn),
+  ("openconnect", Circumvention::Vpn),
+  ("vpnc", Circumvention::Vpn),
+  ("tailscaled", Circumvention::Vpn),
+  ("warp-svc", Circumvention::Vpn),
+  ("sshuttle", Circumvention::Proxy),
+  ("v2ray", Circumvention::Proxy),
+  ("xray", Circumvention::Proxy),
+  ("ss-local", Circumvention::Proxy),
+  ("sslocal", Circumvention::Proxy),
+  ("sing-box", Circumvention::Proxy),
+  ("hysteria", Circumvention::Proxy),
+  ("psiphon", Circumvention::Proxy),
+];
+
+/// Tcp ports of Tor relays and directories, the local ports of Tor's socks
+/// proxy, the ports of OpenVPN and PPTP, and the port of socks proxies.
+pub const TCP_PORTS: &[(u16, Circumvention)] = &[
+  (9001, Circumvention::Tor),
+  (9030, Circumvention::Tor),
+  (9050, Circumvention::Tor),
+  (9150, Circumvention::Tor),
+  (1194, Circumvention::Vpn),
+  (1723, Circumvention::Vpn),
+  (1080, Circumvention::Proxy),
+];
+
+/// Udp ports of OpenVPN, WireGuard, IPsec and L2TP.
+pub const UDP_PORTS: &[(u16, Circumvention)] = &[
+  (1194, Circumvention::Vpn),
+  (51820, Circumvention::Vpn),
+  (500, Circumvention::Vpn),
+  (4500, Circumvention::Vpn),
+  (1701, Circumvention::Vpn),
+];
+
+/// A comma-separated list of `ports`, as the firewall's multiport match
+/// takes them.
+pub fn port_list(ports: &[(u16, Circumvention)]) -> String {
+  ports
+    .iter()
+    .map(|(port, _)| port.to_string())
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// What a new network interface named `name` of link kind `kind` is for,
+/// if it tunnels traffic.
+pub fn classify_interface(name: &str, kind: Option<&str>) -> Option<Circumvention> {
+  let is_tunnel_kind = kind.is_some_and(|kind| TUNNEL_INTERFACE_KINDS.contains(&kind));
+  let has_vpn_name = VPN_INTERFACE_NAME_PREFIXES
+    .iter()
+    .any(|prefix| name.starts_with(prefix));
+
+  if is_tunnel_kind || has_vpn_name {
+    Some(Circumvention::Vpn)
+  } else {
+    None
+  }
+}
+
+/// The options of ssh that take a value, which makes the rest of a cluster
+/// of options, like "-oDynamicForward" or "-iD", that value.
+const SSH_OPTIONS_WITH_VALUES: &str = "BbcEeFIiJLlmOopQRSW";
+
+/// Whether `argument` of ssh includes "-D", which opens a socks proxy, or
+/// "-w", which opens a tunnel device.
+fn opens_ssh_tunnel(argument: &str) -> bool {
+  let Some(options) = argument.strip_prefix('-') else {
+    return false;
+  };
+
+  for option in options.chars() {
+    if option == 'D' || option == 'w' {
+      return true;
+    }
+
+    if SSH_OPTIONS_WITH_VALUES.contains(option) {
+      return false;
+    }
+  }
+
+  false
+}
+
+/// What `process` is for, if it's a tunneling program.
+pub fn classify_process(process: &ProcessInfo) -> Option<Circumvention> {
+  if let Some((_, circumvention)) = PROCESS_NAMES
+    .iter()
+    .find(|(name, _)| *name == process.name)
+  {
+    return Some(*circumvention);
+  }
+
+  let is_tunneling_ssh = process.name == "ssh"
+    && process
+      .command_line
+      .iter()
+      .skip(1)
+      .any(|argument| opens_ssh_tunnel(argument));
+
+  if is_tunneling_ssh {
+    Some(Circumvention::Proxy)
+  } else {
+    None
+  }
+}
+
+/// What a connection to `port` over `protocol` is for, if that port is
+/// known to carry tunneled traffic.
+pub fn classify_connection(protocol: TransportProtocol, port: u16) -> Option<Circumvention> {
+  let ports = match protocol {
+    TransportProtocol::Tcp => TCP_PORTS,
+    TransportProtocol::Udp => UDP_PORTS,
+  };
+
+  ports
+    .iter()
+    .find(|(known_port, _)| *known_port == port)
+    .map(|(_, circumvention)| *circumvention)
+}
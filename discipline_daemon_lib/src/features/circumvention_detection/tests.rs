@@ -0,0 +1,212 @@
+use crate::operating_system_integration::{
+  parse_interface_changes,
+  ConnectedSocket,
+  InterfaceChange,
+  ProcessInfo,
+  TransportProtocol,
+  UserId,
+};
+use crate::{CountdownTimer, DateTime, Duration};
+use super::signatures::{classify_connection, classify_interface, classify_process, port_list, UDP_PORTS};
+use super::*;
+
+fn attribute(attribute_type: u16, payload: &[u8], into: &mut Vec<u8>) {
+  into.extend_from_slice(&(4 + payload.len() as u16).to_ne_bytes());
+  into.extend_from_slice(&attribute_type.to_ne_bytes());
+  into.extend_from_slice(payload);
+  while into.len() % 4 != 0 {
+    into.push(0);
+  }
+}
+
+/// A link message of `message_type` for the interface `name` of `kind`.
+fn link_message(message_type: u16, name: &str, kind: Option<&str>) -> Vec<u8> {
+  let mut attributes = Vec::new();
+  attribute(3, format!("{name}\0").as_bytes(), &mut attributes);
+  if let Some(kind) = kind {
+    let mut link_info = Vec::new();
+    attribute(1, format!("{kind}\0").as_bytes(), &mut link_info);
+    // The link info attribute is marked as nested.
+    attribute(18 | 0x8000, &link_info, &mut attributes);
+  }
+
+  let length = 16 + 16 + attributes.len();
+  let mut message = Vec::new();
+  message.extend_from_slice(&(length as u32).to_ne_bytes());
+  message.extend_from_slice(&message_type.to_ne_bytes());
+  message.extend_from_slice(&[0; 10]);
+  message.extend_from_slice(&[0; 16]);
+  message.extend_from_slice(&attributes);
+  message
+}
+
+fn process(process_id: u32, user_id: u32, command_line: &[&str]) -> ProcessInfo {
+  let name = command_line[0].rsplit('/').next().unwrap();
+  ProcessInfo {
+    process_id,
+    user_id: UserId::new(user_id),
+    name: name.chars().take(15).collect(),
+    command_line: command_line.iter().map(|argument| argument.to_string()).collect(),
+  }
+}
+
+fn detection_of(user_ids: &[u32], response: Response) -> CircumventionDetection {
+  let now = DateTime::now();
+  let settings = user_ids
+    .iter()
+    .map(|user_id| SettingCreator { response }.create(UserId::new(*user_id), now).unwrap())
+    .collect();
+
+  CircumventionDetection::new(settings)
+}
+
+#[test]
+fn parses_link_messages() {
+  let mut datagram = link_message(16, "wg0", Some("wireguard"));
+  // A route message, which isn't about links.
+  datagram.extend_from_slice(&link_message(24, "ignored", None));
+  datagram.extend_from_slice(&link_message(17, "tun3", None));
+
+  assert_eq!(parse_interface_changes(&datagram), vec![
+    InterfaceChange::AddedOrChanged { name: "wg0".into(), kind: Some("wireguard".into()) },
+    InterfaceChange::Removed { name: "tun3".into() },
+  ]);
+
+  // Truncated messages are dropped rather than misread.
+  let message = link_message(16, "wg0", None);
+  assert_eq!(parse_interface_changes(&message[..message.len() - 4]), vec![]);
+  assert_eq!(parse_interface_changes(&[0; 3]), vec![]);
+}
+
+#[test]
+fn classifies_signatures() {
+  assert_eq!(classify_interface("wg0", None), Some(Circumvention::Vpn));
+  assert_eq!(classify_interface("office", Some("wireguard")), Some(Circumvention::Vpn));
+  assert_eq!(classify_interface("tailscale0", Some("tun")), Some(Circumvention::Vpn));
+  assert_eq!(classify_interface("eth0", None), None);
+  assert_eq!(classify_interface("docker0", Some("bridge")), None);
+
+  assert_eq!(classify_process(&process(1, 1000, &["/usr/bin/tor"])), Some(Circumvention::Tor));
+  assert_eq!(classify_process(&process(1, 1000, &["/usr/sbin/openvpn", "client.ovpn"])), Some(Circumvention::Vpn));
+  assert_eq!(classify_process(&process(1, 1000, &["/usr/bin/snowflake-client"])), Some(Circumvention::Tor));
+  assert_eq!(classify_process(&process(1, 1000, &["torsocks"])), None);
+
+  assert_eq!(classify_process(&process(1, 1000, &["ssh", "-ND", "1080", "host"])), Some(Circumvention::Proxy));
+  assert_eq!(classify_process(&process(1, 1000, &["ssh", "-w", "0:0", "host"])), Some(Circumvention::Proxy));
+  // "D" and "w" as the values of other options.
+  assert_eq!(classify_process(&process(1, 1000, &["ssh", "-oForwardAgent=yes", "host"])), None);
+  assert_eq!(classify_process(&process(1, 1000, &["ssh", "-iD", "host"])), None);
+  assert_eq!(classify_process(&process(1, 1000, &["ssh", "host"])), None);
+
+  assert_eq!(classify_connection(TransportProtocol::Tcp, 9001), Some(Circumvention::Tor));
+  assert_eq!(classify_connection(TransportProtocol::Udp, 51820), Some(Circumvention::Vpn));
+  assert_eq!(classify_connection(TransportProtocol::Udp, 9001), None);
+  assert_eq!(classify_connection(TransportProtocol::Tcp, 443), None);
+
+  assert_eq!(port_list(UDP_PORTS), "1194,51820,500,4500,1701");
+}
+
+#[test]
+fn reports_new_interfaces_to_every_user() {
+  let detection = detection_of(&[1000, 1001], Response::Log);
+  detection.ignore_preexisting_interfaces(vec!["wg0".into()]);
+
+  assert!(detection.on_interface_added("wg0", Some("wireguard")).is_empty());
+  assert!(detection.on_interface_added("eth1", None).is_empty());
+
+  let findings = detection.on_interface_added("tun0", Some("tun"));
+  let mut user_ids: Vec<u32> = findings.iter().map(|finding| finding.user_id.as_raw()).collect();
+  user_ids.sort();
+  assert_eq!(user_ids, vec![1000, 1001]);
+  assert_eq!(findings[0].detection, Detection { kind: DetectionKind::Interface, subject: "tun0".into() });
+
+  // Changes to an interface that was reported aren't reported again, until
+  // it's removed.
+  assert!(detection.on_interface_added("tun0", Some("tun")).is_empty());
+  detection.on_interface_removed("tun0").unwrap();
+  assert_eq!(detection.on_interface_added("tun0", Some("tun")).len(), 2);
+
+  // Once a preexisting interface is removed, the next one is new.
+  detection.on_interface_removed("wg0").unwrap();
+  assert_eq!(detection.on_interface_added("wg0", Some("wireguard")).len(), 2);
+}
+
+#[test]
+fn reports_processes_of_managed_users_once() {
+  let detection = detection_of(&[1000], Response::Log);
+  let tor = process(40, 1000, &["tor", "-f", "torrc"]);
+  let processes = vec![
+    tor.clone(),
+    process(41, 1001, &["openvpn"]),
+    process(42, 1000, &["firefox"]),
+  ];
+
+  let findings = detection.on_processes(&processes);
+  assert_eq!(findings, vec![Finding {
+    user_id: UserId::new(1000),
+    circumvention: Circumvention::Tor,
+    detection: Detection { kind: DetectionKind::Process, subject: "tor -f torrc".into() },
+    process_id: Some(40),
+  }]);
+
+  assert!(detection.on_processes(&processes).is_empty());
+
+  // A process that exited and started again is reported again.
+  assert!(detection.on_processes(&[]).is_empty());
+  assert_eq!(detection.on_processes(&[tor]).len(), 1);
+}
+
+#[test]
+fn reports_connections_of_managed_users_once() {
+  let detection = detection_of(&[1000], Response::Log);
+  let socket = |user_id: u32, protocol, address: &str| ConnectedSocket {
+    protocol,
+    remote_address: address.parse().unwrap(),
+    user_id: UserId::new(user_id),
+  };
+
+  let sockets = vec![
+    socket(1000, TransportProtocol::Tcp, "198.51.100.7:9001"),
+    socket(1000, TransportProtocol::Tcp, "198.51.100.7:443"),
+    socket(1001, TransportProtocol::Udp, "203.0.113.9:51820"),
+  ];
+
+  let findings = detection.on_connections(&sockets);
+  assert_eq!(findings.len(), 1);
+  assert_eq!(findings[0].circumvention, Circumvention::Tor);
+  assert_eq!(findings[0].detection.subject, "tcp 198.51.100.7:9001");
+  assert_eq!(findings[0].process_id, None);
+
+  assert!(detection.on_connections(&sockets).is_empty());
+  assert!(detection.on_connections(&[]).is_empty());
+  assert_eq!(detection.on_connections(&sockets).len(), 1);
+}
+
+#[test]
+fn validates_settings_and_blocks_screens_for_penalties() {
+  let user_id = UserId::new(1000);
+  let now = DateTime::now();
+  let violation = |minutes| Response::Violation {
+    screen_blocking_duration: Duration::unchecked_from_minutes(minutes),
+  };
+
+  assert!(matches!(
+    SettingCreator { response: violation(0) }.create(user_id, now),
+    Err(SettingCreatorError::ScreenBlockingDurationIsZero),
+  ));
+  assert!(matches!(
+    SettingCreator { response: violation(24 * 60 + 1) }.create(user_id, now),
+    Err(SettingCreatorError::ScreenBlockingDurationTooLong),
+  ));
+
+  let setting = SettingCreator { response: violation(30) }.create(user_id, now).unwrap();
+  let detection = CircumventionDetection::new(vec![setting]);
+  assert!(!detection.is_screen_blocked(user_id, now));
+
+  let penalty = CountdownTimer::new(Duration::unchecked_from_minutes(30), now);
+  let detection = CircumventionDetection::new(vec![Setting::from_fields(user_id, violation(30), penalty)]);
+  let later = |minutes: u64| now.checked_add(&Duration::unchecked_from_minutes(minutes)).unwrap();
+  assert!(detection.is_screen_blocked(user_id, later(29)));
+  assert!(!detection.is_screen_blocked(user_id, later(31)));
+  assert!(!detection.is_screen_blocked(UserId::new(1001), now));
+}
@@ -7,6 +7,10 @@ use crate::{
 pub const MAXIMUM_RULE_NUMBER: usize = 10;
 pub const MAXIMUM_POLICY_NUMBER: usize = 5;
 
+/// How many times overlapping rules are followed into each other before
+/// `Regulation::blocks_until` gives up on finding a resumption time.
+const MAXIMUM_RESUMPTION_STEPS: usize = 64;
+
 // TODO: Add a variant that is effective according to a weekday and time range condition
 // TODO: Add a variant that is effective according to a screen time allowance condition
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -34,6 +38,29 @@ impl RuleActivator {
       }
     }
   }
+
+  /// Returns the first moment, at or after `now`, at which this activator is
+  /// not effective, or `None` if it's effective all the time.
+  pub fn effective_until(&self, now: DateTime) -> Option<DateTime> {
+    if !self.is_effective(now) {
+      return Some(now);
+    }
+
+    match self {
+      RuleActivator::OnWeekday(_) => {
+        now.next_midnight()
+      }
+      RuleActivator::InTimeRange(time_range) => {
+        time_range.occurrence_end(now)
+      }
+      RuleActivator::InWeekdayRange(weekday_range) => {
+        weekday_range.occurrence_end(now)
+      }
+      RuleActivator::AllTheTime => {
+        None
+      }
+    }
+  }
 }
 
 /// A Rule may not be made less restrictive after it is created.
@@ -197,6 +224,10 @@ impl Policy {
     self.rules.iter().any(|rule| rule.is_effective(now))
   } 
 
+  pub fn effective_rules(&self, now: DateTime) -> impl Iterator<Item = &Rule> {
+    self.rules.iter().filter(move |rule| rule.is_effective(now))
+  }
+
   pub fn reached_maximum_rules_allowed(&self) -> bool {
     self.rules.len() >= MAXIMUM_RULE_NUMBER
   }
@@ -258,6 +289,47 @@ impl Regulation {
     }
   }
 
+  /// Returns the rules of enabled policies that are in effect at `now`,
+  /// paired with their policies.
+  pub fn blocking_rules(&self, now: DateTime) -> Vec<(&Policy, &Rule)> {
+    self
+      .policies
+      .iter()
+      .filter(|policy| policy.is_enabled())
+      .flat_map(|policy| policy
+        .effective_rules(now)
+        .map(move |rule| (policy, rule))
+      )
+      .collect()
+  }
+
+  /// Returns the first moment, at or after `now`, at which `calculate_action`
+  /// stops returning `Action::Block`, following rules that take over from
+  /// each other. Returns `None` if access never resumes on its own.
+  pub fn blocks_until(&self, now: DateTime) -> Option<DateTime> {
+    let mut time = now;
+
+    for _ in 0..MAXIMUM_RESUMPTION_STEPS {
+      let mut latest_end = None;
+
+      for (_, rule) in self.blocking_rules(time) {
+        let end = rule.activator.effective_until(time)?;
+        latest_end = Some(latest_end.map_or(end, |latest: DateTime| latest.max(end)));
+      }
+
+      match latest_end {
+        Some(end) if end > time => {
+          time = end;
+        }
+        _ => {
+          return Some(time);
+        }
+      }
+    }
+
+    None
+  }
+
   pub fn are_some_policies_enabled(&self) -> bool {
     self.policies.iter().any(|policy| policy.is_enabled())
   }
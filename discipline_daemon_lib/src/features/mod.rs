@@ -1,4 +1,8 @@
 pub mod screen_access_regulation;
 pub mod internet_access_regulation;
-mod web_regulation_non_intrusive;
+pub mod web_regulation_non_intrusive;
+pub mod web_regulation_intrusive;
+pub mod circumvention_detection;
+pub mod browser_policy;
+pub mod browser_extension;
 // pub mod data_vaults;
\ No newline at end of file
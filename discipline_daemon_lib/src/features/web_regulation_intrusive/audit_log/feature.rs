@@ -0,0 +1,358 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::sync::{Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+use crate::database::web_regulation_intrusive_audit_log as audit_db;
+use crate::operating_system_integration::UserId;
+use crate::{CountdownTimer, Daemon, Database, DateTime, Duration, GenericError, Uuid};
+use super::super::traffic::Exchange;
+
+/// The longest entries may be kept for.
+pub const MAXIMUM_RETENTION: Duration = Duration::unchecked_from_days(365);
+
+/// The longest a setting can be protected from being loosened, which is the
+/// same as for SafeSearch.
+pub const MAXIMUM_PROTECTION_DURATION: Duration = Duration::unchecked_from_days(21);
+
+/// How much of a navigation is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Privacy {
+  /// The host name and the path and query.
+  FullUrls,
+  /// The host name only.
+  DomainsOnly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Decision {
+  Allowed,
+  Blocked,
+}
+
+impl Decision {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Decision::Allowed => "allowed",
+      Decision::Blocked => "blocked",
+    }
+  }
+}
+
+/// Opts a managed user into having their navigations recorded.
+///
+/// While its protector is running, the setting can't be changed or turned
+/// off, so the entries recorded under it can't be cleared either.
+#[derive(Debug, Clone)]
+pub struct Setting {
+  user_id: UserId,
+  privacy: Privacy,
+  retention: Duration,
+  protector: CountdownTimer,
+}
+
+impl Setting {
+  pub fn from_fields(
+    user_id: UserId,
+    privacy: Privacy,
+    retention: Duration,
+    protector: CountdownTimer,
+  ) -> Self {
+    Self {
+      user_id,
+      privacy,
+      retention,
+      protector,
+    }
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn privacy(&self) -> Privacy {
+    self.privacy
+  }
+
+  /// How long entries are kept for.
+  pub fn retention(&self) -> Duration {
+    self.retention
+  }
+
+  pub fn protector(&self) -> &CountdownTimer {
+    &self.protector
+  }
+
+  pub fn synchronize(&mut self, now: DateTime) {
+    self.protector.synchronize(now);
+  }
+
+  pub fn is_protected(&mut self, now: DateTime) -> bool {
+    self.synchronize(now);
+    self.protector.is_running()
+  }
+
+  /// Protects the setting for `duration` from `now`, replacing the
+  /// previous protection.
+  pub fn protect_for(&mut self, duration: Duration, now: DateTime) {
+    self.protector = CountdownTimer::new(duration, now);
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingCreator {
+  pub privacy: Privacy,
+  pub retention: Duration,
+  pub protect_for: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SettingCreatorError {
+  RetentionIsZero,
+  RetentionTooLong,
+  ProtectionTooLong { maximum: Duration },
+}
+
+impl SettingCreator {
+  pub fn create(self, user_id: UserId, now: DateTime) -> Result<Setting, SettingCreatorError> {
+    if self.retention.is_zero() {
+      return Err(SettingCreatorError::RetentionIsZero);
+    }
+    if self.retention > MAXIMUM_RETENTION {
+      return Err(SettingCreatorError::RetentionTooLong);
+    }
+    if self.protect_for > MAXIMUM_PROTECTION_DURATION {
+      return Err(SettingCreatorError::ProtectionTooLong { maximum: MAXIMUM_PROTECTION_DURATION });
+    }
+
+    Ok(Setting::from_fields(
+      user_id,
+      self.privacy,
+      self.retention,
+      CountdownTimer::new(self.protect_for, now),
+    ))
+  }
+}
+
+/// A top-level navigation of a managed user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+  id: Uuid,
+  user_id: UserId,
+  time: DateTime,
+  domain: String,
+  /// The path and query, unless the user's privacy setting keeps only
+  /// domains.
+  path: Option<String>,
+  decision: Decision,
+}
+
+impl Entry {
+  pub fn new(user_id: UserId, time: DateTime, exchange: &Exchange, privacy: Privacy, decision: Decision) -> Self {
+    let path = match privacy {
+      Privacy::FullUrls => Some(exchange.request.target.clone()),
+      Privacy::DomainsOnly => None,
+    };
+
+    Self {
+      id: Uuid::new_v4(),
+      user_id,
+      time,
+      domain: exchange.host.clone(),
+      path,
+      decision,
+    }
+  }
+
+  pub fn from_fields(
+    id: Uuid,
+    user_id: UserId,
+    time: DateTime,
+    domain: String,
+    path: Option<String>,
+    decision: Decision,
+  ) -> Self {
+    Self {
+      id,
+      user_id,
+      time,
+      domain,
+      path,
+      decision,
+    }
+  }
+
+  pub fn id(&self) -> &Uuid {
+    &self.id
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn time(&self) -> DateTime {
+    self.time
+  }
+
+  pub fn domain(&self) -> &str {
+    &self.domain
+  }
+
+  pub fn path(&self) -> Option<&str> {
+    self.path.as_deref()
+  }
+
+  pub fn decision(&self) -> Decision {
+    self.decision
+  }
+}
+
+/// Narrows down the entries of a user retrieved. Unset fields match every
+/// entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Query {
+  pub user_id: UserId,
+  /// Matches entries of this domain and its subdomains.
+  pub domain: Option<String>,
+  /// Matches entries at or after this time.
+  pub from: Option<DateTime>,
+  /// Matches entries at or before this time.
+  pub till: Option<DateTime>,
+}
+
+impl Query {
+  /// The queried domain, lowercased and without a trailing dot.
+  pub fn normalized_domain(&self) -> Option<String> {
+    self
+      .domain
+      .as_ref()
+      .map(|domain| domain.trim().trim_end_matches('.').to_ascii_lowercase())
+      .filter(|domain| !domain.is_empty())
+  }
+}
+
+// SECTION: Export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExportFormat {
+  Csv,
+  Json,
+}
+
+impl ExportFormat {
+  pub fn export(&self, entries: &[Entry]) -> Result<String, GenericError> {
+    match self {
+      ExportFormat::Csv => {
+        Ok(export_csv(entries))
+      }
+      ExportFormat::Json => {
+        export_json(entries)
+      }
+    }
+  }
+}
+
+/// Fields starting like a formula get a `'` in front, so spreadsheets
+/// show them as text rather than run what users typed into urls.
+fn write_csv_field(field: &str, into: &mut String) {
+  let field = if field.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+    Cow::Owned(format!("'{field}"))
+  } else {
+    Cow::Borrowed(field)
+  };
+
+  if field.contains([',', '"', '\n', '\r']) {
+    into.push('"');
+    into.push_str(&field.replace('"', "\"\""));
+    into.push('"');
+  } else {
+    into.push_str(&field);
+  }
+}
+
+/// One line per entry with a header line, as RFC 4180 describes.
+pub fn export_csv(entries: &[Entry]) -> String {
+  let mut csv = String::from("time,user_id,domain,path,decision\r\n");
+
+  for entry in entries {
+    write!(csv, "{},{},", entry.time.to_rfc_3339(), entry.user_id.as_raw()).unwrap();
+    write_csv_field(&entry.domain, &mut csv);
+    csv.push(',');
+    write_csv_field(entry.path().unwrap_or_default(), &mut csv);
+    csv.push(',');
+    csv.push_str(entry.decision.as_str());
+    csv.push_str("\r\n");
+  }
+
+  csv
+}
+
+/// An array with an object per entry.
+pub fn export_json(entries: &[Entry]) -> Result<String, GenericError> {
+  let entries: Vec<serde_json::Value> = entries
+    .iter()
+    .map(|entry| serde_json::json!({
+      "time": entry.time.to_rfc_3339(),
+      "user_id": entry.user_id.as_raw(),
+      "domain": entry.domain,
+      "path": entry.path,
+      "decision": entry.decision.as_str(),
+    }))
+    .collect();
+
+  serde_json::to_string_pretty(&entries).map_err(|error|
+    GenericError::new("exporting audit log entries as json")
+      .add_error(error.to_string())
+  )
+}
+
+// SECTION: Recording.
+/// Records the top-level navigations of the managed users who opted in,
+/// for accountability partners to review.
+pub struct AuditLog {
+  settings: Mutex<Vec<Setting>>,
+}
+
+impl AuditLog {
+  pub fn open(database: &Database) -> Result<Self, GenericError> {
+    let settings = audit_db::retrieve_all_settings(database)
+      .map_err(|error| error.change_context("opening the audit log"))?;
+
+    Ok(Self::new(settings))
+  }
+
+  pub fn new(settings: Vec<Setting>) -> Self {
+    Self {
+      settings: Mutex::new(settings),
+    }
+  }
+
+  pub fn settings(&self) -> MutexGuard<'_, Vec<Setting>> {
+    self.settings.lock().unwrap()
+  }
+
+  /// The entry to record for `exchange`, if it's a top-level navigation of
+  /// a user who opted in, along with how long to keep it for.
+  pub fn entry_of(&self, exchange: &Exchange, decision: Decision, now: DateTime) -> Option<(Entry, Duration)> {
+    let user_id = exchange.user_id?;
+    if !exchange.is_top_level_navigation() {
+      return None;
+    }
+
+    let settings = self.settings();
+    let setting = settings.iter().find(|setting| setting.user_id == user_id)?;
+
+    Some((
+      Entry::new(user_id, now, exchange, setting.privacy, decision),
+      setting.retention,
+    ))
+  }
+
+  pub fn record(&self, daemon: &Daemon, exchange: &Exchange, decision: Decision) {
+    let Some((entry, retention)) = self.entry_of(exchange, decision, DateTime::now()) else {
+      return;
+    };
+
+    if let Err(error) = audit_db::add_entry(daemon.database(), &entry, retention) {
+      daemon.internal_logger().log_error(error.change_context("recording a navigation in the audit log"));
+    }
+  }
+}
@@ -0,0 +1,19 @@
+pub mod feature;
+pub use feature::{
+  export_csv,
+  export_json,
+  AuditLog,
+  Decision,
+  Entry,
+  ExportFormat,
+  Privacy,
+  Query,
+  Setting,
+  SettingCreator,
+  SettingCreatorError,
+  MAXIMUM_PROTECTION_DURATION,
+  MAXIMUM_RETENTION,
+};
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,173 @@
+use crate::operating_system_integration::UserId;
+use crate::{CountdownTimer, DateTime, Duration, Uuid};
+use super::super::traffic::*;
+use super::*;
+
+fn at(timestamp: i64) -> DateTime {
+  DateTime::from_timestamp(timestamp).unwrap()
+}
+
+fn audit_log(privacy: Privacy) -> AuditLog {
+  AuditLog::new(vec![
+    Setting::from_fields(
+      UserId::new(1000),
+      privacy,
+      Duration::unchecked_from_days(30),
+      CountdownTimer::new(Duration::ZERO, at(0)),
+    ),
+  ])
+}
+
+fn entry(domain: &str, path: Option<&str>, decision: Decision) -> Entry {
+  Entry::from_fields(
+    Uuid::nil(),
+    UserId::new(1000),
+    at(1_700_000_000_000),
+    domain.into(),
+    path.map(String::from),
+    decision,
+  )
+}
+
+#[test]
+fn creates_settings() {
+  let creator = |retention, protect_for| SettingCreator {
+    privacy: Privacy::FullUrls,
+    retention,
+    protect_for,
+  };
+
+  assert!(matches!(
+    creator(Duration::ZERO, Duration::ZERO).create(UserId::new(1000), at_minute(0)),
+    Err(SettingCreatorError::RetentionIsZero),
+  ));
+  assert!(matches!(
+    creator(Duration::unchecked_from_days(366), Duration::ZERO).create(UserId::new(1000), at_minute(0)),
+    Err(SettingCreatorError::RetentionTooLong),
+  ));
+  assert!(matches!(
+    creator(MAXIMUM_RETENTION, Duration::unchecked_from_days(22)).create(UserId::new(1000), at_minute(0)),
+    Err(SettingCreatorError::ProtectionTooLong { .. }),
+  ));
+
+  let mut setting = creator(MAXIMUM_RETENTION, Duration::unchecked_from_minutes(10))
+    .create(UserId::new(1000), at_minute(0))
+    .unwrap();
+  assert_eq!(setting.user_id(), UserId::new(1000));
+  assert_eq!(setting.privacy(), Privacy::FullUrls);
+  assert_eq!(setting.retention(), MAXIMUM_RETENTION);
+  assert!(setting.is_protected(at_minute(9)));
+  assert!(!setting.is_protected(at_minute(10)));
+}
+
+#[test]
+fn records_navigations_of_users_who_opted_in() {
+  let audit_log = audit_log(Privacy::FullUrls);
+  let now = at(1_700_000_000_000);
+
+  let (entry, retention) = audit_log
+    .entry_of(&Exchange::navigation_to("https://example.com/a?b=c"), Decision::Blocked, now)
+    .unwrap();
+
+  assert_eq!(entry.user_id(), UserId::new(1000));
+  assert_eq!(entry.time(), now);
+  assert_eq!(entry.domain(), "example.com");
+  assert_eq!(entry.path(), Some("/a?b=c"));
+  assert_eq!(entry.decision(), Decision::Blocked);
+  assert_eq!(retention, Duration::unchecked_from_days(30));
+
+  let other_user = Exchange::navigation_to("https://example.com/").with_user_id(Some(UserId::new(1001)));
+  assert!(audit_log.entry_of(&other_user, Decision::Allowed, now).is_none());
+
+  let unmanaged = Exchange::navigation_to("https://example.com/").with_user_id(None);
+  assert!(audit_log.entry_of(&unmanaged, Decision::Allowed, now).is_none());
+}
+
+#[test]
+fn skips_requests_that_are_not_navigations() {
+  let audit_log = audit_log(Privacy::FullUrls);
+  let now = at(1_700_000_000_000);
+
+  let subresource = Exchange::to("https://example.com/image.png").fetching("image");
+  assert!(audit_log.entry_of(&subresource, Decision::Allowed, now).is_none());
+
+  let form = Exchange::navigation_to("https://example.com/submit").with_method("POST");
+  assert!(audit_log.entry_of(&form, Decision::Allowed, now).is_none());
+}
+
+#[test]
+fn keeps_only_domains_when_asked_to() {
+  let audit_log = audit_log(Privacy::DomainsOnly);
+
+  let (entry, _) = audit_log
+    .entry_of(&Exchange::navigation_to("https://example.com/private"), Decision::Allowed, at(0))
+    .unwrap();
+
+  assert_eq!(entry.domain(), "example.com");
+  assert_eq!(entry.path(), None);
+}
+
+#[test]
+fn normalizes_queried_domains() {
+  let query = |domain: Option<&str>| Query {
+    user_id: UserId::new(1000),
+    domain: domain.map(String::from),
+    from: None,
+    till: None,
+  };
+
+  assert_eq!(query(Some(" Example.COM. ")).normalized_domain().as_deref(), Some("example.com"));
+  assert_eq!(query(Some(".")).normalized_domain(), None);
+  assert_eq!(query(None).normalized_domain(), None);
+}
+
+#[test]
+fn exports_csv() {
+  let entries = [
+    entry("example.com", Some("/search?q=a,b"), Decision::Allowed),
+    entry("example.org", Some("/say \"hi\""), Decision::Blocked),
+    entry("example.net", None, Decision::Allowed),
+    entry("-example.com", Some("=HYPERLINK(\"http://evil.example\")"), Decision::Allowed),
+    entry("example.com", Some("@SUM(1,2)"), Decision::Blocked),
+  ];
+
+  assert_eq!(
+    ExportFormat::Csv.export(&entries).unwrap(),
+    concat!(
+      "time,user_id,domain,path,decision\r\n",
+      "2023-11-14T22:13:20.000Z,1000,example.com,\"/search?q=a,b\",allowed\r\n",
+      "2023-11-14T22:13:20.000Z,1000,example.org,\"/say \"\"hi\"\"\",blocked\r\n",
+      "2023-11-14T22:13:20.000Z,1000,example.net,,allowed\r\n",
+      "2023-11-14T22:13:20.000Z,1000,'-example.com,\"'=HYPERLINK(\"\"http://evil.example\"\")\",allowed\r\n",
+      "2023-11-14T22:13:20.000Z,1000,example.com,\"'@SUM(1,2)\",blocked\r\n",
+    ),
+  );
+}
+
+#[test]
+fn exports_json() {
+  let entries = [
+    entry("example.com", Some("/a"), Decision::Blocked),
+    entry("example.net", None, Decision::Allowed),
+  ];
+
+  let json = ExportFormat::Json.export(&entries).unwrap();
+  let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+  assert_eq!(json, serde_json::json!([
+    {
+      "time": "2023-11-14T22:13:20.000Z",
+      "user_id": 1000,
+      "domain": "example.com",
+      "path": "/a",
+      "decision": "blocked",
+    },
+    {
+      "time": "2023-11-14T22:13:20.000Z",
+      "user_id": 1000,
+      "domain": "example.net",
+      "path": null,
+      "decision": "allowed",
+    },
+  ]));
+}
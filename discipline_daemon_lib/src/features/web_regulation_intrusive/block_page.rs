@@ -0,0 +1,90 @@
+use std::fmt::Write;
+use crate::api::block_pages::{BlockReason, WebRuleFeature};
+use crate::{DateTime, Duration, Uuid};
+use super::traffic::{Exchange, Response};
+
+/// Escapes `text` so it can be placed inside html element content or a
+/// quoted attribute value.
+pub fn escape_html_into(text: &str, into: &mut String) {
+  for char in text.chars() {
+    match char {
+      '&' => into.push_str("&amp;"),
+      '<' => into.push_str("&lt;"),
+      '>' => into.push_str("&gt;"),
+      '"' => into.push_str("&quot;"),
+      '\'' => into.push_str("&#39;"),
+      _ => into.push(char),
+    }
+  }
+}
+
+pub fn escape_html(text: &str) -> String {
+  let mut escaped = String::with_capacity(text.len());
+  escape_html_into(text, &mut escaped);
+  escaped
+}
+
+/// Writes a `<time>` element showing `datetime` in UTC, which a script in the
+/// page replaces with the browser's local time.
+pub fn write_time(datetime: DateTime, into: &mut String) {
+  write!(
+    into,
+    "<time data-timestamp=\"{}\">{} UTC</time>",
+    datetime.timestamp(),
+    datetime.to_iso_8601_like(),
+  ).unwrap();
+}
+
+/// Writes a countdown from `remaining` to zero, which a script in the page
+/// keeps ticking.
+pub fn write_countdown(remaining: Duration, into: &mut String) {
+  let milliseconds = remaining.total_milliseconds();
+  let seconds = milliseconds.div_ceil(1000);
+  write!(
+    into,
+    "<span data-countdown=\"{}\">{}:{:02}</span>",
+    milliseconds,
+    seconds / 60,
+    seconds % 60,
+  ).unwrap();
+}
+
+/// Sends a top-level navigation the rule `rule_id` of `feature` blocks to
+/// the daemon's block page, which tells the user why and until when. Other
+/// requests have no page to show it in, so they get an empty 403.
+pub fn respond(block_page_port: u16, exchange: &Exchange, feature: WebRuleFeature, rule_id: Uuid) -> Response {
+  respond_with_reason(block_page_port, exchange, BlockReason::WebRule { feature, rule_id })
+}
+
+/// `respond` for blocks that aren't made by a web rule.
+pub fn respond_with_reason(block_page_port: u16, exchange: &Exchange, reason: BlockReason) -> Response {
+  if exchange.is_top_level_navigation() {
+    Response::redirect(&reason.block_page_url(block_page_port, &exchange.url()))
+  } else {
+    Response::html(403, "Forbidden", "")
+  }
+}
+
+pub(crate) static STYLE: &str = "\
+body{margin:0;min-height:100vh;display:flex;align-items:center;justify-content:center;\
+font-family:system-ui,sans-serif;background:#f4f4f5;color:#18181b}\
+main{max-width:36rem;padding:2rem;background:#fff;border-radius:.75rem;\
+box-shadow:0 1px 3px rgba(0,0,0,.1)}\
+h1{margin-top:0;font-size:1.5rem}\
+p{line-height:1.5}\
+@media(prefers-color-scheme:dark){body{background:#18181b;color:#f4f4f5}main{background:#27272a}}";
+
+pub(crate) static SCRIPT: &str = "\
+for(const t of document.querySelectorAll('time[data-timestamp]')){\
+t.textContent=new Date(Number(t.dataset.timestamp)).toLocaleString()}\
+for(const c of document.querySelectorAll('[data-countdown]')){\
+const end=performance.now()+Number(c.dataset.countdown);\
+const tick=()=>{const s=Math.max(0,Math.ceil((end-performance.now())/1000));\
+c.textContent=Math.floor(s/60)+':'+String(s%60).padStart(2,'0');\
+if(s>0){setTimeout(tick,250)}};tick()}\
+for(const a of document.querySelectorAll('a[data-resume-at]')){\
+const wait=Number(a.dataset.resumeAt)-Date.now()+1000;\
+if(wait<2147483647){setTimeout(()=>{location.href=a.href},Math.max(0,wait))}}\
+for(const a of document.querySelectorAll('a[data-resume-in]')){\
+const wait=Number(a.dataset.resumeIn);\
+if(wait<2147483647){setTimeout(()=>{location.href=a.href},wait)}}";
@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use rcgen::{
+  BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType,
+  ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose, SanType,
+};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::{CertifiedKey, SigningKey};
+use crate::{DateTime, GenericError};
+
+static CERTIFICATE_FILE_NAME: &str = "WebRegulationIntrusiveCertificateAuthority.pem";
+static PRIVATE_KEY_FILE_NAME: &str = "WebRegulationIntrusiveCertificateAuthorityPrivateKey.pem";
+static COMMON_NAME: &str = "Discipline Local Certificate Authority";
+static ORGANIZATION_NAME: &str = "Discipline";
+
+/// Leaf certificates are minted on demand, once per server name, and kept
+/// here. When the cache gets this big, it's emptied to bound memory usage.
+const MAXIMUM_CACHED_LEAF_CERTIFICATES: usize = 2048;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Leaf certificates are valid from a day before they're minted, in case the
+/// client's clock is a little behind, until this long after. Browsers reject
+/// leaf certificates valid for longer than 398 days in all.
+const LEAF_CERTIFICATE_LIFETIME: Duration = Duration::from_secs(397 * 24 * 60 * 60);
+
+fn certificate_authority_params() -> Result<CertificateParams, GenericError> {
+  let mut params = CertificateParams::new(Vec::new()).map_err(|error|
+    GenericError::new("creating the parameters of the certificate authority")
+      .add_attachment("error", error.to_string())
+  )?;
+
+  // The distinguished name must be stable across restarts because it's what
+  // links minted leaf certificates to the installed authority certificate.
+  let mut distinguished_name = DistinguishedName::new();
+  distinguished_name.push(DnType::CommonName, COMMON_NAME);
+  distinguished_name.push(DnType::OrganizationName, ORGANIZATION_NAME);
+  params.distinguished_name = distinguished_name;
+  params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+  params.key_usages = vec![
+    KeyUsagePurpose::KeyCertSign,
+    KeyUsagePurpose::CrlSign,
+    KeyUsagePurpose::DigitalSignature,
+  ];
+
+  Ok(params)
+}
+
+/// A certificate authority generated once per installation whose private key
+/// never leaves the daemon's data directory. It signs the certificates we
+/// present to intercepted clients.
+pub struct CertificateAuthority {
+  certificate_path: PathBuf,
+  certificate_pem: String,
+  certificate_der: CertificateDer<'static>,
+  /// An in-memory re-creation of the authority certificate, with the same
+  /// distinguished name and key, used by rcgen as the issuer of leaf certificates.
+  issuer: Certificate,
+  issuer_key: KeyPair,
+  /// All leaf certificates share this key. Generating a key per server name
+  /// would make the first connection to every website noticeably slower.
+  leaf_key: KeyPair,
+  leaf_signing_key: Arc<dyn SigningKey>,
+  cached_leaf_certificates: Mutex<HashMap<String, Arc<CertifiedKey>>>,
+}
+
+impl fmt::Debug for CertificateAuthority {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("CertificateAuthority")
+      .field("certificate_path", &self.certificate_path)
+      .finish_non_exhaustive()
+  }
+}
+
+impl CertificateAuthority {
+  /// Loads the certificate authority from `data_directory`, generating and
+  /// storing a new one if there isn't any.
+  pub fn open(data_directory: &Path) -> Result<Self, GenericError> {
+    let certificate_path = data_directory.join(CERTIFICATE_FILE_NAME);
+    let private_key_path = data_directory.join(PRIVATE_KEY_FILE_NAME);
+
+    let (certificate_pem, issuer_key) = if certificate_path.exists() && private_key_path.exists() {
+      load(&certificate_path, &private_key_path)
+    } else {
+      generate(&certificate_path, &private_key_path)
+    }.map_err(|error|
+      error.change_context("opening the web regulation certificate authority")
+    )?;
+
+    let certificate_der = CertificateDer::from_pem_slice(certificate_pem.as_bytes()).map_err(|error|
+      GenericError::new("opening the web regulation certificate authority")
+        .add_error("the certificate file is not a valid pem certificate")
+        .add_attachment("path", certificate_path.to_string_lossy())
+        .add_attachment("error", error.to_string())
+    )?;
+
+    let issuer = certificate_authority_params()?
+      .self_signed(&issuer_key)
+      .map_err(|error|
+        GenericError::new("opening the web regulation certificate authority")
+          .add_error("failed to re-create the issuer certificate")
+          .add_attachment("error", error.to_string())
+      )?;
+
+    let leaf_key = KeyPair::generate().map_err(|error|
+      GenericError::new("opening the web regulation certificate authority")
+        .add_error("failed to generate the leaf certificates key")
+        .add_attachment("error", error.to_string())
+    )?;
+
+    let leaf_signing_key = rustls::crypto::ring::sign::any_supported_type(
+      &PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(leaf_key.serialize_der()))
+    ).map_err(|error|
+      GenericError::new("opening the web regulation certificate authority")
+        .add_error("rustls doesn't support the leaf certificates key")
+        .add_attachment("error", error.to_string())
+    )?;
+
+    Ok(Self {
+      certificate_path,
+      certificate_pem,
+      certificate_der,
+      issuer,
+      issuer_key,
+      leaf_key,
+      leaf_signing_key,
+      cached_leaf_certificates: Mutex::new(HashMap::new()),
+    })
+  }
+
+  pub fn certificate_pem(&self) -> &str {
+    &self.certificate_pem
+  }
+
+  /// The path of the pem-encoded authority certificate, which is what trust
+  /// stores are given. The private key is stored next to it.
+  pub fn certificate_path(&self) -> &Path {
+    &self.certificate_path
+  }
+
+  /// Returns a certificate for `server_name` signed by this authority, minting
+  /// one if it isn't cached yet.
+  pub fn leaf_certificate(&self, server_name: &str) -> Result<Arc<CertifiedKey>, GenericError> {
+    let server_name = server_name.to_ascii_lowercase();
+
+    if let Some(certificate) = self
+      .cached_leaf_certificates
+      .lock()
+      .unwrap()
+      .get(&server_name)
+    {
+      return Ok(Arc::clone(certificate));
+    }
+
+    let certificate = Arc::new(self.mint_leaf_certificate(&server_name)?);
+
+    let mut cached_leaf_certificates = self.cached_leaf_certificates.lock().unwrap();
+    if cached_leaf_certificates.len() >= MAXIMUM_CACHED_LEAF_CERTIFICATES {
+      cached_leaf_certificates.clear();
+    }
+    cached_leaf_certificates.insert(server_name, Arc::clone(&certificate));
+
+    Ok(certificate)
+  }
+
+  fn mint_leaf_certificate(&self, server_name: &str) -> Result<CertifiedKey, GenericError> {
+    let mut params = CertificateParams::new(Vec::new()).map_err(|error|
+      GenericError::new("minting a leaf certificate")
+        .add_attachment("server name", server_name)
+        .add_attachment("error", error.to_string())
+    )?;
+
+    let subject_alternative_name = match server_name.parse() {
+      Ok(ip_address) => {
+        SanType::IpAddress(ip_address)
+      }
+      Err(_) => {
+        SanType::DnsName(server_name.try_into().map_err(|error: rcgen::Error|
+          GenericError::new("minting a leaf certificate")
+            .add_error("server name is not a valid dns name")
+            .add_attachment("server name", server_name)
+            .add_attachment("error", error.to_string())
+        )?)
+      }
+    };
+
+    let mut distinguished_name = DistinguishedName::new();
+    distinguished_name.push(DnType::CommonName, server_name);
+    params.distinguished_name = distinguished_name;
+    params.subject_alt_names = vec![subject_alternative_name];
+    params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+    params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+    params.use_authority_key_identifier_extension = true;
+
+    let now = SystemTime::now();
+    params.not_before = (now - DAY).into();
+    params.not_after = (now + LEAF_CERTIFICATE_LIFETIME).into();
+
+    let certificate = params
+      .signed_by(&self.leaf_key, &self.issuer, &self.issuer_key)
+      .map_err(|error|
+        GenericError::new("minting a leaf certificate")
+          .add_error("failed to sign the leaf certificate")
+          .add_attachment("server name", server_name)
+          .add_attachment("error", error.to_string())
+      )?;
+
+    Ok(CertifiedKey::new(
+      vec![certificate.der().clone(), self.certificate_der.clone()],
+      Arc::clone(&self.leaf_signing_key),
+    ))
+  }
+}
+
+/// Lets rustls ask the certificate authority for a certificate matching the
+/// server name the client sent in its hello message.
+#[derive(Debug)]
+pub struct LeafCertificateResolver {
+  pub certificate_authority: Arc<CertificateAuthority>,
+  /// The name certificates are minted for when the client sends none, as
+  /// clients connecting to an ip address do.
+  pub fallback_server_name: Option<String>,
+}
+
+impl ResolvesServerCert for LeafCertificateResolver {
+  fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+    let server_name = client_hello
+      .server_name()
+      .or(self.fallback_server_name.as_deref())?;
+
+    self.certificate_authority.leaf_certificate(server_name).ok()
+  }
+}
+
+fn load(certificate_path: &Path, private_key_path: &Path) -> Result<(String, KeyPair), GenericError> {
+  let certificate_pem = fs::read_to_string(certificate_path).map_err(|error|
+    GenericError::new("loading the certificate authority")
+      .add_error("failed to read the certificate file")
+      .add_attachment("path", certificate_path.to_string_lossy())
+      .add_attachment("io error", error.to_string())
+  )?;
+
+  let private_key_pem = fs::read_to_string(private_key_path).map_err(|error|
+    GenericError::new("loading the certificate authority")
+      .add_error("failed to read the private key file")
+      .add_attachment("path", private_key_path.to_string_lossy())
+      .add_attachment("io error", error.to_string())
+  )?;
+
+  let private_key = KeyPair::from_pem(&private_key_pem).map_err(|error|
+    GenericError::new("loading the certificate authority")
+      .add_error("the private key file is not a valid pem private key")
+      .add_attachment("path", private_key_path.to_string_lossy())
+      .add_attachment("error", error.to_string())
+  )?;
+
+  Ok((certificate_pem, private_key))
+}
+
+fn generate(certificate_path: &Path, private_key_path: &Path) -> Result<(String, KeyPair), GenericError> {
+  let private_key = KeyPair::generate().map_err(|error|
+    GenericError::new("generating a certificate authority")
+      .add_error("failed to generate a private key")
+      .add_attachment("error", error.to_string())
+  )?;
+
+  let mut params = certificate_authority_params()?;
+  let now = DateTime::now();
+  params.not_before = rcgen::date_time_ymd(now.year() - 1, now.month() as u8, 1);
+  params.not_after = rcgen::date_time_ymd(now.year() + 20, now.month() as u8, 1);
+
+  let certificate = params.self_signed(&private_key).map_err(|error|
+    GenericError::new("generating a certificate authority")
+      .add_error("failed to self-sign the certificate")
+      .add_attachment("error", error.to_string())
+  )?;
+
+  // A key left behind without its certificate is of no use. Removing it
+  // lets the key file be created anew, readable by root only from the start.
+  if let Err(error) = fs::remove_file(private_key_path) {
+    if error.kind() != ErrorKind::NotFound {
+      return Err(
+        GenericError::new("generating a certificate authority")
+          .add_error("failed to remove the private key file left behind without a certificate")
+          .add_attachment("path", private_key_path.to_string_lossy())
+          .add_attachment("io error", error.to_string())
+      );
+    }
+  }
+
+  OpenOptions::new()
+    .write(true)
+    .create_new(true)
+    .mode(0o600)
+    .open(private_key_path)
+    .and_then(|mut file| file.write_all(private_key.serialize_pem().as_bytes()))
+    .map_err(|error|
+      GenericError::new("generating a certificate authority")
+        .add_error("failed to write the private key file")
+        .add_attachment("path", private_key_path.to_string_lossy())
+        .add_attachment("io error", error.to_string())
+    )?;
+
+  let certificate_pem = certificate.pem();
+  fs::write(certificate_path, &certificate_pem).map_err(|error|
+    GenericError::new("generating a certificate authority")
+      .add_error("failed to write the certificate file")
+      .add_attachment("path", certificate_path.to_string_lossy())
+      .add_attachment("io error", error.to_string())
+  )?;
+
+  Ok((certificate_pem, private_key))
+}
@@ -0,0 +1,393 @@
+use std::ops::Range;
+use std::sync::{Arc, Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+use crate::database::web_regulation_intrusive_content_filter_rule as rule_db;
+use crate::operating_system_integration::UserId;
+use crate::{Daemon, Database, DateTime, GenericError, Uuid};
+use crate::api::block_pages::WebRuleFeature;
+use super::super::block_page;
+use super::super::rule_activator::RuleActivator;
+use super::super::traffic::*;
+use super::super::url_pattern::{UrlPattern, UrlScoped, UrlScopedList};
+use super::matcher::{NormalizedText, WordMatcher};
+use super::text::{html_text, json_text};
+use super::word_lists::Language;
+
+pub const MAXIMUM_RULES_PER_USER: usize = 20;
+pub const MAXIMUM_KEYWORDS_PER_RULE: usize = 1000;
+pub const MAXIMUM_KEYWORD_LENGTH: usize = 100;
+
+/// What a rule does to a response with at least as many words as its threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+  /// Replace each char of the words with `*`.
+  MaskWords,
+  /// Show a block page instead.
+  BlockPage,
+}
+
+/// Looks for the words of some languages' lists and the rule's own keywords
+/// in the text of html and json responses, and acts on responses with at
+/// least `threshold` of them while its activator is effective.
+#[derive(Debug, Clone)]
+pub struct Rule {
+  id: Uuid,
+  user_id: UserId,
+  languages: Vec<Language>,
+  keywords: Vec<String>,
+  threshold: u32,
+  action: Action,
+  url_pattern: Option<UrlPattern>,
+  activator: RuleActivator,
+  matcher: Arc<WordMatcher>,
+}
+
+impl Rule {
+  #[allow(clippy::too_many_arguments)]
+  pub fn from_fields(
+    id: Uuid,
+    user_id: UserId,
+    languages: Vec<Language>,
+    keywords: Vec<String>,
+    threshold: u32,
+    action: Action,
+    url_pattern: Option<UrlPattern>,
+    activator: RuleActivator,
+  ) -> Self {
+    let words: Vec<&str> = languages
+      .iter()
+      .flat_map(|language| language.words())
+      .chain(keywords.iter().map(String::as_str))
+      .collect();
+
+    Self {
+      matcher: Arc::new(WordMatcher::new(words)),
+      id,
+      user_id,
+      languages,
+      keywords,
+      threshold,
+      action,
+      url_pattern,
+      activator,
+    }
+  }
+
+  pub fn id(&self) -> &Uuid {
+    &self.id
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn languages(&self) -> &Vec<Language> {
+    &self.languages
+  }
+
+  pub fn keywords(&self) -> &Vec<String> {
+    &self.keywords
+  }
+
+  pub fn threshold(&self) -> u32 {
+    self.threshold
+  }
+
+  pub fn action(&self) -> Action {
+    self.action
+  }
+
+  pub fn url_pattern(&self) -> Option<&UrlPattern> {
+    self.url_pattern.as_ref()
+  }
+
+  pub fn activator(&self) -> &RuleActivator {
+    &self.activator
+  }
+
+  /// Whether the rule applies to an exchange its url pattern matches.
+  pub fn applies_to(&self, exchange: &Exchange, now: DateTime) -> bool {
+    exchange.user_id == Some(self.user_id) && self.activator.is_effective(now)
+  }
+
+  /// The byte ranges of the words this rule looks for in `text`.
+  pub fn find(&self, text: &NormalizedText) -> Vec<Range<usize>> {
+    self.matcher.find(text)
+  }
+}
+
+impl UrlScoped for Rule {
+  fn url_patterns(&self) -> &[UrlPattern] {
+    self.url_pattern.as_slice()
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleCreator {
+  pub id: Option<Uuid>,
+  pub languages: Vec<Language>,
+  pub keywords: Vec<String>,
+  pub threshold: u32,
+  pub action: Action,
+  pub url_pattern: Option<String>,
+  pub activator: RuleActivator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RuleCreatorError {
+  /// There are neither languages nor keywords to look for.
+  NoWords,
+  TooManyKeywords { maximum: usize },
+  /// The keyword is empty, longer than `MAXIMUM_KEYWORD_LENGTH` chars or
+  /// has control chars, like line breaks.
+  InvalidKeyword { keyword: String },
+  ZeroThreshold,
+  InvalidUrlPattern,
+}
+
+impl RuleCreator {
+  pub fn create(self, user_id: UserId) -> Result<Rule, RuleCreatorError> {
+    let mut languages = Vec::new();
+    for language in self.languages {
+      if !languages.contains(&language) {
+        languages.push(language);
+      }
+    }
+
+    if self.keywords.len() > MAXIMUM_KEYWORDS_PER_RULE {
+      return Err(RuleCreatorError::TooManyKeywords { maximum: MAXIMUM_KEYWORDS_PER_RULE });
+    }
+
+    let keywords = clean_keywords(self.keywords)
+      .map_err(|keyword| RuleCreatorError::InvalidKeyword { keyword })?;
+
+    if languages.is_empty() && keywords.is_empty() {
+      return Err(RuleCreatorError::NoWords);
+    }
+
+    if self.threshold == 0 {
+      return Err(RuleCreatorError::ZeroThreshold);
+    }
+
+    let url_pattern = self
+      .url_pattern
+      .as_deref()
+      .map(UrlPattern::parse)
+      .transpose()
+      .map_err(|_| RuleCreatorError::InvalidUrlPattern)?;
+
+    Ok(Rule::from_fields(
+      self.id.unwrap_or_else(Uuid::new_v4),
+      user_id,
+      languages,
+      keywords,
+      self.threshold,
+      self.action,
+      url_pattern,
+      self.activator,
+    ))
+  }
+}
+
+/// Trims `keywords` and drops duplicates, or returns the first keyword that
+/// is empty, longer than `MAXIMUM_KEYWORD_LENGTH` chars or has control chars.
+pub fn clean_keywords(keywords: Vec<String>) -> Result<Vec<String>, String> {
+  let mut cleaned: Vec<String> = Vec::new();
+  for keyword in keywords {
+    let trimmed = keyword.trim();
+    if trimmed.is_empty()
+      || trimmed.chars().count() > MAXIMUM_KEYWORD_LENGTH
+      || trimmed.chars().any(char::is_control)
+    {
+      return Err(keyword);
+    }
+
+    if !cleaned.iter().any(|other| other == trimmed) {
+      cleaned.push(trimmed.to_string());
+    }
+  }
+
+  Ok(cleaned)
+}
+
+// SECTION: Traffic handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BodyKind {
+  Html,
+  Json,
+}
+
+impl BodyKind {
+  fn of(response: &ResponseHead) -> Option<BodyKind> {
+    if response.status_code == 204 || response.status_code == 304 {
+      return None;
+    }
+
+    let content_type = response.content_type()?;
+    match content_type.as_str() {
+      "text/html" | "application/xhtml+xml" => {
+        Some(BodyKind::Html)
+      }
+      "application/json" | "text/json" => {
+        Some(BodyKind::Json)
+      }
+      _ if content_type.ends_with("+json") => {
+        Some(BodyKind::Json)
+      }
+      _ => {
+        None
+      }
+    }
+  }
+
+  fn text(&self, body: &str) -> NormalizedText {
+    match self {
+      BodyKind::Html => html_text(body),
+      BodyKind::Json => json_text(body),
+    }
+  }
+}
+
+/// Masks words in, or blocks, html and json responses that have words of
+/// effective content filter rules in their text.
+///
+/// Only bodies in utf-8 are looked at, which are nearly all of them these
+/// days. Blocking wins over masking when several rules apply.
+pub struct ContentFilter {
+  rules: Mutex<UrlScopedList<Rule>>,
+}
+
+impl ContentFilter {
+  pub fn open(database: &Database) -> Result<Self, GenericError> {
+    let rules = rule_db::retrieve_all_rules(database)
+      .map_err(|error| error.change_context("opening the content filter"))?;
+
+    Ok(Self::new(rules))
+  }
+
+  pub fn new(rules: Vec<Rule>) -> Self {
+    Self {
+      rules: Mutex::new(UrlScopedList::new(rules)),
+    }
+  }
+
+  pub fn rules(&self) -> MutexGuard<'_, UrlScopedList<Rule>> {
+    self.rules.lock().unwrap()
+  }
+
+  /// The rules applying to the exchange as of `now`.
+  pub fn rules_for(&self, exchange: &Exchange, now: DateTime) -> Vec<Rule> {
+    let rules = self.rules();
+    rules
+      .matching(exchange)
+      .into_iter()
+      .map(|index| &rules[index])
+      .filter(|rule| rule.applies_to(exchange, now))
+      .cloned()
+      .collect()
+  }
+
+  fn has_rules_for(&self, exchange: &Exchange) -> bool {
+    !self.rules_for(exchange, DateTime::now()).is_empty()
+  }
+}
+
+impl TrafficHandler for ContentFilter {
+  fn may_inspect_response_body(&self, _daemon: &Daemon, exchange: &Exchange) -> bool {
+    self.has_rules_for(exchange)
+  }
+
+  fn on_response(
+    &self,
+    _daemon: &Daemon,
+    exchange: &Exchange,
+    response: &mut ResponseHead,
+  ) -> ResponseVerdict {
+    if BodyKind::of(response).is_some() && self.has_rules_for(exchange) {
+      ResponseVerdict::InspectBody
+    } else {
+      ResponseVerdict::Forward
+    }
+  }
+
+  fn on_response_body(
+    &self,
+    daemon: &Daemon,
+    exchange: &Exchange,
+    response: &mut ResponseHead,
+    body: &mut Vec<u8>,
+  ) -> BodyVerdict {
+    let rules = self.rules_for(exchange, DateTime::now());
+    match filter_body(&rules, response, body) {
+      Some(rule_id) => {
+        BodyVerdict::Respond(block_page::respond(
+          daemon.configuration().block_page_port(),
+          exchange,
+          WebRuleFeature::ContentFilter,
+          rule_id,
+        ))
+      }
+      None => {
+        BodyVerdict::Forward
+      }
+    }
+  }
+}
+
+/// Masks the words `rules` find in `body`, or returns the id of a rule that
+/// blocks the body. Bodies that aren't html or json in utf-8 are left alone.
+pub fn filter_body(rules: &[Rule], response: &ResponseHead, body: &mut Vec<u8>) -> Option<Uuid> {
+  if rules.is_empty() {
+    return None;
+  }
+
+  let kind = BodyKind::of(response)?;
+  let text = kind.text(std::str::from_utf8(body).ok()?);
+
+  let mut masked = Vec::new();
+  for rule in rules {
+    let words = rule.find(&text);
+    if words.len() < rule.threshold as usize {
+      continue;
+    }
+
+    match rule.action {
+      Action::BlockPage => {
+        return Some(rule.id);
+      }
+      Action::MaskWords => {
+        masked.extend(words);
+      }
+    }
+  }
+
+  if !masked.is_empty() {
+    mask(body, &text, &masked);
+  }
+
+  None
+}
+
+/// Replaces each source char of `words`, byte ranges of `text`, with `*`.
+pub fn mask(body: &mut Vec<u8>, text: &NormalizedText, words: &[Range<usize>]) {
+  let mut sources: Vec<Range<usize>> = words
+    .iter()
+    .flat_map(|word| text.sources_of(word.clone()))
+    .filter(|source| !source.is_empty())
+    .cloned()
+    .collect();
+
+  sources.sort_unstable_by_key(|source| source.start);
+  sources.dedup();
+
+  let mut masked = Vec::with_capacity(body.len());
+  let mut index = 0;
+  for source in sources {
+    masked.extend_from_slice(&body[index..source.start]);
+    masked.push(b'*');
+    index = source.end;
+  }
+  masked.extend_from_slice(&body[index..]);
+
+  *body = masked;
+}
@@ -0,0 +1,204 @@
+use std::ops::Range;
+use aho_corasick::{AhoCorasick, MatchKind};
+use unicode_normalization::char::{decompose_compatible, is_combining_mark};
+
+// SECTION: Normalization.
+/// Text prepared for matching, remembering where each of its chars came from.
+///
+/// Text is decomposed by compatibility, so `ｆｕｌｌ ｗｉｄｔｈ` and `ﬁ`
+/// ligatures match their plain forms, and lowercased. Accents are dropped
+/// from Latin letters, so `pùta` matches `puta`, while other scripts keep
+/// their marks, which are part of spelling there. Invisible chars that are
+/// used to break up words are dropped, and runs of whitespace become one space.
+#[derive(Debug, Clone, Default)]
+pub struct NormalizedText {
+  text: String,
+  /// The byte offset of each char of `text`.
+  offsets: Vec<usize>,
+  /// Where in the source each char of `text` came from, by the index of the char.
+  sources: Vec<Range<usize>>,
+  is_after_latin_letter: bool,
+}
+
+impl NormalizedText {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn normalize(text: &str) -> Self {
+    let mut normalized = Self::new();
+    for (index, char) in text.char_indices() {
+      normalized.push(char, index..index + char.len_utf8());
+    }
+    normalized
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.text
+  }
+
+  /// Appends `char`, which is at `source` in the source.
+  pub fn push(&mut self, char: char, source: Range<usize>) {
+    if is_invisible(char) {
+      return;
+    }
+
+    if char.is_whitespace() {
+      self.push_break();
+      return;
+    }
+
+    let char = match char {
+      '\u{2018}' | '\u{2019}' | '\u{02bc}' => '\'',
+      _ => char,
+    };
+
+    decompose_compatible(char, |decomposed| {
+      if is_combining_mark(decomposed) {
+        if !self.is_after_latin_letter {
+          self.push_normalized(decomposed, source.clone());
+        }
+        return;
+      }
+
+      self.is_after_latin_letter = is_latin_letter(decomposed);
+      for lowercase in decomposed.to_lowercase() {
+        self.push_normalized(lowercase, source.clone());
+      }
+    });
+  }
+
+  /// Separates what comes next from what came before, like whitespace does.
+  pub fn push_break(&mut self) {
+    self.is_after_latin_letter = false;
+    if !self.text.is_empty() && !self.text.ends_with(' ') {
+      let source = self.sources.last().map_or(0..0, |source| source.end..source.end);
+      self.push_normalized(' ', source);
+    }
+  }
+
+  fn push_normalized(&mut self, char: char, source: Range<usize>) {
+    self.offsets.push(self.text.len());
+    self.sources.push(source);
+    self.text.push(char);
+  }
+
+  /// The source ranges of the chars in `range`, a byte range of the
+  /// normalized text. Chars from the same source char share a range.
+  pub fn sources_of(&self, range: Range<usize>) -> impl Iterator<Item = &Range<usize>> + '_ {
+    let start = self.offsets.partition_point(|offset| *offset < range.start);
+    let end = self.offsets.partition_point(|offset| *offset < range.end);
+    self.sources[start..end].iter()
+  }
+}
+
+fn is_invisible(char: char) -> bool {
+  matches!(char, '\u{ad}' | '\u{200b}'..='\u{200d}' | '\u{2060}' | '\u{feff}')
+}
+
+fn is_latin_letter(char: char) -> bool {
+  char.is_ascii_alphabetic()
+    || ('\u{c0}'..='\u{24f}').contains(&char) && char != '\u{d7}' && char != '\u{f7}'
+    || ('\u{1e00}'..='\u{1eff}').contains(&char)
+}
+
+fn is_word_char(char: char) -> bool {
+  char.is_alphanumeric() || is_combining_mark(char)
+}
+
+/// Whether `char` belongs to a script written without spaces between
+/// words, where words can't be told apart by their boundaries.
+fn is_unspaced(char: char) -> bool {
+  matches!(
+    char,
+    '\u{0e00}'..='\u{0eff}' // Thai and Lao
+      | '\u{1000}'..='\u{109f}' // Myanmar
+      | '\u{1780}'..='\u{17ff}' // Khmer
+      | '\u{3040}'..='\u{30ff}' // Hiragana and Katakana
+      | '\u{31f0}'..='\u{31ff}'
+      | '\u{3400}'..='\u{4dbf}' // Han
+      | '\u{4e00}'..='\u{9fff}'
+      | '\u{f900}'..='\u{faff}'
+      | '\u{20000}'..='\u{2ffff}'
+  )
+}
+
+// SECTION: Matching.
+/// Finds words and phrases in normalized text.
+#[derive(Debug, Clone)]
+pub struct WordMatcher {
+  automaton: AhoCorasick,
+}
+
+impl WordMatcher {
+  pub fn new<'a>(words: impl IntoIterator<Item = &'a str>) -> Self {
+    let mut patterns: Vec<String> = words
+      .into_iter()
+      .map(|word| NormalizedText::normalize(word).as_str().trim().to_string())
+      .filter(|word| !word.is_empty())
+      .collect();
+
+    patterns.sort_unstable();
+    patterns.dedup();
+
+    let automaton = AhoCorasick::builder()
+      .match_kind(MatchKind::Standard)
+      .build(&patterns)
+      .expect("word lists are far below the size limits of the automaton");
+
+    Self { automaton }
+  }
+
+  /// The byte ranges of the words found in `text`, in order and without overlaps.
+  ///
+  /// A word is only found where it isn't part of a longer word, so `ass`
+  /// isn't found in `class`. In scripts written without spaces, words are
+  /// found wherever they are.
+  pub fn find(&self, text: &NormalizedText) -> Vec<Range<usize>> {
+    let text = text.as_str();
+
+    let mut found: Vec<Range<usize>> = self
+      .automaton
+      .find_overlapping_iter(text)
+      .map(|found| found.range())
+      .filter(|range| is_at_word_boundaries(text, range))
+      .collect();
+
+    // Prefer the longest of the words starting at the same place, like
+    // `asshole` over `ass`.
+    found.sort_unstable_by_key(|range| (range.start, usize::MAX - range.end));
+
+    let mut words: Vec<Range<usize>> = Vec::new();
+    for range in found {
+      if words.last().is_none_or(|last| last.end <= range.start) {
+        words.push(range);
+      }
+    }
+    words
+  }
+}
+
+fn is_at_word_boundaries(text: &str, range: &Range<usize>) -> bool {
+  let found = &text[range.clone()];
+  let (Some(first), Some(last)) = (found.chars().next(), found.chars().next_back()) else {
+    return false;
+  };
+
+  let before = text[..range.start].chars().next_back();
+  let after = text[range.end..].chars().next();
+
+  // Never end between a letter and its marks.
+  if after.is_some_and(is_combining_mark) {
+    return false;
+  }
+
+  let is_start_boundary = before.is_none_or(|before| !is_word_char(before))
+    || !is_word_char(first)
+    || is_unspaced(first);
+
+  let is_end_boundary = after.is_none_or(|after| !is_word_char(after))
+    || !is_word_char(last)
+    || is_unspaced(last);
+
+  is_start_boundary && is_end_boundary
+}
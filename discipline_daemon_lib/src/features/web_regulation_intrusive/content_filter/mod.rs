@@ -0,0 +1,26 @@
+pub mod word_lists;
+pub use word_lists::Language;
+
+pub mod matcher;
+pub use matcher::{NormalizedText, WordMatcher};
+
+pub mod text;
+pub use text::{html_text, json_text};
+
+pub mod feature;
+pub use feature::{
+  clean_keywords,
+  filter_body,
+  mask,
+  Action,
+  ContentFilter,
+  Rule,
+  RuleCreator,
+  RuleCreatorError,
+  MAXIMUM_KEYWORDS_PER_RULE,
+  MAXIMUM_KEYWORD_LENGTH,
+  MAXIMUM_RULES_PER_USER,
+};
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,165 @@
+use crate::operating_system_integration::UserId;
+use super::super::rule_activator::RuleActivator;
+use super::super::traffic::*;
+use super::*;
+
+fn found<'a>(matcher: &WordMatcher, text: &'a NormalizedText) -> Vec<&'a str> {
+  matcher
+    .find(text)
+    .into_iter()
+    .map(|range| &text.as_str()[range])
+    .collect()
+}
+
+fn words(words: &[&str], text: &str) -> Vec<String> {
+  let matcher = WordMatcher::new(words.iter().copied());
+  let text = NormalizedText::normalize(text);
+  found(&matcher, &text).into_iter().map(str::to_string).collect()
+}
+
+fn creator(languages: Vec<Language>, keywords: &[&str], threshold: u32, action: Action) -> RuleCreator {
+  RuleCreator {
+    id: None,
+    languages,
+    keywords: keywords.iter().map(|keyword| keyword.to_string()).collect(),
+    threshold,
+    action,
+    url_pattern: None,
+    activator: RuleActivator::AllTheTime,
+  }
+}
+
+#[test]
+fn loads_every_bundled_list() {
+  for language in Language::ALL {
+    assert!(!language.words().is_empty(), "{language:?}");
+  }
+
+  assert!(Language::English.words().contains(&"bloody hell"));
+  assert!(Language::French.words().contains(&"baise"));
+}
+
+#[test]
+fn normalizes_text() {
+  let normalize = |text: &str| NormalizedText::normalize(text).as_str().to_string();
+
+  assert_eq!(normalize("ＦＵＬＬ  Width\n\tﬁne"), "full width fine");
+  assert_eq!(normalize("Crème BRÛLÉE"), "creme brulee");
+  assert_eq!(normalize("sh\u{200b}it s\u{ad}hit"), "shit shit");
+  assert_eq!(normalize("va t’en"), "va t'en");
+  // Marks of other scripts are part of their spelling.
+  assert_eq!(normalize("हिन्दी"), "हिन्दी");
+}
+
+#[test]
+fn finds_whole_words_only() {
+  assert_eq!(words(&["ass"], "A class of bass. Ass!"), ["ass"]);
+  assert_eq!(words(&["ass", "asshole"], "what an ASSHOLE"), ["asshole"]);
+  assert_eq!(words(&["2 girls 1 cup"], "2   girls\n1 cup"), ["2 girls 1 cup"]);
+  assert_eq!(words(&["puta"], "pùta, putamen"), ["puta"]);
+  assert_eq!(words(&["a$$"], "a$$ a$$hole"), ["a$$", "a$$"]);
+}
+
+#[test]
+fn finds_words_in_unspaced_scripts() {
+  assert_eq!(words(&["三级片"], "这是三级片吗"), ["三级片"]);
+  assert_eq!(words(&["กู"], "กูไม่รู้"), ["กู"]);
+  // A word that ends before a mark is part of a longer one.
+  assert_eq!(words(&["か"], "が"), Vec::<String>::new());
+}
+
+#[test]
+fn reads_text_of_html() {
+  let html = "<!DOCTYPE html><html><head><title>Damn &amp; blast</title>\
+    <script>var damn = '<p>damn</p>';</script><style>.damn{}</style></head>\
+    <body><p class=\"damn\" title='damn'>da<!-- -->mn it<br>d&#97;mn</p>a < b</body></html>";
+
+  assert_eq!(html_text(html).as_str().trim_end(), "damn & blast da mn it damn a < b");
+}
+
+#[test]
+fn reads_text_of_json() {
+  let json = r#")]}'
+    {"damn": "Damn\nit", "list": ["damn", 1, true, "😀 \"damn\""], "x": "unclosed"#;
+
+  assert_eq!(json_text(json).as_str(), "damn it damn 😀 \"damn\" unclosed");
+}
+
+#[test]
+fn masks_words_in_their_source() {
+  let matcher = WordMatcher::new(["damn", "bloody hell"]);
+
+  let html = "<p title=\"damn\">D&#97;mn, <b>bloody</b>\n  hell!</p>";
+  let text = html_text(html);
+  let mut body = html.as_bytes().to_vec();
+  mask(&mut body, &text, &matcher.find(&text));
+  assert_eq!(String::from_utf8(body).unwrap(), "<p title=\"damn\">****, <b>******</b>\n  ****!</p>");
+
+  let json = r#"{"damn":"damn ünd"}"#;
+  let text = json_text(json);
+  let mut body = json.as_bytes().to_vec();
+  mask(&mut body, &text, &matcher.find(&text));
+  assert_eq!(String::from_utf8(body).unwrap(), r#"{"damn":"**** ünd"}"#);
+}
+
+#[test]
+fn creates_rules() {
+  let user_id = UserId::new(1000);
+
+  let rule = creator(vec![], &[" Foo ", "foo", "bar baz"], 1, Action::MaskWords).create(user_id).unwrap();
+  assert_eq!(rule.keywords(), &["Foo", "foo", "bar baz"]);
+
+  assert!(matches!(
+    creator(vec![], &[], 1, Action::MaskWords).create(user_id),
+    Err(RuleCreatorError::NoWords),
+  ));
+  assert!(matches!(
+    creator(vec![], &["a\nb"], 1, Action::MaskWords).create(user_id),
+    Err(RuleCreatorError::InvalidKeyword { .. }),
+  ));
+  assert!(matches!(
+    creator(vec![], &["  "], 1, Action::MaskWords).create(user_id),
+    Err(RuleCreatorError::InvalidKeyword { .. }),
+  ));
+  assert!(matches!(
+    creator(vec![Language::English], &[], 0, Action::BlockPage).create(user_id),
+    Err(RuleCreatorError::ZeroThreshold),
+  ));
+}
+
+#[test]
+fn masks_or_blocks_once_the_threshold_is_reached() {
+  let user_id = UserId::new(1000);
+  let rules = vec![
+    creator(vec![], &["heck"], 2, Action::MaskWords).create(user_id).unwrap(),
+    creator(vec![], &["heck"], 3, Action::BlockPage).create(user_id).unwrap(),
+  ];
+
+  let filter = |content_type: &str, body: &str| {
+    let mut response = ResponseHead::new(200, "OK");
+    response.headers.append("Content-Type", content_type);
+    let mut body = body.as_bytes().to_vec();
+    let blocked = filter_body(&rules, &response, &mut body);
+    (blocked, String::from_utf8(body).unwrap())
+  };
+
+  assert_eq!(filter("text/html", "<p>heck</p>"), (None, "<p>heck</p>".into()));
+  assert_eq!(filter("text/html; charset=utf-8", "<p>heck, HECK</p>"), (None, "<p>****, ****</p>".into()));
+  assert_eq!(filter("text/html", "<p>heck heck heck</p>").0, Some(*rules[1].id()));
+  assert_eq!(filter("application/ld+json", r#"{"heck": ["heck", "heck"]}"#), (None, r#"{"heck": ["****", "****"]}"#.into()));
+  assert_eq!(filter("text/plain", "heck heck heck"), (None, "heck heck heck".into()));
+}
+
+#[test]
+fn applies_rules_to_their_user_and_urls() {
+  let now = crate::DateTime::now();
+  let mut creator = creator(vec![Language::English], &[], 1, Action::BlockPage);
+  creator.url_pattern = Some("*.example.com".into());
+  let content_filter = ContentFilter::new(vec![creator.create(UserId::new(1000)).unwrap()]);
+
+  assert_eq!(content_filter.rules_for(&Exchange::to("https://www.example.com/"), now).len(), 1);
+  assert!(content_filter.rules_for(&Exchange::to("https://example.org/"), now).is_empty());
+
+  let other_user = Exchange::to("https://www.example.com/").with_user_id(Some(UserId::new(1001)));
+  assert!(content_filter.rules_for(&other_user, now).is_empty());
+}
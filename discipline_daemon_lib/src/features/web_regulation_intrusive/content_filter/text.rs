@@ -0,0 +1,215 @@
+use super::super::element_rules::rewriter::decode_character_reference;
+use super::matcher::NormalizedText;
+
+/// Elements whose content isn't text the user reads.
+const HIDDEN_RAW_TEXT_ELEMENTS: [&str; 7] = [
+  "script",
+  "style",
+  "xmp",
+  "iframe",
+  "noembed",
+  "noframes",
+  "noscript",
+];
+
+/// Elements whose content is text, tags or not.
+const VISIBLE_RAW_TEXT_ELEMENTS: [&str; 2] = [
+  "title",
+  "textarea",
+];
+
+// SECTION: Html.
+/// The text of an html document, which is what's between its tags, without
+/// scripts, styles and comments. Tags separate words.
+pub fn html_text(html: &str) -> NormalizedText {
+  let mut text = NormalizedText::new();
+  let bytes = html.as_bytes();
+  let mut index = 0;
+
+  while index < html.len() {
+    let rest = &html[index..];
+    if let Some(comment) = rest.strip_prefix("<!--") {
+      text.push_break();
+      index += comment.find("-->").map_or(rest.len(), |end| end + 7);
+      continue;
+    }
+
+    if rest.starts_with('<') && bytes.get(index + 1).is_some_and(|next| next.is_ascii_alphabetic() || matches!(next, b'/' | b'!' | b'?')) {
+      text.push_break();
+      let tag_length = find_tag_end(rest).unwrap_or(rest.len());
+      let name = tag_name(&rest[..tag_length]);
+      index += tag_length;
+
+      if rest.as_bytes()[1] != b'/' {
+        if HIDDEN_RAW_TEXT_ELEMENTS.contains(&name.as_str()) {
+          index += find_end_tag(&html[index..], &name).unwrap_or(html.len() - index);
+        } else if VISIBLE_RAW_TEXT_ELEMENTS.contains(&name.as_str()) {
+          let end = index + find_end_tag(&html[index..], &name).unwrap_or(html.len() - index);
+          push_html_text(&mut text, html, index..end);
+          index = end;
+        }
+      }
+      continue;
+    }
+
+    // A `<` that doesn't start a tag is text.
+    let first_length = rest.chars().next().unwrap().len_utf8();
+    let end = index + first_length + rest[first_length..].find('<').unwrap_or(rest.len() - first_length);
+    push_html_text(&mut text, html, index..end);
+    index = end;
+  }
+
+  text
+}
+
+/// Pushes the chars of `html[range]`, decoding character references.
+fn push_html_text(text: &mut NormalizedText, html: &str, range: std::ops::Range<usize>) {
+  let mut index = range.start;
+  while index < range.end {
+    let rest = &html[index..range.end];
+    if let Some((char, length)) = decode_character_reference(rest) {
+      text.push(char, index..index + length);
+      index += length;
+      continue;
+    }
+
+    let char = rest.chars().next().unwrap();
+    text.push(char, index..index + char.len_utf8());
+    index += char.len_utf8();
+  }
+}
+
+/// The length of the tag `html` starts with, up to and including its `>`.
+fn find_tag_end(html: &str) -> Option<usize> {
+  let mut quote = None;
+  for (index, byte) in html.bytes().enumerate().skip(1) {
+    match (quote, byte) {
+      (None, b'"' | b'\'') => {
+        quote = Some(byte);
+      }
+      (Some(open), _) if open == byte => {
+        quote = None;
+      }
+      (None, b'>') => {
+        return Some(index + 1);
+      }
+      _ => {}
+    }
+  }
+  None
+}
+
+fn tag_name(tag: &str) -> String {
+  tag
+    .trim_start_matches(['<', '/'])
+    .split(|char: char| char.is_ascii_whitespace() || char == '/' || char == '>')
+    .next()
+    .unwrap_or_default()
+    .to_ascii_lowercase()
+}
+
+/// Where the end tag of the raw text element `name` starts in `html`.
+fn find_end_tag(html: &str, name: &str) -> Option<usize> {
+  let bytes = html.as_bytes();
+  let length = name.len() + 2;
+  (0..bytes.len().saturating_sub(length - 1)).find(|index| {
+    bytes[*index..*index + length].starts_with(b"</")
+      && bytes[*index + 2..*index + length].eq_ignore_ascii_case(name.as_bytes())
+      && bytes.get(*index + length).is_none_or(|next| matches!(next, b'>' | b'/' | b' ' | b'\t' | b'\n' | b'\r' | b'\x0c'))
+  })
+}
+
+// SECTION: Json.
+/// The text of a json document, which is its string values. Keys aren't
+/// text, and each string is its own run of words.
+///
+/// This doesn't check the document is valid, so it also works for json
+/// with a prefix against hijacking, like `)]}'`, or wrapped in a callback.
+pub fn json_text(json: &str) -> NormalizedText {
+  let mut text = NormalizedText::new();
+  let mut index = 0;
+
+  while let Some(start) = json[index..].find('"').map(|start| index + start) {
+    let (content_end, end) = find_string_end(json, start);
+    let is_key = json[end..].trim_start().starts_with(':');
+    if !is_key {
+      text.push_break();
+      push_json_string(&mut text, json, start + 1..content_end);
+    }
+    index = end;
+  }
+
+  text
+}
+
+/// Where the content of the string starting at `start` ends, and the index
+/// after its closing quote. Both are the end of `json` if it isn't closed.
+fn find_string_end(json: &str, start: usize) -> (usize, usize) {
+  let bytes = json.as_bytes();
+  let mut index = start + 1;
+  while index < bytes.len() {
+    match bytes[index] {
+      b'\\' => {
+        index += 2;
+      }
+      b'"' => {
+        return (index, index + 1);
+      }
+      _ => {
+        index += 1;
+      }
+    }
+  }
+  (json.len(), json.len())
+}
+
+/// Pushes the chars of the string content `json[range]`, decoding escapes.
+fn push_json_string(text: &mut NormalizedText, json: &str, range: std::ops::Range<usize>) {
+  let mut index = range.start;
+  while index < range.end {
+    let rest = &json[index..range.end];
+    let (char, length) = match rest.strip_prefix('\\') {
+      Some(escaped) => {
+        decode_json_escape(escaped).map_or((None, 1), |(char, length)| (char, length + 1))
+      }
+      None => {
+        let char = rest.chars().next().unwrap();
+        (Some(char), char.len_utf8())
+      }
+    };
+
+    if let Some(char) = char {
+      text.push(char, index..index + length);
+    }
+    index += length;
+  }
+}
+
+/// Decodes the escape after a backslash into its char, if it has one, and
+/// the length of the escape without the backslash.
+fn decode_json_escape(escaped: &str) -> Option<(Option<char>, usize)> {
+  let first = escaped.chars().next()?;
+  let char = match first {
+    'n' | 'r' | 't' | 'b' | 'f' => ' ',
+    'u' => {
+      let code = u16::from_str_radix(escaped.get(1..5)?, 16).ok()?;
+      if !(0xd800..0xdc00).contains(&code) {
+        return Some((char::from_u32(code.into()), 5));
+      }
+
+      // A surrogate pair, like `\ud83d\ude00`.
+      let low = escaped
+        .get(5..11)
+        .and_then(|low| low.strip_prefix("\\u"))
+        .and_then(|low| u16::from_str_radix(low, 16).ok())
+        .filter(|low| (0xdc00..0xe000).contains(low));
+      return match low {
+        Some(low) => Some((char::decode_utf16([code, low]).next()?.ok(), 11)),
+        None => Some((None, 5)),
+      };
+    }
+    other => other,
+  };
+
+  Some((Some(char), first.len_utf8()))
+}
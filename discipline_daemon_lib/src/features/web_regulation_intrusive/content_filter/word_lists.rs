@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+
+/// A language with a bundled list of profane and offensive words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Language {
+  Arabic,
+  Chinese,
+  Czech,
+  Danish,
+  Dutch,
+  English,
+  Esperanto,
+  Filipino,
+  Finnish,
+  French,
+  German,
+  Hindi,
+  Hungarian,
+  Indonesian,
+  Italian,
+  Japanese,
+  Kabyle,
+  Korean,
+  Norwegian,
+  Persian,
+  Polish,
+  Portuguese,
+  Russian,
+  Spanish,
+  Swedish,
+  Thai,
+  Turkish,
+}
+
+enum WordList {
+  /// A json array of strings.
+  Json(&'static str),
+  /// One word or phrase per line.
+  Lines(&'static str),
+}
+
+macro_rules! blocklist {
+  ($path:literal) => {
+    include_str!(concat!("../../../../../discipline_attachments/blocklists/", $path))
+  };
+}
+
+impl Language {
+  pub const ALL: [Language; 27] = [
+    Language::Arabic,
+    Language::Chinese,
+    Language::Czech,
+    Language::Danish,
+    Language::Dutch,
+    Language::English,
+    Language::Esperanto,
+    Language::Filipino,
+    Language::Finnish,
+    Language::French,
+    Language::German,
+    Language::Hindi,
+    Language::Hungarian,
+    Language::Indonesian,
+    Language::Italian,
+    Language::Japanese,
+    Language::Kabyle,
+    Language::Korean,
+    Language::Norwegian,
+    Language::Persian,
+    Language::Polish,
+    Language::Portuguese,
+    Language::Russian,
+    Language::Spanish,
+    Language::Swedish,
+    Language::Thai,
+    Language::Turkish,
+  ];
+
+  /// The bundled lists of this language. Some languages are in both
+  /// collections, and the English one also has Facebook's list and British slang.
+  fn word_lists(&self) -> &'static [WordList] {
+    match self {
+      Language::Arabic => &[
+        WordList::Json(blocklist!("bad-words-in-different-languages/arabic.json")),
+        WordList::Lines(blocklist!("bad-words-in-different-languages-2/ar")),
+      ],
+      Language::Chinese => &[
+        WordList::Json(blocklist!("bad-words-in-different-languages/chinese.json")),
+        WordList::Lines(blocklist!("bad-words-in-different-languages-2/zh")),
+      ],
+      Language::Czech => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/cs"))],
+      Language::Danish => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/da"))],
+      Language::Dutch => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/nl"))],
+      Language::English => &[
+        WordList::Json(blocklist!("bad-words-in-different-languages/english.json")),
+        WordList::Json(blocklist!("bad-words-in-different-languages/british.json")),
+        WordList::Json(blocklist!("facebook-blocked-words.json")),
+        WordList::Lines(blocklist!("bad-words-in-different-languages-2/en")),
+      ],
+      Language::Esperanto => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/eo"))],
+      Language::Filipino => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/fil"))],
+      Language::Finnish => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/fi"))],
+      Language::French => &[
+        WordList::Json(blocklist!("bad-words-in-different-languages/french.json")),
+        WordList::Lines(blocklist!("bad-words-in-different-languages-2/fr")),
+        WordList::Lines(blocklist!("bad-words-in-different-languages-2/fr-CA-u-sd-caqc")),
+      ],
+      Language::German => &[
+        WordList::Json(blocklist!("bad-words-in-different-languages/german.json")),
+        WordList::Lines(blocklist!("bad-words-in-different-languages-2/de")),
+      ],
+      Language::Hindi => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/hi"))],
+      Language::Hungarian => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/hu"))],
+      Language::Indonesian => &[WordList::Json(blocklist!("bad-words-in-different-languages/indonesian.json"))],
+      Language::Italian => &[
+        WordList::Json(blocklist!("bad-words-in-different-languages/italian.json")),
+        WordList::Lines(blocklist!("bad-words-in-different-languages-2/it")),
+      ],
+      Language::Japanese => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/ja"))],
+      Language::Kabyle => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/kab"))],
+      Language::Korean => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/ko"))],
+      Language::Norwegian => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/no"))],
+      Language::Persian => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/fa"))],
+      Language::Polish => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/pl"))],
+      Language::Portuguese => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/pt"))],
+      Language::Russian => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/ru"))],
+      Language::Spanish => &[
+        WordList::Json(blocklist!("bad-words-in-different-languages/spanish.json")),
+        WordList::Lines(blocklist!("bad-words-in-different-languages-2/es")),
+      ],
+      Language::Swedish => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/sv"))],
+      Language::Thai => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/th"))],
+      Language::Turkish => &[WordList::Lines(blocklist!("bad-words-in-different-languages-2/tr"))],
+    }
+  }
+
+  /// The words and phrases of this language's lists, as they're written there.
+  pub fn words(&self) -> Vec<&'static str> {
+    let mut words = Vec::new();
+    for word_list in self.word_lists() {
+      match word_list {
+        WordList::Json(json) => {
+          let list: Vec<&'static str> = serde_json::from_str(json)
+            .expect("bundled json word lists are arrays of strings without escapes");
+          words.extend(list);
+        }
+        WordList::Lines(lines) => {
+          words.extend(lines.lines());
+        }
+      }
+    }
+
+    words.retain(|word| !word.trim().is_empty());
+    words
+  }
+}
@@ -0,0 +1,109 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use ring::digest::{digest, SHA256};
+use super::traffic::ResponseHead;
+
+/// Changes the content security policies of `response` so `script`, inlined
+/// into the page, may run and fetch from the page's own origin.
+///
+/// Policies that already allow inline scripts are left as they are, as
+/// listing a hash would disallow the page's own inline scripts.
+pub fn allow_inline_script(response: &mut ResponseHead, script: &str) {
+  let hash = format!("'sha256-{}'", BASE64.encode(digest(&SHA256, script.as_bytes())));
+
+  let policies: Vec<String> = response
+    .headers
+    .get_all("Content-Security-Policy")
+    .map(|policy| allow_in_policy(policy, &hash))
+    .collect();
+
+  if policies.is_empty() {
+    return;
+  }
+
+  response.headers.remove("Content-Security-Policy");
+  for policy in policies {
+    response.headers.append("Content-Security-Policy", policy);
+  }
+}
+
+struct Directive {
+  name: String,
+  sources: Vec<String>,
+}
+
+fn allow_in_policy(policy: &str, hash: &str) -> String {
+  let mut directives: Vec<Directive> = policy
+    .split(';')
+    .filter_map(|directive| {
+      let mut parts = directive.split_ascii_whitespace();
+      Some(Directive {
+        name: parts.next()?.to_ascii_lowercase(),
+        sources: parts.map(str::to_string).collect(),
+      })
+    })
+    .collect();
+
+  add_source(&mut directives, "script-src", hash, allows_inline_scripts);
+  if directives.iter().any(|directive| directive.name == "script-src-elem") {
+    add_source(&mut directives, "script-src-elem", hash, allows_inline_scripts);
+  }
+  add_source(&mut directives, "connect-src", "'self'", allows_own_origin);
+
+  directives
+    .iter()
+    .map(|directive| {
+      let mut text = directive.name.clone();
+      for source in &directive.sources {
+        text.push(' ');
+        text.push_str(source);
+      }
+      text
+    })
+    .collect::<Vec<String>>()
+    .join("; ")
+}
+
+/// Adds `source` to the directive `name`, or to a copy of `default-src` if
+/// the policy falls back on it, unless the sources already allow what
+/// `source` would.
+fn add_source(
+  directives: &mut Vec<Directive>,
+  name: &str,
+  source: &str,
+  already_allows: fn(&[String]) -> bool,
+) {
+  if let Some(directive) = directives.iter_mut().find(|directive| directive.name == name) {
+    if !already_allows(&directive.sources) {
+      directive.sources.push(source.to_string());
+    }
+    return;
+  }
+
+  let Some(default) = directives.iter().find(|directive| directive.name == "default-src") else {
+    return;
+  };
+
+  if already_allows(&default.sources) {
+    return;
+  }
+
+  let mut sources = default.sources.clone();
+  sources.retain(|source| source != "'none'");
+  sources.push(source.to_string());
+  directives.push(Directive {
+    name: name.to_string(),
+    sources,
+  });
+}
+
+/// Whether inline scripts run, which they don't if there's a nonce or hash
+/// next to `'unsafe-inline'`.
+fn allows_inline_scripts(sources: &[String]) -> bool {
+  let has = |prefix: &str| sources.iter().any(|source| source.to_ascii_lowercase().starts_with(prefix));
+  has("'unsafe-inline'") && !has("'nonce-") && !has("'sha") && !has("'strict-dynamic'")
+}
+
+fn allows_own_origin(sources: &[String]) -> bool {
+  sources.iter().any(|source| source.eq_ignore_ascii_case("'self'") || source == "*")
+}
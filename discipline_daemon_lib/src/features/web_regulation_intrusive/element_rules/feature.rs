@@ -0,0 +1,173 @@
+use std::sync::{Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+use crate::database::web_regulation_intrusive_element_rule_sheet as sheet_db;
+use crate::operating_system_integration::UserId;
+use crate::{Daemon, Database, GenericError, Uuid};
+use super::super::traffic::*;
+use super::language::{self, ParseError, Rule};
+use super::rewriter::HtmlRewriter;
+
+pub const MAXIMUM_SHEETS_PER_USER: usize = 100;
+pub const MAXIMUM_SOURCE_LENGTH: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SourceError {
+  TooLong { maximum_length: usize },
+  NoRules,
+  Invalid { errors: Vec<ParseError> },
+}
+
+/// Element rules as the user wrote them, along with what they parse to.
+#[derive(Debug, Clone)]
+pub struct Source {
+  text: String,
+  rules: Vec<Rule>,
+}
+
+impl Source {
+  pub fn parse(text: String) -> Result<Self, SourceError> {
+    if text.len() > MAXIMUM_SOURCE_LENGTH {
+      return Err(SourceError::TooLong { maximum_length: MAXIMUM_SOURCE_LENGTH });
+    }
+
+    let rules = language::parse(&text).map_err(|errors| SourceError::Invalid { errors })?;
+    if rules.is_empty() {
+      return Err(SourceError::NoRules);
+    }
+
+    Ok(Self { text, rules })
+  }
+
+  pub fn text(&self) -> &String {
+    &self.text
+  }
+
+  pub fn rules(&self) -> &Vec<Rule> {
+    &self.rules
+  }
+}
+
+/// A user's element rules, kept together as they were written.
+#[derive(Debug, Clone)]
+pub struct RuleSheet {
+  id: Uuid,
+  user_id: UserId,
+  source: Source,
+}
+
+impl RuleSheet {
+  pub fn from_fields(id: Uuid, user_id: UserId, source: Source) -> Self {
+    Self {
+      id,
+      user_id,
+      source,
+    }
+  }
+
+  pub fn id(&self) -> &Uuid {
+    &self.id
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn source(&self) -> &Source {
+    &self.source
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleSheetCreator {
+  pub id: Option<Uuid>,
+  pub source: String,
+}
+
+impl RuleSheetCreator {
+  pub fn create(self, user_id: UserId) -> Result<RuleSheet, SourceError> {
+    Ok(RuleSheet::from_fields(
+      self.id.unwrap_or_else(Uuid::new_v4),
+      user_id,
+      Source::parse(self.source)?,
+    ))
+  }
+}
+
+// SECTION: Traffic handler.
+/// Applies users' element rules to the web pages they load.
+pub struct ElementRegulation {
+  sheets: Mutex<Vec<RuleSheet>>,
+}
+
+impl ElementRegulation {
+  pub fn open(database: &Database) -> Result<Self, GenericError> {
+    let sheets = sheet_db::retrieve_all_sheets(database)
+      .map_err(|error| error.change_context("opening the element regulation"))?;
+
+    Ok(Self {
+      sheets: Mutex::new(sheets),
+    })
+  }
+
+  pub fn sheets(&self) -> MutexGuard<'_, Vec<RuleSheet>> {
+    self.sheets.lock().unwrap()
+  }
+
+  fn has_rules_for(&self, exchange: &Exchange) -> bool {
+    self
+      .sheets()
+      .iter()
+      .filter(|sheet| exchange.user_id == Some(sheet.user_id))
+      .flat_map(|sheet| sheet.source.rules())
+      .any(|rule| rule.applies_to_host(&exchange.host))
+  }
+
+  fn rules_for(&self, exchange: &Exchange) -> Vec<Rule> {
+    self
+      .sheets()
+      .iter()
+      .filter(|sheet| exchange.user_id == Some(sheet.user_id))
+      .flat_map(|sheet| sheet.source.rules())
+      .filter(|rule| rule.applies_to_host(&exchange.host))
+      .cloned()
+      .collect()
+  }
+}
+
+fn is_html(response: &ResponseHead) -> bool {
+  let is_html_type = response
+    .content_type()
+    .is_some_and(|content_type| content_type == "text/html" || content_type == "application/xhtml+xml");
+
+  // The rewriter only understands encodings that are supersets of ascii.
+  let is_utf_16 = response
+    .headers
+    .get("Content-Type")
+    .is_some_and(|value| value.to_ascii_lowercase().contains("utf-16"));
+
+  is_html_type && !is_utf_16 && response.status_code != 204 && response.status_code != 304
+}
+
+impl TrafficHandler for ElementRegulation {
+  fn may_inspect_response_body(&self, _daemon: &Daemon, exchange: &Exchange) -> bool {
+    self.has_rules_for(exchange)
+  }
+
+  fn on_response(
+    &self,
+    _daemon: &Daemon,
+    exchange: &Exchange,
+    response: &mut ResponseHead,
+  ) -> ResponseVerdict {
+    if !is_html(response) {
+      return ResponseVerdict::Forward;
+    }
+
+    let rules = self.rules_for(exchange);
+    if rules.is_empty() {
+      return ResponseVerdict::Forward;
+    }
+
+    ResponseVerdict::RewriteBody(Box::new(HtmlRewriter::new(rules)))
+  }
+}
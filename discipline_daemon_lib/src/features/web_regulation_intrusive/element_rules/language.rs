@@ -0,0 +1,669 @@
+use std::fmt::{self, Display, Formatter};
+use serde::{Deserialize, Serialize};
+use super::super::url_pattern::HostPattern;
+use super::selector::*;
+
+/// Selectors with more compounds than this are rejected, since matching them
+/// gets slow on deep pages.
+pub const MAXIMUM_SELECTOR_COMPOUNDS: usize = 16;
+pub const MAXIMUM_REPLACEMENT_LENGTH: usize = 1000;
+pub const DEFAULT_BLUR_RADIUS: u8 = 16;
+pub const MAXIMUM_BLUR_RADIUS: u8 = 100;
+/// Parsing stops after this many errors, which are likely caused by the
+/// first ones anyway.
+const MAXIMUM_ERRORS: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+  /// Remove the element and its content from the page.
+  Remove,
+  /// Keep the element, but don't display it.
+  Hide,
+  /// Blur the element and its content by `radius` pixels.
+  Blur { radius: u8 },
+  /// Mute the element if it's a video or audio.
+  Mute,
+  /// Replace the element and its content with a text.
+  Replace { text: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+  /// The hosts of the `@host` block the rule is in. Empty if it's in none,
+  /// in which case it applies to every host.
+  pub hosts: Vec<HostPattern>,
+  pub selectors: Vec<ComplexSelector>,
+  pub action: Action,
+}
+
+impl Rule {
+  /// `host` must be lowercase.
+  pub fn applies_to_host(&self, host: &str) -> bool {
+    self.hosts.is_empty() || self.hosts.iter().any(|pattern| pattern.matches(host))
+  }
+
+  /// Whether this matches `element`, whose ancestors are `ancestors`.
+  pub fn matches(&self, element: &Element, ancestors: &[Element]) -> bool {
+    self.selectors.iter().any(|selector| selector.matches(element, ancestors))
+  }
+}
+
+/// What's wrong with some rules and where. Lines and columns start at one
+/// and columns count characters.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParseError {
+  pub line: usize,
+  pub column: usize,
+  pub message: String,
+}
+
+impl Display for ParseError {
+  fn fmt(&self, into: &mut Formatter<'_>) -> fmt::Result {
+    write!(into, "line {}, column {}: {}", self.line, self.column, self.message)
+  }
+}
+
+/// Parses `source`, returning every rule in it, or every error found.
+///
+/// Rules look like css:
+///
+/// ```text
+/// // Applies to every website.
+/// img[alt*="casino" i], .sponsored { remove }
+///
+/// @host *.youtube.com, youtube.com {
+///   #comments { replace "Comments are hidden" }
+///   ytd-thumbnail img { blur(24px) }
+///   video { mute }
+/// }
+/// ```
+///
+/// Selectors support type, `*`, `#id`, `.class`, attribute selectors with
+/// every css operator and the `i` flag, `:not(...)`, and the descendant and
+/// child combinators. Each rule has exactly one action: `remove`, `hide`,
+/// `blur` with an optional radius, `mute`, or `replace` with a quoted text.
+pub fn parse(source: &str) -> Result<Vec<Rule>, Vec<ParseError>> {
+  let mut parser = Parser {
+    chars: source.chars().collect(),
+    index: 0,
+    errors: Vec::new(),
+  };
+
+  let rules = parser.parse_rules();
+  if parser.errors.is_empty() {
+    Ok(rules)
+  } else {
+    Err(parser.errors)
+  }
+}
+
+// SECTION: Parser.
+fn is_ident_start(char: char) -> bool {
+  char.is_ascii_alphabetic() || char == '_' || char == '-' || !char.is_ascii()
+}
+
+fn is_ident_char(char: char) -> bool {
+  char.is_ascii_alphanumeric() || char == '_' || char == '-' || !char.is_ascii()
+}
+
+struct Parser {
+  chars: Vec<char>,
+  index: usize,
+  errors: Vec<ParseError>,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.index).copied()
+  }
+
+  fn peek_at(&self, offset: usize) -> Option<char> {
+    self.chars.get(self.index + offset).copied()
+  }
+
+  fn is_at_end(&self) -> bool {
+    self.index >= self.chars.len()
+  }
+
+  fn consume(&mut self, char: char) -> bool {
+    if self.peek() == Some(char) {
+      self.index += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  /// Skips whitespace and comments. Returns whether there were any.
+  fn skip_trivia(&mut self) -> bool {
+    let start = self.index;
+    loop {
+      match (self.peek(), self.peek_at(1)) {
+        (Some(char), _) if char.is_whitespace() => {
+          self.index += 1;
+        }
+        (Some('/'), Some('/')) => {
+          while self.peek().is_some_and(|char| char != '\n') {
+            self.index += 1;
+          }
+        }
+        (Some('/'), Some('*')) => {
+          self.index += 2;
+          while !self.is_at_end() && (self.peek(), self.peek_at(1)) != (Some('*'), Some('/')) {
+            self.index += 1;
+          }
+          self.index = (self.index + 2).min(self.chars.len());
+        }
+        _ => {
+          return self.index != start;
+        }
+      }
+    }
+  }
+
+  fn error_at(&self, index: usize, message: impl Into<String>) -> ParseError {
+    let before = &self.chars[..index.min(self.chars.len())];
+    let line = before.iter().filter(|char| **char == '\n').count() + 1;
+    let column = match before.iter().rposition(|char| *char == '\n') {
+      Some(newline) => before.len() - newline,
+      None => before.len() + 1,
+    };
+
+    ParseError {
+      line,
+      column,
+      message: message.into(),
+    }
+  }
+
+  fn error(&self, message: impl Into<String>) -> ParseError {
+    self.error_at(self.index, message)
+  }
+
+  /// Describes what's next, for error messages.
+  fn describe_next(&self) -> String {
+    match self.peek() {
+      None => {
+        "the end of the rules".into()
+      }
+      Some('\n') => {
+        "the end of the line".into()
+      }
+      Some(char) if is_ident_char(char) => {
+        let word: String = self.chars[self.index..]
+          .iter()
+          .take_while(|char| is_ident_char(**char))
+          .collect();
+        format!("`{word}`")
+      }
+      Some(char) => {
+        format!("`{char}`")
+      }
+    }
+  }
+
+  fn expected(&self, what: &str) -> ParseError {
+    self.error(format!("expected {what}, but found {}", self.describe_next()))
+  }
+
+  fn expect(&mut self, char: char) -> Result<(), ParseError> {
+    if self.consume(char) {
+      Ok(())
+    } else {
+      Err(self.expected(&format!("`{char}`")))
+    }
+  }
+
+  /// Skips past the end of the rule or block an error was found in, so
+  /// parsing can go on and find more errors.
+  fn recover(&mut self) {
+    let mut depth = 0;
+    while let Some(char) = self.peek() {
+      self.index += 1;
+      match char {
+        '"' | '\'' => {
+          while self.peek().is_some_and(|other| other != char && other != '\n') {
+            self.index += 1;
+          }
+          self.consume(char);
+        }
+        '{' => {
+          depth += 1;
+        }
+        '}' if depth <= 1 => {
+          return;
+        }
+        '}' => {
+          depth -= 1;
+        }
+        _ => {}
+      }
+    }
+  }
+
+  fn push_error(&mut self, error: ParseError) {
+    if self.errors.len() < MAXIMUM_ERRORS {
+      self.errors.push(error);
+    }
+  }
+
+  fn parse_rules(&mut self) -> Vec<Rule> {
+    let mut rules = Vec::new();
+
+    loop {
+      self.skip_trivia();
+      if self.is_at_end() || self.errors.len() >= MAXIMUM_ERRORS {
+        return rules;
+      }
+
+      let result = if self.peek() == Some('@') {
+        self.parse_host_block(&mut rules)
+      } else {
+        self.parse_rule(&[]).map(|rule| rules.push(rule))
+      };
+
+      if let Err(error) = result {
+        self.push_error(error);
+        self.recover();
+      }
+    }
+  }
+
+  fn parse_ident(&mut self) -> Option<String> {
+    let start = self.index;
+    let first = self.peek()?;
+    if !is_ident_start(first) {
+      return None;
+    }
+    // Like in css, "-" followed by a digit starts a number, not a name.
+    if first == '-' && self.peek_at(1).is_some_and(|char| char.is_ascii_digit()) {
+      return None;
+    }
+
+    while self.peek().is_some_and(is_ident_char) {
+      self.index += 1;
+    }
+
+    Some(self.chars[start..self.index].iter().collect())
+  }
+
+  fn parse_string(&mut self) -> Result<Option<String>, ParseError> {
+    let Some(quote) = self.peek().filter(|char| *char == '"' || *char == '\'') else {
+      return Ok(None);
+    };
+
+    let start = self.index;
+    self.index += 1;
+
+    let mut string = String::new();
+    loop {
+      match self.peek() {
+        None | Some('\n') => {
+          return Err(self.error_at(start, "this text has no closing quote"));
+        }
+        Some('\\') => {
+          self.index += 1;
+          match self.peek() {
+            None | Some('\n') => {
+              return Err(self.error_at(start, "this text has no closing quote"));
+            }
+            Some(char) => {
+              string.push(char);
+              self.index += 1;
+            }
+          }
+        }
+        Some(char) if char == quote => {
+          self.index += 1;
+          return Ok(Some(string));
+        }
+        Some(char) => {
+          string.push(char);
+          self.index += 1;
+        }
+      }
+    }
+  }
+
+  // SECTION: Host blocks.
+  fn parse_host_block(&mut self, rules: &mut Vec<Rule>) -> Result<(), ParseError> {
+    let start = self.index;
+    self.expect('@')?;
+    match self.parse_ident() {
+      Some(name) if name.eq_ignore_ascii_case("host") => {}
+      Some(name) => {
+        return Err(self.error_at(start, format!("unknown block `@{name}`; the only block is `@host`")));
+      }
+      None => {
+        return Err(self.expected("`host` after `@`"));
+      }
+    }
+
+    let mut hosts = Vec::new();
+    loop {
+      self.skip_trivia();
+      let host_start = self.index;
+      while self.peek().is_some_and(|char| !char.is_whitespace() && char != ',' && char != '{') {
+        self.index += 1;
+      }
+
+      if self.index == host_start {
+        return Err(self.expected("a host, like `example.com` or `*.example.com`"));
+      }
+
+      let host: String = self.chars[host_start..self.index].iter().collect();
+      let pattern = HostPattern::parse(&host).map_err(|_| {
+        self.error_at(host_start, format!("`{host}` isn't a host; expected a host like `example.com` or `*.example.com`"))
+      })?;
+      hosts.push(pattern);
+
+      self.skip_trivia();
+      if !self.consume(',') {
+        break;
+      }
+    }
+
+    self.expect('{')?;
+
+    loop {
+      self.skip_trivia();
+      if self.consume('}') {
+        return Ok(());
+      }
+      if self.is_at_end() {
+        return Err(self.error_at(start, "this `@host` block has no closing `}`"));
+      }
+      if self.errors.len() >= MAXIMUM_ERRORS {
+        return Ok(());
+      }
+      if self.peek() == Some('@') {
+        return Err(self.error("`@host` blocks can't be nested"));
+      }
+
+      match self.parse_rule(&hosts) {
+        Ok(rule) => {
+          rules.push(rule);
+        }
+        Err(error) => {
+          self.push_error(error);
+          self.recover();
+        }
+      }
+    }
+  }
+
+  // SECTION: Rules.
+  fn parse_rule(&mut self, hosts: &[HostPattern]) -> Result<Rule, ParseError> {
+    let mut selectors = Vec::new();
+    loop {
+      selectors.push(self.parse_complex_selector()?);
+      self.skip_trivia();
+      if !self.consume(',') {
+        break;
+      }
+      self.skip_trivia();
+    }
+
+    self.expect('{')?;
+    self.skip_trivia();
+    let action = self.parse_action()?;
+    self.skip_trivia();
+    self.consume(';');
+    self.skip_trivia();
+
+    if !self.consume('}') {
+      if let Some(name) = self.parse_ident() {
+        return Err(self.error(format!(
+          "a rule has one action; write another rule with the same selectors for `{name}`",
+        )));
+      }
+      return Err(self.expected("`}` after the action"));
+    }
+
+    Ok(Rule {
+      hosts: hosts.to_vec(),
+      selectors,
+      action,
+    })
+  }
+
+  fn parse_action(&mut self) -> Result<Action, ParseError> {
+    let start = self.index;
+    let Some(name) = self.parse_ident() else {
+      return Err(self.expected("an action: `remove`, `hide`, `blur`, `mute` or `replace`"));
+    };
+
+    match name.as_str() {
+      "remove" => {
+        Ok(Action::Remove)
+      }
+      "hide" => {
+        Ok(Action::Hide)
+      }
+      "mute" => {
+        Ok(Action::Mute)
+      }
+      "blur" => {
+        if !self.consume('(') {
+          return Ok(Action::Blur { radius: DEFAULT_BLUR_RADIUS });
+        }
+
+        self.skip_trivia();
+        let number_start = self.index;
+        while self.peek().is_some_and(|char| char.is_ascii_digit()) {
+          self.index += 1;
+        }
+        if self.index == number_start {
+          return Err(self.expected("a blur radius in pixels, like `blur(16px)`"));
+        }
+
+        let number: String = self.chars[number_start..self.index].iter().collect();
+        let radius = number
+          .parse::<u8>()
+          .ok()
+          .filter(|radius| (1..=MAXIMUM_BLUR_RADIUS).contains(radius))
+          .ok_or_else(|| {
+            self.error_at(number_start, format!("the blur radius must be from 1 to {MAXIMUM_BLUR_RADIUS} pixels"))
+          })?;
+
+        if self.peek() == Some('p') && self.peek_at(1) == Some('x') {
+          self.index += 2;
+        }
+        self.skip_trivia();
+        self.expect(')')?;
+        Ok(Action::Blur { radius })
+      }
+      "replace" => {
+        self.skip_trivia();
+        let text_start = self.index;
+        let Some(text) = self.parse_string()? else {
+          return Err(self.expected("the text to replace with, in quotes, like `replace \"Hidden\"`"));
+        };
+
+        if text.chars().count() > MAXIMUM_REPLACEMENT_LENGTH {
+          return Err(self.error_at(
+            text_start,
+            format!("the text to replace with can't be longer than {MAXIMUM_REPLACEMENT_LENGTH} characters"),
+          ));
+        }
+
+        Ok(Action::Replace { text })
+      }
+      _ => {
+        Err(self.error_at(
+          start,
+          format!("unknown action `{name}`; expected `remove`, `hide`, `blur`, `mute` or `replace`"),
+        ))
+      }
+    }
+  }
+
+  // SECTION: Selectors.
+  fn parse_complex_selector(&mut self) -> Result<ComplexSelector, ParseError> {
+    let start = self.index;
+    let mut selector = ComplexSelector::new(self.parse_compound_selector()?);
+
+    loop {
+      let had_whitespace = self.skip_trivia();
+      let combinator = match self.peek() {
+        Some('>') => {
+          self.index += 1;
+          self.skip_trivia();
+          Combinator::Child
+        }
+        Some('+') | Some('~') => {
+          return Err(self.error("sibling combinators, `+` and `~`, aren't supported"));
+        }
+        None | Some(',') | Some('{') => {
+          return Ok(selector);
+        }
+        Some(_) if had_whitespace => {
+          Combinator::Descendant
+        }
+        Some(_) => {
+          return Err(self.expected("a selector, `,` or `{`"));
+        }
+      };
+
+      selector.push(combinator, self.parse_compound_selector()?);
+      if selector.compound_count() > MAXIMUM_SELECTOR_COMPOUNDS {
+        return Err(self.error_at(
+          start,
+          format!("this selector is too long; selectors can have up to {MAXIMUM_SELECTOR_COMPOUNDS} parts"),
+        ));
+      }
+    }
+  }
+
+  fn parse_compound_selector(&mut self) -> Result<CompoundSelector, ParseError> {
+    let mut selectors = Vec::new();
+
+    if self.consume('*') {
+      selectors.push(SimpleSelector::Universal);
+    } else if let Some(name) = self.parse_ident() {
+      selectors.push(SimpleSelector::Type(name.to_ascii_lowercase()));
+    }
+
+    loop {
+      match self.peek() {
+        Some('#') => {
+          self.index += 1;
+          let id = self.parse_ident().ok_or_else(|| self.expected("an id after `#`"))?;
+          selectors.push(SimpleSelector::Id(id));
+        }
+        Some('.') => {
+          self.index += 1;
+          let class = self.parse_ident().ok_or_else(|| self.expected("a class name after `.`"))?;
+          selectors.push(SimpleSelector::Class(class));
+        }
+        Some('[') => {
+          selectors.push(SimpleSelector::Attribute(self.parse_attribute_selector()?));
+        }
+        Some(':') => {
+          selectors.push(self.parse_pseudo_class()?);
+        }
+        _ => {
+          break;
+        }
+      }
+    }
+
+    if selectors.is_empty() {
+      return Err(self.expected("a selector, like `img`, `.class` or `#id`"));
+    }
+
+    Ok(CompoundSelector(selectors))
+  }
+
+  fn parse_attribute_selector(&mut self) -> Result<AttributeSelector, ParseError> {
+    self.expect('[')?;
+    self.skip_trivia();
+    let name = self
+      .parse_ident()
+      .ok_or_else(|| self.expected("an attribute name after `[`"))?
+      .to_ascii_lowercase();
+    self.skip_trivia();
+
+    let operator = match (self.peek(), self.peek_at(1)) {
+      (Some('='), _) => Some((AttributeOperator::Equals, 1)),
+      (Some('~'), Some('=')) => Some((AttributeOperator::Includes, 2)),
+      (Some('|'), Some('=')) => Some((AttributeOperator::DashMatch, 2)),
+      (Some('^'), Some('=')) => Some((AttributeOperator::Prefix, 2)),
+      (Some('$'), Some('=')) => Some((AttributeOperator::Suffix, 2)),
+      (Some('*'), Some('=')) => Some((AttributeOperator::Substring, 2)),
+      (Some(']'), _) => None,
+      _ => {
+        return Err(self.expected("`]` or an operator like `=`, `^=` or `*=`"));
+      }
+    };
+
+    let Some((operator, length)) = operator else {
+      self.index += 1;
+      return Ok(AttributeSelector {
+        name,
+        operator: None,
+        is_case_insensitive: false,
+      });
+    };
+
+    self.index += length;
+    self.skip_trivia();
+    let value = match self.parse_string()? {
+      Some(value) => value,
+      None => self
+        .parse_ident()
+        .ok_or_else(|| self.expected("a value, either a word or a text in quotes"))?,
+    };
+
+    self.skip_trivia();
+    let is_case_insensitive = match self.peek() {
+      Some('i') | Some('I') => {
+        self.index += 1;
+        true
+      }
+      Some('s') | Some('S') => {
+        self.index += 1;
+        false
+      }
+      _ => {
+        false
+      }
+    };
+
+    self.skip_trivia();
+    self.expect(']')?;
+
+    Ok(AttributeSelector {
+      name,
+      operator: Some((operator, value)),
+      is_case_insensitive,
+    })
+  }
+
+  fn parse_pseudo_class(&mut self) -> Result<SimpleSelector, ParseError> {
+    let start = self.index;
+    self.expect(':')?;
+    if self.peek() == Some(':') {
+      return Err(self.error_at(start, "pseudo-elements, like `::before`, aren't supported"));
+    }
+
+    let name = self
+      .parse_ident()
+      .ok_or_else(|| self.expected("a pseudo-class name after `:`"))?;
+
+    if !name.eq_ignore_ascii_case("not") {
+      return Err(self.error_at(start, format!("the pseudo-class `:{name}` isn't supported; only `:not(...)` is")));
+    }
+
+    self.expect('(')?;
+    self.skip_trivia();
+    let compound = self.parse_compound_selector()?;
+    self.skip_trivia();
+    if self.peek() != Some(')') {
+      return Err(self.expected("`)`; `:not(...)` takes a single selector without combinators"));
+    }
+    self.index += 1;
+
+    Ok(SimpleSelector::Not(compound))
+  }
+}
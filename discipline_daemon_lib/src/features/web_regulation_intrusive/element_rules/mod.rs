@@ -0,0 +1,22 @@
+pub mod selector;
+pub use selector::{ComplexSelector, Element};
+
+pub mod language;
+pub use language::{parse, Action, ParseError, Rule};
+
+pub mod rewriter;
+pub use rewriter::HtmlRewriter;
+
+pub mod feature;
+pub use feature::{
+  ElementRegulation,
+  RuleSheet,
+  RuleSheetCreator,
+  Source,
+  SourceError,
+  MAXIMUM_SHEETS_PER_USER,
+  MAXIMUM_SOURCE_LENGTH,
+};
+
+#[cfg(test)]
+mod tests;
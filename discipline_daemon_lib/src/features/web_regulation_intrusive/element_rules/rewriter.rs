@@ -0,0 +1,577 @@
+use std::fmt::Write;
+use super::super::block_page::escape_html;
+use super::super::traffic::BodyRewriter;
+use super::language::{Action, Rule};
+use super::selector::Element;
+
+/// Start tags longer than this are treated as text, so a page can't make us
+/// hold back all of itself.
+const MAXIMUM_TAG_LENGTH: usize = 64 * 1024;
+
+/// Elements nested deeper than this aren't tracked. Browsers give up on
+/// nesting long before this.
+const MAXIMUM_DEPTH: usize = 512;
+
+const VOID_ELEMENTS: &[&str] = &[
+  "area", "base", "br", "col", "embed", "hr", "img", "input",
+  "link", "meta", "param", "source", "track", "wbr",
+];
+
+/// Elements whose content isn't markup and only ends at their end tag.
+const RAW_TEXT_ELEMENTS: &[&str] = &[
+  "script", "style", "textarea", "title", "xmp", "iframe", "noembed", "noframes", "noscript",
+];
+
+/// Elements whose start tag ends an open `p`.
+const CLOSES_PARAGRAPH: &[&str] = &[
+  "address", "article", "aside", "blockquote", "details", "dialog", "div", "dl",
+  "fieldset", "figcaption", "figure", "footer", "form", "h1", "h2", "h3", "h4",
+  "h5", "h6", "header", "hgroup", "hr", "main", "menu", "nav", "ol", "p", "pre",
+  "section", "table", "ul",
+];
+
+/// The elements a start tag named `name` ends if they're the current element.
+fn implicitly_closed_by(name: &str) -> &'static [&'static str] {
+  match name {
+    "li" => &["li", "p"],
+    "dt" | "dd" => &["dt", "dd", "p"],
+    "option" => &["option"],
+    "optgroup" => &["optgroup", "option"],
+    "tr" => &["tr", "td", "th"],
+    "td" | "th" => &["td", "th"],
+    "thead" | "tbody" | "tfoot" => &["thead", "tbody", "tfoot", "tr", "td", "th"],
+    name if CLOSES_PARAGRAPH.contains(&name) => &["p"],
+    _ => &[],
+  }
+}
+
+// SECTION: Tokens.
+enum Token {
+  StartTag,
+  EndTag,
+  /// Comments, doctypes and everything else passed on as is.
+  Other,
+  /// A `<` that doesn't start a tag.
+  Text,
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+  haystack
+    .windows(needle.len())
+    .position(|window| window == needle)
+}
+
+/// The length of the token at the start of `input`, which starts with `<`,
+/// or `None` if it isn't complete yet.
+fn next_token(input: &[u8]) -> Option<(Token, usize)> {
+  let until = |needle: &[u8], from: usize| {
+    input
+      .get(from..)
+      .and_then(|rest| find(rest, needle))
+      .map(|index| from + index + needle.len())
+  };
+
+  match input.get(1)? {
+    b'!' => {
+      if input.starts_with(b"<!--") {
+        until(b"-->", 4).map(|length| (Token::Other, length))
+      } else if b"<!--".starts_with(&input[..input.len().min(4)]) {
+        None
+      } else {
+        until(b">", 2).map(|length| (Token::Other, length))
+      }
+    }
+    b'?' => {
+      until(b">", 2).map(|length| (Token::Other, length))
+    }
+    b'/' => {
+      match input.get(2)? {
+        byte if byte.is_ascii_alphabetic() => until(b">", 3).map(|length| (Token::EndTag, length)),
+        _ => until(b">", 2).map(|length| (Token::Other, length)),
+      }
+    }
+    byte if byte.is_ascii_alphabetic() => {
+      start_tag_length(input).map(|length| (Token::StartTag, length))
+    }
+    _ => {
+      Some((Token::Text, 1))
+    }
+  }
+}
+
+/// The length of the start tag at the start of `input`, skipping `>` in
+/// quoted attribute values.
+fn start_tag_length(input: &[u8]) -> Option<usize> {
+  let mut quote = None;
+  let mut is_value_next = false;
+
+  for (index, byte) in input.iter().enumerate().skip(1) {
+    match quote {
+      Some(quote_byte) => {
+        if *byte == quote_byte {
+          quote = None;
+        }
+      }
+      None => {
+        match byte {
+          b'>' => {
+            return Some(index + 1);
+          }
+          b'=' => {
+            is_value_next = true;
+          }
+          b'"' | b'\'' if is_value_next => {
+            quote = Some(*byte);
+            is_value_next = false;
+          }
+          byte if byte.is_ascii_whitespace() => {}
+          _ => {
+            is_value_next = false;
+          }
+        }
+      }
+    }
+  }
+
+  None
+}
+
+/// The index of the end tag of the raw text element `name` in `input`.
+fn find_raw_text_end(input: &[u8], name: &str) -> Option<usize> {
+  let length = name.len() + 2;
+  (0..input.len().saturating_sub(length)).find(|index| {
+    let candidate = &input[*index..*index + length];
+    candidate.starts_with(b"</")
+      && candidate[2..].eq_ignore_ascii_case(name.as_bytes())
+      && matches!(input[index + length], b'>' | b'/' | b' ' | b'\t' | b'\n' | b'\r' | b'\x0c')
+  })
+}
+
+// SECTION: Tags.
+/// Decodes the character references likely to show up in attribute values
+/// selectors look at. Others are left as they are.
+fn decode_character_references(value: &str) -> String {
+  if !value.contains('&') {
+    return value.to_string();
+  }
+
+  let mut decoded = String::with_capacity(value.len());
+  let mut rest = value;
+  while let Some(index) = rest.find('&') {
+    decoded.push_str(&rest[..index]);
+    rest = &rest[index..];
+
+    match decode_character_reference(rest) {
+      Some((char, length)) => {
+        decoded.push(char);
+        rest = &rest[length..];
+      }
+      None => {
+        decoded.push('&');
+        rest = &rest[1..];
+      }
+    }
+  }
+
+  decoded.push_str(rest);
+  decoded
+}
+
+/// Decodes the character reference `input` starts with, like `&amp;` or
+/// `&#x27;`, into its char and length. Only numeric references and the most
+/// common named ones are known.
+pub(crate) fn decode_character_reference(input: &str) -> Option<(char, usize)> {
+  let reference = input
+    .strip_prefix('&')?
+    .find(';')
+    .filter(|end| *end <= 32)
+    .map(|end| &input[1..end + 1])?;
+
+  let char = match reference {
+    "amp" => '&',
+    "lt" => '<',
+    "gt" => '>',
+    "quot" => '"',
+    "apos" => '\'',
+    "nbsp" => '\u{a0}',
+    _ => {
+      let number = reference.strip_prefix('#')?;
+      let code = match number.strip_prefix(['x', 'X']) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => number.parse().ok()?,
+      };
+      char::from_u32(code)?
+    }
+  };
+
+  Some((char, reference.len() + 2))
+}
+
+/// Parses a complete start tag into an element and whether it ends in `/>`.
+fn parse_start_tag(tag: &[u8]) -> (Element, bool) {
+  let tag = String::from_utf8_lossy(tag);
+  let inner = &tag[1..tag.len() - 1];
+  let is_self_closing = inner.ends_with('/');
+
+  let name_end = inner
+    .find(|char: char| char.is_ascii_whitespace() || char == '/')
+    .unwrap_or(inner.len());
+  let mut element = Element::new(inner[..name_end].to_ascii_lowercase());
+
+  let mut rest = &inner[name_end..];
+  loop {
+    rest = rest.trim_start_matches(|char: char| char.is_ascii_whitespace() || char == '/');
+    if rest.is_empty() {
+      break;
+    }
+
+    // The first character is part of the name even if it's "=".
+    let first_length = rest.chars().next().map_or(0, char::len_utf8);
+    let name_end = rest[first_length..]
+      .find(|char: char| char.is_ascii_whitespace() || char == '/' || char == '=')
+      .map_or(rest.len(), |index| index + first_length);
+    let name = rest[..name_end].to_ascii_lowercase();
+    rest = rest[name_end..].trim_start_matches(|char: char| char.is_ascii_whitespace());
+
+    let value = match rest.strip_prefix('=') {
+      Some(after) => {
+        let after = after.trim_start_matches(|char: char| char.is_ascii_whitespace());
+        match after.chars().next() {
+          Some(quote @ ('"' | '\'')) => {
+            let end = after[1..].find(quote).map_or(after.len(), |index| index + 1);
+            rest = after.get(end + 1..).unwrap_or_default();
+            &after[1..end]
+          }
+          _ => {
+            let end = after.find(|char: char| char.is_ascii_whitespace()).unwrap_or(after.len());
+            rest = &after[end..];
+            &after[..end]
+          }
+        }
+      }
+      None => "",
+    };
+
+    // Like browsers, only the first of repeated attributes counts.
+    if element.attribute(&name).is_none() {
+      element.attributes.push((name, decode_character_references(value)));
+    }
+  }
+
+  (element, is_self_closing)
+}
+
+/// Writes `element` as a start tag with the changes `actions` make to it.
+fn write_changed_start_tag(element: &Element, actions: &[&Action], is_self_closing: bool, output: &mut Vec<u8>) {
+  let mut style = element.attribute("style").unwrap_or_default().trim().to_string();
+  let mut is_muted = element.attribute("muted").is_some();
+
+  for action in actions {
+    let declaration = match action {
+      Action::Hide => "display: none !important".to_string(),
+      Action::Blur { radius } => format!("filter: blur({radius}px) !important"),
+      Action::Mute => {
+        is_muted = true;
+        continue;
+      }
+      Action::Remove | Action::Replace { .. } => continue,
+    };
+
+    if !style.is_empty() && !style.ends_with(';') {
+      style.push(';');
+    }
+    style.push_str(&declaration);
+  }
+
+  let mut tag = format!("<{}", element.name);
+  for (name, value) in &element.attributes {
+    if name == "style" {
+      continue;
+    }
+    write!(tag, " {name}=\"{}\"", escape_html(value)).unwrap();
+  }
+  if !style.is_empty() {
+    write!(tag, " style=\"{}\"", escape_html(&style)).unwrap();
+  }
+  if is_muted && element.attribute("muted").is_none() {
+    tag.push_str(" muted");
+  }
+  tag.push_str(if is_self_closing { " />" } else { ">" });
+
+  output.extend_from_slice(tag.as_bytes());
+}
+
+/// A stylesheet applying the rules to elements that scripts add later, when
+/// the page is already past the rewriter.
+fn stylesheet(rules: &[Rule]) -> String {
+  let mut declarations = String::new();
+  for rule in rules {
+    let declaration = match &rule.action {
+      Action::Remove | Action::Hide | Action::Replace { .. } => "display: none !important".to_string(),
+      Action::Blur { radius } => format!("filter: blur({radius}px) !important"),
+      Action::Mute => continue,
+    };
+
+    for (index, selector) in rule.selectors.iter().enumerate() {
+      if index > 0 {
+        declarations.push_str(", ");
+      }
+      write!(declarations, "{selector}").unwrap();
+    }
+    writeln!(declarations, " {{ {declaration}; }}").unwrap();
+  }
+
+  if declarations.is_empty() {
+    String::new()
+  } else {
+    format!("<style data-discipline>\n{declarations}</style>")
+  }
+}
+
+// SECTION: Rewriter.
+/// Applies element rules to an html document as it streams through.
+///
+/// This isn't a full html parser. It tokenizes tags and tracks the open
+/// elements, including the end tags html lets pages leave out in lists,
+/// tables and paragraphs, which is enough to match selectors and to find
+/// where removed elements end. Everything it doesn't change is passed on
+/// byte for byte.
+pub struct HtmlRewriter {
+  rules: Vec<Rule>,
+  /// The end of the input that may be a token cut in two.
+  pending: Vec<u8>,
+  open_elements: Vec<Element>,
+  /// The name of the element whose content is raw text, like `script`, if
+  /// we're in one.
+  raw_text_element: Option<String>,
+  /// The index in `open_elements` of the element being removed, if any.
+  removed_element: Option<usize>,
+  /// Inserted into the head, or before the body if there's no head.
+  stylesheet: Option<String>,
+}
+
+impl HtmlRewriter {
+  pub fn new(rules: Vec<Rule>) -> Self {
+    let stylesheet = Some(stylesheet(&rules)).filter(|stylesheet| !stylesheet.is_empty());
+
+    Self {
+      rules,
+      pending: Vec::new(),
+      open_elements: Vec::new(),
+      raw_text_element: None,
+      removed_element: None,
+      stylesheet,
+    }
+  }
+
+  fn emit(&self, bytes: &[u8], output: &mut Vec<u8>) {
+    if self.removed_element.is_none() {
+      output.extend_from_slice(bytes);
+    }
+  }
+
+  fn emit_stylesheet(&mut self, output: &mut Vec<u8>) {
+    if let Some(stylesheet) = self.stylesheet.take() {
+      output.extend_from_slice(stylesheet.as_bytes());
+    }
+  }
+
+  /// Pops the current element, ending a removal if it was the removed one.
+  fn pop(&mut self) {
+    self.open_elements.pop();
+    if self.removed_element.is_some_and(|removed| self.open_elements.len() <= removed) {
+      self.removed_element = None;
+    }
+  }
+
+  fn push(&mut self, element: Element) {
+    if RAW_TEXT_ELEMENTS.contains(&element.name.as_str()) {
+      self.raw_text_element = Some(element.name.clone());
+    }
+    if self.open_elements.len() < MAXIMUM_DEPTH {
+      self.open_elements.push(element);
+    }
+  }
+
+  fn is_in_foreign_content(&self) -> bool {
+    self
+      .open_elements
+      .iter()
+      .any(|element| element.name == "svg" || element.name == "math")
+  }
+
+  fn on_start_tag(&mut self, tag: &[u8], output: &mut Vec<u8>) {
+    let (element, is_self_closing) = parse_start_tag(tag);
+
+    let closed = implicitly_closed_by(&element.name);
+    while self
+      .open_elements
+      .last()
+      .is_some_and(|open| closed.contains(&open.name.as_str()))
+    {
+      self.pop();
+    }
+
+    // "/>" only ends elements in svg and mathml. In html it's ignored.
+    let has_content = !VOID_ELEMENTS.contains(&element.name.as_str())
+      && !(is_self_closing && (self.is_in_foreign_content() || element.name == "svg" || element.name == "math"));
+
+    if self.removed_element.is_some() {
+      if has_content {
+        self.push(element);
+      }
+      return;
+    }
+
+    if element.name == "body" {
+      self.emit_stylesheet(output);
+    }
+
+    let actions: Vec<&Action> = self
+      .rules
+      .iter()
+      .filter(|rule| rule.matches(&element, &self.open_elements))
+      .map(|rule| &rule.action)
+      .collect();
+
+    let removal = actions
+      .iter()
+      .find(|action| matches!(action, Action::Remove | Action::Replace { .. }));
+
+    if let Some(removal) = removal {
+      if let Action::Replace { text } = removal {
+        output.extend_from_slice(escape_html(text).as_bytes());
+      }
+      if has_content {
+        self.removed_element = Some(self.open_elements.len());
+        self.push(element);
+      }
+      return;
+    }
+
+    if actions.is_empty() {
+      output.extend_from_slice(tag);
+    } else {
+      write_changed_start_tag(&element, &actions, is_self_closing, output);
+    }
+
+    let is_head = element.name == "head";
+    if has_content {
+      self.push(element);
+    }
+    if is_head {
+      self.emit_stylesheet(output);
+    }
+  }
+
+  fn on_end_tag(&mut self, tag: &[u8], output: &mut Vec<u8>) {
+    let name_end = tag[2..]
+      .iter()
+      .position(|byte| byte.is_ascii_whitespace() || *byte == b'/' || *byte == b'>')
+      .map_or(tag.len(), |index| index + 2);
+    let name = String::from_utf8_lossy(&tag[2..name_end]).to_ascii_lowercase();
+
+    let index = self.open_elements.iter().rposition(|element| element.name == name);
+
+    // The end tag of an element outside the removed one, which ends the
+    // removal by closing its parents, is still sent.
+    let is_sent = match (self.removed_element, index) {
+      (None, _) => true,
+      (Some(removed), Some(index)) => index < removed,
+      (Some(_), None) => false,
+    };
+
+    if let Some(index) = index {
+      while self.open_elements.len() > index {
+        self.pop();
+      }
+    }
+
+    if is_sent {
+      output.extend_from_slice(tag);
+    }
+  }
+
+  /// Rewrites as much of `input` as possible. Returns how much of it was
+  /// used, the rest is held back until more input comes, or until the end.
+  fn rewrite(&mut self, input: &[u8], output: &mut Vec<u8>, is_end: bool) -> usize {
+    let mut position = 0;
+
+    while position < input.len() {
+      let rest = &input[position..];
+
+      if let Some(name) = &self.raw_text_element {
+        match find_raw_text_end(rest, name) {
+          Some(index) => {
+            self.emit(&rest[..index], output);
+            position += index;
+            self.raw_text_element = None;
+          }
+          None => {
+            // Hold back what may be the start of the end tag.
+            let held_back = if is_end { 0 } else { (name.len() + 2).min(rest.len()) };
+            self.emit(&rest[..rest.len() - held_back], output);
+            position += rest.len() - held_back;
+            return position;
+          }
+        }
+        continue;
+      }
+
+      let Some(index) = rest.iter().position(|byte| *byte == b'<') else {
+        self.emit(rest, output);
+        return input.len();
+      };
+
+      if index > 0 {
+        self.emit(&rest[..index], output);
+        position += index;
+        continue;
+      }
+
+      match next_token(rest) {
+        Some((Token::StartTag, length)) => {
+          self.on_start_tag(&rest[..length], output);
+          position += length;
+        }
+        Some((Token::EndTag, length)) => {
+          self.on_end_tag(&rest[..length], output);
+          position += length;
+        }
+        Some((Token::Other, length)) => {
+          self.emit(&rest[..length], output);
+          position += length;
+        }
+        Some((Token::Text, _)) => {
+          self.emit(b"<", output);
+          position += 1;
+        }
+        None if is_end || rest.len() > MAXIMUM_TAG_LENGTH => {
+          self.emit(b"<", output);
+          position += 1;
+        }
+        None => {
+          return position;
+        }
+      }
+    }
+
+    position
+  }
+}
+
+impl BodyRewriter for HtmlRewriter {
+  fn write(&mut self, input: &[u8], output: &mut Vec<u8>) {
+    let mut pending = std::mem::take(&mut self.pending);
+    pending.extend_from_slice(input);
+
+    let used = self.rewrite(&pending, output, false);
+    pending.drain(..used);
+    self.pending = pending;
+  }
+
+  fn end(&mut self, output: &mut Vec<u8>) {
+    let pending = std::mem::take(&mut self.pending);
+    self.rewrite(&pending, output, true);
+  }
+}
@@ -0,0 +1,287 @@
+use std::fmt::{self, Display, Formatter, Write};
+
+/// An element as selectors see it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Element {
+  /// The lowercased tag name.
+  pub name: String,
+  /// Attributes in the order they were written, with lowercased names and
+  /// decoded values.
+  pub attributes: Vec<(String, String)>,
+}
+
+impl Element {
+  pub fn new(name: impl Into<String>) -> Self {
+    Self {
+      name: name.into(),
+      attributes: Vec::new(),
+    }
+  }
+
+  pub fn with_attribute(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+    self.attributes.push((name.into(), value.into()));
+    self
+  }
+
+  pub fn attribute(&self, name: &str) -> Option<&str> {
+    self
+      .attributes
+      .iter()
+      .find(|(attribute_name, _)| attribute_name == name)
+      .map(|(_, value)| value.as_str())
+  }
+
+  fn has_class(&self, class: &str) -> bool {
+    self
+      .attribute("class")
+      .is_some_and(|classes| classes.split_ascii_whitespace().any(|other| other == class))
+  }
+}
+
+// SECTION: Attribute selectors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeOperator {
+  /// `[name=value]`
+  Equals,
+  /// `[name~=value]`, one of the whitespace-separated words is `value`.
+  Includes,
+  /// `[name|=value]`, `value` or `value` followed by `-`.
+  DashMatch,
+  /// `[name^=value]`
+  Prefix,
+  /// `[name$=value]`
+  Suffix,
+  /// `[name*=value]`
+  Substring,
+}
+
+impl AttributeOperator {
+  fn as_str(&self) -> &'static str {
+    match self {
+      AttributeOperator::Equals => "=",
+      AttributeOperator::Includes => "~=",
+      AttributeOperator::DashMatch => "|=",
+      AttributeOperator::Prefix => "^=",
+      AttributeOperator::Suffix => "$=",
+      AttributeOperator::Substring => "*=",
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeSelector {
+  /// Lowercased.
+  pub name: String,
+  /// `None` for `[name]`, which only checks the attribute is there.
+  pub operator: Option<(AttributeOperator, String)>,
+  pub is_case_insensitive: bool,
+}
+
+impl AttributeSelector {
+  fn matches(&self, element: &Element) -> bool {
+    let Some(value) = element.attribute(&self.name) else {
+      return false;
+    };
+
+    let Some((operator, expected)) = &self.operator else {
+      return true;
+    };
+
+    let (value, expected) = if self.is_case_insensitive {
+      (value.to_lowercase(), expected.to_lowercase())
+    } else {
+      (value.to_string(), expected.clone())
+    };
+
+    match operator {
+      AttributeOperator::Equals => {
+        value == expected
+      }
+      AttributeOperator::Includes => {
+        value.split_ascii_whitespace().any(|word| word == expected)
+      }
+      AttributeOperator::DashMatch => {
+        value == expected
+          || value
+            .strip_prefix(&expected)
+            .is_some_and(|rest| rest.starts_with('-'))
+      }
+      // Empty values match nothing, as in css.
+      AttributeOperator::Prefix => {
+        !expected.is_empty() && value.starts_with(&expected)
+      }
+      AttributeOperator::Suffix => {
+        !expected.is_empty() && value.ends_with(&expected)
+      }
+      AttributeOperator::Substring => {
+        !expected.is_empty() && value.contains(&expected)
+      }
+    }
+  }
+}
+
+// SECTION: Selectors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimpleSelector {
+  /// `*`
+  Universal,
+  /// `img`, lowercased.
+  Type(String),
+  /// `#name`
+  Id(String),
+  /// `.name`
+  Class(String),
+  Attribute(AttributeSelector),
+  /// `:not(compound)`
+  Not(CompoundSelector),
+}
+
+impl SimpleSelector {
+  fn matches(&self, element: &Element) -> bool {
+    match self {
+      SimpleSelector::Universal => true,
+      SimpleSelector::Type(name) => element.name == *name,
+      SimpleSelector::Id(id) => element.attribute("id") == Some(id.as_str()),
+      SimpleSelector::Class(class) => element.has_class(class),
+      SimpleSelector::Attribute(attribute) => attribute.matches(element),
+      SimpleSelector::Not(compound) => !compound.matches(element),
+    }
+  }
+}
+
+/// Simple selectors written together, like `img.thumbnail[alt]`, which all
+/// have to match the same element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompoundSelector(pub Vec<SimpleSelector>);
+
+impl CompoundSelector {
+  pub fn matches(&self, element: &Element) -> bool {
+    self.0.iter().all(|selector| selector.matches(element))
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Combinator {
+  /// `a b`
+  Descendant,
+  /// `a > b`
+  Child,
+}
+
+/// Compound selectors joined by combinators, like `#related > div img`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComplexSelector {
+  /// From left to right. Never empty.
+  compounds: Vec<CompoundSelector>,
+  /// `combinators[i]` is between `compounds[i]` and `compounds[i + 1]`.
+  combinators: Vec<Combinator>,
+}
+
+impl ComplexSelector {
+  pub fn new(first: CompoundSelector) -> Self {
+    Self {
+      compounds: vec![first],
+      combinators: Vec::new(),
+    }
+  }
+
+  pub fn push(&mut self, combinator: Combinator, compound: CompoundSelector) {
+    self.combinators.push(combinator);
+    self.compounds.push(compound);
+  }
+
+  pub fn compound_count(&self) -> usize {
+    self.compounds.len()
+  }
+
+  /// Whether this matches `element`, whose ancestors are `ancestors`, from
+  /// the root element down to its parent.
+  pub fn matches(&self, element: &Element, ancestors: &[Element]) -> bool {
+    let last = self.compounds.len() - 1;
+    self.compounds[last].matches(element) && self.matches_ancestors(last, ancestors)
+  }
+
+  /// Whether the compounds before `index` match `ancestors`, given the
+  /// compound at `index` matched the element right below them.
+  fn matches_ancestors(&self, index: usize, ancestors: &[Element]) -> bool {
+    if index == 0 {
+      return true;
+    }
+
+    let compound = &self.compounds[index - 1];
+    match self.combinators[index - 1] {
+      Combinator::Child => {
+        ancestors.split_last().is_some_and(|(parent, rest)| {
+          compound.matches(parent) && self.matches_ancestors(index - 1, rest)
+        })
+      }
+      Combinator::Descendant => {
+        (0..ancestors.len()).rev().any(|ancestor| {
+          compound.matches(&ancestors[ancestor]) && self.matches_ancestors(index - 1, &ancestors[..ancestor])
+        })
+      }
+    }
+  }
+}
+
+// SECTION: Writing selectors as css.
+/// Writes `string` as a quoted css string that's also safe inside a
+/// `<style>` element.
+fn write_css_string(string: &str, into: &mut Formatter<'_>) -> fmt::Result {
+  into.write_char('"')?;
+  for char in string.chars() {
+    match char {
+      '"' => into.write_str("\\\"")?,
+      '\\' => into.write_str("\\\\")?,
+      '<' => into.write_str("\\3c ")?,
+      '\n' => into.write_str("\\a ")?,
+      _ => into.write_char(char)?,
+    }
+  }
+  into.write_char('"')
+}
+
+impl Display for SimpleSelector {
+  fn fmt(&self, into: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      SimpleSelector::Universal => into.write_char('*'),
+      SimpleSelector::Type(name) => into.write_str(name),
+      SimpleSelector::Id(id) => write!(into, "#{id}"),
+      SimpleSelector::Class(class) => write!(into, ".{class}"),
+      SimpleSelector::Attribute(attribute) => {
+        write!(into, "[{}", attribute.name)?;
+        if let Some((operator, value)) = &attribute.operator {
+          into.write_str(operator.as_str())?;
+          write_css_string(value, into)?;
+          if attribute.is_case_insensitive {
+            into.write_str(" i")?;
+          }
+        }
+        into.write_char(']')
+      }
+      SimpleSelector::Not(compound) => write!(into, ":not({compound})"),
+    }
+  }
+}
+
+impl Display for CompoundSelector {
+  fn fmt(&self, into: &mut Formatter<'_>) -> fmt::Result {
+    for selector in &self.0 {
+      write!(into, "{selector}")?;
+    }
+    Ok(())
+  }
+}
+
+impl Display for ComplexSelector {
+  fn fmt(&self, into: &mut Formatter<'_>) -> fmt::Result {
+    write!(into, "{}", self.compounds[0])?;
+    for (combinator, compound) in self.combinators.iter().zip(&self.compounds[1..]) {
+      match combinator {
+        Combinator::Descendant => write!(into, " {compound}")?,
+        Combinator::Child => write!(into, " > {compound}")?,
+      }
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,278 @@
+use super::super::traffic::BodyRewriter;
+use super::{parse, Action, Element, HtmlRewriter, ParseError, Rule, Source, SourceError};
+
+fn rules(source: &str) -> Vec<Rule> {
+  parse(source).unwrap()
+}
+
+fn errors(source: &str) -> Vec<String> {
+  parse(source)
+    .unwrap_err()
+    .iter()
+    .map(ParseError::to_string)
+    .collect()
+}
+
+/// Rewrites `html` in one go.
+fn rewrite(source: &str, html: &str) -> String {
+  let mut rewriter = HtmlRewriter::new(rules(source));
+  let mut output = Vec::new();
+  rewriter.write(html.as_bytes(), &mut output);
+  rewriter.end(&mut output);
+  String::from_utf8(output).unwrap()
+}
+
+/// Rewrites `html` a byte at a time, so every token gets cut in two.
+fn rewrite_bytewise(source: &str, html: &str) -> String {
+  let mut rewriter = HtmlRewriter::new(rules(source));
+  let mut output = Vec::new();
+  for byte in html.as_bytes() {
+    rewriter.write(std::slice::from_ref(byte), &mut output);
+  }
+  rewriter.end(&mut output);
+  String::from_utf8(output).unwrap()
+}
+
+/// The rewritten document without the injected stylesheet.
+fn rewrite_body(source: &str, html: &str) -> String {
+  let rewritten = rewrite(source, html);
+  assert_eq!(rewritten, rewrite_bytewise(source, html));
+  rewritten
+}
+
+#[test]
+fn parses_rules() {
+  let parsed = rules(r#"
+    // Everywhere.
+    img[alt*="casino" i], .sponsored { remove }
+
+    @host *.youtube.com, youtube.com {
+      #comments { replace "Comments are hidden"; }
+      ytd-thumbnail > img:not(.avatar) { blur(24px) }
+      /* Sound off. */
+      video { mute }
+    }
+
+    DIV.Banner[data-kind=ad] { hide }
+  "#);
+
+  assert_eq!(parsed.len(), 5);
+  assert!(parsed[0].hosts.is_empty());
+  assert_eq!(parsed[0].action, Action::Remove);
+  assert_eq!(parsed[0].selectors.len(), 2);
+  assert_eq!(parsed[0].selectors[0].to_string(), r#"img[alt*="casino" i]"#);
+
+  assert!(parsed[1].applies_to_host("www.youtube.com"));
+  assert!(parsed[1].applies_to_host("youtube.com"));
+  assert!(!parsed[1].applies_to_host("example.com"));
+  assert_eq!(parsed[1].action, Action::Replace { text: "Comments are hidden".into() });
+
+  assert_eq!(parsed[2].action, Action::Blur { radius: 24 });
+  assert_eq!(parsed[2].selectors[0].to_string(), "ytd-thumbnail > img:not(.avatar)");
+  assert_eq!(parsed[3].action, Action::Mute);
+
+  assert!(parsed[4].applies_to_host("example.com"));
+  assert_eq!(parsed[4].selectors[0].to_string(), "div.Banner[data-kind=\"ad\"]");
+}
+
+#[test]
+fn reports_errors_with_positions() {
+  assert_eq!(
+    errors("img { delete }"),
+    ["line 1, column 7: unknown action `delete`; expected `remove`, `hide`, `blur`, `mute` or `replace`"],
+  );
+
+  assert_eq!(
+    errors("img {\n  remove\n  blur\n}"),
+    ["line 3, column 7: a rule has one action; write another rule with the same selectors for `blur`"],
+  );
+
+  assert_eq!(
+    errors("p + img { remove }"),
+    ["line 1, column 3: sibling combinators, `+` and `~`, aren't supported"],
+  );
+
+  assert_eq!(
+    errors("a:hover { hide }"),
+    ["line 1, column 2: the pseudo-class `:hover` isn't supported; only `:not(...)` is"],
+  );
+
+  assert_eq!(
+    errors(".promo { replace \"Gone }"),
+    ["line 1, column 18: this text has no closing quote"],
+  );
+
+  assert_eq!(
+    errors("@host not a host { img { remove } }"),
+    ["line 1, column 11: expected `{`, but found `a`"],
+  );
+
+  assert_eq!(
+    errors("@media print { img { remove } }"),
+    ["line 1, column 1: unknown block `@media`; the only block is `@host`"],
+  );
+
+  assert_eq!(
+    errors("img { blur(0px) }"),
+    ["line 1, column 12: the blur radius must be from 1 to 100 pixels"],
+  );
+
+  assert_eq!(
+    errors("img[alt { hide }"),
+    ["line 1, column 9: expected `]` or an operator like `=`, `^=` or `*=`, but found `{`"],
+  );
+
+  assert_eq!(
+    errors("{ remove }"),
+    ["line 1, column 1: expected a selector, like `img`, `.class` or `#id`, but found `{`"],
+  );
+}
+
+#[test]
+fn keeps_going_after_errors() {
+  assert_eq!(
+    errors("img { delete }\nvideo { mute }\n.ad > { hide }\n@host example.com {\n  #x { explode }\n}"),
+    [
+      "line 1, column 7: unknown action `delete`; expected `remove`, `hide`, `blur`, `mute` or `replace`",
+      "line 3, column 7: expected a selector, like `img`, `.class` or `#id`, but found `{`",
+      "line 5, column 8: unknown action `explode`; expected `remove`, `hide`, `blur`, `mute` or `replace`",
+    ],
+  );
+}
+
+#[test]
+fn matches_selectors() {
+  let rule = |source: &str| rules(&format!("{source} {{ hide }}")).remove(0);
+
+  let html = Element::new("html");
+  let body = Element::new("body").with_attribute("class", "dark  wide");
+  let list = Element::new("ul").with_attribute("id", "related");
+  let item = Element::new("li").with_attribute("data-kind", "Video-Short");
+  let image = Element::new("img").with_attribute("alt", "A Casino Ad");
+
+  let ancestors = [html.clone(), body.clone(), list.clone(), item.clone()];
+
+  assert!(rule("img").matches(&image, &ancestors));
+  assert!(rule("*").matches(&image, &ancestors));
+  assert!(rule(".wide img").matches(&image, &ancestors));
+  assert!(rule("#related > li > img").matches(&image, &ancestors));
+  assert!(rule("body #related img").matches(&image, &ancestors));
+  assert!(!rule("#related > img").matches(&image, &ancestors));
+  assert!(!rule("img img").matches(&image, &ancestors));
+
+  assert!(rule("[alt*=casino i]").matches(&image, &ancestors));
+  assert!(!rule("[alt*=casino]").matches(&image, &ancestors));
+  assert!(rule("[alt^=\"A \"]").matches(&image, &ancestors));
+  assert!(rule("[alt$=Ad]").matches(&image, &ancestors));
+  assert!(rule("[alt~=Casino]").matches(&image, &ancestors));
+  assert!(!rule("[alt~=Cas]").matches(&image, &ancestors));
+  assert!(rule("li[data-kind|=video i] img").matches(&image, &ancestors));
+  assert!(!rule("[alt*=\"\"]").matches(&image, &ancestors));
+  assert!(!rule("[src]").matches(&image, &ancestors));
+
+  assert!(rule("img:not(.avatar)").matches(&image, &ancestors));
+  assert!(!rule("img:not([alt])").matches(&image, &ancestors));
+}
+
+#[test]
+fn removes_and_replaces_elements() {
+  assert_eq!(
+    rewrite_body(".ad { remove }", "<div><p>Hi</p><div class=\"ad\"><a href=/>Buy <b>now</b></a><img src=x></div><p>Bye</p></div>"),
+    "<div><p>Hi</p><p>Bye</p></div>",
+  );
+
+  assert_eq!(
+    rewrite_body("#comments { replace \"No <comments>\" }", "<main><section id=comments><h2>Comments</h2></section></main>"),
+    "<main>No &lt;comments&gt;</main>",
+  );
+
+  // Void elements have no end tag to wait for.
+  assert_eq!(
+    rewrite_body("img { remove }", "<p>a<img src=\"x.png\">b<br>c</p>"),
+    "<p>ab<br>c</p>",
+  );
+}
+
+#[test]
+fn ends_removals_at_implied_end_tags() {
+  // The next item ends the removed one, though the page never closed it.
+  assert_eq!(
+    rewrite_body("li.ad { remove }", "<ul><li>One<li class=ad>Ad<li>Two</ul>"),
+    "<ul><li>One<li>Two</ul>",
+  );
+
+  // So does the end of the list around it.
+  assert_eq!(
+    rewrite_body("li.ad { remove }", "<ul><li>One<li class=ad>Ad</ul><p>After</p>"),
+    "<ul><li>One</ul><p>After</p>",
+  );
+}
+
+#[test]
+fn changes_styles_and_attributes() {
+  assert_eq!(
+    rewrite_body("img { blur(8px) }", "<img src=\"a.png\" style=\"width: 10px\" alt='say \"hi\"'>"),
+    "<img src=\"a.png\" alt=\"say &quot;hi&quot;\" style=\"width: 10px;filter: blur(8px) !important\">",
+  );
+
+  assert_eq!(
+    rewrite_body("video { mute }", "<video src=v.mp4 autoplay></video>"),
+    "<video src=\"v.mp4\" autoplay=\"\" muted></video>",
+  );
+
+  // Several rules can change the same element.
+  assert_eq!(
+    rewrite_body(".a { hide }\n.b { blur }", "<div class=\"a b\"></div>"),
+    "<div class=\"a b\" style=\"display: none !important;filter: blur(16px) !important\"></div>",
+  );
+}
+
+#[test]
+fn leaves_raw_text_and_comments_alone() {
+  let html = "<script>if (a < b) { document.write('<div class=ad>x</div>') }</script><!-- <div class=ad> --><div class=ad>y</div>";
+  assert_eq!(
+    rewrite_body(".ad { remove }", html),
+    "<script>if (a < b) { document.write('<div class=ad>x</div>') }</script><!-- <div class=ad> -->",
+  );
+
+  // Removing a script removes its content, tags or not.
+  assert_eq!(
+    rewrite_body("script { remove }", "<p>a</p><script>x = '</p><p>'</script><p>b</p>"),
+    "<p>a</p><p>b</p>",
+  );
+
+  // Pages without matching elements come through unchanged.
+  let page = "<!DOCTYPE html><title>a < b</title><p data-x='>'>Fish & chips<img/></p>< not a tag";
+  assert_eq!(rewrite_body("video { mute }", page), page);
+}
+
+#[test]
+fn injects_a_stylesheet_for_later_elements() {
+  assert_eq!(
+    rewrite_body(".ad { remove }\nvideo { mute }", "<html><head><title>t</title></head><body></body></html>"),
+    "<html><head><style data-discipline>\n.ad { display: none !important; }\n</style><title>t</title></head><body></body></html>",
+  );
+
+  // Without a head, it goes before the body.
+  assert_eq!(
+    rewrite_body("img { blur(4px) }", "<body>x</body>"),
+    "<style data-discipline>\nimg { filter: blur(4px) !important; }\n</style><body>x</body>",
+  );
+
+  // Selectors can't break out of the style element.
+  assert_eq!(
+    rewrite_body("[title=\"</style><script>\"] { hide }", "<head></head>"),
+    "<head><style data-discipline>\n[title=\"\\3c /style>\\3c script>\"] { display: none !important; }\n</style></head>",
+  );
+}
+
+#[test]
+fn validates_sources() {
+  assert!(matches!(Source::parse("img { remove }".into()), Ok(source) if source.rules().len() == 1));
+  assert_eq!(Source::parse("// Nothing yet.".into()).unwrap_err(), SourceError::NoRules);
+  assert!(matches!(Source::parse("img { }".into()), Err(SourceError::Invalid { errors }) if errors.len() == 1));
+  assert!(matches!(
+    Source::parse("img { remove }\n".repeat(10_000)),
+    Err(SourceError::TooLong { .. }),
+  ));
+}
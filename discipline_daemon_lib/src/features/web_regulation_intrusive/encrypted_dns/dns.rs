@@ -0,0 +1,334 @@
+use std::net::IpAddr;
+
+/// The record type of service binding records.
+pub const SVCB: u16 = 64;
+/// The record type of service binding records for https, whose `ech`
+/// parameter carries the configurations browsers encrypt the tls client
+/// hello with.
+pub const HTTPS: u16 = 65;
+
+const A: u16 = 1;
+const NS: u16 = 2;
+const CNAME: u16 = 5;
+const SOA: u16 = 6;
+const PTR: u16 = 12;
+const MX: u16 = 15;
+const AAAA: u16 = 28;
+const RRSIG: u16 = 46;
+
+/// The class of internet records, the only one in use.
+const IN: u16 = 1;
+
+/// The response code for a server that couldn't answer.
+pub const SERVFAIL: u8 = 2;
+/// The response code for a name that doesn't exist.
+pub const NXDOMAIN: u8 = 3;
+
+const HEADER_LENGTH: usize = 12;
+
+/// How many compression pointers a name may follow, so looping names are
+/// rejected.
+const MAXIMUM_POINTERS: usize = 32;
+
+/// The first question of a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Question {
+  /// The lowercased name, without the trailing dot.
+  pub name: String,
+  pub record_type: u16,
+}
+
+/// The labels of a name as they appear in a message, and the offset right
+/// after the name, not following compression pointers.
+fn read_name(message: &[u8], mut offset: usize) -> Option<(Vec<&[u8]>, usize)> {
+  let mut labels = Vec::new();
+  let mut end = None;
+  let mut pointers = 0;
+
+  loop {
+    let length = *message.get(offset)? as usize;
+    match length >> 6 {
+      0 if length == 0 => {
+        return Some((labels, end.unwrap_or(offset + 1)));
+      }
+      0 => {
+        labels.push(message.get(offset + 1..offset + 1 + length)?);
+        offset += 1 + length;
+      }
+      3 => {
+        let low_bits = *message.get(offset + 1)? as usize;
+        end.get_or_insert(offset + 2);
+
+        pointers += 1;
+        if pointers > MAXIMUM_POINTERS {
+          return None;
+        }
+
+        offset = (length & 0x3f) << 8 | low_bits;
+      }
+      _ => {
+        return None;
+      }
+    }
+  }
+}
+
+/// Writes a name without compression.
+fn write_name(labels: &[&[u8]], into: &mut Vec<u8>) {
+  for label in labels {
+    into.push(label.len() as u8);
+    into.extend_from_slice(label);
+  }
+
+  into.push(0);
+}
+
+fn read_u16(message: &[u8], offset: usize) -> Option<u16> {
+  let bytes = message.get(offset..offset + 2)?;
+  Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn count(message: &[u8], index: usize) -> Option<u16> {
+  read_u16(message, 4 + index * 2)
+}
+
+/// The question of a query, if it asks exactly one.
+pub fn question(query: &[u8]) -> Option<Question> {
+  if query.len() < HEADER_LENGTH || count(query, 0)? != 1 {
+    return None;
+  }
+
+  let (labels, end) = read_name(query, HEADER_LENGTH)?;
+  let name = labels
+    .iter()
+    .map(|label| String::from_utf8_lossy(label).to_ascii_lowercase())
+    .collect::<Vec<_>>()
+    .join(".");
+
+  Some(Question {
+    name,
+    record_type: read_u16(query, end)?,
+  })
+}
+
+/// A response to `query` with `response_code` and no records, made without
+/// asking any server.
+pub fn answer_locally(query: &[u8], response_code: u8) -> Option<Vec<u8>> {
+  if query.len() < HEADER_LENGTH || count(query, 0)? != 1 {
+    return None;
+  }
+
+  let (labels, end) = read_name(query, HEADER_LENGTH)?;
+  let type_and_class = query.get(end..end + 4)?;
+
+  let mut response = Vec::with_capacity(query.len());
+  response.extend_from_slice(&query[..2]);
+  // Marks it as a response and keeps the operation code and whether
+  // recursion was desired.
+  response.push(0x80 | (query[2] & 0x79));
+  // Recursion is available.
+  response.push(0x80 | (response_code & 0x0f));
+  response.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+  write_name(&labels, &mut response);
+  response.extend_from_slice(type_and_class);
+  Some(response)
+}
+
+/// A response to `query` aliasing the name it asks about to `target`, made
+/// without asking any server.
+///
+/// `address` is given as the address of `target` if it's of the type asked
+/// for. Records of other types are left out, as if `target` had none.
+pub fn answer_with_alias(query: &[u8], target: &str, address: IpAddr, time_to_live: u32) -> Option<Vec<u8>> {
+  if query.len() < HEADER_LENGTH || count(query, 0)? != 1 {
+    return None;
+  }
+
+  let (labels, end) = read_name(query, HEADER_LENGTH)?;
+  let record_type = read_u16(query, end)?;
+  if read_u16(query, end + 2)? != IN {
+    return None;
+  }
+
+  let address = match (record_type, address) {
+    (A, IpAddr::V4(address)) => {
+      Some(address.octets().to_vec())
+    }
+    (AAAA, IpAddr::V6(address)) => {
+      Some(address.octets().to_vec())
+    }
+    _ => {
+      None
+    }
+  };
+
+  let target: Vec<&[u8]> = target.split('.').map(str::as_bytes).collect();
+  let mut target_name = Vec::new();
+  write_name(&target, &mut target_name);
+
+  let mut response = Vec::with_capacity(query.len() + 2 * target_name.len() + 40);
+  response.extend_from_slice(&query[..2]);
+  // Marks it as a response and keeps the operation code and whether
+  // recursion was desired.
+  response.push(0x80 | (query[2] & 0x79));
+  // Recursion is available.
+  response.push(0x80);
+  let answers = 1 + u16::from(address.is_some());
+  response.extend_from_slice(&[0, 1]);
+  response.extend_from_slice(&answers.to_be_bytes());
+  response.extend_from_slice(&[0, 0, 0, 0]);
+  write_name(&labels, &mut response);
+  response.extend_from_slice(&record_type.to_be_bytes());
+  response.extend_from_slice(&IN.to_be_bytes());
+
+  write_record(&labels, CNAME, time_to_live, &target_name, &mut response);
+  if let Some(address) = address {
+    write_record(&target, record_type, time_to_live, &address, &mut response);
+  }
+
+  Some(response)
+}
+
+fn write_record(name: &[&[u8]], record_type: u16, time_to_live: u32, data: &[u8], into: &mut Vec<u8>) {
+  write_name(name, into);
+  into.extend_from_slice(&record_type.to_be_bytes());
+  into.extend_from_slice(&IN.to_be_bytes());
+  into.extend_from_slice(&time_to_live.to_be_bytes());
+  into.extend_from_slice(&(data.len() as u16).to_be_bytes());
+  into.extend_from_slice(data);
+}
+
+struct Record<'a> {
+  name: Vec<&'a [u8]>,
+  record_type: u16,
+  /// The class and the time to live.
+  class_and_ttl: &'a [u8],
+  data_offset: usize,
+  data: &'a [u8],
+}
+
+impl Record<'_> {
+  fn is_of(&self, record_types: &[u16]) -> bool {
+    if record_types.contains(&self.record_type) {
+      return true;
+    }
+
+    // Signatures begin with the type of the records they cover.
+    self.record_type == RRSIG
+      && read_u16(self.data, 0).is_some_and(|covered| record_types.contains(&covered))
+  }
+
+  /// Writes the record without compression, decompressing the names in its
+  /// data, which later records may otherwise point into.
+  fn write(&self, message: &[u8], into: &mut Vec<u8>) -> Option<()> {
+    write_name(&self.name, into);
+    into.extend_from_slice(&self.record_type.to_be_bytes());
+    into.extend_from_slice(self.class_and_ttl);
+
+    let mut data = Vec::with_capacity(self.data.len());
+    match self.record_type {
+      NS | CNAME | PTR => {
+        let (name, _) = read_name(message, self.data_offset)?;
+        write_name(&name, &mut data);
+      }
+      MX => {
+        data.extend_from_slice(self.data.get(..2)?);
+        let (name, _) = read_name(message, self.data_offset + 2)?;
+        write_name(&name, &mut data);
+      }
+      SOA => {
+        let (primary, end) = read_name(message, self.data_offset)?;
+        let (mailbox, end) = read_name(message, end)?;
+        write_name(&primary, &mut data);
+        write_name(&mailbox, &mut data);
+        data.extend_from_slice(message.get(end..end + 20)?);
+      }
+      _ => {
+        data.extend_from_slice(self.data);
+      }
+    }
+
+    into.extend_from_slice(&u16::try_from(data.len()).ok()?.to_be_bytes());
+    into.extend_from_slice(&data);
+    Some(())
+  }
+}
+
+fn read_record(message: &[u8], offset: usize) -> Option<(Record<'_>, usize)> {
+  let (name, offset) = read_name(message, offset)?;
+  let record_type = read_u16(message, offset)?;
+  let class_and_ttl = message.get(offset + 2..offset + 8)?;
+  let data_length = read_u16(message, offset + 8)? as usize;
+  let data_offset = offset + 10;
+  let data = message.get(data_offset..data_offset + data_length)?;
+
+  let record = Record {
+    name,
+    record_type,
+    class_and_ttl,
+    data_offset,
+    data,
+  };
+
+  Some((record, data_offset + data_length))
+}
+
+/// `response` without the records of `record_types` and the signatures
+/// covering them, or `None` if it can't be parsed.
+///
+/// Responses with nothing to remove are returned as is. Others are written
+/// anew without compression, since removing records shifts the offsets
+/// compression pointers refer to.
+pub fn strip_records(response: &[u8], record_types: &[u16]) -> Option<Vec<u8>> {
+  if response.len() < HEADER_LENGTH {
+    return None;
+  }
+
+  let mut offset = HEADER_LENGTH;
+  let mut questions = Vec::new();
+  for _ in 0..count(response, 0)? {
+    let (name, end) = read_name(response, offset)?;
+    questions.push((name, response.get(end..end + 4)?));
+    offset = end + 4;
+  }
+
+  // The answer, authority and additional sections.
+  let mut sections = Vec::new();
+  let mut is_stripped = false;
+  for index in 1..4 {
+    let mut records = Vec::new();
+    for _ in 0..count(response, index)? {
+      let (record, end) = read_record(response, offset)?;
+      offset = end;
+
+      if record.is_of(record_types) {
+        is_stripped = true;
+      } else {
+        records.push(record);
+      }
+    }
+
+    sections.push(records);
+  }
+
+  if !is_stripped {
+    return Some(response.to_vec());
+  }
+
+  let mut stripped = Vec::with_capacity(response.len());
+  stripped.extend_from_slice(&response[..6]);
+  for records in &sections {
+    stripped.extend_from_slice(&(records.len() as u16).to_be_bytes());
+  }
+
+  for (name, type_and_class) in &questions {
+    write_name(name, &mut stripped);
+    stripped.extend_from_slice(type_and_class);
+  }
+
+  for record in sections.iter().flatten() {
+    record.write(response, &mut stripped)?;
+  }
+
+  Some(stripped)
+}
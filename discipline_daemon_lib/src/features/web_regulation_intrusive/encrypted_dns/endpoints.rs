@@ -0,0 +1,175 @@
+use std::net::IpAddr;
+use super::super::url_pattern::HostPattern;
+
+/// The domain browsers look up to decide whether to turn on their built-in
+/// DNS over https. A negative answer tells them the network filters DNS.
+pub const CANARY_DOMAIN: &str = "use-application-dns.net";
+
+/// The udp ports encrypted DNS is served on: 443 for DNS over http/3 and
+/// DNS over quic, 784 and 8853 for early DNS over quic deployments, and 853
+/// for DNS over quic and DNS over dtls.
+pub const ENCRYPTED_DNS_UDP_PORTS: &str = "443,784,853,8853";
+
+/// The media types of DNS over https requests and responses.
+static DNS_MEDIA_TYPES: &[&str] = &[
+  "application/dns-message",
+  "application/dns-json",
+];
+
+/// Host names of public DNS over https and DNS over quic servers, which
+/// browsers and operating systems are configured with or upgrade to.
+static KNOWN_HOST_PATTERNS: &[&str] = &[
+  "*.cloudflare-dns.com",
+  "one.one.one.one",
+  "dns.google",
+  "dns.google.com",
+  "dns64.dns.google",
+  "*.quad9.net",
+  "doh.opendns.com",
+  "doh.familyshield.opendns.com",
+  "*.adguard-dns.com",
+  "dns.adguard.com",
+  "*.nextdns.io",
+  "doh.cleanbrowsing.org",
+  "*.dns.mullvad.net",
+  "dns.mullvad.net",
+  "freedns.controld.com",
+  "dns.controld.com",
+  "*.dns0.eu",
+  "doh.dns.sb",
+  "dns.alidns.com",
+  "doh.pub",
+  "dns.switch.ch",
+  "doh.ffmuc.net",
+  "dns.twnic.tw",
+  "doh.libredns.gr",
+];
+
+/// Addresses of the same servers, for clients that connect to them without
+/// resolving a host name first.
+static KNOWN_ADDRESSES: &[&str] = &[
+  // Cloudflare.
+  "1.1.1.1",
+  "1.0.0.1",
+  "1.1.1.2",
+  "1.0.0.2",
+  "1.1.1.3",
+  "1.0.0.3",
+  "2606:4700:4700::1111",
+  "2606:4700:4700::1001",
+  "2606:4700:4700::1112",
+  "2606:4700:4700::1002",
+  "2606:4700:4700::1113",
+  "2606:4700:4700::1003",
+  // Google.
+  "8.8.8.8",
+  "8.8.4.4",
+  "2001:4860:4860::8888",
+  "2001:4860:4860::8844",
+  // Quad9.
+  "9.9.9.9",
+  "149.112.112.112",
+  "9.9.9.10",
+  "149.112.112.10",
+  "9.9.9.11",
+  "149.112.112.11",
+  "2620:fe::fe",
+  "2620:fe::9",
+  // OpenDNS.
+  "208.67.222.222",
+  "208.67.220.220",
+  "208.67.222.123",
+  "208.67.220.123",
+  // AdGuard.
+  "94.140.14.14",
+  "94.140.15.15",
+  "94.140.14.15",
+  "94.140.15.16",
+  "2a10:50c0::ad1:ff",
+  "2a10:50c0::ad2:ff",
+  // NextDNS.
+  "45.90.28.0",
+  "45.90.30.0",
+  // CleanBrowsing.
+  "185.228.168.168",
+  "185.228.169.168",
+  "185.228.168.9",
+  "185.228.169.9",
+  // Mullvad.
+  "194.242.2.2",
+  "194.242.2.3",
+  // Control D.
+  "76.76.2.0",
+  "76.76.10.0",
+  // DNS0.EU.
+  "193.110.81.0",
+  "185.253.5.0",
+  // AliDNS.
+  "223.5.5.5",
+  "223.6.6.6",
+];
+
+/// The maintained lists of encrypted DNS servers.
+#[derive(Debug, Clone)]
+pub struct KnownEndpoints {
+  host_patterns: Vec<HostPattern>,
+  addresses: Vec<IpAddr>,
+}
+
+impl KnownEndpoints {
+  pub fn new() -> Self {
+    Self {
+      host_patterns: KNOWN_HOST_PATTERNS
+        .iter()
+        .filter_map(|pattern| HostPattern::parse(pattern).ok())
+        .collect(),
+      addresses: KNOWN_ADDRESSES
+        .iter()
+        .filter_map(|address| address.parse().ok())
+        .collect(),
+    }
+  }
+
+  pub fn addresses(&self) -> &Vec<IpAddr> {
+    &self.addresses
+  }
+
+  /// Whether `host`, a host name or an ip address, is one of the servers.
+  pub fn contains_host(&self, host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    let address = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(address) = address.parse::<IpAddr>() {
+      return self.contains_address(address);
+    }
+
+    self
+      .host_patterns
+      .iter()
+      .any(|pattern| pattern.matches(&host))
+  }
+
+  pub fn contains_address(&self, address: IpAddr) -> bool {
+    let address = match address {
+      IpAddr::V6(address) => {
+        address.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(IpAddr::V6(address))
+      }
+      address => {
+        address
+      }
+    };
+
+    self.addresses.contains(&address)
+  }
+}
+
+impl Default for KnownEndpoints {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Whether `media_type`, lowercase and without parameters, is that of a DNS
+/// over https message.
+pub fn is_dns_media_type(media_type: &str) -> bool {
+  DNS_MEDIA_TYPES.contains(&media_type)
+}
@@ -0,0 +1,305 @@
+use std::net::IpAddr;
+use std::sync::{Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+use crate::database::web_regulation_intrusive_encrypted_dns_setting as setting_db;
+use crate::operating_system_integration::{self as os, UserId};
+use crate::{Daemon, Database, GenericError};
+use super::super::traffic::{Exchange, RequestHead, RequestVerdict, Response, ResponseHead, TrafficHandler};
+use super::dns::{self, HTTPS, NXDOMAIN, SERVFAIL, SVCB};
+use super::endpoints::{is_dns_media_type, KnownEndpoints, CANARY_DOMAIN, ENCRYPTED_DNS_UDP_PORTS};
+
+/// What the firewall rules redirecting DNS to the forwarder for this feature
+/// are tagged with.
+const FIREWALL_RULE_PURPOSE: &str = "discipline-encrypted-dns-prevention";
+
+/// The ways encrypted DNS can be kept from bypassing DNS and host name
+/// based regulation, each of which can be taken on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Measures {
+  /// Drop DNS over tls and DNS over quic to any server, which both use
+  /// port 853.
+  pub block_dns_over_tls: bool,
+  /// Block the servers in the maintained list of encrypted DNS servers,
+  /// whether they're reached by name or address, as well as DNS over https
+  /// requests to any other server.
+  pub block_known_endpoints: bool,
+  /// Answer the `use-application-dns.net` canary with NXDOMAIN, which tells
+  /// browsers to keep their built-in DNS over https off.
+  pub answer_canary: bool,
+  /// Strip HTTPS and SVCB records from DNS answers, so browsers can't find
+  /// the configurations to encrypt the tls server name indication with.
+  pub strip_service_bindings: bool,
+}
+
+impl Measures {
+  pub const NONE: Measures = Measures {
+    block_dns_over_tls: false,
+    block_known_endpoints: false,
+    answer_canary: false,
+    strip_service_bindings: false,
+  };
+
+  pub const ALL: Measures = Measures {
+    block_dns_over_tls: true,
+    block_known_endpoints: true,
+    answer_canary: true,
+    strip_service_bindings: true,
+  };
+
+  /// Whether the user's plain DNS has to go through our DNS forwarder.
+  pub fn need_forwarder(&self) -> bool {
+    self.block_known_endpoints || self.answer_canary || self.strip_service_bindings
+  }
+}
+
+/// The measures taken against a managed user's encrypted DNS.
+#[derive(Debug, Clone)]
+pub struct Setting {
+  user_id: UserId,
+  measures: Measures,
+}
+
+impl Setting {
+  pub fn from_fields(user_id: UserId, measures: Measures) -> Self {
+    Self {
+      user_id,
+      measures,
+    }
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn measures(&self) -> Measures {
+    self.measures
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingCreator {
+  pub measures: Measures,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SettingCreatorError {
+  NoMeasures,
+}
+
+impl SettingCreator {
+  pub fn create(self, user_id: UserId) -> Result<Setting, SettingCreatorError> {
+    if self.measures == Measures::NONE {
+      return Err(SettingCreatorError::NoMeasures);
+    }
+
+    Ok(Setting::from_fields(user_id, self.measures))
+  }
+}
+
+// SECTION: Enforcement.
+/// Keeps managed users from resolving host names through encrypted DNS,
+/// which we can neither see nor regulate.
+///
+/// Port 853 and the udp ports of known servers are dropped by the firewall,
+/// connections and DNS over https requests to known servers are refused by
+/// the proxy, and the users' plain DNS is redirected to our DNS forwarder,
+/// which answers the canary and strips service binding records. Programs
+/// that resolve through systemd-resolved's D-Bus or varlink interfaces
+/// rather than over port 53 aren't covered by the forwarder.
+pub struct EncryptedDnsPrevention {
+  settings: Mutex<Vec<Setting>>,
+  known_endpoints: KnownEndpoints,
+  forwarder_port: u16,
+}
+
+impl EncryptedDnsPrevention {
+  pub fn open(database: &Database, forwarder_port: u16) -> Result<Self, GenericError> {
+    let settings = setting_db::retrieve_all_settings(database)
+      .map_err(|error| error.change_context("opening encrypted DNS prevention"))?;
+
+    Ok(Self::new(settings, forwarder_port))
+  }
+
+  pub fn new(settings: Vec<Setting>, forwarder_port: u16) -> Self {
+    Self {
+      settings: Mutex::new(settings),
+      known_endpoints: KnownEndpoints::new(),
+      forwarder_port,
+    }
+  }
+
+  pub fn settings(&self) -> MutexGuard<'_, Vec<Setting>> {
+    self.settings.lock().unwrap()
+  }
+
+  pub fn known_endpoints(&self) -> &KnownEndpoints {
+    &self.known_endpoints
+  }
+
+  pub fn forwarder_port(&self) -> u16 {
+    self.forwarder_port
+  }
+
+  pub fn measures_of(&self, user_id: UserId) -> Measures {
+    self
+      .settings()
+      .iter()
+      .find(|setting| setting.user_id == user_id)
+      .map(|setting| setting.measures)
+      .unwrap_or(Measures::NONE)
+  }
+
+  /// The measures taken against a query that reached the forwarder.
+  ///
+  /// Only the queries of users with a setting are redirected to it, so those
+  /// whose owner we couldn't find get every measure.
+  pub fn measures_of_query_owner(&self, user_id: Option<UserId>) -> Measures {
+    match user_id {
+      Some(user_id) => {
+        self.measures_of(user_id)
+      }
+      None => {
+        Measures::ALL
+      }
+    }
+  }
+
+  /// Whether a tls connection of `user_id` to `host`, a host name or an ip
+  /// address, or to `address` is to be refused.
+  pub fn blocks_connection(&self, user_id: Option<UserId>, host: &str, address: Option<IpAddr>) -> bool {
+    let Some(user_id) = user_id else {
+      return false;
+    };
+
+    self.measures_of(user_id).block_known_endpoints
+      && (
+        self.known_endpoints.contains_host(host)
+          || address.is_some_and(|address| self.known_endpoints.contains_address(address))
+      )
+  }
+
+  /// Answers a DNS `query` with `measures` taken, using `forward` to ask the
+  /// upstream server. `None` means the query is to go unanswered.
+  pub fn resolve(
+    &self,
+    measures: Measures,
+    query: &[u8],
+    forward: impl FnOnce(&[u8]) -> Option<Vec<u8>>,
+  ) -> Option<Vec<u8>> {
+    if let Some(question) = dns::question(query) {
+      if measures.answer_canary && question.name == CANARY_DOMAIN {
+        return dns::answer_locally(query, NXDOMAIN);
+      }
+
+      if measures.block_known_endpoints && self.known_endpoints.contains_host(&question.name) {
+        return dns::answer_locally(query, NXDOMAIN);
+      }
+    }
+
+    let Some(response) = forward(query) else {
+      return dns::answer_locally(query, SERVFAIL);
+    };
+
+    if !measures.strip_service_bindings {
+      return Some(response);
+    }
+
+    // Responses we can't make sense of could be hiding records from us.
+    match dns::strip_records(&response, &[SVCB, HTTPS]) {
+      Some(response) => {
+        Some(response)
+      }
+      None => {
+        dns::answer_locally(query, SERVFAIL)
+      }
+    }
+  }
+
+  /// Adds and removes the firewall rules of `user_id` so they match
+  /// `measures`.
+  pub fn apply_firewall_rules(&self, user_id: UserId, measures: Measures) -> Result<(), GenericError> {
+    if measures.block_dns_over_tls {
+      os::block_dns_over_tls_of_user(&user_id)?;
+    } else {
+      os::unblock_dns_over_tls_of_user(&user_id)?;
+    }
+
+    let addresses = self.known_endpoints.addresses();
+    if measures.block_known_endpoints {
+      os::block_udp_traffic_of_user_to_addresses(&user_id, addresses, ENCRYPTED_DNS_UDP_PORTS)?;
+    } else {
+      os::unblock_udp_traffic_of_user_to_addresses(&user_id, addresses, ENCRYPTED_DNS_UDP_PORTS)?;
+    }
+
+    if measures.need_forwarder() {
+      os::redirect_dns_of_user_to_forwarder(&user_id, self.forwarder_port, FIREWALL_RULE_PURPOSE)?;
+    } else {
+      os::stop_redirecting_dns_of_user_to_forwarder(&user_id, self.forwarder_port, FIREWALL_RULE_PURPOSE)?;
+    }
+
+    Ok(())
+  }
+
+  /// Restores the firewall rules of every setting, which don't survive
+  /// restarts of the machine.
+  pub fn apply_all_firewall_rules(&self) -> Result<(), GenericError> {
+    let settings = self.settings().clone();
+    for setting in settings {
+      self
+        .apply_firewall_rules(setting.user_id, setting.measures)
+        .map_err(|error| error.change_context("applying the firewall rules of all encrypted DNS settings"))?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Whether `request` is a DNS over https request, going by its media types
+/// or by the `dns` parameter of the standard `/dns-query` endpoint.
+pub fn is_dns_over_https_request(request: &RequestHead) -> bool {
+  if request.content_type().is_some_and(|media_type| is_dns_media_type(&media_type)) {
+    return true;
+  }
+
+  let accepts_dns = request
+    .headers
+    .get_all("Accept")
+    .flat_map(|value| value.split(','))
+    .any(|media_type| {
+      let media_type = media_type.split(';').next().unwrap_or_default();
+      is_dns_media_type(&media_type.trim().to_ascii_lowercase())
+    });
+
+  if accepts_dns {
+    return true;
+  }
+
+  let (path, query) = request.target.split_once('?').unwrap_or((&request.target, ""));
+  path.ends_with("/dns-query")
+    && query.split('&').any(|parameter| parameter.starts_with("dns="))
+}
+
+fn blocked() -> Response {
+  let mut head = ResponseHead::new(403, "Forbidden");
+  head.headers.append("Cache-Control", "no-store");
+  Response::new(head, Vec::new())
+}
+
+impl TrafficHandler for EncryptedDnsPrevention {
+  fn on_request(&self, _daemon: &Daemon, exchange: &mut Exchange) -> RequestVerdict {
+    let Some(user_id) = exchange.user_id else {
+      return RequestVerdict::Forward;
+    };
+
+    if !self.measures_of(user_id).block_known_endpoints {
+      return RequestVerdict::Forward;
+    }
+
+    if self.known_endpoints.contains_host(&exchange.host) || is_dns_over_https_request(&exchange.request) {
+      // The clients are programs rather than people, so there's no page.
+      return RequestVerdict::Respond(blocked());
+    }
+
+    RequestVerdict::Forward
+  }
+}
@@ -0,0 +1,221 @@
+use std::fs;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration as StandardDuration;
+use crate::operating_system_integration::{retrieve_tcp_socket_owner, retrieve_udp_socket_owner, UserId};
+use crate::{Daemon, GenericError};
+
+/// How long to wait for a nameserver's answer before asking the next one.
+const UPSTREAM_TIMEOUT: StandardDuration = StandardDuration::from_secs(3);
+
+/// How long a tcp connection may go without a query before we close it.
+const TCP_IDLE_TIMEOUT: StandardDuration = StandardDuration::from_secs(10);
+
+const MAXIMUM_MESSAGE_LENGTH: usize = u16::MAX as usize;
+
+/// The nameserver glibc uses when "/etc/resolv.conf" names none.
+const DEFAULT_NAMESERVER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 53);
+
+/// Starts answering the DNS queries redirected to `port` on the loopback
+/// interfaces, over both udp and tcp.
+///
+/// Queries are answered by the nameservers the system is configured with,
+/// rather than the ones they were sent to, with the measures of the user
+/// who made them taken. Queries for hosts SafeSearch pins for the user are
+/// answered by SafeSearch instead.
+pub fn run(daemon: Arc<Daemon>, port: u16) -> Result<(), GenericError> {
+  for address in [
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port),
+    SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), port),
+  ] {
+    let socket = UdpSocket::bind(address).map_err(|error|
+      GenericError::new("running the DNS forwarder")
+        .add_error("failed to bind the udp socket")
+        .add_attachment("address", address.to_string())
+        .add_attachment("io error", error.to_string())
+    )?;
+
+    let listener = TcpListener::bind(address).map_err(|error|
+      GenericError::new("running the DNS forwarder")
+        .add_error("failed to bind the tcp listener")
+        .add_attachment("address", address.to_string())
+        .add_attachment("io error", error.to_string())
+    )?;
+
+    let udp_daemon = Arc::clone(&daemon);
+    thread::spawn(move || serve_udp(udp_daemon, Arc::new(socket)));
+
+    let daemon = Arc::clone(&daemon);
+    thread::spawn(move || {
+      for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+          continue;
+        };
+
+        let daemon = Arc::clone(&daemon);
+        thread::spawn(move || {
+          let _ = serve_tcp(&daemon, stream);
+        });
+      }
+    });
+  }
+
+  Ok(())
+}
+
+fn answer(
+  daemon: &Daemon,
+  user_id: Option<UserId>,
+  query: &[u8],
+  forward: impl FnOnce(&[u8]) -> Option<Vec<u8>>,
+) -> Option<Vec<u8>> {
+  if let Some(response) = daemon.web_regulation_intrusive().safe_search().answer_query(user_id, query) {
+    return Some(response);
+  }
+
+  let prevention = daemon.web_regulation_intrusive().encrypted_dns_prevention();
+  let measures = prevention.measures_of_query_owner(user_id);
+  prevention.resolve(measures, query, forward)
+}
+
+fn serve_udp(daemon: Arc<Daemon>, socket: Arc<UdpSocket>) {
+  let mut buffer = vec![0; MAXIMUM_MESSAGE_LENGTH];
+  loop {
+    let Ok((length, peer_address)) = socket.recv_from(&mut buffer) else {
+      continue;
+    };
+
+    // Looked up right away, before the querying socket goes away.
+    let user_id = retrieve_udp_socket_owner(peer_address);
+    let query = buffer[..length].to_vec();
+
+    let daemon = Arc::clone(&daemon);
+    let socket = Arc::clone(&socket);
+    thread::spawn(move || {
+      if let Some(response) = answer(&daemon, user_id, &query, forward_over_udp) {
+        let _ = socket.send_to(&response, peer_address);
+      }
+    });
+  }
+}
+
+fn serve_tcp(daemon: &Daemon, mut stream: TcpStream) -> io::Result<()> {
+  let user_id = stream.peer_addr().ok().and_then(retrieve_tcp_socket_owner);
+  stream.set_read_timeout(Some(TCP_IDLE_TIMEOUT))?;
+
+  while let Some(query) = read_tcp_message(&mut stream)? {
+    let Some(response) = answer(daemon, user_id, &query, forward_over_tcp) else {
+      return Ok(());
+    };
+
+    write_tcp_message(&mut stream, &response)?;
+  }
+
+  Ok(())
+}
+
+/// Reads a message prefixed with its length, as DNS over tcp sends them.
+/// `None` means the peer closed the connection between messages.
+fn read_tcp_message(stream: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+  let mut length = [0; 2];
+  match stream.read_exact(&mut length) {
+    Ok(()) => {}
+    Err(error) if error.kind() == ErrorKind::UnexpectedEof => {
+      return Ok(None);
+    }
+    Err(error) => {
+      return Err(error);
+    }
+  }
+
+  let mut message = vec![0; u16::from_be_bytes(length) as usize];
+  stream.read_exact(&mut message)?;
+  Ok(Some(message))
+}
+
+fn write_tcp_message(stream: &mut impl Write, message: &[u8]) -> io::Result<()> {
+  let length = u16::try_from(message.len())
+    .map_err(|_| io::Error::new(ErrorKind::InvalidData, "DNS message too long for tcp"))?;
+
+  let mut framed = Vec::with_capacity(message.len() + 2);
+  framed.extend_from_slice(&length.to_be_bytes());
+  framed.extend_from_slice(message);
+  stream.write_all(&framed)
+}
+
+/// The nameservers in "/etc/resolv.conf", in order.
+fn system_nameservers() -> Vec<SocketAddr> {
+  let nameservers: Vec<SocketAddr> = fs::read_to_string("/etc/resolv.conf")
+    .unwrap_or_default()
+    .lines()
+    .filter_map(|line| line.trim().strip_prefix("nameserver"))
+    .filter_map(|address| {
+      // Link-local ipv6 addresses may carry a zone, which IpAddr can't.
+      let address = address.trim().split('%').next().unwrap_or_default();
+      address.parse::<IpAddr>().ok()
+    })
+    .map(|address| SocketAddr::new(address, 53))
+    .collect();
+
+  if nameservers.is_empty() {
+    vec![DEFAULT_NAMESERVER]
+  } else {
+    nameservers
+  }
+}
+
+fn forward_over_udp(query: &[u8]) -> Option<Vec<u8>> {
+  if query.len() < 2 {
+    return None;
+  }
+
+  let mut buffer = vec![0; MAXIMUM_MESSAGE_LENGTH];
+  for nameserver in system_nameservers() {
+    let local_address = match nameserver {
+      SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+      SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+    };
+
+    let Ok(socket) = UdpSocket::bind(local_address) else {
+      continue;
+    };
+
+    if socket.set_read_timeout(Some(UPSTREAM_TIMEOUT)).is_err()
+      || socket.connect(nameserver).is_err()
+      || socket.send(query).is_err()
+    {
+      continue;
+    }
+
+    // Whatever doesn't carry the query's id isn't an answer to it.
+    while let Ok(length) = socket.recv(&mut buffer) {
+      if length >= 2 && buffer[..2] == query[..2] {
+        return Some(buffer[..length].to_vec());
+      }
+    }
+  }
+
+  None
+}
+
+fn forward_over_tcp(query: &[u8]) -> Option<Vec<u8>> {
+  for nameserver in system_nameservers() {
+    let Ok(mut stream) = TcpStream::connect_timeout(&nameserver, UPSTREAM_TIMEOUT) else {
+      continue;
+    };
+
+    if stream.set_read_timeout(Some(UPSTREAM_TIMEOUT)).is_err()
+      || write_tcp_message(&mut stream, query).is_err()
+    {
+      continue;
+    }
+
+    if let Ok(Some(response)) = read_tcp_message(&mut stream) {
+      return Some(response);
+    }
+  }
+
+  None
+}
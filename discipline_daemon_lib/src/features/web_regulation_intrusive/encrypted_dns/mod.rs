@@ -0,0 +1,19 @@
+pub mod endpoints;
+pub use endpoints::{is_dns_media_type, KnownEndpoints, CANARY_DOMAIN};
+
+pub mod dns;
+
+pub mod forwarder;
+
+pub mod feature;
+pub use feature::{
+  is_dns_over_https_request,
+  EncryptedDnsPrevention,
+  Measures,
+  Setting,
+  SettingCreator,
+  SettingCreatorError,
+};
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,221 @@
+use std::net::IpAddr;
+use crate::operating_system_integration::UserId;
+use super::super::traffic::{Headers, RequestHead};
+use super::dns::{self, HTTPS, NXDOMAIN, SERVFAIL, SVCB};
+use super::*;
+
+const CNAME: u16 = 5;
+const A: u16 = 1;
+const RRSIG: u16 = 46;
+
+fn name(name: &str, into: &mut Vec<u8>) {
+  for label in name.split('.') {
+    into.push(label.len() as u8);
+    into.extend_from_slice(label.as_bytes());
+  }
+  into.push(0);
+}
+
+fn query(question: &str, record_type: u16) -> Vec<u8> {
+  // Id 0x1234, recursion desired, one question.
+  let mut query = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+  name(question, &mut query);
+  query.extend_from_slice(&record_type.to_be_bytes());
+  query.extend_from_slice(&[0, 1]);
+  query
+}
+
+fn record(owner: &[u8], record_type: u16, data: &[u8], into: &mut Vec<u8>) {
+  into.extend_from_slice(owner);
+  into.extend_from_slice(&record_type.to_be_bytes());
+  into.extend_from_slice(&[0, 1, 0, 0, 0x0e, 0x10]);
+  into.extend_from_slice(&(data.len() as u16).to_be_bytes());
+  into.extend_from_slice(data);
+}
+
+/// A response to a query for the HTTPS records of `www.example.com`, whose
+/// names point back at the question: a CNAME to `cdn.example.com`, the
+/// HTTPS record of the target and its signature, and an address in the
+/// additional section, owned by a name pointing into the CNAME's data.
+fn response() -> Vec<u8> {
+  let mut response = query("www.example.com", HTTPS);
+  response[2] |= 0x80;
+  response[3] = 0x80;
+  response[6..12].copy_from_slice(&[0, 3, 0, 0, 0, 1]);
+
+  // The question's name is at 12, and `example.com` at 16.
+  let mut cname_data = vec![3];
+  cname_data.extend_from_slice(b"cdn");
+  cname_data.extend_from_slice(&[0xc0, 16]);
+  let cname_data_offset = response.len() + 2 + 10;
+  record(&[0xc0, 12], CNAME, &cname_data, &mut response);
+
+  let https_data = [0, 1, 0, 0, 5, 0, 4, b'e', b'c', b'h', b'!'];
+  let cname_target = [0xc0, cname_data_offset as u8];
+  record(&cname_target, HTTPS, &https_data, &mut response);
+
+  let mut signature = HTTPS.to_be_bytes().to_vec();
+  signature.extend_from_slice(&[13, 3, 0, 0, 0x0e, 0x10]);
+  record(&cname_target, RRSIG, &signature, &mut response);
+
+  record(&cname_target, A, &[192, 0, 2, 1], &mut response);
+  response
+}
+
+#[test]
+fn parses_questions() {
+  let question = dns::question(&query("Use-Application-DNS.net", 1)).unwrap();
+  assert_eq!(question.name, CANARY_DOMAIN);
+  assert_eq!(question.record_type, 1);
+
+  // A loop of compression pointers.
+  let mut looping = query("example.com", 1);
+  looping.truncate(12);
+  looping.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1]);
+  assert!(dns::question(&looping).is_none());
+  assert!(dns::question(&[0; 5]).is_none());
+}
+
+#[test]
+fn answers_locally() {
+  let query = query("use-application-dns.net", 1);
+  let answer = dns::answer_locally(&query, NXDOMAIN).unwrap();
+
+  assert_eq!(answer[..2], [0x12, 0x34]);
+  // A response, with recursion desired and available.
+  assert_eq!(answer[2], 0x81);
+  assert_eq!(answer[3], 0x80 | NXDOMAIN);
+  assert_eq!(answer[4..12], [0, 1, 0, 0, 0, 0, 0, 0]);
+  assert_eq!(answer[12..], query[12..]);
+}
+
+#[test]
+fn answers_with_aliases() {
+  let address = IpAddr::V4([216, 239, 38, 120].into());
+  let mut target = Vec::new();
+  name("forcesafesearch.google.com", &mut target);
+
+  let query_a = query("www.google.com", A);
+  let answer = dns::answer_with_alias(&query_a, "forcesafesearch.google.com", address, 60).unwrap();
+
+  let mut expected = query_a.clone();
+  expected[2] = 0x81;
+  expected[3] = 0x80;
+  expected[6..8].copy_from_slice(&[0, 2]);
+  let mut owner = Vec::new();
+  name("www.google.com", &mut owner);
+  let mut cname = Vec::new();
+  record(&owner, CNAME, &target, &mut cname);
+  let mut address_record = Vec::new();
+  record(&target, A, &[216, 239, 38, 120], &mut address_record);
+  // Our records live a minute rather than the hour `record` gives them.
+  cname[owner.len() + 6..owner.len() + 8].copy_from_slice(&[0, 60]);
+  address_record[target.len() + 6..target.len() + 8].copy_from_slice(&[0, 60]);
+  expected.extend_from_slice(&cname);
+  expected.extend_from_slice(&address_record);
+  assert_eq!(answer, expected);
+
+  // Other types get the alias alone.
+  let answer = dns::answer_with_alias(&query("www.google.com", HTTPS), "forcesafesearch.google.com", address, 60).unwrap();
+  assert_eq!(answer[6..8], [0, 1]);
+  assert!(answer.ends_with(&target));
+}
+
+#[test]
+fn strips_service_bindings() {
+  let response = response();
+  let stripped = dns::strip_records(&response, &[SVCB, HTTPS]).unwrap();
+
+  // The CNAME stays in the answers and the address in the additional section.
+  assert_eq!(stripped[6..12], [0, 1, 0, 0, 0, 1]);
+  assert!(!stripped.windows(4).any(|window| window == b"ech!"));
+
+  // Written without compression, so names no longer point at anything.
+  assert_eq!(dns::strip_records(&stripped, &[]).unwrap(), stripped);
+  assert!(stripped.windows(17).any(|window| window == b"\x03cdn\x07example\x03com\x00"));
+  assert!(stripped.ends_with(&[0, 4, 192, 0, 2, 1]));
+
+  // Nothing to strip.
+  assert_eq!(dns::strip_records(&response, &[12345]).unwrap(), response);
+
+  assert!(dns::strip_records(&response[..response.len() - 1], &[HTTPS]).is_none());
+}
+
+#[test]
+fn resolves_with_measures() {
+  let prevention = EncryptedDnsPrevention::new(Vec::new(), 9153);
+  let forwarded = |_: &[u8]| Some(response());
+  let rcode = |answer: Option<Vec<u8>>| answer.unwrap()[3] & 0x0f;
+
+  let canary = query(CANARY_DOMAIN, 1);
+  assert_eq!(rcode(prevention.resolve(Measures::ALL, &canary, forwarded)), NXDOMAIN);
+  assert_eq!(prevention.resolve(Measures::NONE, &canary, forwarded), Some(response()));
+
+  let known = query("mozilla.cloudflare-dns.com", 1);
+  assert_eq!(rcode(prevention.resolve(Measures::ALL, &known, forwarded)), NXDOMAIN);
+
+  let https = query("www.example.com", HTTPS);
+  let stripped = prevention.resolve(Measures::ALL, &https, forwarded).unwrap();
+  assert_eq!(stripped[6..8], [0, 1]);
+
+  assert_eq!(rcode(prevention.resolve(Measures::ALL, &https, |_| None)), SERVFAIL);
+  let truncated = |_: &[u8]| Some(response()[..40].to_vec());
+  assert_eq!(rcode(prevention.resolve(Measures::ALL, &https, truncated)), SERVFAIL);
+}
+
+#[test]
+fn recognizes_known_endpoints() {
+  let endpoints = KnownEndpoints::new();
+  assert!(endpoints.contains_host("dns.google"));
+  assert!(endpoints.contains_host("Chrome.Cloudflare-DNS.com."));
+  assert!(endpoints.contains_host("[2606:4700:4700::1111]"));
+  assert!(endpoints.contains_host("1.1.1.1"));
+  assert!(!endpoints.contains_host("google.com"));
+  assert!(endpoints.contains_address("::ffff:8.8.8.8".parse::<IpAddr>().unwrap()));
+  assert!(!endpoints.contains_address("192.0.2.1".parse::<IpAddr>().unwrap()));
+}
+
+#[test]
+fn blocks_connections_of_users_with_the_measure() {
+  let user_id = UserId::new(1000);
+  let measures = Measures {
+    block_known_endpoints: true,
+    ..Measures::NONE
+  };
+  let setting = SettingCreator { measures }.create(user_id).unwrap();
+  let prevention = EncryptedDnsPrevention::new(vec![setting], 9153);
+
+  let address = Some("8.8.4.4".parse().unwrap());
+  assert!(prevention.blocks_connection(Some(user_id), "example.com", address));
+  assert!(prevention.blocks_connection(Some(user_id), "dns.google", None));
+  assert!(!prevention.blocks_connection(Some(UserId::new(1001)), "dns.google", None));
+  assert!(!prevention.blocks_connection(None, "dns.google", None));
+
+  assert!(matches!(
+    SettingCreator { measures: Measures::NONE }.create(user_id),
+    Err(SettingCreatorError::NoMeasures),
+  ));
+}
+
+#[test]
+fn recognizes_dns_over_https_requests() {
+  let request = |target: &str, header: Option<(&str, &str)>| {
+    let mut headers = Headers::new();
+    if let Some((name, value)) = header {
+      headers.append(name, value);
+    }
+
+    RequestHead {
+      method: "GET".into(),
+      target: target.into(),
+      version: 1,
+      headers,
+    }
+  };
+
+  assert!(is_dns_over_https_request(&request("/dns-query?dns=AAABAAAB", None)));
+  assert!(is_dns_over_https_request(&request("/q", Some(("Accept", "text/html, application/dns-message")))));
+  assert!(is_dns_over_https_request(&request("/q", Some(("Content-Type", "application/dns-message")))));
+  assert!(!is_dns_over_https_request(&request("/dns-query", None)));
+  assert!(!is_dns_over_https_request(&request("/search?dns=1", None)));
+}
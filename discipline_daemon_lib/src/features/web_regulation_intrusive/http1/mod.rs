@@ -0,0 +1,277 @@
+use std::io::{self, Read, Write};
+use http::{HeaderMap, Version};
+use llhttp_rs::message::{Item, MessageKind, MessageReader, RequestReader, ResponseReader};
+use super::traffic::{Headers, RequestHead, ResponseHead};
+
+pub use llhttp_rs::message::BodyFraming;
+
+const COPY_BUFFER_LENGTH: usize = 16 * 1024;
+
+fn invalid_data(message: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn collect_headers(parsed: &HeaderMap) -> Headers {
+  let mut headers = Headers::new();
+  for (name, value) in parsed {
+    headers.append(name.as_str(), String::from_utf8_lossy(value.as_bytes()));
+  }
+  headers
+}
+
+fn minor_version(version: Version) -> u8 {
+  if version == Version::HTTP_10 || version == Version::HTTP_09 {
+    0
+  } else {
+    1
+  }
+}
+
+/// Reads the head of the next request. Returns `None` if the connection was
+/// closed before a request started.
+pub fn read_request_head<R: Read>(reader: &mut RequestReader<R>) -> io::Result<Option<(RequestHead, BodyFraming)>> {
+  match reader.next_item()? {
+    Some(Item::Head { head, framing, .. }) => {
+      Ok(Some((
+        RequestHead {
+          method: head.method.to_string(),
+          target: head.target,
+          version: minor_version(head.version),
+          headers: collect_headers(&head.headers),
+        },
+        framing,
+      )))
+    }
+    None => {
+      Ok(None)
+    }
+    Some(_) => {
+      Err(invalid_data("expected a request head"))
+    }
+  }
+}
+
+/// Reads the head of the next response. `request_method` is the method of
+/// the request it answers, since responses to HEAD requests have no body
+/// whatever their head says.
+pub fn read_response_head<R: Read>(
+  reader: &mut ResponseReader<R>,
+  request_method: &str,
+) -> io::Result<(ResponseHead, BodyFraming)> {
+  if request_method == "HEAD" {
+    reader.decoder_mut().skip_body_of_next_message();
+  }
+
+  match reader.next_item()? {
+    Some(Item::Head { head, framing, .. }) => {
+      Ok((
+        ResponseHead {
+          status_code: head.status.as_u16(),
+          reason: head.reason,
+          version: minor_version(head.version),
+          headers: collect_headers(&head.headers),
+        },
+        framing,
+      ))
+    }
+    None => {
+      Err(io::ErrorKind::UnexpectedEof.into())
+    }
+    Some(_) => {
+      Err(invalid_data("expected a response head"))
+    }
+  }
+}
+
+/// Replaces the framing headers of a message whose body we rewrote.
+pub fn set_content_length(headers: &mut Headers, length: usize) {
+  headers.remove("Transfer-Encoding");
+  headers.insert("Content-Length", length.to_string());
+}
+
+// SECTION: Body reading.
+/// Yields the decoded bytes of the body of the message whose head was just
+/// read and stops at its end. Must be read to the end, even for messages
+/// without a body, before the next message can be read.
+pub struct BodyReader<'a, K: MessageKind, R> {
+  reader: &'a mut MessageReader<K, R>,
+  /// Bytes of the last body piece that didn't fit in the caller's buffer.
+  left_over: Vec<u8>,
+  left_over_start: usize,
+  is_finished: bool,
+}
+
+impl<'a, K: MessageKind, R: Read> BodyReader<'a, K, R> {
+  pub fn new(reader: &'a mut MessageReader<K, R>) -> Self {
+    Self {
+      reader,
+      left_over: Vec::new(),
+      left_over_start: 0,
+      is_finished: false,
+    }
+  }
+
+  /// The bytes that were taken from the message but not read yet. A reader
+  /// dropped before the end of the body must hand them over.
+  pub fn into_left_over(mut self) -> Vec<u8> {
+    self.left_over.split_off(self.left_over_start)
+  }
+}
+
+impl<'a, K: MessageKind, R: Read> Read for BodyReader<'a, K, R> {
+  fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+    if self.left_over_start < self.left_over.len() {
+      let left_over = &self.left_over[self.left_over_start..];
+      let length = left_over.len().min(buffer.len());
+      buffer[..length].copy_from_slice(&left_over[..length]);
+      self.left_over_start += length;
+      return Ok(length);
+    }
+
+    if self.is_finished || buffer.is_empty() {
+      return Ok(0);
+    }
+
+    match self.reader.next_item()? {
+      Some(Item::Body(bytes)) => {
+        let length = bytes.len().min(buffer.len());
+        buffer[..length].copy_from_slice(&bytes[..length]);
+        self.left_over.clear();
+        self.left_over.extend_from_slice(&bytes[length..]);
+        self.left_over_start = 0;
+        Ok(length)
+      }
+      Some(Item::MessageComplete { .. }) => {
+        self.is_finished = true;
+        Ok(0)
+      }
+      None => {
+        Err(io::ErrorKind::UnexpectedEof.into())
+      }
+      Some(_) => {
+        Err(invalid_data("expected a message body"))
+      }
+    }
+  }
+}
+
+pub enum ReadBody {
+  Complete(Vec<u8>),
+  /// The body is longer than the limit. Holds its first part and the rest is
+  /// still unread.
+  Truncated(Vec<u8>),
+}
+
+pub fn read_body(body: &mut impl Read, limit: usize) -> io::Result<ReadBody> {
+  let mut bytes = Vec::new();
+  body.by_ref().take(limit as u64 + 1).read_to_end(&mut bytes)?;
+
+  if bytes.len() > limit {
+    Ok(ReadBody::Truncated(bytes))
+  } else {
+    Ok(ReadBody::Complete(bytes))
+  }
+}
+
+/// Reads the body until `length` bytes were read or it ends.
+pub fn read_body_start(body: &mut impl Read, length: usize) -> io::Result<Vec<u8>> {
+  let mut bytes = Vec::new();
+  body.by_ref().take(length as u64).read_to_end(&mut bytes)?;
+  Ok(bytes)
+}
+
+// SECTION: Body writing.
+fn write_chunk(writer: &mut impl Write, data: &[u8]) -> io::Result<()> {
+  if data.is_empty() {
+    return Ok(());
+  }
+
+  write!(writer, "{:x}\r\n", data.len())?;
+  writer.write_all(data)?;
+  writer.write_all(b"\r\n")
+}
+
+/// Writes part of a body that was already read, framed as `framing`, which
+/// must be the framing declared in the head that was written to `writer`.
+/// The rest is written with `copy_body`.
+pub fn write_body_part(writer: &mut impl Write, data: &[u8], framing: BodyFraming) -> io::Result<()> {
+  match framing {
+    BodyFraming::None => Ok(()),
+    BodyFraming::ContentLength(_) | BodyFraming::UntilEof => writer.write_all(data),
+    BodyFraming::Chunked => write_chunk(writer, data),
+  }
+}
+
+/// Copies the rest of `body` to `writer` framed as `framing`, which must be
+/// the framing declared in the head that was written to `writer`.
+pub fn copy_body(
+  body: &mut impl Read,
+  writer: &mut impl Write,
+  framing: BodyFraming,
+) -> io::Result<()> {
+  let mut buffer = vec![0; COPY_BUFFER_LENGTH];
+
+  match framing {
+    BodyFraming::None => {
+      // There's nothing to copy, but the end of the message still has to be read.
+      io::copy(body, &mut io::sink())?;
+    }
+    BodyFraming::ContentLength(_) | BodyFraming::UntilEof => {
+      loop {
+        let read = body.read(&mut buffer)?;
+        if read == 0 {
+          break;
+        }
+        writer.write_all(&buffer[..read])?;
+      }
+    }
+    BodyFraming::Chunked => {
+      loop {
+        let read = body.read(&mut buffer)?;
+        if read == 0 {
+          break;
+        }
+        write_chunk(writer, &buffer[..read])?;
+        // Don't hold back streamed responses, like server-sent events.
+        writer.flush()?;
+      }
+      writer.write_all(b"0\r\n\r\n")?;
+    }
+  }
+
+  writer.flush()
+}
+
+/// Like `copy_body`, but passes the body through `rewrite` on its way. The
+/// end of the body is signaled to `rewrite` with `None`, so it can write
+/// what it held back.
+pub fn copy_rewritten_body(
+  body: &mut impl Read,
+  writer: &mut impl Write,
+  framing: BodyFraming,
+  mut rewrite: impl FnMut(Option<&[u8]>) -> Vec<u8>,
+) -> io::Result<()> {
+  if framing == BodyFraming::None {
+    return copy_body(body, writer, framing);
+  }
+
+  let mut buffer = vec![0; COPY_BUFFER_LENGTH];
+  loop {
+    let read = body.read(&mut buffer)?;
+    if read == 0 {
+      break;
+    }
+    write_body_part(writer, &rewrite(Some(&buffer[..read])), framing)?;
+    writer.flush()?;
+  }
+
+  write_body_part(writer, &rewrite(None), framing)?;
+  if framing == BodyFraming::Chunked {
+    writer.write_all(b"0\r\n\r\n")?;
+  }
+
+  writer.flush()
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,97 @@
+use std::io::{self, Read};
+use llhttp_rs::message::{RequestReader, ResponseReader};
+use super::*;
+
+/// Hands out its bytes a few at a time, like a slow connection.
+struct Trickle<'a>(&'a [u8]);
+
+impl Read for Trickle<'_> {
+  fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+    let length = self.0.len().min(buffer.len()).min(5);
+    buffer[..length].copy_from_slice(&self.0[..length]);
+    self.0 = &self.0[length..];
+    Ok(length)
+  }
+}
+
+fn read_to_end(body: &mut impl Read) -> Vec<u8> {
+  let mut bytes = Vec::new();
+  body.read_to_end(&mut bytes).unwrap();
+  bytes
+}
+
+#[test]
+fn reads_pipelined_requests() {
+  let mut reader = RequestReader::new(Trickle(
+    b"POST /upload?x=1 HTTP/1.1\r\n\
+      Host: example.com\r\n\
+      Transfer-Encoding: chunked\r\n\
+      X-Custom: a\r\n\
+      X-Custom: b\r\n\
+      \r\n\
+      5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n\
+      GET http://example.com/next HTTP/1.0\r\n\
+      \r\n",
+  ));
+
+  let (head, framing) = read_request_head(&mut reader).unwrap().unwrap();
+  assert_eq!(head.method, "POST");
+  assert_eq!(head.target, "/upload?x=1");
+  assert_eq!(head.version, 1);
+  assert_eq!(head.headers.get("host"), Some("example.com"));
+  assert_eq!(head.headers.get_all("x-custom").collect::<Vec<_>>(), vec!["a", "b"]);
+  assert_eq!(framing, BodyFraming::Chunked);
+  assert_eq!(read_to_end(&mut BodyReader::new(&mut reader)), b"hello world");
+
+  let (head, framing) = read_request_head(&mut reader).unwrap().unwrap();
+  assert_eq!(head.method, "GET");
+  assert_eq!(head.target, "http://example.com/next");
+  assert_eq!(head.version, 0);
+  assert_eq!(framing, BodyFraming::None);
+  assert!(read_to_end(&mut BodyReader::new(&mut reader)).is_empty());
+
+  assert!(read_request_head(&mut reader).unwrap().is_none());
+}
+
+#[test]
+fn rejects_malformed_requests() {
+  let mut reader = RequestReader::new(&b"GET / HTTP/1.1\r\nBad Header\r\n\r\n"[..]);
+  let error = read_request_head(&mut reader).unwrap_err();
+  assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+  let mut reader = RequestReader::new(&b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort"[..]);
+  read_request_head(&mut reader).unwrap().unwrap();
+  assert!(BodyReader::new(&mut reader).read_to_end(&mut Vec::new()).is_err());
+}
+
+#[test]
+fn skips_bodies_of_responses_to_head_requests() {
+  let mut reader = ResponseReader::new(
+    &b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nHTTP/1.1 204 No Content\r\n\r\n"[..],
+  );
+
+  let (head, framing) = read_response_head(&mut reader, "HEAD").unwrap();
+  assert_eq!(head.status_code, 200);
+  assert_eq!(framing, BodyFraming::None);
+  assert!(read_to_end(&mut BodyReader::new(&mut reader)).is_empty());
+
+  let (head, _) = read_response_head(&mut reader, "GET").unwrap();
+  assert_eq!(head.status_code, 204);
+  assert_eq!(head.reason, "No Content");
+}
+
+#[test]
+fn copies_bodies_in_their_framing() {
+  let mut reader = ResponseReader::new(
+    &b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n"[..],
+  );
+  let (_, framing) = read_response_head(&mut reader, "GET").unwrap();
+
+  let mut written = Vec::new();
+  copy_rewritten_body(&mut BodyReader::new(&mut reader), &mut written, framing, |data| {
+    data.map(|data| data.to_ascii_uppercase()).unwrap_or_default()
+  })
+  .unwrap();
+
+  assert_eq!(written, b"3\r\nABC\r\n2\r\nDE\r\n0\r\n\r\n");
+}
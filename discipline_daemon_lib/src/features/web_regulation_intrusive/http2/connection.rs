@@ -0,0 +1,816 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use super::duplex::Duplex;
+use super::frames::*;
+
+/// A header field of an http/2 message, pseudo-header fields like `:path`
+/// included. Names are lowercase.
+pub type Field = (String, String);
+
+/// How many streams we let the peer have open at once.
+const MAXIMUM_CONCURRENT_STREAMS: u32 = 100;
+/// How much of a stream's body the peer may send before we read it.
+const STREAM_WINDOW_SIZE: u32 = 256 * 1024;
+/// How much the peer may send on all streams together before we take it in.
+const CONNECTION_WINDOW_SIZE: u32 = 16 * 1024 * 1024;
+/// The dynamic table size our header compression needs the peer to allow.
+const ENCODER_TABLE_SIZE: u32 = 4096;
+/// Header blocks longer than this end the connection.
+const MAXIMUM_HEADER_BLOCK_LENGTH: usize = 256 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+  Client,
+  Server,
+}
+
+/// An http/2 connection whose streams can be used from different threads.
+///
+/// A thread of its own reads what the peer sends and hands it to the
+/// streams. Received data is buffered up to the flow control window we
+/// give the peer, and data we send waits for the window it gives us.
+pub struct Connection {
+  role: Role,
+  shared: Mutex<Shared>,
+  changed: Condvar,
+  writer: Mutex<Writer>,
+  tcp_stream: TcpStream,
+}
+
+struct Shared {
+  streams: HashMap<u32, StreamState>,
+  /// Streams the peer opened that weren't accepted yet.
+  opened: VecDeque<u32>,
+  last_opened_stream_id: u32,
+  /// How much data we may still send on the connection.
+  send_window: i64,
+  /// The window the peer gives each new stream.
+  initial_send_window: i64,
+  peer_maximum_frame_size: u32,
+  peer_maximum_concurrent_streams: u32,
+  /// The peer won't accept new streams.
+  is_going_away: bool,
+  /// Nothing can be sent or received anymore.
+  is_closed: bool,
+}
+
+#[derive(Default)]
+struct StreamState {
+  head: Option<Vec<Field>>,
+  is_head_received: bool,
+  /// Whether the head ended the message, meaning it has no body.
+  is_head_final: bool,
+  trailers: Option<Vec<Field>>,
+  data: VecDeque<u8>,
+  /// Data read but not yet given back to the stream window.
+  unacknowledged: u32,
+  send_window: i64,
+  is_remote_closed: bool,
+  is_local_closed: bool,
+  /// The error code the stream was reset with, by either side.
+  reset: Option<u32>,
+}
+
+struct Writer {
+  writer: Box<dyn Write + Send>,
+  /// Compresses header fields, unless the peer's dynamic table is too small
+  /// for it, in which case fields are sent as literals.
+  encoder: Option<hpack::Encoder<'static>>,
+  is_table_size_update_pending: bool,
+  next_stream_id: u32,
+}
+
+impl Writer {
+  fn write_frame(&mut self, kind: u8, flags: u8, stream_id: u32, payload: &[u8]) -> io::Result<()> {
+    write_frame(&mut self.writer, kind, flags, stream_id, payload)?;
+    self.writer.flush()
+  }
+
+  fn encode(&mut self, fields: &[Field]) -> Vec<u8> {
+    let mut block = Vec::new();
+    if self.is_table_size_update_pending {
+      // A dynamic table size update to zero.
+      block.push(0x20);
+      self.is_table_size_update_pending = false;
+    }
+
+    match &mut self.encoder {
+      Some(encoder) => {
+        let fields: Vec<(Vec<u8>, Vec<u8>)> = fields
+          .iter()
+          .map(|(name, value)| (name.to_ascii_lowercase().into_bytes(), value.clone().into_bytes()))
+          .collect();
+        block.extend_from_slice(&encoder.encode(&fields));
+      }
+      None => {
+        for (name, value) in fields {
+          // A literal field without indexing, with a literal name.
+          block.push(0);
+          encode_string(name.to_ascii_lowercase().as_bytes(), &mut block);
+          encode_string(value.as_bytes(), &mut block);
+        }
+      }
+    }
+
+    block
+  }
+
+  fn write_header_block(&mut self, stream_id: u32, block: &[u8], end_stream: bool, maximum_frame_size: u32) -> io::Result<()> {
+    let mut fragments = block.chunks(maximum_frame_size as usize).peekable();
+    let mut kind = HEADERS;
+    let mut flags = if end_stream { END_STREAM } else { 0 };
+
+    loop {
+      let fragment = fragments.next().unwrap_or_default();
+      if fragments.peek().is_none() {
+        flags |= END_HEADERS;
+      }
+      write_frame(&mut self.writer, kind, flags, stream_id, fragment)?;
+      if flags & END_HEADERS != 0 {
+        break;
+      }
+      kind = CONTINUATION;
+      flags = 0;
+    }
+
+    self.writer.flush()
+  }
+}
+
+/// Encodes a string literal without huffman coding.
+fn encode_string(string: &[u8], into: &mut Vec<u8>) {
+  encode_integer(string.len(), 7, into);
+  into.extend_from_slice(string);
+}
+
+fn encode_integer(mut value: usize, prefix_bits: u8, into: &mut Vec<u8>) {
+  let prefix_maximum = (1 << prefix_bits) - 1;
+  if value < prefix_maximum {
+    into.push(value as u8);
+    return;
+  }
+
+  into.push(prefix_maximum as u8);
+  value -= prefix_maximum;
+  while value >= 128 {
+    into.push((value % 128 + 128) as u8);
+    value /= 128;
+  }
+  into.push(value as u8);
+}
+
+fn closed_error() -> io::Error {
+  io::Error::new(ErrorKind::ConnectionAborted, "the http/2 connection is closed")
+}
+
+fn reset_error(code: u32) -> io::Error {
+  io::Error::new(ErrorKind::ConnectionReset, format!("the http/2 stream was reset with error {code}"))
+}
+
+impl Connection {
+  /// Starts an http/2 connection to a server. Streams are opened with
+  /// `open_stream`.
+  pub fn client(duplex: Duplex) -> io::Result<Arc<Connection>> {
+    Self::start(Role::Client, duplex)
+  }
+
+  /// Starts serving an http/2 connection of a client. The streams it opens
+  /// are returned by `accept`.
+  pub fn server(duplex: Duplex) -> io::Result<Arc<Connection>> {
+    Self::start(Role::Server, duplex)
+  }
+
+  fn start(role: Role, duplex: Duplex) -> io::Result<Arc<Connection>> {
+    let Duplex { reader, mut writer, tcp_stream } = duplex;
+
+    if role == Role::Client {
+      writer.write_all(PREFACE)?;
+    }
+
+    let mut settings = vec![
+      (MAX_CONCURRENT_STREAMS, MAXIMUM_CONCURRENT_STREAMS),
+      (INITIAL_WINDOW_SIZE, STREAM_WINDOW_SIZE),
+    ];
+    if role == Role::Client {
+      settings.push((ENABLE_PUSH, 0));
+    }
+
+    write_frame(&mut writer, SETTINGS, 0, 0, &settings_payload(&settings))?;
+    write_frame(
+      &mut writer,
+      WINDOW_UPDATE,
+      0,
+      0,
+      &(CONNECTION_WINDOW_SIZE - DEFAULT_WINDOW_SIZE).to_be_bytes(),
+    )?;
+    writer.flush()?;
+
+    let connection = Arc::new(Connection {
+      role,
+      shared: Mutex::new(Shared {
+        streams: HashMap::new(),
+        opened: VecDeque::new(),
+        last_opened_stream_id: 0,
+        send_window: DEFAULT_WINDOW_SIZE as i64,
+        initial_send_window: DEFAULT_WINDOW_SIZE as i64,
+        peer_maximum_frame_size: DEFAULT_MAXIMUM_FRAME_SIZE,
+        peer_maximum_concurrent_streams: u32::MAX,
+        is_going_away: false,
+        is_closed: false,
+      }),
+      changed: Condvar::new(),
+      writer: Mutex::new(Writer {
+        writer,
+        encoder: Some(hpack::Encoder::new()),
+        is_table_size_update_pending: false,
+        next_stream_id: 1,
+      }),
+      tcp_stream,
+    });
+
+    let reading_connection = Arc::clone(&connection);
+    thread::spawn(move || {
+      let result = reading_connection.read_frames(reader);
+      reading_connection.end(result.err());
+    });
+
+    Ok(connection)
+  }
+
+  fn shared(&self) -> MutexGuard<'_, Shared> {
+    self.shared.lock().unwrap()
+  }
+
+  fn writer(&self) -> MutexGuard<'_, Writer> {
+    self.writer.lock().unwrap()
+  }
+
+  /// Whether new streams can be opened.
+  pub fn is_accepting_streams(&self) -> bool {
+    let shared = self.shared();
+    !shared.is_closed && !shared.is_going_away
+  }
+
+  /// Waits for the peer to open a stream. Returns `None` once the connection
+  /// is closed.
+  pub fn accept(self: &Arc<Self>) -> Option<Stream> {
+    let mut shared = self.shared();
+    loop {
+      if let Some(id) = shared.opened.pop_front() {
+        return Some(Stream::new(Arc::clone(self), id));
+      }
+      if shared.is_closed {
+        return None;
+      }
+      shared = self.changed.wait(shared).unwrap();
+    }
+  }
+
+  /// Opens a stream by sending the head of a request. `end_stream` tells
+  /// whether the request has no body.
+  pub fn open_stream(self: &Arc<Self>, fields: &[Field], end_stream: bool) -> io::Result<Stream> {
+    let maximum_frame_size = {
+      let mut shared = self.shared();
+      loop {
+        if shared.is_closed || shared.is_going_away {
+          return Err(closed_error());
+        }
+
+        let open_streams = shared.streams.keys().filter(|id| *id % 2 == 1).count();
+        if open_streams < shared.peer_maximum_concurrent_streams as usize {
+          break;
+        }
+
+        shared = self.changed.wait(shared).unwrap();
+      }
+      shared.peer_maximum_frame_size
+    };
+
+    // Stream ids have to increase in the order streams are opened, so the
+    // id is picked while holding the writer.
+    let mut writer = self.writer();
+    let id = writer.next_stream_id;
+    writer.next_stream_id += 2;
+
+    {
+      let mut shared = self.shared();
+      let send_window = shared.initial_send_window;
+      shared.streams.insert(id, StreamState {
+        send_window,
+        is_local_closed: end_stream,
+        ..StreamState::default()
+      });
+    }
+
+    // The stream resets itself if dropped on error, which needs the writer.
+    let stream = Stream::new(Arc::clone(self), id);
+    let block = writer.encode(fields);
+    let result = writer.write_header_block(id, &block, end_stream, maximum_frame_size);
+    drop(writer);
+    result.map(|()| stream)
+  }
+
+  /// Tells the peer we're done and closes the connection.
+  pub fn close(&self) {
+    let last_opened_stream_id = {
+      let mut shared = self.shared();
+      if shared.is_closed {
+        return;
+      }
+      shared.is_closed = true;
+      self.changed.notify_all();
+      shared.last_opened_stream_id
+    };
+
+    let mut payload = last_opened_stream_id.to_be_bytes().to_vec();
+    payload.extend_from_slice(&NO_ERROR.to_be_bytes());
+    let _ = self.writer().write_frame(GOAWAY, 0, 0, &payload);
+    let _ = self.tcp_stream.shutdown(Shutdown::Both);
+  }
+
+  /// Marks the connection closed after it stopped being read, telling the
+  /// peer why if it broke the protocol.
+  fn end(&self, error: Option<io::Error>) {
+    let last_opened_stream_id = {
+      let mut shared = self.shared();
+      shared.is_closed = true;
+      self.changed.notify_all();
+      shared.last_opened_stream_id
+    };
+
+    let code = error
+      .as_ref()
+      .and_then(|error| error.get_ref())
+      .and_then(|error| error.downcast_ref::<ConnectionError>())
+      .map(|error| error.code);
+
+    if let Some(code) = code {
+      let mut payload = last_opened_stream_id.to_be_bytes().to_vec();
+      payload.extend_from_slice(&code.to_be_bytes());
+      let _ = self.writer().write_frame(GOAWAY, 0, 0, &payload);
+    }
+
+    let _ = self.tcp_stream.shutdown(Shutdown::Both);
+  }
+
+  // SECTION: Reading.
+  fn read_frames(&self, mut reader: Box<dyn Read + Send>) -> io::Result<()> {
+    if self.role == Role::Server {
+      let mut preface = [0; PREFACE.len()];
+      reader.read_exact(&mut preface)?;
+      if preface != PREFACE {
+        return Err(connection_error(PROTOCOL_ERROR, "invalid connection preface"));
+      }
+    }
+
+    let mut decoder = hpack::Decoder::new();
+    // Data taken in but not yet given back to the connection window.
+    let mut unacknowledged = 0;
+
+    while let Some(frame) = read_frame(&mut reader, DEFAULT_MAXIMUM_FRAME_SIZE)? {
+      match frame.kind {
+        DATA => {
+          unacknowledged += frame.payload.len() as u32;
+          if unacknowledged >= CONNECTION_WINDOW_SIZE / 2 {
+            self.writer().write_frame(WINDOW_UPDATE, 0, 0, &unacknowledged.to_be_bytes())?;
+            unacknowledged = 0;
+          }
+
+          self.on_data(&frame)?;
+        }
+        HEADERS => {
+          let mut block = header_block_fragment(&frame)?.to_vec();
+          let mut is_complete = frame.has_flag(END_HEADERS);
+          while !is_complete {
+            let Some(continuation) = read_frame(&mut reader, DEFAULT_MAXIMUM_FRAME_SIZE)? else {
+              return Err(ErrorKind::UnexpectedEof.into());
+            };
+            if continuation.kind != CONTINUATION || continuation.stream_id != frame.stream_id {
+              return Err(connection_error(PROTOCOL_ERROR, "header block interrupted"));
+            }
+            block.extend_from_slice(&continuation.payload);
+            if block.len() > MAXIMUM_HEADER_BLOCK_LENGTH {
+              return Err(connection_error(PROTOCOL_ERROR, "header block too long"));
+            }
+            is_complete = continuation.has_flag(END_HEADERS);
+          }
+
+          // Decoding must happen even for streams we forgot about, to keep
+          // the decoder's table in sync with the peer's.
+          let fields = decoder
+            .decode(&block)
+            .map_err(|_| connection_error(COMPRESSION_ERROR, "undecodable header block"))?
+            .into_iter()
+            .map(|(name, value)| (
+              String::from_utf8_lossy(&name).into_owned(),
+              String::from_utf8_lossy(&value).into_owned(),
+            ))
+            .collect();
+
+          self.on_headers(frame.stream_id, fields, frame.has_flag(END_STREAM))?;
+        }
+        RST_STREAM => {
+          let code = read_u32(&frame.payload)?;
+          let mut shared = self.shared();
+          if let Some(stream) = shared.streams.get_mut(&frame.stream_id) {
+            stream.reset = Some(code);
+            self.changed.notify_all();
+          }
+        }
+        SETTINGS if !frame.has_flag(ACK) => {
+          self.on_settings(&parse_settings(&frame.payload)?)?;
+        }
+        PING if !frame.has_flag(ACK) => {
+          self.writer().write_frame(PING, ACK, 0, &frame.payload)?;
+        }
+        GOAWAY => {
+          let last_stream_id = read_u32(&frame.payload)? & MAXIMUM_WINDOW_SIZE;
+          let mut shared = self.shared();
+          shared.is_going_away = true;
+          // Streams we opened past the last one the peer processed never
+          // will be.
+          for (id, stream) in &mut shared.streams {
+            if *id > last_stream_id && (*id % 2 == 1) == (self.role == Role::Client) {
+              stream.reset = Some(REFUSED_STREAM);
+            }
+          }
+          self.changed.notify_all();
+        }
+        WINDOW_UPDATE => {
+          let increment = (read_u32(&frame.payload)? & MAXIMUM_WINDOW_SIZE) as i64;
+          let mut shared = self.shared();
+          if frame.stream_id == 0 {
+            shared.send_window += increment;
+            if shared.send_window > MAXIMUM_WINDOW_SIZE as i64 {
+              return Err(connection_error(FLOW_CONTROL_ERROR, "connection window overflow"));
+            }
+          } else if let Some(stream) = shared.streams.get_mut(&frame.stream_id) {
+            stream.send_window += increment;
+          }
+          self.changed.notify_all();
+        }
+        PUSH_PROMISE => {
+          return Err(connection_error(PROTOCOL_ERROR, "server push is disabled"));
+        }
+        CONTINUATION => {
+          return Err(connection_error(PROTOCOL_ERROR, "CONTINUATION without a header block"));
+        }
+        _ => {
+          // Acknowledgements, PRIORITY and unknown frame types don't need
+          // any action.
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  fn on_data(&self, frame: &Frame) -> io::Result<()> {
+    let data = unpadded(frame)?;
+
+    {
+      let mut shared = self.shared();
+      let Some(stream) = shared.streams.get_mut(&frame.stream_id) else {
+        return Ok(());
+      };
+
+      if stream.is_remote_closed || stream.reset.is_some() {
+        return Ok(());
+      }
+
+      stream.data.extend(data);
+      stream.is_remote_closed = frame.has_flag(END_STREAM);
+      self.changed.notify_all();
+    }
+
+    // Padding is never read, so its share of the stream window is given
+    // back right away.
+    let padding = (frame.payload.len() - data.len()) as u32;
+    if padding > 0 && !frame.has_flag(END_STREAM) {
+      self.writer().write_frame(WINDOW_UPDATE, 0, frame.stream_id, &padding.to_be_bytes())?;
+    }
+
+    Ok(())
+  }
+
+  fn on_headers(&self, stream_id: u32, fields: Vec<Field>, end_stream: bool) -> io::Result<()> {
+    let mut shared = self.shared();
+
+    if let Some(stream) = shared.streams.get_mut(&stream_id) {
+      if stream.is_remote_closed || stream.reset.is_some() {
+        return Ok(());
+      }
+
+      let is_informational = fields
+        .iter()
+        .any(|(name, value)| name == ":status" && value.starts_with('1'));
+
+      if is_informational {
+        return Ok(());
+      }
+
+      if stream.is_head_received {
+        stream.trailers = Some(fields);
+      } else {
+        stream.head = Some(fields);
+        stream.is_head_received = true;
+        stream.is_head_final = end_stream;
+      }
+
+      stream.is_remote_closed = end_stream;
+      self.changed.notify_all();
+      return Ok(());
+    }
+
+    let is_new = self.role == Role::Server
+      && stream_id % 2 == 1
+      && stream_id > shared.last_opened_stream_id;
+
+    if !is_new {
+      // The stream was dropped.
+      return Ok(());
+    }
+
+    shared.last_opened_stream_id = stream_id;
+    let send_window = shared.initial_send_window;
+    shared.streams.insert(stream_id, StreamState {
+      head: Some(fields),
+      is_head_received: true,
+      is_head_final: end_stream,
+      send_window,
+      is_remote_closed: end_stream,
+      ..StreamState::default()
+    });
+    shared.opened.push_back(stream_id);
+    self.changed.notify_all();
+    Ok(())
+  }
+
+  fn on_settings(&self, settings: &[(u16, u32)]) -> io::Result<()> {
+    {
+      let mut shared = self.shared();
+      for (identifier, value) in settings {
+        match *identifier {
+          INITIAL_WINDOW_SIZE => {
+            if *value > MAXIMUM_WINDOW_SIZE {
+              return Err(connection_error(FLOW_CONTROL_ERROR, "initial window size too large"));
+            }
+            let change = *value as i64 - shared.initial_send_window;
+            shared.initial_send_window = *value as i64;
+            for stream in shared.streams.values_mut() {
+              stream.send_window += change;
+            }
+          }
+          MAX_FRAME_SIZE => {
+            if !(DEFAULT_MAXIMUM_FRAME_SIZE..=(1 << 24) - 1).contains(value) {
+              return Err(connection_error(PROTOCOL_ERROR, "invalid maximum frame size"));
+            }
+            shared.peer_maximum_frame_size = *value;
+          }
+          MAX_CONCURRENT_STREAMS => {
+            shared.peer_maximum_concurrent_streams = *value;
+          }
+          _ => {}
+        }
+      }
+      self.changed.notify_all();
+    }
+
+    let mut writer = self.writer();
+    let table_size = settings
+      .iter()
+      .rev()
+      .find(|(identifier, _)| *identifier == HEADER_TABLE_SIZE)
+      .map(|(_, value)| *value);
+
+    if table_size.is_some_and(|size| size < ENCODER_TABLE_SIZE) && writer.encoder.is_some() {
+      writer.encoder = None;
+      writer.is_table_size_update_pending = true;
+    }
+
+    writer.write_frame(SETTINGS, ACK, 0, &[])
+  }
+}
+
+// SECTION: Streams.
+/// One request and its response on a connection. Reading it yields the body
+/// of the message the peer sends, and can happen on one thread while
+/// another sends ours. Dropping it before both sides finished resets it.
+pub struct Stream {
+  connection: Arc<Connection>,
+  id: u32,
+}
+
+impl Stream {
+  fn new(connection: Arc<Connection>, id: u32) -> Self {
+    Self {
+      connection,
+      id,
+    }
+  }
+
+  pub fn id(&self) -> u32 {
+    self.id
+  }
+
+  /// Waits for the head of the message the peer sends. Returns its fields
+  /// and whether a body follows. Can only be called once.
+  pub fn read_head(&self) -> io::Result<(Vec<Field>, bool)> {
+    let mut shared = self.connection.shared();
+    loop {
+      let stream = shared.streams.get_mut(&self.id).ok_or_else(closed_error)?;
+      if let Some(head) = stream.head.take() {
+        return Ok((head, !stream.is_head_final));
+      }
+      if let Some(code) = stream.reset {
+        return Err(reset_error(code));
+      }
+      if shared.is_closed {
+        return Err(closed_error());
+      }
+      shared = self.connection.changed.wait(shared).unwrap();
+    }
+  }
+
+  /// The trailer fields the peer sent after the body, once it was read.
+  pub fn take_trailers(&self) -> Option<Vec<Field>> {
+    self
+      .connection
+      .shared()
+      .streams
+      .get_mut(&self.id)
+      .and_then(|stream| stream.trailers.take())
+  }
+
+  fn check_sendable(&self) -> io::Result<u32> {
+    let shared = self.connection.shared();
+    let stream = shared.streams.get(&self.id).ok_or_else(closed_error)?;
+    if let Some(code) = stream.reset {
+      return Err(reset_error(code));
+    }
+    if shared.is_closed || stream.is_local_closed {
+      return Err(closed_error());
+    }
+    Ok(shared.peer_maximum_frame_size)
+  }
+
+  fn close_locally(&self) {
+    if let Some(stream) = self.connection.shared().streams.get_mut(&self.id) {
+      stream.is_local_closed = true;
+    }
+  }
+
+  /// Sends the head of our message, or the trailers after its body.
+  /// `end_stream` tells whether nothing follows.
+  pub fn send_head(&self, fields: &[Field], end_stream: bool) -> io::Result<()> {
+    let maximum_frame_size = self.check_sendable()?;
+
+    let mut writer = self.connection.writer();
+    let block = writer.encode(fields);
+    writer.write_header_block(self.id, &block, end_stream, maximum_frame_size)?;
+    drop(writer);
+
+    if end_stream {
+      self.close_locally();
+    }
+    Ok(())
+  }
+
+  /// Sends part of the body of our message, waiting for the peer to make
+  /// room for it.
+  pub fn send_data(&self, mut data: &[u8], end_stream: bool) -> io::Result<()> {
+    if data.is_empty() && !end_stream {
+      return Ok(());
+    }
+
+    loop {
+      let length = {
+        let mut shared = self.connection.shared();
+        loop {
+          if shared.is_closed {
+            return Err(closed_error());
+          }
+          let connection_window = shared.send_window;
+          let maximum_frame_size = shared.peer_maximum_frame_size as i64;
+          let stream = shared.streams.get_mut(&self.id).ok_or_else(closed_error)?;
+          if let Some(code) = stream.reset {
+            return Err(reset_error(code));
+          }
+          if stream.is_local_closed {
+            return Err(closed_error());
+          }
+
+          let available = connection_window
+            .min(stream.send_window)
+            .min(maximum_frame_size)
+            .min(data.len() as i64);
+
+          if available > 0 || data.is_empty() {
+            let available = available.max(0);
+            stream.send_window -= available;
+            shared.send_window -= available;
+            break available as usize;
+          }
+
+          shared = self.connection.changed.wait(shared).unwrap();
+        }
+      };
+
+      let (chunk, rest) = data.split_at(length);
+      data = rest;
+      let is_last = end_stream && data.is_empty();
+
+      self.connection.writer().write_frame(DATA, if is_last { END_STREAM } else { 0 }, self.id, chunk)?;
+
+      if data.is_empty() {
+        if is_last {
+          self.close_locally();
+        }
+        return Ok(());
+      }
+    }
+  }
+
+  /// Ends our message after its body.
+  pub fn finish(&self) -> io::Result<()> {
+    self.send_data(&[], true)
+  }
+}
+
+impl Read for &Stream {
+  fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+    if buffer.is_empty() {
+      return Ok(0);
+    }
+
+    let increment = {
+      let mut shared = self.connection.shared();
+      loop {
+        let is_closed = shared.is_closed;
+        let stream = shared.streams.get_mut(&self.id).ok_or_else(closed_error)?;
+        if !stream.data.is_empty() {
+          let length = stream.data.len().min(buffer.len());
+          for (to, from) in buffer.iter_mut().zip(stream.data.drain(..length)) {
+            *to = from;
+          }
+
+          stream.unacknowledged += length as u32;
+          if !stream.is_remote_closed && stream.unacknowledged >= STREAM_WINDOW_SIZE / 2 {
+            break (length, std::mem::take(&mut stream.unacknowledged));
+          }
+          break (length, 0);
+        }
+        if let Some(code) = stream.reset {
+          return Err(reset_error(code));
+        }
+        if stream.is_remote_closed {
+          return Ok(0);
+        }
+        if is_closed {
+          return Err(closed_error());
+        }
+        shared = self.connection.changed.wait(shared).unwrap();
+      }
+    };
+
+    let (read, increment) = increment;
+    if increment > 0 {
+      self.connection.writer().write_frame(WINDOW_UPDATE, 0, self.id, &increment.to_be_bytes())?;
+    }
+
+    Ok(read)
+  }
+}
+
+impl Read for Stream {
+  fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+    (&*self).read(buffer)
+  }
+}
+
+impl Drop for Stream {
+  fn drop(&mut self) {
+    let is_reset_needed = {
+      let mut shared = self.connection.shared();
+      let is_closed = shared.is_closed;
+      let state = shared.streams.remove(&self.id);
+      // Someone may be waiting for a stream slot.
+      self.connection.changed.notify_all();
+
+      state.filter(|state| {
+        !is_closed
+          && state.reset.is_none()
+          && !(state.is_local_closed && state.is_remote_closed)
+      })
+    };
+
+    if let Some(state) = is_reset_needed {
+      // A complete message doesn't need the rest of the peer's.
+      let code = if state.is_local_closed { NO_ERROR } else { CANCEL };
+      let _ = self.connection.writer().write_frame(RST_STREAM, 0, self.id, &code.to_be_bytes());
+    }
+  }
+}
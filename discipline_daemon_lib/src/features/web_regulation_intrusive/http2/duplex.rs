@@ -0,0 +1,116 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+
+const TLS_READ_BUFFER_LENGTH: usize = 16 * 1024;
+
+/// A connection split in a reading end and a writing end, so one thread can
+/// wait for what the peer sends while others write to it.
+pub struct Duplex {
+  pub reader: Box<dyn Read + Send>,
+  pub writer: Box<dyn Write + Send>,
+  /// The underlying tcp connection, to shut both ends down.
+  pub tcp_stream: TcpStream,
+}
+
+impl Duplex {
+  pub fn tcp(stream: TcpStream) -> io::Result<Self> {
+    Ok(Self {
+      reader: Box::new(stream.try_clone()?),
+      writer: Box::new(stream.try_clone()?),
+      tcp_stream: stream,
+    })
+  }
+
+  /// Splits a tls session whose handshake is complete.
+  pub fn tls(connection: impl Into<rustls::Connection>, stream: TcpStream) -> io::Result<Self> {
+    let session = Arc::new(Mutex::new(TlsSession {
+      connection: connection.into(),
+      stream: stream.try_clone()?,
+    }));
+
+    Ok(Self {
+      reader: Box::new(TlsReader {
+        session: Arc::clone(&session),
+        stream: stream.try_clone()?,
+        buffer: vec![0; TLS_READ_BUFFER_LENGTH],
+      }),
+      writer: Box::new(TlsWriter { session }),
+      tcp_stream: stream,
+    })
+  }
+}
+
+/// A tls session shared by both ends. Its lock is never held while waiting
+/// for the peer to send something, only while writing to it.
+struct TlsSession {
+  connection: rustls::Connection,
+  stream: TcpStream,
+}
+
+impl TlsSession {
+  fn write_tls(&mut self) -> io::Result<()> {
+    while self.connection.wants_write() {
+      self.connection.write_tls(&mut self.stream)?;
+    }
+    Ok(())
+  }
+}
+
+struct TlsReader {
+  session: Arc<Mutex<TlsSession>>,
+  stream: TcpStream,
+  buffer: Vec<u8>,
+}
+
+impl Read for TlsReader {
+  fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+    loop {
+      match self.session.lock().unwrap().connection.reader().read(buffer) {
+        Err(error) if error.kind() == ErrorKind::WouldBlock => {}
+        result => {
+          return result;
+        }
+      }
+
+      let read = self.stream.read(&mut self.buffer)?;
+
+      let mut session = self.session.lock().unwrap();
+      let mut received = &self.buffer[..read];
+      loop {
+        // Nothing received tells the session the peer closed the connection.
+        session.connection.read_tls(&mut received)?;
+        if let Err(error) = session.connection.process_new_packets() {
+          // Let the peer know what went wrong.
+          let _ = session.write_tls();
+          return Err(io::Error::new(ErrorKind::InvalidData, error));
+        }
+        if received.is_empty() {
+          break;
+        }
+      }
+
+      // Answers to what was received, like key updates.
+      session.write_tls()?;
+    }
+  }
+}
+
+struct TlsWriter {
+  session: Arc<Mutex<TlsSession>>,
+}
+
+impl Write for TlsWriter {
+  fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+    let mut session = self.session.lock().unwrap();
+    let written = session.connection.writer().write(data)?;
+    session.write_tls()?;
+    Ok(written)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    let mut session = self.session.lock().unwrap();
+    session.connection.writer().flush()?;
+    session.write_tls()
+  }
+}
@@ -0,0 +1,192 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+/// What a client sends before anything else on an http/2 connection.
+pub const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+pub const FRAME_HEADER_LENGTH: usize = 9;
+/// The largest frame payload either side may send unless told otherwise.
+pub const DEFAULT_MAXIMUM_FRAME_SIZE: u32 = 16 * 1024;
+/// The flow control window of new streams and connections.
+pub const DEFAULT_WINDOW_SIZE: u32 = 65_535;
+pub const MAXIMUM_WINDOW_SIZE: u32 = (1 << 31) - 1;
+
+// SECTION: Frame types.
+pub const DATA: u8 = 0x0;
+pub const HEADERS: u8 = 0x1;
+pub const RST_STREAM: u8 = 0x3;
+pub const SETTINGS: u8 = 0x4;
+pub const PUSH_PROMISE: u8 = 0x5;
+pub const PING: u8 = 0x6;
+pub const GOAWAY: u8 = 0x7;
+pub const WINDOW_UPDATE: u8 = 0x8;
+pub const CONTINUATION: u8 = 0x9;
+
+// SECTION: Frame flags.
+pub const END_STREAM: u8 = 0x1;
+pub const ACK: u8 = 0x1;
+pub const END_HEADERS: u8 = 0x4;
+pub const PADDED: u8 = 0x8;
+pub const PRIORITY_FLAG: u8 = 0x20;
+
+// SECTION: Settings.
+pub const HEADER_TABLE_SIZE: u16 = 0x1;
+pub const ENABLE_PUSH: u16 = 0x2;
+pub const MAX_CONCURRENT_STREAMS: u16 = 0x3;
+pub const INITIAL_WINDOW_SIZE: u16 = 0x4;
+pub const MAX_FRAME_SIZE: u16 = 0x5;
+
+// SECTION: Error codes.
+pub const NO_ERROR: u32 = 0x0;
+pub const PROTOCOL_ERROR: u32 = 0x1;
+pub const FLOW_CONTROL_ERROR: u32 = 0x3;
+pub const FRAME_SIZE_ERROR: u32 = 0x6;
+pub const REFUSED_STREAM: u32 = 0x7;
+pub const CANCEL: u32 = 0x8;
+pub const COMPRESSION_ERROR: u32 = 0x9;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+  pub kind: u8,
+  pub flags: u8,
+  pub stream_id: u32,
+  pub payload: Vec<u8>,
+}
+
+impl Frame {
+  pub fn has_flag(&self, flag: u8) -> bool {
+    self.flags & flag != 0
+  }
+}
+
+/// The connection is unusable because the peer broke the protocol. The
+/// error code is sent to the peer in a GOAWAY frame.
+#[derive(Debug)]
+pub struct ConnectionError {
+  pub code: u32,
+  pub message: &'static str,
+}
+
+impl std::fmt::Display for ConnectionError {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(formatter, "http/2 connection error {}: {}", self.code, self.message)
+  }
+}
+
+impl std::error::Error for ConnectionError {}
+
+pub fn connection_error(code: u32, message: &'static str) -> io::Error {
+  io::Error::new(ErrorKind::InvalidData, ConnectionError { code, message })
+}
+
+/// Reads the next frame. Returns `None` if the connection was closed
+/// between frames.
+pub fn read_frame(reader: &mut impl Read, maximum_frame_size: u32) -> io::Result<Option<Frame>> {
+  let mut header = [0; FRAME_HEADER_LENGTH];
+  let mut filled = 0;
+  while filled < header.len() {
+    match reader.read(&mut header[filled..]) {
+      Ok(0) if filled == 0 => {
+        return Ok(None);
+      }
+      Ok(0) => {
+        return Err(ErrorKind::UnexpectedEof.into());
+      }
+      Ok(read) => {
+        filled += read;
+      }
+      Err(error) if error.kind() == ErrorKind::Interrupted => {}
+      Err(error) => {
+        return Err(error);
+      }
+    }
+  }
+
+  let length = u32::from_be_bytes([0, header[0], header[1], header[2]]);
+  if length > maximum_frame_size {
+    return Err(connection_error(FRAME_SIZE_ERROR, "frame larger than the maximum frame size"));
+  }
+
+  let mut payload = vec![0; length as usize];
+  reader.read_exact(&mut payload)?;
+
+  Ok(Some(Frame {
+    kind: header[3],
+    flags: header[4],
+    stream_id: u32::from_be_bytes([header[5], header[6], header[7], header[8]]) & MAXIMUM_WINDOW_SIZE,
+    payload,
+  }))
+}
+
+pub fn write_frame(writer: &mut impl Write, kind: u8, flags: u8, stream_id: u32, payload: &[u8]) -> io::Result<()> {
+  let mut frame = Vec::with_capacity(FRAME_HEADER_LENGTH + payload.len());
+  frame.extend_from_slice(&(payload.len() as u32).to_be_bytes()[1..]);
+  frame.push(kind);
+  frame.push(flags);
+  frame.extend_from_slice(&stream_id.to_be_bytes());
+  frame.extend_from_slice(payload);
+  writer.write_all(&frame)
+}
+
+/// The payload of a DATA, HEADERS or PUSH_PROMISE frame without its padding.
+pub fn unpadded(frame: &Frame) -> io::Result<&[u8]> {
+  if !frame.has_flag(PADDED) {
+    return Ok(&frame.payload);
+  }
+
+  let Some((&padding, rest)) = frame.payload.split_first() else {
+    return Err(connection_error(PROTOCOL_ERROR, "padded frame without a padding length"));
+  };
+
+  rest
+    .len()
+    .checked_sub(padding as usize)
+    .map(|length| &rest[..length])
+    .ok_or_else(|| connection_error(PROTOCOL_ERROR, "padding longer than the frame"))
+}
+
+/// The header block fragment of a HEADERS frame.
+pub fn header_block_fragment(frame: &Frame) -> io::Result<&[u8]> {
+  let payload = unpadded(frame)?;
+  if !frame.has_flag(PRIORITY_FLAG) {
+    return Ok(payload);
+  }
+
+  // The stream dependency and weight, which we have no use for.
+  payload
+    .get(5..)
+    .ok_or_else(|| connection_error(FRAME_SIZE_ERROR, "HEADERS frame too short for its priority"))
+}
+
+pub fn settings_payload(settings: &[(u16, u32)]) -> Vec<u8> {
+  let mut payload = Vec::with_capacity(settings.len() * 6);
+  for (identifier, value) in settings {
+    payload.extend_from_slice(&identifier.to_be_bytes());
+    payload.extend_from_slice(&value.to_be_bytes());
+  }
+  payload
+}
+
+pub fn parse_settings(payload: &[u8]) -> io::Result<Vec<(u16, u32)>> {
+  if !payload.len().is_multiple_of(6) {
+    return Err(connection_error(FRAME_SIZE_ERROR, "SETTINGS frame of an invalid length"));
+  }
+
+  Ok(
+    payload
+      .chunks_exact(6)
+      .map(|setting| (
+        u16::from_be_bytes([setting[0], setting[1]]),
+        u32::from_be_bytes([setting[2], setting[3], setting[4], setting[5]]),
+      ))
+      .collect()
+  )
+}
+
+/// Reads a big-endian u32 at the start of a payload, ignoring the reserved
+/// bit where there is one.
+pub fn read_u32(payload: &[u8]) -> io::Result<u32> {
+  payload
+    .get(..4)
+    .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    .ok_or_else(|| connection_error(FRAME_SIZE_ERROR, "frame too short"))
+}
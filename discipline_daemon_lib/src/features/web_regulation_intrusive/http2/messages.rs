@@ -0,0 +1,138 @@
+use http::StatusCode;
+use super::super::traffic::{Exchange, Headers, RequestHead, ResponseHead};
+use super::connection::Field;
+
+/// Headers that only concern the http/1.1 connection they're sent on and
+/// are forbidden in http/2.
+const CONNECTION_SPECIFIC_HEADERS: [&str; 5] = [
+  "connection",
+  "keep-alive",
+  "proxy-connection",
+  "transfer-encoding",
+  "upgrade",
+];
+
+fn is_forwarded(name: &str, value: &str) -> bool {
+  if name.eq_ignore_ascii_case("te") {
+    return value.trim().eq_ignore_ascii_case("trailers");
+  }
+
+  !CONNECTION_SPECIFIC_HEADERS
+    .iter()
+    .any(|header| name.eq_ignore_ascii_case(header))
+}
+
+/// Turns the fields of a request into a head handlers understand, with the
+/// `:authority` as its "Host" header. Returns `None` if pseudo-header fields
+/// are missing.
+pub fn request_head(fields: Vec<Field>) -> Option<RequestHead> {
+  let mut method = None;
+  let mut path = None;
+  let mut authority = None;
+  let mut cookies = Vec::new();
+  let mut headers = Headers::new();
+
+  for (name, value) in fields {
+    match name.as_str() {
+      ":method" => {
+        method = Some(value);
+      }
+      ":path" => {
+        path = Some(value);
+      }
+      ":authority" => {
+        authority = Some(value);
+      }
+      ":scheme" | ":protocol" => {}
+      // Cookies may be split across fields for better compression.
+      "cookie" => {
+        cookies.push(value);
+      }
+      _ => {
+        headers.append(name, value);
+      }
+    }
+  }
+
+  if let Some(authority) = authority {
+    if !headers.contains("Host") {
+      headers.insert("host", authority);
+    }
+  }
+
+  if !cookies.is_empty() {
+    headers.append("cookie", cookies.join("; "));
+  }
+
+  Some(RequestHead {
+    method: method?,
+    target: path?,
+    version: 1,
+    headers,
+  })
+}
+
+/// The fields of a request sent on to the website of `exchange`.
+pub fn request_fields(exchange: &Exchange) -> Vec<Field> {
+  let request = &exchange.request;
+  let authority = match request.headers.get("Host") {
+    Some(host) => host.to_string(),
+    None if exchange.port == exchange.scheme.default_port() => exchange.host.clone(),
+    None => format!("{}:{}", exchange.host, exchange.port),
+  };
+
+  let mut fields = vec![
+    (":method".to_string(), request.method.clone()),
+    (":scheme".to_string(), exchange.scheme.as_str().to_string()),
+    (":authority".to_string(), authority),
+    (":path".to_string(), request.target.clone()),
+  ];
+
+  fields.extend(
+    request
+      .headers
+      .iter()
+      .filter(|(name, value)| !name.eq_ignore_ascii_case("host") && is_forwarded(name, value))
+      .map(|(name, value)| (name.to_ascii_lowercase(), value.to_string()))
+  );
+
+  fields
+}
+
+/// Turns the fields of a response into a head handlers understand. Returns
+/// `None` if the status is missing or invalid.
+pub fn response_head(fields: Vec<Field>) -> Option<ResponseHead> {
+  let mut status = None;
+  let mut headers = Headers::new();
+
+  for (name, value) in fields {
+    if name == ":status" {
+      status = value.parse::<StatusCode>().ok();
+    } else if !name.starts_with(':') {
+      headers.append(name, value);
+    }
+  }
+
+  let status = status?;
+  Some(ResponseHead {
+    status_code: status.as_u16(),
+    reason: status.canonical_reason().unwrap_or_default().to_string(),
+    version: 1,
+    headers,
+  })
+}
+
+/// The fields of a response sent on to the client.
+pub fn response_fields(response: &ResponseHead) -> Vec<Field> {
+  let mut fields = vec![(":status".to_string(), response.status_code.to_string())];
+
+  fields.extend(
+    response
+      .headers
+      .iter()
+      .filter(|(name, value)| is_forwarded(name, value))
+      .map(|(name, value)| (name.to_ascii_lowercase(), value.to_string()))
+  );
+
+  fields
+}
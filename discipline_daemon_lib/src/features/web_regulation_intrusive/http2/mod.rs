@@ -0,0 +1,13 @@
+pub mod frames;
+
+pub mod duplex;
+pub use duplex::Duplex;
+
+pub mod connection;
+pub use connection::{Connection, Stream};
+
+pub mod messages;
+pub use messages::{request_fields, request_head, response_fields, response_head};
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,253 @@
+use std::io::Read;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use tokio::runtime::Runtime;
+use super::super::traffic::*;
+use super::connection::Field;
+use super::*;
+
+fn runtime() -> Runtime {
+  tokio::runtime::Builder::new_multi_thread()
+    .enable_io()
+    .build()
+    .unwrap()
+}
+
+fn field(name: &str, value: &str) -> Field {
+  (name.into(), value.into())
+}
+
+fn value<'a>(fields: &'a [Field], name: &str) -> Option<&'a str> {
+  fields
+    .iter()
+    .find(|(field_name, _)| field_name == name)
+    .map(|(_, value)| value.as_str())
+}
+
+/// Starts an h2 server that answers every request with its body, the path
+/// it was sent to in "x-path" and the body length in an "x-length" trailer.
+fn start_echo_server(runtime: &Runtime) -> SocketAddr {
+  let listener = runtime
+    .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+    .unwrap();
+  let address = listener.local_addr().unwrap();
+
+  runtime.spawn(async move {
+    loop {
+      let (socket, _) = listener.accept().await.unwrap();
+      tokio::spawn(async move {
+        let mut connection = h2::server::handshake(socket).await.unwrap();
+        while let Some(Ok((request, mut respond))) = connection.accept().await {
+          tokio::spawn(async move {
+            let (parts, mut body) = request.into_parts();
+            let mut received = Vec::new();
+            while let Some(chunk) = body.data().await {
+              let chunk = chunk.unwrap();
+              let _ = body.flow_control().release_capacity(chunk.len());
+              received.extend_from_slice(&chunk);
+            }
+
+            let response = http::Response::builder()
+              .status(200)
+              .header("x-path", parts.uri.path())
+              .body(())
+              .unwrap();
+
+            let mut trailers = http::HeaderMap::new();
+            trailers.insert("x-length", received.len().to_string().parse().unwrap());
+
+            let mut sender = respond.send_response(response, false).unwrap();
+            sender.send_data(received.into(), false).unwrap();
+            sender.send_trailers(trailers).unwrap();
+          });
+        }
+      });
+    }
+  });
+
+  address
+}
+
+#[test]
+fn exchanges_concurrent_streams_with_an_h2_server() {
+  let runtime = runtime();
+  let address = start_echo_server(&runtime);
+
+  let connection = Connection::client(Duplex::tcp(TcpStream::connect(address).unwrap()).unwrap()).unwrap();
+
+  thread::scope(|scope| {
+    for index in 0..8u8 {
+      let connection = &connection;
+      scope.spawn(move || {
+        let path = format!("/{index}");
+        // Larger than every default window, so flow control is exercised.
+        let body = vec![index; 1024 * 1024];
+
+        let stream = connection.open_stream(&[
+          field(":method", "POST"),
+          field(":scheme", "https"),
+          field(":authority", "example.com"),
+          field(":path", &path),
+        ], false).unwrap();
+        stream.send_data(&body, true).unwrap();
+
+        let (fields, has_body) = stream.read_head().unwrap();
+        assert_eq!(value(&fields, ":status"), Some("200"));
+        assert_eq!(value(&fields, "x-path"), Some(path.as_str()));
+        assert!(has_body);
+
+        let mut received = Vec::new();
+        (&stream).read_to_end(&mut received).unwrap();
+        assert!(received == body);
+
+        let trailers = stream.take_trailers().unwrap();
+        assert_eq!(value(&trailers, "x-length"), Some("1048576"));
+      });
+    }
+  });
+
+  connection.close();
+}
+
+#[test]
+fn reports_bodyless_responses() {
+  let runtime = runtime();
+  let address = start_echo_server(&runtime);
+
+  let connection = Connection::client(Duplex::tcp(TcpStream::connect(address).unwrap()).unwrap()).unwrap();
+  let stream = connection.open_stream(&[
+    field(":method", "GET"),
+    field(":scheme", "https"),
+    field(":authority", "example.com"),
+    field(":path", "/"),
+  ], true).unwrap();
+
+  let (fields, _) = stream.read_head().unwrap();
+  assert_eq!(value(&fields, "x-path"), Some("/"));
+
+  let mut received = Vec::new();
+  (&stream).read_to_end(&mut received).unwrap();
+  assert!(received.is_empty());
+  assert_eq!(value(&stream.take_trailers().unwrap(), "x-length"), Some("0"));
+}
+
+#[test]
+fn serves_concurrent_streams_to_an_h2_client() {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let address = listener.local_addr().unwrap();
+
+  thread::spawn(move || {
+    let (socket, _) = listener.accept().unwrap();
+    let connection = Connection::server(Duplex::tcp(socket).unwrap()).unwrap();
+    thread::scope(|scope| {
+      while let Some(stream) = connection.accept() {
+        scope.spawn(move || {
+          let (fields, has_body) = stream.read_head().unwrap();
+          let mut body = Vec::new();
+          if has_body {
+            (&stream).read_to_end(&mut body).unwrap();
+          }
+
+          let path = value(&fields, ":path").unwrap().to_string();
+          stream.send_head(&[field(":status", "200"), field("x-path", &path)], false).unwrap();
+          stream.send_data(&body, true).unwrap();
+        });
+      }
+    });
+  });
+
+  runtime().block_on(async move {
+    let socket = tokio::net::TcpStream::connect(address).await.unwrap();
+    let (client, connection) = h2::client::handshake(socket).await.unwrap();
+    tokio::spawn(async move {
+      let _ = connection.await;
+    });
+
+    // Each response is read on a task of its own, as bodies left unread
+    // hold on to the connection window.
+    let mut responses = Vec::new();
+    for index in 0..8u8 {
+      let mut client = client.clone().ready().await.unwrap();
+      let request = http::Request::builder()
+        .method("POST")
+        .uri(format!("https://example.com/{index}"))
+        .body(())
+        .unwrap();
+
+      let (response, mut body) = client.send_request(request, false).unwrap();
+      body.send_data(vec![index; 100_000].into(), true).unwrap();
+
+      responses.push(tokio::spawn(async move {
+        let response = response.await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["x-path"].to_str().unwrap(), format!("/{index}"));
+
+        let mut body = response.into_body();
+        let mut received = Vec::new();
+        while let Some(chunk) = body.data().await {
+          let chunk = chunk.unwrap();
+          let _ = body.flow_control().release_capacity(chunk.len());
+          received.extend_from_slice(&chunk);
+        }
+        assert!(received == vec![index; 100_000]);
+      }));
+    }
+
+    for response in responses {
+      response.await.unwrap();
+    }
+  });
+}
+
+#[test]
+fn turns_request_fields_into_heads() {
+  let request = request_head(vec![
+    field(":method", "GET"),
+    field(":scheme", "https"),
+    field(":authority", "example.com"),
+    field(":path", "/search?q=1"),
+    field("cookie", "a=1"),
+    field("cookie", "b=2"),
+  ]).unwrap();
+
+  assert_eq!(request.method, "GET");
+  assert_eq!(request.target, "/search?q=1");
+  assert_eq!(request.headers.get("Host"), Some("example.com"));
+  assert_eq!(request.headers.get("Cookie"), Some("a=1; b=2"));
+
+  assert!(request_head(vec![field(":method", "GET"), field(":authority", "example.com")]).is_none());
+}
+
+#[test]
+fn strips_connection_specific_headers() {
+  let exchange = Exchange::to("https://example.com:8443/")
+    .with_user_id(None)
+    .with_header("Host", "example.com:8443")
+    .with_header("Connection", "keep-alive")
+    .with_header("Transfer-Encoding", "chunked")
+    .with_header("TE", "trailers")
+    .with_header("Accept", "text/html");
+
+  assert_eq!(request_fields(&exchange), vec![
+    field(":method", "GET"),
+    field(":scheme", "https"),
+    field(":authority", "example.com:8443"),
+    field(":path", "/"),
+    field("te", "trailers"),
+    field("accept", "text/html"),
+  ]);
+
+  let mut response = ResponseHead::new(404, "Not Found");
+  response.headers.insert("Keep-Alive", "timeout=5");
+  response.headers.insert("Content-Type", "text/html");
+
+  assert_eq!(response_fields(&response), vec![
+    field(":status", "404"),
+    field("content-type", "text/html"),
+  ]);
+
+  let response = response_head(vec![field(":status", "404"), field("content-type", "text/html")]).unwrap();
+  assert_eq!(response.status_code, 404);
+  assert_eq!(response.reason, "Not Found");
+  assert_eq!(response.headers.get("Content-Type"), Some("text/html"));
+}
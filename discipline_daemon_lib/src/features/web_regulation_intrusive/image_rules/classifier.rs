@@ -0,0 +1,166 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration as StandardDuration;
+use image::DynamicImage;
+use ring::digest::{digest, SHA256};
+use crate::GenericError;
+use super::images;
+
+/// How many images' scores a `CachedClassifier` remembers.
+pub const MAXIMUM_CACHED_CLASSIFICATIONS: usize = 10_000;
+/// How long to wait for an image to be classified. The classification goes
+/// on afterwards, so the image's scores are known the next time it's seen.
+pub const CLASSIFICATION_TIMEOUT: StandardDuration = StandardDuration::from_secs(3);
+
+/// Tells how much an image looks like each of some classes, like "porn" or
+/// "anime".
+pub trait ImageClassifier: Send + Sync {
+  /// The names of the classes, in the order of the scores of `classify`.
+  fn classes(&self) -> &[String];
+
+  /// A score from 0 to 1 for each class.
+  fn classify(&self, image: &DynamicImage) -> Result<Vec<f32>, GenericError>;
+}
+
+type ContentHash = [u8; 32];
+
+#[derive(Default)]
+struct Cache {
+  scores: HashMap<ContentHash, Arc<Vec<f32>>>,
+  /// The hashes in `scores`, oldest first.
+  order: VecDeque<ContentHash>,
+}
+
+/// Remembers the scores of images by the hash of their content, since the
+/// same images show up again and again, and classifying is slow.
+pub struct CachedClassifier {
+  classifier: Arc<dyn ImageClassifier>,
+  cache: Mutex<Cache>,
+}
+
+impl CachedClassifier {
+  pub fn new(classifier: Arc<dyn ImageClassifier>) -> Self {
+    Self {
+      classifier,
+      cache: Mutex::new(Cache::default()),
+    }
+  }
+
+  pub fn classes(&self) -> &[String] {
+    self.classifier.classes()
+  }
+
+  fn cached(&self, hash: &ContentHash) -> Option<Arc<Vec<f32>>> {
+    self.cache.lock().unwrap().scores.get(hash).cloned()
+  }
+
+  fn remember(&self, hash: ContentHash, scores: Arc<Vec<f32>>) {
+    let mut cache = self.cache.lock().unwrap();
+    if cache.scores.insert(hash, scores).is_some() {
+      return;
+    }
+
+    cache.order.push_back(hash);
+    if cache.order.len() > MAXIMUM_CACHED_CLASSIFICATIONS {
+      if let Some(oldest) = cache.order.pop_front() {
+        cache.scores.remove(&oldest);
+      }
+    }
+  }
+
+  /// Decodes and classifies an encoded image, unless its scores are cached.
+  pub fn classify(&self, body: &[u8]) -> Result<Arc<Vec<f32>>, GenericError> {
+    let hash: ContentHash = digest(&SHA256, body).as_ref().try_into().unwrap();
+    if let Some(scores) = self.cached(&hash) {
+      return Ok(scores);
+    }
+
+    let image = images::decode(body)
+      .map_err(|error| error.change_context("classifying an image"))?;
+
+    let scores = self
+      .classifier
+      .classify(&image)
+      .map_err(|error| error.change_context("classifying an image"))?;
+
+    if scores.len() != self.classes().len() {
+      return Err(
+        GenericError::new("classifying an image")
+          .add_error("the classifier returned a score count different from its class count")
+          .add_attachment("scores", scores.len().to_string())
+          .add_attachment("classes", self.classes().len().to_string())
+      );
+    }
+
+    let scores = Arc::new(scores);
+    self.remember(hash, Arc::clone(&scores));
+    Ok(scores)
+  }
+
+  /// Like `classify`, but on another thread, giving up on waiting after
+  /// `CLASSIFICATION_TIMEOUT`.
+  pub fn classify_with_timeout(self: &Arc<Self>, body: Vec<u8>) -> Result<Arc<Vec<f32>>, GenericError> {
+    let (sender, receiver) = mpsc::channel();
+    let classifier = Arc::clone(self);
+
+    thread::Builder::new()
+      .name("image classification".into())
+      .spawn(move || {
+        // The receiver is gone if we took too long.
+        let _ = sender.send(classifier.classify(&body));
+      })
+      .map_err(|error|
+        GenericError::new("classifying an image")
+          .add_error("failed to spawn a thread")
+          .add_attachment("io error", error.to_string())
+      )?;
+
+    receiver
+      .recv_timeout(CLASSIFICATION_TIMEOUT)
+      .unwrap_or_else(|_| Err(
+        GenericError::new("classifying an image")
+          .add_error("timed out")
+      ))
+  }
+
+  /// The score of `class` in `scores` returned by `classify`.
+  pub fn score_of(&self, scores: &[f32], class: &str) -> Option<f32> {
+    let index = self.classes().iter().position(|other| other == class)?;
+    scores.get(index).copied()
+  }
+}
+
+// SECTION: Test double.
+/// Gives every image the same scores and counts how many it was given.
+pub struct FixedClassifier {
+  classes: Vec<String>,
+  scores: Vec<f32>,
+  classified: AtomicUsize,
+}
+
+impl FixedClassifier {
+  pub fn new(classes_and_scores: &[(&str, f32)]) -> Self {
+    Self {
+      classes: classes_and_scores.iter().map(|(class, _)| class.to_string()).collect(),
+      scores: classes_and_scores.iter().map(|(_, score)| *score).collect(),
+      classified: AtomicUsize::new(0),
+    }
+  }
+
+  pub fn classified(&self) -> usize {
+    self.classified.load(Ordering::Relaxed)
+  }
+}
+
+impl ImageClassifier for FixedClassifier {
+  fn classes(&self) -> &[String] {
+    &self.classes
+  }
+
+  fn classify(&self, _image: &DynamicImage) -> Result<Vec<f32>, GenericError> {
+    self.classified.fetch_add(1, Ordering::Relaxed);
+    Ok(self.scores.clone())
+  }
+}
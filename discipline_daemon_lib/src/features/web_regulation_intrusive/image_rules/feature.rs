@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+use url::Url;
+use crate::database::web_regulation_intrusive_image_rule as rule_db;
+use crate::database::web_regulation_intrusive_image_classifier as classifier_db;
+use crate::operating_system_integration::UserId;
+use crate::{Daemon, Database, DateTime, GenericError, Uuid};
+use super::super::rule_activator::RuleActivator;
+use super::super::traffic::*;
+use super::super::url_pattern::{UrlPattern, UrlScoped, UrlScopedList};
+use super::classifier::CachedClassifier;
+use super::images::{self, Replacement, MAXIMUM_IMAGE_LENGTH};
+use super::onnx::{Classifier, ClassifierModel};
+
+pub const MAXIMUM_RULES_PER_USER: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+  /// Blur the image beyond recognition, keeping its size.
+  Blur,
+  /// Replace the image with a plain gray one of the same size.
+  Replace,
+}
+
+/// Holds for images a classifier scores above `threshold` for `class`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassificationCondition {
+  pub classifier_id: Uuid,
+  pub class: String,
+  /// From 0 to 1.
+  pub threshold: f32,
+}
+
+impl ClassificationCondition {
+  /// Whether `scores`, which `classifier` gave an image, satisfy the condition.
+  pub fn holds(&self, classifier: &CachedClassifier, scores: &[f32]) -> bool {
+    classifier
+      .score_of(scores, &self.class)
+      .is_some_and(|score| score > self.threshold)
+  }
+}
+
+/// Blurs or replaces images whose url, or the page showing them, matches the
+/// rule's patterns while its activator is effective, and, if the rule has a
+/// classification condition, that satisfy it. Patterns left out match
+/// everything, but there's always at least one pattern or a condition.
+#[derive(Debug, Clone)]
+pub struct Rule {
+  id: Uuid,
+  user_id: UserId,
+  image_url_pattern: Option<UrlPattern>,
+  page_url_pattern: Option<UrlPattern>,
+  classification: Option<ClassificationCondition>,
+  action: Action,
+  activator: RuleActivator,
+}
+
+impl Rule {
+  pub fn from_fields(
+    id: Uuid,
+    user_id: UserId,
+    image_url_pattern: Option<UrlPattern>,
+    page_url_pattern: Option<UrlPattern>,
+    classification: Option<ClassificationCondition>,
+    action: Action,
+    activator: RuleActivator,
+  ) -> Self {
+    Self {
+      id,
+      user_id,
+      image_url_pattern,
+      page_url_pattern,
+      classification,
+      action,
+      activator,
+    }
+  }
+
+  pub fn id(&self) -> &Uuid {
+    &self.id
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn image_url_pattern(&self) -> Option<&UrlPattern> {
+    self.image_url_pattern.as_ref()
+  }
+
+  pub fn page_url_pattern(&self) -> Option<&UrlPattern> {
+    self.page_url_pattern.as_ref()
+  }
+
+  pub fn classification(&self) -> Option<&ClassificationCondition> {
+    self.classification.as_ref()
+  }
+
+  pub fn action(&self) -> Action {
+    self.action
+  }
+
+  pub fn activator(&self) -> &RuleActivator {
+    &self.activator
+  }
+
+  /// Whether the rule applies at `now` to the image `exchange` requests,
+  /// which its image url pattern matches, leaving aside its classification
+  /// condition.
+  pub fn applies_to(&self, exchange: &Exchange, now: DateTime) -> bool {
+    exchange.user_id == Some(self.user_id)
+      && self
+        .page_url_pattern
+        .as_ref()
+        .is_none_or(|url_pattern| page_url(exchange).is_some_and(|url| url_pattern.matches_url(&url)))
+      && self.activator.is_effective(now)
+  }
+}
+
+impl UrlScoped for Rule {
+  fn url_patterns(&self) -> &[UrlPattern] {
+    self.image_url_pattern.as_slice()
+  }
+}
+
+/// The url of the page the image is shown on, as told by the `Referer`
+/// header. Browsers send at least the origin of the page by default, so
+/// page url patterns with more than a host only match on pages whose
+/// referrer policy sends the whole url.
+fn page_url(exchange: &Exchange) -> Option<Url> {
+  let referer = exchange.request.headers.get("Referer")?;
+  Url::parse(referer).ok()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleCreator {
+  pub id: Option<Uuid>,
+  pub image_url_pattern: Option<String>,
+  pub page_url_pattern: Option<String>,
+  pub classification: Option<ClassificationCondition>,
+  pub action: Action,
+  pub activator: RuleActivator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RuleCreatorError {
+  /// None of the patterns nor a classification condition were given, which
+  /// would match every image.
+  NoPatterns,
+  InvalidImageUrlPattern,
+  InvalidPageUrlPattern,
+  /// The classification threshold isn't from 0 to 1.
+  InvalidThreshold,
+}
+
+impl RuleCreator {
+  pub fn create(self, user_id: UserId) -> Result<Rule, RuleCreatorError> {
+    let image_url_pattern = self
+      .image_url_pattern
+      .as_deref()
+      .map(UrlPattern::parse)
+      .transpose()
+      .map_err(|_| RuleCreatorError::InvalidImageUrlPattern)?;
+
+    let page_url_pattern = self
+      .page_url_pattern
+      .as_deref()
+      .map(UrlPattern::parse)
+      .transpose()
+      .map_err(|_| RuleCreatorError::InvalidPageUrlPattern)?;
+
+    if self
+      .classification
+      .as_ref()
+      .is_some_and(|condition| !(0.0..=1.0).contains(&condition.threshold))
+    {
+      return Err(RuleCreatorError::InvalidThreshold);
+    }
+
+    if image_url_pattern.is_none()
+      && page_url_pattern.is_none()
+      && self.classification.is_none()
+    {
+      return Err(RuleCreatorError::NoPatterns);
+    }
+
+    Ok(Rule::from_fields(
+      self.id.unwrap_or_else(Uuid::new_v4),
+      user_id,
+      image_url_pattern,
+      page_url_pattern,
+      self.classification,
+      self.action,
+      self.activator,
+    ))
+  }
+}
+
+// SECTION: Traffic handler.
+/// Blurs or replaces jpeg, png, webp and gif images that effective rules
+/// apply to. When several rules apply, replacing wins over blurring.
+///
+/// Images are judged by their `Content-Type` and then by their signature,
+/// and any image a rule applies to that can't be decoded, or is too large,
+/// is replaced rather than let through. The same goes for images that can't
+/// be classified in time, but rules whose classifier can't be opened are
+/// skipped, so a broken model doesn't hide every image.
+pub struct ImageRegulation {
+  rules: Mutex<UrlScopedList<Rule>>,
+  classifiers: Mutex<Vec<Classifier>>,
+}
+
+/// What became of classifying an image with a classifier.
+enum Classification {
+  Scored(Arc<CachedClassifier>, Arc<Vec<f32>>),
+  /// The image couldn't be decoded or classified in time.
+  Failed,
+  /// The classifier couldn't be opened.
+  Unavailable,
+}
+
+impl ImageRegulation {
+  pub fn open(database: &Database) -> Result<Self, GenericError> {
+    let rules = rule_db::retrieve_all_rules(database)
+      .map_err(|error| error.change_context("opening the image regulation"))?;
+
+    let models = classifier_db::retrieve_all_models(database)
+      .map_err(|error| error.change_context("opening the image regulation"))?;
+
+    Ok(Self::new(rules, models))
+  }
+
+  pub fn new(rules: Vec<Rule>, models: Vec<ClassifierModel>) -> Self {
+    Self {
+      rules: Mutex::new(UrlScopedList::new(rules)),
+      classifiers: Mutex::new(models.into_iter().map(Classifier::new).collect()),
+    }
+  }
+
+  /// Lock `rules` before `classifiers` when both are needed.
+  pub fn rules(&self) -> MutexGuard<'_, UrlScopedList<Rule>> {
+    self.rules.lock().unwrap()
+  }
+
+  pub fn classifiers(&self) -> MutexGuard<'_, Vec<Classifier>> {
+    self.classifiers.lock().unwrap()
+  }
+
+  fn classify(&self, daemon: &Daemon, classifier_id: &Uuid, body: &[u8]) -> Classification {
+    let classifier = {
+      let mut classifiers = self.classifiers();
+      let Some(classifier) = classifiers
+        .iter_mut()
+        .find(|classifier| classifier.model().id() == classifier_id)
+      else {
+        return Classification::Unavailable;
+      };
+
+      match classifier.load() {
+        Ok(Some(classifier)) => {
+          classifier
+        }
+        Ok(None) => {
+          return Classification::Unavailable;
+        }
+        Err(error) => {
+          daemon.internal_logger().log_error(error.change_context("applying image rules"));
+          return Classification::Unavailable;
+        }
+      }
+    };
+
+    match classifier.classify_with_timeout(body.to_vec()) {
+      Ok(scores) => {
+        Classification::Scored(classifier, scores)
+      }
+      Err(error) => {
+        daemon.internal_logger().log_error(error.change_context("applying image rules"));
+        Classification::Failed
+      }
+    }
+  }
+
+  /// What to do with `body`, the image `exchange` requests, if anything.
+  /// Each classifier the rules need classifies it once.
+  fn action_for_image(&self, daemon: &Daemon, exchange: &Exchange, body: &[u8], now: DateTime) -> Option<Action> {
+    let rules = self.rules_for(exchange, now);
+    let mut classifications = HashMap::new();
+    rules
+      .iter()
+      .filter(|rule| {
+        let Some(condition) = rule.classification() else {
+          return true;
+        };
+
+        let classification = classifications
+          .entry(condition.classifier_id)
+          .or_insert_with(|| self.classify(daemon, &condition.classifier_id, body));
+
+        match classification {
+          Classification::Scored(classifier, scores) => condition.holds(classifier, scores),
+          Classification::Failed => true,
+          Classification::Unavailable => false,
+        }
+      })
+      .map(Rule::action)
+      .max_by_key(|action| *action == Action::Replace)
+  }
+
+  /// What to do with the image `exchange` requests, if anything, leaving
+  /// aside classification conditions.
+  pub fn action_for(&self, exchange: &Exchange, now: DateTime) -> Option<Action> {
+    self
+      .rules_for(exchange, now)
+      .iter()
+      .map(Rule::action)
+      .max_by_key(|action| *action == Action::Replace)
+  }
+
+  fn rules_for(&self, exchange: &Exchange, now: DateTime) -> Vec<Rule> {
+    let rules = self.rules();
+    rules
+      .matching(exchange)
+      .into_iter()
+      .map(|index| &rules[index])
+      .filter(|rule| rule.applies_to(exchange, now))
+      .cloned()
+      .collect()
+  }
+}
+
+fn is_image(response: &ResponseHead) -> bool {
+  response.status_code == 200
+    && response
+      .content_type()
+      .is_some_and(|content_type| matches!(
+        content_type.as_str(),
+        "image/jpeg" | "image/jpg" | "image/pjpeg" | "image/png" | "image/apng" | "image/webp" | "image/gif"
+      ))
+}
+
+/// Puts `replacement` in place of the body of `response`.
+pub fn replace(response: &mut ResponseHead, body: &mut Vec<u8>, replacement: Replacement) {
+  response.headers.insert("Content-Type", replacement.content_type);
+  // The original may be shown once the rule is no longer effective.
+  response.headers.insert("Cache-Control", "no-store");
+  for header in ["ETag", "Last-Modified", "Content-Range", "Accept-Ranges", "Content-Disposition"] {
+    response.headers.remove(header);
+  }
+  *body = replacement.body;
+}
+
+impl TrafficHandler for ImageRegulation {
+  fn may_inspect_response_body(&self, _daemon: &Daemon, exchange: &Exchange) -> bool {
+    self.action_for(exchange, DateTime::now()).is_some()
+  }
+
+  fn on_response(
+    &self,
+    _daemon: &Daemon,
+    exchange: &Exchange,
+    response: &mut ResponseHead,
+  ) -> ResponseVerdict {
+    if !is_image(response) || self.action_for(exchange, DateTime::now()).is_none() {
+      return ResponseVerdict::Forward;
+    }
+
+    let is_too_long = response
+      .headers
+      .get("Content-Length")
+      .and_then(|length| length.trim().parse::<usize>().ok())
+      .is_some_and(|length| length > MAXIMUM_IMAGE_LENGTH);
+
+    if is_too_long || response.is_content_encoded() {
+      let mut head = response.clone();
+      let mut body = Vec::new();
+      replace(&mut head, &mut body, images::placeholder(None));
+      return ResponseVerdict::Respond(Response::new(head, body));
+    }
+
+    ResponseVerdict::InspectBody
+  }
+
+  fn on_response_body(
+    &self,
+    daemon: &Daemon,
+    exchange: &Exchange,
+    response: &mut ResponseHead,
+    body: &mut Vec<u8>,
+  ) -> BodyVerdict {
+    if !is_image(response) {
+      return BodyVerdict::Forward;
+    }
+
+    let replacement = match self.action_for_image(daemon, exchange, body, DateTime::now()) {
+      Some(Action::Blur) => {
+        images::blur_or_replace(std::mem::take(body))
+      }
+      Some(Action::Replace) => {
+        images::placeholder(images::dimensions(body))
+      }
+      None => {
+        return BodyVerdict::Forward;
+      }
+    };
+
+    replace(response, body, replacement);
+    BodyVerdict::Forward
+  }
+}
@@ -0,0 +1,166 @@
+use std::io::Cursor;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration as StandardDuration;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageReader, Limits};
+use crate::GenericError;
+
+/// Longer images are replaced without being looked at.
+pub const MAXIMUM_IMAGE_LENGTH: usize = 8 * 1024 * 1024;
+pub const MAXIMUM_IMAGE_SIDE: u32 = 8192;
+pub const MAXIMUM_IMAGE_ALLOCATION: u64 = 256 * 1024 * 1024;
+/// How long blurring may take before the image is replaced instead, so a
+/// large image doesn't hold up the page.
+pub const BLUR_TIMEOUT: StandardDuration = StandardDuration::from_secs(2);
+
+/// The longer side images are shrunk to before blurring them. Blurring the
+/// small image and stretching it back is a much stronger blur than what
+/// could be done at full size in the same time.
+const BLURRED_SIDE: u32 = 32;
+const BLURRED_JPEG_QUALITY: u8 = 70;
+
+pub const FORMATS: [ImageFormat; 4] = [
+  ImageFormat::Jpeg,
+  ImageFormat::Png,
+  ImageFormat::WebP,
+  ImageFormat::Gif,
+];
+
+/// An image to send instead of the one the website sent.
+#[derive(Debug, Clone)]
+pub struct Replacement {
+  pub content_type: &'static str,
+  pub body: Vec<u8>,
+}
+
+fn reader(body: &[u8]) -> Result<ImageReader<Cursor<&[u8]>>, GenericError> {
+  let mut reader = ImageReader::new(Cursor::new(body))
+    .with_guessed_format()
+    .map_err(|error|
+      GenericError::new("reading an image")
+        .add_error("failed to guess the format")
+        .add_attachment("io error", error.to_string())
+    )?;
+
+  let format = reader.format();
+  if !format.is_some_and(|format| FORMATS.contains(&format)) {
+    return Err(
+      GenericError::new("reading an image")
+        .add_error("the image isn't a jpeg, png, webp or gif")
+        .add_attachment("format", format!("{format:?}"))
+    );
+  }
+
+  let mut limits = Limits::default();
+  limits.max_image_width = Some(MAXIMUM_IMAGE_SIDE);
+  limits.max_image_height = Some(MAXIMUM_IMAGE_SIDE);
+  limits.max_alloc = Some(MAXIMUM_IMAGE_ALLOCATION);
+  reader.limits(limits);
+
+  Ok(reader)
+}
+
+/// The width and height of an image, read from its header only.
+pub fn dimensions(body: &[u8]) -> Option<(u32, u32)> {
+  reader(body).ok()?.into_dimensions().ok()
+}
+
+/// Decodes a jpeg, png, webp or gif image within the limits. Animations are
+/// reduced to their first frame.
+pub fn decode(body: &[u8]) -> Result<DynamicImage, GenericError> {
+  reader(body)?
+    .decode()
+    .map_err(|error|
+      GenericError::new("decoding an image")
+        .add_error("failed to decode the image")
+        .add_attachment("image error", error.to_string())
+    )
+}
+
+/// Decodes an image, blurs it beyond recognition and encodes it again, as
+/// a png if it has an alpha channel and as a jpeg otherwise.
+pub fn blur(body: &[u8]) -> Result<Replacement, GenericError> {
+  let image = decode(body).map_err(|error| error.change_context("blurring an image"))?;
+
+  let (width, height) = (image.width(), image.height());
+  let blurred = image
+    .thumbnail(BLURRED_SIDE, BLURRED_SIDE)
+    .blur(2.0)
+    .resize_exact(width, height, FilterType::Triangle);
+
+  let mut encoded = Vec::new();
+  let (content_type, result) = if image.color().has_alpha() {
+    let encoder = PngEncoder::new(&mut encoded);
+    ("image/png", DynamicImage::ImageRgba8(blurred.to_rgba8()).write_with_encoder(encoder))
+  } else {
+    let encoder = JpegEncoder::new_with_quality(&mut encoded, BLURRED_JPEG_QUALITY);
+    ("image/jpeg", DynamicImage::ImageRgb8(blurred.to_rgb8()).write_with_encoder(encoder))
+  };
+
+  result.map_err(|error|
+    GenericError::new("blurring an image")
+      .add_error("failed to encode the blurred image")
+      .add_attachment("image error", error.to_string())
+  )?;
+
+  Ok(Replacement {
+    content_type,
+    body: encoded,
+  })
+}
+
+/// A plain gray image taking up the same space as the original, if its
+/// dimensions are known.
+pub fn placeholder(dimensions: Option<(u32, u32)>) -> Replacement {
+  let size = match dimensions {
+    Some((width, height)) => {
+      format!(" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\" preserveAspectRatio=\"none\"")
+    }
+    None => {
+      String::new()
+    }
+  };
+
+  let svg = format!(
+    "<svg xmlns=\"http://www.w3.org/2000/svg\"{size}><rect width=\"100%\" height=\"100%\" fill=\"#a1a1aa\"/></svg>"
+  );
+
+  Replacement {
+    content_type: "image/svg+xml",
+    body: svg.into_bytes(),
+  }
+}
+
+/// Blurs an image on another thread, replacing it with a placeholder if that
+/// fails or takes longer than `BLUR_TIMEOUT`. Images that can't be looked
+/// at are never let through as they are.
+pub fn blur_or_replace(body: Vec<u8>) -> Replacement {
+  let dimensions = dimensions(&body);
+  if dimensions.is_none() {
+    return placeholder(None);
+  }
+
+  let (sender, receiver) = mpsc::channel();
+  let spawned = thread::Builder::new()
+    .name("image blur".into())
+    .spawn(move || {
+      // The receiver is gone if we took too long.
+      let _ = sender.send(blur(&body));
+    });
+
+  if spawned.is_err() {
+    return placeholder(dimensions);
+  }
+
+  match receiver.recv_timeout(BLUR_TIMEOUT) {
+    Ok(Ok(replacement)) => {
+      replacement
+    }
+    Ok(Err(_)) | Err(_) => {
+      placeholder(dimensions)
+    }
+  }
+}
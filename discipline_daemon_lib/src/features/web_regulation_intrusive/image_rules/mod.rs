@@ -0,0 +1,31 @@
+pub mod images;
+pub use images::{blur, blur_or_replace, placeholder, Replacement};
+
+pub mod classifier;
+pub use classifier::{CachedClassifier, FixedClassifier, ImageClassifier};
+
+pub mod onnx;
+pub use onnx::{
+  Classifier,
+  ClassifierModel,
+  ClassifierModelCreator,
+  ClassifierModelCreatorError,
+  Normalization,
+  OnnxClassifier,
+  OutputKind,
+  MAXIMUM_CLASSIFIERS,
+};
+
+pub mod feature;
+pub use feature::{
+  Action,
+  ClassificationCondition,
+  ImageRegulation,
+  Rule,
+  RuleCreator,
+  RuleCreatorError,
+  MAXIMUM_RULES_PER_USER,
+};
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,402 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use image::imageops::FilterType;
+use image::DynamicImage;
+use ort::session::Session;
+use ort::value::Tensor;
+use serde::{Deserialize, Serialize};
+use crate::{GenericError, Uuid};
+use super::classifier::{CachedClassifier, ImageClassifier};
+
+pub const MAXIMUM_CLASSIFIERS: usize = 20;
+pub const MAXIMUM_CLASSES_PER_CLASSIFIER: usize = 1000;
+pub const MAXIMUM_CLASS_LENGTH: usize = 100;
+pub const MAXIMUM_NAME_LENGTH: usize = 100;
+
+/// The side of the square input of models that take images of any size.
+const DEFAULT_INPUT_SIDE: u32 = 224;
+
+const IMAGENET_MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+const IMAGENET_DEVIATION: [f32; 3] = [0.229, 0.224, 0.225];
+
+/// The channel values a model expects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Normalization {
+  /// From 0 to 255.
+  ZeroTo255,
+  /// From 0 to 1.
+  ZeroToOne,
+  /// From -1 to 1.
+  MinusOneToOne,
+  /// From 0 to 1, then standardized with the mean and deviation of
+  /// ImageNet, as most models trained with PyTorch expect.
+  ImageNet,
+}
+
+impl Normalization {
+  fn normalize(self, channel: usize, value: u8) -> f32 {
+    let value = value as f32;
+    match self {
+      Normalization::ZeroTo255 => value,
+      Normalization::ZeroToOne => value / 255.0,
+      Normalization::MinusOneToOne => value / 127.5 - 1.0,
+      Normalization::ImageNet => (value / 255.0 - IMAGENET_MEAN[channel]) / IMAGENET_DEVIATION[channel],
+    }
+  }
+}
+
+/// What a model's output means.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputKind {
+  /// Scores from 0 to 1, like the output of a softmax layer.
+  Probabilities,
+  /// Raw scores, turned into probabilities with a softmax.
+  Logits,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+  /// Batch, channels, height, width.
+  ChannelsFirst,
+  /// Batch, height, width, channels.
+  ChannelsLast,
+}
+
+/// Classifies images with an ONNX model that takes a batch of RGB images as
+/// its first input and gives a score per class as its first output, running
+/// on the CPU.
+///
+/// ONNX Runtime is loaded when the first model is opened, from the system's
+/// `libonnxruntime.so`, or the library the `ORT_DYLIB_PATH` environment
+/// variable names.
+pub struct OnnxClassifier {
+  session: Mutex<Session>,
+  classes: Vec<String>,
+  normalization: Normalization,
+  output_kind: OutputKind,
+  layout: Layout,
+  width: u32,
+  height: u32,
+}
+
+fn ort_error(action: &str, error: ort::Error) -> GenericError {
+  GenericError::new(action)
+    .add_error("onnx runtime failed")
+    .add_attachment("onnx runtime error", error.to_string())
+}
+
+/// The size of a dimension of the input, or `None` if any size goes.
+fn dimension(shape: &[i64], index: usize) -> Option<u32> {
+  let size = *shape.get(index)?;
+  if size <= 0 {
+    return None;
+  }
+
+  u32::try_from(size).ok()
+}
+
+impl OnnxClassifier {
+  pub fn open(
+    model_path: &Path,
+    classes: Vec<String>,
+    normalization: Normalization,
+    output_kind: OutputKind,
+  ) -> Result<Self, GenericError> {
+    let action = "opening an onnx image classifier";
+
+    let session = Session::builder()
+      .and_then(|builder| builder.with_intra_threads(1))
+      .and_then(|builder| builder.commit_from_file(model_path))
+      .map_err(|error|
+        ort_error(action, error).add_attachment("model path", model_path.to_string_lossy())
+      )?;
+
+    let Some(shape) = session.inputs.first().and_then(|input| input.input_type.tensor_shape()) else {
+      return Err(
+        GenericError::new(action)
+          .add_error("the model's first input isn't a tensor")
+          .add_attachment("model path", model_path.to_string_lossy())
+      );
+    };
+
+    let (layout, height_index, width_index) = match (shape.len(), dimension(shape, 1), dimension(shape, 3)) {
+      (4, Some(3), _) => (Layout::ChannelsFirst, 2, 3),
+      (4, _, Some(3)) => (Layout::ChannelsLast, 1, 2),
+      _ => {
+        return Err(
+          GenericError::new(action)
+            .add_error("the model's first input isn't a batch of RGB images")
+            .add_attachment("model path", model_path.to_string_lossy())
+            .add_attachment("input shape", format!("{:?}", &shape[..]))
+        );
+      }
+    };
+
+    Ok(Self {
+      height: dimension(shape, height_index).unwrap_or(DEFAULT_INPUT_SIDE),
+      width: dimension(shape, width_index).unwrap_or(DEFAULT_INPUT_SIDE),
+      session: Mutex::new(session),
+      classes,
+      normalization,
+      output_kind,
+      layout,
+    })
+  }
+
+  fn input(&self, image: &DynamicImage) -> Vec<f32> {
+    let image = image
+      .resize_exact(self.width, self.height, FilterType::Triangle)
+      .to_rgb8();
+
+    let pixels = (self.width * self.height) as usize;
+    let mut input = vec![0.0; pixels * 3];
+    for (index, pixel) in image.pixels().enumerate() {
+      for channel in 0..3 {
+        let value = self.normalization.normalize(channel, pixel.0[channel]);
+        match self.layout {
+          Layout::ChannelsFirst => input[channel * pixels + index] = value,
+          Layout::ChannelsLast => input[index * 3 + channel] = value,
+        }
+      }
+    }
+
+    input
+  }
+}
+
+fn softmax(scores: &mut [f32]) {
+  let maximum = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+  let mut sum = 0.0;
+  for score in scores.iter_mut() {
+    *score = (*score - maximum).exp();
+    sum += *score;
+  }
+
+  for score in scores.iter_mut() {
+    *score /= sum;
+  }
+}
+
+impl ImageClassifier for OnnxClassifier {
+  fn classes(&self) -> &[String] {
+    &self.classes
+  }
+
+  fn classify(&self, image: &DynamicImage) -> Result<Vec<f32>, GenericError> {
+    let action = "classifying an image with an onnx model";
+
+    let shape = match self.layout {
+      Layout::ChannelsFirst => [1, 3, self.height as i64, self.width as i64],
+      Layout::ChannelsLast => [1, self.height as i64, self.width as i64, 3],
+    };
+
+    let input = Tensor::from_array((shape, self.input(image)))
+      .map_err(|error| ort_error(action, error))?;
+
+    let mut session = self.session.lock().unwrap();
+    let outputs = session
+      .run(ort::inputs![input])
+      .map_err(|error| ort_error(action, error))?;
+
+    let (_, scores) = outputs[0]
+      .try_extract_tensor::<f32>()
+      .map_err(|error| ort_error(action, error))?;
+
+    let mut scores = scores.to_vec();
+    if self.output_kind == OutputKind::Logits {
+      softmax(&mut scores);
+    }
+
+    Ok(scores)
+  }
+}
+
+// SECTION: Models.
+/// An ONNX model file that image rules may classify images with. Models are
+/// provided by the administrator and shared by every user.
+#[derive(Debug, Clone)]
+pub struct ClassifierModel {
+  id: Uuid,
+  name: String,
+  model_path: PathBuf,
+  classes: Vec<String>,
+  normalization: Normalization,
+  output_kind: OutputKind,
+}
+
+impl ClassifierModel {
+  pub fn from_fields(
+    id: Uuid,
+    name: String,
+    model_path: PathBuf,
+    classes: Vec<String>,
+    normalization: Normalization,
+    output_kind: OutputKind,
+  ) -> Self {
+    Self {
+      id,
+      name,
+      model_path,
+      classes,
+      normalization,
+      output_kind,
+    }
+  }
+
+  pub fn id(&self) -> &Uuid {
+    &self.id
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn model_path(&self) -> &Path {
+    &self.model_path
+  }
+
+  pub fn classes(&self) -> &[String] {
+    &self.classes
+  }
+
+  pub fn normalization(&self) -> Normalization {
+    self.normalization
+  }
+
+  pub fn output_kind(&self) -> OutputKind {
+    self.output_kind
+  }
+
+  pub fn open(&self) -> Result<OnnxClassifier, GenericError> {
+    OnnxClassifier::open(&self.model_path, self.classes.clone(), self.normalization, self.output_kind)
+      .map_err(|error|
+        error
+          .change_context("opening an image classifier model")
+          .add_attachment("model name", &self.name)
+      )
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassifierModelCreator {
+  pub id: Option<Uuid>,
+  pub name: String,
+  pub model_path: String,
+  /// The names of the classes, in the order of the model's output.
+  pub classes: Vec<String>,
+  pub normalization: Normalization,
+  pub output_kind: OutputKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ClassifierModelCreatorError {
+  InvalidName,
+  /// The model path isn't absolute.
+  RelativeModelPath,
+  NoClasses,
+  TooManyClasses,
+  /// A class name is empty, too long, or has a line break.
+  InvalidClass { class: String },
+  DuplicateClass { class: String },
+}
+
+impl ClassifierModelCreator {
+  pub fn create(self) -> Result<ClassifierModel, ClassifierModelCreatorError> {
+    let name = self.name.trim();
+    if name.is_empty() || name.len() > MAXIMUM_NAME_LENGTH {
+      return Err(ClassifierModelCreatorError::InvalidName);
+    }
+
+    let model_path = PathBuf::from(self.model_path);
+    if !model_path.is_absolute() {
+      return Err(ClassifierModelCreatorError::RelativeModelPath);
+    }
+
+    if self.classes.is_empty() {
+      return Err(ClassifierModelCreatorError::NoClasses);
+    }
+    if self.classes.len() > MAXIMUM_CLASSES_PER_CLASSIFIER {
+      return Err(ClassifierModelCreatorError::TooManyClasses);
+    }
+
+    for (index, class) in self.classes.iter().enumerate() {
+      if class.is_empty() || class.len() > MAXIMUM_CLASS_LENGTH || class.contains(['\n', '\r']) {
+        return Err(ClassifierModelCreatorError::InvalidClass { class: class.clone() });
+      }
+      if self.classes[..index].contains(class) {
+        return Err(ClassifierModelCreatorError::DuplicateClass { class: class.clone() });
+      }
+    }
+
+    Ok(ClassifierModel::from_fields(
+      self.id.unwrap_or_else(Uuid::new_v4),
+      name.to_string(),
+      model_path,
+      self.classes,
+      self.normalization,
+      self.output_kind,
+    ))
+  }
+}
+
+enum LoadState {
+  Unloaded,
+  Loaded(Arc<CachedClassifier>),
+  /// Opening the model failed, and it isn't tried again until the daemon
+  /// restarts or the model is added again.
+  Failed,
+}
+
+/// A model and, once an image rule needs it, the classifier running it.
+pub struct Classifier {
+  model: ClassifierModel,
+  state: LoadState,
+}
+
+impl Classifier {
+  /// A classifier that opens `model` the first time it's needed.
+  pub fn new(model: ClassifierModel) -> Self {
+    Self {
+      model,
+      state: LoadState::Unloaded,
+    }
+  }
+
+  /// A classifier that runs `classifier` instead of opening `model`.
+  pub fn loaded(model: ClassifierModel, classifier: Arc<dyn ImageClassifier>) -> Self {
+    Self {
+      model,
+      state: LoadState::Loaded(Arc::new(CachedClassifier::new(classifier))),
+    }
+  }
+
+  pub fn model(&self) -> &ClassifierModel {
+    &self.model
+  }
+
+  /// The classifier running the model, opening it if it wasn't yet. Returns
+  /// `Ok(None)` if opening it failed before.
+  pub fn load(&mut self) -> Result<Option<Arc<CachedClassifier>>, GenericError> {
+    match &self.state {
+      LoadState::Loaded(classifier) => {
+        Ok(Some(Arc::clone(classifier)))
+      }
+      LoadState::Failed => {
+        Ok(None)
+      }
+      LoadState::Unloaded => {
+        match self.model.open() {
+          Ok(classifier) => {
+            let classifier = Arc::new(CachedClassifier::new(Arc::new(classifier)));
+            self.state = LoadState::Loaded(Arc::clone(&classifier));
+            Ok(Some(classifier))
+          }
+          Err(error) => {
+            self.state = LoadState::Failed;
+            Err(error)
+          }
+        }
+      }
+    }
+  }
+}
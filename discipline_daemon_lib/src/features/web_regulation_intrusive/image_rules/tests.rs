@@ -0,0 +1,206 @@
+use std::io::Cursor;
+use std::sync::Arc;
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage, Rgba, RgbaImage};
+use crate::operating_system_integration::UserId;
+use crate::{DateTime, Uuid};
+use super::super::rule_activator::RuleActivator;
+use super::super::traffic::*;
+use super::feature::replace;
+use super::images::dimensions;
+use super::*;
+
+fn creator(image_url_pattern: Option<&str>, page_url_pattern: Option<&str>) -> RuleCreator {
+  RuleCreator {
+    id: None,
+    image_url_pattern: image_url_pattern.map(String::from),
+    page_url_pattern: page_url_pattern.map(String::from),
+    classification: None,
+    action: Action::Blur,
+    activator: RuleActivator::AllTheTime,
+  }
+}
+
+fn encode(image: DynamicImage, format: ImageFormat) -> Vec<u8> {
+  let mut encoded = Cursor::new(Vec::new());
+  image.write_to(&mut encoded, format).unwrap();
+  encoded.into_inner()
+}
+
+/// Black and white stripes, one pixel wide.
+fn stripes(width: u32, height: u32) -> RgbImage {
+  RgbImage::from_fn(width, height, |x, _| {
+    if x % 2 == 0 { Rgb([0, 0, 0]) } else { Rgb([255, 255, 255]) }
+  })
+}
+
+#[test]
+fn creates_rules() {
+  let user_id = UserId::new(1000);
+
+  assert!(matches!(
+    creator(None, None).create(user_id),
+    Err(RuleCreatorError::NoPatterns),
+  ));
+  assert!(matches!(
+    creator(Some("example.com?=watch"), None).create(user_id),
+    Err(RuleCreatorError::InvalidImageUrlPattern),
+  ));
+  assert!(matches!(
+    creator(None, Some("example.com?=x")).create(user_id),
+    Err(RuleCreatorError::InvalidPageUrlPattern),
+  ));
+}
+
+#[test]
+fn applies_to_images_by_url_and_page() {
+  let now = DateTime::now();
+  let by_image = ImageRegulation::new(
+    vec![creator(Some("*.cdn.example/avatars"), None).create(UserId::new(1000)).unwrap()],
+    Vec::new(),
+  );
+  let by_page = ImageRegulation::new(
+    vec![creator(None, Some("*.example.com/gallery")).create(UserId::new(1000)).unwrap()],
+    Vec::new(),
+  );
+
+  let image = |url| Exchange::to(url).fetching("image");
+  assert_eq!(by_image.action_for(&image("https://img.cdn.example/avatars/a.jpg"), now), Some(Action::Blur));
+  assert_eq!(by_image.action_for(&image("https://img.cdn.example/a.jpg"), now), None);
+  assert_eq!(by_image.action_for(&image("https://img.other.example/avatars/a.jpg"), now), None);
+
+  let shown_on_page = image("https://img.cdn.example/a.jpg").with_header("Referer", "https://www.example.com/gallery/1");
+  assert_eq!(by_page.action_for(&shown_on_page, now), Some(Action::Blur));
+  assert_eq!(by_page.action_for(&image("https://img.cdn.example/a.jpg").with_header("Referer", "https://www.example.com/"), now), None);
+  assert_eq!(by_page.action_for(&image("https://img.cdn.example/a.jpg").with_header("Referer", "https://example.org/"), now), None);
+  assert_eq!(by_page.action_for(&image("https://img.cdn.example/a.jpg"), now), None);
+
+  let other_user = shown_on_page.with_user_id(Some(UserId::new(1001)));
+  assert_eq!(by_page.action_for(&other_user, now), None);
+}
+
+#[test]
+fn blurs_every_format_keeping_the_size() {
+  let opaque = DynamicImage::ImageRgb8(stripes(64, 48));
+  let transparent = DynamicImage::ImageRgba8(RgbaImage::from_fn(64, 48, |x, _| {
+    if x % 2 == 0 { Rgba([0, 0, 0, 0]) } else { Rgba([255, 255, 255, 255]) }
+  }));
+
+  for (image, format, content_type) in [
+    (&opaque, ImageFormat::Jpeg, "image/jpeg"),
+    (&opaque, ImageFormat::Gif, "image/png"),
+    (&opaque, ImageFormat::WebP, "image/jpeg"),
+    (&transparent, ImageFormat::Png, "image/png"),
+  ] {
+    let blurred = blur(&encode(image.clone(), format)).unwrap();
+    assert_eq!(blurred.content_type, content_type, "{format:?}");
+    assert_eq!(dimensions(&blurred.body), Some((64, 48)), "{format:?}");
+
+    // The stripes are blurred into a flat gray.
+    let decoded = image::load_from_memory(&blurred.body).unwrap().to_rgb8();
+    let [red, ..] = decoded.get_pixel(32, 24).0;
+    assert!((64..=192).contains(&red), "{format:?}: {red}");
+  }
+}
+
+#[test]
+fn replaces_what_cant_be_blurred() {
+  let placeholder = blur_or_replace(b"GIF89a, or so it says".to_vec());
+  assert_eq!(placeholder.content_type, "image/svg+xml");
+  assert!(!String::from_utf8(placeholder.body).unwrap().contains("viewBox"));
+
+  let sized = super::placeholder(Some((640, 480)));
+  assert!(String::from_utf8(sized.body).unwrap().contains("width=\"640\" height=\"480\""));
+
+  let mut response = ResponseHead::new(200, "OK");
+  response.headers.append("Content-Type", "image/png");
+  response.headers.append("ETag", "\"abc\"");
+  let mut body = encode(DynamicImage::ImageRgb8(stripes(4, 4)), ImageFormat::Png);
+  let replacement = super::placeholder(dimensions(&body));
+  replace(&mut response, &mut body, replacement);
+  assert_eq!(response.headers.get("Content-Type"), Some("image/svg+xml"));
+  assert_eq!(response.headers.get("ETag"), None);
+  assert!(String::from_utf8(body).unwrap().contains("width=\"4\""));
+}
+
+fn condition(class: &str, threshold: f32) -> ClassificationCondition {
+  ClassificationCondition {
+    classifier_id: Uuid::new_v4(),
+    class: class.into(),
+    threshold,
+  }
+}
+
+#[test]
+fn creates_rules_with_classification_conditions() {
+  let user_id = UserId::new(1000);
+
+  let mut by_class = creator(None, None);
+  by_class.classification = Some(condition("porn", 0.8));
+  assert!(by_class.create(user_id).unwrap().classification().is_some());
+
+  for threshold in [-0.1, 1.5, f32::NAN] {
+    let mut out_of_range = creator(None, None);
+    out_of_range.classification = Some(condition("porn", threshold));
+    assert!(matches!(
+      out_of_range.create(user_id),
+      Err(RuleCreatorError::InvalidThreshold),
+    ));
+  }
+}
+
+#[test]
+fn caches_classifications_by_content() {
+  let fixed = Arc::new(FixedClassifier::new(&[("neutral", 0.1), ("porn", 0.9)]));
+  let classifier = Arc::new(CachedClassifier::new(fixed.clone()));
+
+  let first = encode(DynamicImage::ImageRgb8(stripes(8, 8)), ImageFormat::Png);
+  let second = encode(DynamicImage::ImageRgb8(stripes(8, 4)), ImageFormat::Png);
+
+  let scores = classifier.classify(&first).unwrap();
+  classifier.classify(&first).unwrap();
+  classifier.classify_with_timeout(first.clone()).unwrap();
+  assert_eq!(fixed.classified(), 1);
+
+  classifier.classify(&second).unwrap();
+  assert_eq!(fixed.classified(), 2);
+
+  assert!(classifier.classify(b"not an image").is_err());
+  assert_eq!(fixed.classified(), 2);
+
+  assert!(condition("porn", 0.8).holds(&classifier, &scores));
+  assert!(!condition("porn", 0.9).holds(&classifier, &scores));
+  assert!(condition("neutral", 0.05).holds(&classifier, &scores));
+  assert!(!condition("anime", 0.0).holds(&classifier, &scores));
+}
+
+#[test]
+fn creates_classifier_models() {
+  let model_creator = |model_path: &str, classes: &[&str]| ClassifierModelCreator {
+    id: None,
+    name: "nsfw".into(),
+    model_path: model_path.into(),
+    classes: classes.iter().map(|class| class.to_string()).collect(),
+    normalization: Normalization::ImageNet,
+    output_kind: OutputKind::Logits,
+  };
+
+  let model = model_creator("/var/lib/models/nsfw.onnx", &["neutral", "porn"]).create().unwrap();
+  assert_eq!(model.classes(), ["neutral", "porn"]);
+
+  assert!(matches!(
+    model_creator("nsfw.onnx", &["porn"]).create(),
+    Err(ClassifierModelCreatorError::RelativeModelPath),
+  ));
+  assert!(matches!(
+    model_creator("/nsfw.onnx", &[]).create(),
+    Err(ClassifierModelCreatorError::NoClasses),
+  ));
+  assert!(matches!(
+    model_creator("/nsfw.onnx", &["porn", "a\nb"]).create(),
+    Err(ClassifierModelCreatorError::InvalidClass { .. }),
+  ));
+  assert!(matches!(
+    model_creator("/nsfw.onnx", &["porn", "porn"]).create(),
+    Err(ClassifierModelCreatorError::DuplicateClass { .. }),
+  ));
+}
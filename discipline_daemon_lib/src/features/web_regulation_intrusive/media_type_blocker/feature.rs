@@ -0,0 +1,255 @@
+use std::sync::{Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+use crate::database::web_regulation_intrusive_media_type_rule as rule_db;
+use crate::operating_system_integration::UserId;
+use crate::{Daemon, Database, DateTime, GenericError, Uuid};
+use crate::api::block_pages::WebRuleFeature;
+use super::super::block_page;
+use super::super::media_types::{DetectedMedia, MediaKind};
+use super::super::rule_activator::RuleActivator;
+use super::super::traffic::*;
+use super::super::url_pattern::{UrlPattern, UrlScoped, UrlScopedList};
+
+pub const MAXIMUM_RULES_PER_USER: usize = 100;
+
+/// Blocks responses of some media kinds while its activator is effective,
+/// optionally only from urls matching a pattern.
+#[derive(Debug, Clone)]
+pub struct Rule {
+  id: Uuid,
+  user_id: UserId,
+  media_kinds: Vec<MediaKind>,
+  url_pattern: Option<UrlPattern>,
+  activator: RuleActivator,
+}
+
+impl Rule {
+  pub fn from_fields(
+    id: Uuid,
+    user_id: UserId,
+    media_kinds: Vec<MediaKind>,
+    url_pattern: Option<UrlPattern>,
+    activator: RuleActivator,
+  ) -> Self {
+    Self {
+      id,
+      user_id,
+      media_kinds,
+      url_pattern,
+      activator,
+    }
+  }
+
+  pub fn id(&self) -> &Uuid {
+    &self.id
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn media_kinds(&self) -> &Vec<MediaKind> {
+    &self.media_kinds
+  }
+
+  pub fn url_pattern(&self) -> Option<&UrlPattern> {
+    self.url_pattern.as_ref()
+  }
+
+  pub fn activator(&self) -> &RuleActivator {
+    &self.activator
+  }
+
+  /// Whether the rule applies to an exchange its url pattern matches.
+  pub fn applies_to(&self, exchange: &Exchange, now: DateTime) -> bool {
+    exchange.user_id == Some(self.user_id) && self.activator.is_effective(now)
+  }
+}
+
+impl UrlScoped for Rule {
+  fn url_patterns(&self) -> &[UrlPattern] {
+    self.url_pattern.as_slice()
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleCreator {
+  pub id: Option<Uuid>,
+  pub media_kinds: Vec<MediaKind>,
+  pub url_pattern: Option<String>,
+  pub activator: RuleActivator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RuleCreatorError {
+  NoMediaKinds,
+  InvalidUrlPattern,
+}
+
+impl RuleCreator {
+  pub fn create(self, user_id: UserId) -> Result<Rule, RuleCreatorError> {
+    let mut media_kinds = Vec::new();
+    for media_kind in self.media_kinds {
+      if !media_kinds.contains(&media_kind) {
+        media_kinds.push(media_kind);
+      }
+    }
+
+    if media_kinds.is_empty() {
+      return Err(RuleCreatorError::NoMediaKinds);
+    }
+
+    let url_pattern = self
+      .url_pattern
+      .as_deref()
+      .map(UrlPattern::parse)
+      .transpose()
+      .map_err(|_| RuleCreatorError::InvalidUrlPattern)?;
+
+    Ok(Rule::from_fields(
+      self.id.unwrap_or_else(Uuid::new_v4),
+      user_id,
+      media_kinds,
+      url_pattern,
+      self.activator,
+    ))
+  }
+}
+
+// SECTION: Traffic handler.
+/// Blocks responses whose media kind is blocked by an effective rule.
+///
+/// A response's kind is decided by its `Content-Type`, the extension of its
+/// file name and the signature at the start of its body. It's blocked if any
+/// of these is of a blocked kind, so mislabeled responses don't get through.
+/// Bodies with a content coding can't be sniffed and are judged by the rest.
+pub struct MediaTypeBlocker {
+  rules: Mutex<UrlScopedList<Rule>>,
+}
+
+impl MediaTypeBlocker {
+  pub fn open(database: &Database) -> Result<Self, GenericError> {
+    let rules = rule_db::retrieve_all_rules(database)
+      .map_err(|error| error.change_context("opening the media type blocker"))?;
+
+    Ok(Self::new(rules))
+  }
+
+  pub fn new(rules: Vec<Rule>) -> Self {
+    Self {
+      rules: Mutex::new(UrlScopedList::new(rules)),
+    }
+  }
+
+  pub fn rules(&self) -> MutexGuard<'_, UrlScopedList<Rule>> {
+    self.rules.lock().unwrap()
+  }
+
+  /// The media kinds blocked for this exchange as of `now`.
+  pub fn blocked_media_kinds(&self, exchange: &Exchange, now: DateTime) -> Vec<MediaKind> {
+    let mut media_kinds = Vec::new();
+
+    let rules = self.rules();
+    for index in rules.matching(exchange) {
+      let rule = &rules[index];
+      if !rule.applies_to(exchange, now) {
+        continue;
+      }
+
+      for media_kind in &rule.media_kinds {
+        if !media_kinds.contains(media_kind) {
+          media_kinds.push(*media_kind);
+        }
+      }
+    }
+
+    media_kinds
+  }
+
+  /// The id of the first rule blocking the media the response carries.
+  fn judge(&self, exchange: &Exchange, response: &ResponseHead, body_start: Option<&[u8]>) -> Option<Uuid> {
+    let now = DateTime::now();
+    let rules = self.rules();
+    let applying: Vec<&Rule> = rules
+      .matching(exchange)
+      .into_iter()
+      .map(|index| &rules[index])
+      .filter(|rule| rule.applies_to(exchange, now))
+      .collect();
+
+    if applying.is_empty() {
+      return None;
+    }
+
+    let detected = DetectedMedia::detect(exchange, response, body_start);
+    applying
+      .into_iter()
+      .find(|rule| rule.media_kinds.iter().any(|media_kind| detected.is(*media_kind)))
+      .map(|rule| rule.id)
+  }
+}
+
+/// Whether the body starts at the start of the file, so its signature is
+/// there to sniff.
+fn is_sniffable(response: &ResponseHead) -> bool {
+  match response.status_code {
+    200 => {
+      true
+    }
+    206 => {
+      response
+        .headers
+        .get("Content-Range")
+        .is_some_and(|range| range.trim_start().starts_with("bytes 0-"))
+    }
+    _ => {
+      false
+    }
+  }
+}
+
+impl TrafficHandler for MediaTypeBlocker {
+  fn on_response(
+    &self,
+    daemon: &Daemon,
+    exchange: &Exchange,
+    response: &mut ResponseHead,
+  ) -> ResponseVerdict {
+    if let Some(rule_id) = self.judge(exchange, response, None) {
+      return ResponseVerdict::Respond(block_page::respond(
+        daemon.configuration().block_page_port(),
+        exchange,
+        WebRuleFeature::MediaTypeRule,
+        rule_id,
+      ));
+    }
+
+    if is_sniffable(response) && !self.blocked_media_kinds(exchange, DateTime::now()).is_empty() {
+      ResponseVerdict::SniffBody
+    } else {
+      ResponseVerdict::Forward
+    }
+  }
+
+  fn on_response_body_start(
+    &self,
+    daemon: &Daemon,
+    exchange: &Exchange,
+    response: &ResponseHead,
+    body_start: &[u8],
+  ) -> BodyVerdict {
+    match self.judge(exchange, response, Some(body_start)) {
+      Some(rule_id) => {
+        BodyVerdict::Respond(block_page::respond(
+          daemon.configuration().block_page_port(),
+          exchange,
+          WebRuleFeature::MediaTypeRule,
+          rule_id,
+        ))
+      }
+      None => {
+        BodyVerdict::Forward
+      }
+    }
+  }
+}
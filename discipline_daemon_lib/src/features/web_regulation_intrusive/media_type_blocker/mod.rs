@@ -0,0 +1,11 @@
+pub mod feature;
+pub use feature::{
+  MediaTypeBlocker,
+  Rule,
+  RuleCreator,
+  RuleCreatorError,
+  MAXIMUM_RULES_PER_USER,
+};
+
+#[cfg(test)]
+mod tests;
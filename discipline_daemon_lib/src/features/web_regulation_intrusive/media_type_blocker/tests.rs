@@ -0,0 +1,117 @@
+use crate::operating_system_integration::UserId;
+use crate::DateTime;
+use super::super::media_types::{sniff_mime, DetectedMedia, MediaKind};
+use super::super::rule_activator::RuleActivator;
+use super::super::traffic::{Exchange, Headers, RequestHead, ResponseHead, Scheme};
+use super::{MediaTypeBlocker, RuleCreator, RuleCreatorError};
+
+fn response_with(headers: &[(&str, &str)]) -> ResponseHead {
+  let mut response = ResponseHead::new(200, "OK");
+  for (name, value) in headers {
+    response.headers.append(*name, *value);
+  }
+  response
+}
+
+fn creator(media_kinds: Vec<MediaKind>, url_pattern: Option<&str>) -> RuleCreator {
+  RuleCreator {
+    id: None,
+    media_kinds,
+    url_pattern: url_pattern.map(str::to_string),
+    activator: RuleActivator::AllTheTime,
+  }
+}
+
+#[test]
+fn sniffs_signatures() {
+  assert_eq!(sniff_mime(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some("image/png"));
+  assert_eq!(sniff_mime(b"GIF89a\x01\0\x01\0"), Some("image/gif"));
+  assert_eq!(sniff_mime(b"\0\0\0\x20ftypisom\0\0\x02\0"), Some("video/mp4"));
+  assert_eq!(sniff_mime(b"\x1a\x45\xdf\xa3\x9f\x42\x86\x81\x01\x42\x82\x84webm"), Some("video/webm"));
+  assert_eq!(sniff_mime(b"%PDF-1.7\n"), Some("application/pdf"));
+  assert_eq!(sniff_mime(b"\x7fELF\x02\x01\x01"), Some("application/x-executable"));
+  assert_eq!(
+    sniff_mime(b"PK\x03\x04\x14\0\0\0\x08\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\x13\0\0\0AndroidManifest.xml"),
+    Some("application/vnd.android.package-archive"),
+  );
+  assert_eq!(sniff_mime(b"PK\x03\x04\x14\0\0\0\x08\0readme.txt"), Some("application/zip"));
+
+  assert_eq!(sniff_mime(b"<!doctype html><html>"), None);
+  assert_eq!(sniff_mime(b"{\"json\": true}"), None);
+  assert_eq!(sniff_mime(b""), None);
+}
+
+#[test]
+fn detects_media_from_every_source() {
+  let exchange = Exchange::to("https://example.com/download?id=42");
+
+  // A video served as a plain download is still a video.
+  let response = response_with(&[("Content-Type", "application/octet-stream")]);
+  let detected = DetectedMedia::detect(&exchange, &response, Some(b"\0\0\0\x20ftypisom"));
+  assert!(detected.is(MediaKind::Video));
+  assert!(!detected.is(MediaKind::AndroidPackage));
+
+  // So is an app named as one in its Content-Disposition.
+  let response = response_with(&[
+    ("Content-Type", "application/octet-stream"),
+    ("Content-Disposition", "attachment; filename=\"App.APK\""),
+  ]);
+  let detected = DetectedMedia::detect(&exchange, &response, None);
+  assert_eq!(detected.by_filename_extension, Some("application/vnd.android.package-archive"));
+  assert!(detected.is(MediaKind::AndroidPackage));
+  assert!(detected.is(MediaKind::Archive));
+
+  // The path is used when there's no Content-Disposition.
+  let exchange = Exchange::to("https://example.com/media/clip.mkv?download=1");
+  let response = response_with(&[("Content-Type", "text/plain; charset=utf-8")]);
+  let detected = DetectedMedia::detect(&exchange, &response, None);
+  assert!(detected.is(MediaKind::Video));
+  assert!(detected.is(MediaKind::Text));
+  assert!(!detected.is(MediaKind::Image));
+}
+
+#[test]
+fn classifies_mimes() {
+  assert!(MediaKind::Video.contains_mime("application/vnd.apple.mpegurl"));
+  assert!(MediaKind::Executable.contains_mime("application/x-msdownload"));
+  assert!(MediaKind::Binary.contains_mime("image/png"));
+  assert!(!MediaKind::Binary.contains_mime("application/json"));
+  assert!(!MediaKind::Binary.contains_mime("text/html"));
+  assert!(MediaKind::Document.contains_mime("application/vnd.openxmlformats-officedocument.wordprocessingml.document"));
+  assert!(!MediaKind::Image.contains_mime("video/mp4"));
+}
+
+#[test]
+fn creates_rules() {
+  let user_id = UserId::new(1000);
+
+  assert!(matches!(creator(Vec::new(), None).create(user_id), Err(RuleCreatorError::NoMediaKinds)));
+  assert!(matches!(
+    creator(vec![MediaKind::Video], Some("example.com?=watch")).create(user_id),
+    Err(RuleCreatorError::InvalidUrlPattern),
+  ));
+
+  let rule = creator(vec![MediaKind::Video, MediaKind::AndroidPackage, MediaKind::Video], None)
+    .create(user_id)
+    .unwrap();
+  assert_eq!(rule.media_kinds(), &vec![MediaKind::Video, MediaKind::AndroidPackage]);
+}
+
+#[test]
+fn applies_rules_to_their_user_and_urls() {
+  let now = DateTime::now();
+  let blocker = MediaTypeBlocker::new(vec![
+    creator(vec![MediaKind::Video], Some("*.example.com/media")).create(UserId::new(1000)).unwrap(),
+    creator(vec![MediaKind::Archive], None).create(UserId::new(1000)).unwrap(),
+  ]);
+
+  assert_eq!(
+    blocker.blocked_media_kinds(&Exchange::to("https://cdn.example.com/media/clip.mp4"), now),
+    vec![MediaKind::Video, MediaKind::Archive],
+  );
+  assert_eq!(blocker.blocked_media_kinds(&Exchange::to("https://cdn.example.com/app.js"), now), vec![MediaKind::Archive]);
+  assert_eq!(blocker.blocked_media_kinds(&Exchange::to("https://example.org/media"), now), vec![MediaKind::Archive]);
+
+  let other_user = Exchange::to("https://cdn.example.com/media").with_user_id(Some(UserId::new(1001)));
+  assert!(blocker.blocked_media_kinds(&other_user, now).is_empty());
+}
@@ -1,10 +1,15 @@
+use serde::{Deserialize, Serialize};
+use super::mimes;
+use super::traffic::{Exchange, ResponseHead};
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MediaKind {
   /// Matches any image.
   Image,
-  /// Matches any video.
+  /// Matches any video, including streaming playlists.
   Video,
+  /// Matches any audio.
+  Audio,
   /// Matches any font.
   Font,
   /// Matches any archive.
@@ -31,4 +36,465 @@ pub enum MediaKind {
   GifLike,
   /// Matches any icon.
   Icon,
-}
\ No newline at end of file
+  /// Matches any PDF document.
+  Pdf,
+  /// Matches any Android app package.
+  AndroidPackage,
+  /// Matches any program or installer for a desktop operating system.
+  Executable,
+}
+
+const JAVASCRIPT_MIMES: &[&str] = &[
+  "text/javascript",
+  "text/ecmascript",
+  "application/javascript",
+  "application/x-javascript",
+  "application/ecmascript",
+];
+
+const TYPESCRIPT_MIMES: &[&str] = &[
+  "application/typescript",
+  "application/x-typescript",
+  "text/typescript",
+];
+
+const VIDEO_PLAYLIST_MIMES: &[&str] = &[
+  "application/vnd.apple.mpegurl",
+  "application/x-mpegurl",
+  "application/dash+xml",
+];
+
+const FONT_MIMES: &[&str] = &[
+  "application/vnd.ms-fontobject",
+  "application/font-woff",
+  "application/font-sfnt",
+  "application/x-font-ttf",
+  "application/x-font-otf",
+];
+
+const ARCHIVE_MIMES: &[&str] = &[
+  "application/zip",
+  "application/x-zip-compressed",
+  "application/gzip",
+  "application/x-gzip",
+  "application/x-bzip",
+  "application/x-bzip2",
+  "application/x-xz",
+  "application/x-tar",
+  "application/x-7z-compressed",
+  "application/vnd.rar",
+  "application/x-rar-compressed",
+  "application/x-freearc",
+  "application/java-archive",
+  "application/vnd.android.package-archive",
+];
+
+const DOCUMENT_MIMES: &[&str] = &[
+  "application/pdf",
+  "application/x-pdf",
+  "application/rtf",
+  "application/msword",
+  "application/vnd.ms-excel",
+  "application/vnd.ms-powerpoint",
+  "application/vnd.visio",
+  "application/vnd.amazon.ebook",
+  "application/epub+zip",
+  "application/x-abiword",
+];
+
+const SHELL_SCRIPT_MIMES: &[&str] = &[
+  "application/x-sh",
+  "application/x-csh",
+  "application/x-shellscript",
+  "text/x-shellscript",
+];
+
+const EXECUTABLE_MIMES: &[&str] = &[
+  "application/vnd.microsoft.portable-executable",
+  "application/x-msdownload",
+  "application/x-msdos-program",
+  "application/x-msi",
+  "application/x-executable",
+  "application/x-elf",
+  "application/x-sharedlib",
+  "application/x-mach-binary",
+  "application/x-apple-diskimage",
+  "application/vnd.debian.binary-package",
+  "application/x-rpm",
+];
+
+impl MediaKind {
+  pub const ALL: [MediaKind; 19] = [
+    MediaKind::Image,
+    MediaKind::Video,
+    MediaKind::Audio,
+    MediaKind::Font,
+    MediaKind::Archive,
+    MediaKind::WebPage,
+    MediaKind::WebStyle,
+    MediaKind::WebScript,
+    MediaKind::JavaScript,
+    MediaKind::TypeScript,
+    MediaKind::Binary,
+    MediaKind::ShellScript,
+    MediaKind::Text,
+    MediaKind::Document,
+    MediaKind::GifLike,
+    MediaKind::Icon,
+    MediaKind::Pdf,
+    MediaKind::AndroidPackage,
+    MediaKind::Executable,
+  ];
+
+  /// A lowercase name for messages, like "video".
+  pub fn name(&self) -> &'static str {
+    match self {
+      MediaKind::Image => "image",
+      MediaKind::Video => "video",
+      MediaKind::Audio => "audio",
+      MediaKind::Font => "font",
+      MediaKind::Archive => "archive",
+      MediaKind::WebPage => "web page",
+      MediaKind::WebStyle => "stylesheet",
+      MediaKind::WebScript => "web script",
+      MediaKind::JavaScript => "JavaScript",
+      MediaKind::TypeScript => "TypeScript",
+      MediaKind::Binary => "binary",
+      MediaKind::ShellScript => "shell script",
+      MediaKind::Text => "text",
+      MediaKind::Document => "document",
+      MediaKind::GifLike => "animated image",
+      MediaKind::Icon => "icon",
+      MediaKind::Pdf => "PDF",
+      MediaKind::AndroidPackage => "Android app",
+      MediaKind::Executable => "program",
+    }
+  }
+
+  /// Whether `mime`, a lowercase mime without parameters, is of this kind.
+  pub fn contains_mime(&self, mime: &str) -> bool {
+    let (top_level, subtype) = mime.split_once('/').unwrap_or((mime, ""));
+
+    match self {
+      MediaKind::Image => {
+        top_level == "image"
+      }
+      MediaKind::Video => {
+        top_level == "video" || VIDEO_PLAYLIST_MIMES.contains(&mime)
+      }
+      MediaKind::Audio => {
+        top_level == "audio"
+      }
+      MediaKind::Font => {
+        top_level == "font" || FONT_MIMES.contains(&mime)
+      }
+      MediaKind::Archive => {
+        ARCHIVE_MIMES.contains(&mime)
+      }
+      MediaKind::WebPage => {
+        mime == "text/html" || mime == "application/xhtml+xml"
+      }
+      MediaKind::WebStyle => {
+        mime == "text/css"
+      }
+      MediaKind::WebScript => {
+        JAVASCRIPT_MIMES.contains(&mime)
+          || TYPESCRIPT_MIMES.contains(&mime)
+          || mime == "application/wasm"
+      }
+      MediaKind::JavaScript => {
+        JAVASCRIPT_MIMES.contains(&mime)
+      }
+      MediaKind::TypeScript => {
+        TYPESCRIPT_MIMES.contains(&mime)
+      }
+      MediaKind::Binary => {
+        !is_textual(top_level, subtype, mime)
+      }
+      MediaKind::ShellScript => {
+        SHELL_SCRIPT_MIMES.contains(&mime)
+      }
+      MediaKind::Text => {
+        top_level == "text"
+      }
+      MediaKind::Document => {
+        top_level == "text"
+          || DOCUMENT_MIMES.contains(&mime)
+          || subtype.starts_with("vnd.openxmlformats-officedocument.")
+          || subtype.starts_with("vnd.oasis.opendocument.")
+      }
+      MediaKind::GifLike => {
+        mime == "image/gif" || mime == "image/apng"
+      }
+      MediaKind::Icon => {
+        mime == "image/vnd.microsoft.icon" || mime == "image/x-icon"
+      }
+      MediaKind::Pdf => {
+        mime == "application/pdf" || mime == "application/x-pdf"
+      }
+      MediaKind::AndroidPackage => {
+        mime == "application/vnd.android.package-archive"
+      }
+      MediaKind::Executable => {
+        EXECUTABLE_MIMES.contains(&mime)
+      }
+    }
+  }
+}
+
+fn is_textual(top_level: &str, subtype: &str, mime: &str) -> bool {
+  top_level == "text"
+    || subtype == "json"
+    || subtype == "xml"
+    || subtype.ends_with("+json")
+    || subtype.ends_with("+xml")
+    || JAVASCRIPT_MIMES.contains(&mime)
+    || TYPESCRIPT_MIMES.contains(&mime)
+    || SHELL_SCRIPT_MIMES.contains(&mime)
+    || mime == "application/x-www-form-urlencoded"
+}
+
+// SECTION: Detection.
+/// The mimes a response may be. Each comes from a different place, and a
+/// response is of a kind if any of them is, so a response can't get past a
+/// rule by lying in one of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DetectedMedia {
+  /// From the `Content-Type` header.
+  pub declared: Option<String>,
+  /// From the extension of the file name in `Content-Disposition`, or else
+  /// of the last segment of the path.
+  pub by_filename_extension: Option<&'static str>,
+  /// From the signature at the start of the body.
+  pub sniffed: Option<&'static str>,
+}
+
+impl DetectedMedia {
+  pub fn detect(exchange: &Exchange, response: &ResponseHead, body_start: Option<&[u8]>) -> Self {
+    let filename = response
+      .headers
+      .get("Content-Disposition")
+      .and_then(filename_of_content_disposition)
+      .unwrap_or_else(|| {
+        exchange
+          .request
+          .path()
+          .rsplit('/')
+          .next()
+          .unwrap_or_default()
+      });
+
+    Self {
+      declared: response.content_type(),
+      by_filename_extension: filename
+        .rsplit_once('.')
+        .and_then(|(_, extension)| mimes::mime_of_filename_extension(extension)),
+      sniffed: body_start.and_then(sniff_mime),
+    }
+  }
+
+  pub fn mimes(&self) -> impl Iterator<Item = &str> {
+    self
+      .declared
+      .as_deref()
+      .into_iter()
+      .chain(self.by_filename_extension)
+      .chain(self.sniffed)
+  }
+
+  pub fn is(&self, kind: MediaKind) -> bool {
+    self.mimes().any(|mime| kind.contains_mime(mime))
+  }
+}
+
+/// Reads the `filename` parameter of a `Content-Disposition` header, like
+/// `attachment; filename="app.apk"`. `filename*` is skipped, since only the
+/// extension matters and it's the same in both.
+fn filename_of_content_disposition(header: &str) -> Option<&str> {
+  header.split(';').skip(1).find_map(|parameter| {
+    let (name, value) = parameter.split_once('=')?;
+    if !name.trim().eq_ignore_ascii_case("filename") {
+      return None;
+    }
+    Some(value.trim().trim_matches('"'))
+  })
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+  haystack
+    .windows(needle.len())
+    .any(|window| window == needle)
+}
+
+/// Guesses the mime of a body from the signature at its start. Only formats
+/// with a reliable signature are recognized, so text formats aren't.
+pub fn sniff_mime(bytes: &[u8]) -> Option<&'static str> {
+  let starts_with = |signature: &[u8]| bytes.starts_with(signature);
+  let at = |offset: usize, signature: &[u8]| {
+    bytes
+      .get(offset..offset + signature.len())
+      .is_some_and(|found| found == signature)
+  };
+
+  // SECTION: Images.
+  if starts_with(b"\x89PNG\r\n\x1a\n") {
+    // Animated pngs have an animation control chunk before the image data.
+    return Some(if contains(bytes, b"acTL") { "image/apng" } else { "image/png" });
+  }
+  if starts_with(b"\xff\xd8\xff") {
+    return Some("image/jpeg");
+  }
+  if starts_with(b"GIF87a") || starts_with(b"GIF89a") {
+    return Some("image/gif");
+  }
+  if starts_with(b"RIFF") && at(8, b"WEBP") {
+    return Some("image/webp");
+  }
+  if starts_with(b"II*\0") || starts_with(b"MM\0*") {
+    return Some("image/tiff");
+  }
+  if starts_with(b"\0\0\x01\0") && bytes.len() >= 6 {
+    return Some("image/vnd.microsoft.icon");
+  }
+  // "BM" alone is too common, so check the reserved fields are zero too.
+  if starts_with(b"BM") && at(6, b"\0\0\0\0") {
+    return Some("image/bmp");
+  }
+
+  // SECTION: Video and audio.
+  if at(4, b"ftyp") {
+    let brand = bytes.get(8..12).unwrap_or_default();
+    return Some(match brand {
+      b"avif" | b"avis" => "image/avif",
+      b"heic" | b"heix" | b"mif1" | b"msf1" => "image/heic",
+      b"qt  " => "video/quicktime",
+      b"M4A " | b"M4B " => "audio/mp4",
+      brand if brand.starts_with(b"3gp") => "video/3gpp",
+      brand if brand.starts_with(b"3g2") => "video/3gpp2",
+      _ => "video/mp4",
+    });
+  }
+  if starts_with(b"\x1a\x45\xdf\xa3") {
+    return Some(if contains(bytes, b"webm") { "video/webm" } else { "video/x-matroska" });
+  }
+  if starts_with(b"RIFF") && at(8, b"AVI ") {
+    return Some("video/x-msvideo");
+  }
+  if starts_with(b"RIFF") && at(8, b"WAVE") {
+    return Some("audio/wav");
+  }
+  if starts_with(b"OggS") {
+    return Some(if contains(bytes, b"\x80theora") { "video/ogg" } else { "audio/ogg" });
+  }
+  if starts_with(b"FLV\x01") {
+    return Some("video/x-flv");
+  }
+  if starts_with(b"\0\0\x01\xba") || starts_with(b"\0\0\x01\xb3") {
+    return Some("video/mpeg");
+  }
+  // Transport streams are a sequence of 188 byte packets starting with 0x47.
+  if starts_with(b"\x47") && at(188, b"\x47") && (bytes.len() <= 376 || at(376, b"\x47")) {
+    return Some("video/mp2t");
+  }
+  if starts_with(b"#EXTM3U") {
+    return Some("application/vnd.apple.mpegurl");
+  }
+  if starts_with(b"fLaC") {
+    return Some("audio/flac");
+  }
+  if starts_with(b"MThd") {
+    return Some("audio/midi");
+  }
+  if starts_with(b"ID3") {
+    return Some("audio/mpeg");
+  }
+  if let [0xff, second, ..] = bytes {
+    if second & 0xf0 == 0xf0 && second & 0x06 == 0 {
+      return Some("audio/aac");
+    }
+    if second & 0xe0 == 0xe0 && second & 0x06 != 0 {
+      return Some("audio/mpeg");
+    }
+  }
+
+  // SECTION: Fonts.
+  if starts_with(b"wOFF") {
+    return Some("font/woff");
+  }
+  if starts_with(b"wOF2") {
+    return Some("font/woff2");
+  }
+  if starts_with(b"OTTO") {
+    return Some("font/otf");
+  }
+  if starts_with(b"\0\x01\0\0\0") {
+    return Some("font/ttf");
+  }
+
+  // SECTION: Documents.
+  if starts_with(b"%PDF-") {
+    return Some("application/pdf");
+  }
+  if starts_with(b"{\\rtf") {
+    return Some("application/rtf");
+  }
+
+  // SECTION: Archives and packages.
+  if starts_with(b"PK\x03\x04") {
+    // The names of the first entries tell what the zip is for.
+    if contains(bytes, b"AndroidManifest.xml") || contains(bytes, b"classes.dex") {
+      return Some("application/vnd.android.package-archive");
+    }
+    if contains(bytes, b"mimetypeapplication/epub+zip") {
+      return Some("application/epub+zip");
+    }
+    if contains(bytes, b"META-INF/MANIFEST.MF") {
+      return Some("application/java-archive");
+    }
+    return Some("application/zip");
+  }
+  if starts_with(b"\x1f\x8b") {
+    return Some("application/gzip");
+  }
+  if starts_with(b"BZh") {
+    return Some("application/x-bzip2");
+  }
+  if starts_with(b"\xfd7zXZ\0") {
+    return Some("application/x-xz");
+  }
+  if starts_with(b"7z\xbc\xaf\x27\x1c") {
+    return Some("application/x-7z-compressed");
+  }
+  if starts_with(b"Rar!\x1a\x07") {
+    return Some("application/vnd.rar");
+  }
+  if at(257, b"ustar") {
+    return Some("application/x-tar");
+  }
+  if starts_with(b"!<arch>\ndebian") {
+    return Some("application/vnd.debian.binary-package");
+  }
+  if starts_with(b"\xed\xab\xee\xdb") {
+    return Some("application/x-rpm");
+  }
+
+  // SECTION: Executables.
+  if starts_with(b"\x7fELF") {
+    return Some("application/x-executable");
+  }
+  if starts_with(b"MZ") && bytes.len() >= 64 {
+    return Some("application/vnd.microsoft.portable-executable");
+  }
+  if starts_with(b"\xfe\xed\xfa\xce")
+    || starts_with(b"\xfe\xed\xfa\xcf")
+    || starts_with(b"\xce\xfa\xed\xfe")
+    || starts_with(b"\xcf\xfa\xed\xfe")
+  {
+    return Some("application/x-mach-binary");
+  }
+  if starts_with(b"\0asm") {
+    return Some("application/wasm");
+  }
+
+  None
+}
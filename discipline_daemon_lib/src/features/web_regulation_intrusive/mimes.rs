@@ -6,8 +6,8 @@ pub struct Mime<'a> {
 
 static AUDIO_AAC: Mime<'static> = Mime {
   name: "Advanced Audio Coding",
-  mime: "audio/acc",
-  filename_extensions: &["acc"],
+  mime: "audio/aac",
+  filename_extensions: &["aac"],
 };
 
 static APPLICATION_X_ABIWORD: Mime<'static> = Mime {
@@ -18,7 +18,7 @@ static APPLICATION_X_ABIWORD: Mime<'static> = Mime {
 
 static APPLICATION_X_FREEARC: Mime<'static> = Mime {
   name: "FreeArc",
-  mime: "application-x-freearc",
+  mime: "application/x-freearc",
   filename_extensions: &["arc"],
 };
 
@@ -182,7 +182,7 @@ static AUDIO_MIDI: Mime<'static> = Mime {
 
 static AUDIO_X_MIDI: Mime<'static> = Mime {
   name: "Musical Instrument Digital Interface (MIDI)",
-  mime: "audio/midi",
+  mime: "audio/x-midi",
   filename_extensions: &["mid", "midi"],
 };
 
@@ -466,27 +466,220 @@ static APPLICATION_XML: Mime<'static> = Mime {
 // but this is still used sometimes. 
 static TEXT_XML: Mime<'static> = Mime {
   name: "Extensible Markup Language",
-  mime: "text/html",
+  mime: "text/xml",
   filename_extensions: &["xml"]
 };
 
 
+static APPLICATION_VND_ANDROID_PACKAGE_ARCHIVE: Mime<'static> = Mime {
+  name: "Android Package",
+  mime: "application/vnd.android.package-archive",
+  filename_extensions: &["apk"],
+};
+
+static APPLICATION_VND_MICROSOFT_PORTABLE_EXECUTABLE: Mime<'static> = Mime {
+  name: "Windows Executable",
+  mime: "application/vnd.microsoft.portable-executable",
+  filename_extensions: &["exe", "dll"],
+};
 
+static APPLICATION_X_MSI: Mime<'static> = Mime {
+  name: "Windows Installer Package",
+  mime: "application/x-msi",
+  filename_extensions: &["msi"],
+};
 
-pub enum MediaKind {
-  Audio,
-  Video,
-  Image,
-  GIF,
-}
+static APPLICATION_X_APPLE_DISKIMAGE: Mime<'static> = Mime {
+  name: "Apple Disk Image",
+  mime: "application/x-apple-diskimage",
+  filename_extensions: &["dmg"],
+};
 
-pub fn moon<'a>() {
-  let mimes = Vec::<Media<'a>>::new();
+static APPLICATION_VND_DEBIAN_BINARY_PACKAGE: Mime<'static> = Mime {
+  name: "Debian Package",
+  mime: "application/vnd.debian.binary-package",
+  filename_extensions: &["deb"],
+};
+
+static APPLICATION_X_RPM: Mime<'static> = Mime {
+  name: "RPM Package",
+  mime: "application/x-rpm",
+  filename_extensions: &["rpm"],
+};
+
+static APPLICATION_X_XZ: Mime<'static> = Mime {
+  name: "XZ archive",
+  mime: "application/x-xz",
+  filename_extensions: &["xz"],
+};
+
+static APPLICATION_WASM: Mime<'static> = Mime {
+  name: "WebAssembly",
+  mime: "application/wasm",
+  filename_extensions: &["wasm"],
+};
 
-  let mut mime = Media {
-    media_name: "Advanced Audio Coding",
-    mimes: &["audio/aac"],
-    filename_extensions: &["aac"],
-    media_kinds: &[MediaKind::Audio]
-  };
-}
\ No newline at end of file
+static AUDIO_FLAC: Mime<'static> = Mime {
+  name: "Free Lossless Audio Codec",
+  mime: "audio/flac",
+  filename_extensions: &["flac"],
+};
+
+static AUDIO_MP4: Mime<'static> = Mime {
+  name: "MPEG-4 audio",
+  mime: "audio/mp4",
+  filename_extensions: &["m4a"],
+};
+
+static VIDEO_QUICKTIME: Mime<'static> = Mime {
+  name: "QuickTime video",
+  mime: "video/quicktime",
+  filename_extensions: &["mov"],
+};
+
+static VIDEO_X_MATROSKA: Mime<'static> = Mime {
+  name: "Matroska video",
+  mime: "video/x-matroska",
+  filename_extensions: &["mkv"],
+};
+
+static VIDEO_X_FLV: Mime<'static> = Mime {
+  name: "Flash video",
+  mime: "video/x-flv",
+  filename_extensions: &["flv"],
+};
+
+static APPLICATION_VND_APPLE_MPEGURL: Mime<'static> = Mime {
+  name: "HTTP Live Streaming playlist",
+  mime: "application/vnd.apple.mpegurl",
+  filename_extensions: &["m3u8"],
+};
+
+static APPLICATION_DASH_XML: Mime<'static> = Mime {
+  name: "MPEG-DASH manifest",
+  mime: "application/dash+xml",
+  filename_extensions: &["mpd"],
+};
+
+static IMAGE_APNG: Mime<'static> = Mime {
+  name: "Animated Portable Network Graphics",
+  mime: "image/apng",
+  filename_extensions: &["apng"],
+};
+
+static IMAGE_HEIC: Mime<'static> = Mime {
+  name: "High Efficiency Image File Format",
+  mime: "image/heic",
+  filename_extensions: &["heic", "heif"],
+};
+
+/// Every known mime, in no particular order.
+pub static MIMES: &[&Mime<'static>] = &[
+  &AUDIO_AAC,
+  &APPLICATION_X_ABIWORD,
+  &APPLICATION_X_FREEARC,
+  &IMAGE_AVIF,
+  &VIDEO_X_MSVIDEO,
+  &APPLICATION_VND_AMAZON_EBOOK,
+  &APPLICATION_OCTET_STREAM,
+  &IMAGE_BMP,
+  &APPLICATION_X_BZIP,
+  &APPLICATION_X_BZIP2,
+  &APPLICATION_X_CDF,
+  &APPLICATION_X_CSH,
+  &TEXT_CSS,
+  &TEXT_CSV,
+  &APPLICATION_MSWORD,
+  &APPLICATION_VND_OPENXMLFORMATS_OFFICEDOCUMENT_WORDPROCESSINGML_DOCUMENT,
+  &APPLICATION_VND_MS_FONTOBJECT,
+  &APPLICATION_EPUB_ZIP,
+  &APPLICATION_GZIP,
+  &IMAGE_GIF,
+  &TEXT_HTML,
+  &IMAGE_VND_MICROSOFT_ICON,
+  &TEXT_CALENDAR,
+  &APPLICATION_JAVA_ARCHIVE,
+  &IMAGE_JPEG,
+  &APPLICATION_JAVASCRIPT,
+  &APPLICATION_JSON,
+  &APPLICATION_LD_JSON,
+  &AUDIO_MIDI,
+  &AUDIO_X_MIDI,
+  &TEXT_JAVASCRIPT,
+  &AUDIO_MPEG,
+  &VIDEO_MP4,
+  &VIDEO_MPEG,
+  &APPLICATION_VND_APPLE_INSTALLER_XML,
+  &APPLICATION_VND_OASIS_OPENDOCUMENT_PRESENTATION,
+  &APPLICATION_VND_OASIS_OPENDOCUMENT_SPREADSHEET,
+  &APPLICATION_VND_OASIS_OPENDOCUMENT_TEXT,
+  &AUDIO_OGG,
+  &VIDEO_OGG,
+  &APPLICATION_OGG,
+  &AUDIO_OPUS,
+  &FONT_OTF,
+  &IMAGE_PNG,
+  &APPLICATION_PDF,
+  &APPLICATION_X_HTTP_PHP,
+  &APPLICATION_VND_MS_POWERPOINT,
+  &APPLICATION_VND_OPENXMLFORMATS_OFFIECEDOCUMENT_PRESENTATIONML_PRESENTATION,
+  &APPLICATION_VND_RAR,
+  &APPLICATION_RTF,
+  &APPLICATION_X_SH,
+  &IMAGE_SVG_XML,
+  &APPLICATION_X_TAR,
+  &IMAGE_TIFF,
+  &VIDEO_MP2T,
+  &FONT_TTF,
+  &TEXT_PLAIN,
+  &APPLICATION_VND_VISIO,
+  &AUDIO_WAV,
+  &AUDIO_WEBM,
+  &VIDEO_WEBM,
+  &IMAGE_WEBP,
+  &FONT_WOFF,
+  &FONT_WOFF2,
+  &APPLICATION_XHTML_XML,
+  &APPLICATION_VND_MS_EXCEL,
+  &APPLICATION_VND_OPENXMLFORMATS_OFFICEDOCUMENT_SPREADSHEETML_SHEET,
+  &APPLICATION_VND_MOZILLA_XUL_XML,
+  &APPLICATION_ZIP,
+  &VIDEO_3GPP,
+  &AUDIO_3GPP,
+  &VIDEO_3GPP2,
+  &AUDIO_3GPP2,
+  &APPLICATION_X_7Z_COMPRESSED,
+  &APPLICATION_XML,
+  &TEXT_XML,
+  &APPLICATION_VND_ANDROID_PACKAGE_ARCHIVE,
+  &APPLICATION_VND_MICROSOFT_PORTABLE_EXECUTABLE,
+  &APPLICATION_X_MSI,
+  &APPLICATION_X_APPLE_DISKIMAGE,
+  &APPLICATION_VND_DEBIAN_BINARY_PACKAGE,
+  &APPLICATION_X_RPM,
+  &APPLICATION_X_XZ,
+  &APPLICATION_WASM,
+  &AUDIO_FLAC,
+  &AUDIO_MP4,
+  &VIDEO_QUICKTIME,
+  &VIDEO_X_MATROSKA,
+  &VIDEO_X_FLV,
+  &APPLICATION_VND_APPLE_MPEGURL,
+  &APPLICATION_DASH_XML,
+  &IMAGE_APNG,
+  &IMAGE_HEIC,
+];
+
+/// Returns the mime of files with `extension`, which is matched
+/// case-insensitively and without the leading '.'.
+pub fn mime_of_filename_extension(extension: &str) -> Option<&'static str> {
+  MIMES
+    .iter()
+    .find(|mime| {
+      mime
+        .filename_extensions
+        .iter()
+        .any(|known| known.eq_ignore_ascii_case(extension))
+    })
+    .map(|mime| mime.mime)
+}
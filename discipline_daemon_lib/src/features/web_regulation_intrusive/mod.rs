@@ -21,202 +21,289 @@
 /// - blur or replace them if they are images
 /// - blur them or remove the sound if they are videos
 
-mod media_types;
-
-use serde::{Deserialize, Serialize};
-use crate::{DateTime, Hour, Uuid, Weekday};
-use super::{CountdownTimer, TimeRange, PasswordAuthenticationStatus, WeekdayRange};
-
-// SECTION: Protector.
-#[derive(Debug, Clone)]
-pub enum Protector {
-  AtWeekday(Weekday),
-  NotAtWeekday(Weekday),
-  InTimeRange(TimeRange),
-  NotInTimeRange(TimeRange),
-  AtHour(Hour),
-  NotAtHour(Hour),
-  CountdownTimer(CountdownTimer),
-  InWeekdayRange(WeekdayRange),
-  NotInWeekdayRange(WeekdayRange),
-  PasswordAuthenticationStatus(PasswordAuthenticationStatus),
-}
-
-impl Protector {
-  pub fn is_protecting(&mut self, now: DateTime) -> bool {
-    match self {
-      Protector::AtWeekday(weekday) => {
-        now.weekday() == *weekday
-      }
-      Protector::NotAtWeekday(weekday) => {
-        now.weekday() != *weekday
-      }
-      Protector::AtHour(hour) => {
-        now.hour() == *hour
-      }
-      Protector::NotAtHour(hour) => {
-        now.hour() != *hour
-      }
-      Protector::InTimeRange(time_range) => {
-        time_range.contains(now.time())
-      }
-      Protector::NotInTimeRange(time_range) => {
-        !time_range.contains(now.time())
-      }
-      Protector::CountdownTimer(countdown) => {
-        !countdown.is_finished()
-      }
-      Protector::InWeekdayRange(weekday_range) => {
-        weekday_range.contains(now.weekday())
-      }
-      Protector::NotInWeekdayRange(weekday_range) => {
-        !weekday_range.contains(now.weekday())
-      }
-      Protector::PasswordAuthenticationStatus(password_authentication_status) => {
-        password_authentication_status.is_locked()
-      }
-    }
-  }  
-}
-
-// SECTION: Activator.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum Activator {
-  AtWeekday(Weekday),
-  NotAtWeekday(Weekday),
-  InTimeRange(TimeRange),
-  NotInTimeRange(TimeRange),
-  AtHour(Hour),
-  NotAtHour(Hour),
-  CountdownTimer(CountdownTimer),
-  InWeekdayRange(WeekdayRange),
-  NotInWeekdayRange(WeekdayRange),
-}
-
-impl Activator {
-  pub fn is_activated(&mut self, now: DateTime) -> bool {
-    match self {
-      Activator::AtWeekday(weekday) => {
-        now.weekday() == *weekday
-      }
-      Activator::NotAtWeekday(weekday) => {
-        now.weekday() != *weekday
-      }
-      Activator::AtHour(hour) => {
-        now.hour() == *hour
-      }
-      Activator::NotAtHour(hour) => {
-        now.hour() != *hour
-      }
-      Activator::InTimeRange(time_range) => {
-        time_range.contains(now.time())
-      }
-      Activator::NotInTimeRange(time_range) => {
-        !time_range.contains(now.time())
-      }
-      Activator::CountdownTimer(countdown) => {
-        countdown.synchronize(now);
-        countdown.is_running()
-      }
-      Activator::InWeekdayRange(weekday_range) => {
-        weekday_range.contains(now.weekday())
-      }
-      Activator::NotInWeekdayRange(weekday_range) => {
-        !weekday_range.contains(now.weekday())
-      }
-    }
-  }
-}
-
-// SECTION: Action.
-pub enum Action {
-  Allow,
-  Block,
-}
-
-// SECTION: MediaTypeRule.
-pub enum MediaType {
-  AllImageTypes,
-  AllVideoTypes,
-  AllAudioTypes,
-
-}
-
-pub struct MediaTypeRule {
-  id: Uuid,
-  protector: Protector, 
-  activator: Activator, 
-  media_type: MediaType,
-  action: Action,
-  message_head_matcher: (),
-  actions: (),
-}
-// 
-pub enum RuleAction {
-  BlockMediaTypes,
-  AllowMediaTypes,
-
-}
-// [block/allow] [message/media types] when [always/never/countdown timer/hour/uri/]
-// []
-pub struct GoogleSafeSearchRule {
-  id: Uuid,
-  activator: (),
-  protector: (),
-  action: GoogleSafeSearchAction,
-}
-
-pub enum GoogleSafeSearchAction {
-  Blur,
-  Filter,
-  Disable,
-}
-
-pub struct Enforcer {
-  rules: Vec<Rule>,
-}
-
-
-// GoogleSafeSearch
-// YahooSafeSearch
-// BingSafeSearch
-// Block youtube videos
-// Allow youtube videos
-// Allow youtube videos
-// Allow Urls
-// Block Urls
-// Block media types
-// allow media types
-
-
-pub struct Rule {
-  id: Uuid,
-  
-  action: Action,
-}
-
-pub enum Matcher {
-  
-  String(String),
-  Boolean(bool),
-  Number(i64),
-  DateTime(DateTime),
-  BeforeDateTime(DateTime),
-  AfterDateTime(DateTime),
-}
-
-pub struct StringMatcher {
-  string: String,
-  is_case_sensitive: bool,
-}
-
-pub struct StringStartMatcher {
-  string: String,
-  is_case_sensitive: bool
-}
-
-pub struct StringEndMatcher {
-  string: String,
-  is_case_sesnitive: bool,
-}
+pub mod mimes;
 
+pub mod media_types;
+pub use media_types::{DetectedMedia, MediaKind};
+
+pub mod rule_activator;
+pub use rule_activator::RuleActivator;
+
+pub mod certificate_authority;
+pub use certificate_authority::CertificateAuthority;
+
+pub mod traffic;
+pub use traffic::{
+  BodyRewriter,
+  BodyVerdict,
+  Exchange,
+  Headers,
+  RequestHead,
+  RequestVerdict,
+  Response,
+  ResponseHead,
+  ResponseVerdict,
+  Scheme,
+  TrafficHandler,
+};
+
+mod http1;
+mod http2;
+
+pub mod url_pattern;
+pub use url_pattern::{HostPattern, HostPatternSet, PathPrefix, UrlPattern, UrlPatternSet, UrlScoped, UrlScopedList};
+
+pub mod no_intercept;
+pub use no_intercept::NoInterceptHosts;
+
+pub(crate) mod block_page;
+
+mod content_security_policy;
+
+pub mod website_visits_limiter;
+pub use website_visits_limiter::WebsiteVisitsLimiter;
+
+pub mod website_visit_delayer;
+pub use website_visit_delayer::WebsiteVisitDelayer;
+
+pub mod youtube;
+pub use youtube::YoutubeRegulation;
+
+pub mod twitter;
+pub use twitter::TwitterRegulation;
+
+pub mod media_type_blocker;
+pub use media_type_blocker::MediaTypeBlocker;
+
+pub mod element_rules;
+pub use element_rules::ElementRegulation;
+
+pub mod content_filter;
+pub use content_filter::ContentFilter;
+
+pub mod safe_search;
+pub use safe_search::SafeSearch;
+
+pub mod search_queries;
+pub use search_queries::SearchQueryBlocker;
+
+pub mod view_time_allowance;
+pub use view_time_allowance::ViewTimeAllowances;
+
+pub mod image_rules;
+pub use image_rules::ImageRegulation;
+
+pub mod protobuf;
+pub use protobuf::ProtobufRegulation;
+
+pub mod encrypted_dns;
+pub use encrypted_dns::EncryptedDnsPrevention;
+
+pub mod audit_log;
+pub use audit_log::AuditLog;
+
+pub mod proxy_auto_config;
+pub use proxy_auto_config::ProxyAutoConfig;
+
+mod proxy;
+pub use proxy::Proxy;
+
+#[cfg(test)]
+mod tests;
+
+// use serde::{Deserialize, Serialize};
+// use crate::{DateTime, Hour, Uuid, Weekday};
+// use super::{CountdownTimer, TimeRange, PasswordAuthenticationStatus, WeekdayRange};
+//
+// // SECTION: Protector.
+// #[derive(Debug, Clone)]
+// pub enum Protector {
+//   AtWeekday(Weekday),
+//   NotAtWeekday(Weekday),
+//   InTimeRange(TimeRange),
+//   NotInTimeRange(TimeRange),
+//   AtHour(Hour),
+//   NotAtHour(Hour),
+//   CountdownTimer(CountdownTimer),
+//   InWeekdayRange(WeekdayRange),
+//   NotInWeekdayRange(WeekdayRange),
+//   PasswordAuthenticationStatus(PasswordAuthenticationStatus),
+// }
+//
+// impl Protector {
+//   pub fn is_protecting(&mut self, now: DateTime) -> bool {
+//     match self {
+//       Protector::AtWeekday(weekday) => {
+//         now.weekday() == *weekday
+//       }
+//       Protector::NotAtWeekday(weekday) => {
+//         now.weekday() != *weekday
+//       }
+//       Protector::AtHour(hour) => {
+//         now.hour() == *hour
+//       }
+//       Protector::NotAtHour(hour) => {
+//         now.hour() != *hour
+//       }
+//       Protector::InTimeRange(time_range) => {
+//         time_range.contains(now.time())
+//       }
+//       Protector::NotInTimeRange(time_range) => {
+//         !time_range.contains(now.time())
+//       }
+//       Protector::CountdownTimer(countdown) => {
+//         !countdown.is_finished()
+//       }
+//       Protector::InWeekdayRange(weekday_range) => {
+//         weekday_range.contains(now.weekday())
+//       }
+//       Protector::NotInWeekdayRange(weekday_range) => {
+//         !weekday_range.contains(now.weekday())
+//       }
+//       Protector::PasswordAuthenticationStatus(password_authentication_status) => {
+//         password_authentication_status.is_locked()
+//       }
+//     }
+//   }  
+// }
+//
+// // SECTION: Activator.
+// #[derive(Debug, Clone, Serialize, Deserialize)]
+// pub enum Activator {
+//   AtWeekday(Weekday),
+//   NotAtWeekday(Weekday),
+//   InTimeRange(TimeRange),
+//   NotInTimeRange(TimeRange),
+//   AtHour(Hour),
+//   NotAtHour(Hour),
+//   CountdownTimer(CountdownTimer),
+//   InWeekdayRange(WeekdayRange),
+//   NotInWeekdayRange(WeekdayRange),
+// }
+//
+// impl Activator {
+//   pub fn is_activated(&mut self, now: DateTime) -> bool {
+//     match self {
+//       Activator::AtWeekday(weekday) => {
+//         now.weekday() == *weekday
+//       }
+//       Activator::NotAtWeekday(weekday) => {
+//         now.weekday() != *weekday
+//       }
+//       Activator::AtHour(hour) => {
+//         now.hour() == *hour
+//       }
+//       Activator::NotAtHour(hour) => {
+//         now.hour() != *hour
+//       }
+//       Activator::InTimeRange(time_range) => {
+//         time_range.contains(now.time())
+//       }
+//       Activator::NotInTimeRange(time_range) => {
+//         !time_range.contains(now.time())
+//       }
+//       Activator::CountdownTimer(countdown) => {
+//         countdown.synchronize(now);
+//         countdown.is_running()
+//       }
+//       Activator::InWeekdayRange(weekday_range) => {
+//         weekday_range.contains(now.weekday())
+//       }
+//       Activator::NotInWeekdayRange(weekday_range) => {
+//         !weekday_range.contains(now.weekday())
+//       }
+//     }
+//   }
+// }
+//
+// // SECTION: Action.
+// pub enum Action {
+//   Allow,
+//   Block,
+// }
+//
+// // SECTION: MediaTypeRule.
+// pub enum MediaType {
+//   AllImageTypes,
+//   AllVideoTypes,
+//   AllAudioTypes,
+//
+// }
+//
+// pub struct MediaTypeRule {
+//   id: Uuid,
+//   protector: Protector, 
+//   activator: Activator, 
+//   media_type: MediaType,
+//   action: Action,
+//   message_head_matcher: (),
+//   actions: (),
+// }
+// // 
+// pub enum RuleAction {
+//   BlockMediaTypes,
+//   AllowMediaTypes,
+//
+// }
+// // [block/allow] [message/media types] when [always/never/countdown timer/hour/uri/]
+// // []
+// pub struct GoogleSafeSearchRule {
+//   id: Uuid,
+//   activator: (),
+//   protector: (),
+//   action: GoogleSafeSearchAction,
+// }
+//
+// pub enum GoogleSafeSearchAction {
+//   Blur,
+//   Filter,
+//   Disable,
+// }
+//
+// pub struct Enforcer {
+//   rules: Vec<Rule>,
+// }
+//
+//
+// // GoogleSafeSearch
+// // YahooSafeSearch
+// // BingSafeSearch
+// // Block youtube videos
+// // Allow youtube videos
+// // Allow youtube videos
+// // Allow Urls
+// // Block Urls
+// // Block media types
+// // allow media types
+//
+//
+// pub struct Rule {
+//   id: Uuid,
+//
+//   action: Action,
+// }
+//
+// pub enum Matcher {
+//
+//   String(String),
+//   Boolean(bool),
+//   Number(i64),
+//   DateTime(DateTime),
+//   BeforeDateTime(DateTime),
+//   AfterDateTime(DateTime),
+// }
+//
+// pub struct StringMatcher {
+//   string: String,
+//   is_case_sensitive: bool,
+// }
+//
+// pub struct StringStartMatcher {
+//   string: String,
+//   is_case_sensitive: bool
+// }
+//
+// pub struct StringEndMatcher {
+//   string: String,
+//   is_case_sesnitive: bool,
+// }
@@ -0,0 +1,77 @@
+use super::url_pattern::{HostPattern, HostPatternSet};
+
+/// Hosts whose clients pin certificates or otherwise refuse to talk through
+/// an intercepting proxy. Their traffic is always tunneled untouched.
+static BUILT_IN_HOST_PATTERNS: &[&str] = &[
+  "*.apple.com",
+  "*.icloud.com",
+  "*.mzstatic.com",
+  "*.dropbox.com",
+  "*.dropboxapi.com",
+  "*.1password.com",
+  "*.bitwarden.com",
+  "*.signal.org",
+  "*.whatsapp.net",
+  "*.windowsupdate.com",
+  "*.update.microsoft.com",
+  "*.ubuntu.com",
+  "*.snapcraft.io",
+  "*.flathub.org",
+  "*.fedoraproject.org",
+  "*.archlinux.org",
+  "*.debian.org",
+];
+
+/// Decides which connections to tunnel without decrypting them.
+#[derive(Debug, Clone)]
+pub struct NoInterceptHosts {
+  built_in: Vec<HostPattern>,
+  user_added: Vec<HostPattern>,
+  /// Both of the above, compiled.
+  matcher: HostPatternSet,
+}
+
+impl NoInterceptHosts {
+  pub fn new(user_added: Vec<HostPattern>) -> Self {
+    let mut no_intercept_hosts = Self {
+      built_in: BUILT_IN_HOST_PATTERNS
+        .iter()
+        .filter_map(|pattern| HostPattern::parse(pattern).ok())
+        .collect(),
+      user_added,
+      matcher: HostPatternSet::default(),
+    };
+
+    no_intercept_hosts.compile();
+    no_intercept_hosts
+  }
+
+  fn compile(&mut self) {
+    self.matcher = HostPatternSet::new(self.built_in.iter().chain(self.user_added.iter()));
+  }
+
+  pub fn is_intercepted(&self, host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    !self.matcher.is_match(&host)
+  }
+
+  pub fn user_added(&self) -> &Vec<HostPattern> {
+    &self.user_added
+  }
+
+  pub fn contains(&self, pattern: &HostPattern) -> bool {
+    self.user_added.contains(pattern)
+  }
+
+  pub fn add(&mut self, pattern: HostPattern) {
+    if !self.contains(&pattern) {
+      self.user_added.push(pattern);
+      self.compile();
+    }
+  }
+
+  pub fn remove(&mut self, pattern: &HostPattern) {
+    self.user_added.retain(|other| other != pattern);
+    self.compile();
+  }
+}
@@ -0,0 +1,535 @@
+use std::sync::{Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+use crate::database::web_regulation_intrusive_protobuf_field_name as field_name_db;
+use crate::database::web_regulation_intrusive_protobuf_rule as rule_db;
+use crate::operating_system_integration::UserId;
+use crate::{Daemon, Database, DateTime, GenericError, Uuid};
+use super::super::rule_activator::RuleActivator;
+use super::super::traffic::*;
+use super::super::url_pattern::{PathPattern, PathPrefix, UrlPattern, UrlScoped, UrlScopedList};
+use super::wire::{self, FieldPath, Framing, Interpretation, Value};
+
+pub const MAXIMUM_RULES_PER_USER: usize = 100;
+pub const MAXIMUM_FIELD_NAMES_PER_USER: usize = 1000;
+pub const MAXIMUM_NAME_LENGTH: usize = 100;
+pub const MAXIMUM_OPERAND_LENGTH: usize = 1000;
+
+/// Which of the messages of an exchange a field belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+  Request,
+  Response,
+}
+
+// SECTION: Field names.
+/// A name a user gave to a field of the messages of some methods, along with
+/// how to interpret it, since messages carry only field numbers.
+#[derive(Debug, Clone)]
+pub struct FieldName {
+  id: Uuid,
+  user_id: UserId,
+  /// The path of the methods, like `/package.Service/Method` for gRPC.
+  method: PathPrefix,
+  direction: Direction,
+  path: FieldPath,
+  name: String,
+  interpretation: Interpretation,
+}
+
+impl FieldName {
+  #[allow(clippy::too_many_arguments)]
+  pub fn from_fields(
+    id: Uuid,
+    user_id: UserId,
+    method: PathPrefix,
+    direction: Direction,
+    path: FieldPath,
+    name: String,
+    interpretation: Interpretation,
+  ) -> Self {
+    Self {
+      id,
+      user_id,
+      method,
+      direction,
+      path,
+      name,
+      interpretation,
+    }
+  }
+
+  pub fn id(&self) -> &Uuid {
+    &self.id
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn method(&self) -> &PathPrefix {
+    &self.method
+  }
+
+  pub fn direction(&self) -> Direction {
+    self.direction
+  }
+
+  pub fn path(&self) -> &FieldPath {
+    &self.path
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn interpretation(&self) -> Interpretation {
+    self.interpretation
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldNameCreator {
+  pub id: Option<Uuid>,
+  pub method: String,
+  pub direction: Direction,
+  pub path: String,
+  pub name: String,
+  pub interpretation: Interpretation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum FieldNameCreatorError {
+  InvalidMethod,
+  InvalidPath,
+  /// The name is empty, too long, or looks like a field path.
+  InvalidName,
+}
+
+impl FieldNameCreator {
+  pub fn create(self, user_id: UserId) -> Result<FieldName, FieldNameCreatorError> {
+    let method = PathPrefix::new(self.method)
+      .map_err(|_| FieldNameCreatorError::InvalidMethod)?;
+
+    let path = FieldPath::parse(&self.path)
+      .map_err(|_| FieldNameCreatorError::InvalidPath)?;
+
+    let name = self.name.trim();
+    if name.is_empty() || name.len() > MAXIMUM_NAME_LENGTH || FieldPath::parse(name).is_ok() {
+      return Err(FieldNameCreatorError::InvalidName);
+    }
+
+    Ok(FieldName::from_fields(
+      self.id.unwrap_or_else(Uuid::new_v4),
+      user_id,
+      method,
+      self.direction,
+      path,
+      name.to_string(),
+      self.interpretation,
+    ))
+  }
+}
+
+/// The name `user_id` gave the field at `path` of the messages of `method`.
+pub fn name_of(
+  field_names: &[FieldName],
+  user_id: UserId,
+  method: &str,
+  direction: Direction,
+  path: &FieldPath,
+) -> Option<String> {
+  field_names
+    .iter()
+    .find(|field_name| {
+      field_name.user_id == user_id
+        && field_name.direction == direction
+        && field_name.path == *path
+        && field_name.method.matches(method)
+    })
+    .map(|field_name| field_name.name.clone())
+}
+
+// SECTION: Rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Operator {
+  /// The field is there and has the interpretation.
+  Present,
+  /// The field's value, written out, is the operand. Numbers are compared as
+  /// numbers.
+  Equals,
+  /// The field's value, written out, has the operand in it, ignoring case.
+  Contains,
+  AtLeast,
+  AtMost,
+}
+
+/// Blocks exchanges with a url matching a pattern whose messages have a
+/// field whose value satisfies a condition, while its activator is
+/// effective. The path of the pattern, if any, names the methods.
+#[derive(Debug, Clone)]
+pub struct Rule {
+  id: Uuid,
+  user_id: UserId,
+  url_pattern: UrlPattern,
+  direction: Direction,
+  path: FieldPath,
+  interpretation: Interpretation,
+  operator: Operator,
+  operand: String,
+  activator: RuleActivator,
+}
+
+impl Rule {
+  #[allow(clippy::too_many_arguments)]
+  pub fn from_fields(
+    id: Uuid,
+    user_id: UserId,
+    url_pattern: UrlPattern,
+    direction: Direction,
+    path: FieldPath,
+    interpretation: Interpretation,
+    operator: Operator,
+    operand: String,
+    activator: RuleActivator,
+  ) -> Self {
+    Self {
+      id,
+      user_id,
+      url_pattern,
+      direction,
+      path,
+      interpretation,
+      operator,
+      operand,
+      activator,
+    }
+  }
+
+  pub fn id(&self) -> &Uuid {
+    &self.id
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn url_pattern(&self) -> &UrlPattern {
+    &self.url_pattern
+  }
+
+  pub fn direction(&self) -> Direction {
+    self.direction
+  }
+
+  pub fn path(&self) -> &FieldPath {
+    &self.path
+  }
+
+  pub fn interpretation(&self) -> Interpretation {
+    self.interpretation
+  }
+
+  pub fn operator(&self) -> Operator {
+    self.operator
+  }
+
+  pub fn operand(&self) -> &str {
+    &self.operand
+  }
+
+  pub fn activator(&self) -> &RuleActivator {
+    &self.activator
+  }
+
+  /// Whether the rule looks at the messages going in `direction` in
+  /// `exchange`, which its url pattern matches, at `now`.
+  pub fn applies_to(&self, exchange: &Exchange, direction: Direction, now: DateTime) -> bool {
+    exchange.user_id == Some(self.user_id)
+      && self.direction == direction
+      && self.activator.is_effective(now)
+  }
+
+  fn is_satisfied_by(&self, value: &Value) -> bool {
+    match self.operator {
+      Operator::Present => {
+        true
+      }
+      Operator::Equals => {
+        match (value.as_number(), self.operand.trim().parse::<f64>()) {
+          (Some(number), Ok(operand)) => number == operand,
+          _ => value.to_string() == self.operand,
+        }
+      }
+      Operator::Contains => {
+        value.to_string().to_lowercase().contains(&self.operand.to_lowercase())
+      }
+      Operator::AtLeast | Operator::AtMost => {
+        let (Some(number), Ok(operand)) = (value.as_number(), self.operand.trim().parse::<f64>()) else {
+          return false;
+        };
+
+        if self.operator == Operator::AtLeast {
+          number >= operand
+        } else {
+          number <= operand
+        }
+      }
+    }
+  }
+
+  /// Whether a value of the field in `message` satisfies the condition.
+  pub fn matches(&self, message: &[u8]) -> bool {
+    wire::values_at(message, self.path.numbers())
+      .into_iter()
+      .filter_map(|value| self.interpretation.read(value))
+      .any(|value| self.is_satisfied_by(&value))
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleCreator {
+  pub id: Option<Uuid>,
+  /// Like `*.example.com/search.Search/Query` for the `Query` method of
+  /// the `search.Search` gRPC service.
+  pub url_pattern: String,
+  pub direction: Direction,
+  /// A field path, like `2.1`, or the name of a field of the messages of
+  /// the method that is the url pattern's path.
+  pub field: String,
+  /// How to interpret the field. Required for field paths, and overrides the
+  /// interpretation of named fields.
+  pub interpretation: Option<Interpretation>,
+  pub operator: Operator,
+  pub operand: String,
+  pub activator: RuleActivator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RuleCreatorError {
+  InvalidUrlPattern,
+  /// The field is neither a field path nor a name given to a field of the
+  /// method's messages, or the url pattern's path isn't a single method.
+  UnknownField,
+  NoInterpretation,
+  /// The operand is too long, or isn't a number for a comparison.
+  InvalidOperand,
+}
+
+impl RuleCreator {
+  /// `field_names` are looked up for named fields.
+  pub fn create(self, user_id: UserId, field_names: &[FieldName]) -> Result<Rule, RuleCreatorError> {
+    let url_pattern = UrlPattern::parse(&self.url_pattern)
+      .map_err(|_| RuleCreatorError::InvalidUrlPattern)?;
+
+    let method: Option<&PathPrefix> = match url_pattern.path() {
+      Some(PathPattern::Prefix(method)) => Some(method),
+      _ => None,
+    };
+
+    let (path, interpretation) = match FieldPath::parse(&self.field) {
+      Ok(path) => {
+        (path, self.interpretation.ok_or(RuleCreatorError::NoInterpretation)?)
+      }
+      Err(_) => {
+        let field = self.field.trim();
+        let field_name = field_names
+          .iter()
+          .find(|field_name| {
+            field_name.user_id == user_id
+              && field_name.direction == self.direction
+              && field_name.name == field
+              && method.is_some_and(|method| *method == field_name.method)
+          })
+          .ok_or(RuleCreatorError::UnknownField)?;
+
+        (field_name.path.clone(), self.interpretation.unwrap_or(field_name.interpretation))
+      }
+    };
+
+    let is_comparison = matches!(self.operator, Operator::AtLeast | Operator::AtMost);
+    if self.operand.len() > MAXIMUM_OPERAND_LENGTH
+      || is_comparison && !self.operand.trim().parse::<f64>().is_ok_and(f64::is_finite)
+    {
+      return Err(RuleCreatorError::InvalidOperand);
+    }
+
+    Ok(Rule::from_fields(
+      self.id.unwrap_or_else(Uuid::new_v4),
+      user_id,
+      url_pattern,
+      self.direction,
+      path,
+      interpretation,
+      self.operator,
+      self.operand,
+      self.activator,
+    ))
+  }
+}
+
+impl UrlScoped for Rule {
+  fn url_patterns(&self) -> &[UrlPattern] {
+    std::slice::from_ref(&self.url_pattern)
+  }
+}
+
+// SECTION: Traffic handler.
+/// Blocks gRPC, gRPC-Web and plain protobuf exchanges whose request or
+/// response messages match effective rules.
+///
+/// Messages are read without their schema, so rules name fields by number.
+/// Compressed gRPC messages can't be read. Websites are asked not to
+/// compress the responses rules look at, but requests compressed by the
+/// client go through.
+pub struct ProtobufRegulation {
+  rules: Mutex<UrlScopedList<Rule>>,
+  field_names: Mutex<Vec<FieldName>>,
+}
+
+impl ProtobufRegulation {
+  pub fn open(database: &Database) -> Result<Self, GenericError> {
+    let rules = rule_db::retrieve_all_rules(database)
+      .map_err(|error| error.change_context("opening the protobuf regulation"))?;
+
+    let field_names = field_name_db::retrieve_all_field_names(database)
+      .map_err(|error| error.change_context("opening the protobuf regulation"))?;
+
+    Ok(Self::new(rules, field_names))
+  }
+
+  pub fn new(rules: Vec<Rule>, field_names: Vec<FieldName>) -> Self {
+    Self {
+      rules: Mutex::new(UrlScopedList::new(rules)),
+      field_names: Mutex::new(field_names),
+    }
+  }
+
+  pub fn rules(&self) -> MutexGuard<'_, UrlScopedList<Rule>> {
+    self.rules.lock().unwrap()
+  }
+
+  pub fn field_names(&self) -> MutexGuard<'_, Vec<FieldName>> {
+    self.field_names.lock().unwrap()
+  }
+
+  fn rules_for(&self, exchange: &Exchange, direction: Direction, now: DateTime) -> Vec<Rule> {
+    let rules = self.rules();
+    rules
+      .matching(exchange)
+      .into_iter()
+      .map(|index| &rules[index])
+      .filter(|rule| rule.applies_to(exchange, direction, now))
+      .cloned()
+      .collect()
+  }
+
+  fn has_rules_for(&self, exchange: &Exchange, direction: Direction, now: DateTime) -> bool {
+    !self.rules_for(exchange, direction, now).is_empty()
+  }
+
+  /// Whether a message of `body` matches a rule that applies to messages
+  /// going in `direction`.
+  pub fn matches(&self, exchange: &Exchange, direction: Direction, framing: Framing, body: &[u8], now: DateTime) -> bool {
+    let rules = self.rules_for(exchange, direction, now);
+    if rules.is_empty() {
+      return false;
+    }
+
+    wire::messages(framing, body)
+      .iter()
+      .any(|message| rules.iter().any(|rule| rule.matches(message)))
+  }
+}
+
+/// The response to a blocked exchange. gRPC clients get an error status they
+/// understand, with `content_type` as the type of the empty body.
+pub fn blocked(framing: Framing, content_type: &str) -> Response {
+  if !framing.is_grpc() {
+    let mut head = ResponseHead::new(403, "Forbidden");
+    head.headers.append("Cache-Control", "no-store");
+    return Response::new(head, Vec::new());
+  }
+
+  let mut head = ResponseHead::new(200, "OK");
+  head.headers.append("Content-Type", content_type);
+  head.headers.append("Cache-Control", "no-store");
+  // PERMISSION_DENIED.
+  head.headers.append("grpc-status", "7");
+  head.headers.append("grpc-message", "Blocked by Discipline");
+  Response::new(head, Vec::new())
+}
+
+impl TrafficHandler for ProtobufRegulation {
+  fn may_inspect_response_body(&self, _daemon: &Daemon, exchange: &Exchange) -> bool {
+    self.has_rules_for(exchange, Direction::Response, DateTime::now())
+  }
+
+  fn on_request(&self, _daemon: &Daemon, exchange: &mut Exchange) -> RequestVerdict {
+    if self.has_rules_for(exchange, Direction::Response, DateTime::now()) {
+      exchange.request.headers.insert("grpc-accept-encoding", "identity");
+    }
+
+    RequestVerdict::Forward
+  }
+
+  fn may_inspect_request_body(&self, _daemon: &Daemon, exchange: &Exchange) -> bool {
+    exchange
+      .request
+      .content_type()
+      .is_some_and(|content_type| Framing::of(&content_type).is_some())
+      && self.has_rules_for(exchange, Direction::Request, DateTime::now())
+  }
+
+  fn on_request_body(&self, _daemon: &Daemon, exchange: &Exchange, body: &[u8]) -> RequestVerdict {
+    let Some(content_type) = exchange.request.content_type() else {
+      return RequestVerdict::Forward;
+    };
+    let Some(framing) = Framing::of(&content_type) else {
+      return RequestVerdict::Forward;
+    };
+
+    if self.matches(exchange, Direction::Request, framing, body, DateTime::now()) {
+      return RequestVerdict::Respond(blocked(framing, &content_type));
+    }
+
+    RequestVerdict::Forward
+  }
+
+  fn on_response(
+    &self,
+    _daemon: &Daemon,
+    exchange: &Exchange,
+    response: &mut ResponseHead,
+  ) -> ResponseVerdict {
+    let is_protobuf = response
+      .content_type()
+      .is_some_and(|content_type| Framing::of(&content_type).is_some());
+
+    if is_protobuf && self.has_rules_for(exchange, Direction::Response, DateTime::now()) {
+      return ResponseVerdict::InspectBody;
+    }
+
+    ResponseVerdict::Forward
+  }
+
+  fn on_response_body(
+    &self,
+    _daemon: &Daemon,
+    exchange: &Exchange,
+    response: &mut ResponseHead,
+    body: &mut Vec<u8>,
+  ) -> BodyVerdict {
+    let Some(content_type) = response.content_type() else {
+      return BodyVerdict::Forward;
+    };
+    let Some(framing) = Framing::of(&content_type) else {
+      return BodyVerdict::Forward;
+    };
+
+    if self.matches(exchange, Direction::Response, framing, body, DateTime::now()) {
+      return BodyVerdict::Respond(blocked(framing, &content_type));
+    }
+
+    BodyVerdict::Forward
+  }
+}
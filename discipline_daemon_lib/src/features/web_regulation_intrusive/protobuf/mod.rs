@@ -0,0 +1,22 @@
+pub mod wire;
+pub use wire::{FieldPath, Framing, InspectedField, Interpretation, Value, WireValue};
+
+pub mod feature;
+pub use feature::{
+  blocked,
+  name_of,
+  Direction,
+  FieldName,
+  FieldNameCreator,
+  FieldNameCreatorError,
+  Operator,
+  ProtobufRegulation,
+  Rule,
+  RuleCreator,
+  RuleCreatorError,
+  MAXIMUM_FIELD_NAMES_PER_USER,
+  MAXIMUM_RULES_PER_USER,
+};
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,209 @@
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use crate::operating_system_integration::UserId;
+use super::super::rule_activator::RuleActivator;
+use super::super::traffic::{Exchange, Headers, RequestHead, Scheme};
+use super::wire::{decode_message, frame, inspect, messages, values_at};
+use super::*;
+
+fn varint(mut value: u64, into: &mut Vec<u8>) {
+  while value >= 0x80 {
+    into.push(value as u8 | 0x80);
+    value >>= 7;
+  }
+  into.push(value as u8);
+}
+
+fn varint_field(number: u32, value: u64, into: &mut Vec<u8>) {
+  varint(u64::from(number) << 3, into);
+  varint(value, into);
+}
+
+fn bytes_field(number: u32, value: &[u8], into: &mut Vec<u8>) {
+  varint(u64::from(number) << 3 | 2, into);
+  varint(value.len() as u64, into);
+  into.extend_from_slice(value);
+}
+
+fn fixed32_field(number: u32, value: u32, into: &mut Vec<u8>) {
+  varint(u64::from(number) << 3 | 5, into);
+  into.extend_from_slice(&value.to_le_bytes());
+}
+
+/// A search request: `{ 1: "cute cats", 2: { 1: 20, 2: -3 as a sint32 }, 3: 0.5 as a float }`.
+fn search_request() -> Vec<u8> {
+  let mut paging = Vec::new();
+  varint_field(1, 20, &mut paging);
+  varint_field(2, 5, &mut paging);
+
+  let mut message = Vec::new();
+  bytes_field(1, b"cute cats", &mut message);
+  bytes_field(2, &paging, &mut message);
+  fixed32_field(3, 0.5f32.to_bits(), &mut message);
+  message
+}
+
+fn creator(field: &str, interpretation: Option<Interpretation>, operator: Operator, operand: &str) -> RuleCreator {
+  RuleCreator {
+    id: None,
+    url_pattern: "*.example.com/search.Search/Query".into(),
+    direction: Direction::Request,
+    field: field.into(),
+    interpretation,
+    operator,
+    operand: operand.into(),
+    activator: RuleActivator::AllTheTime,
+  }
+}
+
+#[test]
+fn decodes_messages_without_a_schema() {
+  let message = search_request();
+  let fields = decode_message(&message).unwrap();
+  assert_eq!(fields.len(), 3);
+  assert_eq!(fields[0].value, WireValue::LengthDelimited(b"cute cats"));
+
+  assert_eq!(values_at(&message, &[2, 1]), [WireValue::Varint(20)]);
+  // Field 1 doesn't decode as a message, so it has no fields.
+  assert!(values_at(&message, &[1, 1]).is_empty());
+
+  let read = |path: &[u32], interpretation: Interpretation| {
+    interpretation.read(values_at(&message, path)[0]).map(|value| value.to_string())
+  };
+  assert_eq!(read(&[2, 2], Interpretation::ZigZag).as_deref(), Some("-3"));
+  assert_eq!(read(&[3], Interpretation::Float).as_deref(), Some("0.5"));
+  assert_eq!(read(&[1], Interpretation::Text).as_deref(), Some("cute cats"));
+  assert_eq!(read(&[1], Interpretation::Unsigned), None);
+
+  // Truncated, and a group.
+  assert!(decode_message(&message[..message.len() - 1]).is_none());
+  assert!(decode_message(&[0x0b]).is_none());
+}
+
+#[test]
+fn parses_field_paths() {
+  assert_eq!(FieldPath::parse("2.1").unwrap().numbers(), [2, 1]);
+  assert_eq!(FieldPath::parse("2.1").unwrap().to_string(), "2.1");
+  for invalid in ["", "0", "1..2", "query", "536870912"] {
+    assert!(FieldPath::parse(invalid).is_err(), "{invalid}");
+  }
+}
+
+#[test]
+fn splits_bodies_into_messages() {
+  let message = search_request();
+
+  let mut compressed = frame(b"compressed");
+  compressed[0] = 1;
+  let mut trailers = frame(b"grpc-status:0");
+  trailers[0] = 0x80;
+
+  let mut grpc_web = frame(&message);
+  grpc_web.extend_from_slice(&compressed);
+  grpc_web.extend_from_slice(&frame(b""));
+  grpc_web.extend_from_slice(&trailers);
+  assert_eq!(messages(Framing::GrpcWeb, &grpc_web), [message.clone(), Vec::new()]);
+
+  // Frames encoded one at a time, so padding shows up in the middle.
+  let mut text = BASE64_STANDARD.encode(frame(&message));
+  text.push_str(&BASE64_STANDARD.encode(trailers));
+  assert_eq!(messages(Framing::GrpcWebText, text.as_bytes()), [&message[..]]);
+
+  assert_eq!(messages(Framing::Plain, &message), [&message[..]]);
+  assert!(messages(Framing::Grpc, &frame(&message)[..10]).is_empty());
+
+  assert_eq!(Framing::of("application/grpc-web+proto"), Some(Framing::GrpcWeb));
+  assert_eq!(Framing::of("application/json"), None);
+}
+
+#[test]
+fn creates_rules_naming_fields() {
+  let user_id = UserId::new(1000);
+  let query = FieldNameCreator {
+    id: None,
+    method: "/search.Search/Query".into(),
+    direction: Direction::Request,
+    path: "1".into(),
+    name: "query".into(),
+    interpretation: Interpretation::Text,
+  }
+  .create(user_id)
+  .unwrap();
+  let field_names = [query];
+
+  let rule = creator("query", None, Operator::Contains, "cats").create(user_id, &field_names).unwrap();
+  assert_eq!(rule.path().numbers(), [1]);
+  assert_eq!(rule.interpretation(), Interpretation::Text);
+
+  assert!(matches!(
+    creator("query", None, Operator::Contains, "cats").create(UserId::new(1001), &field_names),
+    Err(RuleCreatorError::UnknownField),
+  ));
+  assert!(matches!(
+    creator("2.1", None, Operator::AtLeast, "10").create(user_id, &field_names),
+    Err(RuleCreatorError::NoInterpretation),
+  ));
+
+  let mut any_method = creator("query", None, Operator::Contains, "cats");
+  any_method.url_pattern = "*.example.com".into();
+  assert!(matches!(any_method.create(user_id, &field_names), Err(RuleCreatorError::UnknownField)));
+  assert!(matches!(
+    creator("2.1", Some(Interpretation::Unsigned), Operator::AtLeast, "many").create(user_id, &field_names),
+    Err(RuleCreatorError::InvalidOperand),
+  ));
+
+  let name_of = |path: &FieldPath| name_of(&field_names, user_id, "/search.Search/Query", Direction::Request, path);
+  let inspected = inspect(&search_request(), &name_of);
+  assert_eq!(inspected[0].name.as_deref(), Some("query"));
+  assert_eq!(inspected[1].fields[1].path.to_string(), "2.2");
+  assert!(inspected[1].fields[1].readings.contains(&(Interpretation::ZigZag, "-3".into())));
+}
+
+#[test]
+fn matches_field_values() {
+  let user_id = UserId::new(1000);
+  let message = search_request();
+  let matches = |field: &str, interpretation: Interpretation, operator: Operator, operand: &str| {
+    creator(field, Some(interpretation), operator, operand)
+      .create(user_id, &[])
+      .unwrap()
+      .matches(&message)
+  };
+
+  assert!(matches("1", Interpretation::Text, Operator::Contains, "CATS"));
+  assert!(!matches("1", Interpretation::Text, Operator::Equals, "cute"));
+  assert!(matches("2.1", Interpretation::Unsigned, Operator::Equals, "20.0"));
+  assert!(matches("2.1", Interpretation::Unsigned, Operator::AtLeast, "20"));
+  assert!(!matches("2.1", Interpretation::Unsigned, Operator::AtMost, "19"));
+  assert!(matches("2.2", Interpretation::ZigZag, Operator::AtMost, "-3"));
+  assert!(matches("3", Interpretation::Float, Operator::Present, ""));
+  assert!(!matches("3", Interpretation::Text, Operator::Present, ""));
+  assert!(!matches("4", Interpretation::Unsigned, Operator::Present, ""));
+}
+
+#[test]
+fn applies_rules_to_their_urls() {
+  let rule = creator("1", Some(Interpretation::Text), Operator::Contains, "cats")
+    .create(UserId::new(1000), &[])
+    .unwrap();
+  let protobuf_regulation = ProtobufRegulation::new(vec![rule], Vec::new());
+
+  let call = |host: &str, method: &str| {
+    let exchange = Exchange::to(&format!("https://{host}{method}")).with_method("POST");
+    protobuf_regulation.matches(&exchange, Direction::Request, Framing::Plain, &search_request(), crate::DateTime::now())
+  };
+
+  assert!(call("api.example.com", "/search.Search/Query"));
+  assert!(call("api.example.com", "//search.Search/%51uery"));
+  assert!(!call("api.example.com", "/search.Search/Suggest"));
+  assert!(!call("api.example.org", "/search.Search/Query"));
+}
+
+#[test]
+fn blocks_grpc_with_a_status() {
+  let response = blocked(Framing::GrpcWeb, "application/grpc-web+proto");
+  assert_eq!(response.head.status_code, 200);
+  assert_eq!(response.head.headers.get("grpc-status"), Some("7"));
+  assert_eq!(response.head.headers.get("Content-Type"), Some("application/grpc-web+proto"));
+
+  assert_eq!(blocked(Framing::Plain, "application/x-protobuf").head.status_code, 403);
+}
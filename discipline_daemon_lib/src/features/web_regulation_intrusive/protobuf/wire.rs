@@ -0,0 +1,464 @@
+use std::fmt;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use crate::GenericError;
+
+/// How deep `inspect` looks into fields that decode as messages themselves.
+pub const MAXIMUM_INSPECTION_DEPTH: usize = 16;
+/// How many fields `inspect` lists per message.
+pub const MAXIMUM_INSPECTED_FIELDS: usize = 1000;
+
+// SECTION: Messages.
+/// The value of a field as it's encoded, without the schema that tells what
+/// it means.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireValue<'a> {
+  Varint(u64),
+  Fixed64(u64),
+  /// A string, bytes, a nested message or packed repeated numbers.
+  LengthDelimited(&'a [u8]),
+  Fixed32(u32),
+}
+
+impl WireValue<'_> {
+  pub fn type_name(&self) -> &'static str {
+    match self {
+      WireValue::Varint(_) => "varint",
+      WireValue::Fixed64(_) => "fixed64",
+      WireValue::LengthDelimited(_) => "length-delimited",
+      WireValue::Fixed32(_) => "fixed32",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field<'a> {
+  pub number: u32,
+  pub value: WireValue<'a>,
+}
+
+fn read_varint(bytes: &[u8], position: &mut usize) -> Option<u64> {
+  let mut value = 0u64;
+  for shift in (0..64).step_by(7) {
+    let byte = *bytes.get(*position)?;
+    *position += 1;
+    value |= u64::from(byte & 0x7f) << shift;
+    if byte & 0x80 == 0 {
+      return Some(value);
+    }
+  }
+
+  None
+}
+
+fn read_fixed<const LENGTH: usize>(bytes: &[u8], position: &mut usize) -> Option<[u8; LENGTH]> {
+  let fixed = bytes.get(*position..*position + LENGTH)?.try_into().ok()?;
+  *position += LENGTH;
+  Some(fixed)
+}
+
+/// The fields of a protobuf message, in the order they're encoded. Returns
+/// `None` if `bytes` isn't a valid encoding of a message, which says nothing
+/// about whether it's the message one hoped for. Groups, which are long
+/// deprecated, aren't supported.
+pub fn decode_message(bytes: &[u8]) -> Option<Vec<Field<'_>>> {
+  let mut fields = Vec::new();
+  let mut position = 0;
+
+  while position < bytes.len() {
+    let key = read_varint(bytes, &mut position)?;
+    let number = u32::try_from(key >> 3).ok().filter(|number| *number != 0)?;
+
+    let value = match key & 0b111 {
+      0 => {
+        WireValue::Varint(read_varint(bytes, &mut position)?)
+      }
+      1 => {
+        WireValue::Fixed64(u64::from_le_bytes(read_fixed(bytes, &mut position)?))
+      }
+      2 => {
+        let length = usize::try_from(read_varint(bytes, &mut position)?).ok()?;
+        let value = bytes.get(position..position.checked_add(length)?)?;
+        position += length;
+        WireValue::LengthDelimited(value)
+      }
+      5 => {
+        WireValue::Fixed32(u32::from_le_bytes(read_fixed(bytes, &mut position)?))
+      }
+      _ => {
+        return None;
+      }
+    };
+
+    fields.push(Field { number, value });
+  }
+
+  Some(fields)
+}
+
+/// The field numbers leading to a field of a nested message, like `2.1` for
+/// field 1 of the message in field 2.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FieldPath(Vec<u32>);
+
+impl FieldPath {
+  pub const MAXIMUM_DEPTH: usize = 16;
+
+  pub fn parse(path: &str) -> Result<Self, GenericError> {
+    let numbers: Option<Vec<u32>> = path
+      .trim()
+      .split('.')
+      .map(|number| number.parse::<u32>().ok().filter(|number| (1..1 << 29).contains(number)))
+      .collect();
+
+    match numbers {
+      Some(numbers) if numbers.len() <= Self::MAXIMUM_DEPTH => {
+        Ok(Self(numbers))
+      }
+      _ => {
+        Err(
+          GenericError::new("parsing a protobuf FieldPath")
+            .add_error("path isn't field numbers separated by dots, or is too deep")
+            .add_attachment("path", path)
+        )
+      }
+    }
+  }
+
+  pub fn numbers(&self) -> &[u32] {
+    &self.0
+  }
+
+  fn child(&self, number: u32) -> Self {
+    let mut numbers = self.0.clone();
+    numbers.push(number);
+    Self(numbers)
+  }
+}
+
+impl fmt::Display for FieldPath {
+  fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (index, number) in self.0.iter().enumerate() {
+      if index != 0 {
+        formatter.write_str(".")?;
+      }
+      write!(formatter, "{number}")?;
+    }
+    Ok(())
+  }
+}
+
+/// The values of the field at `path` in `message`. Fields on the way there
+/// that don't decode as messages are passed over, and repeated fields give
+/// a value each.
+pub fn values_at<'a>(message: &'a [u8], path: &[u32]) -> Vec<WireValue<'a>> {
+  let Some((number, rest)) = path.split_first() else {
+    return Vec::new();
+  };
+  let Some(fields) = decode_message(message) else {
+    return Vec::new();
+  };
+
+  let mut values = Vec::new();
+  for field in fields.into_iter().filter(|field| field.number == *number) {
+    if rest.is_empty() {
+      values.push(field.value);
+    } else if let WireValue::LengthDelimited(nested) = field.value {
+      values.extend(values_at(nested, rest));
+    }
+  }
+
+  values
+}
+
+// SECTION: Interpretations.
+/// One of the meanings a wire value might have, since the wire format
+/// doesn't say which of several types a field was declared with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interpretation {
+  /// `uint32`, `uint64`, `fixed32`, `fixed64` or an enum.
+  Unsigned,
+  /// `int32`, `int64`, `sfixed32` or `sfixed64`.
+  Signed,
+  /// `sint32` or `sint64`.
+  ZigZag,
+  Boolean,
+  Float,
+  Double,
+  /// A UTF-8 string.
+  Text,
+  Bytes,
+}
+
+impl Interpretation {
+  pub const ALL: [Interpretation; 8] = [
+    Interpretation::Unsigned,
+    Interpretation::Signed,
+    Interpretation::ZigZag,
+    Interpretation::Boolean,
+    Interpretation::Float,
+    Interpretation::Double,
+    Interpretation::Text,
+    Interpretation::Bytes,
+  ];
+
+  /// What `value` means under this interpretation, if it can have this
+  /// interpretation at all.
+  pub fn read(self, value: WireValue<'_>) -> Option<Value> {
+    match (self, value) {
+      (Interpretation::Unsigned, WireValue::Varint(number) | WireValue::Fixed64(number)) => {
+        Some(Value::Unsigned(number))
+      }
+      (Interpretation::Unsigned, WireValue::Fixed32(number)) => {
+        Some(Value::Unsigned(number.into()))
+      }
+      (Interpretation::Signed, WireValue::Varint(number) | WireValue::Fixed64(number)) => {
+        Some(Value::Signed(number as i64))
+      }
+      (Interpretation::Signed, WireValue::Fixed32(number)) => {
+        Some(Value::Signed((number as i32).into()))
+      }
+      (Interpretation::ZigZag, WireValue::Varint(number)) => {
+        Some(Value::Signed((number >> 1) as i64 ^ -((number & 1) as i64)))
+      }
+      (Interpretation::Boolean, WireValue::Varint(number @ (0 | 1))) => {
+        Some(Value::Boolean(number == 1))
+      }
+      (Interpretation::Float, WireValue::Fixed32(bits)) => {
+        Some(Value::Float(f32::from_bits(bits).into()))
+      }
+      (Interpretation::Double, WireValue::Fixed64(bits)) => {
+        Some(Value::Float(f64::from_bits(bits)))
+      }
+      (Interpretation::Text, WireValue::LengthDelimited(bytes)) => {
+        std::str::from_utf8(bytes).ok().map(|text| Value::Text(text.to_string()))
+      }
+      (Interpretation::Bytes, WireValue::LengthDelimited(bytes)) => {
+        Some(Value::Bytes(bytes.to_vec()))
+      }
+      _ => {
+        None
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+  Unsigned(u64),
+  Signed(i64),
+  Boolean(bool),
+  Float(f64),
+  Text(String),
+  Bytes(Vec<u8>),
+}
+
+impl Value {
+  pub fn as_number(&self) -> Option<f64> {
+    match self {
+      Value::Unsigned(number) => Some(*number as f64),
+      Value::Signed(number) => Some(*number as f64),
+      Value::Float(number) => Some(*number),
+      Value::Boolean(_) | Value::Text(_) | Value::Bytes(_) => None,
+    }
+  }
+}
+
+/// Bytes are written in lowercase hex.
+impl fmt::Display for Value {
+  fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Value::Unsigned(number) => write!(formatter, "{number}"),
+      Value::Signed(number) => write!(formatter, "{number}"),
+      Value::Boolean(boolean) => write!(formatter, "{boolean}"),
+      Value::Float(number) => write!(formatter, "{number}"),
+      Value::Text(text) => formatter.write_str(text),
+      Value::Bytes(bytes) => {
+        for byte in bytes {
+          write!(formatter, "{byte:02x}")?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+// SECTION: Bodies.
+/// How protobuf messages are put in an http body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+  /// `application/grpc`: each message after a byte of flags and its length.
+  Grpc,
+  /// `application/grpc-web`: like gRPC, with the trailers in a last frame.
+  GrpcWeb,
+  /// `application/grpc-web-text`: gRPC-Web in base64.
+  GrpcWebText,
+  /// `application/x-protobuf` and the like: the body is one message.
+  Plain,
+}
+
+impl Framing {
+  /// The framing of bodies of `media_type`, lowercased and without
+  /// parameters, if they hold protobuf messages.
+  pub fn of(media_type: &str) -> Option<Self> {
+    match media_type {
+      "application/grpc" | "application/grpc+proto" => {
+        Some(Framing::Grpc)
+      }
+      "application/grpc-web" | "application/grpc-web+proto" => {
+        Some(Framing::GrpcWeb)
+      }
+      "application/grpc-web-text" | "application/grpc-web-text+proto" => {
+        Some(Framing::GrpcWebText)
+      }
+      "application/x-protobuf" | "application/protobuf" | "application/vnd.google.protobuf" => {
+        Some(Framing::Plain)
+      }
+      _ => {
+        None
+      }
+    }
+  }
+
+  pub fn is_grpc(&self) -> bool {
+    *self != Framing::Plain
+  }
+}
+
+const COMPRESSED_FLAG: u8 = 0x01;
+const TRAILERS_FLAG: u8 = 0x80;
+
+/// Decodes base64 made of chunks that were each encoded on their own, so
+/// padding may show up in the middle. Stops at the first invalid chunk.
+fn decode_base64_chunks(text: &[u8]) -> Vec<u8> {
+  let text: Vec<u8> = text.iter().copied().filter(|byte| !byte.is_ascii_whitespace()).collect();
+
+  let mut bytes = Vec::new();
+  let mut start = 0;
+  let mut index = 0;
+  while start < text.len() {
+    while index < text.len() && text[index] != b'=' {
+      index += 1;
+    }
+    while index < text.len() && text[index] == b'=' {
+      index += 1;
+    }
+
+    match STANDARD.decode(&text[start..index]) {
+      Ok(chunk) => bytes.extend_from_slice(&chunk),
+      Err(_) => break,
+    }
+    start = index;
+  }
+
+  bytes
+}
+
+/// The messages of an http body. Compressed gRPC messages are left out, as
+/// are gRPC-Web trailers and whatever follows a truncated frame.
+pub fn messages(framing: Framing, body: &[u8]) -> Vec<Vec<u8>> {
+  let decoded;
+  let mut frames = match framing {
+    Framing::Plain => {
+      return vec![body.to_vec()];
+    }
+    Framing::Grpc | Framing::GrpcWeb => {
+      body
+    }
+    Framing::GrpcWebText => {
+      decoded = decode_base64_chunks(body);
+      &decoded[..]
+    }
+  };
+
+  let mut messages = Vec::new();
+  while frames.len() >= 5 {
+    let flags = frames[0];
+    let length = u32::from_be_bytes([frames[1], frames[2], frames[3], frames[4]]) as usize;
+    let Some(message) = frames.get(5..5 + length) else {
+      break;
+    };
+
+    if flags & (COMPRESSED_FLAG | TRAILERS_FLAG) == 0 {
+      messages.push(message.to_vec());
+    }
+    frames = &frames[5 + length..];
+  }
+
+  messages
+}
+
+/// Frames `message` as a gRPC or gRPC-Web message.
+pub fn frame(message: &[u8]) -> Vec<u8> {
+  let mut frame = Vec::with_capacity(message.len() + 5);
+  frame.push(0);
+  frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+  frame.extend_from_slice(message);
+  frame
+}
+
+// SECTION: Inspection.
+/// What a field of a message might be, to help figuring out which field
+/// holds what.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InspectedField {
+  pub path: FieldPath,
+  /// The name the user gave the field, if any.
+  pub name: Option<String>,
+  pub wire_type: String,
+  /// The value under each interpretation the wire type allows.
+  pub readings: Vec<(Interpretation, String)>,
+  /// The fields of the value, if it decodes as a message.
+  pub fields: Vec<InspectedField>,
+}
+
+/// Lists the fields of `message` along with what they might be, naming them
+/// with `name_of`.
+pub fn inspect(message: &[u8], name_of: &impl Fn(&FieldPath) -> Option<String>) -> Vec<InspectedField> {
+  inspect_at(message, &FieldPath(Vec::new()), name_of)
+}
+
+fn inspect_at(
+  message: &[u8],
+  parent: &FieldPath,
+  name_of: &impl Fn(&FieldPath) -> Option<String>,
+) -> Vec<InspectedField> {
+  let Some(fields) = decode_message(message) else {
+    return Vec::new();
+  };
+
+  fields
+    .into_iter()
+    .take(MAXIMUM_INSPECTED_FIELDS)
+    .map(|field| {
+      let path = parent.child(field.number);
+
+      let readings = Interpretation::ALL
+        .into_iter()
+        .filter_map(|interpretation| {
+          interpretation
+            .read(field.value)
+            .map(|value| (interpretation, value.to_string()))
+        })
+        .collect();
+
+      let fields = match field.value {
+        WireValue::LengthDelimited(nested) if path.0.len() < MAXIMUM_INSPECTION_DEPTH => {
+          inspect_at(nested, &path, name_of)
+        }
+        _ => {
+          Vec::new()
+        }
+      };
+
+      InspectedField {
+        name: name_of(&path),
+        wire_type: field.value.type_name().into(),
+        readings,
+        fields,
+        path,
+      }
+    })
+    .collect()
+}
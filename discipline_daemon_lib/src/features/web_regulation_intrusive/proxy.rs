@@ -0,0 +1,1693 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread;
+use std::time::Duration as StandardDuration;
+use rustls::pki_types::ServerName;
+use rustls::server::Acceptor;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+use llhttp_rs::message::{RequestReader, ResponseReader};
+use crate::database::web_regulation_intrusive_no_intercept_host as no_intercept_db;
+use crate::database::web_regulation_intrusive_proxied_host as proxied_host_db;
+use crate::operating_system_integration::{
+  UserId,
+  retrieve_original_destination,
+  retrieve_tcp_socket_owner,
+  install_certificate_into_system_trust_store,
+};
+use crate::{Daemon, Database, GenericError};
+use super::certificate_authority::{CertificateAuthority, LeafCertificateResolver};
+use super::http1::{self, BodyFraming, BodyReader, ReadBody};
+use super::http2::{self, Duplex};
+use super::no_intercept::NoInterceptHosts;
+use super::proxy_auto_config::ProxyAutoConfig;
+use super::traffic::*;
+use super::website_visit_delayer::WebsiteVisitDelayer;
+use super::website_visits_limiter::WebsiteVisitsLimiter;
+use super::twitter::TwitterRegulation;
+use super::media_type_blocker::MediaTypeBlocker;
+use super::element_rules::ElementRegulation;
+use super::content_filter::ContentFilter;
+use super::safe_search::{self, SafeSearch};
+use super::search_queries::SearchQueryBlocker;
+use super::view_time_allowance::ViewTimeAllowances;
+use super::image_rules::ImageRegulation;
+use super::protobuf::ProtobufRegulation;
+use super::encrypted_dns::{self, EncryptedDnsPrevention};
+use super::audit_log::{AuditLog, Decision};
+use super::youtube::YoutubeRegulation;
+
+/// How long relays of tls connections wait for the rest of a record from one
+/// side before checking the other.
+const RELAY_RECORD_TIMEOUT: StandardDuration = StandardDuration::from_millis(100);
+const UPSTREAM_CONNECT_TIMEOUT: StandardDuration = StandardDuration::from_secs(15);
+
+/// The first byte of a tls record carrying a handshake message.
+const TLS_HANDSHAKE_RECORD: u8 = 0x16;
+
+/// The alpn protocol id of http/2 over tls.
+const HTTP2: &[u8] = b"h2";
+const HTTP1: &[u8] = b"http/1.1";
+
+/// How much of a body is copied at once between http/2 streams.
+const COPY_BUFFER_LENGTH: usize = 16 * 1024;
+
+/// A bidirectional byte stream, possibly tls-encrypted, over a tcp connection.
+pub trait Socket: Read + Write + Send {
+  fn tcp_stream(&self) -> &TcpStream;
+
+  /// Whether reads go straight to the tcp stream, with no tls session in
+  /// between.
+  fn is_plain(&self) -> bool {
+    false
+  }
+
+  /// Whether a read returns without waiting on the tcp stream, because the
+  /// tls session holds on to data it already decrypted.
+  fn has_buffered_data(&self) -> bool {
+    false
+  }
+}
+
+impl Socket for TcpStream {
+  fn tcp_stream(&self) -> &TcpStream {
+    self
+  }
+
+  fn is_plain(&self) -> bool {
+    true
+  }
+}
+
+impl Socket for StreamOwned<ServerConnection, TcpStream> {
+  fn tcp_stream(&self) -> &TcpStream {
+    &self.sock
+  }
+
+  fn has_buffered_data(&self) -> bool {
+    !self.conn.wants_read()
+  }
+}
+
+impl Socket for StreamOwned<ClientConnection, TcpStream> {
+  fn tcp_stream(&self) -> &TcpStream {
+    &self.sock
+  }
+
+  fn has_buffered_data(&self) -> bool {
+    !self.conn.wants_read()
+  }
+}
+
+impl Socket for Box<dyn Socket> {
+  fn tcp_stream(&self) -> &TcpStream {
+    self.as_ref().tcp_stream()
+  }
+
+  fn is_plain(&self) -> bool {
+    self.as_ref().is_plain()
+  }
+
+  fn has_buffered_data(&self) -> bool {
+    self.as_ref().has_buffered_data()
+  }
+}
+
+/// Intercepts the web traffic of managed users so `TrafficHandler`s can
+/// regulate it.
+///
+/// Connections arrive either redirected by iptables, in which case they may
+/// be plain http or tls, or from clients configured to use us as an explicit
+/// proxy. Tls connections are decrypted using certificates minted by our own
+/// certificate authority, unless their host is in the no-intercept list, in
+/// which case they are tunneled as is.
+///
+/// Clients offering http/2 get it if the website speaks it too, in which
+/// case each stream is handled like an http/1.x exchange.
+pub struct Proxy {
+  port: u16,
+  certificate_authority: Arc<CertificateAuthority>,
+  server_configuration: Arc<ServerConfig>,
+  client_configuration: Arc<ClientConfig>,
+  http2_server_configuration: Arc<ServerConfig>,
+  http2_client_configuration: Arc<ClientConfig>,
+  no_intercept_hosts: RwLock<NoInterceptHosts>,
+  proxy_auto_config: RwLock<ProxyAutoConfig>,
+  handlers: RwLock<Vec<Arc<dyn TrafficHandler>>>,
+  website_visit_delayer: Arc<WebsiteVisitDelayer>,
+  website_visits_limiter: Arc<WebsiteVisitsLimiter>,
+  youtube: Arc<YoutubeRegulation>,
+  twitter: Arc<TwitterRegulation>,
+  media_type_blocker: Arc<MediaTypeBlocker>,
+  element_regulation: Arc<ElementRegulation>,
+  content_filter: Arc<ContentFilter>,
+  safe_search: Arc<SafeSearch>,
+  search_query_blocker: Arc<SearchQueryBlocker>,
+  view_time_allowances: Arc<ViewTimeAllowances>,
+  image_regulation: Arc<ImageRegulation>,
+  protobuf_regulation: Arc<ProtobufRegulation>,
+  encrypted_dns_prevention: Arc<EncryptedDnsPrevention>,
+  audit_log: AuditLog,
+}
+
+impl Proxy {
+  pub fn open(
+    database: &Database,
+    data_directory: &Path,
+    port: u16,
+    dns_forwarder_port: u16,
+  ) -> Result<Self, GenericError> {
+    let certificate_authority = Arc::new(CertificateAuthority::open(data_directory)?);
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+    let mut server_configuration = ServerConfig::builder_with_provider(Arc::clone(&provider))
+      .with_safe_default_protocol_versions()
+      .map_err(|error|
+        GenericError::new("creating the web regulation proxy")
+          .add_error("failed to create the tls server configuration")
+          .add_attachment("error", error.to_string())
+      )?
+      .with_no_client_auth()
+      .with_cert_resolver(Arc::new(LeafCertificateResolver {
+        certificate_authority: Arc::clone(&certificate_authority),
+        fallback_server_name: None,
+      }));
+
+    server_configuration.alpn_protocols = vec![HTTP1.to_vec()];
+
+    let mut http2_server_configuration = server_configuration.clone();
+    http2_server_configuration.alpn_protocols = vec![HTTP2.to_vec(), HTTP1.to_vec()];
+
+    let root_certificates = RootCertStore {
+      roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+
+    let mut client_configuration = ClientConfig::builder_with_provider(provider)
+      .with_safe_default_protocol_versions()
+      .map_err(|error|
+        GenericError::new("creating the web regulation proxy")
+          .add_error("failed to create the tls client configuration")
+          .add_attachment("error", error.to_string())
+      )?
+      .with_root_certificates(root_certificates)
+      .with_no_client_auth();
+
+    client_configuration.alpn_protocols = vec![HTTP1.to_vec()];
+
+    let mut http2_client_configuration = client_configuration.clone();
+    http2_client_configuration.alpn_protocols = vec![HTTP2.to_vec(), HTTP1.to_vec()];
+
+    let no_intercept_hosts = NoInterceptHosts::new(
+      no_intercept_db::retrieve_all_host_patterns(database)?
+    );
+
+    let proxy_auto_config = ProxyAutoConfig::new(
+      proxied_host_db::retrieve_all_host_patterns(database)?
+    );
+
+    let website_visit_delayer = Arc::new(WebsiteVisitDelayer::open(database)?);
+    let website_visits_limiter = Arc::new(WebsiteVisitsLimiter::open(database)?);
+    let youtube = Arc::new(YoutubeRegulation::open(database)?);
+    let twitter = Arc::new(TwitterRegulation::open(database)?);
+    let media_type_blocker = Arc::new(MediaTypeBlocker::open(database)?);
+    let element_regulation = Arc::new(ElementRegulation::open(database)?);
+    let content_filter = Arc::new(ContentFilter::open(database)?);
+    let safe_search = Arc::new(SafeSearch::open(database, dns_forwarder_port)?);
+    let search_query_blocker = Arc::new(SearchQueryBlocker::open(database)?);
+    let view_time_allowances = Arc::new(ViewTimeAllowances::open(database)?);
+    let image_regulation = Arc::new(ImageRegulation::open(database)?);
+    let protobuf_regulation = Arc::new(ProtobufRegulation::open(database)?);
+    let encrypted_dns_prevention = Arc::new(EncryptedDnsPrevention::open(database, dns_forwarder_port)?);
+    let audit_log = AuditLog::open(database)?;
+
+    // The delayer goes first so the block page it sends to doesn't use up a visit.
+    let handlers: Vec<Arc<dyn TrafficHandler>> = vec![
+      Arc::clone(&website_visit_delayer) as Arc<dyn TrafficHandler>,
+      Arc::clone(&website_visits_limiter) as Arc<dyn TrafficHandler>,
+      Arc::clone(&media_type_blocker) as Arc<dyn TrafficHandler>,
+      Arc::clone(&youtube) as Arc<dyn TrafficHandler>,
+      Arc::clone(&twitter) as Arc<dyn TrafficHandler>,
+      Arc::clone(&element_regulation) as Arc<dyn TrafficHandler>,
+      Arc::clone(&content_filter) as Arc<dyn TrafficHandler>,
+      Arc::clone(&safe_search) as Arc<dyn TrafficHandler>,
+      Arc::clone(&search_query_blocker) as Arc<dyn TrafficHandler>,
+      Arc::clone(&view_time_allowances) as Arc<dyn TrafficHandler>,
+      Arc::clone(&image_regulation) as Arc<dyn TrafficHandler>,
+      Arc::clone(&protobuf_regulation) as Arc<dyn TrafficHandler>,
+      Arc::clone(&encrypted_dns_prevention) as Arc<dyn TrafficHandler>,
+    ];
+
+    Ok(Self {
+      port,
+      certificate_authority,
+      server_configuration: Arc::new(server_configuration),
+      client_configuration: Arc::new(client_configuration),
+      http2_server_configuration: Arc::new(http2_server_configuration),
+      http2_client_configuration: Arc::new(http2_client_configuration),
+      no_intercept_hosts: RwLock::new(no_intercept_hosts),
+      proxy_auto_config: RwLock::new(proxy_auto_config),
+      handlers: RwLock::new(handlers),
+      website_visit_delayer,
+      website_visits_limiter,
+      youtube,
+      twitter,
+      media_type_blocker,
+      element_regulation,
+      content_filter,
+      safe_search,
+      search_query_blocker,
+      view_time_allowances,
+      image_regulation,
+      protobuf_regulation,
+      encrypted_dns_prevention,
+      audit_log,
+    })
+  }
+
+  pub fn port(&self) -> u16 {
+    self.port
+  }
+
+  pub fn certificate_authority(&self) -> &CertificateAuthority {
+    &self.certificate_authority
+  }
+
+  /// `configuration`, but answering clients that send no server name with a
+  /// certificate for `host`, the address they connected to.
+  fn answering_as(&self, configuration: &ServerConfig, host: &str) -> Arc<ServerConfig> {
+    let mut configuration = configuration.clone();
+    configuration.cert_resolver = Arc::new(LeafCertificateResolver {
+      certificate_authority: Arc::clone(&self.certificate_authority),
+      fallback_server_name: Some(host.to_string()),
+    });
+
+    Arc::new(configuration)
+  }
+
+  pub fn no_intercept_hosts(&self) -> RwLockReadGuard<'_, NoInterceptHosts> {
+    self.no_intercept_hosts.read().unwrap()
+  }
+
+  pub fn no_intercept_hosts_mut(&self) -> RwLockWriteGuard<'_, NoInterceptHosts> {
+    self.no_intercept_hosts.write().unwrap()
+  }
+
+  pub fn proxy_auto_config(&self) -> RwLockReadGuard<'_, ProxyAutoConfig> {
+    self.proxy_auto_config.read().unwrap()
+  }
+
+  pub fn proxy_auto_config_mut(&self) -> RwLockWriteGuard<'_, ProxyAutoConfig> {
+    self.proxy_auto_config.write().unwrap()
+  }
+
+  pub fn website_visit_delayer(&self) -> &WebsiteVisitDelayer {
+    &self.website_visit_delayer
+  }
+
+  pub fn website_visits_limiter(&self) -> &WebsiteVisitsLimiter {
+    &self.website_visits_limiter
+  }
+
+  pub fn youtube(&self) -> &YoutubeRegulation {
+    &self.youtube
+  }
+
+  pub fn twitter(&self) -> &TwitterRegulation {
+    &self.twitter
+  }
+
+  pub fn media_type_blocker(&self) -> &MediaTypeBlocker {
+    &self.media_type_blocker
+  }
+
+  pub fn element_regulation(&self) -> &ElementRegulation {
+    &self.element_regulation
+  }
+
+  pub fn content_filter(&self) -> &ContentFilter {
+    &self.content_filter
+  }
+
+  pub fn safe_search(&self) -> &SafeSearch {
+    &self.safe_search
+  }
+
+  pub fn search_query_blocker(&self) -> &SearchQueryBlocker {
+    &self.search_query_blocker
+  }
+
+  pub fn view_time_allowances(&self) -> &ViewTimeAllowances {
+    &self.view_time_allowances
+  }
+
+  pub fn image_regulation(&self) -> &ImageRegulation {
+    &self.image_regulation
+  }
+
+  pub fn protobuf_regulation(&self) -> &ProtobufRegulation {
+    &self.protobuf_regulation
+  }
+
+  pub fn encrypted_dns_prevention(&self) -> &EncryptedDnsPrevention {
+    &self.encrypted_dns_prevention
+  }
+
+  pub fn audit_log(&self) -> &AuditLog {
+    &self.audit_log
+  }
+
+  pub fn add_handler(&self, handler: Arc<dyn TrafficHandler>) {
+    self.handlers.write().unwrap().push(handler);
+  }
+
+  fn handlers(&self) -> Vec<Arc<dyn TrafficHandler>> {
+    self.handlers.read().unwrap().clone()
+  }
+
+  /// Trusts our certificate authority system-wide, starts accepting
+  /// connections on the loopback interfaces and starts the DNS forwarder.
+  pub fn run(&self, daemon: Arc<Daemon>) -> Result<(), GenericError> {
+    install_certificate_into_system_trust_store(self.certificate_authority.certificate_pem())
+      .map_err(|error| error.change_context("running the web regulation proxy"))?;
+
+    for address in [
+      SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), self.port),
+      SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), self.port),
+    ] {
+      let listener = TcpListener::bind(address).map_err(|error|
+        GenericError::new("running the web regulation proxy")
+          .add_error("failed to bind the listener")
+          .add_attachment("address", address.to_string())
+          .add_attachment("io error", error.to_string())
+      )?;
+
+      let daemon = Arc::clone(&daemon);
+      thread::spawn(move || {
+        for stream in listener.incoming() {
+          let Ok(stream) = stream else {
+            continue;
+          };
+
+          let daemon = Arc::clone(&daemon);
+          thread::spawn(move || {
+            // Errors here are almost always peers going away mid-exchange,
+            // which isn't worth reporting.
+            let _ = handle_connection(&daemon, stream);
+          });
+        }
+      });
+    }
+
+    encrypted_dns::forwarder::run(Arc::clone(&daemon), self.encrypted_dns_prevention.forwarder_port())
+      .map_err(|error| error.change_context("running the web regulation proxy"))?;
+
+    if let Err(error) = self.encrypted_dns_prevention.apply_all_firewall_rules() {
+      daemon.internal_logger().log_error(error);
+    }
+
+    if let Err(error) = self.safe_search.apply_all_firewall_rules() {
+      daemon.internal_logger().log_error(error);
+    }
+
+    thread::spawn(move || loop {
+      let interval = match daemon.web_regulation_intrusive().safe_search().addresses().refresh() {
+        Ok(()) => {
+          safe_search::addresses::REFRESH_INTERVAL
+        }
+        Err(error) => {
+          daemon.internal_logger().log_error(error);
+          safe_search::addresses::RETRY_INTERVAL
+        }
+      };
+
+      thread::sleep(interval);
+    });
+
+    Ok(())
+  }
+}
+
+/// Where a connection is headed, as far as we know before reading from it.
+#[derive(Debug, Clone)]
+pub(super) struct Destination {
+  pub(super) user_id: Option<UserId>,
+  /// The host name from the CONNECT request or the tls server name indication.
+  pub(super) host: Option<String>,
+  pub(super) port: u16,
+  /// The address iptables redirected the connection away from.
+  pub(super) address: Option<SocketAddr>,
+}
+
+fn handle_connection(daemon: &Daemon, stream: TcpStream) -> io::Result<()> {
+  let _ = stream.set_nodelay(true);
+
+  let user_id = stream.peer_addr().ok().and_then(retrieve_tcp_socket_owner);
+
+  // Connections made to us directly rather than redirected to us report our
+  // own address as their original destination.
+  let redirected_from = retrieve_original_destination(&stream)
+    .filter(|address| Some(*address) != stream.local_addr().ok());
+
+  let destination = Destination {
+    user_id,
+    host: None,
+    port: redirected_from.map(|address| address.port()).unwrap_or(80),
+    address: redirected_from,
+  };
+
+  handle_plain_or_tls(daemon, stream, destination)
+}
+
+fn handle_plain_or_tls(daemon: &Daemon, stream: TcpStream, destination: Destination) -> io::Result<()> {
+  let mut first_byte = [0];
+  if stream.peek(&mut first_byte)? == 0 {
+    return Ok(());
+  }
+
+  if first_byte[0] == TLS_HANDSHAKE_RECORD {
+    handle_tls(daemon, stream, destination)
+  } else {
+    handle_plain(daemon, stream, destination)
+  }
+}
+
+fn handle_plain(daemon: &Daemon, stream: TcpStream, destination: Destination) -> io::Result<()> {
+  let mut client = RequestReader::new(stream);
+
+  // A CONNECT request opens a tunnel to whatever follows.
+  if destination.host.is_none() && destination.address.is_none() {
+    let Some((request, request_framing)) = http1::read_request_head(&mut client)? else {
+      return Ok(());
+    };
+
+    if request.method == "CONNECT" {
+      let Some((host, port)) = split_authority(&request.target) else {
+        client.get_mut().write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")?;
+        return Ok(());
+      };
+
+      client.get_mut().write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")?;
+
+      // Clients wait for our answer before sending anything else.
+      let (stream, read_ahead) = client.into_parts();
+      if !read_ahead.is_empty() {
+        return Err(io::Error::new(ErrorKind::InvalidData, "data sent before CONNECT was answered"));
+      }
+
+      return handle_plain_or_tls(
+        daemon,
+        stream,
+        Destination {
+          host: Some(host),
+          port: port.unwrap_or(443),
+          ..destination
+        },
+      );
+    }
+
+    return serve(daemon, client, Scheme::Http, destination, Some((request, request_framing)), None);
+  }
+
+  serve(daemon, client, Scheme::Http, destination, None, None)
+}
+
+/// Reads through a tcp stream while keeping a copy of everything read, so
+/// it can be replayed to the website if we end up not intercepting.
+struct RecordingReader<'a> {
+  stream: &'a TcpStream,
+  recorded: Vec<u8>,
+}
+
+impl<'a> Read for RecordingReader<'a> {
+  fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+    let read = self.stream.read(buffer)?;
+    self.recorded.extend_from_slice(&buffer[..read]);
+    Ok(read)
+  }
+}
+
+fn handle_tls(daemon: &Daemon, stream: TcpStream, mut destination: Destination) -> io::Result<()> {
+  let proxy = daemon.web_regulation_intrusive();
+
+  let mut reader = RecordingReader {
+    stream: &stream,
+    recorded: Vec::new(),
+  };
+
+  let mut acceptor = Acceptor::default();
+  let accepted = loop {
+    if acceptor.read_tls(&mut reader)? == 0 {
+      return Ok(());
+    }
+
+    match acceptor.accept() {
+      Ok(Some(accepted)) => {
+        break accepted;
+      }
+      Ok(None) => {
+        continue;
+      }
+      Err(_) => {
+        return Ok(());
+      }
+    }
+  };
+
+  let recorded = reader.recorded;
+
+  let server_name = accepted.client_hello().server_name().map(str::to_ascii_lowercase);
+  let sent_server_name = server_name.is_some();
+  if server_name.is_some() {
+    destination.host = server_name;
+  }
+
+  if destination.port == 80 {
+    destination.port = 443;
+  }
+
+  let Some(host) = destination.host.clone().or_else(||
+    destination.address.map(|address| address.ip().to_string())
+  ) else {
+    return Ok(());
+  };
+
+  let address = destination.address.map(|address| address.ip());
+  if proxy.encrypted_dns_prevention.blocks_connection(destination.user_id, &host, address) {
+    return Ok(());
+  }
+
+  if !proxy.no_intercept_hosts().is_intercepted(&host) {
+    pin_to_safe_address(daemon, &mut destination, &host)?;
+    let mut upstream = connect(&destination, &host)?;
+    let mut stream = stream;
+    return relay(&mut stream, &recorded, &mut upstream, &[]);
+  }
+
+  // We can only offer the client http/2 once we know the website speaks it,
+  // so connect to the website before answering.
+  let offers_http2 = accepted
+    .client_hello()
+    .alpn()
+    .is_some_and(|mut protocols| protocols.any(|protocol| protocol == HTTP2));
+
+  let mut upstream = None;
+  if offers_http2 {
+    pin_to_safe_address(daemon, &mut destination, &host)?;
+    // On failure, the first request gets a 502 like with http/1.x clients.
+    upstream = connect_tls(&destination, &host, &proxy.http2_client_configuration).ok();
+  }
+
+  let speaks_http2 = upstream
+    .as_ref()
+    .is_some_and(|(connection, _)| connection.alpn_protocol() == Some(HTTP2));
+
+  let server_configuration = if speaks_http2 {
+    &proxy.http2_server_configuration
+  } else {
+    &proxy.server_configuration
+  };
+
+  let server_configuration = if sent_server_name {
+    Arc::clone(server_configuration)
+  } else {
+    proxy.answering_as(server_configuration, &host)
+  };
+
+  let Ok(mut connection) = accepted.into_connection(server_configuration) else {
+    return Ok(());
+  };
+
+  let mut stream = stream;
+  if speaks_http2 {
+    while connection.is_handshaking() {
+      connection.complete_io(&mut stream)?;
+    }
+
+    match upstream {
+      Some((upstream_connection, upstream_stream)) if connection.alpn_protocol() == Some(HTTP2) => {
+        return serve_http2(
+          daemon,
+          Duplex::tls(connection, stream)?,
+          Duplex::tls(upstream_connection, upstream_stream)?,
+          destination,
+          host,
+        );
+      }
+      _ => {
+        // The client changed its mind, and the website already agreed on http/2.
+        upstream = None;
+      }
+    }
+  }
+
+  let upstream = upstream.map(|(connection, upstream_stream)| {
+    let socket: Box<dyn Socket> = Box::new(StreamOwned::new(connection, upstream_stream));
+    (host, destination.port, ResponseReader::new(socket))
+  });
+
+  let client = StreamOwned::new(connection, stream);
+  serve(daemon, RequestReader::new(client), Scheme::Https, destination, None, upstream)
+}
+
+/// Points `destination` at the safe address `host` is pinned to while
+/// SafeSearch is enforced on its user.
+fn pin_to_safe_address(daemon: &Daemon, destination: &mut Destination, host: &str) -> io::Result<()> {
+  let host = host.to_ascii_lowercase();
+  let pinned_address = daemon
+    .web_regulation_intrusive()
+    .safe_search()
+    .pinned_address(destination.user_id, &host, destination.port)?;
+
+  if let Some(address) = pinned_address {
+    destination.address = Some(address);
+  }
+
+  Ok(())
+}
+
+fn connect(destination: &Destination, host: &str) -> io::Result<TcpStream> {
+  let stream = match destination.address {
+    Some(address) => {
+      TcpStream::connect_timeout(&address, UPSTREAM_CONNECT_TIMEOUT)?
+    }
+    None => {
+      let host = host.trim_start_matches('[').trim_end_matches(']');
+      TcpStream::connect((host, destination.port))?
+    }
+  };
+
+  let _ = stream.set_nodelay(true);
+  Ok(stream)
+}
+
+fn connect_upstream(
+  daemon: &Daemon,
+  scheme: Scheme,
+  destination: &Destination,
+  host: &str,
+) -> io::Result<Box<dyn Socket>> {
+  let stream = connect(destination, host)?;
+
+  match scheme {
+    Scheme::Http => {
+      Ok(Box::new(stream))
+    }
+    Scheme::Https => {
+      let server_name = ServerName::try_from(host.to_string())
+        .map_err(|error| io::Error::new(ErrorKind::InvalidInput, error))?;
+
+      let connection = ClientConnection::new(
+        Arc::clone(&daemon.web_regulation_intrusive().client_configuration),
+        server_name,
+      ).map_err(io::Error::other)?;
+
+      Ok(Box::new(StreamOwned::new(connection, stream)))
+    }
+  }
+}
+
+/// Connects to a website over tls and completes the handshake, so the
+/// protocol it picked is known.
+fn connect_tls(
+  destination: &Destination,
+  host: &str,
+  configuration: &Arc<ClientConfig>,
+) -> io::Result<(ClientConnection, TcpStream)> {
+  let mut stream = connect(destination, host)?;
+
+  let server_name = ServerName::try_from(host.to_string())
+    .map_err(|error| io::Error::new(ErrorKind::InvalidInput, error))?;
+
+  let mut connection = ClientConnection::new(Arc::clone(configuration), server_name)
+    .map_err(io::Error::other)?;
+
+  while connection.is_handshaking() {
+    connection.complete_io(&mut stream)?;
+  }
+
+  Ok((connection, stream))
+}
+
+/// Splits "host:port", where host may be a bracketed ipv6 address.
+pub(super) fn split_authority(authority: &str) -> Option<(String, Option<u16>)> {
+  let authority = authority.trim();
+  if authority.is_empty() {
+    return None;
+  }
+
+  let (host, port) = match authority.rfind(':') {
+    Some(index) if !authority[index..].contains(']') => {
+      (&authority[..index], Some(authority[index + 1..].parse().ok()?))
+    }
+    _ => {
+      (authority, None)
+    }
+  };
+
+  Some((host.to_ascii_lowercase(), port))
+}
+
+/// A connection to a website along with the host and port it's for.
+type Upstream = (String, u16, ResponseReader<Box<dyn Socket>>);
+
+/// Exchanges http/1.x messages between a client and the websites it
+/// requests, letting handlers look at each exchange on the way.
+fn serve<C: Socket>(
+  daemon: &Daemon,
+  mut client: RequestReader<C>,
+  scheme: Scheme,
+  destination: Destination,
+  mut pending_request: Option<(RequestHead, BodyFraming)>,
+  // The website connection, reused across requests for the same host.
+  mut upstream: Option<Upstream>,
+) -> io::Result<()> {
+  let handlers = daemon.web_regulation_intrusive().handlers();
+  let audit_log = daemon.web_regulation_intrusive().audit_log();
+
+  loop {
+    let (request, request_framing) = match pending_request.take() {
+      Some(request) => request,
+      None => match http1::read_request_head(&mut client)? {
+        Some(request) => request,
+        None => return Ok(()),
+      },
+    };
+
+    let mut exchange = match prepare_exchange(scheme, &destination, request) {
+      Ok(exchange) => exchange,
+      Err(response) => {
+        write_response(client.get_mut(), &response, "GET", false)?;
+        return Ok(());
+      }
+    };
+
+    let client_keep_alive = exchange.request.is_keep_alive();
+
+    if let Some(response) = evaluate_request(daemon, &handlers, &mut exchange) {
+      audit_log.record(daemon, &exchange, Decision::Blocked);
+      io::copy(&mut BodyReader::new(&mut client), &mut io::sink())?;
+      write_response(client.get_mut(), &response, &exchange.request.method, client_keep_alive)?;
+      if !client_keep_alive {
+        return Ok(());
+      }
+      continue;
+    }
+
+    let is_request_body_inspected = request_framing != BodyFraming::None
+      && is_request_body_wanted(daemon, &handlers, &exchange);
+
+    let mut request_body = None;
+    if is_request_body_inspected {
+      if exchange.request.headers.contains_token("Expect", "100-continue") {
+        // The client waits for this, or a while, before sending the body.
+        client.get_mut().write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+        exchange.request.headers.remove("Expect");
+      }
+
+      let mut body_reader = BodyReader::new(&mut client);
+      request_body = Some(match http1::read_body(&mut body_reader, MAXIMUM_INSPECTED_BODY_LENGTH)? {
+        ReadBody::Complete(body) => {
+          ReadBody::Complete(body)
+        }
+        ReadBody::Truncated(mut body) => {
+          body.extend_from_slice(&body_reader.into_left_over());
+          ReadBody::Truncated(body)
+        }
+      });
+    }
+
+    if let Some(ReadBody::Complete(body)) = &request_body {
+      if let Some(response) = evaluate_request_body(daemon, &handlers, &exchange, body) {
+        audit_log.record(daemon, &exchange, Decision::Blocked);
+        write_response(client.get_mut(), &response, &exchange.request.method, client_keep_alive)?;
+        if !client_keep_alive {
+          return Ok(());
+        }
+        continue;
+      }
+
+      http1::set_content_length(&mut exchange.request.headers, body.len());
+    }
+
+    let is_reusable = upstream
+      .as_ref()
+      .is_some_and(|(host, port, _)| *host == exchange.host && *port == exchange.port);
+
+    if !is_reusable {
+      let mut upstream_destination = Destination {
+        port: exchange.port,
+        ..destination.clone()
+      };
+
+      let socket = match pin_to_safe_address(daemon, &mut upstream_destination, &exchange.host)
+        .and_then(|()| connect_upstream(daemon, scheme, &upstream_destination, &exchange.host))
+      {
+        Ok(socket) => socket,
+        Err(_) => {
+          let response = Response::html(502, "Bad Gateway", "");
+          write_response(client.get_mut(), &response, &exchange.request.method, false)?;
+          return Ok(());
+        }
+      };
+
+      upstream = Some((exchange.host.clone(), exchange.port, ResponseReader::new(socket)));
+    }
+
+    let (_, _, upstream_reader) = upstream.as_mut().unwrap();
+
+    let mut head = Vec::new();
+    exchange.request.write_into(&mut head);
+    upstream_reader.get_mut().write_all(&head)?;
+    match request_body {
+      Some(ReadBody::Complete(body)) => {
+        upstream_reader.get_mut().write_all(&body)?;
+        upstream_reader.get_mut().flush()?;
+      }
+      Some(ReadBody::Truncated(body)) => {
+        // Too long to inspect. Send what we have and stream the rest.
+        http1::write_body_part(upstream_reader.get_mut(), &body, request_framing)?;
+        http1::copy_body(&mut BodyReader::new(&mut client), upstream_reader.get_mut(), request_framing)?;
+      }
+      None => {
+        http1::copy_body(
+          &mut BodyReader::new(&mut client),
+          upstream_reader.get_mut(),
+          request_framing,
+        )?;
+      }
+    }
+
+    let (mut response, response_framing) = loop {
+      let (response, framing) = http1::read_response_head(upstream_reader, &exchange.request.method)?;
+      if (100..200).contains(&response.status_code) && response.status_code != 101 {
+        io::copy(&mut BodyReader::new(upstream_reader), &mut io::sink())?;
+        let mut head = Vec::new();
+        response.write_into(&mut head);
+        client.get_mut().write_all(&head)?;
+        continue;
+      }
+      break (response, framing);
+    };
+
+    if response.status_code == 101 {
+      let mut head = Vec::new();
+      response.write_into(&mut head);
+      client.get_mut().write_all(&head)?;
+
+      let (mut upstream_socket, upstream_read_ahead) = upstream.take().unwrap().2.into_parts();
+      let (mut client_socket, client_read_ahead) = client.into_parts();
+      return relay(&mut client_socket, &client_read_ahead, &mut upstream_socket, &upstream_read_ahead);
+    }
+
+    // The website declined to switch protocols, so the client's next
+    // request is http again.
+    client.resume_after_upgrade();
+
+    let (mut verdict, is_sniffed, mut rewriters) = evaluate_response(daemon, &handlers, &exchange, &mut response);
+
+    let mut body_reader = BodyReader::new(upstream_reader);
+
+    // The part of the body read so far, which is yet to be sent.
+    let mut body_start = Vec::new();
+    if is_sniffed
+      && !matches!(verdict, ResponseVerdict::Respond(_))
+      && !response.is_content_encoded()
+      && response_framing != BodyFraming::None
+    {
+      body_start = http1::read_body_start(&mut body_reader, SNIFFED_BODY_LENGTH)?;
+      if let Some(ours) = evaluate_response_body_start(daemon, &handlers, &exchange, &response, &body_start) {
+        verdict = ResponseVerdict::Respond(ours);
+      }
+    }
+
+    if response.is_content_encoded() || response_framing == BodyFraming::None {
+      rewriters.clear();
+    }
+
+    let mut keep_alive = client_keep_alive
+      && response.is_keep_alive()
+      && response_framing != BodyFraming::UntilEof;
+
+    match verdict {
+      ResponseVerdict::Respond(ours) => {
+        audit_log.record(daemon, &exchange, Decision::Blocked);
+        // Don't leave the website's response half-read on a connection we may reuse.
+        upstream = None;
+        write_response(client.get_mut(), &ours, &exchange.request.method, client_keep_alive)?;
+        if !client_keep_alive {
+          return Ok(());
+        }
+        continue;
+      }
+      ResponseVerdict::InspectBody if !response.is_content_encoded() && response_framing != BodyFraming::None => {
+        let limit = MAXIMUM_INSPECTED_BODY_LENGTH - body_start.len();
+
+        match http1::read_body(&mut body_reader, limit)? {
+          ReadBody::Complete(rest) => {
+            let mut body = body_start;
+            body.extend_from_slice(&rest);
+            if !rewriters.is_empty() {
+              let mut rewritten = rewrite_body(&mut rewriters, Some(&body));
+              rewritten.extend_from_slice(&rewrite_body(&mut rewriters, None));
+              body = rewritten;
+            }
+
+            let ours = evaluate_response_body(daemon, &handlers, &exchange, &mut response, &mut body);
+
+            let decision = if ours.is_some() { Decision::Blocked } else { Decision::Allowed };
+            audit_log.record(daemon, &exchange, decision);
+
+            let complete = ours.unwrap_or_else(|| Response::new(response.clone(), body));
+            write_response(client.get_mut(), &complete, &exchange.request.method, keep_alive)?;
+          }
+          ReadBody::Truncated(rest) => {
+            audit_log.record(daemon, &exchange, Decision::Allowed);
+
+            let mut body = body_start;
+            body.extend_from_slice(&rest);
+            if !rewriters.is_empty() {
+              body = rewrite_body(&mut rewriters, Some(&body));
+            }
+
+            // Too long to inspect. Send what we have and stream the rest,
+            // delimiting the body by closing the connection.
+            response.headers.remove("Content-Length");
+            response.headers.remove("Transfer-Encoding");
+            response.headers.insert("Connection", "close");
+
+            let mut head = Vec::new();
+            response.write_into(&mut head);
+            client.get_mut().write_all(&head)?;
+            client.get_mut().write_all(&body)?;
+            if rewriters.is_empty() {
+              http1::copy_body(&mut body_reader, client.get_mut(), BodyFraming::UntilEof)?;
+            } else {
+              http1::copy_rewritten_body(&mut body_reader, client.get_mut(), BodyFraming::UntilEof, |input| {
+                rewrite_body(&mut rewriters, input)
+              })?;
+            }
+            shutdown(client.get_ref());
+            return Ok(());
+          }
+        }
+      }
+      _ if !rewriters.is_empty() => {
+        audit_log.record(daemon, &exchange, Decision::Allowed);
+
+        // The length changes, so the body is sent chunked, or delimited by
+        // closing the connection if either side only speaks HTTP/1.0.
+        response.headers.remove("Content-Length");
+        response.headers.remove("Transfer-Encoding");
+        let framing = if exchange.request.version >= 1 && response.version >= 1 {
+          response.headers.insert("Transfer-Encoding", "chunked");
+          BodyFraming::Chunked
+        } else {
+          keep_alive = false;
+          BodyFraming::UntilEof
+        };
+
+        if !keep_alive {
+          response.headers.insert("Connection", "close");
+        }
+
+        let mut head = Vec::new();
+        response.write_into(&mut head);
+        client.get_mut().write_all(&head)?;
+        http1::write_body_part(client.get_mut(), &rewrite_body(&mut rewriters, Some(&body_start)), framing)?;
+        http1::copy_rewritten_body(&mut body_reader, client.get_mut(), framing, |input| {
+          rewrite_body(&mut rewriters, input)
+        })?;
+      }
+      _ => {
+        audit_log.record(daemon, &exchange, Decision::Allowed);
+
+        if !keep_alive {
+          response.headers.insert("Connection", "close");
+        }
+
+        let mut head = Vec::new();
+        response.write_into(&mut head);
+        client.get_mut().write_all(&head)?;
+        http1::write_body_part(client.get_mut(), &body_start, response_framing)?;
+        http1::copy_body(&mut body_reader, client.get_mut(), response_framing)?;
+      }
+    }
+
+    if !keep_alive {
+      shutdown(client.get_ref());
+      return Ok(());
+    }
+  }
+}
+
+pub(super) fn prepare_exchange(scheme: Scheme, destination: &Destination, mut request: RequestHead) -> Result<Exchange, Response> {
+  request.headers.remove("Proxy-Connection");
+  request.headers.remove("Proxy-Authorization");
+
+  // Explicitly proxied plain http requests carry the whole url as their target.
+  if !request.target.starts_with('/') && request.method != "OPTIONS" {
+    let url = url::Url::parse(&request.target).map_err(|_| bad_request())?;
+    let host = url.host_str().ok_or_else(bad_request)?.to_ascii_lowercase();
+    let port = url.port_or_known_default().unwrap_or(scheme.default_port());
+
+    if is_misdirected(destination, &host) {
+      return Err(misdirected_request());
+    }
+
+    request.target = match url.query() {
+      Some(query) => format!("{}?{}", url.path(), query),
+      None => url.path().to_string(),
+    };
+
+    return Ok(Exchange {
+      user_id: destination.user_id,
+      scheme,
+      host,
+      port,
+      request,
+    });
+  }
+
+  let authority_host = request
+    .headers
+    .get("Host")
+    .and_then(split_authority)
+    .map(|(host, _)| host);
+
+  if authority_host.as_ref().is_some_and(|host| is_misdirected(destination, host)) {
+    return Err(misdirected_request());
+  }
+
+  let host = destination
+    .host
+    .clone()
+    .or(authority_host)
+    .or_else(|| destination.address.map(|address| address.ip().to_string()))
+    .ok_or_else(bad_request)?;
+
+  Ok(Exchange {
+    user_id: destination.user_id,
+    scheme,
+    host,
+    port: destination.port,
+    request,
+  })
+}
+
+/// Whether a request for `host` came over a connection for another host,
+/// as told by the CONNECT request or the tls server name indication. Rules
+/// are evaluated for the host we know the connection is for, so a request
+/// naming another one, which we'd forward there, could slip past them.
+fn is_misdirected(destination: &Destination, host: &str) -> bool {
+  destination
+    .host
+    .as_deref()
+    .is_some_and(|destination_host| destination_host.trim_end_matches('.') != host.trim_end_matches('.'))
+}
+
+fn bad_request() -> Response {
+  Response::html(400, "Bad Request", "")
+}
+
+fn misdirected_request() -> Response {
+  Response::html(421, "Misdirected Request", "")
+}
+
+// SECTION: Rule evaluation shared by http/1.x exchanges and http/2 streams.
+
+/// Lets handlers look at a request. Returns the response of the first that
+/// answers it in the website's stead.
+fn evaluate_request(
+  daemon: &Daemon,
+  handlers: &[Arc<dyn TrafficHandler>],
+  exchange: &mut Exchange,
+) -> Option<Response> {
+  // Handlers can only read bodies we don't have to decompress.
+  if handlers.iter().any(|handler| handler.may_inspect_response_body(daemon, exchange)) {
+    exchange.request.headers.insert("Accept-Encoding", "identity");
+  }
+
+  for handler in handlers {
+    if let RequestVerdict::Respond(response) = handler.on_request(daemon, exchange) {
+      return Some(response);
+    }
+  }
+
+  None
+}
+
+fn is_request_body_wanted(daemon: &Daemon, handlers: &[Arc<dyn TrafficHandler>], exchange: &Exchange) -> bool {
+  !exchange.request.is_content_encoded()
+    && handlers.iter().any(|handler| handler.may_inspect_request_body(daemon, exchange))
+}
+
+fn evaluate_request_body(
+  daemon: &Daemon,
+  handlers: &[Arc<dyn TrafficHandler>],
+  exchange: &Exchange,
+  body: &[u8],
+) -> Option<Response> {
+  for handler in handlers {
+    if let RequestVerdict::Respond(response) = handler.on_request_body(daemon, exchange, body) {
+      return Some(response);
+    }
+  }
+
+  None
+}
+
+/// Lets handlers look at a response. Returns what to do with it, whether
+/// handlers want to see the start of its body first, and the rewriters to
+/// pass its body through.
+fn evaluate_response(
+  daemon: &Daemon,
+  handlers: &[Arc<dyn TrafficHandler>],
+  exchange: &Exchange,
+  response: &mut ResponseHead,
+) -> (ResponseVerdict, bool, Vec<Box<dyn BodyRewriter>>) {
+  let mut verdict = ResponseVerdict::Forward;
+  let mut is_sniffed = false;
+  let mut rewriters = Vec::new();
+  for handler in handlers {
+    match handler.on_response(daemon, exchange, response) {
+      ResponseVerdict::Forward => {}
+      ResponseVerdict::RewriteBody(rewriter) => {
+        rewriters.push(rewriter);
+      }
+      ResponseVerdict::InspectBody => {
+        verdict = ResponseVerdict::InspectBody;
+      }
+      ResponseVerdict::SniffBody => {
+        is_sniffed = true;
+      }
+      respond @ ResponseVerdict::Respond(_) => {
+        verdict = respond;
+        break;
+      }
+    }
+  }
+
+  (verdict, is_sniffed, rewriters)
+}
+
+fn evaluate_response_body_start(
+  daemon: &Daemon,
+  handlers: &[Arc<dyn TrafficHandler>],
+  exchange: &Exchange,
+  response: &ResponseHead,
+  body_start: &[u8],
+) -> Option<Response> {
+  for handler in handlers {
+    if let BodyVerdict::Respond(ours) = handler.on_response_body_start(daemon, exchange, response, body_start) {
+      return Some(ours);
+    }
+  }
+
+  None
+}
+
+fn evaluate_response_body(
+  daemon: &Daemon,
+  handlers: &[Arc<dyn TrafficHandler>],
+  exchange: &Exchange,
+  response: &mut ResponseHead,
+  body: &mut Vec<u8>,
+) -> Option<Response> {
+  for handler in handlers {
+    if let BodyVerdict::Respond(ours) = handler.on_response_body(daemon, exchange, response, body) {
+      return Some(ours);
+    }
+  }
+
+  None
+}
+
+/// Passes the next part of a body through every rewriter in turn, or ends
+/// them all if `input` is `None`.
+fn rewrite_body(rewriters: &mut [Box<dyn BodyRewriter>], input: Option<&[u8]>) -> Vec<u8> {
+  let mut data = input.unwrap_or_default().to_vec();
+  for rewriter in rewriters {
+    let mut output = Vec::new();
+    rewriter.write(&data, &mut output);
+    if input.is_none() {
+      rewriter.end(&mut output);
+    }
+    data = output;
+  }
+  data
+}
+
+fn write_response(
+  client: &mut impl Write,
+  response: &Response,
+  request_method: &str,
+  keep_alive: bool,
+) -> io::Result<()> {
+  let mut head = response.head.clone();
+  head.version = 1;
+  http1::set_content_length(&mut head.headers, response.body.len());
+  if !keep_alive {
+    head.headers.insert("Connection", "close");
+  }
+
+  let mut bytes = Vec::new();
+  head.write_into(&mut bytes);
+  if request_method != "HEAD" {
+    bytes.extend_from_slice(&response.body);
+  }
+
+  client.write_all(&bytes)?;
+  client.flush()
+}
+
+// SECTION: HTTP/2.
+
+/// The website's end of an http/2 connection we serve, reopened whenever the
+/// website stops taking new streams on it.
+struct Http2Upstream<'a> {
+  daemon: &'a Daemon,
+  destination: Destination,
+  host: String,
+  connection: Mutex<Arc<http2::Connection>>,
+}
+
+impl Http2Upstream<'_> {
+  fn connection(&self) -> io::Result<Arc<http2::Connection>> {
+    let mut connection = self.connection.lock().unwrap();
+    if !connection.is_accepting_streams() {
+      let proxy = self.daemon.web_regulation_intrusive();
+      let (tls, stream) = connect_tls(&self.destination, &self.host, &proxy.http2_client_configuration)?;
+      if tls.alpn_protocol() != Some(HTTP2) {
+        return Err(io::Error::other("the website no longer speaks http/2"));
+      }
+      *connection = http2::Connection::client(Duplex::tls(tls, stream)?)?;
+    }
+
+    Ok(Arc::clone(&connection))
+  }
+
+  fn close(&self) {
+    self.connection.lock().unwrap().close();
+  }
+}
+
+/// Exchanges http/2 streams between a client and the website it connected
+/// to, each on a thread of its own, letting handlers look at each stream
+/// like `serve` lets them look at each exchange.
+fn serve_http2(
+  daemon: &Daemon,
+  client: Duplex,
+  upstream: Duplex,
+  destination: Destination,
+  host: String,
+) -> io::Result<()> {
+  let client = http2::Connection::server(client)?;
+  let upstream = Http2Upstream {
+    daemon,
+    destination,
+    host,
+    connection: Mutex::new(http2::Connection::client(upstream)?),
+  };
+
+  let handlers = daemon.web_regulation_intrusive().handlers();
+
+  thread::scope(|scope| {
+    while let Some(stream) = client.accept() {
+      let (handlers, upstream) = (&handlers, &upstream);
+      scope.spawn(move || {
+        // Like in `handle_connection`, errors are peers going away.
+        let _ = serve_stream(daemon, handlers, upstream, stream);
+      });
+    }
+
+    upstream.close();
+  });
+
+  client.close();
+  Ok(())
+}
+
+fn serve_stream(
+  daemon: &Daemon,
+  handlers: &[Arc<dyn TrafficHandler>],
+  upstream: &Http2Upstream,
+  client: http2::Stream,
+) -> io::Result<()> {
+  let audit_log = daemon.web_regulation_intrusive().audit_log();
+
+  let (fields, has_request_body) = client.read_head()?;
+  // Requests without a path, like CONNECT, aren't forwarded.
+  let exchange = http2::request_head(fields)
+    .ok_or_else(bad_request)
+    .and_then(|request| prepare_exchange(Scheme::Https, &upstream.destination, request));
+
+  let mut exchange = match exchange {
+    Ok(exchange) => exchange,
+    Err(response) => {
+      return send_http2_response(&client, &response, "GET");
+    }
+  };
+
+  if let Some(response) = evaluate_request(daemon, handlers, &mut exchange) {
+    audit_log.record(daemon, &exchange, Decision::Blocked);
+    return send_http2_response(&client, &response, &exchange.request.method);
+  }
+
+  let mut request_body = None;
+  if has_request_body && is_request_body_wanted(daemon, handlers, &exchange) {
+    request_body = Some(http1::read_body(&mut &client, MAXIMUM_INSPECTED_BODY_LENGTH)?);
+  }
+
+  if let Some(ReadBody::Complete(body)) = &request_body {
+    if let Some(response) = evaluate_request_body(daemon, handlers, &exchange, body) {
+      audit_log.record(daemon, &exchange, Decision::Blocked);
+      return send_http2_response(&client, &response, &exchange.request.method);
+    }
+
+    http1::set_content_length(&mut exchange.request.headers, body.len());
+  }
+
+  let fields = http2::request_fields(&exchange);
+  let website = match upstream
+    .connection()
+    .and_then(|connection| connection.open_stream(&fields, !has_request_body))
+  {
+    Ok(website) => website,
+    Err(_) => {
+      return send_http2_response(&client, &Response::html(502, "Bad Gateway", ""), &exchange.request.method);
+    }
+  };
+
+  thread::scope(|scope| {
+    // The rest of the request body is copied while the response comes in, so
+    // streams both sides keep sending on, like gRPC's, keep flowing.
+    match request_body {
+      Some(ReadBody::Complete(body)) => {
+        website.send_data(&body, true)?;
+      }
+      Some(ReadBody::Truncated(body)) => {
+        // Too long to inspect. Send what we have and stream the rest.
+        let (client, website) = (&client, &website);
+        scope.spawn(move || {
+          website
+            .send_data(&body, false)
+            .and_then(|()| copy_http2_body(client, website, &mut []))
+        });
+      }
+      None if has_request_body => {
+        let (client, website) = (&client, &website);
+        scope.spawn(move || copy_http2_body(client, website, &mut []));
+      }
+      None => {}
+    }
+
+    forward_http2_response(daemon, handlers, &exchange, &client, &website)
+  })
+}
+
+/// Relays the response to the request of `exchange` from the website's
+/// stream to the client's, letting handlers look at it on the way.
+fn forward_http2_response(
+  daemon: &Daemon,
+  handlers: &[Arc<dyn TrafficHandler>],
+  exchange: &Exchange,
+  client: &http2::Stream,
+  website: &http2::Stream,
+) -> io::Result<()> {
+  let audit_log = daemon.web_regulation_intrusive().audit_log();
+  let method = &exchange.request.method;
+
+  let response = website
+    .read_head()
+    .ok()
+    .and_then(|(fields, has_body)| Some((http2::response_head(fields)?, has_body)));
+
+  let Some((mut response, has_response_body)) = response else {
+    return send_http2_response(client, &Response::html(502, "Bad Gateway", ""), method);
+  };
+
+  let (mut verdict, is_sniffed, mut rewriters) = evaluate_response(daemon, handlers, exchange, &mut response);
+
+  // The part of the body read so far, which is yet to be sent.
+  let mut body_start = Vec::new();
+  if is_sniffed
+    && !matches!(verdict, ResponseVerdict::Respond(_))
+    && !response.is_content_encoded()
+    && has_response_body
+  {
+    body_start = http1::read_body_start(&mut &*website, SNIFFED_BODY_LENGTH)?;
+    if let Some(ours) = evaluate_response_body_start(daemon, handlers, exchange, &response, &body_start) {
+      verdict = ResponseVerdict::Respond(ours);
+    }
+  }
+
+  if response.is_content_encoded() || !has_response_body {
+    rewriters.clear();
+  }
+
+  match verdict {
+    ResponseVerdict::Respond(ours) => {
+      audit_log.record(daemon, exchange, Decision::Blocked);
+      send_http2_response(client, &ours, method)
+    }
+    ResponseVerdict::InspectBody if !response.is_content_encoded() && has_response_body => {
+      let limit = MAXIMUM_INSPECTED_BODY_LENGTH - body_start.len();
+
+      match http1::read_body(&mut &*website, limit)? {
+        ReadBody::Complete(rest) => {
+          let mut body = body_start;
+          body.extend_from_slice(&rest);
+          if !rewriters.is_empty() {
+            let mut rewritten = rewrite_body(&mut rewriters, Some(&body));
+            rewritten.extend_from_slice(&rewrite_body(&mut rewriters, None));
+            body = rewritten;
+          }
+
+          let ours = evaluate_response_body(daemon, handlers, exchange, &mut response, &mut body);
+
+          let decision = if ours.is_some() { Decision::Blocked } else { Decision::Allowed };
+          audit_log.record(daemon, exchange, decision);
+
+          if let Some(ours) = ours {
+            return send_http2_response(client, &ours, method);
+          }
+
+          http1::set_content_length(&mut response.headers, body.len());
+          client.send_head(&http2::response_fields(&response), false)?;
+          client.send_data(&body, false)?;
+          // Only the website's trailers are left.
+          copy_http2_body(website, client, &mut [])
+        }
+        ReadBody::Truncated(rest) => {
+          audit_log.record(daemon, exchange, Decision::Allowed);
+
+          let mut body = body_start;
+          body.extend_from_slice(&rest);
+          if !rewriters.is_empty() {
+            body = rewrite_body(&mut rewriters, Some(&body));
+            response.headers.remove("Content-Length");
+          }
+
+          // Too long to inspect. Send what we have and stream the rest.
+          client.send_head(&http2::response_fields(&response), false)?;
+          client.send_data(&body, false)?;
+          copy_http2_body(website, client, &mut rewriters)
+        }
+      }
+    }
+    _ if !rewriters.is_empty() => {
+      audit_log.record(daemon, exchange, Decision::Allowed);
+
+      response.headers.remove("Content-Length");
+      client.send_head(&http2::response_fields(&response), false)?;
+      client.send_data(&rewrite_body(&mut rewriters, Some(&body_start)), false)?;
+      copy_http2_body(website, client, &mut rewriters)
+    }
+    _ => {
+      audit_log.record(daemon, exchange, Decision::Allowed);
+
+      client.send_head(&http2::response_fields(&response), !has_response_body)?;
+      if !has_response_body {
+        return Ok(());
+      }
+
+      client.send_data(&body_start, false)?;
+      copy_http2_body(website, client, &mut [])
+    }
+  }
+}
+
+/// Copies the rest of a body between streams, through `rewriters` if there
+/// are any, then ends it with the trailers that followed it.
+fn copy_http2_body(
+  mut from: &http2::Stream,
+  to: &http2::Stream,
+  rewriters: &mut [Box<dyn BodyRewriter>],
+) -> io::Result<()> {
+  let mut buffer = vec![0; COPY_BUFFER_LENGTH];
+  loop {
+    let read = from.read(&mut buffer)?;
+    if read == 0 {
+      break;
+    }
+
+    if rewriters.is_empty() {
+      to.send_data(&buffer[..read], false)?;
+    } else {
+      to.send_data(&rewrite_body(rewriters, Some(&buffer[..read])), false)?;
+    }
+  }
+
+  if !rewriters.is_empty() {
+    to.send_data(&rewrite_body(rewriters, None), false)?;
+  }
+
+  match from.take_trailers() {
+    Some(trailers) => {
+      to.send_head(&trailers, true)
+    }
+    None => {
+      to.finish()
+    }
+  }
+}
+
+fn send_http2_response(client: &http2::Stream, response: &Response, request_method: &str) -> io::Result<()> {
+  let mut head = response.head.clone();
+  http1::set_content_length(&mut head.headers, response.body.len());
+  let fields = http2::response_fields(&head);
+
+  if request_method == "HEAD" || response.body.is_empty() {
+    return client.send_head(&fields, true);
+  }
+
+  client.send_head(&fields, false)?;
+  client.send_data(&response.body, true)
+}
+
+fn shutdown(socket: &impl Socket) {
+  let _ = socket.tcp_stream().shutdown(Shutdown::Both);
+}
+
+fn is_timeout(error: &io::Error) -> bool {
+  matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
+
+/// Copies bytes both ways until either side closes its connection. Used for
+/// tunnels and upgraded connections, like websockets. `a_read_ahead` and
+/// `b_read_ahead` are bytes already read from `a` and `b` respectively.
+pub(super) fn relay(
+  a: &mut impl Socket,
+  a_read_ahead: &[u8],
+  b: &mut impl Socket,
+  b_read_ahead: &[u8],
+) -> io::Result<()> {
+  b.write_all(a_read_ahead)?;
+  b.flush()?;
+  a.write_all(b_read_ahead)?;
+  a.flush()?;
+
+  let result = if a.is_plain() && b.is_plain() {
+    relay_plain(a.tcp_stream(), b.tcp_stream())
+  } else {
+    relay_tls(a, b)
+  };
+
+  shutdown(a);
+  shutdown(b);
+  result
+}
+
+/// Copies each direction on its own thread, so neither waits on the other.
+fn relay_plain(a: &TcpStream, b: &TcpStream) -> io::Result<()> {
+  thread::scope(|scope| {
+    let a_to_b = scope.spawn(|| copy_until_closed(a, b));
+    let b_to_a = copy_until_closed(b, a);
+    a_to_b.join().unwrap_or(Ok(())).and(b_to_a)
+  })
+}
+
+/// Copies `from` into `to` until `from` is done sending, and tells `to` so.
+fn copy_until_closed(mut from: &TcpStream, mut to: &TcpStream) -> io::Result<()> {
+  match io::copy(&mut from, &mut to) {
+    Ok(_) => {
+      let _ = to.shutdown(Shutdown::Write);
+      Ok(())
+    }
+    Err(error) => {
+      // Wake up the copy going the other way.
+      let _ = from.shutdown(Shutdown::Both);
+      let _ = to.shutdown(Shutdown::Both);
+      Err(error)
+    }
+  }
+}
+
+/// Neither side's socket can be read from another thread once wrapped in a
+/// tls session, so wait until either has something to read.
+fn relay_tls(a: &mut impl Socket, b: &mut impl Socket) -> io::Result<()> {
+  // Data that is not application data, like a session ticket, makes the
+  // socket readable, yet its read waits for the next record.
+  a.tcp_stream().set_read_timeout(Some(RELAY_RECORD_TIMEOUT))?;
+  b.tcp_stream().set_read_timeout(Some(RELAY_RECORD_TIMEOUT))?;
+
+  let mut buffer = vec![0; COPY_BUFFER_LENGTH];
+  loop {
+    let (a_is_readable, b_is_readable) = wait_until_readable(a, b)?;
+
+    if a_is_readable && !pump(a, b, &mut buffer)? {
+      return Ok(());
+    }
+
+    if b_is_readable && !pump(b, a, &mut buffer)? {
+      return Ok(());
+    }
+  }
+}
+
+fn wait_until_readable(a: &impl Socket, b: &impl Socket) -> io::Result<(bool, bool)> {
+  if a.has_buffered_data() || b.has_buffered_data() {
+    return Ok((a.has_buffered_data(), b.has_buffered_data()));
+  }
+
+  let descriptor_of = |socket: &TcpStream| socket.as_raw_fd();
+  let mut descriptors = [descriptor_of(a.tcp_stream()), descriptor_of(b.tcp_stream())].map(|fd| libc::pollfd {
+    fd,
+    events: libc::POLLIN,
+    revents: 0,
+  });
+
+  // SAFETY: `descriptors` is an array of two valid pollfd structures.
+  while unsafe { libc::poll(descriptors.as_mut_ptr(), 2, -1) } < 0 {
+    let error = io::Error::last_os_error();
+    if error.kind() != ErrorKind::Interrupted {
+      return Err(error);
+    }
+  }
+
+  Ok((descriptors[0].revents != 0, descriptors[1].revents != 0))
+}
+
+/// Moves available bytes from one side to the other. Returns false once
+/// `from` is closed.
+fn pump(from: &mut impl Read, to: &mut impl Write, buffer: &mut [u8]) -> io::Result<bool> {
+  match from.read(buffer) {
+    Ok(0) => {
+      Ok(false)
+    }
+    Ok(read) => {
+      to.write_all(&buffer[..read])?;
+      to.flush()?;
+      Ok(true)
+    }
+    Err(error) if is_timeout(&error) => {
+      Ok(true)
+    }
+    Err(error) => {
+      Err(error)
+    }
+  }
+}
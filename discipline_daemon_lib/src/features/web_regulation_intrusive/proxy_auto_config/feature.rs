@@ -0,0 +1,90 @@
+use std::fmt::Write;
+use std::net::SocketAddr;
+use super::super::url_pattern::HostPattern;
+
+/// The path the block page server serves the proxy auto-config file on.
+pub const PROXY_AUTO_CONFIG_PATH: &str = "/proxy.pac";
+
+pub const PROXY_AUTO_CONFIG_MEDIA_TYPE: &str = "application/x-ns-proxy-autoconfig";
+
+/// Sends the traffic of chosen hosts to a local proxy where it can't be
+/// redirected there transparently, through a proxy auto-config file that
+/// the system proxy settings of managed users point browsers at.
+///
+/// Everything else goes direct. Unlike the firewall redirection, this only
+/// binds clients that honor the system proxy settings.
+#[derive(Debug, Clone)]
+pub struct ProxyAutoConfig {
+  proxied_hosts: Vec<HostPattern>,
+}
+
+impl ProxyAutoConfig {
+  pub fn new(proxied_hosts: Vec<HostPattern>) -> Self {
+    Self {
+      proxied_hosts,
+    }
+  }
+
+  /// The url of the proxy auto-config file, served by the block page server.
+  pub fn url(block_page_port: u16) -> String {
+    format!("http://127.0.0.1:{block_page_port}{PROXY_AUTO_CONFIG_PATH}")
+  }
+
+  pub fn proxied_hosts(&self) -> &Vec<HostPattern> {
+    &self.proxied_hosts
+  }
+
+  pub fn contains(&self, pattern: &HostPattern) -> bool {
+    self.proxied_hosts.contains(pattern)
+  }
+
+  pub fn add(&mut self, pattern: HostPattern) {
+    if !self.contains(&pattern) {
+      self.proxied_hosts.push(pattern);
+    }
+  }
+
+  pub fn remove(&mut self, pattern: &HostPattern) {
+    self.proxied_hosts.retain(|other| other != pattern);
+  }
+
+  pub fn is_proxied(&self, host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    self
+      .proxied_hosts
+      .iter()
+      .any(|pattern| pattern.matches(&host))
+  }
+
+  /// Generates the proxy auto-config script, which matches hosts the way
+  /// `is_proxied` does.
+  ///
+  /// There is deliberately no fallback to going direct when the proxy is
+  /// unreachable, since that would let users bypass it by stopping it.
+  pub fn script(&self, proxy_address: SocketAddr) -> String {
+    let mut script = String::new();
+    script.push_str("function FindProxyForURL(url, host) {\n");
+    script.push_str("  host = host.toLowerCase().replace(/\\.$/, \"\");\n");
+
+    // Host patterns only contain letters, digits, dashes, underscores and
+    // dots, so they need no escaping in string literals.
+    for pattern in &self.proxied_hosts {
+      let condition = match pattern.as_str().strip_prefix("*.") {
+        Some(suffix) => {
+          format!("host == \"{suffix}\" || dnsDomainIs(host, \".{suffix}\")")
+        }
+        None => {
+          format!("host == \"{}\"", pattern.as_str())
+        }
+      };
+
+      let _ = writeln!(script, "  if ({condition}) {{");
+      let _ = writeln!(script, "    return \"PROXY {proxy_address}\";");
+      script.push_str("  }\n");
+    }
+
+    script.push_str("  return \"DIRECT\";\n");
+    script.push_str("}\n");
+    script
+  }
+}
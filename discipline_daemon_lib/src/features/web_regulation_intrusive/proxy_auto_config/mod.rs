@@ -0,0 +1,9 @@
+pub mod feature;
+pub use feature::{
+  ProxyAutoConfig,
+  PROXY_AUTO_CONFIG_MEDIA_TYPE,
+  PROXY_AUTO_CONFIG_PATH,
+};
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,49 @@
+use std::net::SocketAddr;
+use super::super::url_pattern::HostPattern;
+use super::*;
+
+fn proxy_auto_config(patterns: &[&str]) -> ProxyAutoConfig {
+  ProxyAutoConfig::new(
+    patterns
+      .iter()
+      .map(|pattern| HostPattern::parse(pattern).unwrap())
+      .collect()
+  )
+}
+
+fn proxy_address() -> SocketAddr {
+  "127.0.0.1:9119".parse().unwrap()
+}
+
+#[test]
+fn proxies_matching_hosts_only() {
+  let proxy_auto_config = proxy_auto_config(&["*.example.com", "news.org"]);
+
+  assert!(proxy_auto_config.is_proxied("example.com"));
+  assert!(proxy_auto_config.is_proxied("WWW.Example.com."));
+  assert!(proxy_auto_config.is_proxied("news.org"));
+  assert!(!proxy_auto_config.is_proxied("www.news.org"));
+  assert!(!proxy_auto_config.is_proxied("notexample.com"));
+}
+
+#[test]
+fn scripts_route_proxied_hosts_through_the_proxy() {
+  let script = proxy_auto_config(&["*.example.com", "news.org"]).script(proxy_address());
+
+  assert!(script.starts_with("function FindProxyForURL(url, host) {\n"));
+  assert!(script.contains(
+    "  if (host == \"example.com\" || dnsDomainIs(host, \".example.com\")) {\n    return \"PROXY 127.0.0.1:9119\";\n  }\n"
+  ));
+  assert!(script.contains(
+    "  if (host == \"news.org\") {\n    return \"PROXY 127.0.0.1:9119\";\n  }\n"
+  ));
+  assert!(script.ends_with("  return \"DIRECT\";\n}\n"));
+}
+
+#[test]
+fn scripts_without_proxied_hosts_go_direct() {
+  let script = proxy_auto_config(&[]).script("[::1]:3128".parse().unwrap());
+
+  assert!(!script.contains("PROXY"));
+  assert!(script.contains("return \"DIRECT\";"));
+}
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+use crate::{DateTime, TimeRange, Weekday, WeekdayRange};
+
+/// When a web rule is in effect.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RuleActivator {
+  AllTheTime,
+  OnWeekday(Weekday),
+  InTimeRange(TimeRange),
+  InWeekdayRange(WeekdayRange),
+}
+
+impl RuleActivator {
+  pub fn is_effective(&self, now: DateTime) -> bool {
+    match self {
+      RuleActivator::OnWeekday(weekday) => {
+        now.weekday() == *weekday
+      }
+      RuleActivator::InTimeRange(time_range) => {
+        time_range.contains_time(now.time())
+      }
+      RuleActivator::InWeekdayRange(weekday_range) => {
+        weekday_range.contains_weekday(now.weekday())
+      }
+      RuleActivator::AllTheTime => {
+        true
+      }
+    }
+  }
+
+  /// Returns the first moment, at or after `now`, at which this activator is
+  /// not effective, or `None` if it's effective all the time.
+  pub fn effective_until(&self, now: DateTime) -> Option<DateTime> {
+    if !self.is_effective(now) {
+      return Some(now);
+    }
+
+    match self {
+      RuleActivator::OnWeekday(_) => {
+        now.next_midnight()
+      }
+      RuleActivator::InTimeRange(time_range) => {
+        time_range.occurrence_end(now)
+      }
+      RuleActivator::InWeekdayRange(weekday_range) => {
+        weekday_range.occurrence_end(now)
+      }
+      RuleActivator::AllTheTime => {
+        None
+      }
+    }
+  }
+}
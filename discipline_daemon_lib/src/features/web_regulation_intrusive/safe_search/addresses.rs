@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, ToSocketAddrs};
+use std::sync::RwLock;
+use std::time::Duration as StandardDuration;
+use crate::GenericError;
+use super::endpoints::Service;
+
+/// How often the safe hosts are resolved again, as their addresses change
+/// now and then.
+pub const REFRESH_INTERVAL: StandardDuration = StandardDuration::from_secs(60 * 60);
+/// How soon resolving is tried again after it failed.
+pub const RETRY_INTERVAL: StandardDuration = StandardDuration::from_secs(60);
+
+/// The resolved addresses of the safe hosts, which connections to pinned
+/// hosts are made to instead of the addresses the hosts resolve to.
+#[derive(Debug, Default)]
+pub struct SafeAddresses {
+  addresses: RwLock<HashMap<Service, IpAddr>>,
+}
+
+impl SafeAddresses {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Resolves the safe host of every service again. Services whose host
+  /// fails to resolve keep their previous address.
+  pub fn refresh(&self) -> Result<(), GenericError> {
+    let mut error = None;
+    for service in Service::ALL {
+      if let Err(resolve_error) = self.resolve(service) {
+        error = Some(
+          GenericError::new("refreshing the SafeSearch addresses")
+            .add_error("failed to resolve a safe host")
+            .add_attachment("host", service.safe_host())
+            .add_attachment("io error", resolve_error.to_string())
+        );
+      }
+    }
+
+    match error {
+      Some(error) => Err(error),
+      None => Ok(()),
+    }
+  }
+
+  /// The address connections pinned to `service` are made to, resolving
+  /// it now if it wasn't resolved yet.
+  pub fn address_of(&self, service: Service) -> io::Result<IpAddr> {
+    if let Some(address) = self.addresses.read().unwrap().get(&service) {
+      return Ok(*address);
+    }
+
+    self.resolve(service)
+  }
+
+  pub fn set(&self, service: Service, address: IpAddr) {
+    self.addresses.write().unwrap().insert(service, address);
+  }
+
+  /// Ipv4 addresses are preferred as they work on every network.
+  fn resolve(&self, service: Service) -> io::Result<IpAddr> {
+    let mut addresses: Vec<IpAddr> = (service.safe_host(), 443)
+      .to_socket_addrs()?
+      .map(|address| address.ip())
+      .collect();
+
+    addresses.sort_by_key(IpAddr::is_ipv6);
+
+    let Some(address) = addresses.first().copied() else {
+      return Err(io::Error::new(ErrorKind::NotFound, "the safe host has no addresses"));
+    };
+
+    self.set(service, address);
+    Ok(address)
+  }
+}
@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+
+/// A site whose filtered results can be forced by connecting to its safe
+/// address instead of its usual one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Service {
+  /// Google search with SafeSearch locked on.
+  Google,
+  /// Bing search with SafeSearch set to strict.
+  Bing,
+  /// YouTube in strict restricted mode.
+  Youtube,
+}
+
+impl Service {
+  pub const ALL: [Service; 3] = [
+    Service::Google,
+    Service::Bing,
+    Service::Youtube,
+  ];
+
+  /// The host whose addresses serve the filtered version of the service.
+  pub fn safe_host(&self) -> &'static str {
+    match self {
+      Service::Google => "forcesafesearch.google.com",
+      Service::Bing => "strict.bing.com",
+      Service::Youtube => "restrict.youtube.com",
+    }
+  }
+}
+
+const BING_HOSTS: [&str; 2] = [
+  "bing.com",
+  "www.bing.com",
+];
+
+const YOUTUBE_HOSTS: [&str; 6] = [
+  "youtube.com",
+  "www.youtube.com",
+  "m.youtube.com",
+  "youtubei.googleapis.com",
+  "youtube.googleapis.com",
+  "www.youtube-nocookie.com",
+];
+
+/// Hosts that serve search results without honoring the safe addresses,
+/// either because they resolve elsewhere or because they're other front
+/// ends of the same search.
+const ALTERNATIVE_ENDPOINTS: [(&str, Service); 6] = [
+  ("ipv4.google.com", Service::Google),
+  ("ipv6.google.com", Service::Google),
+  ("encrypted.google.com", Service::Google),
+  ("nosslsearch.google.com", Service::Google),
+  ("cn.bing.com", Service::Bing),
+  ("www2.bing.com", Service::Bing),
+];
+
+/// The service whose safe address `host` is pinned to, if any. `host` must
+/// be lowercase.
+pub fn pinned_service(host: &str) -> Option<Service> {
+  let host = host.trim_end_matches('.');
+
+  if is_google_search_host(host) {
+    return Some(Service::Google);
+  }
+
+  if BING_HOSTS.contains(&host) {
+    return Some(Service::Bing);
+  }
+
+  if YOUTUBE_HOSTS.contains(&host) {
+    return Some(Service::Youtube);
+  }
+
+  None
+}
+
+/// The service `host` is an alternative search endpoint of, if any. `host`
+/// must be lowercase.
+pub fn alternative_endpoint_service(host: &str) -> Option<Service> {
+  let host = host.trim_end_matches('.');
+  ALTERNATIVE_ENDPOINTS
+    .iter()
+    .find(|(endpoint, _)| *endpoint == host)
+    .map(|(_, service)| *service)
+}
+
+/// Whether `host` is Google search on any of its country domains, like
+/// `www.google.com`, `google.de` or `www.google.co.uk`.
+pub fn is_google_search_host(host: &str) -> bool {
+  let host = host.strip_prefix("www.").unwrap_or(host);
+  let Some(top_level_domain) = host.strip_prefix("google.") else {
+    return false;
+  };
+
+  let labels: Vec<&str> = top_level_domain.split('.').collect();
+  labels.len() <= 2
+    && labels
+      .iter()
+      .all(|label| (2..=3).contains(&label.len()) && label.bytes().all(|byte| byte.is_ascii_lowercase()))
+}
@@ -0,0 +1,257 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+use crate::database::web_regulation_intrusive_safe_search_setting as setting_db;
+use crate::operating_system_integration::{self as os, UserId};
+use crate::{CountdownTimer, Daemon, Database, DateTime, Duration, GenericError};
+use crate::api::block_pages::BlockReason;
+use super::super::block_page;
+use super::super::encrypted_dns::dns::{self, SERVFAIL};
+use super::super::rule_activator::RuleActivator;
+use super::super::traffic::{Exchange, RequestVerdict, TrafficHandler};
+use super::addresses::SafeAddresses;
+use super::endpoints::{alternative_endpoint_service, pinned_service, Service};
+
+/// The longest a setting can be protected from being turned off, which is
+/// the same as for internet access policies.
+pub const MAXIMUM_PROTECTION_DURATION: Duration = Duration::unchecked_from_days(21);
+
+/// How long resolvers may cache the aliases we answer with, which is short
+/// so the hosts resolve normally again soon after a setting stops being
+/// effective.
+const ALIAS_TIME_TO_LIVE: u32 = 60;
+
+/// What the firewall rules redirecting DNS to the forwarder for this feature
+/// are tagged with.
+const FIREWALL_RULE_PURPOSE: &str = "discipline-safe-search";
+
+/// Forces the filtered versions of some search and video services on a user
+/// while its activator is effective.
+///
+/// The setting can't be turned off while its protector is running, even
+/// outside the times its activator is effective.
+#[derive(Debug, Clone)]
+pub struct Setting {
+  user_id: UserId,
+  services: Vec<Service>,
+  activator: RuleActivator,
+  protector: CountdownTimer,
+}
+
+impl Setting {
+  pub fn from_fields(
+    user_id: UserId,
+    services: Vec<Service>,
+    activator: RuleActivator,
+    protector: CountdownTimer,
+  ) -> Self {
+    Self {
+      user_id,
+      services,
+      activator,
+      protector,
+    }
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn services(&self) -> &Vec<Service> {
+    &self.services
+  }
+
+  pub fn activator(&self) -> &RuleActivator {
+    &self.activator
+  }
+
+  pub fn protector(&self) -> &CountdownTimer {
+    &self.protector
+  }
+
+  pub fn synchronize(&mut self, now: DateTime) {
+    self.protector.synchronize(now);
+  }
+
+  pub fn is_protected(&mut self, now: DateTime) -> bool {
+    self.synchronize(now);
+    self.protector.is_running()
+  }
+
+  /// Protects the setting for `duration` from `now`, replacing the
+  /// previous protection.
+  pub fn protect_for(&mut self, duration: Duration, now: DateTime) {
+    self.protector = CountdownTimer::new(duration, now);
+  }
+
+  /// Whether `service` is forced on `user_id` at `now`.
+  pub fn enforces(&self, user_id: Option<UserId>, service: Service, now: DateTime) -> bool {
+    user_id == Some(self.user_id)
+      && self.services.contains(&service)
+      && self.activator.is_effective(now)
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingCreator {
+  pub services: Vec<Service>,
+  pub activator: RuleActivator,
+  pub protect_for: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SettingCreatorError {
+  NoServices,
+  ProtectionTooLong { maximum: Duration },
+}
+
+impl SettingCreator {
+  pub fn create(self, user_id: UserId, now: DateTime) -> Result<Setting, SettingCreatorError> {
+    let mut services = Vec::new();
+    for service in self.services {
+      if !services.contains(&service) {
+        services.push(service);
+      }
+    }
+
+    if services.is_empty() {
+      return Err(SettingCreatorError::NoServices);
+    }
+
+    if self.protect_for > MAXIMUM_PROTECTION_DURATION {
+      return Err(SettingCreatorError::ProtectionTooLong { maximum: MAXIMUM_PROTECTION_DURATION });
+    }
+
+    Ok(Setting::from_fields(
+      user_id,
+      services,
+      self.activator,
+      CountdownTimer::new(self.protect_for, now),
+    ))
+  }
+}
+
+// SECTION: Traffic handler.
+/// Enforces SafeSearch and restricted mode.
+///
+/// The DNS queries of users it's enforced on for the hosts of a service are
+/// answered by the DNS forwarder with an alias to the safe host of the
+/// service, which serves the same site with the filter locked on, so even
+/// connections that skip the proxy go there. Connections that do go through
+/// the proxy are made to the safe address of the service too, in case the
+/// host was resolved some other way. The safe addresses are resolved
+/// periodically by the proxy. Requests to other endpoints of the services,
+/// which don't have safe addresses, are blocked.
+pub struct SafeSearch {
+  settings: Mutex<Vec<Setting>>,
+  addresses: SafeAddresses,
+  forwarder_port: u16,
+}
+
+impl SafeSearch {
+  pub fn open(database: &Database, forwarder_port: u16) -> Result<Self, GenericError> {
+    let settings = setting_db::retrieve_all_settings(database)
+      .map_err(|error| error.change_context("opening SafeSearch enforcement"))?;
+
+    Ok(Self::new(settings, forwarder_port))
+  }
+
+  pub fn new(settings: Vec<Setting>, forwarder_port: u16) -> Self {
+    Self {
+      settings: Mutex::new(settings),
+      addresses: SafeAddresses::new(),
+      forwarder_port,
+    }
+  }
+
+  pub fn settings(&self) -> MutexGuard<'_, Vec<Setting>> {
+    self.settings.lock().unwrap()
+  }
+
+  pub fn addresses(&self) -> &SafeAddresses {
+    &self.addresses
+  }
+
+  fn is_enforced(&self, user_id: Option<UserId>, service: Service) -> bool {
+    let now = DateTime::now();
+    self
+      .settings()
+      .iter()
+      .any(|setting| setting.enforces(user_id, service, now))
+  }
+
+  /// Where a connection of `user_id` to `host` is to be made instead of
+  /// the address `host` resolves to, if anywhere. `host` must be lowercase.
+  pub fn pinned_address(&self, user_id: Option<UserId>, host: &str, port: u16) -> io::Result<Option<SocketAddr>> {
+    let Some(service) = pinned_service(host) else {
+      return Ok(None);
+    };
+
+    if !self.is_enforced(user_id, service) {
+      return Ok(None);
+    }
+
+    let address = self.addresses.address_of(service)?;
+    Ok(Some(SocketAddr::new(address, port)))
+  }
+
+  /// Our own answer to a DNS query of `user_id`, if the host it asks about
+  /// is pinned for them, aliasing the host to the safe host of its service.
+  pub fn answer_query(&self, user_id: Option<UserId>, query: &[u8]) -> Option<Vec<u8>> {
+    let question = dns::question(query)?;
+    let service = pinned_service(&question.name)?;
+    if !self.is_enforced(user_id, service) {
+      return None;
+    }
+
+    match self.addresses.address_of(service) {
+      Ok(address) => {
+        dns::answer_with_alias(query, service.safe_host(), address, ALIAS_TIME_TO_LIVE)
+      }
+      Err(_) => {
+        dns::answer_locally(query, SERVFAIL)
+      }
+    }
+  }
+
+  /// Redirects the DNS of `user_id` to the forwarder while they have a
+  /// setting, and stops once they don't.
+  pub fn apply_firewall_rules(&self, user_id: UserId, has_setting: bool) -> Result<(), GenericError> {
+    if has_setting {
+      os::redirect_dns_of_user_to_forwarder(&user_id, self.forwarder_port, FIREWALL_RULE_PURPOSE)
+    } else {
+      os::stop_redirecting_dns_of_user_to_forwarder(&user_id, self.forwarder_port, FIREWALL_RULE_PURPOSE)
+    }
+  }
+
+  /// Restores the firewall rules of every setting, which don't survive
+  /// restarts of the machine.
+  pub fn apply_all_firewall_rules(&self) -> Result<(), GenericError> {
+    let user_ids: Vec<UserId> = self.settings().iter().map(Setting::user_id).collect();
+    for user_id in user_ids {
+      self
+        .apply_firewall_rules(user_id, true)
+        .map_err(|error| error.change_context("applying the firewall rules of all SafeSearch settings"))?;
+    }
+
+    Ok(())
+  }
+}
+
+impl TrafficHandler for SafeSearch {
+  fn on_request(&self, daemon: &Daemon, exchange: &mut Exchange) -> RequestVerdict {
+    match alternative_endpoint_service(&exchange.host) {
+      Some(service) if self.is_enforced(exchange.user_id, service) => {
+        RequestVerdict::Respond(block_page::respond_with_reason(
+          daemon.configuration().block_page_port(),
+          exchange,
+          BlockReason::SafeSearch,
+        ))
+      }
+      _ => {
+        RequestVerdict::Forward
+      }
+    }
+  }
+}
@@ -0,0 +1,17 @@
+pub mod endpoints;
+pub use endpoints::{alternative_endpoint_service, is_google_search_host, pinned_service, Service};
+
+pub mod addresses;
+pub use addresses::SafeAddresses;
+
+pub mod feature;
+pub use feature::{
+  SafeSearch,
+  Setting,
+  SettingCreator,
+  SettingCreatorError,
+  MAXIMUM_PROTECTION_DURATION,
+};
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,129 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use crate::operating_system_integration::UserId;
+use crate::{DateTime, Duration};
+use super::super::encrypted_dns::dns::SERVFAIL;
+use super::super::rule_activator::RuleActivator;
+use super::*;
+
+fn creator(services: Vec<Service>, protect_for: Duration) -> SettingCreator {
+  SettingCreator {
+    services,
+    activator: RuleActivator::AllTheTime,
+    protect_for,
+  }
+}
+
+#[test]
+fn pins_search_and_video_hosts() {
+  for host in ["www.google.com", "google.com", "www.google.de", "google.co.uk", "www.google.com.au."] {
+    assert_eq!(pinned_service(host), Some(Service::Google), "{host}");
+  }
+
+  for host in ["mail.google.com", "google.example.com", "www.google.co.uk.example", "googleapis.com"] {
+    assert_eq!(pinned_service(host), None, "{host}");
+  }
+
+  assert_eq!(pinned_service("www.bing.com"), Some(Service::Bing));
+  assert_eq!(pinned_service("m.youtube.com"), Some(Service::Youtube));
+  assert_eq!(pinned_service("youtubei.googleapis.com"), Some(Service::Youtube));
+  assert_eq!(pinned_service("www.youtube-nocookie.com"), Some(Service::Youtube));
+  assert_eq!(pinned_service("restrict.youtube.com"), None);
+}
+
+#[test]
+fn finds_alternative_endpoints() {
+  assert_eq!(alternative_endpoint_service("ipv6.google.com"), Some(Service::Google));
+  assert_eq!(alternative_endpoint_service("cn.bing.com."), Some(Service::Bing));
+  assert_eq!(alternative_endpoint_service("www.google.com"), None);
+}
+
+#[test]
+fn creates_settings() {
+  let user_id = UserId::new(1000);
+  let now = DateTime::now();
+
+  let mut setting = creator(vec![Service::Bing, Service::Bing, Service::Google], Duration::unchecked_from_hours(1))
+    .create(user_id, now)
+    .unwrap();
+  assert_eq!(setting.services(), &[Service::Bing, Service::Google]);
+  assert!(setting.is_protected(now));
+
+  assert!(matches!(
+    creator(vec![], Duration::ZERO).create(user_id, now),
+    Err(SettingCreatorError::NoServices),
+  ));
+  assert!(matches!(
+    creator(vec![Service::Google], Duration::unchecked_from_days(22)).create(user_id, now),
+    Err(SettingCreatorError::ProtectionTooLong { .. }),
+  ));
+}
+
+#[test]
+fn stops_protecting_once_the_protector_finishes() {
+  let now = DateTime::now();
+  let mut setting = creator(vec![Service::Google], Duration::unchecked_from_minutes(10))
+    .create(UserId::new(1000), now)
+    .unwrap();
+
+  assert!(setting.is_protected(now.checked_add(&Duration::unchecked_from_minutes(9)).unwrap()));
+  assert!(!setting.is_protected(now.checked_add(&Duration::unchecked_from_minutes(10)).unwrap()));
+
+  setting.protect_for(Duration::unchecked_from_hours(2), now);
+  assert!(setting.is_protected(now.checked_add(&Duration::unchecked_from_hours(1)).unwrap()));
+}
+
+#[test]
+fn pins_connections_of_enforced_users_only() {
+  let user_id = UserId::new(1000);
+  let setting = creator(vec![Service::Google], Duration::ZERO)
+    .create(user_id, DateTime::now())
+    .unwrap();
+
+  let safe_search = SafeSearch::new(vec![setting], 5353);
+  let safe_address = IpAddr::V4(Ipv4Addr::new(216, 239, 38, 120));
+  safe_search.addresses().set(Service::Google, safe_address);
+
+  assert_eq!(
+    safe_search.pinned_address(Some(user_id), "www.google.com", 443).unwrap(),
+    Some(SocketAddr::new(safe_address, 443)),
+  );
+  assert_eq!(safe_search.pinned_address(Some(user_id), "www.bing.com", 443).unwrap(), None);
+  assert_eq!(safe_search.pinned_address(Some(UserId::new(1001)), "www.google.com", 443).unwrap(), None);
+  assert_eq!(safe_search.pinned_address(None, "www.google.com", 443).unwrap(), None);
+}
+
+#[test]
+fn answers_dns_queries_of_enforced_users_only() {
+  let user_id = UserId::new(1000);
+  let setting = creator(vec![Service::Google], Duration::ZERO)
+    .create(user_id, DateTime::now())
+    .unwrap();
+
+  let safe_search = SafeSearch::new(vec![setting], 5353);
+
+  // Id 0x1234, recursion desired, one question for the A records.
+  let query = |host: &str| {
+    let mut query = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in host.split('.') {
+      query.push(label.len() as u8);
+      query.extend_from_slice(label.as_bytes());
+    }
+    query.extend_from_slice(&[0, 0, 1, 0, 1]);
+    query
+  };
+
+  // Until the safe address is known, the queries fail rather than resolve
+  // to the usual hosts.
+  let answer = safe_search.answer_query(Some(user_id), &query("www.google.com")).unwrap();
+  assert_eq!(answer[3], 0x80 | SERVFAIL);
+
+  safe_search.addresses().set(Service::Google, IpAddr::V4(Ipv4Addr::new(216, 239, 38, 120)));
+  let answer = safe_search.answer_query(Some(user_id), &query("www.google.com")).unwrap();
+  assert_eq!(answer[6..8], [0, 2]);
+  assert!(answer.windows(15).any(|window| window == b"forcesafesearch"));
+  assert!(answer.ends_with(&[0, 4, 216, 239, 38, 120]));
+
+  assert_eq!(safe_search.answer_query(Some(user_id), &query("www.bing.com")), None);
+  assert_eq!(safe_search.answer_query(Some(UserId::new(1001)), &query("www.google.com")), None);
+  assert_eq!(safe_search.answer_query(None, &query("www.google.com")), None);
+}
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use crate::GenericError;
+use super::super::safe_search::is_google_search_host;
+use super::super::traffic::Exchange;
+use super::super::url_pattern::UrlPattern;
+
+/// A search engine whose search requests are recognized out of the box.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Engine {
+  Google,
+  Bing,
+  DuckDuckGo,
+  /// YouTube's own search, not Google's video search.
+  Youtube,
+}
+
+impl Engine {
+  pub const ALL: [Engine; 4] = [
+    Engine::Google,
+    Engine::Bing,
+    Engine::DuckDuckGo,
+    Engine::Youtube,
+  ];
+
+  /// The query parameter of the search requests to `host` and `path`, if
+  /// they're search requests of this engine. Requests for suggestions are
+  /// search requests too, as they show results of their own.
+  fn query_parameter(&self, host: &str, path: &str) -> Option<&'static str> {
+    match self {
+      Engine::Google => {
+        let is_search = is_google_search_host(host)
+          && matches!(path, "/search" | "/complete/search");
+
+        let is_suggestions = matches!(host, "suggestqueries.google.com" | "clients1.google.com")
+          && path == "/complete/search";
+
+        (is_search || is_suggestions).then_some("q")
+      }
+      Engine::Bing => {
+        if !matches!(host, "bing.com" | "www.bing.com") {
+          return None;
+        }
+
+        let path = path.to_ascii_lowercase();
+        match path.as_str() {
+          "/search" | "/images/search" | "/videos/search" | "/news/search" => {
+            Some("q")
+          }
+          "/as/suggestions" | "/osjson.aspx" => {
+            Some("qry")
+          }
+          _ => {
+            None
+          }
+        }
+      }
+      Engine::DuckDuckGo => {
+        let is_search = matches!(host, "duckduckgo.com" | "www.duckduckgo.com" | "safe.duckduckgo.com")
+          && matches!(path, "/" | "/ac/");
+
+        let is_html_search = matches!(host, "html.duckduckgo.com" | "lite.duckduckgo.com")
+          && matches!(path, "/" | "/html" | "/html/" | "/lite" | "/lite/");
+
+        (is_search || is_html_search).then_some("q")
+      }
+      Engine::Youtube => {
+        let is_search = matches!(host, "youtube.com" | "www.youtube.com" | "m.youtube.com")
+          && path == "/results";
+
+        let is_suggestions = matches!(host, "suggestqueries-clients6.youtube.com" | "suggestqueries.google.com")
+          && path == "/complete/search";
+
+        if is_search {
+          Some("search_query")
+        } else if is_suggestions {
+          Some("q")
+        } else {
+          None
+        }
+      }
+    }
+  }
+
+  /// The query of `exchange` if it's a search request of this engine.
+  pub fn query_of(&self, exchange: &Exchange) -> Option<String> {
+    let parameter = self.query_parameter(&exchange.host, exchange.request.path())?;
+    exchange.request.query_parameter(parameter)
+  }
+}
+
+/// A search engine that isn't built in, given by the url pattern of its
+/// search requests and which query parameter has the query.
+#[derive(Debug, Clone)]
+pub struct CustomEngine {
+  url_pattern: UrlPattern,
+  query_parameter: String,
+}
+
+impl CustomEngine {
+  pub const MAXIMUM_QUERY_PARAMETER_LENGTH: usize = 100;
+
+  pub fn new(url_pattern: UrlPattern, query_parameter: String) -> Result<Self, GenericError> {
+    if query_parameter.is_empty()
+      || query_parameter.len() > Self::MAXIMUM_QUERY_PARAMETER_LENGTH
+      || query_parameter.contains(['&', '=', '#'])
+      || query_parameter.chars().any(|char| char.is_control() || char.is_whitespace())
+    {
+      return Err(
+        GenericError::new("creating a CustomEngine")
+          .add_error("the query parameter is empty, too long or has chars query parameter names can't have")
+          .add_attachment("query parameter", query_parameter)
+      );
+    }
+
+    Ok(Self {
+      url_pattern,
+      query_parameter,
+    })
+  }
+
+  pub fn url_pattern(&self) -> &UrlPattern {
+    &self.url_pattern
+  }
+
+  pub fn query_parameter(&self) -> &str {
+    &self.query_parameter
+  }
+
+  /// The query of `exchange` if it's a search request of this engine.
+  pub fn query_of(&self, exchange: &Exchange) -> Option<String> {
+    if !self.url_pattern.matches(exchange) {
+      return None;
+    }
+
+    exchange.request.query_parameter(&self.query_parameter)
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomEngineCreator {
+  pub url_pattern: String,
+  pub query_parameter: String,
+}
@@ -0,0 +1,295 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+use crate::database::web_regulation_intrusive_search_query_rule as rule_db;
+use crate::operating_system_integration::UserId;
+use crate::{Daemon, Database, DateTime, GenericError, Uuid};
+use crate::api::block_pages::WebRuleFeature;
+use super::super::block_page;
+use super::super::content_filter::{clean_keywords, Language, NormalizedText, WordMatcher};
+use super::super::rule_activator::RuleActivator;
+use super::super::traffic::{Exchange, RequestVerdict, Response, TrafficHandler};
+use super::super::url_pattern::UrlPattern;
+use super::engines::{CustomEngine, CustomEngineCreator, Engine};
+
+pub const MAXIMUM_RULES_PER_USER: usize = 20;
+pub const MAXIMUM_KEYWORDS_PER_RULE: usize = 1000;
+pub const MAXIMUM_REDIRECT_URL_LENGTH: usize = 2048;
+
+/// What a rule does to a search whose query has one of its words.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+  /// Show a block page instead of the results.
+  Block,
+  /// Send the browser to `url` instead, like a page with something better
+  /// to do.
+  Redirect { url: String },
+}
+
+/// Stops searches on some engines whose query has words of some languages'
+/// lists or the rule's own keywords, while its activator is effective.
+#[derive(Debug, Clone)]
+pub struct Rule {
+  id: Uuid,
+  user_id: UserId,
+  engines: Vec<Engine>,
+  custom_engine: Option<CustomEngine>,
+  languages: Vec<Language>,
+  keywords: Vec<String>,
+  action: Action,
+  activator: RuleActivator,
+  matcher: Arc<WordMatcher>,
+}
+
+impl Rule {
+  #[allow(clippy::too_many_arguments)]
+  pub fn from_fields(
+    id: Uuid,
+    user_id: UserId,
+    engines: Vec<Engine>,
+    custom_engine: Option<CustomEngine>,
+    languages: Vec<Language>,
+    keywords: Vec<String>,
+    action: Action,
+    activator: RuleActivator,
+  ) -> Self {
+    let words: Vec<&str> = languages
+      .iter()
+      .flat_map(|language| language.words())
+      .chain(keywords.iter().map(String::as_str))
+      .collect();
+
+    Self {
+      matcher: Arc::new(WordMatcher::new(words)),
+      id,
+      user_id,
+      engines,
+      custom_engine,
+      languages,
+      keywords,
+      action,
+      activator,
+    }
+  }
+
+  pub fn id(&self) -> &Uuid {
+    &self.id
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn engines(&self) -> &Vec<Engine> {
+    &self.engines
+  }
+
+  pub fn custom_engine(&self) -> Option<&CustomEngine> {
+    self.custom_engine.as_ref()
+  }
+
+  pub fn languages(&self) -> &Vec<Language> {
+    &self.languages
+  }
+
+  pub fn keywords(&self) -> &Vec<String> {
+    &self.keywords
+  }
+
+  pub fn action(&self) -> &Action {
+    &self.action
+  }
+
+  pub fn activator(&self) -> &RuleActivator {
+    &self.activator
+  }
+
+  /// The query of `exchange` if it's a search request of one of the rule's
+  /// engines.
+  pub fn query_of(&self, exchange: &Exchange) -> Option<String> {
+    self
+      .engines
+      .iter()
+      .find_map(|engine| engine.query_of(exchange))
+      .or_else(|| self.custom_engine.as_ref()?.query_of(exchange))
+  }
+
+  /// Whether the rule stops `exchange` at `now`.
+  pub fn stops(&self, exchange: &Exchange, now: DateTime) -> bool {
+    if exchange.user_id != Some(self.user_id) || !self.activator.is_effective(now) {
+      return false;
+    }
+
+    let Some(query) = self.query_of(exchange) else {
+      return false;
+    };
+
+    !self.matcher.find(&NormalizedText::normalize(&query)).is_empty()
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleCreator {
+  pub id: Option<Uuid>,
+  pub engines: Vec<Engine>,
+  pub custom_engine: Option<CustomEngineCreator>,
+  pub languages: Vec<Language>,
+  pub keywords: Vec<String>,
+  pub action: Action,
+  pub activator: RuleActivator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RuleCreatorError {
+  /// There are neither built-in engines nor a custom one.
+  NoEngines,
+  InvalidCustomEngineUrlPattern,
+  InvalidCustomEngineQueryParameter,
+  /// There are neither languages nor keywords to look for.
+  NoWords,
+  TooManyKeywords { maximum: usize },
+  /// The keyword is empty, too long or has control chars, like line breaks.
+  InvalidKeyword { keyword: String },
+  /// The url isn't an http or https url or is longer than
+  /// `MAXIMUM_REDIRECT_URL_LENGTH`.
+  InvalidRedirectUrl,
+}
+
+impl RuleCreator {
+  pub fn create(self, user_id: UserId) -> Result<Rule, RuleCreatorError> {
+    let mut engines = Vec::new();
+    for engine in self.engines {
+      if !engines.contains(&engine) {
+        engines.push(engine);
+      }
+    }
+
+    let custom_engine = match self.custom_engine {
+      Some(creator) => {
+        let url_pattern = UrlPattern::parse(&creator.url_pattern)
+          .map_err(|_| RuleCreatorError::InvalidCustomEngineUrlPattern)?;
+
+        let custom_engine = CustomEngine::new(url_pattern, creator.query_parameter)
+          .map_err(|_| RuleCreatorError::InvalidCustomEngineQueryParameter)?;
+
+        Some(custom_engine)
+      }
+      None => {
+        None
+      }
+    };
+
+    if engines.is_empty() && custom_engine.is_none() {
+      return Err(RuleCreatorError::NoEngines);
+    }
+
+    let mut languages = Vec::new();
+    for language in self.languages {
+      if !languages.contains(&language) {
+        languages.push(language);
+      }
+    }
+
+    if self.keywords.len() > MAXIMUM_KEYWORDS_PER_RULE {
+      return Err(RuleCreatorError::TooManyKeywords { maximum: MAXIMUM_KEYWORDS_PER_RULE });
+    }
+
+    let keywords = clean_keywords(self.keywords)
+      .map_err(|keyword| RuleCreatorError::InvalidKeyword { keyword })?;
+
+    if languages.is_empty() && keywords.is_empty() {
+      return Err(RuleCreatorError::NoWords);
+    }
+
+    if let Action::Redirect { url } = &self.action {
+      if !is_valid_redirect_url(url) {
+        return Err(RuleCreatorError::InvalidRedirectUrl);
+      }
+    }
+
+    Ok(Rule::from_fields(
+      self.id.unwrap_or_else(Uuid::new_v4),
+      user_id,
+      engines,
+      custom_engine,
+      languages,
+      keywords,
+      self.action,
+      self.activator,
+    ))
+  }
+}
+
+fn is_valid_redirect_url(url: &str) -> bool {
+  let Some(rest) = url
+    .strip_prefix("https://")
+    .or_else(|| url.strip_prefix("http://"))
+  else {
+    return false;
+  };
+
+  !rest.is_empty()
+    && !rest.starts_with('/')
+    && url.len() <= MAXIMUM_REDIRECT_URL_LENGTH
+    && url.chars().all(|char| char.is_ascii_graphic())
+}
+
+// SECTION: Traffic handler.
+/// Stops searches with queries that have words of effective search query
+/// rules. Pages are sent to the block page or where the rule redirects to,
+/// while suggestions and other background requests get an empty response.
+///
+/// When several rules stop a search, the first one created decides.
+pub struct SearchQueryBlocker {
+  rules: Mutex<Vec<Rule>>,
+}
+
+impl SearchQueryBlocker {
+  pub fn open(database: &Database) -> Result<Self, GenericError> {
+    let rules = rule_db::retrieve_all_rules(database)
+      .map_err(|error| error.change_context("opening the search query blocker"))?;
+
+    Ok(Self {
+      rules: Mutex::new(rules),
+    })
+  }
+
+  pub fn rules(&self) -> MutexGuard<'_, Vec<Rule>> {
+    self.rules.lock().unwrap()
+  }
+}
+
+impl TrafficHandler for SearchQueryBlocker {
+  fn on_request(&self, daemon: &Daemon, exchange: &mut Exchange) -> RequestVerdict {
+    let now = DateTime::now();
+    let stopping = self
+      .rules()
+      .iter()
+      .find(|rule| rule.stops(exchange, now))
+      .map(|rule| (rule.id, rule.action().clone()));
+
+    match stopping {
+      Some((rule_id, action)) => {
+        RequestVerdict::Respond(respond(daemon.configuration().block_page_port(), exchange, rule_id, &action))
+      }
+      None => {
+        RequestVerdict::Forward
+      }
+    }
+  }
+}
+
+/// What the search `exchange` the rule `rule_id` stops with `action` gets.
+pub fn respond(block_page_port: u16, exchange: &Exchange, rule_id: Uuid, action: &Action) -> Response {
+  if !exchange.is_top_level_navigation() {
+    return Response::no_content();
+  }
+
+  match action {
+    Action::Block => {
+      block_page::respond(block_page_port, exchange, WebRuleFeature::SearchQueryRule, rule_id)
+    }
+    Action::Redirect { url } => {
+      Response::redirect(url)
+    }
+  }
+}
@@ -0,0 +1,18 @@
+pub mod engines;
+pub use engines::{CustomEngine, CustomEngineCreator, Engine};
+
+pub mod feature;
+pub use feature::{
+  respond,
+  Action,
+  Rule,
+  RuleCreator,
+  RuleCreatorError,
+  SearchQueryBlocker,
+  MAXIMUM_KEYWORDS_PER_RULE,
+  MAXIMUM_REDIRECT_URL_LENGTH,
+  MAXIMUM_RULES_PER_USER,
+};
+
+#[cfg(test)]
+mod tests;
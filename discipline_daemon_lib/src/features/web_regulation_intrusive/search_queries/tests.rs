@@ -0,0 +1,123 @@
+use crate::operating_system_integration::UserId;
+use crate::{DateTime, Uuid};
+use super::super::content_filter::Language;
+use super::super::rule_activator::RuleActivator;
+use super::super::traffic::*;
+use super::*;
+
+fn creator(engines: Vec<Engine>, keywords: &[&str], action: Action) -> RuleCreator {
+  RuleCreator {
+    id: None,
+    engines,
+    custom_engine: None,
+    languages: vec![],
+    keywords: keywords.iter().map(|keyword| keyword.to_string()).collect(),
+    action,
+    activator: RuleActivator::AllTheTime,
+  }
+}
+
+fn query(engine: Engine, url: &str) -> Option<String> {
+  engine.query_of(&Exchange::navigation_to(url))
+}
+
+#[test]
+fn decodes_query_parameters() {
+  let request = Exchange::navigation_to("https://example.com/?a=1&q=caf%C3%A9+au%20lait&q=second&bad=%zz%").request;
+  assert_eq!(request.query_parameter("q").as_deref(), Some("café au lait"));
+  assert_eq!(request.query_parameter("bad").as_deref(), Some("%zz%"));
+  assert_eq!(request.query_parameter("missing"), None);
+}
+
+#[test]
+fn extracts_queries_of_built_in_engines() {
+  assert_eq!(query(Engine::Google, "https://www.google.com/search?q=red+shoes").as_deref(), Some("red shoes"));
+  assert_eq!(query(Engine::Google, "https://www.google.co.uk/complete/search?client=gws&q=red").as_deref(), Some("red"));
+  assert_eq!(query(Engine::Google, "https://mail.google.com/search?q=red"), None);
+  assert_eq!(query(Engine::Bing, "https://www.bing.com/images/search?q=red").as_deref(), Some("red"));
+  assert_eq!(query(Engine::Bing, "https://www.bing.com/AS/Suggestions?qry=red").as_deref(), Some("red"));
+  assert_eq!(query(Engine::DuckDuckGo, "https://duckduckgo.com/?q=red&ia=web").as_deref(), Some("red"));
+  assert_eq!(query(Engine::DuckDuckGo, "https://html.duckduckgo.com/html/?q=red").as_deref(), Some("red"));
+  assert_eq!(query(Engine::Youtube, "https://www.youtube.com/results?search_query=red").as_deref(), Some("red"));
+  assert_eq!(query(Engine::Youtube, "https://www.youtube.com/watch?v=dQw4w9WgXcQ"), None);
+}
+
+#[test]
+fn extracts_queries_of_custom_engines() {
+  let mut creator = creator(vec![], &["red"], Action::Block);
+  creator.custom_engine = Some(CustomEngineCreator {
+    url_pattern: "*.example.com/find".into(),
+    query_parameter: "text".into(),
+  });
+  let rule = creator.create(UserId::new(1000)).unwrap();
+
+  assert_eq!(rule.query_of(&Exchange::navigation_to("https://search.example.com/find/all?text=red")).as_deref(), Some("red"));
+  assert_eq!(rule.query_of(&Exchange::navigation_to("https://search.example.com/?text=red")), None);
+  assert_eq!(rule.query_of(&Exchange::navigation_to("https://www.google.com/search?q=red")), None);
+}
+
+#[test]
+fn creates_rules() {
+  let user_id = UserId::new(1000);
+
+  assert!(matches!(
+    creator(vec![], &["red"], Action::Block).create(user_id),
+    Err(RuleCreatorError::NoEngines),
+  ));
+  assert!(matches!(
+    creator(vec![Engine::Google], &[], Action::Block).create(user_id),
+    Err(RuleCreatorError::NoWords),
+  ));
+  assert!(matches!(
+    creator(vec![Engine::Google], &["red"], Action::Redirect { url: "javascript:alert(1)".into() }).create(user_id),
+    Err(RuleCreatorError::InvalidRedirectUrl),
+  ));
+
+  let mut creator = creator(vec![Engine::Google], &["red"], Action::Block);
+  creator.custom_engine = Some(CustomEngineCreator {
+    url_pattern: "example.com".into(),
+    query_parameter: "a=b".into(),
+  });
+  assert!(matches!(
+    creator.create(user_id),
+    Err(RuleCreatorError::InvalidCustomEngineQueryParameter),
+  ));
+}
+
+#[test]
+fn stops_searches_with_listed_words() {
+  let rule = creator(vec![Engine::Google, Engine::Youtube], &["red shoes"], Action::Block)
+    .create(UserId::new(1000))
+    .unwrap();
+  let now = DateTime::now();
+
+  assert!(rule.stops(&Exchange::navigation_to("https://www.google.com/search?q=buy+RED+Shoes"), now));
+  assert!(!rule.stops(&Exchange::navigation_to("https://www.google.com/search?q=red+shoestring"), now));
+  assert!(!rule.stops(&Exchange::navigation_to("https://www.bing.com/search?q=red+shoes"), now));
+
+  let other_user = Exchange::navigation_to("https://www.google.com/search?q=red+shoes").with_user_id(Some(UserId::new(1001)));
+  assert!(!rule.stops(&other_user, now));
+
+  let mut creator = creator(vec![Engine::Bing], &[], Action::Block);
+  creator.languages = vec![Language::English];
+  let rule = creator.create(UserId::new(1000)).unwrap();
+  assert!(rule.stops(&Exchange::navigation_to("https://www.bing.com/search?q=bloody+hell"), now));
+}
+
+#[test]
+fn blocks_pages_and_empties_suggestions() {
+  let rule_id = Uuid::new_v4();
+  let page = Exchange::navigation_to("https://www.google.com/search?q=red");
+  let block_page = respond(9090, &page, rule_id, &Action::Block);
+  assert_eq!(block_page.head.status_code, 302);
+  assert!(block_page.head.headers.get("Location").unwrap().starts_with(&format!(
+    "http://127.0.0.1:9090/blocked?reason=web-rule&feature=search-query-rule&rule={rule_id}&url=https%3A%2F%2Fwww.google.com%2Fsearch",
+  )));
+
+  let redirect = respond(9090, &page, rule_id, &Action::Redirect { url: "https://example.com/".into() });
+  assert_eq!(redirect.head.status_code, 302);
+  assert_eq!(redirect.head.headers.get("Location"), Some("https://example.com/"));
+
+  let suggestions = Exchange::to("https://www.google.com/complete/search?q=red").fetching("empty");
+  assert_eq!(respond(9090, &suggestions, rule_id, &Action::Block).head.status_code, 204);
+}
@@ -0,0 +1,334 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use rustls::client::danger::ServerCertVerifier;
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::sign::CertifiedKey;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, ServerConfig, ServerConnection};
+use crate::operating_system_integration::UserId;
+use crate::Uuid;
+use super::certificate_authority::{CertificateAuthority, LeafCertificateResolver};
+use super::proxy::{prepare_exchange, relay, split_authority, Destination};
+use super::*;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A data directory of its own for a test to keep a certificate authority in.
+fn temporary_directory() -> PathBuf {
+  let directory = std::env::temp_dir().join(format!("discipline-certificate-authority-{}", Uuid::new_v4()));
+  fs::create_dir_all(&directory).unwrap();
+  directory
+}
+
+/// Verifies `certificate` for `server_name` the way a browser trusting
+/// `certificate_authority` would, as if the time were `at`.
+fn verify(
+  certificate_authority: &CertificateAuthority,
+  certificate: &CertifiedKey,
+  server_name: &str,
+  at: SystemTime,
+) -> Result<(), rustls::Error> {
+  let mut roots = RootCertStore::empty();
+  roots
+    .add(CertificateDer::from_pem_slice(certificate_authority.certificate_pem().as_bytes()).unwrap())
+    .unwrap();
+
+  let verifier = WebPkiServerVerifier::builder_with_provider(
+    Arc::new(roots),
+    Arc::new(rustls::crypto::ring::default_provider()),
+  )
+  .build()
+  .unwrap();
+
+  verifier
+    .verify_server_cert(
+      &certificate.cert[0],
+      &certificate.cert[1..],
+      &ServerName::try_from(server_name.to_string()).unwrap(),
+      &[],
+      UnixTime::since_unix_epoch(at.duration_since(UNIX_EPOCH).unwrap()),
+    )
+    .map(|_| ())
+}
+
+#[test]
+fn generates_and_reloads_the_certificate_authority() {
+  let directory = temporary_directory();
+
+  let generated = CertificateAuthority::open(&directory).unwrap();
+  let private_key_path = directory.join("WebRegulationIntrusiveCertificateAuthorityPrivateKey.pem");
+  let mode = fs::metadata(&private_key_path).unwrap().permissions().mode();
+  assert_eq!(mode & 0o777, 0o600);
+
+  let reloaded = CertificateAuthority::open(&directory).unwrap();
+  assert_eq!(reloaded.certificate_pem(), generated.certificate_pem());
+
+  // A key left behind without its certificate is replaced.
+  fs::remove_file(generated.certificate_path()).unwrap();
+  let regenerated = CertificateAuthority::open(&directory).unwrap();
+  assert_ne!(regenerated.certificate_pem(), generated.certificate_pem());
+
+  fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn mints_leaf_certificates_browsers_accept() {
+  let directory = temporary_directory();
+  let certificate_authority = CertificateAuthority::open(&directory).unwrap();
+  let now = SystemTime::now();
+
+  let certificate = certificate_authority.leaf_certificate("www.example.com").unwrap();
+  assert!(verify(&certificate_authority, &certificate, "www.example.com", now).is_ok());
+  assert!(verify(&certificate_authority, &certificate, "example.com", now).is_err());
+
+  // Valid from a day ago for at most 398 days in all.
+  assert!(verify(&certificate_authority, &certificate, "www.example.com", now - DAY + Duration::from_secs(60)).is_ok());
+  assert!(verify(&certificate_authority, &certificate, "www.example.com", now - 2 * DAY).is_err());
+  assert!(verify(&certificate_authority, &certificate, "www.example.com", now + 396 * DAY).is_ok());
+  assert!(verify(&certificate_authority, &certificate, "www.example.com", now + 398 * DAY).is_err());
+
+  let ip_certificate = certificate_authority.leaf_certificate("192.0.2.1").unwrap();
+  assert!(verify(&certificate_authority, &ip_certificate, "192.0.2.1", now).is_ok());
+
+  fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn caches_leaf_certificates_per_server_name() {
+  let directory = temporary_directory();
+  let certificate_authority = CertificateAuthority::open(&directory).unwrap();
+
+  let certificate = certificate_authority.leaf_certificate("www.example.com").unwrap();
+  let same = certificate_authority.leaf_certificate("WWW.example.com").unwrap();
+  let other = certificate_authority.leaf_certificate("example.org").unwrap();
+  assert!(Arc::ptr_eq(&certificate, &same));
+  assert!(!Arc::ptr_eq(&certificate, &other));
+
+  fs::remove_dir_all(&directory).unwrap();
+}
+
+/// Whether a client trusting `certificate_authority` and connecting to
+/// `address`, which it sends no server name for, completes a handshake with
+/// a server resolving certificates with `resolver`.
+fn completes_handshake(
+  certificate_authority: &CertificateAuthority,
+  resolver: LeafCertificateResolver,
+  address: &str,
+) -> bool {
+  let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+  let server_configuration = ServerConfig::builder_with_provider(Arc::clone(&provider))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_no_client_auth()
+    .with_cert_resolver(Arc::new(resolver));
+
+  let mut roots = RootCertStore::empty();
+  roots
+    .add(CertificateDer::from_pem_slice(certificate_authority.certificate_pem().as_bytes()).unwrap())
+    .unwrap();
+
+  let client_configuration = ClientConfig::builder_with_provider(provider)
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+
+  let mut server = ServerConnection::new(Arc::new(server_configuration)).unwrap();
+  let mut client = ClientConnection::new(
+    Arc::new(client_configuration),
+    ServerName::try_from(address.to_string()).unwrap(),
+  )
+  .unwrap();
+
+  while client.is_handshaking() {
+    let mut records = Vec::new();
+    client.write_tls(&mut records).unwrap();
+    server.read_tls(&mut records.as_slice()).unwrap();
+    if server.process_new_packets().is_err() {
+      return false;
+    }
+
+    let mut records = Vec::new();
+    server.write_tls(&mut records).unwrap();
+    client.read_tls(&mut records.as_slice()).unwrap();
+    if client.process_new_packets().is_err() {
+      return false;
+    }
+  }
+
+  true
+}
+
+#[test]
+fn answers_clients_without_a_server_name_for_their_address() {
+  let directory = temporary_directory();
+  let certificate_authority = Arc::new(CertificateAuthority::open(&directory).unwrap());
+  let resolver = |fallback_server_name: Option<&str>| LeafCertificateResolver {
+    certificate_authority: Arc::clone(&certificate_authority),
+    fallback_server_name: fallback_server_name.map(String::from),
+  };
+
+  assert!(completes_handshake(&certificate_authority, resolver(Some("192.0.2.1")), "192.0.2.1"));
+  assert!(!completes_handshake(&certificate_authority, resolver(Some("192.0.2.2")), "192.0.2.1"));
+  assert!(!completes_handshake(&certificate_authority, resolver(None), "192.0.2.1"));
+
+  fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn relays_both_ways_at_once() {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let connection = || {
+    let connecting = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (accepted, _) = listener.accept().unwrap();
+    (connecting, accepted)
+  };
+
+  let (mut client, mut from_client) = connection();
+  let (mut to_website, mut website) = connection();
+  let relaying = thread::spawn(move || relay(&mut from_client, b"GET", &mut to_website, b""));
+
+  let mut received = [0; 3];
+  website.read_exact(&mut received).unwrap();
+  assert_eq!(&received, b"GET");
+
+  // The website speaks first, while the client waits.
+  website.write_all(b"hello").unwrap();
+  let mut received = [0; 5];
+  client.read_exact(&mut received).unwrap();
+  assert_eq!(&received, b"hello");
+
+  // Each side closing its half is passed on, and the other half stays open.
+  client.shutdown(Shutdown::Write).unwrap();
+  let mut received = Vec::new();
+  website.read_to_end(&mut received).unwrap();
+  assert!(received.is_empty());
+
+  website.write_all(b"bye").unwrap();
+  website.shutdown(Shutdown::Write).unwrap();
+  let mut received = Vec::new();
+  client.read_to_end(&mut received).unwrap();
+  assert_eq!(received, b"bye");
+
+  relaying.join().unwrap().unwrap();
+}
+
+#[test]
+fn matches_no_intercept_hosts() {
+  let mut no_intercept_hosts = NoInterceptHosts::new(vec![HostPattern::parse("*.example.com").unwrap()]);
+
+  assert!(!no_intercept_hosts.is_intercepted("www.apple.com"));
+  assert!(!no_intercept_hosts.is_intercepted("example.com"));
+  assert!(!no_intercept_hosts.is_intercepted("WWW.Example.com."));
+  assert!(no_intercept_hosts.is_intercepted("example.org"));
+  assert!(no_intercept_hosts.is_intercepted("notexample.com"));
+
+  let example_org = HostPattern::parse("example.org").unwrap();
+  no_intercept_hosts.add(example_org.clone());
+  no_intercept_hosts.add(example_org.clone());
+  assert_eq!(no_intercept_hosts.user_added().len(), 2);
+  assert!(!no_intercept_hosts.is_intercepted("example.org"));
+  assert!(no_intercept_hosts.is_intercepted("www.example.org"));
+
+  no_intercept_hosts.remove(&example_org);
+  assert!(no_intercept_hosts.is_intercepted("example.org"));
+}
+
+#[test]
+fn splits_authorities() {
+  assert_eq!(split_authority("Example.com"), Some(("example.com".into(), None)));
+  assert_eq!(split_authority(" example.com:8443 "), Some(("example.com".into(), Some(8443))));
+  assert_eq!(split_authority("[::1]"), Some(("[::1]".into(), None)));
+  assert_eq!(split_authority("[::1]:443"), Some(("[::1]".into(), Some(443))));
+  assert_eq!(split_authority("example.com:port"), None);
+  assert_eq!(split_authority(""), None);
+}
+
+fn request(target: &str, host: Option<&str>) -> RequestHead {
+  let mut headers = Headers::new();
+  if let Some(host) = host {
+    headers.append("Host", host);
+  }
+  headers.append("Proxy-Authorization", "Basic dXNlcjpwYXNz");
+
+  RequestHead {
+    method: "GET".into(),
+    target: target.into(),
+    version: 1,
+    headers,
+  }
+}
+
+fn destination(host: Option<&str>, port: u16) -> Destination {
+  Destination {
+    user_id: Some(UserId::new(1000)),
+    host: host.map(String::from),
+    port,
+    address: None,
+  }
+}
+
+fn status_code(result: Result<Exchange, Response>) -> u16 {
+  result.unwrap_err().head.status_code
+}
+
+#[test]
+fn prepares_exchanges() {
+  let intercepted = destination(Some("www.example.com"), 443);
+
+  let exchange = prepare_exchange(Scheme::Https, &intercepted, request("/watch?v=1", Some("WWW.example.com:443"))).unwrap();
+  assert_eq!(exchange.host, "www.example.com");
+  assert_eq!(exchange.port, 443);
+  assert_eq!(exchange.request.target, "/watch?v=1");
+  assert_eq!(exchange.user_id, Some(UserId::new(1000)));
+  assert!(!exchange.request.headers.contains("Proxy-Authorization"));
+
+  // Http/1.0 clients may leave the Host header out.
+  let exchange = prepare_exchange(Scheme::Https, &intercepted, request("/", None)).unwrap();
+  assert_eq!(exchange.host, "www.example.com");
+
+  // Rules are evaluated for the tls server name, so requests naming another
+  // host are turned away.
+  assert_eq!(status_code(prepare_exchange(Scheme::Https, &intercepted, request("/", Some("example.org")))), 421);
+  assert_eq!(
+    status_code(prepare_exchange(Scheme::Https, &intercepted, request("https://example.org/", Some("www.example.com")))),
+    421,
+  );
+
+  let exchange = prepare_exchange(
+    Scheme::Https,
+    &intercepted,
+    request("https://www.example.com/search?q=1", Some("www.example.com")),
+  )
+  .unwrap();
+  assert_eq!(exchange.host, "www.example.com");
+  assert_eq!(exchange.request.target, "/search?q=1");
+}
+
+#[test]
+fn prepares_explicitly_proxied_exchanges() {
+  let proxied = destination(None, 80);
+
+  let exchange = prepare_exchange(Scheme::Http, &proxied, request("http://Example.com:8080/a?b=1", Some("example.com:8080"))).unwrap();
+  assert_eq!(exchange.host, "example.com");
+  assert_eq!(exchange.port, 8080);
+  assert_eq!(exchange.request.target, "/a?b=1");
+
+  let exchange = prepare_exchange(Scheme::Http, &proxied, request("http://example.com", None)).unwrap();
+  assert_eq!(exchange.port, 80);
+  assert_eq!(exchange.request.target, "/");
+
+  let exchange = prepare_exchange(Scheme::Http, &proxied, request("/", Some("example.org"))).unwrap();
+  assert_eq!(exchange.host, "example.org");
+
+  assert_eq!(status_code(prepare_exchange(Scheme::Http, &proxied, request("not a url", None))), 400);
+  assert_eq!(status_code(prepare_exchange(Scheme::Http, &proxied, request("/", None))), 400);
+}
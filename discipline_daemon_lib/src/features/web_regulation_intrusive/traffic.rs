@@ -0,0 +1,489 @@
+use url::form_urlencoded;
+use crate::operating_system_integration::UserId;
+use crate::Daemon;
+#[cfg(test)]
+use crate::DateTime;
+
+/// Request and response bodies longer than this are forwarded without being
+/// shown to `TrafficHandler::on_request_body` and `on_response_body`.
+pub const MAXIMUM_INSPECTED_BODY_LENGTH: usize = 8 * 1024 * 1024;
+
+/// How much of a response body `TrafficHandler::on_response_body_start` gets
+/// to see. Enough for file signatures and the first entries of archives.
+pub const SNIFFED_BODY_LENGTH: usize = 4096;
+
+// SECTION: Headers.
+/// Http headers in the order they were received. Name lookups are case-insensitive.
+/// Names of received headers are lowercased, the rest are forwarded as given.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headers {
+  entries: Vec<(String, String)>,
+}
+
+impl Headers {
+  pub fn new() -> Self {
+    Self {
+      entries: Vec::new(),
+    }
+  }
+
+  pub fn get(&self, name: &str) -> Option<&str> {
+    self
+      .entries
+      .iter()
+      .find(|(entry_name, _)| entry_name.eq_ignore_ascii_case(name))
+      .map(|(_, value)| value.as_str())
+  }
+
+  pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    self
+      .entries
+      .iter()
+      .filter(move |(entry_name, _)| entry_name.eq_ignore_ascii_case(name))
+      .map(|(_, value)| value.as_str())
+  }
+
+  pub fn contains(&self, name: &str) -> bool {
+    self.get(name).is_some()
+  }
+
+  /// Whether any of the comma-separated values of the header named `name`
+  /// is `token`, as in `Connection: keep-alive, Upgrade`.
+  pub fn contains_token(&self, name: &str, token: &str) -> bool {
+    self
+      .get_all(name)
+      .flat_map(|value| value.split(','))
+      .any(|value| value.trim().eq_ignore_ascii_case(token))
+  }
+
+  pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+    self.entries.push((name.into(), value.into()));
+  }
+
+  /// Replaces all headers named `name` with a single one.
+  pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+    let name = name.into();
+    self.remove(&name);
+    self.entries.push((name, value.into()));
+  }
+
+  pub fn remove(&mut self, name: &str) {
+    self.entries.retain(|(entry_name, _)| !entry_name.eq_ignore_ascii_case(name));
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+    self
+      .entries
+      .iter()
+      .map(|(name, value)| (name.as_str(), value.as_str()))
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  pub fn write_into(&self, into: &mut Vec<u8>) {
+    for (name, value) in &self.entries {
+      into.extend_from_slice(name.as_bytes());
+      into.extend_from_slice(b": ");
+      into.extend_from_slice(value.as_bytes());
+      into.extend_from_slice(b"\r\n");
+    }
+  }
+}
+
+// SECTION: Message heads.
+/// The media type of a body without parameters, lowercased.
+fn media_type(headers: &Headers) -> Option<String> {
+  headers.get("Content-Type").map(|value| {
+    value
+      .split(';')
+      .next()
+      .unwrap_or_default()
+      .trim()
+      .to_ascii_lowercase()
+  })
+}
+
+/// Whether a body has a content coding, like gzip, applied to it.
+fn is_content_encoded(headers: &Headers) -> bool {
+  headers
+    .get_all("Content-Encoding")
+    .flat_map(|value| value.split(','))
+    .any(|coding| !coding.trim().eq_ignore_ascii_case("identity"))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestHead {
+  pub method: String,
+  /// The request target in origin form, that is, the path and query.
+  pub target: String,
+  /// The minor http version, 0 for HTTP/1.0 and 1 for HTTP/1.1.
+  pub version: u8,
+  pub headers: Headers,
+}
+
+impl RequestHead {
+  pub fn path(&self) -> &str {
+    match self.target.find('?') {
+      Some(index) => &self.target[..index],
+      None => &self.target,
+    }
+  }
+
+  pub fn query(&self) -> Option<&str> {
+    self.target.split_once('?').map(|(_, query)| query)
+  }
+
+  /// The decoded value of the first query parameter named `name`.
+  pub fn query_parameter(&self, name: &str) -> Option<String> {
+    form_urlencoded::parse(self.query()?.as_bytes())
+      .find(|(parameter_name, _)| parameter_name == name)
+      .map(|(_, value)| value.into_owned())
+  }
+
+  /// The media type of the body without parameters, lowercased.
+  pub fn content_type(&self) -> Option<String> {
+    media_type(&self.headers)
+  }
+
+  /// Whether the body has a content coding, like gzip, applied to it.
+  pub fn is_content_encoded(&self) -> bool {
+    is_content_encoded(&self.headers)
+  }
+
+  pub fn is_keep_alive(&self) -> bool {
+    is_keep_alive(self.version, &self.headers)
+  }
+
+  pub fn write_into(&self, into: &mut Vec<u8>) {
+    into.extend_from_slice(self.method.as_bytes());
+    into.push(b' ');
+    into.extend_from_slice(self.target.as_bytes());
+    into.extend_from_slice(if self.version == 0 { b" HTTP/1.0\r\n" } else { b" HTTP/1.1\r\n" });
+    self.headers.write_into(into);
+    into.extend_from_slice(b"\r\n");
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseHead {
+  pub status_code: u16,
+  pub reason: String,
+  /// The minor http version, 0 for HTTP/1.0 and 1 for HTTP/1.1.
+  pub version: u8,
+  pub headers: Headers,
+}
+
+impl ResponseHead {
+  pub fn new(status_code: u16, reason: impl Into<String>) -> Self {
+    Self {
+      status_code,
+      reason: reason.into(),
+      version: 1,
+      headers: Headers::new(),
+    }
+  }
+
+  /// The media type of the body without parameters, lowercased.
+  pub fn content_type(&self) -> Option<String> {
+    media_type(&self.headers)
+  }
+
+  /// Whether the body has a content coding, like gzip, applied to it.
+  pub fn is_content_encoded(&self) -> bool {
+    is_content_encoded(&self.headers)
+  }
+
+  pub fn is_keep_alive(&self) -> bool {
+    is_keep_alive(self.version, &self.headers)
+  }
+
+  pub fn write_into(&self, into: &mut Vec<u8>) {
+    into.extend_from_slice(if self.version == 0 { b"HTTP/1.0 " } else { b"HTTP/1.1 " });
+    into.extend_from_slice(self.status_code.to_string().as_bytes());
+    into.push(b' ');
+    into.extend_from_slice(self.reason.as_bytes());
+    into.extend_from_slice(b"\r\n");
+    self.headers.write_into(into);
+    into.extend_from_slice(b"\r\n");
+  }
+}
+
+fn is_keep_alive(version: u8, headers: &Headers) -> bool {
+  if headers.contains_token("Connection", "close") {
+    return false;
+  }
+
+  version >= 1 || headers.contains_token("Connection", "keep-alive")
+}
+
+// SECTION: Exchange.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+  Http,
+  Https,
+}
+
+impl Scheme {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Scheme::Http => "http",
+      Scheme::Https => "https",
+    }
+  }
+
+  pub fn default_port(&self) -> u16 {
+    match self {
+      Scheme::Http => 80,
+      Scheme::Https => 443,
+    }
+  }
+}
+
+/// A request on its way to a website along with what we know about where it
+/// came from and where it's going.
+#[derive(Debug, Clone)]
+pub struct Exchange {
+  /// The user whose process made the request, if we could find out.
+  pub user_id: Option<UserId>,
+  pub scheme: Scheme,
+  /// The lowercased host name, without the port.
+  pub host: String,
+  pub port: u16,
+  pub request: RequestHead,
+}
+
+impl Exchange {
+  pub fn url(&self) -> String {
+    if self.port == self.scheme.default_port() {
+      format!("{}://{}{}", self.scheme.as_str(), self.host, self.request.target)
+    } else {
+      format!("{}://{}:{}{}", self.scheme.as_str(), self.host, self.port, self.request.target)
+    }
+  }
+
+  /// Whether the request is a browser loading a page into a tab, as opposed
+  /// to a page loading a resource or making a background request.
+  pub fn is_top_level_navigation(&self) -> bool {
+    let headers = &self.request.headers;
+    if self.request.method != "GET" {
+      return false;
+    }
+
+    // Every browser we care about sends "Sec-Fetch-Dest". For older ones,
+    // fall back on what they're willing to accept.
+    match headers.get("Sec-Fetch-Dest") {
+      Some(destination) => {
+        destination.eq_ignore_ascii_case("document")
+      }
+      None => {
+        headers
+          .get("Accept")
+          .is_some_and(|accept| accept.contains("text/html"))
+      }
+    }
+  }
+}
+
+/// Builds the exchanges tests feed to handlers.
+#[cfg(test)]
+impl Exchange {
+  /// A GET request of user 1000 for the absolute http or https `url`, whose
+  /// target is taken as is rather than normalized.
+  pub fn to(url: &str) -> Self {
+    let (scheme, rest) = match url.strip_prefix("https://") {
+      Some(rest) => (Scheme::Https, rest),
+      None => (Scheme::Http, url.strip_prefix("http://").expect("an http or https url")),
+    };
+
+    let (authority, target) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let (host, port) = match authority.rsplit_once(':') {
+      Some((host, port)) if !host.ends_with(':') => (host, port.parse().unwrap()),
+      _ => (authority, scheme.default_port()),
+    };
+
+    Self {
+      user_id: Some(UserId::new(1000)),
+      scheme,
+      host: host.to_ascii_lowercase(),
+      port,
+      request: RequestHead {
+        method: "GET".into(),
+        target: if target.is_empty() { "/".into() } else { target.into() },
+        version: 1,
+        headers: Headers::new(),
+      },
+    }
+  }
+
+  /// `to` as a browser loading `url` into a tab.
+  pub fn navigation_to(url: &str) -> Self {
+    Self::to(url).fetching("document")
+  }
+
+  /// Marks the request as made for `destination`, like `image` or `empty`,
+  /// the way browsers do with `Sec-Fetch-Dest`.
+  pub fn fetching(self, destination: &str) -> Self {
+    self.with_header("Sec-Fetch-Dest", destination)
+  }
+
+  pub fn with_header(mut self, name: &str, value: &str) -> Self {
+    self.request.headers.append(name, value);
+    self
+  }
+
+  pub fn with_method(mut self, method: &str) -> Self {
+    self.request.method = method.into();
+    self
+  }
+
+  pub fn with_user_id(mut self, user_id: Option<UserId>) -> Self {
+    self.user_id = user_id;
+    self
+  }
+}
+
+/// `minute` minutes past a fixed moment, for tests stepping through time.
+#[cfg(test)]
+pub fn at_minute(minute: i64) -> DateTime {
+  DateTime::from_timestamp(1_700_000_000_000 + minute * 60_000).unwrap()
+}
+
+// SECTION: Responses we send ourselves.
+#[derive(Debug, Clone)]
+pub struct Response {
+  pub head: ResponseHead,
+  pub body: Vec<u8>,
+}
+
+impl Response {
+  pub fn new(head: ResponseHead, body: Vec<u8>) -> Self {
+    Self { head, body }
+  }
+
+  pub fn html(status_code: u16, reason: &str, html: impl Into<String>) -> Self {
+    let mut head = ResponseHead::new(status_code, reason);
+    head.headers.append("Content-Type", "text/html; charset=utf-8");
+    head.headers.append("Cache-Control", "no-store");
+    Self::new(head, html.into().into_bytes())
+  }
+
+  pub fn redirect(location: &str) -> Self {
+    let mut head = ResponseHead::new(302, "Found");
+    head.headers.append("Location", location);
+    head.headers.append("Cache-Control", "no-store");
+    Self::new(head, Vec::new())
+  }
+
+  pub fn no_content() -> Self {
+    let mut head = ResponseHead::new(204, "No Content");
+    head.headers.append("Cache-Control", "no-store");
+    Self::new(head, Vec::new())
+  }
+}
+
+// SECTION: Handlers.
+pub enum RequestVerdict {
+  /// Send the request on to the website.
+  Forward,
+  /// Don't contact the website and answer with this response instead.
+  Respond(Response),
+}
+
+pub enum ResponseVerdict {
+  Forward,
+  Respond(Response),
+  /// Buffer the body and pass it to `TrafficHandler::on_response_body`.
+  /// Ignored for bodies with a content coding or longer than `MAXIMUM_INSPECTED_BODY_LENGTH`.
+  InspectBody,
+  /// Read the first `SNIFFED_BODY_LENGTH` bytes of the body and pass them to
+  /// `TrafficHandler::on_response_body_start`, then stream the rest. Ignored
+  /// for bodies with a content coding.
+  SniffBody,
+  /// Pass the body through this rewriter as it streams to the client. The
+  /// rewriters of several handlers run in the order of the handlers. Ignored
+  /// for bodies with a content coding.
+  RewriteBody(Box<dyn BodyRewriter>),
+}
+
+pub enum BodyVerdict {
+  /// Forward the body, including whatever modifications were made to it.
+  Forward,
+  Respond(Response),
+}
+
+/// Changes a response body as it streams through, without waiting for all of it.
+pub trait BodyRewriter: Send {
+  /// Rewrites the next part of the body into `output`. What can't be decided
+  /// yet, like a tag cut in two, may be held back until a later call.
+  fn write(&mut self, input: &[u8], output: &mut Vec<u8>);
+
+  /// Writes whatever was held back, once the body ended.
+  fn end(&mut self, output: &mut Vec<u8>);
+}
+
+/// Something that looks at, and possibly changes, intercepted web traffic.
+///
+/// Handlers are called in the order they were registered. The first one to
+/// answer with a verdict other than `Forward` wins.
+pub trait TrafficHandler: Send + Sync {
+  /// Whether the handler may want to inspect the response body of this
+  /// exchange. If any handler does, the website is asked not to compress it.
+  fn may_inspect_response_body(&self, _daemon: &Daemon, _exchange: &Exchange) -> bool {
+    false
+  }
+
+  fn on_request(&self, _daemon: &Daemon, _exchange: &mut Exchange) -> RequestVerdict {
+    RequestVerdict::Forward
+  }
+
+  /// Whether the handler wants to see the request body of this exchange. If
+  /// any handler does, the body is buffered and passed to `on_request_body`.
+  fn may_inspect_request_body(&self, _daemon: &Daemon, _exchange: &Exchange) -> bool {
+    false
+  }
+
+  /// Called with the request body, if any handler asked for it, before the
+  /// request is sent on. Not called for bodies with a content coding or
+  /// longer than `MAXIMUM_INSPECTED_BODY_LENGTH`.
+  fn on_request_body(&self, _daemon: &Daemon, _exchange: &Exchange, _body: &[u8]) -> RequestVerdict {
+    RequestVerdict::Forward
+  }
+
+  fn on_response(
+    &self,
+    _daemon: &Daemon,
+    _exchange: &Exchange,
+    _response: &mut ResponseHead,
+  ) -> ResponseVerdict {
+    ResponseVerdict::Forward
+  }
+
+  /// Called with the start of the body, or all of it if it's shorter than
+  /// `SNIFFED_BODY_LENGTH`, if any handler asked for it. Modifications to the
+  /// body aren't possible at this point.
+  fn on_response_body_start(
+    &self,
+    _daemon: &Daemon,
+    _exchange: &Exchange,
+    _response: &ResponseHead,
+    _body_start: &[u8],
+  ) -> BodyVerdict {
+    BodyVerdict::Forward
+  }
+
+  fn on_response_body(
+    &self,
+    _daemon: &Daemon,
+    _exchange: &Exchange,
+    _response: &mut ResponseHead,
+    _body: &mut Vec<u8>,
+  ) -> BodyVerdict {
+    BodyVerdict::Forward
+  }
+}
@@ -0,0 +1,310 @@
+use std::sync::{Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::database::web_regulation_intrusive_twitter_filter as filter_db;
+use crate::operating_system_integration::UserId;
+use crate::{Daemon, Database, GenericError, Uuid};
+use crate::api::block_pages::WebRuleFeature;
+use super::super::block_page;
+use super::super::traffic::*;
+use super::filter::{FilterKind, PostId, ScreenName};
+use super::navigation::{self, Target};
+use super::timeline::{self, Tweet};
+
+pub const MAXIMUM_FILTERS_PER_USER: usize = 1000;
+
+#[derive(Debug, Clone)]
+pub struct Filter {
+  id: Uuid,
+  user_id: UserId,
+  kind: FilterKind,
+}
+
+impl Filter {
+  pub fn from_fields(id: Uuid, user_id: UserId, kind: FilterKind) -> Self {
+    Self {
+      id,
+      user_id,
+      kind,
+    }
+  }
+
+  pub fn id(&self) -> &Uuid {
+    &self.id
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn kind(&self) -> &FilterKind {
+    &self.kind
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterCreator {
+  pub id: Option<Uuid>,
+  pub kind: FilterKind,
+}
+
+impl FilterCreator {
+  pub fn create(self, user_id: UserId) -> Filter {
+    Filter {
+      id: self.id.unwrap_or_else(Uuid::new_v4),
+      user_id,
+      kind: self.kind,
+    }
+  }
+}
+
+/// The filters of one user, grouped by kind.
+#[derive(Debug, Clone, Default)]
+pub struct FilterSet {
+  blocked_users: Vec<ScreenName>,
+  blocked_posts: Vec<PostId>,
+  allowed_users: Vec<ScreenName>,
+}
+
+impl FilterSet {
+  pub fn new<'a>(kinds: impl IntoIterator<Item = &'a FilterKind>) -> Self {
+    let mut filter_set = Self::default();
+    for kind in kinds {
+      match kind {
+        FilterKind::BlockedUser(screen_name) => {
+          filter_set.blocked_users.push(screen_name.clone());
+        }
+        FilterKind::BlockedPost(post_id) => {
+          filter_set.blocked_posts.push(post_id.clone());
+        }
+        FilterKind::AllowedUser(screen_name) => {
+          filter_set.allowed_users.push(screen_name.clone());
+        }
+      }
+    }
+    filter_set
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.blocked_users.is_empty()
+      && self.blocked_posts.is_empty()
+      && self.allowed_users.is_empty()
+  }
+
+  pub fn is_user_visible(&self, screen_name: &str) -> bool {
+    let is_blocked = self
+      .blocked_users
+      .iter()
+      .any(|blocked| blocked.matches(screen_name));
+
+    let is_allowed = self.allowed_users.is_empty() || self
+      .allowed_users
+      .iter()
+      .any(|allowed| allowed.matches(screen_name));
+
+    !is_blocked && is_allowed
+  }
+
+  pub fn is_post_visible(&self, post_id: &str, screen_name: Option<&str>) -> bool {
+    let is_blocked = self
+      .blocked_posts
+      .iter()
+      .any(|blocked| blocked.as_str() == post_id);
+
+    // Posts whose author we couldn't read, like deleted ones, stay.
+    !is_blocked && screen_name.is_none_or(|screen_name| self.is_user_visible(screen_name))
+  }
+
+  pub fn is_tweet_visible(&self, tweet: &Tweet) -> bool {
+    self.is_post_visible(&tweet.id, tweet.screen_name.as_deref())
+  }
+
+  pub fn is_target_visible(&self, target: &Target) -> bool {
+    match target {
+      Target::Profile { screen_name } => {
+        self.is_user_visible(screen_name)
+      }
+      Target::Post { screen_name, post_id } => {
+        self.is_post_visible(post_id, screen_name.as_deref())
+      }
+    }
+  }
+}
+
+// SECTION: Traffic handler.
+/// Hides posts from blocked accounts, or from accounts outside the allowed
+/// ones, and blocked posts.
+///
+/// Posts are removed from the GraphQL responses timelines, conversations
+/// and searches are loaded from, and blocked profiles look like they don't
+/// exist when opened from within the site. Opening a blocked profile or post
+/// directly redirects to the block page.
+pub struct TwitterRegulation {
+  filters: Mutex<Vec<Filter>>,
+}
+
+impl TwitterRegulation {
+  pub fn open(database: &Database) -> Result<Self, GenericError> {
+    let filters = filter_db::retrieve_all_filters(database)
+      .map_err(|error| error.change_context("opening the Twitter regulation"))?;
+
+    Ok(Self {
+      filters: Mutex::new(filters),
+    })
+  }
+
+  pub fn filters(&self) -> MutexGuard<'_, Vec<Filter>> {
+    self.filters.lock().unwrap()
+  }
+
+  fn filter_set_of(&self, user_id: Option<UserId>) -> FilterSet {
+    let Some(user_id) = user_id else {
+      return FilterSet::default();
+    };
+
+    FilterSet::new(
+      self
+        .filters()
+        .iter()
+        .filter(|filter| filter.user_id == user_id)
+        .map(|filter| &filter.kind)
+    )
+  }
+
+  /// The id of the filter of `user_id`'s hiding `target`, if any. Targets
+  /// outside the allowed accounts are hidden by the allowed-account
+  /// filters together, so the first of those is named.
+  fn filter_hiding(&self, user_id: Option<UserId>, target: &Target) -> Option<Uuid> {
+    let user_id = user_id?;
+    let filters: Vec<Filter> = self
+      .filters()
+      .iter()
+      .filter(|filter| filter.user_id == user_id)
+      .cloned()
+      .collect();
+
+    if FilterSet::new(filters.iter().map(|filter| &filter.kind)).is_target_visible(target) {
+      return None;
+    }
+
+    let (screen_name, post_id) = match target {
+      Target::Profile { screen_name } => {
+        (Some(screen_name.as_str()), None)
+      }
+      Target::Post { screen_name, post_id } => {
+        (screen_name.as_deref(), Some(post_id.as_str()))
+      }
+    };
+
+    let blocking = filters.iter().find(|filter| match &filter.kind {
+      FilterKind::BlockedUser(blocked) => screen_name.is_some_and(|screen_name| blocked.matches(screen_name)),
+      FilterKind::BlockedPost(blocked) => post_id == Some(blocked.as_str()),
+      FilterKind::AllowedUser(_) => false,
+    });
+
+    blocking
+      .or_else(|| filters.iter().find(|filter| matches!(filter.kind, FilterKind::AllowedUser(_))))
+      .map(|filter| filter.id)
+  }
+
+  fn has_filters_for(&self, user_id: Option<UserId>) -> bool {
+    user_id.is_some_and(|user_id| {
+      self.filters().iter().any(|filter| filter.user_id == user_id)
+    })
+  }
+}
+
+fn is_graphql(exchange: &Exchange) -> bool {
+  navigation::is_twitter_host(&exchange.host)
+    && navigation::is_graphql_path(exchange.request.path())
+}
+
+/// Rewrites a GraphQL response. Returns whether it changed.
+pub fn filter_graphql_response(filter_set: &FilterSet, operation: Option<&str>, response: &mut Value) -> bool {
+  if let Some("UserByScreenName" | "UserByRestId") = operation {
+    let is_hidden = timeline::user_screen_name(response)
+      .is_some_and(|screen_name| !filter_set.is_user_visible(screen_name));
+
+    if is_hidden {
+      // What the site answers for accounts that don't exist.
+      *response = json!({ "data": {} });
+      return true;
+    }
+  }
+
+  timeline::retain_tweets(response, &mut |tweet| filter_set.is_tweet_visible(tweet)) > 0
+}
+
+impl TrafficHandler for TwitterRegulation {
+  fn may_inspect_response_body(&self, _daemon: &Daemon, exchange: &Exchange) -> bool {
+    is_graphql(exchange) && self.has_filters_for(exchange.user_id)
+  }
+
+  fn on_request(&self, daemon: &Daemon, exchange: &mut Exchange) -> RequestVerdict {
+    if !exchange.is_top_level_navigation() || !navigation::is_twitter_host(&exchange.host) {
+      return RequestVerdict::Forward;
+    }
+
+    let Some(target) = navigation::target_from_path(exchange.request.path()) else {
+      return RequestVerdict::Forward;
+    };
+
+    match self.filter_hiding(exchange.user_id, &target) {
+      Some(filter_id) => {
+        RequestVerdict::Respond(block_page::respond(
+          daemon.configuration().block_page_port(),
+          exchange,
+          WebRuleFeature::TwitterFilter,
+          filter_id,
+        ))
+      }
+      None => {
+        RequestVerdict::Forward
+      }
+    }
+  }
+
+  fn on_response(
+    &self,
+    daemon: &Daemon,
+    exchange: &Exchange,
+    response: &mut ResponseHead,
+  ) -> ResponseVerdict {
+    if response.status_code == 200
+      && response.content_type().as_deref() == Some("application/json")
+      && self.may_inspect_response_body(daemon, exchange)
+    {
+      ResponseVerdict::InspectBody
+    } else {
+      ResponseVerdict::Forward
+    }
+  }
+
+  fn on_response_body(
+    &self,
+    _daemon: &Daemon,
+    exchange: &Exchange,
+    _response: &mut ResponseHead,
+    body: &mut Vec<u8>,
+  ) -> BodyVerdict {
+    if !is_graphql(exchange) {
+      return BodyVerdict::Forward;
+    }
+
+    let filter_set = self.filter_set_of(exchange.user_id);
+    if filter_set.is_empty() {
+      return BodyVerdict::Forward;
+    }
+
+    let Ok(mut response) = serde_json::from_slice::<Value>(body) else {
+      return BodyVerdict::Forward;
+    };
+
+    let operation = navigation::graphql_operation(exchange.request.path());
+    if filter_graphql_response(&filter_set, operation, &mut response) {
+      *body = response.to_string().into_bytes();
+    }
+
+    BodyVerdict::Forward
+  }
+}
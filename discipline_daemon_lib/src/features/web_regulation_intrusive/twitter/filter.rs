@@ -0,0 +1,124 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+use crate::GenericError;
+
+/// An account's handle, the part after '@', which is 1 to 15 letters,
+/// digits or '_'. Handles are case-insensitive, so they're kept lowercase.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ScreenName {
+  screen_name: String,
+}
+
+impl ScreenName {
+  pub const MAXIMUM_LENGTH: usize = 15;
+
+  pub fn new(screen_name: String) -> Result<Self, GenericError> {
+    let screen_name = screen_name
+      .strip_prefix('@')
+      .unwrap_or(&screen_name)
+      .to_ascii_lowercase();
+
+    let is_valid = !screen_name.is_empty()
+      && screen_name.len() <= Self::MAXIMUM_LENGTH
+      && screen_name
+        .bytes()
+        .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_');
+
+    if !is_valid {
+      return Err(
+        GenericError::new("creating a Twitter screen name")
+          .add_error("screen name isn't 1 to 15 letters, digits or '_'")
+          .add_attachment("screen name", screen_name)
+      );
+    }
+
+    Ok(Self { screen_name })
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.screen_name
+  }
+
+  pub fn matches(&self, screen_name: &str) -> bool {
+    self.screen_name.eq_ignore_ascii_case(screen_name)
+  }
+}
+
+impl Serialize for ScreenName {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    self.screen_name.serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for ScreenName {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    ScreenName::new(String::deserialize(deserializer)?)
+      .map_err(|error| Error::custom(format!("{error:?}")))
+  }
+}
+
+/// A post's id, the number at the end of `/{screen name}/status/{id}`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PostId {
+  post_id: String,
+}
+
+impl PostId {
+  /// Ids are 64-bit integers, which have at most 20 digits.
+  pub const MAXIMUM_LENGTH: usize = 20;
+
+  pub fn new(post_id: String) -> Result<Self, GenericError> {
+    let is_valid = !post_id.is_empty()
+      && post_id.len() <= Self::MAXIMUM_LENGTH
+      && post_id.bytes().all(|byte| byte.is_ascii_digit());
+
+    if !is_valid {
+      return Err(
+        GenericError::new("creating a Twitter post id")
+          .add_error("post id isn't 1 to 20 digits")
+          .add_attachment("post id", post_id)
+      );
+    }
+
+    Ok(Self { post_id })
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.post_id
+  }
+}
+
+impl Serialize for PostId {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    self.post_id.serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for PostId {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    PostId::new(String::deserialize(deserializer)?)
+      .map_err(|error| Error::custom(format!("{error:?}")))
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterKind {
+  /// Hides the account's posts and blocks its profile.
+  BlockedUser(ScreenName),
+  /// Hides the post and blocks its page.
+  BlockedPost(PostId),
+  /// Once a user has any allowed users, everyone else's posts are hidden
+  /// and their profiles blocked, as if they were all blocked users.
+  AllowedUser(ScreenName),
+}
@@ -1,6 +0,0 @@
-
-pub struct TwitterPlugin {
-  blockedUsers: Vec<String>,
-  blockedPosts: Vec<String>,
-  allowedUsers: Vec<String>,
-}
\ No newline at end of file
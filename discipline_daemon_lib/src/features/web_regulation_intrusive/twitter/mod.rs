@@ -0,0 +1,14 @@
+pub mod filter;
+pub use filter::{FilterKind, PostId, ScreenName};
+
+pub mod navigation;
+pub use navigation::Target;
+
+pub mod timeline;
+pub use timeline::Tweet;
+
+pub mod feature;
+pub use feature::{Filter, FilterCreator, FilterSet, TwitterRegulation, MAXIMUM_FILTERS_PER_USER};
+
+#[cfg(test)]
+mod tests;
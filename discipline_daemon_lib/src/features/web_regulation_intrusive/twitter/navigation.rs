@@ -0,0 +1,120 @@
+/// A page of a specific account or post.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+  /// `/{screen name}` and its tabs, like `/{screen name}/media`.
+  Profile { screen_name: String },
+  /// `/{screen name}/status/{id}`, and `/i/web/status/{id}` which doesn't
+  /// say whose post it is.
+  Post { screen_name: Option<String>, post_id: String },
+}
+
+/// First path segments that are pages of the site rather than profiles.
+const RESERVED_PATHS: &[&str] = &[
+  "about",
+  "account",
+  "bookmarks",
+  "communities",
+  "compose",
+  "download",
+  "explore",
+  "hashtag",
+  "home",
+  "i",
+  "intent",
+  "jobs",
+  "lists",
+  "login",
+  "logout",
+  "messages",
+  "notifications",
+  "oauth",
+  "premium",
+  "premium_sign_up",
+  "privacy",
+  "search",
+  "settings",
+  "share",
+  "signup",
+  "topics",
+  "tos",
+  "who_to_follow",
+];
+
+pub fn is_twitter_host(host: &str) -> bool {
+  matches!(
+    host,
+    "x.com"
+      | "www.x.com"
+      | "mobile.x.com"
+      | "twitter.com"
+      | "www.twitter.com"
+      | "mobile.twitter.com"
+  )
+}
+
+fn is_screen_name(segment: &str) -> bool {
+  !segment.is_empty()
+    && segment.len() <= 15
+    && segment
+      .bytes()
+      .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+}
+
+fn is_post_id(segment: &str) -> bool {
+  !segment.is_empty()
+    && segment.len() <= 20
+    && segment.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+pub fn target_from_path(path: &str) -> Option<Target> {
+  let mut segments = path.trim_start_matches('/').split('/');
+  let first = segments.next()?;
+
+  if first == "i" {
+    let rest: Vec<&str> = segments.collect();
+    let post_id = match rest.as_slice() {
+      ["web", "status", post_id, ..] | ["status", post_id, ..] => post_id,
+      _ => return None,
+    };
+
+    return is_post_id(post_id).then(|| Target::Post {
+      screen_name: None,
+      post_id: post_id.to_string(),
+    });
+  }
+
+  let is_reserved = RESERVED_PATHS
+    .iter()
+    .any(|reserved| reserved.eq_ignore_ascii_case(first));
+
+  if is_reserved || !is_screen_name(first) {
+    return None;
+  }
+
+  match (segments.next(), segments.next()) {
+    (Some("status" | "statuses"), Some(post_id)) if is_post_id(post_id) => {
+      Some(Target::Post {
+        screen_name: Some(first.to_string()),
+        post_id: post_id.to_string(),
+      })
+    }
+    _ => {
+      Some(Target::Profile { screen_name: first.to_string() })
+    }
+  }
+}
+
+/// Whether the request is for the GraphQL api the site loads timelines,
+/// posts and profiles from.
+pub fn is_graphql_path(path: &str) -> bool {
+  path.starts_with("/i/api/graphql/")
+}
+
+/// The operation a GraphQL request runs, the last segment of
+/// `/i/api/graphql/{query id}/{operation}`.
+pub fn graphql_operation(path: &str) -> Option<&str> {
+  path
+    .strip_prefix("/i/api/graphql/")?
+    .split('/')
+    .nth(1)
+}
@@ -0,0 +1,286 @@
+use serde_json::{json, Value};
+use super::feature::filter_graphql_response;
+use super::navigation::{self, Target};
+use super::timeline::{self, Tweet};
+use super::{FilterKind, FilterSet, PostId, ScreenName};
+
+static HOME_TIMELINE: &str = include_str!("fixtures/home_timeline.json");
+static TWEET_DETAIL: &str = include_str!("fixtures/tweet_detail.json");
+static USER_TWEETS: &str = include_str!("fixtures/user_tweets.json");
+static USER_BY_SCREEN_NAME: &str = include_str!("fixtures/user_by_screen_name.json");
+
+fn parse(fixture: &str) -> Value {
+  serde_json::from_str(fixture).unwrap()
+}
+
+fn blocked_user(screen_name: &str) -> FilterKind {
+  FilterKind::BlockedUser(ScreenName::new(screen_name.into()).unwrap())
+}
+
+fn blocked_post(post_id: &str) -> FilterKind {
+  FilterKind::BlockedPost(PostId::new(post_id.into()).unwrap())
+}
+
+fn allowed_user(screen_name: &str) -> FilterKind {
+  FilterKind::AllowedUser(ScreenName::new(screen_name.into()).unwrap())
+}
+
+/// Filters `fixture` as a response to `operation` and returns the ids of
+/// the entries and module items left.
+fn filter(fixture: &str, operation: &str, kinds: &[FilterKind]) -> Vec<String> {
+  let mut response = parse(fixture);
+  filter_graphql_response(&FilterSet::new(kinds), Some(operation), &mut response);
+  entry_ids(&response)
+}
+
+fn entry_ids(value: &Value) -> Vec<String> {
+  let mut entry_ids = Vec::new();
+  let mut next = vec![value];
+
+  while let Some(value) = next.pop() {
+    match value {
+      Value::Object(object) => {
+        if let Some(entry_id) = object.get("entryId").and_then(Value::as_str) {
+          entry_ids.push(entry_id.to_string());
+        }
+        next.extend(object.values().rev());
+      }
+      Value::Array(items) => {
+        next.extend(items.iter().rev());
+      }
+      _ => {}
+    }
+  }
+
+  entry_ids
+}
+
+#[test]
+fn reads_tweets_of_every_layout() {
+  let home_timeline = parse(HOME_TIMELINE);
+  let entries = home_timeline
+    .pointer("/data/home/home_timeline_urt/instructions/0/entries")
+    .unwrap();
+
+  let result = |index: usize| {
+    entries
+      .pointer(&format!("/{index}/content/itemContent/tweet_results/result"))
+      .unwrap()
+  };
+
+  assert_eq!(
+    Tweet::from_result(result(0)),
+    Some(Tweet { id: "1977000000000000001".into(), screen_name: Some("NASA".into()) }),
+  );
+
+  // Retweets and quotes come with the posts they embed.
+  assert_eq!(
+    timeline::tweets_of(result(2)),
+    [
+      Tweet { id: "1977000000000000003".into(), screen_name: Some("github".into()) },
+      Tweet { id: "1976000000000000001".into(), screen_name: Some("rustlang".into()) },
+    ],
+  );
+
+  // The quoted post uses the newer layout with the screen name in `core`.
+  assert_eq!(
+    timeline::tweets_of(result(3)),
+    [
+      Tweet { id: "1977000000000000004".into(), screen_name: Some("NASA".into()) },
+      Tweet { id: "1976000000000000002".into(), screen_name: Some("SpaceX".into()) },
+    ],
+  );
+
+  // Limited posts are wrapped in a `TweetWithVisibilityResults`.
+  assert_eq!(
+    Tweet::from_result(result(4)),
+    Some(Tweet { id: "1977000000000000005".into(), screen_name: Some("elonmusk".into()) }),
+  );
+}
+
+#[test]
+fn leaves_responses_alone_without_filters() {
+  let mut response = parse(HOME_TIMELINE);
+  assert!(!filter_graphql_response(&FilterSet::default(), Some("HomeTimeline"), &mut response));
+  assert_eq!(response, parse(HOME_TIMELINE));
+}
+
+#[test]
+fn hides_posts_of_blocked_users() {
+  assert_eq!(
+    filter(HOME_TIMELINE, "HomeTimeline", &[blocked_user("ElonMusk")]),
+    [
+      "tweet-1977000000000000001",
+      "tweet-1977000000000000003",
+      "tweet-1977000000000000004",
+      "home-conversation-1977000000000000010",
+      "home-conversation-1977000000000000010-tweet-1977000000000000010",
+      "cursor-top-1977000000000000002",
+      "cursor-bottom-1977000000000000001",
+    ],
+  );
+
+  assert_eq!(
+    filter(TWEET_DETAIL, "TweetDetail", &[blocked_user("elonmusk")]),
+    [
+      "tweet-1977000000000000030",
+      "conversationthread-1977000000000000031",
+      "conversationthread-1977000000000000031-tweet-1977000000000000031",
+    ],
+  );
+}
+
+#[test]
+fn hides_posts_retweeting_or_quoting_blocked_users() {
+  let entry_ids = filter(HOME_TIMELINE, "HomeTimeline", &[blocked_user("rustlang"), blocked_user("spacex")]);
+
+  assert!(!entry_ids.contains(&"tweet-1977000000000000003".to_string()));
+  assert!(!entry_ids.contains(&"tweet-1977000000000000004".to_string()));
+  assert!(!entry_ids.contains(&"home-conversation-1977000000000000010-tweet-1977000000000000010".to_string()));
+  assert!(entry_ids.contains(&"home-conversation-1977000000000000010-tweet-1977000000000000011".to_string()));
+  assert!(entry_ids.contains(&"tweet-1977000000000000001".to_string()));
+}
+
+#[test]
+fn hides_blocked_posts() {
+  // Blocking a post hides its retweets too.
+  assert_eq!(
+    filter(HOME_TIMELINE, "HomeTimeline", &[blocked_post("1976000000000000001"), blocked_post("1977000000000000021")]),
+    [
+      "tweet-1977000000000000001",
+      "tweet-1977000000000000002",
+      "tweet-1977000000000000004",
+      "tweet-1977000000000000005",
+      "home-conversation-1977000000000000010",
+      "home-conversation-1977000000000000010-tweet-1977000000000000010",
+      "home-conversation-1977000000000000010-tweet-1977000000000000011",
+      "home-conversation-1977000000000000020",
+      "home-conversation-1977000000000000020-tweet-1977000000000000020",
+      "cursor-top-1977000000000000002",
+      "cursor-bottom-1977000000000000001",
+    ],
+  );
+}
+
+#[test]
+fn hides_posts_of_users_outside_the_allowlist() {
+  assert_eq!(
+    filter(HOME_TIMELINE, "HomeTimeline", &[allowed_user("nasa"), allowed_user("rustlang")]),
+    [
+      "tweet-1977000000000000001",
+      "home-conversation-1977000000000000010",
+      "home-conversation-1977000000000000010-tweet-1977000000000000010",
+      "cursor-top-1977000000000000002",
+      "cursor-bottom-1977000000000000001",
+    ],
+  );
+
+  // Blocked users stay blocked even if they're allowed.
+  assert_eq!(
+    filter(USER_TWEETS, "UserTweets", &[allowed_user("nasa"), blocked_user("nasa")]),
+    ["cursor-bottom-1977000000000000039"],
+  );
+
+  // The pinned post's instruction goes away with it.
+  let mut response = parse(USER_TWEETS);
+  let filter_set = FilterSet::new(&[allowed_user("rustlang")]);
+  assert!(filter_graphql_response(&filter_set, Some("UserTweets"), &mut response));
+
+  let instructions = response
+    .pointer("/data/user/result/timeline/timeline/instructions")
+    .and_then(Value::as_array)
+    .unwrap();
+
+  let types: Vec<&str> = instructions
+    .iter()
+    .filter_map(|instruction| instruction.get("type").and_then(Value::as_str))
+    .collect();
+
+  assert_eq!(types, ["TimelineClearCache", "TimelineAddEntries"]);
+}
+
+#[test]
+fn hides_profiles_of_hidden_users() {
+  let mut response = parse(USER_BY_SCREEN_NAME);
+  assert_eq!(timeline::user_screen_name(&response), Some("elonmusk"));
+
+  let filter_set = FilterSet::new(&[blocked_user("nasa")]);
+  assert!(!filter_graphql_response(&filter_set, Some("UserByScreenName"), &mut response));
+  assert_eq!(response, parse(USER_BY_SCREEN_NAME));
+
+  let filter_set = FilterSet::new(&[blocked_user("elonmusk")]);
+  assert!(filter_graphql_response(&filter_set, Some("UserByScreenName"), &mut response));
+  assert_eq!(response, json!({ "data": {} }));
+}
+
+#[test]
+fn reads_targets_from_paths() {
+  let profile = |screen_name: &str| Some(Target::Profile { screen_name: screen_name.into() });
+  let post = |screen_name: Option<&str>, post_id: &str| Some(Target::Post {
+    screen_name: screen_name.map(str::to_string),
+    post_id: post_id.into(),
+  });
+
+  assert_eq!(navigation::target_from_path("/elonmusk"), profile("elonmusk"));
+  assert_eq!(navigation::target_from_path("/elonmusk/"), profile("elonmusk"));
+  assert_eq!(navigation::target_from_path("/elonmusk/media"), profile("elonmusk"));
+  assert_eq!(navigation::target_from_path("/elonmusk/status/not-a-number"), profile("elonmusk"));
+  assert_eq!(
+    navigation::target_from_path("/elonmusk/status/1977000000000000002"),
+    post(Some("elonmusk"), "1977000000000000002"),
+  );
+  assert_eq!(
+    navigation::target_from_path("/elonmusk/status/1977000000000000002/photo/1"),
+    post(Some("elonmusk"), "1977000000000000002"),
+  );
+  assert_eq!(
+    navigation::target_from_path("/i/web/status/1977000000000000002"),
+    post(None, "1977000000000000002"),
+  );
+
+  for path in ["/", "/home", "/Explore", "/i/bookmarks", "/settings/account", "/search", "/a_name_too_long_for_a_handle"] {
+    assert_eq!(navigation::target_from_path(path), None, "{path}");
+  }
+
+  assert_eq!(
+    navigation::graphql_operation("/i/api/graphql/CwLU7qTfeu0doqhSr6tW4A/UserTweets"),
+    Some("UserTweets"),
+  );
+  assert_eq!(navigation::graphql_operation("/i/api/1.1/jot/client_event.json"), None);
+}
+
+#[test]
+fn decides_which_targets_are_visible() {
+  let post = |screen_name: Option<&str>, post_id: &str| Target::Post {
+    screen_name: screen_name.map(str::to_string),
+    post_id: post_id.into(),
+  };
+
+  let filter_set = FilterSet::new(&[blocked_user("elonmusk"), blocked_post("1977000000000000001")]);
+  assert!(!filter_set.is_target_visible(&Target::Profile { screen_name: "ElonMusk".into() }));
+  assert!(filter_set.is_target_visible(&Target::Profile { screen_name: "nasa".into() }));
+  assert!(!filter_set.is_target_visible(&post(Some("elonmusk"), "1")));
+  assert!(!filter_set.is_target_visible(&post(None, "1977000000000000001")));
+  assert!(filter_set.is_target_visible(&post(None, "1977000000000000002")));
+
+  let filter_set = FilterSet::new(&[allowed_user("nasa")]);
+  assert!(filter_set.is_target_visible(&Target::Profile { screen_name: "NASA".into() }));
+  assert!(!filter_set.is_target_visible(&Target::Profile { screen_name: "spacex".into() }));
+  assert!(!filter_set.is_target_visible(&post(Some("spacex"), "1")));
+}
+
+#[test]
+fn validates_filters_when_deserializing() {
+  let kind: FilterKind = serde_json::from_str(r#"{"BlockedUser":"@ElonMusk"}"#).unwrap();
+  assert_eq!(kind, blocked_user("elonmusk"));
+  assert_eq!(serde_json::to_string(&kind).unwrap(), r#"{"BlockedUser":"elonmusk"}"#);
+
+  let kind: FilterKind = serde_json::from_str(r#"{"BlockedPost":"1977000000000000001"}"#).unwrap();
+  assert_eq!(kind, blocked_post("1977000000000000001"));
+
+  assert!(serde_json::from_str::<FilterKind>(r#"{"BlockedUser":""}"#).is_err());
+  assert!(serde_json::from_str::<FilterKind>(r#"{"AllowedUser":"not a handle"}"#).is_err());
+  assert!(serde_json::from_str::<FilterKind>(r#"{"AllowedUser":"sixteen_letters_"}"#).is_err());
+  assert!(serde_json::from_str::<FilterKind>(r#"{"BlockedPost":"12ab"}"#).is_err());
+  assert!(serde_json::from_str::<FilterKind>(r#"{"BlockedPost":"123456789012345678901"}"#).is_err());
+}
@@ -0,0 +1,126 @@
+use serde_json::Value;
+
+/// A post found in a GraphQL response.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tweet {
+  pub id: String,
+  pub screen_name: Option<String>,
+}
+
+impl Tweet {
+  /// Reads a `tweet_results.result`.
+  pub fn from_result(result: &Value) -> Option<Self> {
+    let result = unwrap_visibility_results(result);
+    let user = result.pointer("/core/user_results/result");
+
+    Some(Self {
+      id: string(result.get("rest_id"))
+        .or_else(|| string(result.pointer("/legacy/id_str")))?,
+      // Newer responses moved the screen name out of `legacy`.
+      screen_name: user.and_then(|user| {
+        string(user.pointer("/legacy/screen_name"))
+          .or_else(|| string(user.pointer("/core/screen_name")))
+      }),
+    })
+  }
+}
+
+/// Posts that were limited get wrapped in a `TweetWithVisibilityResults`.
+fn unwrap_visibility_results(result: &Value) -> &Value {
+  if result.get("__typename").and_then(Value::as_str) == Some("TweetWithVisibilityResults") {
+    result.get("tweet").unwrap_or(result)
+  } else {
+    result
+  }
+}
+
+/// Returns the post of a `tweet_results.result` and the posts it retweets or
+/// quotes, recursively.
+pub fn tweets_of(result: &Value) -> Vec<Tweet> {
+  let mut tweets = Vec::new();
+  let mut next = Some(result);
+
+  while let Some(result) = next.take() {
+    let result = unwrap_visibility_results(result);
+    tweets.extend(Tweet::from_result(result));
+
+    if let Some(retweeted) = result.pointer("/legacy/retweeted_status_result/result") {
+      tweets.extend(tweets_of(retweeted));
+    }
+
+    next = result.pointer("/quoted_status_result/result");
+  }
+
+  tweets
+}
+
+/// Where timeline items keep their post: entries of `TimelineAddEntries`,
+/// items of conversation modules and `TimelineAddToModule`, and the single
+/// entry of `TimelinePinEntry` and `TimelineReplaceEntry`.
+const ITEM_TWEET_POINTERS: [&str; 3] = [
+  "/content/itemContent/tweet_results/result",
+  "/item/itemContent/tweet_results/result",
+  "/entry/content/itemContent/tweet_results/result",
+];
+
+fn item_tweet_result(item: &Value) -> Option<&Value> {
+  ITEM_TWEET_POINTERS
+    .iter()
+    .find_map(|pointer| item.pointer(pointer))
+}
+
+/// Whether `item` is a conversation module all of whose posts were removed.
+fn is_emptied_module(item: &Value) -> bool {
+  item
+    .pointer("/content/items")
+    .and_then(Value::as_array)
+    .is_some_and(Vec::is_empty)
+}
+
+/// Removes the timeline items holding a post for which `keep` returns false
+/// from anywhere in `response`. An item is removed if `keep` rejects its post
+/// or any post that one retweets or quotes. Conversation modules left without
+/// posts are removed too. Returns how many items were removed.
+pub fn retain_tweets(value: &mut Value, keep: &mut impl FnMut(&Tweet) -> bool) -> usize {
+  match value {
+    Value::Array(items) => {
+      let mut removed = 0;
+      for item in items.iter_mut() {
+        removed += retain_tweets(item, keep);
+      }
+
+      let length = items.len();
+      items.retain(|item| {
+        match item_tweet_result(item) {
+          Some(result) => tweets_of(result).iter().all(&mut *keep),
+          None => !is_emptied_module(item),
+        }
+      });
+
+      removed + (length - items.len())
+    }
+    Value::Object(object) => {
+      object
+        .values_mut()
+        .map(|value| retain_tweets(value, keep))
+        .sum()
+    }
+    _ => {
+      0
+    }
+  }
+}
+
+/// Reads the screen name of the account in a `UserByScreenName` or
+/// `UserByRestId` response.
+pub fn user_screen_name(response: &Value) -> Option<&str> {
+  let user = response.pointer("/data/user/result")?;
+  user
+    .pointer("/legacy/screen_name")
+    .or_else(|| user.pointer("/core/screen_name"))
+    .and_then(Value::as_str)
+}
+
+fn string(value: Option<&Value>) -> Option<String> {
+  value?.as_str().map(str::to_string)
+}
@@ -0,0 +1,614 @@
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::hash::{Hash, Hasher};
+use regex::{Regex, RegexBuilder};
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use url::{form_urlencoded, Position, Url};
+use crate::GenericError;
+use super::super::traffic::Exchange;
+
+// SECTION: Host pattern.
+/// A host name, like `example.com`, or a host name suffix, like `*.example.com`,
+/// which also matches `example.com` itself.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HostPattern(String);
+
+impl HostPattern {
+  pub fn parse(pattern: &str) -> Result<Self, GenericError> {
+    let pattern = pattern.trim().trim_end_matches('.').to_ascii_lowercase();
+    let host = pattern.strip_prefix("*.").unwrap_or(&pattern);
+
+    let is_valid = !host.is_empty()
+      && host.len() <= 253
+      && host.split('.').all(|label| {
+        !label.is_empty()
+          && label.len() <= 63
+          && label
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+      });
+
+    if !is_valid {
+      return Err(
+        GenericError::new("parsing a host pattern")
+          .add_error("pattern is neither a host name nor '*.' followed by a host name")
+          .add_attachment("pattern", pattern)
+      );
+    }
+
+    Ok(Self(pattern))
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+
+  /// The host name, without the `*.` of suffixes.
+  pub fn host(&self) -> &str {
+    self.0.strip_prefix("*.").unwrap_or(&self.0)
+  }
+
+  pub fn is_suffix(&self) -> bool {
+    self.0.starts_with("*.")
+  }
+
+  /// `host` must be lowercase.
+  pub fn matches(&self, host: &str) -> bool {
+    match self.0.strip_prefix("*.") {
+      Some(suffix) => {
+        host == suffix
+          || host
+            .strip_suffix(suffix)
+            .is_some_and(|rest| rest.ends_with('.'))
+      }
+      None => {
+        host == self.0
+      }
+    }
+  }
+}
+
+impl Serialize for HostPattern {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    self.0.serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for HostPattern {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    HostPattern::parse(&String::deserialize(deserializer)?)
+      .map_err(|error| Error::custom(format!("{error:?}")))
+  }
+}
+
+// SECTION: Path patterns.
+fn is_unreserved(byte: u8) -> bool {
+  byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~')
+}
+
+/// Spells `path` the one way path patterns compare it, so that a page can't
+/// slip past a pattern by writing its path differently: percent-encoded
+/// unreserved characters are decoded, other escapes uppercased, runs of '/'
+/// collapsed and '.' and '..' segments resolved.
+pub fn normalize_path(path: &str) -> Cow<'_, str> {
+  let has_dot_segments = path.split('/').any(|segment| segment == "." || segment == "..");
+  if !path.starts_with('/') || !(path.contains('%') || path.contains("//") || has_dot_segments) {
+    return Cow::Borrowed(path);
+  }
+
+  let bytes = path.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len());
+  let mut index = 0;
+  while index < bytes.len() {
+    let escaped = bytes
+      .get(index + 1..index + 3)
+      .filter(|_| bytes[index] == b'%')
+      .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+
+    match escaped {
+      Some(byte) if is_unreserved(byte) => {
+        decoded.push(byte);
+        index += 3;
+      }
+      Some(_) => {
+        decoded.push(b'%');
+        decoded.extend(bytes[index + 1..index + 3].iter().map(u8::to_ascii_uppercase));
+        index += 3;
+      }
+      None => {
+        decoded.push(bytes[index]);
+        index += 1;
+      }
+    }
+  }
+
+  // Only ASCII was decoded or uppercased, so this is still UTF-8.
+  let decoded = String::from_utf8(decoded).unwrap_or_default();
+
+  let mut normalized = String::with_capacity(decoded.len());
+  let mut ends_with_slash = false;
+  for segment in decoded[1..].split('/') {
+    ends_with_slash = matches!(segment, "" | "." | "..");
+    match segment {
+      "" | "." => {}
+      ".." => {
+        normalized.truncate(normalized.rfind('/').unwrap_or(0));
+      }
+      segment => {
+        normalized.push('/');
+        normalized.push_str(segment);
+      }
+    }
+  }
+
+  if normalized.is_empty() || ends_with_slash {
+    normalized.push('/');
+  }
+
+  Cow::Owned(normalized)
+}
+
+/// A path that must start with '/'. The pattern matches it and every path
+/// below it. Both are compared normalized, see `normalize_path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathPrefix(String);
+
+impl PathPrefix {
+  pub const MAX_LENGTH: usize = 2048;
+
+  pub fn new(path: String) -> Result<Self, GenericError> {
+    if !path.starts_with('/') {
+      return Err(
+        GenericError::new("creating a PathPrefix")
+          .add_error("path doesn't start with '/'")
+          .add_attachment("path", path)
+      );
+    }
+
+    if path.len() > Self::MAX_LENGTH {
+      return Err(
+        GenericError::new("creating a PathPrefix")
+          .add_error("path is longer than the maximum length")
+          .add_attachment("maximum length", Self::MAX_LENGTH.to_string())
+          .add_attachment("path length", path.len().to_string())
+      );
+    }
+
+    Ok(Self(normalize_path(&path).into_owned()))
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+
+  pub fn matches(&self, path: &str) -> bool {
+    match normalize_path(path).strip_prefix(self.0.as_str()) {
+      Some(rest) => {
+        self.0.ends_with('/') || rest.is_empty() || rest.starts_with('/')
+      }
+      None => {
+        false
+      }
+    }
+  }
+}
+
+impl Serialize for PathPrefix {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    self.0.serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for PathPrefix {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    PathPrefix::new(String::deserialize(deserializer)?)
+      .map_err(|error| Error::custom(format!("{error:?}")))
+  }
+}
+
+/// Whether `text` matches all of `glob`, where `*` stands for any run of
+/// characters, `/` included, and every other character for itself.
+pub fn glob_matches(glob: &str, text: &str) -> bool {
+  let glob = glob.as_bytes();
+  let text = text.as_bytes();
+
+  let (mut glob_index, mut text_index) = (0, 0);
+  // Where the last `*` was and where in the text it started matching, to
+  // retry with it matching one more character when the rest fails.
+  let mut backtrack: Option<(usize, usize)> = None;
+
+  while text_index < text.len() {
+    if glob.get(glob_index) == Some(&b'*') {
+      backtrack = Some((glob_index, text_index));
+      glob_index += 1;
+    } else if glob.get(glob_index) == Some(&text[text_index]) {
+      glob_index += 1;
+      text_index += 1;
+    } else if let Some((star_index, star_text_index)) = backtrack {
+      glob_index = star_index + 1;
+      text_index = star_text_index + 1;
+      backtrack = Some((star_index, text_index));
+    } else {
+      return false;
+    }
+  }
+
+  glob[glob_index..].iter().all(|byte| *byte == b'*')
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathPattern {
+  Prefix(PathPrefix),
+  /// A path with at least one `*`, which must match all of the request's.
+  Glob(String),
+}
+
+impl PathPattern {
+  pub fn parse(path: &str) -> Result<Self, GenericError> {
+    let prefix = PathPrefix::new(path.into())
+      .map_err(|error| error.change_context("parsing a path pattern"))?;
+
+    if path.contains('*') {
+      Ok(PathPattern::Glob(prefix.0))
+    } else {
+      Ok(PathPattern::Prefix(prefix))
+    }
+  }
+
+  pub fn as_str(&self) -> &str {
+    match self {
+      PathPattern::Prefix(prefix) => prefix.as_str(),
+      PathPattern::Glob(glob) => glob,
+    }
+  }
+
+  pub fn matches(&self, path: &str) -> bool {
+    match self {
+      PathPattern::Prefix(prefix) => {
+        prefix.matches(path)
+      }
+      PathPattern::Glob(glob) => {
+        glob_matches(glob, &normalize_path(path))
+      }
+    }
+  }
+
+  /// The longest run of the pattern without `*`, which every path it
+  /// matches contains.
+  pub fn required_literal(&self) -> &str {
+    match self {
+      PathPattern::Prefix(prefix) => {
+        prefix.as_str()
+      }
+      PathPattern::Glob(glob) => {
+        glob.split('*').max_by_key(|literal| literal.len()).unwrap_or_default()
+      }
+    }
+  }
+}
+
+// SECTION: Query constraints.
+/// A query parameter the url must have. Without a value any value will do,
+/// and values may contain `*`s like globs. Names and values are compared
+/// decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryConstraint {
+  name: String,
+  value: Option<String>,
+}
+
+impl QueryConstraint {
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn value(&self) -> Option<&str> {
+    self.value.as_deref()
+  }
+
+  pub fn matches(&self, query: &str) -> bool {
+    form_urlencoded::parse(query.as_bytes()).any(|(name, value)| {
+      name == self.name.as_str()
+        && self
+          .value
+          .as_ref()
+          .is_none_or(|glob| glob_matches(glob, &value))
+    })
+  }
+}
+
+fn decode_query_component(component: &str) -> String {
+  form_urlencoded::parse(format!("x={component}").as_bytes())
+    .next()
+    .map(|(_, decoded)| decoded.into_owned())
+    .unwrap_or_default()
+}
+
+fn parse_query_constraints(query: &str) -> Result<Vec<QueryConstraint>, GenericError> {
+  let mut constraints = Vec::new();
+  for parameter in query.split('&') {
+    let (name, value) = match parameter.split_once('=') {
+      Some((name, value)) => (name, Some(value)),
+      None => (parameter, None),
+    };
+
+    if name.is_empty() {
+      return Err(
+        GenericError::new("parsing query constraints")
+          .add_error("a query parameter has no name")
+          .add_attachment("query", query)
+      );
+    }
+
+    constraints.push(QueryConstraint {
+      name: decode_query_component(name),
+      value: value.map(decode_query_component),
+    });
+  }
+
+  Ok(constraints)
+}
+
+// SECTION: Url pattern.
+enum UrlSource<'a> {
+  Exchange(&'a Exchange),
+  Url(&'a Url),
+}
+
+/// The parts of a url that patterns look at.
+pub(super) struct UrlParts<'a> {
+  /// Lowercase, without a trailing dot.
+  pub host: Cow<'a, str>,
+  /// Normalized with `normalize_path`.
+  pub path: Cow<'a, str>,
+  pub query: Option<&'a str>,
+  source: UrlSource<'a>,
+  /// The whole url without a fragment, for regular expressions, only put
+  /// together once one needs it.
+  url: OnceCell<String>,
+}
+
+impl<'a> UrlParts<'a> {
+  pub fn of_exchange(exchange: &'a Exchange) -> Self {
+    Self {
+      host: Cow::Borrowed(exchange.host.trim_end_matches('.')),
+      path: normalize_path(exchange.request.path()),
+      query: exchange.request.query(),
+      source: UrlSource::Exchange(exchange),
+      url: OnceCell::new(),
+    }
+  }
+
+  /// Urls without a host, like `about:blank`, have an empty one, which only
+  /// patterns for any host match.
+  pub fn of_url(url: &'a Url) -> Self {
+    let host = url.host_str().unwrap_or_default().trim_end_matches('.');
+    Self {
+      host: Cow::Owned(host.to_ascii_lowercase()),
+      path: normalize_path(url.path()),
+      query: url.query(),
+      source: UrlSource::Url(url),
+      url: OnceCell::new(),
+    }
+  }
+
+  pub fn url(&self) -> &str {
+    self.url.get_or_init(|| match self.source {
+      UrlSource::Exchange(exchange) => exchange.url(),
+      UrlSource::Url(url) => url[..Position::AfterQuery].to_string(),
+    })
+  }
+}
+
+/// Which urls a web rule applies to, written like a url without a scheme:
+///
+/// - a host pattern, or `*` for any host, like `*.example.com`,
+/// - optionally, a path, which matches itself and every path below it or,
+///   if it has `*`s, is a glob that must match all of the url's path, like
+///   `/r/*/comments/*`,
+/// - optionally, `?` and `&` separated query parameters the url must have,
+///   like `?v=abc&list`, with `*`s in values standing for anything,
+/// - optionally, whitespace followed by a regular expression that must
+///   match somewhere in the whole url, scheme included.
+///
+/// A url must match every part of the pattern it has.
+#[derive(Debug, Clone)]
+pub struct UrlPattern {
+  /// The pattern as written in its normalized form.
+  source: String,
+  /// `None` for any host.
+  host: Option<HostPattern>,
+  path: Option<PathPattern>,
+  query: Vec<QueryConstraint>,
+  regex: Option<Regex>,
+}
+
+impl UrlPattern {
+  pub const MAXIMUM_QUERY_CONSTRAINTS: usize = 16;
+  pub const MAXIMUM_REGEX_LENGTH: usize = 1024;
+  /// Keeps a compiled regular expression, which is linear in the length of
+  /// what it matches, at a reasonable size.
+  const REGEX_SIZE_LIMIT: usize = 1024 * 1024;
+
+  pub fn parse(pattern: &str) -> Result<Self, GenericError> {
+    let pattern = pattern.trim();
+    if pattern.contains(['\n', '\r']) {
+      return Err(
+        GenericError::new("parsing a url pattern")
+          .add_error("pattern has a line break")
+          .add_attachment("pattern", pattern)
+      );
+    }
+
+    let (url, regex) = match pattern.split_once(char::is_whitespace) {
+      Some((url, regex)) => (url, Some(regex.trim_start())),
+      None => (pattern, None),
+    };
+
+    let (url, query_source) = match url.split_once('?') {
+      Some((url, query)) => (url, Some(query).filter(|query| !query.is_empty())),
+      None => (url, None),
+    };
+
+    let (host, path) = match url.find('/') {
+      Some(index) => (&url[..index], Some(&url[index..])),
+      None => (url, None),
+    };
+
+    let error = |error: GenericError| {
+      error
+        .change_context("parsing a url pattern")
+        .add_attachment("pattern", pattern)
+    };
+
+    let host = match host {
+      "*" => None,
+      host => Some(HostPattern::parse(host).map_err(error)?),
+    };
+
+    let path = path.map(PathPattern::parse).transpose().map_err(error)?;
+
+    let query = query_source
+      .map(parse_query_constraints)
+      .transpose()
+      .map_err(error)?
+      .unwrap_or_default();
+
+    if query.len() > Self::MAXIMUM_QUERY_CONSTRAINTS {
+      return Err(
+        GenericError::new("parsing a url pattern")
+          .add_error("pattern has more query constraints than the maximum")
+          .add_attachment("maximum", Self::MAXIMUM_QUERY_CONSTRAINTS.to_string())
+          .add_attachment("pattern", pattern)
+      );
+    }
+
+    let regex = match regex {
+      Some(regex) if regex.len() > Self::MAXIMUM_REGEX_LENGTH => {
+        return Err(
+          GenericError::new("parsing a url pattern")
+            .add_error("regular expression is longer than the maximum length")
+            .add_attachment("maximum length", Self::MAXIMUM_REGEX_LENGTH.to_string())
+            .add_attachment("pattern", pattern)
+        );
+      }
+      Some(regex) => {
+        let regex = RegexBuilder::new(regex)
+          .size_limit(Self::REGEX_SIZE_LIMIT)
+          .build()
+          .map_err(|regex_error| {
+            GenericError::new("parsing a url pattern")
+              .add_error("regular expression is invalid")
+              .add_attachment("pattern", pattern)
+              .add_attachment("regex error", regex_error.to_string())
+          })?;
+
+        Some(regex)
+      }
+      None => None,
+    };
+
+    let mut source = host.as_ref().map_or("*", HostPattern::as_str).to_string();
+    if let Some(path) = &path {
+      source.push_str(path.as_str());
+    }
+    if let Some(query) = query_source {
+      source.push('?');
+      source.push_str(query);
+    }
+    if let Some(regex) = &regex {
+      source.push(' ');
+      source.push_str(regex.as_str());
+    }
+
+    Ok(Self { source, host, path, query, regex })
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.source
+  }
+
+  pub fn host(&self) -> Option<&HostPattern> {
+    self.host.as_ref()
+  }
+
+  pub fn path(&self) -> Option<&PathPattern> {
+    self.path.as_ref()
+  }
+
+  pub fn query(&self) -> &Vec<QueryConstraint> {
+    &self.query
+  }
+
+  pub fn regex(&self) -> Option<&str> {
+    self.regex.as_ref().map(Regex::as_str)
+  }
+
+  pub fn matches(&self, exchange: &Exchange) -> bool {
+    self.matches_parts(&UrlParts::of_exchange(exchange))
+  }
+
+  pub fn matches_url(&self, url: &Url) -> bool {
+    self.matches_parts(&UrlParts::of_url(url))
+  }
+
+  pub(super) fn matches_parts(&self, parts: &UrlParts) -> bool {
+    self.host.as_ref().is_none_or(|host| host.matches(&parts.host))
+      && self.matches_parts_besides_host(parts)
+  }
+
+  /// For matchers that already know the host matches.
+  pub(super) fn matches_parts_besides_host(&self, parts: &UrlParts) -> bool {
+    self.path.as_ref().is_none_or(|path| path.matches(&parts.path))
+      && self
+        .query
+        .iter()
+        .all(|constraint| parts.query.is_some_and(|query| constraint.matches(query)))
+      && self.regex.as_ref().is_none_or(|regex| regex.is_match(parts.url()))
+  }
+}
+
+impl PartialEq for UrlPattern {
+  fn eq(&self, other: &Self) -> bool {
+    self.source == other.source
+  }
+}
+
+impl Eq for UrlPattern {}
+
+impl Hash for UrlPattern {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.source.hash(state);
+  }
+}
+
+impl Serialize for UrlPattern {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    self.source.serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for UrlPattern {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    UrlPattern::parse(&String::deserialize(deserializer)?)
+      .map_err(|error| Error::custom(format!("{error:?}")))
+  }
+}
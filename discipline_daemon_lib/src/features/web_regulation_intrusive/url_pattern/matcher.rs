@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::ops::{Deref, Index, IndexMut};
+use aho_corasick::{AhoCorasick, MatchKind};
+use url::Url;
+use super::super::traffic::Exchange;
+use super::feature::{HostPattern, UrlPattern, UrlParts};
+
+// SECTION: Hosts.
+#[derive(Debug, Clone, Default)]
+struct HostTrieNode {
+  children: HashMap<String, HostTrieNode>,
+  /// The patterns naming exactly the host this node is at.
+  exact: Vec<usize>,
+  /// The `*.` patterns for the host this node is at.
+  suffix: Vec<usize>,
+}
+
+/// Host patterns compiled into a trie of their labels, last label first, so
+/// matching a host takes one lookup per label of it, however many patterns
+/// there are.
+#[derive(Debug, Clone, Default)]
+pub struct HostPatternSet {
+  root: HostTrieNode,
+}
+
+impl HostPatternSet {
+  pub fn new<'a>(patterns: impl IntoIterator<Item = &'a HostPattern>) -> Self {
+    let mut root = HostTrieNode::default();
+    for (index, pattern) in patterns.into_iter().enumerate() {
+      let mut node = &mut root;
+      for label in pattern.host().rsplit('.') {
+        node = node.children.entry(label.to_string()).or_default();
+      }
+
+      if pattern.is_suffix() {
+        node.suffix.push(index);
+      } else {
+        node.exact.push(index);
+      }
+    }
+
+    Self { root }
+  }
+
+  /// Calls `action` with the index of every pattern matching `host`, which
+  /// must be lowercase.
+  pub fn for_each_match(&self, host: &str, mut action: impl FnMut(usize)) {
+    let mut node = &self.root;
+    for label in host.rsplit('.') {
+      let Some(child) = node.children.get(label) else {
+        return;
+      };
+
+      node = child;
+      node.suffix.iter().copied().for_each(&mut action);
+    }
+
+    node.exact.iter().copied().for_each(action);
+  }
+
+  pub fn is_match(&self, host: &str) -> bool {
+    let mut is_match = false;
+    self.for_each_match(host, |_| is_match = true);
+    is_match
+  }
+}
+
+// SECTION: Urls.
+/// Url patterns compiled for matching many of them at once. Hosts are
+/// matched with a `HostPatternSet` and paths are searched for the literal
+/// text of every path pattern in a single pass, so only the patterns that
+/// can still match are checked in full.
+#[derive(Debug, Clone)]
+pub struct UrlPatternSet {
+  patterns: Vec<UrlPattern>,
+  hosts: HostPatternSet,
+  /// The index of the pattern of each host in `hosts`.
+  host_owners: Vec<usize>,
+  /// The patterns for any host.
+  any_host: Vec<usize>,
+  literals: Option<AhoCorasick>,
+  /// The index of the literal in `literals` each pattern's path must contain.
+  required_literals: Vec<Option<usize>>,
+}
+
+impl UrlPatternSet {
+  pub fn new(patterns: Vec<UrlPattern>) -> Self {
+    let mut host_owners = Vec::new();
+    let mut any_host = Vec::new();
+    for (index, pattern) in patterns.iter().enumerate() {
+      match pattern.host() {
+        Some(_) => host_owners.push(index),
+        None => any_host.push(index),
+      }
+    }
+
+    let hosts = HostPatternSet::new(patterns.iter().filter_map(UrlPattern::host));
+
+    let mut literals: Vec<&str> = Vec::new();
+    let mut literal_indices: HashMap<&str, usize> = HashMap::new();
+    let required_literals = patterns
+      .iter()
+      .map(|pattern| {
+        let literal = pattern.path()?.required_literal();
+        if literal.is_empty() {
+          return None;
+        }
+
+        Some(*literal_indices.entry(literal).or_insert_with(|| {
+          literals.push(literal);
+          literals.len() - 1
+        }))
+      })
+      .collect();
+
+    let literals = (!literals.is_empty()).then(|| {
+      AhoCorasick::builder()
+        .match_kind(MatchKind::Standard)
+        .build(&literals)
+        .expect("paths are far below the size limits of the automaton")
+    });
+
+    Self {
+      patterns,
+      hosts,
+      host_owners,
+      any_host,
+      literals,
+      required_literals,
+    }
+  }
+
+  pub fn patterns(&self) -> &Vec<UrlPattern> {
+    &self.patterns
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.patterns.is_empty()
+  }
+
+  /// The indices of the patterns matching the exchange's url, in order.
+  pub fn matching(&self, exchange: &Exchange) -> Vec<usize> {
+    self.matching_parts(&UrlParts::of_exchange(exchange))
+  }
+
+  pub fn matching_url(&self, url: &Url) -> Vec<usize> {
+    self.matching_parts(&UrlParts::of_url(url))
+  }
+
+  pub fn is_match(&self, exchange: &Exchange) -> bool {
+    !self.matching(exchange).is_empty()
+  }
+
+  pub fn is_match_url(&self, url: &Url) -> bool {
+    !self.matching_url(url).is_empty()
+  }
+
+  fn matching_parts(&self, parts: &UrlParts) -> Vec<usize> {
+    let mut candidates = vec![false; self.patterns.len()];
+    self.hosts.for_each_match(&parts.host, |index| candidates[self.host_owners[index]] = true);
+    for index in &self.any_host {
+      candidates[*index] = true;
+    }
+
+    let mut found_literals = Vec::new();
+    if let Some(literals) = &self.literals {
+      found_literals = vec![false; literals.patterns_len()];
+      let is_needed = candidates
+        .iter()
+        .zip(&self.required_literals)
+        .any(|(is_candidate, literal)| *is_candidate && literal.is_some());
+
+      if is_needed {
+        for found in literals.find_overlapping_iter(parts.path.as_ref()) {
+          found_literals[found.pattern().as_usize()] = true;
+        }
+      }
+    }
+
+    candidates
+      .iter()
+      .enumerate()
+      .filter(|(index, is_candidate)| {
+        **is_candidate
+          && self.required_literals[*index].is_none_or(|literal| found_literals[literal])
+          && self.patterns[*index].matches_parts_besides_host(parts)
+      })
+      .map(|(index, _)| index)
+      .collect()
+  }
+}
+
+// SECTION: Url scoped items.
+/// Something that only applies to the urls matching one of its patterns, or
+/// to every url if it has none.
+pub trait UrlScoped {
+  fn url_patterns(&self) -> &[UrlPattern];
+}
+
+/// Url scoped items with their patterns compiled into a `UrlPatternSet`,
+/// which is compiled again whenever an item is added or removed.
+#[derive(Debug, Clone)]
+pub struct UrlScopedList<T> {
+  items: Vec<T>,
+  matcher: UrlPatternSet,
+  /// The index of the item of each pattern in `matcher`.
+  pattern_owners: Vec<usize>,
+  /// The items without patterns.
+  unscoped: Vec<usize>,
+}
+
+impl<T: UrlScoped> UrlScopedList<T> {
+  pub fn new(items: Vec<T>) -> Self {
+    let mut list = Self {
+      items,
+      matcher: UrlPatternSet::new(Vec::new()),
+      pattern_owners: Vec::new(),
+      unscoped: Vec::new(),
+    };
+
+    list.compile();
+    list
+  }
+
+  fn compile(&mut self) {
+    let mut patterns = Vec::new();
+    self.pattern_owners.clear();
+    self.unscoped.clear();
+    for (index, item) in self.items.iter().enumerate() {
+      if item.url_patterns().is_empty() {
+        self.unscoped.push(index);
+      }
+
+      for pattern in item.url_patterns() {
+        patterns.push(pattern.clone());
+        self.pattern_owners.push(index);
+      }
+    }
+
+    self.matcher = UrlPatternSet::new(patterns);
+  }
+
+  pub fn push(&mut self, item: T) {
+    self.items.push(item);
+    self.compile();
+  }
+
+  pub fn remove(&mut self, index: usize) -> T {
+    let item = self.items.remove(index);
+    self.compile();
+    item
+  }
+
+  /// The items may be changed in place, here and by index, as long as their
+  /// patterns aren't.
+  pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+    self.items.iter_mut()
+  }
+
+  /// The indices of the items applying to the exchange's url, in order.
+  pub fn matching(&self, exchange: &Exchange) -> Vec<usize> {
+    self.owners(self.matcher.matching(exchange))
+  }
+
+  pub fn matching_url(&self, url: &Url) -> Vec<usize> {
+    self.owners(self.matcher.matching_url(url))
+  }
+
+  fn owners(&self, matching_patterns: Vec<usize>) -> Vec<usize> {
+    let mut owners: Vec<usize> = matching_patterns
+      .into_iter()
+      .map(|index| self.pattern_owners[index])
+      .chain(self.unscoped.iter().copied())
+      .collect();
+
+    owners.sort_unstable();
+    owners.dedup();
+    owners
+  }
+}
+
+impl<T> Deref for UrlScopedList<T> {
+  type Target = [T];
+
+  fn deref(&self) -> &[T] {
+    &self.items
+  }
+}
+
+impl<T> Index<usize> for UrlScopedList<T> {
+  type Output = T;
+
+  fn index(&self, index: usize) -> &T {
+    &self.items[index]
+  }
+}
+
+impl<T> IndexMut<usize> for UrlScopedList<T> {
+  fn index_mut(&mut self, index: usize) -> &mut T {
+    &mut self.items[index]
+  }
+}
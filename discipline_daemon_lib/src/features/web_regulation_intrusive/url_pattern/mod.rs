@@ -0,0 +1,16 @@
+pub mod feature;
+pub use feature::{
+  glob_matches,
+  normalize_path,
+  HostPattern,
+  PathPattern,
+  PathPrefix,
+  QueryConstraint,
+  UrlPattern,
+};
+
+pub mod matcher;
+pub use matcher::{HostPatternSet, UrlPatternSet, UrlScoped, UrlScopedList};
+
+#[cfg(test)]
+mod tests;
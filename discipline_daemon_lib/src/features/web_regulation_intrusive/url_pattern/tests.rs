@@ -0,0 +1,205 @@
+use url::Url;
+use super::super::no_intercept::NoInterceptHosts;
+use super::super::traffic::Exchange;
+use super::*;
+
+fn pattern(pattern: &str) -> UrlPattern {
+  UrlPattern::parse(pattern).unwrap()
+}
+
+#[test]
+fn parses_url_patterns() {
+  let parsed = pattern("  *.Example.COM./r/*/comments?sort=top&t  ^https:// ");
+  assert_eq!(parsed.as_str(), "*.example.com/r/*/comments?sort=top&t ^https://");
+  assert_eq!(parsed.host(), Some(&HostPattern::parse("*.example.com").unwrap()));
+  assert_eq!(parsed.path(), Some(&PathPattern::Glob("/r/*/comments".into())));
+  assert_eq!(parsed.query().len(), 2);
+  assert_eq!(parsed.query()[1].value(), None);
+  assert_eq!(parsed.regex(), Some("^https://"));
+
+  assert_eq!(pattern("*").host(), None);
+  assert_eq!(pattern("example.com/?").as_str(), "example.com/");
+  assert_eq!(pattern("example.com/watch").path(), Some(&PathPattern::Prefix(PathPrefix::new("/watch".into()).unwrap())));
+  assert_eq!(pattern("example.com?q=a%20b").query()[0].value(), Some("a b"));
+
+  for invalid in [
+    "",
+    "https://example.com/",
+    "exa%mple.com",
+    "*.*.example.com",
+    "example.com?&q",
+    "example.com?=value",
+    "example.com (",
+    "example.com\n/path",
+  ] {
+    assert!(UrlPattern::parse(invalid).is_err(), "{invalid:?} should be invalid");
+  }
+
+  let long_regex = format!("example.com {}", "a".repeat(UrlPattern::MAXIMUM_REGEX_LENGTH + 1));
+  assert!(UrlPattern::parse(&long_regex).is_err());
+  let many_parameters = format!("example.com?{}", vec!["a"; UrlPattern::MAXIMUM_QUERY_CONSTRAINTS + 1].join("&"));
+  assert!(UrlPattern::parse(&many_parameters).is_err());
+}
+
+#[test]
+fn normalizes_paths() {
+  assert_eq!(normalize_path("/r/foo?x"), "/r/foo?x");
+  assert_eq!(normalize_path("/r/%66oo"), "/r/foo");
+  assert_eq!(normalize_path("/%7Euser/%2e%2E/a%2fb%3f"), "/a%2Fb%3F");
+  assert_eq!(normalize_path("//r///foo//"), "/r/foo/");
+  assert_eq!(normalize_path("/a/./b/../../c"), "/c");
+  assert_eq!(normalize_path("/../.."), "/");
+  assert_eq!(normalize_path("/a/.."), "/");
+  assert_eq!(normalize_path("/50%/%zz/%"), "/50%/%zz/%");
+  assert_eq!(normalize_path("/caf%C3%A9/%e2%82%ac"), "/caf%C3%A9/%E2%82%AC");
+  assert_eq!(normalize_path("*"), "*");
+}
+
+#[test]
+fn matches_paths_however_they_are_written() {
+  let path_prefix = PathPrefix::new("/r/foo".into()).unwrap();
+  for path in ["/r/foo", "/r/%66oo", "//r/foo", "/r//foo/bar", "/r/x/../foo", "/%72/foo"] {
+    assert!(path_prefix.matches(path), "{path} should match");
+  }
+  assert!(!path_prefix.matches("/r/foo%2Fbar"));
+  assert!(!path_prefix.matches("/r/fo"));
+
+  assert_eq!(PathPrefix::new("//r/%66oo/".into()).unwrap().as_str(), "/r/foo/");
+  assert!(pattern("example.com/r/*/comments").matches(&Exchange::to("https://example.com//r/rust/%63omments")));
+
+  let set = UrlPatternSet::new(vec![pattern("example.com/r/foo"), pattern("*.example.com/watch?v")]);
+  assert_eq!(set.matching(&Exchange::to("https://example.com/r/%66oo")), vec![0]);
+  assert_eq!(set.matching(&Exchange::to("https://www.example.com//w%61tch?v=1")), vec![1]);
+}
+
+#[test]
+fn matches_globs() {
+  assert!(glob_matches("/r/*/comments/*", "/r/rust/comments/abc/title"));
+  assert!(glob_matches("*", ""));
+  assert!(glob_matches("/a*b*c", "/abxbc"));
+  assert!(glob_matches("/watch*", "/watch"));
+  assert!(!glob_matches("/a*b*c", "/abxbcd"));
+  assert!(!glob_matches("/r/*/comments", "/r/rust/comment"));
+  assert!(!glob_matches("/shorts", "/shorts/abc"));
+}
+
+#[test]
+fn matches_urls() {
+  let matches = |url_pattern: &str, url: &str| pattern(url_pattern).matches(&Exchange::to(url));
+
+  assert!(matches("example.com", "https://example.com/anything?at=all"));
+  assert!(!matches("example.com", "https://www.example.com/"));
+  assert!(matches("*.example.com", "https://example.com/"));
+  assert!(matches("*.example.com", "http://a.b.example.com:8080/"));
+  assert!(!matches("*.example.com", "https://notexample.com/"));
+  assert!(matches("*/ads", "https://anywhere.org/ads/banner.png"));
+
+  assert!(matches("example.com/watch", "https://example.com/watch/later"));
+  assert!(!matches("example.com/watch", "https://example.com/watchlist"));
+  assert!(matches("example.com/r/*/comments/*", "https://example.com/r/rust/comments/1"));
+  assert!(!matches("example.com/r/*/comments/*", "https://example.com/r/rust"));
+
+  assert!(matches("example.com/watch?v=abc", "https://example.com/watch?list=x&v=abc"));
+  assert!(!matches("example.com/watch?v=abc", "https://example.com/watch?v=abcd"));
+  assert!(!matches("example.com/watch?v=abc", "https://example.com/watch"));
+  assert!(matches("example.com?v", "https://example.com/?v="));
+  assert!(matches("example.com?q=*cats*", "https://example.com/search?q=funny+cats+video"));
+
+  assert!(matches("* ^http://", "http://example.com/"));
+  assert!(!matches("* ^http://", "https://example.com/"));
+  assert!(matches("* :8080/", "https://example.com:8080/"));
+
+  let url_pattern = pattern("*.example.com/page [=#]top$");
+  assert!(!url_pattern.matches_url(&Url::parse("https://www.example.com/page#top").unwrap()));
+  assert!(url_pattern.matches_url(&Url::parse("https://www.example.com/page?q=top#top").unwrap()));
+  assert!(pattern("*.example.com/page").matches_url(&Url::parse("https://WWW.Example.com./page").unwrap()));
+  assert!(!pattern("example.com").matches_url(&Url::parse("about:blank").unwrap()));
+  assert!(pattern("*").matches_url(&Url::parse("about:blank").unwrap()));
+}
+
+#[test]
+fn compiled_sets_match_like_their_patterns() {
+  let patterns: Vec<UrlPattern> = [
+    "example.com",
+    "*.example.com/watch",
+    "*.example.com/r/*/comments",
+    "*.co.uk",
+    "news.co.uk/sport?live",
+    "*/ads/*",
+    "* \\.pdf$",
+    "video.example.com/watch?v=*",
+  ]
+  .iter()
+  .map(|url_pattern| pattern(url_pattern))
+  .collect();
+
+  let set = UrlPatternSet::new(patterns.clone());
+  for url in [
+    "https://example.com/",
+    "https://example.com/watch",
+    "https://video.example.com/watch?v=1",
+    "https://video.example.com/watched",
+    "https://www.example.com/r/rust/comments",
+    "https://news.co.uk/sport/football?live=1",
+    "https://bbc.co.uk/ads/banner",
+    "https://other.org/ads",
+    "https://other.org/paper.pdf",
+    "https://co.uk.other.org/",
+  ] {
+    let exchange = Exchange::to(url);
+    let expected: Vec<usize> = (0..patterns.len())
+      .filter(|index| patterns[*index].matches(&exchange))
+      .collect();
+
+    assert_eq!(set.matching(&exchange), expected, "{url}");
+    assert_eq!(set.is_match(&exchange), !expected.is_empty(), "{url}");
+  }
+
+  assert_eq!(set.matching(&Exchange::to("https://video.example.com/watch?v=1")), vec![1, 7]);
+  assert!(!UrlPatternSet::new(Vec::new()).is_match(&Exchange::to("https://example.com/")));
+
+  let hosts = [
+    HostPattern::parse("*.example.com").unwrap(),
+    HostPattern::parse("www.example.com").unwrap(),
+  ];
+  let set = HostPatternSet::new(&hosts);
+  let mut matching = Vec::new();
+  set.for_each_match("www.example.com", |index| matching.push(index));
+  assert_eq!(matching, vec![0, 1]);
+  assert!(set.is_match("example.com"));
+  assert!(!set.is_match("com"));
+  assert!(!set.is_match("wwwexample.com"));
+}
+
+#[test]
+fn tunnels_hosts_that_refuse_interception() {
+  let mut no_intercept_hosts = NoInterceptHosts::new(vec![HostPattern::parse("bank.example").unwrap()]);
+  assert!(!no_intercept_hosts.is_intercepted("gateway.icloud.com."));
+  assert!(!no_intercept_hosts.is_intercepted("Bank.Example"));
+  assert!(no_intercept_hosts.is_intercepted("www.bank.example"));
+
+  no_intercept_hosts.add(HostPattern::parse("*.bank.example").unwrap());
+  assert!(!no_intercept_hosts.is_intercepted("www.bank.example"));
+  no_intercept_hosts.remove(&HostPattern::parse("*.bank.example").unwrap());
+  assert!(no_intercept_hosts.is_intercepted("www.bank.example"));
+}
+
+#[test]
+fn serializes_url_patterns() {
+  let url_pattern = pattern("*.example.com/watch?v=abc ^https://");
+  let json = serde_json::to_string(&url_pattern).unwrap();
+  assert_eq!(json, "\"*.example.com/watch?v=abc ^https://\"");
+  assert_eq!(serde_json::from_str::<UrlPattern>(&json).unwrap(), url_pattern);
+  assert!(serde_json::from_str::<UrlPattern>("\"example.com?=x\"").is_err());
+
+  let host_pattern = HostPattern::parse("*.Example.com").unwrap();
+  assert_eq!(serde_json::to_string(&host_pattern).unwrap(), "\"*.example.com\"");
+  assert_eq!(serde_json::from_str::<HostPattern>("\"*.example.com\"").unwrap(), host_pattern);
+  assert!(serde_json::from_str::<HostPattern>("\"exa mple.com\"").is_err());
+  assert!(serde_json::from_str::<HostPattern>("\"\"").is_err());
+
+  let path_prefix = PathPrefix::new("/watch".into()).unwrap();
+  assert_eq!(serde_json::to_string(&path_prefix).unwrap(), "\"/watch\"");
+  assert_eq!(serde_json::from_str::<PathPrefix>("\"/watch\"").unwrap(), path_prefix);
+  assert!(serde_json::from_str::<PathPrefix>("\"watch\"").is_err());
+}
@@ -0,0 +1,455 @@
+use std::sync::{Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+use crate::database::web_regulation_intrusive_view_time_allowance as allowance_db;
+use crate::operating_system_integration::UserId;
+use crate::{Daemon, Database, DateTime, Duration, GenericError, Uuid};
+use crate::api::block_pages::WebRuleFeature;
+use super::super::block_page;
+use super::super::content_security_policy;
+use super::super::traffic::{
+  BodyRewriter,
+  Exchange,
+  RequestVerdict,
+  Response,
+  ResponseHead,
+  ResponseVerdict,
+  TrafficHandler,
+};
+use super::super::url_pattern::{UrlPattern, UrlScoped, UrlScopedList};
+
+pub const MAXIMUM_ALLOWANCES_PER_USER: usize = 50;
+pub const MAXIMUM_URL_PATTERNS_PER_ALLOWANCE: usize = 100;
+
+/// The path, on every host, where the injected script reports view time.
+pub const HEARTBEAT_PATH: &str = "/.discipline/view-time";
+
+/// The most a single heartbeat may report. The script reports every 15
+/// seconds while the page is visible, so this only leaves room for slow
+/// timers.
+pub const MAXIMUM_SECONDS_PER_HEARTBEAT: u64 = 60;
+
+/// A daily budget of time a user may spend looking at pages of a group of
+/// url patterns. Time is counted by a script injected into the pages, and only
+/// while the page is visible.
+#[derive(Debug, Clone)]
+pub struct Allowance {
+  id: Uuid,
+  user_id: UserId,
+  url_patterns: Vec<UrlPattern>,
+  daily_allowance: Duration,
+  used_today: Duration,
+  /// Some moment of the day `used_today` belongs to.
+  day: DateTime,
+  /// When time was last credited. Kept in memory only, since its purpose is
+  /// keeping tabs open at the same time from counting the same time twice.
+  last_heartbeat: Option<DateTime>,
+}
+
+impl Allowance {
+  pub fn from_fields(
+    id: Uuid,
+    user_id: UserId,
+    url_patterns: Vec<UrlPattern>,
+    daily_allowance: Duration,
+    used_today: Duration,
+    day: DateTime,
+  ) -> Self {
+    Self {
+      id,
+      user_id,
+      url_patterns,
+      daily_allowance,
+      used_today,
+      day,
+      last_heartbeat: None,
+    }
+  }
+
+  pub fn id(&self) -> &Uuid {
+    &self.id
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn url_patterns(&self) -> &Vec<UrlPattern> {
+    &self.url_patterns
+  }
+
+  pub fn daily_allowance(&self) -> Duration {
+    self.daily_allowance
+  }
+
+  pub fn used_today(&self) -> Duration {
+    self.used_today
+  }
+
+  pub fn day(&self) -> DateTime {
+    self.day
+  }
+
+  /// Whether the allowance applies to an exchange one of its url patterns
+  /// matches.
+  pub fn applies_to(&self, exchange: &Exchange) -> bool {
+    exchange.user_id == Some(self.user_id)
+  }
+
+  /// Starts over with the full allowance once `now` is on another day than
+  /// the one the used time was counted on. Days are UTC days.
+  pub fn synchronize(&mut self, now: DateTime) {
+    let is_same_day = self.day.year() == now.year()
+      && self.day.month() == now.month()
+      && self.day.month_day() == now.month_day();
+
+    if !is_same_day {
+      self.used_today = Duration::ZERO;
+      self.day = now;
+    }
+  }
+
+  /// `synchronize` must be called first.
+  pub fn remaining(&self) -> Duration {
+    self
+      .daily_allowance
+      .checked_sub(&self.used_today)
+      .unwrap_or(Duration::ZERO)
+  }
+
+  /// `synchronize` must be called first.
+  pub fn is_used_up(&self) -> bool {
+    self.remaining().is_zero()
+  }
+
+  /// When the allowance starts over, at the end of the day the used time
+  /// was counted on. `synchronize` must be called first.
+  pub fn renews_at(&self) -> DateTime {
+    self
+      .day
+      .midnight()
+      .checked_add(&Duration::unchecked_from_days(1))
+      .unwrap_or(self.day)
+  }
+
+  /// Counts `seconds` of view time a page reported at `now`. No more is
+  /// counted than has passed since the previous heartbeat, give or take a
+  /// second, so pages open side by side don't use the allowance up faster.
+  /// `synchronize` must be called first.
+  pub fn record_heartbeat(&mut self, seconds: u64, now: DateTime) {
+    let mut seconds = seconds.min(MAXIMUM_SECONDS_PER_HEARTBEAT);
+    if let Some(last_heartbeat) = self.last_heartbeat {
+      let elapsed = now.since_or_zero(&last_heartbeat);
+      seconds = seconds.min(elapsed.total_milliseconds().div_ceil(1000) + 1);
+    }
+
+    if seconds == 0 {
+      return;
+    }
+
+    let credited = Duration::from_milliseconds(seconds * 1000);
+    self.used_today = self
+      .used_today
+      .checked_add(&credited)
+      .unwrap_or(self.daily_allowance)
+      .min(self.daily_allowance);
+    self.last_heartbeat = Some(now);
+  }
+}
+
+impl UrlScoped for Allowance {
+  fn url_patterns(&self) -> &[UrlPattern] {
+    &self.url_patterns
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AllowanceCreator {
+  pub id: Option<Uuid>,
+  pub url_patterns: Vec<String>,
+  pub daily_allowance: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AllowanceCreatorError {
+  NoUrlPatterns,
+  TooManyUrlPatterns { maximum: usize },
+  InvalidUrlPattern { url_pattern: String },
+  /// The allowance is shorter than a minute or longer than a day.
+  InvalidDailyAllowance,
+}
+
+impl AllowanceCreator {
+  pub fn create(self, user_id: UserId, now: DateTime) -> Result<Allowance, AllowanceCreatorError> {
+    if self.url_patterns.len() > MAXIMUM_URL_PATTERNS_PER_ALLOWANCE {
+      return Err(AllowanceCreatorError::TooManyUrlPatterns {
+        maximum: MAXIMUM_URL_PATTERNS_PER_ALLOWANCE,
+      });
+    }
+
+    let mut url_patterns: Vec<UrlPattern> = Vec::new();
+    for url_pattern in self.url_patterns {
+      let parsed = UrlPattern::parse(&url_pattern)
+        .map_err(|_| AllowanceCreatorError::InvalidUrlPattern { url_pattern })?;
+
+      if !url_patterns.iter().any(|other| other.as_str() == parsed.as_str()) {
+        url_patterns.push(parsed);
+      }
+    }
+
+    if url_patterns.is_empty() {
+      return Err(AllowanceCreatorError::NoUrlPatterns);
+    }
+
+    if self.daily_allowance < Duration::unchecked_from_minutes(1)
+      || self.daily_allowance > Duration::unchecked_from_days(1)
+    {
+      return Err(AllowanceCreatorError::InvalidDailyAllowance);
+    }
+
+    Ok(Allowance::from_fields(
+      self.id.unwrap_or_else(Uuid::new_v4),
+      user_id,
+      url_patterns,
+      self.daily_allowance,
+      Duration::ZERO,
+      now,
+    ))
+  }
+}
+
+// SECTION: Traffic handler.
+/// Injects a script into the pages with a view time allowance that shows
+/// the time left and reports, through heartbeats to `HEARTBEAT_PATH`, how
+/// long the page was visible. Once an allowance is used up, every request
+/// its url patterns match is blocked until the next day.
+///
+/// When several allowances cover a page, the one with the least time left
+/// decides.
+pub struct ViewTimeAllowances {
+  allowances: Mutex<UrlScopedList<Allowance>>,
+}
+
+impl ViewTimeAllowances {
+  pub fn open(database: &Database) -> Result<Self, GenericError> {
+    let allowances = allowance_db::retrieve_all_allowances(database)
+      .map_err(|error| error.change_context("opening the view time allowances"))?;
+
+    Ok(Self::new(allowances))
+  }
+
+  pub fn new(allowances: Vec<Allowance>) -> Self {
+    Self {
+      allowances: Mutex::new(UrlScopedList::new(allowances)),
+    }
+  }
+
+  pub fn allowances(&self) -> MutexGuard<'_, UrlScopedList<Allowance>> {
+    self.allowances.lock().unwrap()
+  }
+
+  /// The time left of the matching allowance with the least of it, or `None`
+  /// if no allowance covers `exchange`.
+  pub fn remaining(&self, exchange: &Exchange, now: DateTime) -> Option<Duration> {
+    let mut allowances = self.allowances();
+    let mut remaining = None;
+    for index in applying(&allowances, exchange) {
+      let allowance = &mut allowances[index];
+      allowance.synchronize(now);
+      remaining = Some(remaining.map_or(allowance.remaining(), |other: Duration| other.min(allowance.remaining())));
+    }
+
+    remaining
+  }
+
+  /// Credits `seconds` to every allowance covering the page the heartbeat
+  /// `exchange` reports on and returns the time left afterwards.
+  fn on_heartbeat(&self, daemon: &Daemon, exchange: &Exchange, seconds: u64) -> Option<Duration> {
+    let now = DateTime::now();
+    let page = heartbeat_page(exchange);
+    let mut allowances = self.allowances();
+
+    let mut remaining = None;
+    for index in applying(&allowances, &page) {
+      let allowance = &mut allowances[index];
+      allowance.synchronize(now);
+      let used_before = allowance.used_today();
+      allowance.record_heartbeat(seconds, now);
+
+      if allowance.used_today() != used_before {
+        if let Err(error) = allowance_db::update_usage(daemon.database(), allowance) {
+          daemon.internal_logger().log_error(
+            error.change_context("recording view time")
+          );
+        }
+      }
+
+      remaining = Some(remaining.map_or(allowance.remaining(), |other: Duration| other.min(allowance.remaining())));
+    }
+
+    remaining
+  }
+
+  /// The id of an allowance covering `exchange` that is used up.
+  fn used_up_allowance(&self, exchange: &Exchange, now: DateTime) -> Option<Uuid> {
+    let mut allowances = self.allowances();
+    for index in applying(&allowances, exchange) {
+      let allowance = &mut allowances[index];
+      allowance.synchronize(now);
+      if allowance.is_used_up() {
+        return Some(allowance.id);
+      }
+    }
+
+    None
+  }
+}
+
+/// The indexes of the allowances of `allowances` covering `exchange`.
+fn applying(allowances: &UrlScopedList<Allowance>, exchange: &Exchange) -> Vec<usize> {
+  let mut matching = allowances.matching(exchange);
+  matching.retain(|index| allowances[*index].applies_to(exchange));
+  matching
+}
+
+/// The heartbeat `exchange` as if it requested the page it reports on, whose
+/// path and query the script sends along in the `page` query parameter.
+fn heartbeat_page(exchange: &Exchange) -> Exchange {
+  let mut page = exchange.clone();
+  if let Some(target) = exchange.request.query_parameter("page") {
+    if target.starts_with('/') {
+      page.request.target = target;
+    }
+  }
+
+  page
+}
+
+impl TrafficHandler for ViewTimeAllowances {
+  fn may_inspect_response_body(&self, _daemon: &Daemon, exchange: &Exchange) -> bool {
+    exchange.is_top_level_navigation() && !applying(&self.allowances(), exchange).is_empty()
+  }
+
+  fn on_request(&self, daemon: &Daemon, exchange: &mut Exchange) -> RequestVerdict {
+    if exchange.request.path() == HEARTBEAT_PATH {
+      let seconds = exchange
+        .request
+        .query_parameter("seconds")
+        .and_then(|seconds| seconds.parse().ok())
+        .unwrap_or(0);
+
+      // Pages without an allowance are left alone, in case the path means
+      // something to the website.
+      if let Some(remaining) = self.on_heartbeat(daemon, exchange, seconds) {
+        return RequestVerdict::Respond(heartbeat_response(remaining));
+      }
+
+      return RequestVerdict::Forward;
+    }
+
+    match self.used_up_allowance(exchange, DateTime::now()) {
+      Some(allowance_id) => {
+        RequestVerdict::Respond(block_page::respond(
+          daemon.configuration().block_page_port(),
+          exchange,
+          WebRuleFeature::ViewTimeAllowance,
+          allowance_id,
+        ))
+      }
+      None => {
+        RequestVerdict::Forward
+      }
+    }
+  }
+
+  fn on_response(
+    &self,
+    _daemon: &Daemon,
+    exchange: &Exchange,
+    response: &mut ResponseHead,
+  ) -> ResponseVerdict {
+    if !exchange.is_top_level_navigation() || !is_html_page(response) {
+      return ResponseVerdict::Forward;
+    }
+
+    let Some(remaining) = self.remaining(exchange, DateTime::now()) else {
+      return ResponseVerdict::Forward;
+    };
+
+    content_security_policy::allow_inline_script(response, SCRIPT);
+    ResponseVerdict::RewriteBody(Box::new(ScriptInjector { remaining }))
+  }
+}
+
+fn is_html_page(response: &ResponseHead) -> bool {
+  let is_html_type = response
+    .content_type()
+    .is_some_and(|content_type| content_type == "text/html" || content_type == "application/xhtml+xml");
+
+  // The script is appended as ascii, which other encodings wouldn't read.
+  let is_utf_16 = response
+    .headers
+    .get("Content-Type")
+    .is_some_and(|value| value.to_ascii_lowercase().contains("utf-16"));
+
+  is_html_type && !is_utf_16 && response.status_code == 200
+}
+
+fn heartbeat_response(remaining: Duration) -> Response {
+  let mut head = ResponseHead::new(200, "OK");
+  head.headers.append("Content-Type", "application/json");
+  head.headers.append("Cache-Control", "no-store");
+
+  let body = format!("{{\"remaining\":{}}}", remaining.total_milliseconds());
+  Response::new(head, body.into_bytes())
+}
+
+/// Appends the view time script to the end of the page, which browsers
+/// treat as part of the body even after `</html>`.
+struct ScriptInjector {
+  remaining: Duration,
+}
+
+impl BodyRewriter for ScriptInjector {
+  fn write(&mut self, input: &[u8], output: &mut Vec<u8>) {
+    output.extend_from_slice(input);
+  }
+
+  fn end(&mut self, output: &mut Vec<u8>) {
+    let element = format!(
+      "<script data-remaining=\"{}\">{}</script>",
+      self.remaining.total_milliseconds(),
+      SCRIPT,
+    );
+    output.extend_from_slice(element.as_bytes());
+  }
+}
+
+/// Counts how long the page is visible, reports it every 15 seconds and
+/// when the page is hidden, and shows the time left in a badge. Once no
+/// time is left, the page is reloaded so the proxy blocks it. The badge
+/// lives in a closed shadow root so the page's styles and scripts can't
+/// easily reach it, and its text isn't an html string so it doesn't need
+/// an inline style allowance.
+static SCRIPT: &str = "(()=>{\
+const script=document.currentScript;\
+let remaining=Number(script.dataset.remaining),unreported=0,since=null,asked=false;\
+const badge=document.createElement('div');\
+Object.assign(badge.style,{position:'fixed',right:'12px',bottom:'12px',zIndex:'2147483647',\
+padding:'4px 10px',borderRadius:'999px',background:'rgba(24,24,27,.85)',color:'#fff',\
+font:'600 13px/1.6 system-ui,sans-serif',pointerEvents:'none'});\
+const host=document.createElement('div');\
+host.attachShadow({mode:'closed'}).append(badge);\
+document.documentElement.append(host);\
+const count=()=>{const now=performance.now();\
+if(since!==null){unreported+=now-since;remaining-=now-since}\
+since=document.visibilityState==='visible'?now:null};\
+const show=()=>{const s=Math.max(0,Math.ceil(remaining/1000)),m=Math.floor(s/60);\
+badge.textContent=(m>=60?Math.floor(m/60)+':'+String(m%60).padStart(2,'0'):m)+':'+String(s%60).padStart(2,'0')};\
+const report=()=>{count();const seconds=Math.floor(unreported/1000);unreported-=seconds*1000;\
+fetch('/.discipline/view-time?seconds='+seconds+'&page='+encodeURIComponent(location.pathname+location.search),{method:'POST',keepalive:true,cache:'no-store',credentials:'omit'})\
+.then(response=>response.json()).then(body=>{remaining=body.remaining;asked=false;show();\
+if(remaining<=0){location.reload()}}).catch(()=>{})};\
+document.addEventListener('visibilitychange',()=>{if(document.visibilityState==='visible'){count()}else{report()}});\
+setInterval(()=>{count();show();if(remaining<=0&&!asked){asked=true;report()}},1000);\
+setInterval(report,15000);\
+count();show()})()";
@@ -0,0 +1,14 @@
+pub mod feature;
+pub use feature::{
+  Allowance,
+  AllowanceCreator,
+  AllowanceCreatorError,
+  ViewTimeAllowances,
+  HEARTBEAT_PATH,
+  MAXIMUM_ALLOWANCES_PER_USER,
+  MAXIMUM_URL_PATTERNS_PER_ALLOWANCE,
+  MAXIMUM_SECONDS_PER_HEARTBEAT,
+};
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,133 @@
+use crate::operating_system_integration::UserId;
+use crate::{DateTime, Duration};
+use super::super::content_security_policy::allow_inline_script;
+use super::super::traffic::*;
+use super::*;
+
+fn allowance(url_patterns: &[&str], minutes: u64, now: DateTime) -> Allowance {
+  AllowanceCreator {
+    id: None,
+    url_patterns: url_patterns.iter().map(|pattern| pattern.to_string()).collect(),
+    daily_allowance: Duration::unchecked_from_minutes(minutes),
+  }
+  .create(UserId::new(1000), now)
+  .unwrap()
+}
+
+fn at(timestamp: i64) -> DateTime {
+  DateTime::from_timestamp(timestamp).unwrap()
+}
+
+#[test]
+fn creates_allowances() {
+  let creator = |url_patterns: Vec<&str>, daily_allowance| AllowanceCreator {
+    id: None,
+    url_patterns: url_patterns.into_iter().map(String::from).collect(),
+    daily_allowance,
+  };
+  let user_id = UserId::new(1000);
+  let now = DateTime::now();
+
+  assert!(matches!(
+    creator(vec![], Duration::unchecked_from_hours(1)).create(user_id, now),
+    Err(AllowanceCreatorError::NoUrlPatterns),
+  ));
+  assert!(matches!(
+    creator(vec!["example.com?=watch"], Duration::unchecked_from_hours(1)).create(user_id, now),
+    Err(AllowanceCreatorError::InvalidUrlPattern { .. }),
+  ));
+  assert!(matches!(
+    creator(vec!["example.com"], Duration::from_milliseconds(1000)).create(user_id, now),
+    Err(AllowanceCreatorError::InvalidDailyAllowance),
+  ));
+
+  let allowance = creator(vec!["*.example.com", "example.com/watch", "*.example.com"], Duration::unchecked_from_hours(1))
+    .create(user_id, now)
+    .unwrap();
+  assert_eq!(allowance.url_patterns().len(), 2);
+}
+
+#[test]
+fn covers_the_pages_of_its_url_patterns() {
+  let now = DateTime::now();
+  let allowances = ViewTimeAllowances::new(vec![
+    allowance(&["*.example.com/watch"], 60, now),
+  ]);
+
+  assert!(allowances.remaining(&Exchange::to("https://www.example.com/watch?v=1"), now).is_some());
+  assert!(allowances.remaining(&Exchange::to("https://www.example.com/"), now).is_none());
+  assert!(allowances.remaining(&Exchange::to("https://example.org/watch"), now).is_none());
+
+  let of_another_user = Exchange::to("https://www.example.com/watch").with_user_id(Some(UserId::new(1001)));
+  assert!(allowances.remaining(&of_another_user, now).is_none());
+}
+
+#[test]
+fn counts_heartbeats_within_elapsed_time() {
+  // 2025-01-01 12:00:00 UTC.
+  let noon = 1_735_732_800_000;
+  let mut allowance = allowance(&["example.com"], 5, at(noon));
+
+  allowance.record_heartbeat(15, at(noon));
+  assert_eq!(allowance.used_today(), Duration::from_milliseconds(15_000));
+
+  // Another tab reporting the same 15 seconds right away barely counts.
+  allowance.record_heartbeat(15, at(noon + 500));
+  assert_eq!(allowance.used_today(), Duration::from_milliseconds(17_000));
+
+  allowance.record_heartbeat(1000, at(noon + 600_000));
+  assert_eq!(allowance.used_today(), Duration::from_milliseconds(77_000));
+
+  for minute in 1..10 {
+    allowance.record_heartbeat(60, at(noon + 600_000 + minute * 60_000));
+  }
+  assert!(allowance.is_used_up());
+  assert_eq!(allowance.used_today(), allowance.daily_allowance());
+}
+
+#[test]
+fn starts_over_every_day() {
+  // 2025-01-01 23:59:00 UTC.
+  let late = 1_735_775_940_000;
+  let mut allowance = allowance(&["example.com"], 1, at(late));
+
+  allowance.record_heartbeat(60, at(late));
+  allowance.synchronize(at(late + 30_000));
+  assert!(allowance.is_used_up());
+
+  allowance.synchronize(at(late + 60_000));
+  assert_eq!(allowance.remaining(), Duration::unchecked_from_minutes(1));
+}
+
+#[test]
+fn allows_the_script_in_content_security_policies() {
+  let policy = |value: &str| {
+    let mut response = ResponseHead::new(200, "OK");
+    response.headers.append("Content-Security-Policy", value);
+    allow_inline_script(&mut response, "script");
+    response.headers.get("Content-Security-Policy").unwrap().to_string()
+  };
+
+  let hash = "'sha256-IaAnC39moeTCWTPxOh5aG7tHV1eAcpMMgYkTH5xqquE='";
+  assert_eq!(
+    policy("script-src 'self'; img-src *"),
+    format!("script-src 'self' {hash}; img-src *"),
+  );
+  assert_eq!(
+    policy("default-src 'none'"),
+    format!("default-src 'none'; script-src {hash}; connect-src 'self'"),
+  );
+  assert_eq!(
+    policy("script-src 'unsafe-inline'; connect-src *"),
+    "script-src 'unsafe-inline'; connect-src *",
+  );
+  assert_eq!(
+    policy("script-src 'nonce-abc' 'unsafe-inline'"),
+    format!("script-src 'nonce-abc' 'unsafe-inline' {hash}"),
+  );
+  assert_eq!(policy("frame-ancestors 'none'"), "frame-ancestors 'none'");
+
+  let mut response = ResponseHead::new(200, "OK");
+  allow_inline_script(&mut response, "script");
+  assert!(!response.headers.contains("Content-Security-Policy"));
+}
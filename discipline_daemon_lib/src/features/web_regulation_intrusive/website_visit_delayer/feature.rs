@@ -1,9 +1,264 @@
-/// When the user atemps to access a website, the delay blocks the website
-/// for the number of milliseconds specified in `blockFor`, then allows 
-/// accessing the website for the number of milliseconds specified in
-/// `allowFor`
-pub struct Feature {
-  pub from: DateTime<Utc>,
-  pub block_for: Duration,
-  pub allow_for: Duration,
-}
\ No newline at end of file
+use std::sync::{Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+use crate::database::web_regulation_intrusive_website_visit_delayer as delayer_db;
+use crate::operating_system_integration::UserId;
+use crate::{CountdownTimer, Daemon, Database, DateTime, Duration, GenericError, Uuid};
+use crate::api::block_pages::WebRuleFeature;
+use super::super::block_page;
+use super::super::traffic::{Exchange, RequestVerdict, TrafficHandler};
+use super::super::url_pattern::{UrlPattern, UrlScoped, UrlScopedList};
+
+pub const MAXIMUM_DELAYERS_PER_USER: usize = 100;
+pub const MINIMUM_BLOCK_FOR: Duration = Duration::from_milliseconds(5 * 1000);
+pub const MAXIMUM_BLOCK_FOR: Duration = Duration::unchecked_from_hours(1);
+pub const MINIMUM_ALLOW_FOR: Duration = Duration::unchecked_from_minutes(1);
+pub const MAXIMUM_ALLOW_FOR: Duration = Duration::unchecked_from_hours(24);
+
+/// Where a delayer is in its cycle.
+#[derive(Debug, Clone)]
+pub enum Phase {
+  /// Nobody tried to visit a matching page since the last cycle ended.
+  Idle,
+  /// Matching pages show a countdown until the timer finishes.
+  Blocking(CountdownTimer),
+  /// Matching pages are shown until the timer finishes.
+  Allowing(CountdownTimer),
+}
+
+/// When the user first opens a page matching a url pattern, blocks it for
+/// `block_for`, then allows it for `allow_for`. The cycle starts over with
+/// the next visit after that.
+#[derive(Debug, Clone)]
+pub struct Delayer {
+  id: Uuid,
+  user_id: UserId,
+  url_pattern: UrlPattern,
+  block_for: Duration,
+  allow_for: Duration,
+  phase: Phase,
+}
+
+impl Delayer {
+  pub fn from_fields(
+    id: Uuid,
+    user_id: UserId,
+    url_pattern: UrlPattern,
+    block_for: Duration,
+    allow_for: Duration,
+    phase: Phase,
+  ) -> Self {
+    Self {
+      id,
+      user_id,
+      url_pattern,
+      block_for,
+      allow_for,
+      phase,
+    }
+  }
+
+  pub fn id(&self) -> &Uuid {
+    &self.id
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn url_pattern(&self) -> &UrlPattern {
+    &self.url_pattern
+  }
+
+  pub fn block_for(&self) -> Duration {
+    self.block_for
+  }
+
+  pub fn allow_for(&self) -> Duration {
+    self.allow_for
+  }
+
+  pub fn phase(&self) -> &Phase {
+    &self.phase
+  }
+
+  /// Moves through the phases whose timers finished by `now`. The allowing
+  /// phase starts when the blocking one finishes, not when the user comes
+  /// back, so waiting once doesn't unlock the site indefinitely.
+  pub fn synchronize(&mut self, now: DateTime) {
+    if let Phase::Blocking(timer) = &mut self.phase {
+      let finish_time = timer.finish_time().unwrap_or(now);
+      timer.synchronize(now);
+
+      if timer.is_finished() {
+        let mut timer = CountdownTimer::new(self.allow_for, finish_time);
+        timer.synchronize(now);
+        self.phase = Phase::Allowing(timer);
+      }
+    }
+
+    if let Phase::Allowing(timer) = &mut self.phase {
+      timer.synchronize(now);
+
+      if timer.is_finished() {
+        self.phase = Phase::Idle;
+      }
+    }
+  }
+
+  /// Starts a new cycle if the delayer is idle. `synchronize` must be called
+  /// first. Returns whether a cycle was started.
+  pub fn start(&mut self, now: DateTime) -> bool {
+    if !matches!(self.phase, Phase::Idle) {
+      return false;
+    }
+
+    self.phase = Phase::Blocking(CountdownTimer::new(self.block_for, now));
+    true
+  }
+
+  /// How much longer matching pages are blocked, or `None` if they aren't.
+  /// `synchronize` must be called first.
+  pub fn remaining_block_duration(&self) -> Option<Duration> {
+    match &self.phase {
+      Phase::Blocking(timer) => {
+        Some(timer.remaining_duration())
+      }
+      Phase::Idle | Phase::Allowing(_) => {
+        None
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DelayerCreator {
+  pub id: Option<Uuid>,
+  pub url_pattern: String,
+  pub block_for: Duration,
+  pub allow_for: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DelayerCreatorError {
+  InvalidUrlPattern,
+  InvalidBlockFor,
+  InvalidAllowFor,
+}
+
+impl DelayerCreator {
+  pub fn create(self, user_id: UserId) -> Result<Delayer, DelayerCreatorError> {
+    let url_pattern = UrlPattern::parse(&self.url_pattern)
+      .map_err(|_| DelayerCreatorError::InvalidUrlPattern)?;
+
+    if self.block_for < MINIMUM_BLOCK_FOR || self.block_for > MAXIMUM_BLOCK_FOR {
+      return Err(DelayerCreatorError::InvalidBlockFor);
+    }
+
+    if self.allow_for < MINIMUM_ALLOW_FOR || self.allow_for > MAXIMUM_ALLOW_FOR {
+      return Err(DelayerCreatorError::InvalidAllowFor);
+    }
+
+    Ok(Delayer::from_fields(
+      self.id.unwrap_or_else(Uuid::new_v4),
+      user_id,
+      url_pattern,
+      self.block_for,
+      self.allow_for,
+      Phase::Idle,
+    ))
+  }
+}
+
+impl UrlScoped for Delayer {
+  fn url_patterns(&self) -> &[UrlPattern] {
+    std::slice::from_ref(&self.url_pattern)
+  }
+}
+
+// SECTION: Traffic handler.
+/// Sends pages matching a delayer to the block page, which opens them once
+/// the delayer lets the user through. Only top-level navigations are delayed.
+pub struct WebsiteVisitDelayer {
+  delayers: Mutex<UrlScopedList<Delayer>>,
+}
+
+impl WebsiteVisitDelayer {
+  pub fn open(database: &Database) -> Result<Self, GenericError> {
+    let delayers = delayer_db::retrieve_all_delayers(database)
+      .map_err(|error| error.change_context("opening the website visit delayer"))?;
+
+    Ok(Self::new(delayers))
+  }
+
+  pub fn new(delayers: Vec<Delayer>) -> Self {
+    Self {
+      delayers: Mutex::new(UrlScopedList::new(delayers)),
+    }
+  }
+
+  pub fn delayers(&self) -> MutexGuard<'_, UrlScopedList<Delayer>> {
+    self.delayers.lock().unwrap()
+  }
+
+  /// Moves the delayers the exchange matches through their cycles as of
+  /// `now`, starting the idle ones. Returns the delayers that started a
+  /// cycle, so they can be stored, and the id of the one blocking the
+  /// longest if any of them is still blocking.
+  pub fn visit(&self, exchange: &Exchange, now: DateTime) -> (Vec<Delayer>, Option<Uuid>) {
+    if !exchange.is_top_level_navigation() {
+      return (Vec::new(), None);
+    }
+
+    let mut delayers = self.delayers();
+    let mut started = Vec::new();
+    let mut longest_blocking: Option<(Uuid, Duration)> = None;
+
+    for index in delayers.matching(exchange) {
+      let delayer = &mut delayers[index];
+      if exchange.user_id != Some(delayer.user_id) {
+        continue;
+      }
+
+      delayer.synchronize(now);
+      if delayer.start(now) {
+        started.push(delayer.clone());
+      }
+
+      if let Some(remaining) = delayer.remaining_block_duration() {
+        if longest_blocking.is_none_or(|(_, longest)| remaining > longest) {
+          longest_blocking = Some((delayer.id, remaining));
+        }
+      }
+    }
+
+    (started, longest_blocking.map(|(delayer_id, _)| delayer_id))
+  }
+}
+
+impl TrafficHandler for WebsiteVisitDelayer {
+  fn on_request(&self, daemon: &Daemon, exchange: &mut Exchange) -> RequestVerdict {
+    let (started, blocking_delayer_id) = self.visit(exchange, DateTime::now());
+
+    for delayer in started {
+      if let Err(error) = delayer_db::update_phase(daemon.database(), &delayer) {
+        daemon.internal_logger().log_error(
+          error.change_context("starting a website visit delay")
+        );
+      }
+    }
+
+    match blocking_delayer_id {
+      Some(delayer_id) => {
+        RequestVerdict::Respond(block_page::respond(
+          daemon.configuration().block_page_port(),
+          exchange,
+          WebRuleFeature::WebsiteVisitDelayer,
+          delayer_id,
+        ))
+      }
+      None => {
+        RequestVerdict::Forward
+      }
+    }
+  }
+}
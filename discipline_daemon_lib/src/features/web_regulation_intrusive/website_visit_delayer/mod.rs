@@ -0,0 +1,12 @@
+pub mod feature;
+pub use feature::{
+  Delayer,
+  DelayerCreator,
+  DelayerCreatorError,
+  Phase,
+  WebsiteVisitDelayer,
+  MAXIMUM_DELAYERS_PER_USER,
+};
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,115 @@
+use crate::operating_system_integration::UserId;
+use crate::{Duration, Uuid};
+use super::super::traffic::*;
+use super::*;
+
+/// Blocks `*.example.com/watch` for 2 minutes, then allows it for 10.
+fn delayer() -> Delayer {
+  DelayerCreator {
+    id: None,
+    url_pattern: "*.example.com/watch".into(),
+    block_for: Duration::unchecked_from_minutes(2),
+    allow_for: Duration::unchecked_from_minutes(10),
+  }.create(UserId::new(1000)).unwrap()
+}
+
+/// How long the only delayer of `delayers` still blocks, once `visit`
+/// returned it as the blocking one.
+fn countdown(delayers: &WebsiteVisitDelayer, blocking_delayer_id: Option<Uuid>) -> Duration {
+  let delayer = delayers.delayers()[0].clone();
+  assert_eq!(blocking_delayer_id, Some(*delayer.id()));
+  delayer.remaining_block_duration().unwrap()
+}
+
+#[test]
+fn validates_delayers() {
+  let creator = |url_pattern: &str, block_for, allow_for| DelayerCreator {
+    id: None,
+    url_pattern: url_pattern.into(),
+    block_for,
+    allow_for,
+  };
+  let minutes = Duration::unchecked_from_minutes;
+
+  assert!(creator("example.com", minutes(1), minutes(1)).create(UserId::new(1000)).is_ok());
+  assert!(matches!(
+    creator("example.com?=watch", minutes(1), minutes(1)).create(UserId::new(1000)),
+    Err(DelayerCreatorError::InvalidUrlPattern),
+  ));
+  assert!(matches!(
+    creator("example.com", Duration::from_milliseconds(1000), minutes(1)).create(UserId::new(1000)),
+    Err(DelayerCreatorError::InvalidBlockFor),
+  ));
+  assert!(matches!(
+    creator("example.com", minutes(1), Duration::unchecked_from_hours(25)).create(UserId::new(1000)),
+    Err(DelayerCreatorError::InvalidAllowFor),
+  ));
+}
+
+#[test]
+fn blocks_then_allows_then_starts_over() {
+  let delayers = WebsiteVisitDelayer::new(vec![delayer()]);
+  let visit = |minute| delayers.visit(&Exchange::navigation_to("https://www.example.com/watch?v=1"), at_minute(minute));
+
+  // The first visit starts a cycle, which has to be stored.
+  let (started, blocking_delayer_id) = visit(0);
+  assert_eq!(started.len(), 1);
+  assert!(matches!(started[0].phase(), Phase::Blocking(_)));
+  assert_eq!(countdown(&delayers, blocking_delayer_id), Duration::unchecked_from_minutes(2));
+
+  // Coming back doesn't restart the countdown.
+  let (started, blocking_delayer_id) = visit(1);
+  assert!(started.is_empty());
+  assert_eq!(countdown(&delayers, blocking_delayer_id), Duration::unchecked_from_minutes(1));
+
+  let (started, blocking_delayer_id) = visit(2);
+  assert!(started.is_empty());
+  assert!(blocking_delayer_id.is_none());
+  assert!(matches!(delayers.delayers()[0].phase(), Phase::Allowing(_)));
+  assert!(visit(11).1.is_none());
+
+  // The allowing phase ran from minute 2 to 12, so this is a new cycle.
+  let (started, blocking_delayer_id) = visit(12);
+  assert_eq!(started.len(), 1);
+  assert_eq!(countdown(&delayers, blocking_delayer_id), Duration::unchecked_from_minutes(2));
+}
+
+#[test]
+fn allows_from_when_the_block_ended() {
+  let delayers = WebsiteVisitDelayer::new(vec![delayer()]);
+  let visit = |minute| delayers.visit(&Exchange::navigation_to("https://example.com/watch"), at_minute(minute));
+
+  assert!(visit(0).1.is_some());
+
+  // Waiting out the countdown somewhere else still used up the allowance.
+  assert!(visit(8).1.is_none());
+  let mut delayer = delayers.delayers()[0].clone();
+  let Phase::Allowing(timer) = delayer.phase() else {
+    panic!("expected the delayer to allow");
+  };
+  assert_eq!(timer.remaining_duration(), Duration::unchecked_from_minutes(4));
+
+  delayer.synchronize(at_minute(30));
+  assert!(matches!(delayer.phase(), Phase::Idle));
+}
+
+#[test]
+fn only_delays_matching_navigations() {
+  let delayers = WebsiteVisitDelayer::new(vec![delayer()]);
+
+  for exchange in [
+    Exchange::navigation_to("https://www.example.com/feed"),
+    Exchange::navigation_to("https://other.org/watch"),
+    Exchange::to("https://www.example.com/watch").fetching("image"),
+    Exchange::to("https://www.example.com/watch").fetching("iframe"),
+  ] {
+    let (started, blocking_delayer_id) = delayers.visit(&exchange, at_minute(0));
+    assert!(started.is_empty());
+    assert!(blocking_delayer_id.is_none());
+  }
+
+  let other_user = Exchange::navigation_to("https://example.com/watch").with_user_id(Some(UserId::new(1001)));
+  assert!(delayers.visit(&other_user, at_minute(0)).1.is_none());
+
+  assert!(matches!(delayers.delayers()[0].phase(), Phase::Idle));
+}
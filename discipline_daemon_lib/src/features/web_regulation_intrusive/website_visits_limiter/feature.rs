@@ -1,43 +1,267 @@
-/** 
- * Given a period of time starting at `this.#start` and ending at `this.#start + this.#length`,
- * the session limits how many times you may access the website within that period.
-*/
-pub struct Feature {
-  pub from: DateTime<Utc>,
-  pub max_allowed_visits: u64,
-  pub duration: Duration,
-  pub visits_counter: u64,
-  pub rule_id: RuleId,
-
-
-  // constructor(range: AccessRuleRange, initializer: Initializer) {
-  //   this.#range = range
-  //   this.#start = initializer.start ?? now()
-  //   this.#limit = initializer.limit ?? 100
-  //   this.#length = initializer.length ?? 1000 * 60 * 60
-  //   this.#visitsCount = initializer.visitsCount ?? 0
-  // }
-}
-
-pub struct HttpFeatureApplyArg {
-
-}
-
-impl Feature {
-  pub fn apply(&self, arg: HttpFeatureApplyArg) {
-    // if (this.#start + this.#length >= timestamp) {
-    //   this.#start = timestamp
-    //   this.#visitsCount = 0
-    // }
-
-    // if (this.#visitsCount >= this.#limit) {
-    //   // TODO: Throw a more descriptive message which should include: 
-    //   //  - The title of the owner rule
-    //   //  - The name, id, index of the owner range
-    //   //  - The url that to which access is denied
-    //   throw new Error("Access Denied: reached your maximum number of visits for this session")
-    // }
-
-    // this.#visitsCount++
-  }
-}
\ No newline at end of file
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+use crate::database::web_regulation_intrusive_website_visits_limiter as limiter_db;
+use crate::operating_system_integration::UserId;
+use crate::{Daemon, Database, DateTime, Duration, GenericError, Uuid};
+use crate::api::block_pages::WebRuleFeature;
+use super::super::block_page;
+use super::super::traffic::{Exchange, RequestVerdict, TrafficHandler};
+use super::super::url_pattern::{UrlPattern, UrlScoped, UrlScopedList};
+
+pub const MAXIMUM_LIMITERS_PER_USER: usize = 100;
+pub const MAXIMUM_VISITS_PER_WINDOW: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WindowKind {
+  /// No more than the allowed number of visits within any span of time as
+  /// long as the window. Each visit becomes available again once it's a
+  /// window old.
+  Rolling,
+  /// The window opens with the first visit and all visits become available
+  /// again once it closes.
+  Fixed,
+}
+
+/// Limits how many times a user may open pages matching a url pattern
+/// within a window of time.
+#[derive(Debug, Clone)]
+pub struct Limiter {
+  id: Uuid,
+  user_id: UserId,
+  url_pattern: UrlPattern,
+  maximum_visits: u32,
+  window: Duration,
+  window_kind: WindowKind,
+  /// When the visits that count against the limit happened, oldest first.
+  visits: VecDeque<DateTime>,
+}
+
+impl Limiter {
+  pub fn from_fields(
+    id: Uuid,
+    user_id: UserId,
+    url_pattern: UrlPattern,
+    maximum_visits: u32,
+    window: Duration,
+    window_kind: WindowKind,
+    visits: VecDeque<DateTime>,
+  ) -> Self {
+    Self {
+      id,
+      user_id,
+      url_pattern,
+      maximum_visits,
+      window,
+      window_kind,
+      visits,
+    }
+  }
+
+  pub fn id(&self) -> &Uuid {
+    &self.id
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn url_pattern(&self) -> &UrlPattern {
+    &self.url_pattern
+  }
+
+  pub fn maximum_visits(&self) -> u32 {
+    self.maximum_visits
+  }
+
+  pub fn window(&self) -> Duration {
+    self.window
+  }
+
+  pub fn window_kind(&self) -> WindowKind {
+    self.window_kind
+  }
+
+  pub fn visits(&self) -> &VecDeque<DateTime> {
+    &self.visits
+  }
+
+  fn visit_expires_at(&self, visit: &DateTime) -> DateTime {
+    // Windows are at most a week long, so this only falls back for corrupt data.
+    visit.checked_add(&self.window).unwrap_or(*visit)
+  }
+
+  /// Forgets visits that no longer count against the limit.
+  pub fn forget_expired_visits(&mut self, now: DateTime) {
+    match self.window_kind {
+      WindowKind::Rolling => {
+        while let Some(visit) = self.visits.front() {
+          if self.visit_expires_at(visit) > now {
+            break;
+          }
+          self.visits.pop_front();
+        }
+      }
+      WindowKind::Fixed => {
+        if let Some(visit) = self.visits.front() {
+          if self.visit_expires_at(visit) <= now {
+            self.visits.clear();
+          }
+        }
+      }
+    }
+  }
+
+  /// `forget_expired_visits` must be called first.
+  pub fn remaining_visits(&self) -> u32 {
+    self.maximum_visits.saturating_sub(self.visits.len() as u32)
+  }
+
+  /// When the next visit becomes available, or `None` if one is available
+  /// now. `forget_expired_visits` must be called first.
+  pub fn next_visit_available_at(&self) -> Option<DateTime> {
+    if self.remaining_visits() > 0 {
+      return None;
+    }
+
+    self.visits.front().map(|visit| self.visit_expires_at(visit))
+  }
+
+  pub fn record_visit(&mut self, now: DateTime) {
+    self.visits.push_back(now);
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LimiterCreator {
+  pub id: Option<Uuid>,
+  pub url_pattern: String,
+  pub maximum_visits: u32,
+  pub window: Duration,
+  pub window_kind: WindowKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LimiterCreatorError {
+  InvalidUrlPattern,
+  InvalidMaximumVisits,
+  InvalidWindow,
+}
+
+impl LimiterCreator {
+  pub fn create(self, user_id: UserId) -> Result<Limiter, LimiterCreatorError> {
+    let url_pattern = UrlPattern::parse(&self.url_pattern)
+      .map_err(|_| LimiterCreatorError::InvalidUrlPattern)?;
+
+    if self.maximum_visits == 0 || self.maximum_visits > MAXIMUM_VISITS_PER_WINDOW {
+      return Err(LimiterCreatorError::InvalidMaximumVisits);
+    }
+
+    if self.window < Duration::unchecked_from_minutes(1) || self.window > Duration::new_week() {
+      return Err(LimiterCreatorError::InvalidWindow);
+    }
+
+    Ok(Limiter::from_fields(
+      self.id.unwrap_or_else(Uuid::new_v4),
+      user_id,
+      url_pattern,
+      self.maximum_visits,
+      self.window,
+      self.window_kind,
+      VecDeque::new(),
+    ))
+  }
+}
+
+impl UrlScoped for Limiter {
+  fn url_patterns(&self) -> &[UrlPattern] {
+    std::slice::from_ref(&self.url_pattern)
+  }
+}
+
+// SECTION: Traffic handler.
+/// Counts the pages users open and blocks those matching a limiter once its
+/// visits are used up. Only top-level navigations count as visits, so the
+/// images, scripts and background requests of a page don't.
+pub struct WebsiteVisitsLimiter {
+  limiters: Mutex<UrlScopedList<Limiter>>,
+}
+
+impl WebsiteVisitsLimiter {
+  pub fn open(database: &Database) -> Result<Self, GenericError> {
+    let limiters = limiter_db::retrieve_all_limiters(database)
+      .map_err(|error| error.change_context("opening the website visits limiter"))?;
+
+    Ok(Self::new(limiters))
+  }
+
+  pub fn new(limiters: Vec<Limiter>) -> Self {
+    Self {
+      limiters: Mutex::new(UrlScopedList::new(limiters)),
+    }
+  }
+
+  pub fn limiters(&self) -> MutexGuard<'_, UrlScopedList<Limiter>> {
+    self.limiters.lock().unwrap()
+  }
+
+  /// Counts the exchange as a visit to every limiter it matches as of
+  /// `now`, returning the limiters it was counted against so they can be
+  /// stored, or the id of one of them that has no visits left, in which
+  /// case nothing is counted.
+  pub fn visit(&self, exchange: &Exchange, now: DateTime) -> Result<Vec<Limiter>, Uuid> {
+    if !exchange.is_top_level_navigation() {
+      return Ok(Vec::new());
+    }
+
+    let mut limiters = self.limiters();
+
+    let mut matching = limiters.matching(exchange);
+    matching.retain(|index| exchange.user_id == Some(limiters[*index].user_id));
+    for index in &matching {
+      let limiter = &mut limiters[*index];
+      limiter.forget_expired_visits(now);
+      if limiter.next_visit_available_at().is_some() {
+        return Err(limiter.id);
+      }
+    }
+
+    Ok(
+      matching
+        .into_iter()
+        .map(|index| {
+          let limiter = &mut limiters[index];
+          limiter.record_visit(now);
+          limiter.clone()
+        })
+        .collect()
+    )
+  }
+}
+
+impl TrafficHandler for WebsiteVisitsLimiter {
+  fn on_request(&self, daemon: &Daemon, exchange: &mut Exchange) -> RequestVerdict {
+    let now = DateTime::now();
+    let visited = match self.visit(exchange, now) {
+      Ok(visited) => visited,
+      Err(limiter_id) => {
+        return RequestVerdict::Respond(block_page::respond(
+          daemon.configuration().block_page_port(),
+          exchange,
+          WebRuleFeature::WebsiteVisitsLimiter,
+          limiter_id,
+        ));
+      }
+    };
+
+    for limiter in visited {
+      if let Err(error) = limiter_db::record_visit(daemon.database(), &limiter, now) {
+        daemon.internal_logger().log_error(
+          error.change_context("recording a website visit")
+        );
+      }
+    }
+
+    RequestVerdict::Forward
+  }
+}
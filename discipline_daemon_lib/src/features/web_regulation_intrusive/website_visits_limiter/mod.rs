@@ -0,0 +1,12 @@
+pub mod feature;
+pub use feature::{
+  Limiter,
+  LimiterCreator,
+  LimiterCreatorError,
+  WebsiteVisitsLimiter,
+  WindowKind,
+  MAXIMUM_LIMITERS_PER_USER,
+};
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,113 @@
+use crate::operating_system_integration::UserId;
+use crate::Duration;
+use super::super::traffic::*;
+use super::*;
+
+fn limiter(url_pattern: &str, window_kind: WindowKind) -> Limiter {
+  LimiterCreator {
+    id: None,
+    url_pattern: url_pattern.into(),
+    maximum_visits: 2,
+    window: Duration::unchecked_from_minutes(10),
+    window_kind,
+  }.create(UserId::new(1000)).unwrap()
+}
+
+#[test]
+fn validates_limiters() {
+  let creator = |maximum_visits, window| LimiterCreator {
+    id: None,
+    url_pattern: "example.com".into(),
+    maximum_visits,
+    window,
+    window_kind: WindowKind::Rolling,
+  };
+
+  assert!(creator(1, Duration::unchecked_from_minutes(1)).create(UserId::new(1000)).is_ok());
+  assert!(matches!(
+    creator(0, Duration::unchecked_from_minutes(1)).create(UserId::new(1000)),
+    Err(LimiterCreatorError::InvalidMaximumVisits),
+  ));
+  assert!(matches!(
+    creator(1, Duration::unchecked_from_minutes(1).checked_add(&Duration::new_week()).unwrap()).create(UserId::new(1000)),
+    Err(LimiterCreatorError::InvalidWindow),
+  ));
+
+  let mut invalid_url_pattern = creator(1, Duration::unchecked_from_minutes(1));
+  invalid_url_pattern.url_pattern = "example.com?=watch".into();
+  assert!(matches!(
+    invalid_url_pattern.create(UserId::new(1000)),
+    Err(LimiterCreatorError::InvalidUrlPattern),
+  ));
+}
+
+#[test]
+fn frees_visits_one_by_one_in_rolling_windows() {
+  let mut limiter = limiter("*.example.com", WindowKind::Rolling);
+  limiter.record_visit(at_minute(0));
+  limiter.record_visit(at_minute(4));
+
+  limiter.forget_expired_visits(at_minute(9));
+  assert_eq!(limiter.remaining_visits(), 0);
+  assert_eq!(limiter.next_visit_available_at(), Some(at_minute(10)));
+
+  limiter.forget_expired_visits(at_minute(10));
+  assert_eq!(limiter.remaining_visits(), 1);
+  assert_eq!(limiter.next_visit_available_at(), None);
+  assert_eq!(limiter.visits().iter().copied().collect::<Vec<_>>(), vec![at_minute(4)]);
+
+  limiter.forget_expired_visits(at_minute(14));
+  assert_eq!(limiter.remaining_visits(), 2);
+}
+
+#[test]
+fn frees_all_visits_when_fixed_windows_close() {
+  let mut limiter = limiter("*.example.com", WindowKind::Fixed);
+  limiter.record_visit(at_minute(0));
+  limiter.record_visit(at_minute(9));
+
+  limiter.forget_expired_visits(at_minute(9));
+  assert_eq!(limiter.next_visit_available_at(), Some(at_minute(10)));
+
+  limiter.forget_expired_visits(at_minute(10));
+  assert_eq!(limiter.remaining_visits(), 2);
+  assert!(limiter.visits().is_empty());
+}
+
+#[test]
+fn blocks_navigations_once_visits_are_used_up() {
+  let limiters = WebsiteVisitsLimiter::new(vec![limiter("*.example.com/watch", WindowKind::Rolling)]);
+  let visit = |url: &str, minute| limiters.visit(&Exchange::navigation_to(url), at_minute(minute));
+
+  assert_eq!(visit("https://www.example.com/watch?v=1", 0).unwrap().len(), 1);
+  assert_eq!(visit("https://example.com/watch/later", 1).unwrap().len(), 1);
+
+  // Pages the limiter doesn't match are neither blocked nor counted.
+  assert!(visit("https://www.example.com/feed", 2).unwrap().is_empty());
+  assert!(visit("https://other.org/watch", 2).unwrap().is_empty());
+  let other_user = Exchange::navigation_to("https://example.com/watch").with_user_id(Some(UserId::new(1001)));
+  assert!(limiters.visit(&other_user, at_minute(2)).unwrap().is_empty());
+
+  assert_eq!(visit("https://example.com/watch?v=2", 2).unwrap_err(), *limiters.limiters()[0].id());
+  assert_eq!(limiters.limiters()[0].visits().len(), 2);
+
+  assert_eq!(visit("https://example.com/watch?v=2", 10).unwrap().len(), 1);
+}
+
+#[test]
+fn doesnt_count_subresources() {
+  let limiters = WebsiteVisitsLimiter::new(vec![limiter("*.example.com", WindowKind::Fixed)]);
+
+  for destination in ["image", "script", "empty", "iframe"] {
+    let subresource = Exchange::to("https://www.example.com/").fetching(destination);
+    assert!(limiters.visit(&subresource, at_minute(0)).unwrap().is_empty());
+  }
+  assert!(limiters.limiters()[0].visits().is_empty());
+
+  limiters.visit(&Exchange::navigation_to("https://www.example.com/"), at_minute(0)).unwrap();
+  limiters.visit(&Exchange::navigation_to("https://www.example.com/"), at_minute(1)).unwrap();
+
+  // Even once the limit is reached, the page's resources still load.
+  assert!(limiters.visit(&Exchange::to("https://www.example.com/app.js").fetching("script"), at_minute(2)).is_ok());
+  assert!(limiters.visit(&Exchange::navigation_to("https://www.example.com/"), at_minute(2)).is_err());
+}
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+/// The categories YouTube lets uploaders pick for their videos, numbered as
+/// in YouTube's data api.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CategoryID {
+  FilmAndAnimation = 1,
+  AutosAndVehicles = 2,
+  Music = 10,
+  PetsAndAnimals = 15,
+  Sports = 17,
+  TravelAndEvents = 19,
+  Gaming = 20,
+  PeopleAndBlogs = 22,
+  Comedy = 23,
+  Entertainment = 24,
+  NewsAndPolitics = 25,
+  HowToAndStyle = 26,
+  Education = 27,
+  ScienceAndTechnology = 28,
+  NonprofitsAndActivism = 29,
+}
+
+impl CategoryID {
+  pub const ALL: [CategoryID; 15] = [
+    CategoryID::FilmAndAnimation,
+    CategoryID::AutosAndVehicles,
+    CategoryID::Music,
+    CategoryID::PetsAndAnimals,
+    CategoryID::Sports,
+    CategoryID::TravelAndEvents,
+    CategoryID::Gaming,
+    CategoryID::PeopleAndBlogs,
+    CategoryID::Comedy,
+    CategoryID::Entertainment,
+    CategoryID::NewsAndPolitics,
+    CategoryID::HowToAndStyle,
+    CategoryID::Education,
+    CategoryID::ScienceAndTechnology,
+    CategoryID::NonprofitsAndActivism,
+  ];
+
+  pub fn number(self) -> u8 {
+    self as u8
+  }
+
+  pub fn from_number(number: u8) -> Option<Self> {
+    Self::ALL
+      .into_iter()
+      .find(|category| category.number() == number)
+  }
+
+  /// The English name YouTube shows for the category, which is also what
+  /// watch pages and player responses call it.
+  pub fn name(self) -> &'static str {
+    match self {
+      CategoryID::FilmAndAnimation => "Film & Animation",
+      CategoryID::AutosAndVehicles => "Autos & Vehicles",
+      CategoryID::Music => "Music",
+      CategoryID::PetsAndAnimals => "Pets & Animals",
+      CategoryID::Sports => "Sports",
+      CategoryID::TravelAndEvents => "Travel & Events",
+      CategoryID::Gaming => "Gaming",
+      CategoryID::PeopleAndBlogs => "People & Blogs",
+      CategoryID::Comedy => "Comedy",
+      CategoryID::Entertainment => "Entertainment",
+      CategoryID::NewsAndPolitics => "News & Politics",
+      CategoryID::HowToAndStyle => "Howto & Style",
+      CategoryID::Education => "Education",
+      CategoryID::ScienceAndTechnology => "Science & Technology",
+      CategoryID::NonprofitsAndActivism => "Nonprofits & Activism",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    Self::ALL
+      .into_iter()
+      .find(|category| category.name().eq_ignore_ascii_case(name.trim()))
+  }
+}
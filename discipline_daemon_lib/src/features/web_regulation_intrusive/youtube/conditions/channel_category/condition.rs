@@ -1,3 +1,67 @@
-pub struct Matcher {
-  youtubeChannelId: String,
-}
\ No newline at end of file
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use super::super::super::metadata::VideoMetadata;
+use super::super::super::CategoryID;
+
+/// YouTube doesn't categorize channels, only videos. A channel is in every
+/// category we've seen one of its videos in.
+#[derive(Debug, Clone, Default)]
+pub struct ChannelCategories {
+  categories: HashMap<String, HashSet<CategoryID>>,
+}
+
+impl ChannelCategories {
+  /// Bounds the memory used by channels seen in a long running session.
+  pub const MAX_CHANNELS: usize = 10_000;
+
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn observe(&mut self, video: &VideoMetadata) {
+    let (Some(channel_id), Some(category)) = (&video.channel_id, video.category) else {
+      return;
+    };
+
+    if self.categories.len() >= Self::MAX_CHANNELS && !self.categories.contains_key(channel_id) {
+      self.categories.clear();
+    }
+
+    self
+      .categories
+      .entry(channel_id.clone())
+      .or_default()
+      .insert(category);
+  }
+
+  pub fn contains(&self, channel_id: &str, category: CategoryID) -> bool {
+    self
+      .categories
+      .get(channel_id)
+      .is_some_and(|categories| categories.contains(&category))
+  }
+}
+
+/// Matches the videos of channels in a category, see `ChannelCategories`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Condition {
+  category: CategoryID,
+}
+
+impl Condition {
+  pub fn new(category: CategoryID) -> Self {
+    Self { category }
+  }
+
+  pub fn category(&self) -> CategoryID {
+    self.category
+  }
+
+  pub fn matches(&self, video: &VideoMetadata, channel_categories: &ChannelCategories) -> bool {
+    video.category == Some(self.category)
+      || video
+        .channel_id
+        .as_ref()
+        .is_some_and(|channel_id| channel_categories.contains(channel_id, self.category))
+  }
+}
@@ -0,0 +1,2 @@
+pub mod condition;
+pub use condition::{ChannelCategories, Condition};
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+use crate::GenericError;
+use super::super::super::metadata::VideoMetadata;
+
+/// Matches the videos of the channel with the given id, which looks like
+/// `UC` followed by 22 letters, digits, '-' or '_'.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+  channel_id: String,
+}
+
+impl Condition {
+  pub fn new(channel_id: String) -> Result<Self, GenericError> {
+    let is_valid = channel_id.len() == 24
+      && channel_id.starts_with("UC")
+      && channel_id
+        .bytes()
+        .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_');
+
+    if !is_valid {
+      return Err(
+        GenericError::new("creating a YouTube channel id condition")
+          .add_error("channel id isn't 'UC' followed by 22 letters, digits, '-' or '_'")
+          .add_attachment("channel id", channel_id)
+      );
+    }
+
+    Ok(Self { channel_id })
+  }
+
+  pub fn channel_id(&self) -> &String {
+    &self.channel_id
+  }
+
+  pub fn matches(&self, video: &VideoMetadata) -> bool {
+    video.channel_id.as_ref() == Some(&self.channel_id)
+  }
+}
+
+impl Serialize for Condition {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    self.channel_id.serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for Condition {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    Condition::new(String::deserialize(deserializer)?)
+      .map_err(|error| Error::custom(format!("{error:?}")))
+  }
+}
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+use crate::GenericError;
+use super::super::super::metadata::VideoMetadata;
+
+/// Matches the videos of channels with the given name, ignoring case.
+/// Channel names aren't unique, so prefer the channel id condition when
+/// the id is known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+  name: String,
+  lowercase_name: String,
+}
+
+impl Condition {
+  pub const MIN_LENGTH: usize = 1;
+  pub const MAX_LENGTH: usize = 100;
+
+  pub fn new(name: String) -> Result<Self, GenericError> {
+    let length = name.trim().chars().count();
+    if !(Self::MIN_LENGTH..=Self::MAX_LENGTH).contains(&length) {
+      return Err(
+        GenericError::new("creating a YouTube channel name condition")
+          .add_error("name is too short or too long")
+          .add_attachment("name", name)
+          .add_attachment("min length", Self::MIN_LENGTH.to_string())
+          .add_attachment("max length", Self::MAX_LENGTH.to_string())
+      );
+    }
+
+    let name = name.trim().to_string();
+    Ok(Self {
+      lowercase_name: name.to_lowercase(),
+      name,
+    })
+  }
+
+  pub fn name(&self) -> &String {
+    &self.name
+  }
+
+  pub fn matches(&self, video: &VideoMetadata) -> bool {
+    video
+      .channel_name
+      .as_ref()
+      .is_some_and(|name| name.trim().to_lowercase() == self.lowercase_name)
+  }
+}
+
+impl Serialize for Condition {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    self.name.serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for Condition {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    Condition::new(String::deserialize(deserializer)?)
+      .map_err(|error| Error::custom(format!("{error:?}")))
+  }
+}
@@ -1,6 +1,34 @@
-pub mod channel_category;
-pub mod channel_id;
-pub mod channel_name;
-pub mod video_category;
-pub mod video_id;
-pub mod video_name;
\ No newline at end of file
+use serde::{Deserialize, Serialize};
+use super::metadata::VideoMetadata;
+
+pub mod channel_category;
+pub mod channel_id;
+pub mod channel_name;
+pub mod video_category;
+pub mod video_id;
+pub mod video_name;
+
+pub use channel_category::ChannelCategories;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Condition {
+  VideoId(video_id::Condition),
+  VideoName(video_name::Condition),
+  ChannelId(channel_id::Condition),
+  ChannelName(channel_name::Condition),
+  VideoCategory(video_category::Condition),
+  ChannelCategory(channel_category::Condition),
+}
+
+impl Condition {
+  pub fn matches(&self, video: &VideoMetadata, channel_categories: &ChannelCategories) -> bool {
+    match self {
+      Condition::VideoId(condition) => condition.matches(video),
+      Condition::VideoName(condition) => condition.matches(video),
+      Condition::ChannelId(condition) => condition.matches(video),
+      Condition::ChannelName(condition) => condition.matches(video),
+      Condition::VideoCategory(condition) => condition.matches(video),
+      Condition::ChannelCategory(condition) => condition.matches(video, channel_categories),
+    }
+  }
+}
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+use super::super::super::metadata::VideoMetadata;
+use super::super::super::CategoryID;
+
+/// Matches videos in a category. Only player responses and watch pages say
+/// which category a video is in, so recommendations never match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Condition {
+  category: CategoryID,
+}
+
+impl Condition {
+  pub fn new(category: CategoryID) -> Self {
+    Self { category }
+  }
+
+  pub fn category(&self) -> CategoryID {
+    self.category
+  }
+
+  pub fn matches(&self, video: &VideoMetadata) -> bool {
+    video.category == Some(self.category)
+  }
+}
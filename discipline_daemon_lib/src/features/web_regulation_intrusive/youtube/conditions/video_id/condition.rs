@@ -1,28 +1,50 @@
-use uuid::Uuid;
-
-pub struct Condition {
-  id: Uuid,
-  key: String,
-  path: Vec<String>,
-  owner: usize,
-  videoId: String,
-}
-
-// struct VideoIdCondition {
-//   id: String,
-// }
-// struct ChannelIdCondition {
-//   id: String,
-// }
-// struct VideoCategoryCondition {
-
-// }
-// struct ChannelCategoryCondition {
-
-// }
-// struct VideoNameCondition {
-
-// }
-// struct ChannelNameCondition {
-
-// }
\ No newline at end of file
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+use crate::GenericError;
+use super::super::super::metadata::{is_video_id, VideoMetadata};
+
+/// Matches the video with the given id, the part after `watch?v=` in its url.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+  video_id: String,
+}
+
+impl Condition {
+  pub fn new(video_id: String) -> Result<Self, GenericError> {
+    if !is_video_id(&video_id) {
+      return Err(
+        GenericError::new("creating a YouTube video id condition")
+          .add_error("video id isn't 11 letters, digits, '-' or '_'")
+          .add_attachment("video id", video_id)
+      );
+    }
+
+    Ok(Self { video_id })
+  }
+
+  pub fn video_id(&self) -> &String {
+    &self.video_id
+  }
+
+  pub fn matches(&self, video: &VideoMetadata) -> bool {
+    video.video_id == self.video_id
+  }
+}
+
+impl Serialize for Condition {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    self.video_id.serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for Condition {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    Condition::new(String::deserialize(deserializer)?)
+      .map_err(|error| Error::custom(format!("{error:?}")))
+  }
+}
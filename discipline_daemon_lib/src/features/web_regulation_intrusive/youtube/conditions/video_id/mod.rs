@@ -1 +1,2 @@
-pub mod condition;
\ No newline at end of file
+pub mod condition;
+pub use condition::Condition;
@@ -1,6 +1,64 @@
-use uuid::Uuid;
-
-pub struct Condition {
-  id: Uuid,
-  videoName: String,
-}
\ No newline at end of file
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+use crate::GenericError;
+use super::super::super::metadata::VideoMetadata;
+
+/// Matches videos whose title contains some text, ignoring case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+  text: String,
+  lowercase_text: String,
+}
+
+impl Condition {
+  pub const MIN_LENGTH: usize = 1;
+  pub const MAX_LENGTH: usize = 200;
+
+  pub fn new(text: String) -> Result<Self, GenericError> {
+    let length = text.trim().chars().count();
+    if !(Self::MIN_LENGTH..=Self::MAX_LENGTH).contains(&length) {
+      return Err(
+        GenericError::new("creating a YouTube video name condition")
+          .add_error("text is too short or too long")
+          .add_attachment("text", text)
+          .add_attachment("min length", Self::MIN_LENGTH.to_string())
+          .add_attachment("max length", Self::MAX_LENGTH.to_string())
+      );
+    }
+
+    let text = text.trim().to_string();
+    Ok(Self {
+      lowercase_text: text.to_lowercase(),
+      text,
+    })
+  }
+
+  pub fn text(&self) -> &String {
+    &self.text
+  }
+
+  pub fn matches(&self, video: &VideoMetadata) -> bool {
+    video
+      .title
+      .as_ref()
+      .is_some_and(|title| title.to_lowercase().contains(&self.lowercase_text))
+  }
+}
+
+impl Serialize for Condition {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    self.text.serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for Condition {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    Condition::new(String::deserialize(deserializer)?)
+      .map_err(|error| Error::custom(format!("{error:?}")))
+  }
+}
@@ -1,66 +1,345 @@
-use std::rc::Rc;
-use serde::{Serialize, Deserialize};
-use crate::rules::rule::Rule;
-
-
-#[derive(Debug, PartialEq, Eq, Hash)]
-enum Category {
-  Music = "Music",
-  Gaming = "Gaming",
-  Sports = "Sports",
-  Comedy = "Comedy",
-  Education = "Education",
-  Entertainment = "Entertainment",
-  HowToAndStyle ="Howto & Style",
-  NewsAndPolitics = "News & Politics",
-  PetsAndAnimals = "Pets & Animals",
-  PeopleAndBlogs = "People & Blogs",
-  TravelAndEvents = "Travel & Events",
-  FilmAndAnimation = "Film & Animation",
-  AutosAndVehicles = "Autos & Vehicles",
-  ScienceAndTechnology ="Science & Technology",
-  NonprofitsAndActivism = "Nonprofits & Activism",
-}
-
-#[derive(Debug, PartialEq, Eq, Hash)]
-pub enum CategoryID {
-  Music = 10,
-  Gaming = 20,
-  Sports = 17,
-  Comedy = 23,
-  Education = 27,
-  Entertainment = 24,
-  HowToAndStyle = 26,
-  NewsAndPolitics = 25,
-  PetsAndAnimals = 15,
-  PeopleAndBlogs = 22,
-  TravelAndEvents = 19,
-  FilmAndAnimation = 1,
-  AutosAndVehicles = 2,
-  ScienceAndTechnology = 28,
-  NonprofitsAndActivism = 29,
-}
-
-impl CategoryID {
-  pub fn to_category_name(&self) -> Category {
-    match self {
-      // CategoryID::
-    }
-  }
-}
-
-enum ChannelCategory {
-
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Feature {
-  pub block_sensitive_content: bool,
-  pub blocked_videos: Vec<usize>,
-  pub blocked_channels_by_ids: Vec<String>,
-  pub blocked_channels_by_names: Vec<String>,
-  pub blocked_channels_by_category: Vec<CategoryID>,
-}
-
-impl Feature {
-}
\ No newline at end of file
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::database::web_regulation_intrusive_youtube_rule as rule_db;
+use crate::operating_system_integration::UserId;
+use crate::{Daemon, Database, GenericError, Uuid};
+use crate::api::block_pages::WebRuleFeature;
+use super::super::block_page;
+use super::super::traffic::*;
+use super::conditions::{ChannelCategories, Condition};
+use super::metadata::{self, VideoMetadata};
+
+pub const MAXIMUM_RULES_PER_USER: usize = 500;
+
+static VIDEO_BLOCKED_PNG: &[u8] = include_bytes!("assets/VideoBlocked.png");
+
+/// `assets/VideoBlocked.png` as a data url, so players can show it without
+/// fetching anything.
+fn video_blocked_image_url() -> &'static str {
+  static URL: OnceLock<String> = OnceLock::new();
+  URL.get_or_init(|| {
+    format!(
+      "data:image/png;base64,{}",
+      base64::engine::general_purpose::STANDARD.encode(VIDEO_BLOCKED_PNG),
+    )
+  })
+}
+
+/// Blocks the YouTube videos matching its condition.
+#[derive(Debug, Clone)]
+pub struct Rule {
+  id: Uuid,
+  user_id: UserId,
+  condition: Condition,
+}
+
+impl Rule {
+  pub fn from_fields(id: Uuid, user_id: UserId, condition: Condition) -> Self {
+    Self {
+      id,
+      user_id,
+      condition,
+    }
+  }
+
+  pub fn id(&self) -> &Uuid {
+    &self.id
+  }
+
+  pub fn user_id(&self) -> UserId {
+    self.user_id
+  }
+
+  pub fn condition(&self) -> &Condition {
+    &self.condition
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleCreator {
+  pub id: Option<Uuid>,
+  pub condition: Condition,
+}
+
+impl RuleCreator {
+  pub fn create(self, user_id: UserId) -> Rule {
+    Rule {
+      id: self.id.unwrap_or_else(Uuid::new_v4),
+      user_id,
+      condition: self.condition,
+    }
+  }
+}
+
+// SECTION: Traffic handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Page {
+  /// A watch page, or a shorts, live or embed page, loaded from scratch.
+  Watch,
+  /// `youtubei/v1/player`, which the page fetches when navigating to a
+  /// video without reloading.
+  Player,
+  /// `youtubei/v1/next`, which has the video's title, channel and
+  /// recommendations.
+  Next,
+}
+
+fn is_youtube_host(host: &str) -> bool {
+  matches!(
+    host,
+    "youtube.com"
+      | "www.youtube.com"
+      | "m.youtube.com"
+      | "music.youtube.com"
+      | "youtube-nocookie.com"
+      | "www.youtube-nocookie.com"
+  )
+}
+
+fn classify(exchange: &Exchange) -> Option<Page> {
+  if !is_youtube_host(&exchange.host) {
+    return None;
+  }
+
+  let request = &exchange.request;
+  match request.path() {
+    "/youtubei/v1/player" => {
+      Some(Page::Player)
+    }
+    "/youtubei/v1/next" => {
+      Some(Page::Next)
+    }
+    path => {
+      let is_watch_page = request.method == "GET"
+        && metadata::video_id_from_target(path, request.query()).is_some();
+
+      is_watch_page.then_some(Page::Watch)
+    }
+  }
+}
+
+/// Blocks YouTube videos by id, title, channel and category, and hides
+/// recommendations of blocked videos.
+///
+/// Videos are recognized in watch pages and in the `player` and `next`
+/// responses YouTube fetches when navigating without reloading. Blocked
+/// watch pages redirect to the block page and blocked players show an
+/// error screen instead of the video.
+pub struct YoutubeRegulation {
+  rules: Mutex<Vec<Rule>>,
+  channel_categories: Mutex<ChannelCategories>,
+}
+
+impl YoutubeRegulation {
+  pub fn open(database: &Database) -> Result<Self, GenericError> {
+    let rules = rule_db::retrieve_all_rules(database)
+      .map_err(|error| error.change_context("opening the YouTube regulation"))?;
+
+    Ok(Self {
+      rules: Mutex::new(rules),
+      channel_categories: Mutex::new(ChannelCategories::new()),
+    })
+  }
+
+  pub fn rules(&self) -> MutexGuard<'_, Vec<Rule>> {
+    self.rules.lock().unwrap()
+  }
+
+  fn has_rules_for(&self, user_id: Option<UserId>) -> bool {
+    user_id.is_some_and(|user_id| {
+      self.rules().iter().any(|rule| rule.user_id == user_id)
+    })
+  }
+
+  /// Returns `user_id`'s rules. Holding on to a copy keeps the lock short
+  /// while evaluating large responses.
+  fn rules_of(&self, user_id: UserId) -> Vec<Rule> {
+    self
+      .rules()
+      .iter()
+      .filter(|rule| rule.user_id == user_id)
+      .cloned()
+      .collect()
+  }
+
+  /// Remembers the channel's category and returns the id of the first rule
+  /// blocking the video, if any.
+  fn evaluate(&self, rules: &[Rule], video: &VideoMetadata) -> Option<Uuid> {
+    let mut channel_categories = self.channel_categories.lock().unwrap();
+    channel_categories.observe(video);
+
+    rules
+      .iter()
+      .find(|rule| rule.condition.matches(video, &channel_categories))
+      .map(|rule| rule.id)
+  }
+
+  fn retain_unblocked_recommendations(&self, rules: &[Rule], response: &mut Value) -> usize {
+    let channel_categories = self.channel_categories.lock().unwrap();
+    metadata::retain_recommendations(response, &mut |video| {
+      !rules
+        .iter()
+        .any(|rule| rule.condition.matches(video, &channel_categories))
+    })
+  }
+
+  fn on_watch_page(
+    &self,
+    daemon: &Daemon,
+    exchange: &Exchange,
+    rules: &[Rule],
+    body: &mut Vec<u8>,
+  ) -> BodyVerdict {
+    let Ok(html) = std::str::from_utf8(body) else {
+      return BodyVerdict::Forward;
+    };
+
+    let video = metadata::find_watch_page_variable(html, "ytInitialPlayerResponse")
+      .and_then(|(response, _)| VideoMetadata::from_player_response(&response));
+
+    if let Some(rule_id) = video.and_then(|video| self.evaluate(rules, &video)) {
+      return BodyVerdict::Respond(block_page::respond(
+        daemon.configuration().block_page_port(),
+        exchange,
+        WebRuleFeature::YoutubeRule,
+        rule_id,
+      ));
+    }
+
+    let Some((mut initial_data, range)) = metadata::find_watch_page_variable(html, "ytInitialData") else {
+      return BodyVerdict::Forward;
+    };
+
+    if self.retain_unblocked_recommendations(rules, &mut initial_data) > 0 {
+      let mut html = html.to_string();
+      html.replace_range(range, &initial_data.to_string());
+      *body = html.into_bytes();
+    }
+
+    BodyVerdict::Forward
+  }
+
+  fn on_player(&self, rules: &[Rule], body: &mut Vec<u8>) -> BodyVerdict {
+    let Ok(mut response) = serde_json::from_slice::<Value>(body) else {
+      return BodyVerdict::Forward;
+    };
+
+    let Some(video) = VideoMetadata::from_player_response(&response) else {
+      return BodyVerdict::Forward;
+    };
+
+    if self.evaluate(rules, &video).is_some() {
+      block_player_response(&mut response);
+      *body = response.to_string().into_bytes();
+    }
+
+    BodyVerdict::Forward
+  }
+
+  fn on_next(&self, rules: &[Rule], body: &mut Vec<u8>) -> BodyVerdict {
+    let Ok(mut response) = serde_json::from_slice::<Value>(body) else {
+      return BodyVerdict::Forward;
+    };
+
+    if let Some(video) = VideoMetadata::from_next_response(&response) {
+      // The player response decides whether the video itself plays, but
+      // this one is enough to remember the channel.
+      self.evaluate(rules, &video);
+    }
+
+    if self.retain_unblocked_recommendations(rules, &mut response) > 0 {
+      *body = response.to_string().into_bytes();
+    }
+
+    BodyVerdict::Forward
+  }
+}
+
+impl TrafficHandler for YoutubeRegulation {
+  fn may_inspect_response_body(&self, _daemon: &Daemon, exchange: &Exchange) -> bool {
+    classify(exchange).is_some() && self.has_rules_for(exchange.user_id)
+  }
+
+  fn on_response(
+    &self,
+    daemon: &Daemon,
+    exchange: &Exchange,
+    response: &mut ResponseHead,
+  ) -> ResponseVerdict {
+    let is_expected_type = match classify(exchange) {
+      Some(Page::Watch) => {
+        response.content_type().as_deref() == Some("text/html")
+      }
+      Some(Page::Player | Page::Next) => {
+        response.content_type().as_deref() == Some("application/json")
+      }
+      None => {
+        return ResponseVerdict::Forward;
+      }
+    };
+
+    if response.status_code == 200
+      && is_expected_type
+      && self.may_inspect_response_body(daemon, exchange)
+    {
+      ResponseVerdict::InspectBody
+    } else {
+      ResponseVerdict::Forward
+    }
+  }
+
+  fn on_response_body(
+    &self,
+    daemon: &Daemon,
+    exchange: &Exchange,
+    _response: &mut ResponseHead,
+    body: &mut Vec<u8>,
+  ) -> BodyVerdict {
+    let (Some(page), Some(user_id)) = (classify(exchange), exchange.user_id) else {
+      return BodyVerdict::Forward;
+    };
+
+    let rules = self.rules_of(user_id);
+    if rules.is_empty() {
+      return BodyVerdict::Forward;
+    }
+
+    match page {
+      Page::Watch => self.on_watch_page(daemon, exchange, &rules, body),
+      Page::Player => self.on_player(&rules, body),
+      Page::Next => self.on_next(&rules, body),
+    }
+  }
+}
+
+/// Makes the player show an error screen with `assets/VideoBlocked.png`
+/// instead of playing the video.
+pub fn block_player_response(response: &mut Value) {
+  let Some(object) = response.as_object_mut() else {
+    return;
+  };
+
+  for key in ["streamingData", "playerAds", "adPlacements", "captions", "storyboards", "endscreen"] {
+    object.remove(key);
+  }
+
+  object.insert("playabilityStatus".into(), json!({
+    "status": "ERROR",
+    "reason": "This video is blocked",
+    "errorScreen": {
+      "playerErrorMessageRenderer": {
+        "reason": { "simpleText": "This video is blocked" },
+        "thumbnail": {
+          "thumbnails": [{
+            "url": video_blocked_image_url(),
+            "width": 732,
+            "height": 338,
+          }],
+        },
+        "icon": { "iconType": "ERROR_OUTLINE" },
+      },
+    },
+  }));
+}
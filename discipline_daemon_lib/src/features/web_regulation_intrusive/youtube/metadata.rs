@@ -0,0 +1,227 @@
+use std::ops::Range;
+use serde_json::Value;
+use super::CategoryID;
+
+/// What we know about a video. Which fields are known depends on where it
+/// was found: player responses have everything, recommendations only have
+/// the id, title and channel.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VideoMetadata {
+  pub video_id: String,
+  pub title: Option<String>,
+  pub channel_id: Option<String>,
+  pub channel_name: Option<String>,
+  pub category: Option<CategoryID>,
+}
+
+impl VideoMetadata {
+  /// Reads a player response, the body of `youtubei/v1/player` and the
+  /// `ytInitialPlayerResponse` of watch pages.
+  pub fn from_player_response(response: &Value) -> Option<Self> {
+    let details = response.get("videoDetails")?;
+    let microformat = response
+      .get("microformat")
+      .and_then(|microformat| microformat.get("playerMicroformatRenderer"));
+
+    Some(Self {
+      video_id: string(details.get("videoId"))?,
+      title: string(details.get("title"))
+        .or_else(|| microformat.and_then(|microformat| text(microformat.get("title")))),
+      channel_id: string(details.get("channelId"))
+        .or_else(|| microformat.and_then(|microformat| string(microformat.get("externalChannelId")))),
+      channel_name: string(details.get("author"))
+        .or_else(|| microformat.and_then(|microformat| string(microformat.get("ownerChannelName")))),
+      category: microformat
+        .and_then(|microformat| microformat.get("category"))
+        .and_then(Value::as_str)
+        .and_then(CategoryID::from_name),
+    })
+  }
+
+  /// Reads the video being watched from a next response, the body of
+  /// `youtubei/v1/next` and the `ytInitialData` of watch pages. Next
+  /// responses don't say which category the video is in.
+  pub fn from_next_response(response: &Value) -> Option<Self> {
+    let video_id = response
+      .pointer("/currentVideoEndpoint/watchEndpoint/videoId")
+      .and_then(Value::as_str)?;
+
+    let primary = find_key(response, "videoPrimaryInfoRenderer");
+    let owner = find_key(response, "videoOwnerRenderer");
+
+    Some(Self {
+      video_id: video_id.to_string(),
+      title: primary.and_then(|primary| text(primary.get("title"))),
+      channel_id: owner.and_then(|owner| browse_id(owner.get("navigationEndpoint"))),
+      channel_name: owner.and_then(|owner| text(owner.get("title"))),
+      category: None,
+    })
+  }
+
+  /// Reads a recommended video, which is either a `compactVideoRenderer` or,
+  /// in newer layouts, a `lockupViewModel` of a video.
+  pub fn from_recommendation(item: &Value) -> Option<Self> {
+    if let Some(renderer) = item.get("compactVideoRenderer") {
+      let byline = renderer
+        .get("longBylineText")
+        .or_else(|| renderer.get("shortBylineText"))
+        .and_then(|byline| byline.pointer("/runs/0"));
+
+      return Some(Self {
+        video_id: string(renderer.get("videoId"))?,
+        title: text(renderer.get("title")),
+        channel_id: byline.and_then(|byline| browse_id(byline.get("navigationEndpoint"))),
+        channel_name: byline.and_then(|byline| string(byline.get("text"))),
+        category: None,
+      });
+    }
+
+    if let Some(lockup) = item.get("lockupViewModel") {
+      if lockup.get("contentType").and_then(Value::as_str) != Some("LOCKUP_CONTENT_TYPE_VIDEO") {
+        return None;
+      }
+
+      let metadata = lockup.pointer("/metadata/lockupMetadataViewModel");
+      let channel = metadata.and_then(|metadata| metadata.pointer(
+        "/metadata/contentMetadataViewModel/metadataRows/0/metadataParts/0/text"
+      ));
+
+      return Some(Self {
+        video_id: string(lockup.get("contentId"))?,
+        title: metadata.and_then(|metadata| string(metadata.pointer("/title/content"))),
+        channel_id: channel
+          .and_then(|channel| channel.pointer("/commandRuns/0/onTap/innertubeCommand"))
+          .and_then(|command| browse_id(Some(command))),
+        channel_name: channel.and_then(|channel| string(channel.get("content"))),
+        category: None,
+      });
+    }
+
+    None
+  }
+}
+
+/// Removes the recommended videos for which `keep` returns false from
+/// anywhere in `response`. Returns how many were removed.
+pub fn retain_recommendations(
+  response: &mut Value,
+  keep: &mut impl FnMut(&VideoMetadata) -> bool,
+) -> usize {
+  match response {
+    Value::Array(items) => {
+      let length = items.len();
+      items.retain(|item| {
+        VideoMetadata::from_recommendation(item).is_none_or(|video| keep(&video))
+      });
+
+      let mut removed = length - items.len();
+      for item in items {
+        removed += retain_recommendations(item, keep);
+      }
+      removed
+    }
+    Value::Object(object) => {
+      object
+        .values_mut()
+        .map(|value| retain_recommendations(value, keep))
+        .sum()
+    }
+    _ => {
+      0
+    }
+  }
+}
+
+/// Finds the json object a watch page assigns to `variable`, like
+/// `var ytInitialPlayerResponse = {...};`, and where in `html` it is.
+pub fn find_watch_page_variable(html: &str, variable: &str) -> Option<(Value, Range<usize>)> {
+  let mut offset = 0;
+  loop {
+    offset += html[offset..].find(variable)? + variable.len();
+
+    let rest = &html[offset..];
+    let Some(assignment) = rest.trim_start().strip_prefix('=') else {
+      continue;
+    };
+
+    let object = assignment.trim_start();
+    if !object.starts_with('{') {
+      continue;
+    }
+
+    // The object is followed by more javascript, so parse just one value.
+    let start = offset + (rest.len() - object.len());
+    let mut values = serde_json::Deserializer::from_str(object).into_iter::<Value>();
+    if let Some(Ok(value)) = values.next() {
+      return Some((value, start..start + values.byte_offset()));
+    }
+  }
+}
+
+/// The id of the video a watch url is for, from `/watch?v=ID`,
+/// `/shorts/ID`, `/live/ID` or `/embed/ID`.
+pub fn video_id_from_target(path: &str, query: Option<&str>) -> Option<String> {
+  let video_id = if path == "/watch" {
+    query?
+      .split('&')
+      .find_map(|pair| pair.strip_prefix("v="))?
+  } else {
+    ["/shorts/", "/live/", "/embed/"]
+      .into_iter()
+      .find_map(|prefix| path.strip_prefix(prefix))?
+      .split('/')
+      .next()?
+  };
+
+  is_video_id(video_id).then(|| video_id.to_string())
+}
+
+pub fn is_video_id(video_id: &str) -> bool {
+  video_id.len() == 11
+    && video_id
+      .bytes()
+      .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+fn string(value: Option<&Value>) -> Option<String> {
+  value?.as_str().map(str::to_string)
+}
+
+/// Reads YouTube's formatted text, which is either `{"simpleText": ...}`
+/// or `{"runs": [{"text": ...}, ...]}`.
+fn text(value: Option<&Value>) -> Option<String> {
+  let value = value?;
+  if let Some(text) = value.get("simpleText").and_then(Value::as_str) {
+    return Some(text.to_string());
+  }
+
+  let runs = value.get("runs")?.as_array()?;
+  Some(
+    runs
+      .iter()
+      .filter_map(|run| run.get("text").and_then(Value::as_str))
+      .collect()
+  )
+}
+
+fn browse_id(endpoint: Option<&Value>) -> Option<String> {
+  string(endpoint?.pointer("/browseEndpoint/browseId"))
+}
+
+/// Depth-first search for the first value of a key named `key`.
+fn find_key<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+  match value {
+    Value::Object(object) => {
+      if let Some(found) = object.get(key) {
+        return Some(found);
+      }
+      object.values().find_map(|value| find_key(value, key))
+    }
+    Value::Array(items) => {
+      items.iter().find_map(|item| find_key(item, key))
+    }
+    _ => {
+      None
+    }
+  }
+}
@@ -1,4 +1,14 @@
-pub mod api;
-pub mod feature;
-pub use feature::{CategoryID, Feature};
-pub mod conditions;
\ No newline at end of file
+pub mod category;
+pub use category::CategoryID;
+
+pub mod metadata;
+pub use metadata::VideoMetadata;
+
+pub mod conditions;
+pub use conditions::{ChannelCategories, Condition};
+
+pub mod feature;
+pub use feature::{Rule, RuleCreator, YoutubeRegulation, MAXIMUM_RULES_PER_USER};
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,224 @@
+use serde_json::Value;
+use super::conditions::*;
+use super::feature::block_player_response;
+use super::metadata::{self, VideoMetadata};
+use super::{CategoryID, Condition};
+
+static PLAYER: &str = include_str!("fixtures/player.json");
+static NEXT: &str = include_str!("fixtures/next.json");
+static WATCH: &str = include_str!("fixtures/watch.html");
+
+const VIDEO_ID: &str = "dQw4w9WgXcQ";
+const CHANNEL_ID: &str = "UCuAXFkgsw1L7xaCfnd5JJOw";
+
+fn player() -> Value {
+  serde_json::from_str(PLAYER).unwrap()
+}
+
+fn next() -> Value {
+  serde_json::from_str(NEXT).unwrap()
+}
+
+fn recommended_video_ids(response: &Value) -> Vec<String> {
+  let mut video_ids = Vec::new();
+  let mut response = response.clone();
+  metadata::retain_recommendations(&mut response, &mut |video| {
+    video_ids.push(video.video_id.clone());
+    true
+  });
+  video_ids
+}
+
+#[test]
+fn reads_player_responses() {
+  let video = VideoMetadata::from_player_response(&player()).unwrap();
+
+  assert_eq!(video.video_id, VIDEO_ID);
+  assert_eq!(
+    video.title.as_deref(),
+    Some("Rick Astley - Never Gonna Give You Up (Official Video) (4K Remaster)"),
+  );
+  assert_eq!(video.channel_id.as_deref(), Some(CHANNEL_ID));
+  assert_eq!(video.channel_name.as_deref(), Some("Rick Astley"));
+  assert_eq!(video.category, Some(CategoryID::Music));
+}
+
+#[test]
+fn reads_next_responses() {
+  let video = VideoMetadata::from_next_response(&next()).unwrap();
+
+  assert_eq!(video.video_id, VIDEO_ID);
+  assert_eq!(
+    video.title.as_deref(),
+    Some("Rick Astley - Never Gonna Give You Up (Official Video)"),
+  );
+  assert_eq!(video.channel_id.as_deref(), Some(CHANNEL_ID));
+  assert_eq!(video.channel_name.as_deref(), Some("Rick Astley"));
+  assert_eq!(video.category, None);
+}
+
+#[test]
+fn reads_recommendations_of_both_layouts() {
+  let mut recommendations = Vec::new();
+  let mut response = next();
+  metadata::retain_recommendations(&mut response, &mut |video| {
+    recommendations.push(video.clone());
+    true
+  });
+
+  assert_eq!(recommendations.len(), 2);
+
+  assert_eq!(recommendations[0].video_id, "yPYZpwSpKmA");
+  assert_eq!(recommendations[0].channel_id.as_deref(), Some(CHANNEL_ID));
+  assert_eq!(recommendations[0].channel_name.as_deref(), Some("Rick Astley"));
+
+  assert_eq!(recommendations[1].video_id, "L_jWHffIx5E");
+  assert_eq!(
+    recommendations[1].title.as_deref(),
+    Some("Smash Mouth - All Star (Official Music Video)"),
+  );
+  assert_eq!(recommendations[1].channel_id.as_deref(), Some("UCN1hnUccO4FD5WfM7ithXaw"));
+  assert_eq!(recommendations[1].channel_name.as_deref(), Some("Smash Mouth"));
+}
+
+#[test]
+fn removes_only_blocked_recommendations() {
+  let mut response = next();
+  let removed = metadata::retain_recommendations(&mut response, &mut |video| {
+    video.channel_name.as_deref() != Some("Smash Mouth")
+  });
+
+  assert_eq!(removed, 1);
+  assert_eq!(recommended_video_ids(&response), ["yPYZpwSpKmA"]);
+
+  // Playlists and continuations aren't videos and stay where they were.
+  let results = response
+    .pointer("/contents/twoColumnWatchNextResults/secondaryResults/secondaryResults/results")
+    .and_then(Value::as_array)
+    .unwrap();
+
+  assert_eq!(results.len(), 3);
+  assert!(results[1].get("lockupViewModel").is_some());
+  assert!(results[2].get("continuationItemRenderer").is_some());
+}
+
+#[test]
+fn finds_watch_page_variables() {
+  let (player_response, range) = metadata::find_watch_page_variable(WATCH, "ytInitialPlayerResponse").unwrap();
+  assert_eq!(player_response, player());
+  assert!(WATCH[range.clone()].starts_with('{'));
+  assert!(WATCH[range.end..].starts_with(";var meta"));
+
+  let (initial_data, range) = metadata::find_watch_page_variable(WATCH, "ytInitialData").unwrap();
+  assert_eq!(initial_data, next());
+  assert!(WATCH[range.end..].starts_with(";if (window.ytcsi)"));
+
+  assert!(metadata::find_watch_page_variable(WATCH, "ytInitialReelWatchSequenceResponse").is_none());
+}
+
+#[test]
+fn reads_video_ids_from_targets() {
+  assert_eq!(metadata::video_id_from_target("/watch", Some("v=dQw4w9WgXcQ")).as_deref(), Some(VIDEO_ID));
+  assert_eq!(metadata::video_id_from_target("/watch", Some("list=LL&v=dQw4w9WgXcQ&t=42s")).as_deref(), Some(VIDEO_ID));
+  assert_eq!(metadata::video_id_from_target("/shorts/dQw4w9WgXcQ", None).as_deref(), Some(VIDEO_ID));
+  assert_eq!(metadata::video_id_from_target("/live/dQw4w9WgXcQ", Some("feature=share")).as_deref(), Some(VIDEO_ID));
+  assert_eq!(metadata::video_id_from_target("/embed/dQw4w9WgXcQ/", None).as_deref(), Some(VIDEO_ID));
+
+  assert_eq!(metadata::video_id_from_target("/watch", None), None);
+  assert_eq!(metadata::video_id_from_target("/watch", Some("v=tooshort")), None);
+  assert_eq!(metadata::video_id_from_target("/watch", Some("v=dQw4w9WgXc!")), None);
+  assert_eq!(metadata::video_id_from_target("/feed/subscriptions", None), None);
+  assert_eq!(metadata::video_id_from_target("/", Some("v=dQw4w9WgXcQ")), None);
+}
+
+#[test]
+fn matches_conditions() {
+  let video = VideoMetadata::from_player_response(&player()).unwrap();
+  let mut channel_categories = ChannelCategories::new();
+
+  let matches = |condition: Condition, channel_categories: &ChannelCategories| {
+    condition.matches(&video, channel_categories)
+  };
+
+  assert!(matches(Condition::VideoId(video_id::Condition::new(VIDEO_ID.into()).unwrap()), &channel_categories));
+  assert!(!matches(Condition::VideoId(video_id::Condition::new("yPYZpwSpKmA".into()).unwrap()), &channel_categories));
+
+  assert!(matches(Condition::VideoName(video_name::Condition::new("never gonna".into()).unwrap()), &channel_categories));
+  assert!(!matches(Condition::VideoName(video_name::Condition::new("let you down".into()).unwrap()), &channel_categories));
+
+  assert!(matches(Condition::ChannelId(channel_id::Condition::new(CHANNEL_ID.into()).unwrap()), &channel_categories));
+  assert!(!matches(Condition::ChannelId(channel_id::Condition::new("UCN1hnUccO4FD5WfM7ithXaw".into()).unwrap()), &channel_categories));
+
+  assert!(matches(Condition::ChannelName(channel_name::Condition::new("RICK ASTLEY".into()).unwrap()), &channel_categories));
+  assert!(!matches(Condition::ChannelName(channel_name::Condition::new("Rick".into()).unwrap()), &channel_categories));
+
+  assert!(matches(Condition::VideoCategory(video_category::Condition::new(CategoryID::Music)), &channel_categories));
+  assert!(!matches(Condition::VideoCategory(video_category::Condition::new(CategoryID::Gaming)), &channel_categories));
+
+  // A video in a category puts its channel in it too.
+  let channel_category = Condition::ChannelCategory(channel_category::Condition::new(CategoryID::Music));
+  assert!(matches(channel_category.clone(), &channel_categories));
+
+  // Next responses don't have categories, so the channel has to be known.
+  let uncategorized = VideoMetadata::from_next_response(&next()).unwrap();
+  assert!(!channel_category.matches(&uncategorized, &channel_categories));
+
+  channel_categories.observe(&video);
+  assert!(channel_category.matches(&uncategorized, &channel_categories));
+
+  // Recommendations don't have categories, but their channels may be known.
+  let mut response = next();
+  let removed = metadata::retain_recommendations(&mut response, &mut |video| {
+    !channel_category.matches(video, &channel_categories)
+  });
+  assert_eq!(removed, 1);
+  assert_eq!(recommended_video_ids(&response), ["L_jWHffIx5E"]);
+}
+
+#[test]
+fn blocks_player_responses() {
+  let mut response = player();
+  block_player_response(&mut response);
+
+  for key in ["streamingData", "playerAds", "captions", "storyboards"] {
+    assert!(response.get(key).is_none(), "{key} wasn't removed");
+  }
+
+  assert_eq!(response.pointer("/playabilityStatus/status").and_then(Value::as_str), Some("ERROR"));
+
+  let thumbnail = response
+    .pointer("/playabilityStatus/errorScreen/playerErrorMessageRenderer/thumbnail/thumbnails/0/url")
+    .and_then(Value::as_str)
+    .unwrap();
+  assert!(thumbnail.starts_with("data:image/png;base64,iVBORw0KGgo"));
+
+  // The page still knows which video it is, so it can show its title.
+  let video = VideoMetadata::from_player_response(&response).unwrap();
+  assert_eq!(video.video_id, VIDEO_ID);
+}
+
+#[test]
+fn validates_conditions_when_deserializing() {
+  let condition: Condition = serde_json::from_str(r#"{"VideoId":"dQw4w9WgXcQ"}"#).unwrap();
+  assert_eq!(condition, Condition::VideoId(video_id::Condition::new(VIDEO_ID.into()).unwrap()));
+  assert_eq!(serde_json::to_string(&condition).unwrap(), r#"{"VideoId":"dQw4w9WgXcQ"}"#);
+
+  assert!(serde_json::from_str::<Condition>(r#"{"VideoId":"dQw4w9"}"#).is_err());
+  assert!(serde_json::from_str::<Condition>(r#"{"ChannelId":"dQw4w9WgXcQ"}"#).is_err());
+  assert!(serde_json::from_str::<Condition>(r#"{"VideoName":""}"#).is_err());
+  assert!(serde_json::from_str::<Condition>(&format!(r#"{{"ChannelName":"{}"}}"#, "a".repeat(101))).is_err());
+
+  let condition: Condition = serde_json::from_str(r#"{"VideoCategory":{"category":"Music"}}"#).unwrap();
+  assert_eq!(condition, Condition::VideoCategory(video_category::Condition::new(CategoryID::Music)));
+}
+
+#[test]
+fn converts_categories() {
+  for category in CategoryID::ALL {
+    assert_eq!(CategoryID::from_number(category.number()), Some(category));
+    assert_eq!(CategoryID::from_name(category.name()), Some(category));
+  }
+
+  assert_eq!(CategoryID::from_name("howto & style"), Some(CategoryID::HowToAndStyle));
+  assert_eq!(CategoryID::from_number(0), None);
+}
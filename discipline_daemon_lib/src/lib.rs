@@ -11,7 +11,7 @@ pub mod error {
 }
 
 mod api;
-pub use api::IntoPublic;
+pub use api::{block_pages, IntoPublic};
 
 use uuid::Uuid;
 
@@ -233,6 +233,13 @@ fn execute_update_after_allowing_internet_access_for_user(
     .application_status 
     = ApplicationStatus::Allowed;
 
+  if let Err(error) = stop_redirecting_web_traffic_of_user_to_block_page(
+    &user_id, 
+    daemon.configuration().block_page_port(),
+  ) {
+    daemon.internal_logger().log_error(error);
+  }
+
   schedule_apply_regulation_for_user(
     &daemon.operating_system_integration().async_scheduler(), 
     user_id, 
@@ -259,6 +266,13 @@ fn execute_update_after_blocking_internet_access_for_user(
     .application_status 
     = ApplicationStatus::Blocked;
 
+  if let Err(error) = redirect_web_traffic_of_user_to_block_page(
+    &user_id, 
+    daemon.configuration().block_page_port(),
+  ) {
+    daemon.internal_logger().log_error(error);
+  }
+
   schedule_apply_regulation_for_user(
     &daemon.operating_system_integration().async_scheduler(), 
     user_id, 
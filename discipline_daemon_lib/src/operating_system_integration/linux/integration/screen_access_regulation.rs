@@ -192,6 +192,15 @@ fn execute_apply_regulation_for_user(
     )
   };
 
+  // Violations of circumvention detection block the screen for a while
+  // whatever the regulation says.
+  let action = match action {
+    Action::Allow if daemon.circumvention_detection().is_screen_blocked(user_id, DateTime::now()) => {
+      Action::Block
+    }
+    action => action,
+  };
+
   match action {
     Action::Allow => {
       allow_screen_access_for_user(
@@ -1,12 +1,14 @@
 use std::ffi::CStr;
 use std::mem::MaybeUninit;
+use std::path::PathBuf;
 use super::*;
 
 // TODO: Rename to BasicUserInfo
 pub struct RetrievedUserInfo {
   pub user_id: UserId,
   pub user_name: UserName, 
-  pub user_password: UserPassword
+  pub user_password: UserPassword,
+  pub user_home_directory: PathBuf,
 }
 
 // TODO: Rename to RetrieveBasicUserInfoReturn
@@ -127,10 +129,16 @@ pub fn retrieve_user_info_given_user_id(user_id: UserId) -> RetrieveUserInfoRetu
       return RetrieveUserInfoReturn::Error;
     };
 
+    let user_home_directory = PathBuf::from(
+      CStr::from_ptr(user_information.pw_dir)
+        .to_string_lossy().into_owned()
+    );
+
     RetrieveUserInfoReturn::Success(RetrievedUserInfo { 
       user_id,
       user_name, 
       user_password,
+      user_home_directory,
     })
   }
 }
@@ -240,10 +248,16 @@ pub fn retrieve_user_info_given_user_name(user_name: UserName) -> RetrieveUserIn
       return RetrieveUserInfoReturn::Error;
     };
 
+    let user_home_directory = PathBuf::from(
+      CStr::from_ptr(user_information.pw_dir)
+        .to_string_lossy().into_owned()
+    );
+
     RetrieveUserInfoReturn::Success(RetrievedUserInfo { 
       user_id, 
       user_name,
       user_password,
+      user_home_directory,
     })
   }
 }
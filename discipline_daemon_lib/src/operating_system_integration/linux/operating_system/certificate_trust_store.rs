@@ -0,0 +1,250 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::GenericError;
+use super::*;
+
+// Debian, Ubuntu and derivatives.
+static DEBIAN_ANCHORS_DIRECTORY: &str = "/usr/local/share/ca-certificates";
+// Fedora, Arch and other distributions using p11-kit's "update-ca-trust".
+static P11_KIT_ANCHORS_DIRECTORY: &str = "/etc/pki/ca-trust/source/anchors";
+
+/// The name under which certificates are installed in trust stores.
+/// Also used to find and remove them again.
+pub static INSTALLED_CERTIFICATE_NICKNAME: &str = "Discipline Local Certificate Authority";
+
+static INSTALLED_CERTIFICATE_FILE_NAME: &str = "discipline-local-certificate-authority.crt";
+
+fn run_command(command: &mut Command, action: &str) -> Result<(), GenericError> {
+  let output = command
+    .output()
+    .map_err(|error|
+      GenericError::new(action)
+        .add_error("failed to execute command")
+        .add_attachment("command", format!("{command:?}"))
+        .add_attachment("io error", error.to_string())
+    )?;
+
+  if output.status.success() {
+    return Ok(());
+  }
+
+  Err(
+    GenericError::new(action)
+      .add_error("command failed")
+      .add_attachment("command", format!("{command:?}"))
+      .add_attachment("stderr", String::from_utf8_lossy(&output.stderr))
+  )
+}
+
+/// Installs a pem-encoded certificate authority certificate into the
+/// system-wide trust store used by openssl, gnutls and most command line tools.
+pub fn install_certificate_into_system_trust_store(certificate_pem: &str) -> Result<(), GenericError> {
+  let (anchors_directory, update_command) = if Path::new(P11_KIT_ANCHORS_DIRECTORY).is_dir() {
+    (P11_KIT_ANCHORS_DIRECTORY, "update-ca-trust")
+  } else {
+    (DEBIAN_ANCHORS_DIRECTORY, "update-ca-certificates")
+  };
+
+  let anchors_directory = PathBuf::from(anchors_directory);
+  let certificate_path = anchors_directory.join(INSTALLED_CERTIFICATE_FILE_NAME);
+
+  // Don't rebuild the system bundle on every start if nothing changed.
+  if fs::read_to_string(&certificate_path).is_ok_and(|installed| installed == certificate_pem) {
+    return Ok(());
+  }
+
+  fs::create_dir_all(&anchors_directory).map_err(|error|
+    GenericError::new("installing a certificate into the system trust store")
+      .add_error("failed to create the trust anchors directory")
+      .add_attachment("directory", anchors_directory.to_string_lossy())
+      .add_attachment("io error", error.to_string())
+  )?;
+
+  fs::write(&certificate_path, certificate_pem).map_err(|error|
+    GenericError::new("installing a certificate into the system trust store")
+      .add_error("failed to write the certificate into the trust anchors directory")
+      .add_attachment("path", certificate_path.to_string_lossy())
+      .add_attachment("io error", error.to_string())
+  )?;
+
+  run_command(
+    &mut Command::new(update_command),
+    "installing a certificate into the system trust store",
+  )
+}
+
+/// Removes the certificate installed by `install_certificate_into_system_trust_store`.
+pub fn remove_certificate_from_system_trust_store() -> Result<(), GenericError> {
+  for (anchors_directory, update_command) in [
+    (P11_KIT_ANCHORS_DIRECTORY, "update-ca-trust"),
+    (DEBIAN_ANCHORS_DIRECTORY, "update-ca-certificates"),
+  ] {
+    let certificate_path = Path::new(anchors_directory).join(INSTALLED_CERTIFICATE_FILE_NAME);
+    if !certificate_path.exists() {
+      continue;
+    }
+
+    fs::remove_file(&certificate_path).map_err(|error|
+      GenericError::new("removing a certificate from the system trust store")
+        .add_attachment("path", certificate_path.to_string_lossy())
+        .add_attachment("io error", error.to_string())
+    )?;
+
+    run_command(
+      Command::new(update_command).arg("--fresh"),
+      "removing a certificate from the system trust store",
+    )?;
+  }
+
+  Ok(())
+}
+
+/// Lists the NSS databases of a user: the shared one used by Chromium-based
+/// browsers and the per-profile ones of Firefox and Thunderbird.
+fn find_user_nss_databases(user_home_directory: &Path) -> Vec<PathBuf> {
+  let mut databases = vec![user_home_directory.join(".pki").join("nssdb")];
+
+  for profiles_directory in [
+    user_home_directory.join(".mozilla").join("firefox"),
+    user_home_directory.join("snap").join("firefox").join("common").join(".mozilla").join("firefox"),
+    user_home_directory.join(".thunderbird"),
+  ] {
+    let Ok(entries) = fs::read_dir(&profiles_directory) else {
+      continue;
+    };
+
+    for entry in entries.flatten() {
+      let profile_directory = entry.path();
+      if profile_directory.join("cert9.db").exists() {
+        databases.push(profile_directory);
+      }
+    }
+  }
+
+  databases
+}
+
+/// Runs NSS's "certutil" with `arguments` on `database` as the user, so the
+/// database files keep their ownership.
+fn run_certutil_as_user(
+  user_name: &UserName,
+  database: &Path,
+  arguments: &[&OsStr],
+  action: &str,
+) -> Result<(), GenericError> {
+  run_command(
+    Command::new("runuser")
+      .arg("-u")
+      .arg(user_name.as_ref())
+      .arg("--")
+      .arg("certutil")
+      .args(arguments)
+      .arg("-d")
+      .arg(format!("sql:{}", database.to_string_lossy())),
+    action,
+  )
+}
+
+/// Creates the shared NSS database of a user, which Chromium-based browsers
+/// only create on their first run, so the certificate is trusted from then on.
+/// The directory is created as the user so the browser can write to it later.
+fn create_user_nss_database(user_name: &UserName, database: &Path) -> Result<(), GenericError> {
+  run_command(
+    Command::new("runuser")
+      .arg("-u")
+      .arg(user_name.as_ref())
+      .arg("--")
+      .arg("mkdir")
+      .arg("-p")
+      .arg("-m")
+      .arg("700")
+      .arg(database),
+    "creating a user's nss database",
+  )?;
+
+  run_certutil_as_user(
+    user_name,
+    database,
+    &["-N".as_ref(), "--empty-password".as_ref()],
+    "creating a user's nss database",
+  )
+}
+
+/// Installs a pem-encoded certificate authority certificate into every NSS
+/// database of a user using NSS's "certutil", running it as that user so the
+/// database files keep their ownership.
+pub fn install_certificate_into_user_nss_databases(
+  user_name: &UserName,
+  user_home_directory: &Path,
+  certificate_path: &Path,
+) -> Result<(), GenericError> {
+  let mut errors = Vec::new();
+
+  for database in find_user_nss_databases(user_home_directory) {
+    if !database.join("cert9.db").exists() {
+      if let Err(error) = create_user_nss_database(user_name, &database) {
+        errors.push(error.add_attachment("database", database.to_string_lossy()));
+        continue;
+      }
+    }
+
+    let result = run_certutil_as_user(
+      user_name,
+      &database,
+      &[
+        "-A".as_ref(),
+        "-n".as_ref(),
+        INSTALLED_CERTIFICATE_NICKNAME.as_ref(),
+        "-t".as_ref(),
+        "C,,".as_ref(),
+        "-i".as_ref(),
+        certificate_path.as_os_str(),
+      ],
+      "installing a certificate into a user's nss databases",
+    );
+
+    if let Err(error) = result {
+      errors.push(error);
+    }
+  }
+
+  if errors.is_empty() {
+    return Ok(());
+  }
+
+  // Installing into one database failing doesn't stop the others, and each
+  // failure is reported.
+  let mut error = GenericError::new("installing a certificate into a user's nss databases")
+    .add_error("failed to install the certificate into some of the databases")
+    .add_attachment("user name", user_name.as_ref());
+
+  for database_error in errors {
+    error = error.add_attachment("database error", database_error.to_debug_string());
+  }
+
+  Err(error)
+}
+
+/// Removes the certificate installed by `install_certificate_into_user_nss_databases`.
+pub fn remove_certificate_from_user_nss_databases(
+  user_name: &UserName,
+  user_home_directory: &Path,
+) -> Result<(), GenericError> {
+  for database in find_user_nss_databases(user_home_directory) {
+    if !database.exists() {
+      continue;
+    }
+
+    // "certutil -D" fails when the certificate isn't there, which is fine.
+    let _ = run_certutil_as_user(
+      user_name,
+      &database,
+      &["-D".as_ref(), "-n".as_ref(), INSTALLED_CERTIFICATE_NICKNAME.as_ref()],
+      "removing a certificate from a user's nss databases",
+    );
+  }
+
+  Ok(())
+}
@@ -1,5 +1,7 @@
 use super::*;
+use std::net::IpAddr;
 use std::process::Command;
+use crate::GenericError;
 
 pub fn block_inbound_network_traffic_for_user(
   user_id: &UserId,
@@ -78,3 +80,506 @@ pub fn allow_inbound_network_traffic_for_user(
     }
   }
 }
+
+fn write_web_traffic_redirection_rule(
+  command: &mut Command,
+  operation: &str,
+  user_id: &UserId,
+  proxy_port: u16,
+) {
+  command
+    .arg("-t")
+    .arg("nat")
+    .arg(operation)
+    .arg("OUTPUT")
+    .arg("-p")
+    .arg("tcp")
+    .arg("-m")
+    .arg("owner")
+    .arg("--uid-owner")
+    .arg(user_id.as_raw().to_string())
+    .arg("-m")
+    .arg("multiport")
+    .arg("--dports")
+    .arg("80,443")
+    .arg("-j")
+    .arg("REDIRECT")
+    .arg("--to-ports")
+    .arg(proxy_port.to_string());
+}
+
+fn run_iptables(command: &mut Command, action: &str, user_id: &UserId) -> Result<(), GenericError> {
+  let output = command.output().map_err(|error|
+    GenericError::new(action)
+      .add_error("failed to execute iptables")
+      .add_attachment("user id", user_id.as_raw().to_string())
+      .add_attachment("io error", error.to_string())
+  )?;
+
+  if output.status.success() {
+    return Ok(());
+  }
+
+  Err(
+    GenericError::new(action)
+      .add_error("iptables failed")
+      .add_attachment("user id", user_id.as_raw().to_string())
+      .add_attachment("stderr", String::from_utf8_lossy(&output.stderr))
+  )
+}
+
+/// Transparently redirects the user's outgoing http and https connections
+/// to a proxy listening on this machine.
+pub fn redirect_web_traffic_of_user_to_proxy(
+  user_id: &UserId,
+  proxy_port: u16,
+) -> Result<(), GenericError> {
+  for program in ["iptables", "ip6tables"] {
+    // "-C" succeeds only if the rule already exists, so we don't add it twice.
+    let mut check = Command::new(program);
+    write_web_traffic_redirection_rule(&mut check, "-C", user_id, proxy_port);
+    if check.output().is_ok_and(|output| output.status.success()) {
+      continue;
+    }
+
+    let mut append = Command::new(program);
+    write_web_traffic_redirection_rule(&mut append, "-A", user_id, proxy_port);
+    run_iptables(&mut append, "redirecting web traffic of user to proxy", user_id)?;
+  }
+
+  Ok(())
+}
+
+/// Undoes `redirect_web_traffic_of_user_to_proxy`.
+pub fn stop_redirecting_web_traffic_of_user_to_proxy(
+  user_id: &UserId,
+  proxy_port: u16,
+) -> Result<(), GenericError> {
+  for program in ["iptables", "ip6tables"] {
+    let mut check = Command::new(program);
+    write_web_traffic_redirection_rule(&mut check, "-C", user_id, proxy_port);
+    if !check.output().is_ok_and(|output| output.status.success()) {
+      continue;
+    }
+
+    let mut delete = Command::new(program);
+    write_web_traffic_redirection_rule(&mut delete, "-D", user_id, proxy_port);
+    run_iptables(&mut delete, "stopping redirecting web traffic of user to proxy", user_id)?;
+  }
+
+  Ok(())
+}
+
+fn write_block_page_redirection_rule(
+  command: &mut Command,
+  operation: &str,
+  user_id: &UserId,
+  block_page_port: u16,
+) {
+  command
+    .arg("-t")
+    .arg("nat")
+    .arg(operation)
+    .arg("OUTPUT")
+    .arg("-p")
+    .arg("tcp")
+    .arg("-m")
+    .arg("owner")
+    .arg("--uid-owner")
+    .arg(user_id.as_raw().to_string())
+    .arg("--dport")
+    .arg("80")
+    .arg("-j")
+    .arg("REDIRECT")
+    .arg("--to-ports")
+    .arg(block_page_port.to_string());
+}
+
+/// Transparently redirects the user's outgoing http connections to the
+/// block page server. The rule is inserted at the top of the chain so it
+/// takes precedence over `redirect_web_traffic_of_user_to_proxy`. The block
+/// page server only listens on ipv4, so ipv6 connections are left alone.
+pub fn redirect_web_traffic_of_user_to_block_page(
+  user_id: &UserId,
+  block_page_port: u16,
+) -> Result<(), GenericError> {
+  let mut check = Command::new("iptables");
+  write_block_page_redirection_rule(&mut check, "-C", user_id, block_page_port);
+  if check.output().is_ok_and(|output| output.status.success()) {
+    return Ok(());
+  }
+
+  let mut insert = Command::new("iptables");
+  write_block_page_redirection_rule(&mut insert, "-I", user_id, block_page_port);
+  run_iptables(&mut insert, "redirecting web traffic of user to block page", user_id)
+}
+
+/// Undoes `redirect_web_traffic_of_user_to_block_page`.
+pub fn stop_redirecting_web_traffic_of_user_to_block_page(
+  user_id: &UserId,
+  block_page_port: u16,
+) -> Result<(), GenericError> {
+  delete_rule_if_present(
+    "iptables",
+    |command, operation| write_block_page_redirection_rule(command, operation, user_id, block_page_port),
+    "stopping redirecting web traffic of user to block page",
+    user_id,
+  )
+}
+
+/// The port DNS over tls, DNS over quic and DNS over dtls are served on.
+const ENCRYPTED_DNS_PORT: &str = "853";
+
+/// Adds the rule `write_rule` writes with `program` unless it's already there.
+fn add_rule_once(
+  program: &str,
+  write_rule: impl Fn(&mut Command, &str),
+  action: &str,
+  user_id: &UserId,
+) -> Result<(), GenericError> {
+  // "-C" succeeds only if the rule already exists, so we don't add it twice.
+  let mut check = Command::new(program);
+  write_rule(&mut check, "-C");
+  if check.output().is_ok_and(|output| output.status.success()) {
+    return Ok(());
+  }
+
+  let mut append = Command::new(program);
+  write_rule(&mut append, "-A");
+  run_iptables(&mut append, action, user_id)
+}
+
+/// Deletes the rule `write_rule` writes with `program` if it's there.
+fn delete_rule_if_present(
+  program: &str,
+  write_rule: impl Fn(&mut Command, &str),
+  action: &str,
+  user_id: &UserId,
+) -> Result<(), GenericError> {
+  let mut check = Command::new(program);
+  write_rule(&mut check, "-C");
+  if !check.output().is_ok_and(|output| output.status.success()) {
+    return Ok(());
+  }
+
+  let mut delete = Command::new(program);
+  write_rule(&mut delete, "-D");
+  run_iptables(&mut delete, action, user_id)
+}
+
+fn write_dns_over_tls_rule(
+  command: &mut Command,
+  operation: &str,
+  user_id: &UserId,
+  protocol: &str,
+) {
+  command
+    .arg(operation)
+    .arg("OUTPUT")
+    .arg("-p")
+    .arg(protocol)
+    .arg("--dport")
+    .arg(ENCRYPTED_DNS_PORT)
+    .arg("-m")
+    .arg("owner")
+    .arg("--uid-owner")
+    .arg(user_id.as_raw().to_string())
+    .arg("-j")
+    .arg("DROP");
+}
+
+/// Drops the user's outgoing DNS over tls and DNS over quic traffic, which
+/// both use port 853, whichever server it's headed to.
+pub fn block_dns_over_tls_of_user(user_id: &UserId) -> Result<(), GenericError> {
+  for program in ["iptables", "ip6tables"] {
+    for protocol in ["tcp", "udp"] {
+      add_rule_once(
+        program,
+        |command, operation| write_dns_over_tls_rule(command, operation, user_id, protocol),
+        "blocking DNS over tls of user",
+        user_id,
+      )?;
+    }
+  }
+
+  Ok(())
+}
+
+/// Undoes `block_dns_over_tls_of_user`.
+pub fn unblock_dns_over_tls_of_user(user_id: &UserId) -> Result<(), GenericError> {
+  for program in ["iptables", "ip6tables"] {
+    for protocol in ["tcp", "udp"] {
+      delete_rule_if_present(
+        program,
+        |command, operation| write_dns_over_tls_rule(command, operation, user_id, protocol),
+        "unblocking DNS over tls of user",
+        user_id,
+      )?;
+    }
+  }
+
+  Ok(())
+}
+
+fn write_udp_address_rule(
+  command: &mut Command,
+  operation: &str,
+  user_id: &UserId,
+  address: IpAddr,
+  ports: &str,
+) {
+  command
+    .arg(operation)
+    .arg("OUTPUT")
+    .arg("-d")
+    .arg(address.to_string())
+    .arg("-p")
+    .arg("udp")
+    .arg("-m")
+    .arg("multiport")
+    .arg("--dports")
+    .arg(ports)
+    .arg("-m")
+    .arg("owner")
+    .arg("--uid-owner")
+    .arg(user_id.as_raw().to_string())
+    .arg("-j")
+    .arg("DROP");
+}
+
+fn program_for(address: IpAddr) -> &'static str {
+  match address {
+    IpAddr::V4(_) => "iptables",
+    IpAddr::V6(_) => "ip6tables",
+  }
+}
+
+/// Drops the user's outgoing udp traffic to `addresses` on `ports`, a
+/// comma-separated list.
+///
+/// Tcp traffic isn't covered since the user's web traffic is redirected to
+/// the proxy before these rules would see it.
+pub fn block_udp_traffic_of_user_to_addresses(
+  user_id: &UserId,
+  addresses: &[IpAddr],
+  ports: &str,
+) -> Result<(), GenericError> {
+  for address in addresses {
+    add_rule_once(
+      program_for(*address),
+      |command, operation| write_udp_address_rule(command, operation, user_id, *address, ports),
+      "blocking udp traffic of user to addresses",
+      user_id,
+    )?;
+  }
+
+  Ok(())
+}
+
+/// Undoes `block_udp_traffic_of_user_to_addresses`.
+pub fn unblock_udp_traffic_of_user_to_addresses(
+  user_id: &UserId,
+  addresses: &[IpAddr],
+  ports: &str,
+) -> Result<(), GenericError> {
+  for address in addresses {
+    delete_rule_if_present(
+      program_for(*address),
+      |command, operation| write_udp_address_rule(command, operation, user_id, *address, ports),
+      "unblocking udp traffic of user to addresses",
+      user_id,
+    )?;
+  }
+
+  Ok(())
+}
+
+fn write_dns_redirection_rule(
+  command: &mut Command,
+  operation: &str,
+  user_id: &UserId,
+  protocol: &str,
+  forwarder_port: u16,
+  purpose: &str,
+) {
+  command
+    .arg("-t")
+    .arg("nat")
+    .arg(operation)
+    .arg("OUTPUT")
+    .arg("-p")
+    .arg(protocol)
+    .arg("--dport")
+    .arg("53")
+    .arg("-m")
+    .arg("owner")
+    .arg("--uid-owner")
+    .arg(user_id.as_raw().to_string())
+    .arg("-m")
+    .arg("comment")
+    .arg("--comment")
+    .arg(purpose)
+    .arg("-j")
+    .arg("REDIRECT")
+    .arg("--to-ports")
+    .arg(forwarder_port.to_string());
+}
+
+/// Transparently redirects the user's plain DNS queries, over udp and tcp,
+/// to a DNS forwarder listening on this machine.
+///
+/// The rules are tagged with `purpose`, so each feature that needs the
+/// user's DNS redirected adds and removes rules of its own.
+pub fn redirect_dns_of_user_to_forwarder(
+  user_id: &UserId,
+  forwarder_port: u16,
+  purpose: &str,
+) -> Result<(), GenericError> {
+  for program in ["iptables", "ip6tables"] {
+    for protocol in ["udp", "tcp"] {
+      add_rule_once(
+        program,
+        |command, operation| write_dns_redirection_rule(command, operation, user_id, protocol, forwarder_port, purpose),
+        "redirecting DNS of user to forwarder",
+        user_id,
+      )?;
+    }
+  }
+
+  Ok(())
+}
+
+/// Undoes `redirect_dns_of_user_to_forwarder`.
+pub fn stop_redirecting_dns_of_user_to_forwarder(
+  user_id: &UserId,
+  forwarder_port: u16,
+  purpose: &str,
+) -> Result<(), GenericError> {
+  for program in ["iptables", "ip6tables"] {
+    for protocol in ["udp", "tcp"] {
+      delete_rule_if_present(
+        program,
+        |command, operation| write_dns_redirection_rule(command, operation, user_id, protocol, forwarder_port, purpose),
+        "stopping redirecting DNS of user to forwarder",
+        user_id,
+      )?;
+    }
+  }
+
+  Ok(())
+}
+
+fn write_port_rule(
+  command: &mut Command,
+  operation: &str,
+  user_id: &UserId,
+  protocol: &str,
+  ports: &str,
+) {
+  command
+    .arg(operation)
+    .arg("OUTPUT")
+    .arg("-p")
+    .arg(protocol)
+    .arg("-m")
+    .arg("multiport")
+    .arg("--dports")
+    .arg(ports)
+    .arg("-m")
+    .arg("owner")
+    .arg("--uid-owner")
+    .arg(user_id.as_raw().to_string())
+    .arg("-j")
+    .arg("DROP");
+}
+
+/// Drops the user's outgoing `protocol` traffic to `ports`, a comma-separated
+/// list, whichever address it's headed to.
+pub fn block_traffic_of_user_to_ports(
+  user_id: &UserId,
+  protocol: &str,
+  ports: &str,
+) -> Result<(), GenericError> {
+  for program in ["iptables", "ip6tables"] {
+    add_rule_once(
+      program,
+      |command, operation| write_port_rule(command, operation, user_id, protocol, ports),
+      "blocking traffic of user to ports",
+      user_id,
+    )?;
+  }
+
+  Ok(())
+}
+
+/// Undoes `block_traffic_of_user_to_ports`.
+pub fn unblock_traffic_of_user_to_ports(
+  user_id: &UserId,
+  protocol: &str,
+  ports: &str,
+) -> Result<(), GenericError> {
+  for program in ["iptables", "ip6tables"] {
+    delete_rule_if_present(
+      program,
+      |command, operation| write_port_rule(command, operation, user_id, protocol, ports),
+      "unblocking traffic of user to ports",
+      user_id,
+    )?;
+  }
+
+  Ok(())
+}
+
+fn write_interface_rule(
+  command: &mut Command,
+  operation: &str,
+  user_id: &UserId,
+  interface_name: &str,
+) {
+  command
+    .arg(operation)
+    .arg("OUTPUT")
+    .arg("-o")
+    .arg(interface_name)
+    .arg("-m")
+    .arg("owner")
+    .arg("--uid-owner")
+    .arg(user_id.as_raw().to_string())
+    .arg("-j")
+    .arg("DROP");
+}
+
+/// Drops the user's outgoing traffic routed through the network interface
+/// named `interface_name`.
+pub fn block_traffic_of_user_through_interface(
+  user_id: &UserId,
+  interface_name: &str,
+) -> Result<(), GenericError> {
+  for program in ["iptables", "ip6tables"] {
+    add_rule_once(
+      program,
+      |command, operation| write_interface_rule(command, operation, user_id, interface_name),
+      "blocking traffic of user through interface",
+      user_id,
+    )?;
+  }
+
+  Ok(())
+}
+
+/// Undoes `block_traffic_of_user_through_interface`.
+pub fn unblock_traffic_of_user_through_interface(
+  user_id: &UserId,
+  interface_name: &str,
+) -> Result<(), GenericError> {
+  for program in ["iptables", "ip6tables"] {
+    delete_rule_if_present(
+      program,
+      |command, operation| write_interface_rule(command, operation, user_id, interface_name),
+      "unblocking traffic of user through interface",
+      user_id,
+    )?;
+  }
+
+  Ok(())
+}
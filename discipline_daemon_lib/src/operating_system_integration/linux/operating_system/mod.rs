@@ -20,4 +20,22 @@ mod authentication;
 pub use authentication::*;
 
 mod user_session_control;
-pub use user_session_control::*;
\ No newline at end of file
+pub use user_session_control::*;
+
+mod sockets;
+pub use sockets::*;
+
+mod certificate_trust_store;
+pub use certificate_trust_store::*;
+
+mod network_interfaces;
+pub use network_interfaces::*;
+
+mod processes;
+pub use processes::*;
+
+mod system_proxy;
+pub use system_proxy::*;
+
+mod native_messaging_hosts;
+pub use native_messaging_hosts::*;
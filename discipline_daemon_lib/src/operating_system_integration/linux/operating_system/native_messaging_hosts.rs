@@ -0,0 +1,73 @@
+use std::path::{Path, PathBuf};
+use crate::GenericError;
+use super::system_proxy::{run_as_user, write_user_file};
+use super::*;
+
+/// Where Chromium, Chrome and Brave look for a user's native messaging
+/// host manifests, relative to the home directory.
+static CHROMIUM_MANIFEST_DIRECTORIES: [&str; 3] = [
+  ".config/chromium/NativeMessagingHosts",
+  ".config/google-chrome/NativeMessagingHosts",
+  ".config/BraveSoftware/Brave-Browser/NativeMessagingHosts",
+];
+
+/// Where Firefox looks for a user's native messaging host manifests,
+/// relative to the home directory.
+static FIREFOX_MANIFEST_DIRECTORY: &str = ".mozilla/native-messaging-hosts";
+
+fn manifest_path(user_home_directory: &Path, directory: &str, host_name: &str) -> PathBuf {
+  user_home_directory.join(directory).join(format!("{host_name}.json"))
+}
+
+/// Lets the browsers of a user find the native messaging host `host_name`
+/// by its manifests. Written as the user, like their proxy settings.
+pub fn install_native_messaging_host_manifests(
+  user_name: &UserName,
+  user_home_directory: &Path,
+  host_name: &str,
+  chromium_manifest: &str,
+  firefox_manifest: &str,
+) -> Result<(), GenericError> {
+  let action = "installing a user's native messaging host manifests";
+  let mut error_accumulator = None;
+
+  let manifests = CHROMIUM_MANIFEST_DIRECTORIES
+    .iter()
+    .map(|directory| (*directory, chromium_manifest))
+    .chain([(FIREFOX_MANIFEST_DIRECTORY, firefox_manifest)]);
+
+  for (directory, manifest) in manifests {
+    let path = manifest_path(user_home_directory, directory, host_name);
+    if let Err(error) = write_user_file(user_name, &path, manifest, action) {
+      error_accumulator = Some(error.add_attachment("user name", user_name.as_ref()));
+    }
+  }
+
+  match error_accumulator {
+    None => Ok(()),
+    Some(error) => Err(error),
+  }
+}
+
+pub fn remove_native_messaging_host_manifests(
+  user_name: &UserName,
+  user_home_directory: &Path,
+  host_name: &str,
+) -> Result<(), GenericError> {
+  let action = "removing a user's native messaging host manifests";
+  let mut error_accumulator = None;
+
+  let directories = CHROMIUM_MANIFEST_DIRECTORIES.iter().chain([&FIREFOX_MANIFEST_DIRECTORY]);
+  for directory in directories {
+    let path = manifest_path(user_home_directory, directory, host_name);
+    let result = run_as_user(user_name, &["rm", "-f", &path.to_string_lossy()], None, action);
+    if let Err(error) = result {
+      error_accumulator = Some(error.add_attachment("user name", user_name.as_ref()));
+    }
+  }
+
+  match error_accumulator {
+    None => Ok(()),
+    Some(error) => Err(error),
+  }
+}
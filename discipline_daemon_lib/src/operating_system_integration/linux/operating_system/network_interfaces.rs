@@ -0,0 +1,208 @@
+use std::fs;
+use std::mem::size_of;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use crate::GenericError;
+
+// Defined in "linux/rtnetlink.h" and "linux/if_link.h". Not all of them are
+// exposed by the libc crate.
+const RTMGRP_LINK: u32 = 1;
+const RTM_NEWLINK: u16 = 16;
+const RTM_DELLINK: u16 = 17;
+const IFLA_IFNAME: u16 = 3;
+const IFLA_LINKINFO: u16 = 18;
+const IFLA_INFO_KIND: u16 = 1;
+// Masks out the nested and byte order flags of attribute types.
+const NLA_TYPE_MASK: u16 = 0x3fff;
+
+const NETLINK_MESSAGE_HEADER_LENGTH: usize = 16;
+const INTERFACE_INFO_MESSAGE_LENGTH: usize = 16;
+const ATTRIBUTE_HEADER_LENGTH: usize = 4;
+
+const RECEIVE_BUFFER_LENGTH: usize = 64 * 1024;
+
+/// A change to the network interfaces reported by the kernel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterfaceChange {
+  /// The interface was added, or some of its properties changed.
+  AddedOrChanged {
+    name: String,
+    /// The kind of link, like "tun", "wireguard" or "bridge", if it isn't
+    /// a plain device.
+    kind: Option<String>,
+  },
+  Removed {
+    name: String,
+  },
+}
+
+/// Netlink lengths are padded to multiples of four bytes.
+fn align(length: usize) -> usize {
+  (length + 3) & !3
+}
+
+fn read_u16(buffer: &[u8], offset: usize) -> Option<u16> {
+  let bytes = buffer.get(offset..offset + 2)?;
+  Some(u16::from_ne_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(buffer: &[u8], offset: usize) -> Option<u32> {
+  let bytes = buffer.get(offset..offset + 4)?;
+  Some(u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// The type and payload of each route attribute in `buffer`.
+fn attributes(buffer: &[u8]) -> Vec<(u16, &[u8])> {
+  let mut attributes = Vec::new();
+  let mut offset = 0;
+  while let (Some(length), Some(attribute_type)) = (read_u16(buffer, offset), read_u16(buffer, offset + 2)) {
+    let length = length as usize;
+    let Some(payload) = buffer.get(offset + ATTRIBUTE_HEADER_LENGTH..offset + length) else {
+      break;
+    };
+
+    attributes.push((attribute_type & NLA_TYPE_MASK, payload));
+    offset += align(length.max(ATTRIBUTE_HEADER_LENGTH));
+  }
+
+  attributes
+}
+
+fn string_attribute(payload: &[u8]) -> String {
+  let end = payload.iter().position(|byte| *byte == 0).unwrap_or(payload.len());
+  String::from_utf8_lossy(&payload[..end]).into_owned()
+}
+
+/// Parses the link messages of a netlink datagram, skipping other messages.
+pub fn parse_interface_changes(buffer: &[u8]) -> Vec<InterfaceChange> {
+  let mut changes = Vec::new();
+  let mut offset = 0;
+  while let (Some(length), Some(message_type)) = (read_u32(buffer, offset), read_u16(buffer, offset + 4)) {
+    let length = length as usize;
+    if length < NETLINK_MESSAGE_HEADER_LENGTH {
+      break;
+    }
+
+    let Some(message) = buffer.get(offset..offset + length) else {
+      break;
+    };
+    offset += align(length);
+
+    if message_type != RTM_NEWLINK && message_type != RTM_DELLINK {
+      continue;
+    }
+
+    let Some(attributes_buffer) = message.get(NETLINK_MESSAGE_HEADER_LENGTH + INTERFACE_INFO_MESSAGE_LENGTH..) else {
+      continue;
+    };
+
+    let mut name = None;
+    let mut kind = None;
+    for (attribute_type, payload) in attributes(attributes_buffer) {
+      match attribute_type {
+        IFLA_IFNAME => {
+          name = Some(string_attribute(payload));
+        }
+        IFLA_LINKINFO => {
+          kind = attributes(payload)
+            .into_iter()
+            .find(|(nested_type, _)| *nested_type == IFLA_INFO_KIND)
+            .map(|(_, payload)| string_attribute(payload));
+        }
+        _ => {}
+      }
+    }
+
+    let Some(name) = name else {
+      continue;
+    };
+
+    if message_type == RTM_NEWLINK {
+      changes.push(InterfaceChange::AddedOrChanged { name, kind });
+    } else {
+      changes.push(InterfaceChange::Removed { name });
+    }
+  }
+
+  changes
+}
+
+/// Listens to the kernel's announcements of network interface changes.
+pub struct InterfaceMonitor {
+  socket: OwnedFd,
+}
+
+impl InterfaceMonitor {
+  pub fn open() -> Result<Self, GenericError> {
+    let socket = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, libc::NETLINK_ROUTE) };
+    if socket < 0 {
+      return Err(
+        GenericError::new("opening a network interface monitor")
+          .add_error("failed to create the netlink socket")
+          .add_attachment("io error", std::io::Error::last_os_error().to_string())
+      );
+    }
+
+    let socket = unsafe { OwnedFd::from_raw_fd(socket) };
+
+    let mut address: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+    address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    address.nl_groups = RTMGRP_LINK;
+
+    let status = unsafe {
+      libc::bind(
+        socket.as_raw_fd(),
+        &address as *const libc::sockaddr_nl as *const libc::sockaddr,
+        size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+      )
+    };
+
+    if status != 0 {
+      return Err(
+        GenericError::new("opening a network interface monitor")
+          .add_error("failed to join the link multicast group")
+          .add_attachment("io error", std::io::Error::last_os_error().to_string())
+      );
+    }
+
+    Ok(Self { socket })
+  }
+
+  /// Waits for the next announcement.
+  pub fn receive(&self) -> Result<Vec<InterfaceChange>, GenericError> {
+    let mut buffer = vec![0u8; RECEIVE_BUFFER_LENGTH];
+    let length = unsafe {
+      libc::recv(
+        self.socket.as_raw_fd(),
+        buffer.as_mut_ptr() as *mut libc::c_void,
+        buffer.len(),
+        0,
+      )
+    };
+
+    if length < 0 {
+      return Err(
+        GenericError::new("receiving network interface changes")
+          .add_error("failed to receive from the netlink socket")
+          .add_attachment("io error", std::io::Error::last_os_error().to_string())
+      );
+    }
+
+    Ok(parse_interface_changes(&buffer[..length as usize]))
+  }
+}
+
+/// The names of the network interfaces that exist now.
+pub fn retrieve_network_interface_names() -> Result<Vec<String>, GenericError> {
+  let entries = fs::read_dir("/sys/class/net").map_err(|error|
+    GenericError::new("retrieving network interface names")
+      .add_error("failed to read '/sys/class/net'")
+      .add_attachment("io error", error.to_string())
+  )?;
+
+  Ok(
+    entries
+      .filter_map(|entry| entry.ok())
+      .map(|entry| entry.file_name().to_string_lossy().into_owned())
+      .collect()
+  )
+}
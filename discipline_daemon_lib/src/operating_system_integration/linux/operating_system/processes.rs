@@ -0,0 +1,77 @@
+use std::fs;
+use crate::GenericError;
+use super::*;
+
+/// A running process, as "/proc" describes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessInfo {
+  pub process_id: u32,
+  /// The real user id of the process.
+  pub user_id: UserId,
+  /// The name of the executable, truncated to 15 bytes by the kernel.
+  pub name: String,
+  pub command_line: Vec<String>,
+}
+
+fn retrieve_process(process_id: u32) -> Option<ProcessInfo> {
+  let directory = format!("/proc/{process_id}");
+
+  let status = fs::read_to_string(format!("{directory}/status")).ok()?;
+  // "Uid:" is followed by the real, effective, saved and filesystem ids.
+  let user_id = status
+    .lines()
+    .find_map(|line| line.strip_prefix("Uid:"))?
+    .split_whitespace()
+    .next()?
+    .parse()
+    .ok()?;
+
+  let name = fs::read_to_string(format!("{directory}/comm")).ok()?;
+
+  // Arguments are separated, and terminated, by null bytes.
+  let command_line = fs::read(format!("{directory}/cmdline"))
+    .unwrap_or_default()
+    .split(|byte| *byte == 0)
+    .filter(|argument| !argument.is_empty())
+    .map(|argument| String::from_utf8_lossy(argument).into_owned())
+    .collect();
+
+  Some(ProcessInfo {
+    process_id,
+    user_id: UserId::new(user_id),
+    name: name.trim_end().to_string(),
+    command_line,
+  })
+}
+
+/// The processes running now. Processes that exit while they're being
+/// listed may be left out.
+pub fn retrieve_processes() -> Result<Vec<ProcessInfo>, GenericError> {
+  let entries = fs::read_dir("/proc").map_err(|error|
+    GenericError::new("retrieving processes")
+      .add_error("failed to read '/proc'")
+      .add_attachment("io error", error.to_string())
+  )?;
+
+  Ok(
+    entries
+      .filter_map(|entry| entry.ok())
+      .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+      .filter_map(retrieve_process)
+      .collect()
+  )
+}
+
+pub fn kill_process(process_id: u32) -> Result<(), GenericError> {
+  let status = unsafe { libc::kill(process_id as libc::pid_t, libc::SIGKILL) };
+  if status == 0 {
+    return Ok(());
+  }
+
+  Err(
+    GenericError::new("killing a process")
+      .add_error("kill failed")
+      .add_attachment("process id", process_id.to_string())
+      .add_attachment("io error", std::io::Error::last_os_error().to_string())
+  )
+}
@@ -0,0 +1,245 @@
+use std::fs;
+use std::mem::{size_of, MaybeUninit};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, TcpStream};
+use std::os::fd::AsRawFd;
+use super::*;
+
+// The value of the "st" column of "/proc/net/tcp" for established connections.
+const TCP_ESTABLISHED: &str = "01";
+
+// Defined in "linux/netfilter_ipv4.h". It isn't exposed by the libc crate.
+const SO_ORIGINAL_DST: libc::c_int = 80;
+
+// Defined in "linux/netfilter_ipv6/ip6_tables.h". It isn't exposed by the libc crate.
+const IP6T_SO_ORIGINAL_DST: libc::c_int = 80;
+
+/// Finds the user owning the local end of a tcp connection given that end's
+/// address.
+///
+/// When a process connects to a server running on this machine, the address
+/// the server sees as the peer address is the local address of the process's
+/// socket, which "/proc/net/tcp" lists alongside the id of the user owning it.
+pub fn retrieve_tcp_socket_owner(local_address: SocketAddr) -> Option<UserId> {
+  let table_path = match local_address {
+    SocketAddr::V4(_) => "/proc/net/tcp",
+    SocketAddr::V6(_) => "/proc/net/tcp6",
+  };
+
+  retrieve_socket_owner(table_path, |socket_local_address, socket_state| {
+    socket_state == TCP_ESTABLISHED && socket_local_address == local_address
+  })
+}
+
+/// Finds the user owning the udp socket a datagram was sent from given the
+/// datagram's source address.
+///
+/// Unlike tcp sockets, udp sockets that were never connected are listed with
+/// the unspecified address, so those bound to the source port match too.
+pub fn retrieve_udp_socket_owner(source_address: SocketAddr) -> Option<UserId> {
+  let table_path = match source_address {
+    SocketAddr::V4(_) => "/proc/net/udp",
+    SocketAddr::V6(_) => "/proc/net/udp6",
+  };
+
+  retrieve_socket_owner(table_path, |socket_local_address, _socket_state| {
+    socket_local_address.port() == source_address.port()
+      && (
+        socket_local_address.ip() == source_address.ip()
+          || socket_local_address.ip().is_unspecified()
+      )
+  })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransportProtocol {
+  Tcp,
+  Udp,
+}
+
+/// A socket connected to a remote address, and the user owning it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConnectedSocket {
+  pub protocol: TransportProtocol,
+  pub remote_address: SocketAddr,
+  pub user_id: UserId,
+}
+
+/// Lists the tcp and udp sockets that are connected to, or connecting to,
+/// a remote address. Listening and unconnected sockets are left out.
+pub fn retrieve_connected_sockets() -> Vec<ConnectedSocket> {
+  let mut sockets = Vec::new();
+  for (table_path, protocol) in [
+    ("/proc/net/tcp", TransportProtocol::Tcp),
+    ("/proc/net/tcp6", TransportProtocol::Tcp),
+    ("/proc/net/udp", TransportProtocol::Udp),
+    ("/proc/net/udp6", TransportProtocol::Udp),
+  ] {
+    let Ok(table) = fs::read_to_string(table_path) else {
+      continue;
+    };
+
+    for line in table.lines().skip(1) {
+      let columns: Vec<&str> = line.split_whitespace().collect();
+      let (Some(remote_address), Some(owner)) = (columns.get(2), columns.get(7)) else {
+        continue;
+      };
+
+      let (Some(remote_address), Ok(owner)) = (parse_proc_net_address(remote_address), owner.parse()) else {
+        continue;
+      };
+
+      if remote_address.port() == 0 {
+        continue;
+      }
+
+      sockets.push(ConnectedSocket {
+        protocol,
+        remote_address,
+        user_id: UserId::new(owner),
+      });
+    }
+  }
+
+  sockets
+}
+
+/// Finds the owner of the first socket in the "/proc/net" table at
+/// `table_path` whose local address and state `matches` accepts.
+fn retrieve_socket_owner(
+  table_path: &str,
+  matches: impl Fn(SocketAddr, &str) -> bool,
+) -> Option<UserId> {
+  let table = fs::read_to_string(table_path).ok()?;
+
+  // The first line is the table's header.
+  for line in table.lines().skip(1) {
+    let mut columns = line.split_whitespace();
+    let (
+      Some(_slot),
+      Some(socket_local_address),
+      Some(_socket_remote_address),
+      Some(socket_state),
+      Some(_queues),
+      Some(_timer),
+      Some(_retransmits),
+      Some(socket_owner),
+    ) = (
+      columns.next(),
+      columns.next(),
+      columns.next(),
+      columns.next(),
+      columns.next(),
+      columns.next(),
+      columns.next(),
+      columns.next(),
+    ) else {
+      continue;
+    };
+
+    let Some(socket_local_address) = parse_proc_net_address(socket_local_address) else {
+      continue;
+    };
+
+    if !matches(socket_local_address, socket_state) {
+      continue;
+    }
+
+    return socket_owner.parse().ok().map(UserId::new);
+  }
+
+  None
+}
+
+// Addresses in "/proc/net/tcp" look like "0100007F:1F90" where the ip address
+// is made of native-endian 32-bit words and the port is big-endian.
+fn parse_proc_net_address(address: &str) -> Option<SocketAddr> {
+  let (ip_address, port) = address.split_once(':')?;
+  let port = u16::from_str_radix(port, 16).ok()?;
+
+  let ip_address = match ip_address.len() {
+    8 => {
+      let word = u32::from_str_radix(ip_address, 16).ok()?;
+      IpAddr::V4(Ipv4Addr::from(word.to_ne_bytes()))
+    }
+    32 => {
+      let mut octets = [0u8; 16];
+      for index in 0..4 {
+        let word = u32::from_str_radix(&ip_address[index * 8..index * 8 + 8], 16).ok()?;
+        octets[index * 4..index * 4 + 4].copy_from_slice(&word.to_ne_bytes());
+      }
+
+      let ip_address = Ipv6Addr::from(octets);
+      // Ipv4 connections to dual-stack sockets are listed as ipv4-mapped ipv6 addresses.
+      match ip_address.to_ipv4_mapped() {
+        Some(ip_address) => IpAddr::V4(ip_address),
+        None => IpAddr::V6(ip_address),
+      }
+    }
+    _ => {
+      return None;
+    }
+  };
+
+  Some(SocketAddr::new(ip_address, port))
+}
+
+/// Retrieves the address a connection was originally destined to before
+/// an iptables or ip6tables REDIRECT rule redirected it to us.
+pub fn retrieve_original_destination(stream: &TcpStream) -> Option<SocketAddr> {
+  match stream.local_addr().ok()? {
+    SocketAddr::V4(_) => retrieve_original_ipv4_destination(stream).map(SocketAddr::V4),
+    SocketAddr::V6(_) => retrieve_original_ipv6_destination(stream).map(SocketAddr::V6),
+  }
+}
+
+fn retrieve_original_ipv4_destination(stream: &TcpStream) -> Option<SocketAddrV4> {
+  unsafe {
+    let mut address = MaybeUninit::<libc::sockaddr_in>::zeroed();
+    let mut address_length = size_of::<libc::sockaddr_in>() as libc::socklen_t;
+
+    let status = libc::getsockopt(
+      stream.as_raw_fd(),
+      libc::SOL_IP,
+      SO_ORIGINAL_DST,
+      address.as_mut_ptr() as *mut libc::c_void,
+      &mut address_length,
+    );
+
+    if status != 0 {
+      return None;
+    }
+
+    let address = address.assume_init();
+    Some(SocketAddrV4::new(
+      Ipv4Addr::from(u32::from_be(address.sin_addr.s_addr)),
+      u16::from_be(address.sin_port),
+    ))
+  }
+}
+
+fn retrieve_original_ipv6_destination(stream: &TcpStream) -> Option<SocketAddrV6> {
+  unsafe {
+    let mut address = MaybeUninit::<libc::sockaddr_in6>::zeroed();
+    let mut address_length = size_of::<libc::sockaddr_in6>() as libc::socklen_t;
+
+    let status = libc::getsockopt(
+      stream.as_raw_fd(),
+      libc::SOL_IPV6,
+      IP6T_SO_ORIGINAL_DST,
+      address.as_mut_ptr() as *mut libc::c_void,
+      &mut address_length,
+    );
+
+    if status != 0 {
+      return None;
+    }
+
+    let address = address.assume_init();
+    Some(SocketAddrV6::new(
+      Ipv6Addr::from(address.sin6_addr.s6_addr),
+      u16::from_be(address.sin6_port),
+      u32::from_be(address.sin6_flowinfo),
+      address.sin6_scope_id,
+    ))
+  }
+}
@@ -0,0 +1,285 @@
+use std::env;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use crate::GenericError;
+use super::*;
+
+/// KDE's proxy settings, relative to the home directory.
+static KDE_PROXY_SETTINGS_FILE: &str = ".config/kioslaverc";
+static KDE_PROXY_SETTINGS_SECTION: &str = "Proxy Settings";
+static KDE_PROXY_TYPE: &str = "ProxyType";
+static KDE_PROXY_CONFIG_SCRIPT: &str = "Proxy Config Script";
+/// The proxy types KDE uses for no proxy and for a proxy auto-config url.
+static KDE_PROXY_TYPE_NONE: &str = "0";
+static KDE_PROXY_TYPE_AUTOMATIC: &str = "2";
+
+/// Read by systemd when starting a user's session, relative to the home
+/// directory.
+static ENVIRONMENT_FILE: &str = ".config/environment.d/60-discipline-proxy.conf";
+
+static GNOME_PROXY_SCHEMA: &str = "org.gnome.system.proxy";
+
+/// Runs a program as a user, so it can only touch the files the user can and
+/// what it creates belongs to them. Returns what the program printed.
+pub(super) fn run_as_user(
+  user_name: &UserName,
+  program_and_arguments: &[&str],
+  input: Option<&str>,
+  action: &str,
+) -> Result<String, GenericError> {
+  let mut command = Command::new("runuser");
+  command
+    .arg("-u")
+    .arg(user_name.as_ref())
+    .arg("--")
+    .args(program_and_arguments)
+    .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped());
+
+  let description = format!("{command:?}");
+  let spawn_error = |error: std::io::Error| {
+    GenericError::new(action)
+      .add_error("failed to execute command")
+      .add_attachment("command", description.clone())
+      .add_attachment("io error", error.to_string())
+  };
+
+  let mut child = command.spawn().map_err(spawn_error)?;
+  if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+    stdin.write_all(input.as_bytes()).map_err(spawn_error)?;
+  }
+
+  let output = child.wait_with_output().map_err(spawn_error)?;
+  if output.status.success() {
+    return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+  }
+
+  Err(
+    GenericError::new(action)
+      .add_error("command failed")
+      .add_attachment("command", description)
+      .add_attachment("stderr", String::from_utf8_lossy(&output.stderr))
+  )
+}
+
+fn is_installed(program: &str) -> bool {
+  env::var_os("PATH").is_some_and(|paths| {
+    env::split_paths(&paths).any(|directory| directory.join(program).is_file())
+  })
+}
+
+/// Looks up a key of a section of an ini file like KDE's.
+fn ini_value(contents: &str, section: &str, key: &str) -> Option<String> {
+  let header = format!("[{section}]");
+  contents
+    .lines()
+    .skip_while(|line| line.trim() != header)
+    .skip(1)
+    .take_while(|line| !line.trim_start().starts_with('['))
+    .filter_map(|line| line.split_once('='))
+    .find(|(name, _)| name.trim() == key)
+    .map(|(_, value)| value.trim().to_string())
+}
+
+/// Sets, or removes where `None`, keys of a section of an ini file like
+/// KDE's, keeping everything else as it was.
+fn with_ini_values(contents: &str, section: &str, values: &[(&str, Option<&str>)]) -> String {
+  let mut lines: Vec<String> = contents.lines().map(String::from).collect();
+
+  let header = format!("[{section}]");
+  let start = match lines.iter().position(|line| line.trim() == header) {
+    Some(index) => {
+      index + 1
+    }
+    None => {
+      if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+        lines.push(String::new());
+      }
+      lines.push(header);
+      lines.len()
+    }
+  };
+
+  let mut end = lines[start..]
+    .iter()
+    .position(|line| line.trim_start().starts_with('['))
+    .map_or(lines.len(), |offset| start + offset);
+
+  for (key, value) in values {
+    let existing = lines[start..end]
+      .iter()
+      .position(|line| line.split_once('=').is_some_and(|(name, _)| name.trim() == *key))
+      .map(|offset| start + offset);
+
+    match (existing, value) {
+      (Some(index), Some(value)) => {
+        lines[index] = format!("{key}={value}");
+      }
+      (Some(index), None) => {
+        lines.remove(index);
+        end -= 1;
+      }
+      (None, Some(value)) => {
+        // Before the blank lines separating the section from the next.
+        let mut index = end;
+        while index > start && lines[index - 1].trim().is_empty() {
+          index -= 1;
+        }
+        lines.insert(index, format!("{key}={value}"));
+        end += 1;
+      }
+      (None, None) => {}
+    }
+  }
+
+  let mut contents = lines.join("\n");
+  contents.push('\n');
+  contents
+}
+
+fn read_user_file(user_name: &UserName, path: &Path, action: &str) -> Result<String, GenericError> {
+  if !path.exists() {
+    return Ok(String::new());
+  }
+
+  run_as_user(user_name, &["cat", &path.to_string_lossy()], None, action)
+}
+
+pub(super) fn write_user_file(user_name: &UserName, path: &Path, contents: &str, action: &str) -> Result<(), GenericError> {
+  if let Some(directory) = path.parent() {
+    run_as_user(user_name, &["mkdir", "-p", &directory.to_string_lossy()], None, action)?;
+  }
+
+  run_as_user(user_name, &["tee", &path.to_string_lossy()], Some(contents), action).map(|_| ())
+}
+
+/// Points the proxy settings of a user's desktop sessions at a proxy
+/// auto-config url, for GNOME and KDE, and sets the proxy environment
+/// variables command line programs use to `proxy_address`.
+///
+/// Everything is done as the user, so their files keep their ownership and
+/// links they placed can't make us write elsewhere.
+pub fn install_system_proxy_settings(
+  user_name: &UserName,
+  user_home_directory: &Path,
+  proxy_auto_config_url: &str,
+  proxy_address: SocketAddr,
+) -> Result<(), GenericError> {
+  let action = "installing a user's system proxy settings";
+  let mut error_accumulator = None;
+
+  // "gsettings" needs a session bus to reach dconf, which the user may not
+  // have running.
+  if is_installed("gsettings") && is_installed("dbus-run-session") {
+    for (key, value) in [("autoconfig-url", proxy_auto_config_url), ("mode", "auto")] {
+      let result = run_as_user(
+        user_name,
+        &["dbus-run-session", "--", "gsettings", "set", GNOME_PROXY_SCHEMA, key, value],
+        None,
+        action,
+      );
+
+      if let Err(error) = result {
+        error_accumulator = Some(error.add_attachment("user name", user_name.as_ref()));
+      }
+    }
+  }
+
+  let kde_settings_path = user_home_directory.join(KDE_PROXY_SETTINGS_FILE);
+  let result = read_user_file(user_name, &kde_settings_path, action).and_then(|contents| {
+    let contents = with_ini_values(&contents, KDE_PROXY_SETTINGS_SECTION, &[
+      (KDE_PROXY_TYPE, Some(KDE_PROXY_TYPE_AUTOMATIC)),
+      (KDE_PROXY_CONFIG_SCRIPT, Some(proxy_auto_config_url)),
+    ]);
+    write_user_file(user_name, &kde_settings_path, &contents, action)
+  });
+
+  if let Err(error) = result {
+    error_accumulator = Some(error.add_attachment("user name", user_name.as_ref()));
+  }
+
+  // Environment variables can't tell proxied hosts apart, so everything is
+  // sent through the proxy, which tunnels what it doesn't regulate.
+  let proxy_url = format!("http://{proxy_address}");
+  let mut environment = String::new();
+  for name in ["http_proxy", "https_proxy", "HTTP_PROXY", "HTTPS_PROXY"] {
+    environment.push_str(&format!("{name}={proxy_url}\n"));
+  }
+  for name in ["no_proxy", "NO_PROXY"] {
+    environment.push_str(&format!("{name}=localhost,127.0.0.1,::1\n"));
+  }
+
+  let result = write_user_file(
+    user_name,
+    &user_home_directory.join(ENVIRONMENT_FILE),
+    &environment,
+    action,
+  );
+
+  if let Err(error) = result {
+    error_accumulator = Some(error.add_attachment("user name", user_name.as_ref()));
+  }
+
+  match error_accumulator {
+    None => Ok(()),
+    Some(error) => Err(error),
+  }
+}
+
+/// Removes the settings installed by `install_system_proxy_settings`,
+/// leaving KDE's alone if the user pointed them elsewhere since.
+pub fn remove_system_proxy_settings(
+  user_name: &UserName,
+  user_home_directory: &Path,
+  proxy_auto_config_url: &str,
+) -> Result<(), GenericError> {
+  let action = "removing a user's system proxy settings";
+  let mut error_accumulator = None;
+
+  if is_installed("gsettings") && is_installed("dbus-run-session") {
+    for key in ["mode", "autoconfig-url"] {
+      let result = run_as_user(
+        user_name,
+        &["dbus-run-session", "--", "gsettings", "reset", GNOME_PROXY_SCHEMA, key],
+        None,
+        action,
+      );
+
+      if let Err(error) = result {
+        error_accumulator = Some(error.add_attachment("user name", user_name.as_ref()));
+      }
+    }
+  }
+
+  let kde_settings_path = user_home_directory.join(KDE_PROXY_SETTINGS_FILE);
+  let result = read_user_file(user_name, &kde_settings_path, action).and_then(|contents| {
+    let config_script = ini_value(&contents, KDE_PROXY_SETTINGS_SECTION, KDE_PROXY_CONFIG_SCRIPT);
+    if config_script.as_deref() != Some(proxy_auto_config_url) {
+      return Ok(());
+    }
+
+    let contents = with_ini_values(&contents, KDE_PROXY_SETTINGS_SECTION, &[
+      (KDE_PROXY_TYPE, Some(KDE_PROXY_TYPE_NONE)),
+      (KDE_PROXY_CONFIG_SCRIPT, None),
+    ]);
+    write_user_file(user_name, &kde_settings_path, &contents, action)
+  });
+
+  if let Err(error) = result {
+    error_accumulator = Some(error.add_attachment("user name", user_name.as_ref()));
+  }
+
+  let environment_path = user_home_directory.join(ENVIRONMENT_FILE);
+  let result = run_as_user(user_name, &["rm", "-f", &environment_path.to_string_lossy()], None, action);
+  if let Err(error) = result {
+    error_accumulator = Some(error.add_attachment("user name", user_name.as_ref()));
+  }
+
+  match error_accumulator {
+    None => Ok(()),
+    Some(error) => Err(error),
+  }
+}
@@ -0,0 +1,584 @@
+//! Runs llhttp's own markdown test fixtures, in `llhttp/test`, through `Parser`.
+//!
+//! Each fixture is an http message followed by the log llhttp's test binary
+//! prints while parsing it. `Logger` prints the same log from our callbacks.
+//! Callbacks aren't told where in the input they happen, so only spans,
+//! pauses and errors are compared with their offsets.
+//!
+//! Unless a fixture is marked `noScan`, it's also parsed a byte at a time,
+//! with the pieces of split spans joined back together, like llhttp does.
+
+use std::path::{Path, PathBuf};
+use super::*;
+
+// SECTION: Fixtures.
+struct Fixture {
+  /// Where the fixture is, for failure messages.
+  location: String,
+  meta: serde_json::Value,
+  input: Vec<u8>,
+  expected: Vec<String>,
+}
+
+fn fixtures_directory() -> PathBuf {
+  Path::new(env!("CARGO_MANIFEST_DIR")).join("../llhttp/test")
+}
+
+/// Turns the escapes in a fixture's http block into the bytes they stand
+/// for, like llhttp's `md-test.ts`.
+fn unescape_input(input: &str) -> Vec<u8> {
+  let input = input
+    .replace("\\\r\n", "")
+    .replace("\\\n", "")
+    .replace("\r\n", "\n")
+    .replace('\n', "\r\n")
+    .replace("\\r", "\r")
+    .replace("\\n", "\n")
+    .replace("\\t", "\t")
+    .replace("\\f", "\x0c");
+
+  let input = replace_numeric_escapes(&input, 'x', 16, usize::MAX);
+  let input = replace_numeric_escapes(&input, '0', 8, 3);
+
+  // md-test.ts passes the input to the test binary as a javascript string,
+  // so characters made by the escapes above are encoded as UTF-8.
+  input.into_bytes()
+}
+
+/// Replaces escapes like `\xff` or `\101` with the character they stand
+/// for. An octal escape has no marker, so `marker` is '0' for those.
+fn replace_numeric_escapes(input: &str, marker: char, radix: u32, maximum_digits: usize) -> String {
+  let mut output = String::with_capacity(input.len());
+  let mut chars = input.chars().peekable();
+
+  while let Some(char) = chars.next() {
+    if char != '\\' {
+      output.push(char);
+      continue;
+    }
+
+    if marker != '0' {
+      if chars.peek() != Some(&marker) {
+        output.push(char);
+        continue;
+      }
+
+      let mut lookahead = chars.clone();
+      lookahead.next();
+      if !lookahead.peek().is_some_and(|next| next.is_digit(radix)) {
+        output.push(char);
+        continue;
+      }
+
+      chars.next();
+    }
+
+    let mut digits = String::new();
+    while digits.len() < maximum_digits {
+      match chars.peek() {
+        Some(digit) if digit.is_digit(radix) => {
+          digits.push(*digit);
+          chars.next();
+        }
+        _ => {
+          break;
+        }
+      }
+    }
+
+    if digits.is_empty() {
+      output.push(char);
+      continue;
+    }
+
+    // Like javascript's String.fromCharCode.
+    let code = u32::from_str_radix(&digits, radix).unwrap_or(u32::MAX) & 0xffff;
+    output.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+  }
+
+  output
+}
+
+fn read_fixtures(name: &str) -> Vec<Fixture> {
+  let path = fixtures_directory().join(format!("{name}.md"));
+  let markdown = std::fs::read_to_string(&path)
+    .unwrap_or_else(|error| panic!("failed to read {}: {error}", path.display()));
+
+  let mut fixtures = Vec::new();
+  let mut meta = None;
+  let mut input = None;
+  let mut block: Option<(String, usize, Vec<&str>)> = None;
+
+  for (index, line) in markdown.lines().enumerate() {
+    if let Some((kind, start, lines)) = &mut block {
+      if line.trim_end() != "```" {
+        lines.push(line);
+        continue;
+      }
+
+      let content = lines.join("\n");
+      match kind.as_str() {
+        "http" => {
+          input = Some((*start, content));
+        }
+        "log" => {
+          let (Some(meta), Some((start, input))) = (meta.take(), input.take()) else {
+            panic!("{name}.md:{}: log block without a preceding http block and meta", *start + 1);
+          };
+
+          fixtures.push(Fixture {
+            location: format!("{name}.md:{}", start + 1),
+            meta,
+            input: unescape_input(&input),
+            expected: content
+              .replace("\\t", "\t")
+              .replace("\\f", "\x0c")
+              .lines()
+              .map(str::to_string)
+              .collect(),
+          });
+        }
+        _ => {}
+      }
+
+      block = None;
+      continue;
+    }
+
+    if let Some(kind) = line.strip_prefix("```") {
+      block = Some((kind.trim().to_string(), index, Vec::new()));
+    } else if let Some(json) = line
+      .strip_prefix("<!-- meta=")
+      .and_then(|line| line.strip_suffix("-->"))
+    {
+      meta = Some(serde_json::from_str(json.trim())
+        .unwrap_or_else(|error| panic!("{name}.md:{}: invalid meta: {error}", index + 1)));
+    }
+  }
+
+  assert!(!fixtures.is_empty(), "{name}.md has no fixtures");
+  fixtures
+}
+
+// SECTION: Logging.
+enum Line {
+  Span {
+    offset: usize,
+    name: &'static str,
+    data: Vec<u8>,
+  },
+  Event(String),
+}
+
+struct Logger {
+  is_request: bool,
+  pause_on: Option<String>,
+  skip_body: bool,
+  /// Offset in the whole input of the data passed to the running `parse`.
+  base: usize,
+  data_address: usize,
+  lines: Vec<Line>,
+}
+
+impl Logger {
+  fn span(&mut self, name: &'static str, data: &[u8]) -> Flow {
+    let offset = self.base + (data.as_ptr() as usize).saturating_sub(self.data_address);
+
+    // Spans split across calls to `parse` are joined back together.
+    if let Some(Line::Span { offset: last_offset, name: last_name, data: last_data }) = self.lines.last_mut() {
+      if *last_name == name && *last_offset + last_data.len() == offset {
+        last_data.extend_from_slice(data);
+        return Flow::Continue;
+      }
+    }
+
+    self.lines.push(Line::Span {
+      offset,
+      name,
+      data: data.to_vec(),
+    });
+    Flow::Continue
+  }
+
+  fn event(&mut self, event: impl Into<String>, callback: &str) -> Flow {
+    self.lines.push(Line::Event(event.into()));
+
+    if self.pause_on.as_deref() == Some(callback) {
+      Flow::Pause
+    } else {
+      Flow::Continue
+    }
+  }
+
+  /// Prints the log like llhttp's test binary, which prints the pieces of a
+  /// span between line breaks separately.
+  fn print(&self) -> Vec<String> {
+    let mut printed = Vec::new();
+
+    for line in &self.lines {
+      let (offset, name, data) = match line {
+        Line::Event(event) => {
+          printed.push(event.clone());
+          continue;
+        }
+        Line::Span { offset, name, data } => {
+          (*offset, *name, data)
+        }
+      };
+
+      if data.is_empty() {
+        printed.push(format!("off={offset} len=0 span[{name}]=\"\""));
+        continue;
+      }
+
+      let mut start = 0;
+      for (index, byte) in data.iter().enumerate() {
+        if *byte != b'\r' && *byte != b'\n' {
+          continue;
+        }
+
+        if start < index {
+          printed.push(print_span_piece(offset + start, name, &data[start..index]));
+        }
+
+        let byte_name = if *byte == b'\r' { "cr" } else { "lf" };
+        printed.push(format!("off={} len=1 span[{name}]={byte_name}", offset + index));
+        start = index + 1;
+      }
+
+      if start < data.len() {
+        printed.push(print_span_piece(offset + start, name, &data[start..]));
+      }
+    }
+
+    printed
+  }
+}
+
+fn print_span_piece(offset: usize, name: &str, data: &[u8]) -> String {
+  let text = String::from_utf8_lossy(data);
+  format!("off={offset} len={} span[{name}]=\"{text}\"", data.len())
+}
+
+impl Callbacks for Logger {
+  fn on_message_begin(&mut self, _: &ParserState<'_>) -> Flow {
+    self.event("message begin", "on_message_begin")
+  }
+
+  fn on_message_complete(&mut self, _: &ParserState<'_>) -> Flow {
+    self.event("message complete", "on_message_complete")
+  }
+
+  fn on_protocol(&mut self, _: &ParserState<'_>, data: &[u8]) -> Flow {
+    self.span("protocol", data)
+  }
+
+  fn on_protocol_complete(&mut self, _: &ParserState<'_>) -> Flow {
+    self.event("protocol complete", "on_protocol_complete")
+  }
+
+  fn on_url(&mut self, _: &ParserState<'_>, data: &[u8]) -> Flow {
+    self.span("url", data)
+  }
+
+  fn on_url_complete(&mut self, _: &ParserState<'_>) -> Flow {
+    self.event("url complete", "on_url_complete")
+  }
+
+  fn on_status(&mut self, _: &ParserState<'_>, data: &[u8]) -> Flow {
+    self.span("status", data)
+  }
+
+  fn on_status_complete(&mut self, _: &ParserState<'_>) -> Flow {
+    self.event("status complete", "on_status_complete")
+  }
+
+  fn on_method(&mut self, _: &ParserState<'_>, data: &[u8]) -> Flow {
+    if !self.is_request {
+      return Flow::Continue;
+    }
+
+    self.span("method", data)
+  }
+
+  fn on_method_complete(&mut self, _: &ParserState<'_>) -> Flow {
+    self.event("method complete", "on_method_complete")
+  }
+
+  fn on_version(&mut self, _: &ParserState<'_>, data: &[u8]) -> Flow {
+    self.span("version", data)
+  }
+
+  fn on_version_complete(&mut self, _: &ParserState<'_>) -> Flow {
+    self.event("version complete", "on_version_complete")
+  }
+
+  fn on_header_field(&mut self, _: &ParserState<'_>, data: &[u8]) -> Flow {
+    self.span("header_field", data)
+  }
+
+  fn on_header_field_complete(&mut self, _: &ParserState<'_>) -> Flow {
+    self.event("header_field complete", "on_header_field_complete")
+  }
+
+  fn on_header_value(&mut self, _: &ParserState<'_>, data: &[u8]) -> Flow {
+    self.span("header_value", data)
+  }
+
+  fn on_header_value_complete(&mut self, _: &ParserState<'_>) -> Flow {
+    self.event("header_value complete", "on_header_value_complete")
+  }
+
+  fn on_headers_complete(&mut self, parser: &ParserState<'_>) -> Flow {
+    let inner = parser.inner;
+    let event = if self.is_request {
+      format!(
+        "headers complete method={} v={}/{} flags={:x} content_length={}",
+        inner.method, inner.http_major, inner.http_minor, inner.flags, inner.content_length,
+      )
+    } else {
+      format!(
+        "headers complete status={} v={}/{} flags={:x} content_length={}",
+        inner.status_code, inner.http_major, inner.http_minor, inner.flags, inner.content_length,
+      )
+    };
+
+    let flow = self.event(event, "on_headers_complete");
+    if flow == Flow::Continue && self.skip_body {
+      self.lines.push(Line::Event("skip body".to_string()));
+      return Flow::SkipBody;
+    }
+
+    flow
+  }
+
+  fn on_body(&mut self, _: &ParserState<'_>, data: &[u8]) -> Flow {
+    self.span("body", data)
+  }
+
+  fn on_chunk_header(&mut self, parser: &ParserState<'_>) -> Flow {
+    let event = format!("chunk header len={}", parser.inner.content_length as libc::c_int);
+    self.event(event, "on_chunk_header")
+  }
+
+  fn on_chunk_extension_name(&mut self, _: &ParserState<'_>, data: &[u8]) -> Flow {
+    self.span("chunk_extension_name", data)
+  }
+
+  fn on_chunk_extension_name_complete(&mut self, _: &ParserState<'_>) -> Flow {
+    self.event("chunk_extension_name complete", "on_chunk_extension_name")
+  }
+
+  fn on_chunk_extension_value(&mut self, _: &ParserState<'_>, data: &[u8]) -> Flow {
+    self.span("chunk_extension_value", data)
+  }
+
+  fn on_chunk_extension_value_complete(&mut self, _: &ParserState<'_>) -> Flow {
+    self.event("chunk_extension_value complete", "on_chunk_extension_value")
+  }
+
+  fn on_chunk_complete(&mut self, _: &ParserState<'_>) -> Flow {
+    self.event("chunk complete", "on_chunk_complete")
+  }
+
+  fn on_reset(&mut self, _: &ParserState<'_>) -> Flow {
+    self.event("reset", "on_reset")
+  }
+}
+
+// SECTION: Running.
+/// Makes the parser a fixture's type asks for, like "request-lenient-headers".
+fn new_parser(test_type: &str) -> Parser {
+  let (mut parser, options) = if let Some(options) = test_type.strip_prefix("request") {
+    (Parser::new_request_parser(), options)
+  } else if let Some(options) = test_type.strip_prefix("response") {
+    (Parser::new_response_parser(), options)
+  } else {
+    panic!("unknown fixture type {test_type:?}");
+  };
+
+  match options {
+    "" | "-finish" => {}
+    "-lenient-all" => {
+      parser.set_lenient_headers(true);
+      parser.set_lenient_chunked_length(true);
+      parser.set_lenient_keep_alive(true);
+      parser.set_lenient_transfer_encoding(true);
+      parser.set_lenient_version(true);
+      parser.set_lenient_data_after_close(true);
+      parser.set_lenient_optional_lf_after_cr(true);
+      parser.set_lenient_optional_cr_before_lf(true);
+      parser.set_lenient_optional_crlf_after_chunk(true);
+    }
+    "-lenient-headers" => parser.set_lenient_headers(true),
+    "-lenient-chunked-length" => parser.set_lenient_chunked_length(true),
+    "-lenient-keep-alive" => parser.set_lenient_keep_alive(true),
+    "-lenient-transfer-encoding" => parser.set_lenient_transfer_encoding(true),
+    "-lenient-version" => parser.set_lenient_version(true),
+    "-lenient-data-after-close" => parser.set_lenient_data_after_close(true),
+    "-lenient-optional-lf-after-cr" => parser.set_lenient_optional_lf_after_cr(true),
+    "-lenient-optional-cr-before-lf" => parser.set_lenient_optional_cr_before_lf(true),
+    "-lenient-optional-crlf-after-chunk" => parser.set_lenient_optional_crlf_after_chunk(true),
+    "-lenient-spaces-after-chunk-size" => parser.set_lenient_spaces_after_chunk_size(true),
+    _ => panic!("unknown fixture type {test_type:?}"),
+  }
+
+  parser
+}
+
+/// Parses `data`, resuming after pauses. Returns false once parsing stopped
+/// for good.
+fn parse(parser: &mut Parser, logger: &mut Logger, data: &[u8]) -> bool {
+  let mut start = 0;
+
+  loop {
+    let data = &data[start..];
+    logger.data_address = data.as_ptr() as usize;
+
+    match parser.parse(logger, data) {
+      Ok(Parsed::Done) => {
+        logger.base += data.len();
+        return true;
+      }
+      Ok(Parsed::Paused { offset }) => {
+        logger.lines.push(Line::Event(format!("off={} pause", logger.base + offset)));
+        logger.base += offset;
+        start += offset;
+        parser.resume();
+      }
+      Ok(Parsed::Upgraded { offset }) => {
+        // llhttp's test binary treats this like any other error.
+        let error = parser.error(data);
+        logger.lines.push(Line::Event(format!(
+          "off={} error code={} reason=\"{}\"",
+          logger.base + offset, error.errno().0, error.reason(),
+        )));
+        return false;
+      }
+      Err(error) => {
+        logger.lines.push(Line::Event(format!(
+          "off={} error code={} reason=\"{}\"",
+          logger.base + error.offset(), error.errno().0, error.reason(),
+        )));
+        return false;
+      }
+    }
+  }
+}
+
+fn run_fixture(fixture: &Fixture, chunk_length: usize) -> Vec<String> {
+  let test_type = fixture.meta["type"].as_str().unwrap_or_else(|| {
+    panic!("{}: missing fixture type", fixture.location)
+  });
+
+  let mut parser = new_parser(test_type);
+  let mut logger = Logger {
+    is_request: test_type.starts_with("request"),
+    pause_on: fixture.meta["pause"].as_str().map(str::to_string),
+    skip_body: fixture.meta["skipBody"].as_bool().unwrap_or(false),
+    base: 0,
+    data_address: 0,
+    lines: Vec::new(),
+  };
+
+  for chunk in fixture.input.chunks(chunk_length) {
+    if !parse(&mut parser, &mut logger, chunk) {
+      break;
+    }
+  }
+
+  if test_type.ends_with("-finish") {
+    logger.lines.push(Line::Event(format!("off=NULL finish={}", parser.inner.finish)));
+  }
+
+  logger.print()
+}
+
+/// Drops the offset of lines whose offset `Logger` can't know.
+fn without_unknown_offsets(line: &str) -> &str {
+  let Some(rest) = line.strip_prefix("off=") else {
+    return line;
+  };
+
+  let Some((offset, event)) = rest.split_once(' ') else {
+    return line;
+  };
+
+  let is_known = offset == "NULL"
+    || event.starts_with("len=")
+    || event == "pause"
+    || event.starts_with("error ");
+
+  if is_known { line } else { event }
+}
+
+fn check(name: &str) {
+  let mut failures = Vec::new();
+
+  for fixture in read_fixtures(name) {
+    let expected: Vec<_> = fixture.expected
+      .iter()
+      .map(|line| without_unknown_offsets(line))
+      .collect();
+
+    let mut chunk_lengths = vec![fixture.input.len().max(1)];
+    if fixture.meta["noScan"].as_bool() != Some(true) {
+      chunk_lengths.push(1);
+    }
+
+    for chunk_length in chunk_lengths {
+      let actual = run_fixture(&fixture, chunk_length);
+      if actual != expected {
+        failures.push(format!(
+          "{} (chunks of {chunk_length} bytes)\nexpected:\n  {}\nactual:\n  {}",
+          fixture.location,
+          expected.join("\n  "),
+          actual.join("\n  "),
+        ));
+      }
+    }
+  }
+
+  assert!(failures.is_empty(), "{} failed:\n\n{}", failures.len(), failures.join("\n\n"));
+}
+
+macro_rules! conformance_tests {
+  ($($test_name: ident => $fixture: expr,)*) => {
+    $(
+      #[test]
+      fn $test_name() {
+        check($fixture);
+      }
+    )*
+  };
+}
+
+conformance_tests! {
+  test_request_sample => "request/sample",
+  test_request_lenient_headers => "request/lenient-headers",
+  test_request_lenient_version => "request/lenient-version",
+  test_request_method => "request/method",
+  test_request_uri => "request/uri",
+  test_request_connection => "request/connection",
+  test_request_content_length => "request/content-length",
+  test_request_transfer_encoding => "request/transfer-encoding",
+  test_request_invalid => "request/invalid",
+  test_request_finish => "request/finish",
+  test_request_pausing => "request/pausing",
+  test_request_pipelining => "request/pipelining",
+  test_response_sample => "response/sample",
+  test_response_connection => "response/connection",
+  test_response_content_length => "response/content-length",
+  test_response_transfer_encoding => "response/transfer-encoding",
+  test_response_invalid => "response/invalid",
+  test_response_finish => "response/finish",
+  test_response_lenient_version => "response/lenient-version",
+  test_response_pausing => "response/pausing",
+  test_response_pipelining => "response/pipelining",
+}
+
+#[test]
+fn test_unescape_input() {
+  assert_eq!(unescape_input("GET / HTTP/1.1\nA: \\x41\\101\\t\\\nb\n"), b"GET / HTTP/1.1\r\nA: AA\tb\r\n");
+  assert_eq!(unescape_input("\\xff"), "\u{ff}".as_bytes());
+}
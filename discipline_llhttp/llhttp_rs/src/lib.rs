@@ -1,28 +1,62 @@
 use llhttp_sys::*;
+use std::any::Any;
 use std::ffi::CStr;
+use std::panic::{self, AssertUnwindSafe};
 
-#[derive(Debug, Clone, Copy)]
-pub struct Error(u32);
+pub mod message;
 
-impl From<llhttp_errno_t> for Error {
-  fn from(llhttp_error: llhttp_errno_t) -> Self {
-    Error(llhttp_error.0)
-  }
+#[cfg(test)]
+mod conformance;
+
+/// An error returned by llhttp, along with where in the data it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+  errno: llhttp_errno_t,
+  offset: usize,
+  reason: String,
 }
 
-impl From<Error> for llhttp_errno_t {
-  fn from(error: Error) -> Self {
-    llhttp_errno_t(error.0)
+impl Error {
+  /// Makes an error that didn't come out of llhttp, like a failure to make
+  /// sense of a message that llhttp parsed just fine.
+  pub fn new(errno: llhttp_errno_t, offset: usize, reason: impl Into<String>) -> Self {
+    Self {
+      errno,
+      offset,
+      reason: reason.into(),
+    }
+  }
+
+  pub fn errno(&self) -> llhttp_errno_t {
+    self.errno
+  }
+
+  /// offset returns the position, in the data passed to the failed call, of
+  /// the byte the parser stopped at.
+  pub fn offset(&self) -> usize {
+    self.offset
+  }
+
+  /// reason returns llhttp's description of the error, or the one given by
+  /// the callback that failed.
+  pub fn reason(&self) -> &str {
+    &self.reason
+  }
+
+  /// name returns the name of the errno, like "HPE_INVALID_METHOD".
+  pub fn name(&self) -> &'static str {
+    // llhttp returns static strings for every errno, including unknown ones.
+    let name = unsafe {
+      CStr::from_ptr(llhttp_errno_name(self.errno))
+    };
+
+    name.to_str().unwrap_or("unknown")
   }
 }
 
 impl std::fmt::Display for Error {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    let message = unsafe { 
-      CStr::from_ptr(llhttp_errno_name(llhttp_errno_t(self.0))) 
-    };
-
-    write!(f, "{}", message.to_str().unwrap_or("unknown"))
+    write!(f, "{} at offset {}: {}", self.name(), self.offset, self.reason)
   }
 }
 
@@ -32,30 +66,179 @@ impl std::error::Error for Error {
 
 pub type ParserResult<T> = Result<T, Error>;
 
-struct ParserContext<'a, H: Callbacks + 'a> {
-  parser: &'a mut Parser,
+/// What a callback tells the parser to do next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+  Continue,
+  /// Stops parsing right after the event. `Parser::parse` returns
+  /// `Parsed::Paused` and parsing picks up from there after `Parser::resume`.
+  Pause,
+  /// Only honored by `on_headers_complete`: parses the message as if it had
+  /// no body, as for responses to HEAD requests. Other callbacks ignore it.
+  SkipBody,
+  /// Fails parsing. Data callbacks fail with `HPE_USER` and this reason, while
+  /// the others fail with llhttp's own errno and reason for them, like
+  /// `HPE_CB_HEADERS_COMPLETE`.
+  Fail(&'static CStr),
+}
+
+/// How far a call to `Parser::parse` got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parsed {
+  /// All of the data was parsed.
+  Done,
+  /// A callback returned `Flow::Pause` or the parser was paused. Only the
+  /// first `offset` bytes were parsed. Call `Parser::resume`, then pass the
+  /// rest of the data.
+  Paused { offset: usize },
+  /// A CONNECT or Upgrade message was parsed and the connection switched
+  /// protocols. The bytes from `offset` on belong to the new protocol.
+  Upgraded { offset: usize },
+}
+
+/// A read-only view of the parser, given to callbacks.
+#[derive(Debug, Clone, Copy)]
+pub struct ParserState<'a> {
+  inner: &'a llhttp_t,
+}
+
+impl ParserState<'_> {
+  /// get_version returns the HTTP version of the parsed message.
+  pub fn get_version(&self) -> Option<http::version::Version> {
+    match self.inner.http_major {
+      0 => match self.inner.http_minor {
+        9 => Some(http::version::Version::HTTP_09),
+        _ => None,
+      },
+      1 => match self.inner.http_minor {
+        0 => Some(http::version::Version::HTTP_10),
+        1 => Some(http::version::Version::HTTP_11),
+        _ => None,
+      },
+      2 => Some(http::version::Version::HTTP_2),
+      3 => Some(http::version::Version::HTTP_3),
+      _ => None,
+    }
+  }
+
+  /// get_method returns the HTTP method of the parsed message.
+  pub fn get_method(&self) -> Option<http::method::Method> {
+    match llhttp_method_t(self.inner.method as _) {
+      llhttp_method_t::HTTP_DELETE => Some(http::method::Method::DELETE),
+      llhttp_method_t::HTTP_GET => Some(http::method::Method::GET),
+      llhttp_method_t::HTTP_HEAD => Some(http::method::Method::HEAD),
+      llhttp_method_t::HTTP_POST => Some(http::method::Method::POST),
+      llhttp_method_t::HTTP_PUT => Some(http::method::Method::PUT),
+      llhttp_method_t::HTTP_CONNECT => Some(http::method::Method::CONNECT),
+      llhttp_method_t::HTTP_OPTIONS => Some(http::method::Method::OPTIONS),
+      llhttp_method_t::HTTP_TRACE => Some(http::method::Method::TRACE),
+      llhttp_method_t::HTTP_PATCH => Some(http::method::Method::PATCH),
+      _ => None,
+    }
+  }
+
+  /// get_status_code returns the HTTP status code of the parsed message.
+  pub fn get_status_code(&self) -> Option<http::status::StatusCode> {
+    http::status::StatusCode::from_u16(self.inner.status_code as _).ok()
+  }
+
+  /// get_upgrade returns true if the parsed message is an upgrade request.
+  pub fn get_upgrade(&self) -> bool {
+    self.inner.upgrade != 0
+  }
+
+  /// get_content_length returns the value of the content-length header of
+  /// the parsed message, or what's left of it while parsing the body.
+  pub fn get_content_length(&self) -> Option<u64> {
+    if self.inner.flags as u32 & llhttp_flags::F_CONTENT_LENGTH.0 == 0 {
+      return None;
+    }
+
+    Some(self.inner.content_length)
+  }
+
+  /// is_chunked returns true if the body of the parsed message uses the chunked transfer coding.
+  pub fn is_chunked(&self) -> bool {
+    self.inner.flags as u32 & llhttp_flags::F_CHUNKED.0 != 0
+  }
+
+  /// message_needs_eof returns true if the body of the parsed message ends when the connection is closed.
+  pub fn message_needs_eof(&self) -> bool {
+    unsafe { llhttp_message_needs_eof(self.inner as *const _) != 0 }
+  }
+
+  /// should_keep_alive returns true if the parsed message should keep the connection alive.
+  pub fn should_keep_alive(&self) -> bool {
+    unsafe { llhttp_should_keep_alive(self.inner as *const _) != 0 }
+  }
+}
+
+/// What `llhttp_t::data` points to while `Parser::run` runs.
+struct ParserContext<'a, H: Callbacks> {
   callbacks: &'a mut H,
+  /// A panic raised by a callback. Unwinding through llhttp isn't allowed,
+  /// so it's resumed once llhttp returns.
+  panic: Option<Box<dyn Any + Send>>,
 }
 
-#[inline]
-unsafe fn unwrap_context<'a, H: Callbacks>(parser: *mut llhttp_t) -> &'a mut ParserContext<'a, H> {
-  &mut *((*parser).data as *mut ParserContext<H>)
+/// Calls a callback and turns the `Flow` it returns into what llhttp expects.
+///
+/// # Safety
+///
+/// `parser` must be run by `Parser::run::<H>`.
+unsafe fn call<H: Callbacks>(
+  parser: *mut llhttp_t,
+  skip_body: libc::c_int,
+  callback: impl FnOnce(&mut H, &ParserState<'_>) -> Flow,
+) ->
+  libc::c_int
+{
+  if parser.is_null() || (*parser).data.is_null() {
+    return llhttp_errno_t::HPE_INTERNAL.0 as libc::c_int;
+  }
+
+  let context = &mut *((*parser).data as *mut ParserContext<H>);
+  if context.panic.is_some() {
+    return llhttp_errno_t::HPE_USER.0 as libc::c_int;
+  }
+
+  let state = ParserState { inner: &*parser };
+  let flow = panic::catch_unwind(AssertUnwindSafe(|| {
+    callback(&mut *context.callbacks, &state)
+  }));
+
+  match flow {
+    Ok(Flow::Continue) => {
+      0
+    }
+    Ok(Flow::Pause) => {
+      llhttp_errno_t::HPE_PAUSED.0 as libc::c_int
+    }
+    Ok(Flow::SkipBody) => {
+      skip_body
+    }
+    Ok(Flow::Fail(reason)) => {
+      llhttp_set_error_reason(parser, reason.as_ptr());
+      llhttp_errno_t::HPE_USER.0 as libc::c_int
+    }
+    Err(payload) => {
+      context.panic = Some(payload);
+      llhttp_set_error_reason(parser, c"callback panicked".as_ptr());
+      llhttp_errno_t::HPE_USER.0 as libc::c_int
+    }
+  }
 }
 
 macro_rules! callback_caller {
-  ($callback_name: ident) => {{
+  ($callback_name: ident) => {
+    callback_caller!($callback_name, 0)
+  };
+  ($callback_name: ident, $skip_body: expr) => {{
     extern "C" fn $callback_name<H: Callbacks>(parser: *mut llhttp_t) -> libc::c_int {
-      let context = unsafe { 
-        unwrap_context::<H>(parser) 
-      };
-
-      match context.callbacks.$callback_name(context.parser) {
-        Ok(()) => {
-          0
-        }
-        Err(error) => {
-          error.0 as libc::c_int
-        }
+      unsafe {
+        call::<H>(parser, $skip_body, |callbacks, state| {
+          callbacks.$callback_name(state)
+        })
       }
     }
 
@@ -64,29 +247,24 @@ macro_rules! callback_caller {
 }
 
 macro_rules! data_callback_caller {
-  ($callback_name: ident) => {{    
+  ($callback_name: ident) => {{
     extern "C" fn $callback_name<H: Callbacks>(
-      parser: *mut llhttp_t, 
-      data: *const libc::c_char, 
+      parser: *mut llhttp_t,
+      data: *const libc::c_char,
       data_length: usize,
-    ) -> 
-      libc::c_int 
+    ) ->
+      libc::c_int
     {
-      let data_as_slice = unsafe {
-        std::slice::from_raw_parts(data as *const u8, data_length)
+      let data_as_slice = if data.is_null() {
+        &[]
+      } else {
+        unsafe { std::slice::from_raw_parts(data as *const u8, data_length) }
       };
 
-      let context = unsafe {
-        unwrap_context::<H>(parser)
-      };
-
-      match context.callbacks.$callback_name(context.parser, data_as_slice) {
-        Ok(()) => {
-          0
-        }
-        Err(error) => {
-          error.0 as libc::c_int
-        }
+      unsafe {
+        call::<H>(parser, 0, |callbacks, state| {
+          callbacks.$callback_name(state, data_as_slice)
+        })
       }
     }
 
@@ -96,22 +274,25 @@ macro_rules! data_callback_caller {
 
 macro_rules! noop_callback {
   ($callback_name: ident) => {
-    fn $callback_name(&mut self, _parser: &mut Parser) -> ParserResult<()> {
-      Ok(())
+    fn $callback_name(&mut self, _parser: &ParserState<'_>) -> Flow {
+      Flow::Continue
     }
   };
 }
 
 macro_rules! noop_data_callback {
   ($callback_name: ident) => {
-    fn $callback_name(&mut self, _parser: &mut Parser, _data: &[u8]) -> ParserResult<()> {
-      Ok(())
+    fn $callback_name(&mut self, _parser: &ParserState<'_>, _data: &[u8]) -> Flow {
+      Flow::Continue
     }
   };
 }
 
 /// a list of callbacks that can be used to handle events from the parser
 /// https://github.com/nodejs/llhttp#api
+///
+/// Data callbacks are called more than once for a span, like a url, that is
+/// split across calls to `Parser::parse`.
 pub trait Callbacks: Sized {
   noop_callback!(on_message_begin);
   noop_data_callback!(on_protocol);
@@ -152,7 +333,8 @@ pub trait Callbacks: Sized {
       on_header_value: Some(data_callback_caller!(on_header_value)),
       on_chunk_extension_name: Some(data_callback_caller!(on_chunk_extension_name)),
       on_chunk_extension_value: Some(data_callback_caller!(on_chunk_extension_value)),
-      on_headers_complete: Some(callback_caller!(on_headers_complete)),
+      // Returning 1 from on_headers_complete tells llhttp the message has no body.
+      on_headers_complete: Some(callback_caller!(on_headers_complete, 1)),
       on_body: Some(data_callback_caller!(on_body)),
       on_message_complete: Some(callback_caller!(on_message_complete)),
       on_url_complete: Some(callback_caller!(on_url_complete)),
@@ -170,13 +352,28 @@ pub trait Callbacks: Sized {
   }
 }
 
-#[derive(Debug, Clone, Copy)]
+macro_rules! lenient_setter {
+  ($name: ident, $function: ident) => {
+    pub fn $name(&mut self, enabled: bool) {
+      unsafe {
+        $function(&mut self.inner as *mut _, enabled as libc::c_int);
+      }
+    }
+  };
+}
+
+#[derive(Debug, Clone)]
 pub struct Parser {
   inner: llhttp_t,
 }
 
+// SAFETY: `inner` only points to settings, callbacks and parsed data while
+// `Parser::run` runs, which clears those pointers before returning. The
+// other pointers it holds are llhttp's own static strings.
+unsafe impl Send for Parser {}
+
 impl Parser {
-  pub fn new_request_parser() -> Self {
+  fn new(parser_type: llhttp_type_t) -> Self {
     let mut parser = Parser {
       inner: llhttp_t::default(),
     };
@@ -184,7 +381,7 @@ impl Parser {
     unsafe {
       llhttp_init(
         &mut parser.inner as *mut _,
-        llhttp_type_t::HTTP_REQUEST,
+        parser_type,
         std::ptr::null::<llhttp_settings_t>() as *mut _,
       );
     };
@@ -192,150 +389,214 @@ impl Parser {
     parser
   }
 
-  pub fn new_response_parser() -> Self {
-    let mut parser = Parser {
-      inner: llhttp_t::default(),
-    };
-
-    unsafe {
-      llhttp_init(
-        &mut parser.inner as *mut _,
-        llhttp_type_t::HTTP_RESPONSE,
-        std::ptr::null::<llhttp_settings_t>() as *mut _,
-      );
-    };
+  pub fn new_request_parser() -> Self {
+    Self::new(llhttp_type_t::HTTP_REQUEST)
+  }
 
-    parser
+  pub fn new_response_parser() -> Self {
+    Self::new(llhttp_type_t::HTTP_RESPONSE)
   }
 
   pub fn new_message_parser() -> Self {
-    let mut this = Parser {
-      inner: llhttp_t::default(),
-    };
-    
-    unsafe {
-      llhttp_init(
-        &mut this.inner as *mut _,
-        llhttp_type_t::HTTP_BOTH,
-        std::ptr::null::<llhttp_settings_t>() as *mut _,
-      );
-    };
-    this
+    Self::new(llhttp_type_t::HTTP_BOTH)
   }
 
-  /// Parse full or partial request/response, invoking user callbacks along the way.
-  ///
-  /// If any of llhttp_data_cb returns errno not equal to HPE_OK - the parsing interrupts, and such errno is returned from llhttp_execute(). If HPE_PAUSED was used as a errno, the execution can be resumed with llhttp_resume() call.
-  ///
-  /// In a special case of CONNECT/Upgrade request/response HPE_PAUSED_UPGRADE is returned after fully parsing the request/response. If the user wishes to continue parsing, they need to invoke llhttp_resume_after_upgrade().
-  pub fn parse<H: Callbacks>(&mut self, callbacks: &mut H, data: &[u8]) -> ParserResult<()> {
+  /// Hooks up `callbacks`, lets `function` call into llhttp, then unhooks
+  /// them and returns the errno llhttp returned.
+  fn run<H: Callbacks>(
+    &mut self,
+    callbacks: &mut H,
+    function: impl FnOnce(*mut llhttp_t) -> llhttp_errno_t,
+  ) ->
+    llhttp_errno_t
+  {
     let mut settings = H::into_settings();
     let mut context = ParserContext {
-      parser: self,
       callbacks,
+      panic: None,
     };
 
-    self.inner.data = &mut context as *mut _ as *mut _;
+    self.inner.data = &mut context as *mut ParserContext<H> as *mut _;
     self.inner.settings = &mut settings as *mut _ as *mut _;
 
-    unsafe {
-      let result = llhttp_execute(
-        &mut self.inner as *mut _,
-        data as *const _ as *const _,
-        data.len() as _,
-      );
+    let errno = function(&mut self.inner as *mut _);
+
+    self.inner.data = std::ptr::null_mut();
+    self.inner.settings = std::ptr::null_mut();
+
+    if let Some(payload) = context.panic {
+      panic::resume_unwind(payload);
+    }
+
+    errno
+  }
 
-      match result.0 {
-        0 => Ok(()),
-        _ => Err(result.into()),
+  /// Describes the error the parser stopped with while parsing `data`.
+  fn error(&self, data: &[u8]) -> Error {
+    let errno = unsafe {
+      llhttp_get_errno(&self.inner as *const _)
+    };
+
+    // The error position is stale when the parser was already stopped
+    // before this call, so only trust it if it points into `data`.
+    let position = unsafe {
+      llhttp_get_error_pos(&self.inner as *const _)
+    } as usize;
+    let start = data.as_ptr() as usize;
+    let offset = if (start..=start + data.len()).contains(&position) {
+      position - start
+    } else {
+      0
+    };
+
+    let reason = unsafe {
+      llhttp_get_error_reason(&self.inner as *const _)
+    };
+    let reason = if reason.is_null() {
+      String::new()
+    } else {
+      unsafe { CStr::from_ptr(reason) }.to_string_lossy().into_owned()
+    };
+
+    Error {
+      errno,
+      offset,
+      reason,
+    }
+  }
+
+  /// Parse full or partial request/response, invoking user callbacks along the way.
+  ///
+  /// Parsing stops early when a callback returns `Flow::Pause`, and after a
+  /// CONNECT or Upgrade message, as described by `Parsed`. Errors stick: once
+  /// parsing failed, it keeps failing until the parser is `reset`.
+  pub fn parse<H: Callbacks>(&mut self, callbacks: &mut H, data: &[u8]) -> ParserResult<Parsed> {
+    let errno = self.run(callbacks, |parser| unsafe {
+      llhttp_execute(parser, data.as_ptr() as *const _, data.len())
+    });
+
+    match errno {
+      llhttp_errno_t::HPE_OK => {
+        Ok(Parsed::Done)
+      }
+      llhttp_errno_t::HPE_PAUSED => {
+        Ok(Parsed::Paused { offset: self.error(data).offset })
       }
+      llhttp_errno_t::HPE_PAUSED_UPGRADE => {
+        Ok(Parsed::Upgraded { offset: self.error(data).offset })
+      }
+      _ => {
+        Err(self.error(data))
+      }
+    }
+  }
+
+  /// Tells the parser the connection was closed, which completes messages
+  /// whose bodies are delimited by the end of the connection, and fails if a
+  /// message was cut short. The parser is left paused if `on_message_complete`
+  /// returns `Flow::Pause`.
+  pub fn finish<H: Callbacks>(&mut self, callbacks: &mut H) -> ParserResult<()> {
+    let errno = self.run(callbacks, |parser| unsafe {
+      llhttp_finish(parser)
+    });
+
+    match errno {
+      llhttp_errno_t::HPE_OK | llhttp_errno_t::HPE_PAUSED => Ok(()),
+      _ => Err(self.error(&[])),
     }
   }
 
+  /// state returns what the parser knows about the current message.
+  pub fn state(&self) -> ParserState<'_> {
+    ParserState { inner: &self.inner }
+  }
+
   /// get_version returns the HTTP version of the parsed message.
   pub fn get_version(&self) -> Option<http::version::Version> {
-    match self.inner.http_major {
-      0 => match self.inner.http_minor {
-        9 => Some(http::version::Version::HTTP_09),
-        _ => None,
-      },
-      1 => match self.inner.http_minor {
-        0 => Some(http::version::Version::HTTP_10),
-        1 => Some(http::version::Version::HTTP_11),
-        _ => None,
-      },
-      2 => Some(http::version::Version::HTTP_2),
-      3 => Some(http::version::Version::HTTP_3),
-      _ => None,
-    }
+    self.state().get_version()
   }
 
   /// get_method returns the HTTP method of the parsed message.
   pub fn get_method(&self) -> Option<http::method::Method> {
-    match llhttp_method_t(self.inner.method as _) {
-      llhttp_method_t::HTTP_DELETE => Some(http::method::Method::DELETE),
-      llhttp_method_t::HTTP_GET => Some(http::method::Method::GET),
-      llhttp_method_t::HTTP_HEAD => Some(http::method::Method::HEAD),
-      llhttp_method_t::HTTP_POST => Some(http::method::Method::POST),
-      llhttp_method_t::HTTP_PUT => Some(http::method::Method::PUT),
-      llhttp_method_t::HTTP_CONNECT => Some(http::method::Method::CONNECT),
-      llhttp_method_t::HTTP_OPTIONS => Some(http::method::Method::OPTIONS),
-      llhttp_method_t::HTTP_TRACE => Some(http::method::Method::TRACE),
-      llhttp_method_t::HTTP_PATCH => Some(http::method::Method::PATCH),
-      _ => None,
-    }
+    self.state().get_method()
   }
 
   /// get_status_code returns the HTTP status code of the parsed message.
   pub fn get_status_code(&self) -> Option<http::status::StatusCode> {
-    http::status::StatusCode::from_u16(self.inner.status_code as _).ok()
+    self.state().get_status_code()
   }
 
   /// get_upgrade returns true if the parsed message is an upgrade request.
   pub fn get_upgrade(&self) -> bool {
-    self.inner.upgrade != 0
+    self.state().get_upgrade()
+  }
+
+  /// get_content_length returns the value of the content-length header of
+  /// the parsed message, or what's left of it while parsing the body.
+  pub fn get_content_length(&self) -> Option<u64> {
+    self.state().get_content_length()
+  }
+
+  /// is_chunked returns true if the body of the parsed message uses the chunked transfer coding.
+  pub fn is_chunked(&self) -> bool {
+    self.state().is_chunked()
+  }
+
+  /// message_needs_eof returns true if the body of the parsed message ends when the connection is closed.
+  pub fn message_needs_eof(&self) -> bool {
+    self.state().message_needs_eof()
   }
 
   /// should_keep_alive returns true if the parsed message should keep the connection alive.
   pub fn should_keep_alive(&self) -> bool {
-    unsafe { llhttp_should_keep_alive(&self.inner as *const _) != 0 }
+    self.state().should_keep_alive()
   }
 
-  /// pause pauses the parser.
+  /// pause makes the next call to `parse` stop right away with `Parsed::Paused`.
+  /// Callbacks pause by returning `Flow::Pause` instead.
   pub fn pause(&mut self) {
     unsafe {
       llhttp_pause(&mut self.inner as *mut _);
     }
   }
 
-  /// resume resumes the parser.
+  /// resume resumes the parser after `Parsed::Paused`.
   pub fn resume(&mut self) {
     unsafe {
       llhttp_resume(&mut self.inner as *mut _);
     }
   }
 
-  /// resume_after_upgrade resumes the parser after an upgrade request.
+  /// resume_after_upgrade resumes the parser after `Parsed::Upgraded`, like
+  /// when the server declined the upgrade.
   pub fn resume_after_upgrade(&mut self) {
     unsafe {
       llhttp_resume_after_upgrade(&mut self.inner as *mut _);
     }
   }
+
+  /// reset readies the parser for a new stream of messages, clearing any error.
+  pub fn reset(&mut self) {
+    unsafe {
+      llhttp_reset(&mut self.inner as *mut _);
+    }
+  }
+
+  lenient_setter!(set_lenient_headers, llhttp_set_lenient_headers);
+  lenient_setter!(set_lenient_chunked_length, llhttp_set_lenient_chunked_length);
+  lenient_setter!(set_lenient_keep_alive, llhttp_set_lenient_keep_alive);
+  lenient_setter!(set_lenient_transfer_encoding, llhttp_set_lenient_transfer_encoding);
+  lenient_setter!(set_lenient_version, llhttp_set_lenient_version);
+  lenient_setter!(set_lenient_data_after_close, llhttp_set_lenient_data_after_close);
+  lenient_setter!(set_lenient_optional_lf_after_cr, llhttp_set_lenient_optional_lf_after_cr);
+  lenient_setter!(set_lenient_optional_cr_before_lf, llhttp_set_lenient_optional_cr_before_lf);
+  lenient_setter!(set_lenient_optional_crlf_after_chunk, llhttp_set_lenient_optional_crlf_after_chunk);
+  lenient_setter!(set_lenient_spaces_after_chunk_size, llhttp_set_lenient_spaces_after_chunk_size);
 }
 
 impl Default for Parser {
   fn default() -> Self {
-    let mut this = Parser {
-      inner: llhttp_t::default(),
-    };
-    unsafe {
-      llhttp_init(
-        &mut this.inner as *mut _,
-        llhttp_type_t::HTTP_BOTH,
-        std::ptr::null::<llhttp_settings_t>() as *mut _,
-      );
-    };
-    this
+    Self::new_message_parser()
   }
 }
 
@@ -345,12 +606,6 @@ impl From<Parser> for llhttp_t {
   }
 }
 
-impl From<llhttp_t> for Parser {
-  fn from(inner: llhttp_t) -> Self {
-    Parser { inner }
-  }
-}
-
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -366,32 +621,32 @@ mod tests {
     }
 
     impl Callbacks for TestRequestParser {
-      fn on_url(&mut self, _: &mut Parser, url: &[u8]) -> ParserResult<()> {
+      fn on_url(&mut self, _: &ParserState<'_>, url: &[u8]) -> Flow {
         assert_eq!(b"/say_hello", url);
         self.called_on_url = true;
-        Ok(())
+        Flow::Continue
       }
 
-      fn on_header_field(&mut self, _: &mut Parser, hdr: &[u8]) -> ParserResult<()> {
+      fn on_header_field(&mut self, _: &ParserState<'_>, hdr: &[u8]) -> Flow {
         assert!(hdr == b"Host" || hdr == b"Content-Length");
         self.called_on_header_field = true;
-        Ok(())
+        Flow::Continue
       }
 
-      fn on_header_value(&mut self, _: &mut Parser, val: &[u8]) -> ParserResult<()> {
+      fn on_header_value(&mut self, _: &ParserState<'_>, val: &[u8]) -> Flow {
         assert!(val == b"localhost.localdomain" || val == b"11");
         self.called_on_header_value = true;
-        Ok(())
+        Flow::Continue
       }
 
-      fn on_body(&mut self, parser: &mut Parser, body: &[u8]) -> ParserResult<()> {
-        assert_eq!(parser.get_method(), Some(http::method::Method::Method::POST));
+      fn on_body(&mut self, parser: &ParserState<'_>, body: &[u8]) -> Flow {
+        assert_eq!(parser.get_method(), Some(http::method::Method::POST));
         assert_eq!(parser.get_version(), Some(http::version::Version::HTTP_11));
         assert_eq!(parser.get_status_code(), None);
-        assert_eq!(parser.get_upgrade(), false);
+        assert!(!parser.get_upgrade());
         assert_eq!(body, b"Hello world");
         self.called_on_body = true;
-        Ok(())
+        Flow::Continue
       }
     }
 
@@ -399,7 +654,7 @@ mod tests {
     let mut handler = TestRequestParser::default();
     let mut parser = Parser::new_request_parser();
 
-    parser.parse(&mut handler, req).unwrap();
+    assert_eq!(parser.parse(&mut handler, req), Ok(Parsed::Done));
 
     assert!(handler.called_on_url);
     assert!(handler.called_on_header_field);
@@ -412,20 +667,21 @@ mod tests {
     struct DummyHandler;
     impl Callbacks for DummyHandler {}
 
-    let req = b"GET / HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\r\n";
+    let req = b"GET / HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\r\nframes";
 
     let mut handler = DummyHandler;
     let mut parser = Parser::new_request_parser();
 
-    match parser.parse(&mut handler, req) {
-      Err(err) if llhttp_errno_t::HPE_PAUSED_UPGRADE == err.into() => {
-        assert_eq!(parser.get_upgrade(), true);
-        parser.resume_after_upgrade();
-      }
-      _ => panic!("Unexpected error"),
-    }
+    let offset = match parser.parse(&mut handler, req) {
+      Ok(Parsed::Upgraded { offset }) => offset,
+      result => panic!("Unexpected result {:?}", result),
+    };
+
+    assert_eq!(&req[offset..], b"frames");
+    assert!(parser.get_upgrade());
+    parser.resume_after_upgrade();
 
-    parser.parse(&mut handler, b"").unwrap();
+    assert_eq!(parser.parse(&mut handler, b""), Ok(Parsed::Done));
   }
 
   #[test]
@@ -444,4 +700,81 @@ mod tests {
     parser.parse(&mut handler, &req[10..]).unwrap();
     assert_eq!(parser.get_version(), Some(http::version::Version::HTTP_11));
   }
+
+  #[test]
+  fn test_pause_and_resume() {
+    struct PauseAfterHead;
+    impl Callbacks for PauseAfterHead {
+      fn on_headers_complete(&mut self, _: &ParserState<'_>) -> Flow {
+        Flow::Pause
+      }
+    }
+
+    let req = b"POST / HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc";
+    let mut handler = PauseAfterHead;
+    let mut parser = Parser::new_request_parser();
+
+    let offset = match parser.parse(&mut handler, req) {
+      Ok(Parsed::Paused { offset }) => offset,
+      result => panic!("Unexpected result {:?}", result),
+    };
+    assert_eq!(&req[offset..], b"abc");
+
+    // The parser stays paused until it's resumed.
+    assert_eq!(parser.parse(&mut handler, &req[offset..]), Ok(Parsed::Paused { offset: 0 }));
+
+    parser.resume();
+    assert_eq!(parser.parse(&mut handler, &req[offset..]), Ok(Parsed::Done));
+  }
+
+  #[test]
+  fn test_error_has_errno_and_offset() {
+    struct DummyHandler;
+    impl Callbacks for DummyHandler {}
+
+    let req = b"GET / HTTP/1.1\r\nBad Header: x\r\n\r\n";
+    let mut handler = DummyHandler;
+    let mut parser = Parser::new_request_parser();
+
+    let error = parser.parse(&mut handler, req).unwrap_err();
+    assert_eq!(error.errno(), llhttp_errno_t::HPE_INVALID_HEADER_TOKEN);
+    assert_eq!(error.name(), "HPE_INVALID_HEADER_TOKEN");
+    assert_eq!(&req[error.offset()..error.offset() + 1], b" ");
+    assert!(!error.reason().is_empty());
+
+    // Errors stick until the parser is reset.
+    assert!(parser.parse(&mut handler, b"GET / HTTP/1.1\r\n\r\n").is_err());
+    parser.reset();
+    assert_eq!(parser.parse(&mut handler, b"GET / HTTP/1.1\r\n\r\n"), Ok(Parsed::Done));
+  }
+
+  #[test]
+  fn test_callback_failure() {
+    struct RejectUrls;
+    impl Callbacks for RejectUrls {
+      fn on_url(&mut self, _: &ParserState<'_>, _: &[u8]) -> Flow {
+        Flow::Fail(c"urls are not welcome")
+      }
+    }
+
+    let mut parser = Parser::new_request_parser();
+    let error = parser.parse(&mut RejectUrls, b"GET /path HTTP/1.1\r\n\r\n").unwrap_err();
+
+    assert_eq!(error.errno(), llhttp_errno_t::HPE_USER);
+    assert_eq!(error.reason(), "urls are not welcome");
+  }
+
+  #[test]
+  #[should_panic(expected = "from a callback")]
+  fn test_callback_panic_is_resumed() {
+    struct Panicking;
+    impl Callbacks for Panicking {
+      fn on_message_begin(&mut self, _: &ParserState<'_>) -> Flow {
+        panic!("from a callback");
+      }
+    }
+
+    let mut parser = Parser::new_request_parser();
+    let _ = parser.parse(&mut Panicking, b"GET / HTTP/1.1\r\n\r\n");
+  }
 }
@@ -0,0 +1,778 @@
+//! Incremental decoding of whole HTTP/1.x messages.
+//!
+//! `Decoder` turns bytes into a stream of `Event`s: a head, the decoded
+//! pieces of the body, and the end of the message. Bodies are never
+//! buffered; every `Event::Body` refers to the bytes that were passed to
+//! `Decoder::decode`. `MessageReader` does the same on top of an
+//! `std::io::Read`.
+
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::marker::PhantomData;
+use std::ops::Range;
+use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Version};
+use llhttp_sys::*;
+use crate::{Callbacks, Error, Flow, Parsed, Parser, ParserState};
+
+/// Message heads longer than this, in bytes, are rejected with `HPE_USER`.
+pub const MAXIMUM_HEAD_LENGTH: usize = 64 * 1024;
+/// Messages with more headers than this are rejected with `HPE_USER`.
+pub const MAXIMUM_HEADERS: usize = 128;
+
+const READ_BUFFER_LENGTH: usize = 16 * 1024;
+
+// SECTION: Heads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestHead {
+  pub method: Method,
+  /// The request target exactly as it was sent, usually a path and query,
+  /// but an authority for CONNECT and an absolute url for proxies.
+  pub target: String,
+  pub version: Version,
+  pub headers: HeaderMap,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseHead {
+  pub status: StatusCode,
+  pub reason: String,
+  pub version: Version,
+  pub headers: HeaderMap,
+}
+
+/// How the end of a message body is found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyFraming {
+  None,
+  ContentLength(u64),
+  Chunked,
+  /// The body ends when the connection is closed. Only happens for responses.
+  UntilEof,
+}
+
+/// Whether a decoder parses requests or responses.
+pub trait MessageKind {
+  type Head;
+
+  fn new_parser() -> Parser;
+  fn build_head(parser: &ParserState<'_>, collected: &mut Collected) -> Result<Self::Head, Error>;
+}
+
+pub enum Request {}
+pub enum Response {}
+
+/// An error found by the decoder rather than by llhttp. The offset is filled
+/// in once llhttp stops.
+fn invalid(errno: llhttp_errno_t, reason: &str) -> Error {
+  Error::new(errno, 0, reason)
+}
+
+fn version(parser: &ParserState<'_>) -> Result<Version, Error> {
+  parser
+    .get_version()
+    .ok_or_else(|| invalid(llhttp_errno_t::HPE_INVALID_VERSION, "Unsupported HTTP version"))
+}
+
+impl MessageKind for Request {
+  type Head = RequestHead;
+
+  fn new_parser() -> Parser {
+    Parser::new_request_parser()
+  }
+
+  fn build_head(parser: &ParserState<'_>, collected: &mut Collected) -> Result<RequestHead, Error> {
+    let method = Method::from_bytes(&collected.method)
+      .map_err(|_| invalid(llhttp_errno_t::HPE_INVALID_METHOD, "Invalid method"))?;
+    let target = String::from_utf8(std::mem::take(&mut collected.target))
+      .map_err(|_| invalid(llhttp_errno_t::HPE_INVALID_URL, "Request target is not UTF-8"))?;
+
+    Ok(RequestHead {
+      method,
+      target,
+      version: version(parser)?,
+      headers: std::mem::take(&mut collected.headers),
+    })
+  }
+}
+
+impl MessageKind for Response {
+  type Head = ResponseHead;
+
+  fn new_parser() -> Parser {
+    Parser::new_response_parser()
+  }
+
+  fn build_head(parser: &ParserState<'_>, collected: &mut Collected) -> Result<ResponseHead, Error> {
+    let status = parser
+      .get_status_code()
+      .ok_or_else(|| invalid(llhttp_errno_t::HPE_INVALID_STATUS, "Invalid status code"))?;
+
+    Ok(ResponseHead {
+      status,
+      reason: String::from_utf8_lossy(&collected.reason).into_owned(),
+      version: version(parser)?,
+      headers: std::mem::take(&mut collected.headers),
+    })
+  }
+}
+
+// SECTION: Events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<H> {
+  Head {
+    head: H,
+    framing: BodyFraming,
+    /// The message asks to switch protocols, either with an Upgrade header
+    /// or by being a CONNECT request.
+    upgrade: bool,
+  },
+  /// Decoded body bytes, with chunk framing removed, as a range of the
+  /// input passed to `Decoder::decode`.
+  Body(Range<usize>),
+  MessageComplete {
+    keep_alive: bool,
+  },
+  /// The connection switched protocols. Everything after the bytes the
+  /// decoder consumed belongs to the new protocol.
+  Upgrade,
+}
+
+#[derive(Debug)]
+pub struct Decoded<H> {
+  /// How many bytes of the input were decoded. The rest must be passed to
+  /// the next call to `Decoder::decode`.
+  pub consumed: usize,
+  pub events: Vec<Event<H>>,
+}
+
+// SECTION: Decoder.
+/// What the callbacks gathered of the head being parsed.
+#[derive(Debug, Default)]
+pub struct Collected {
+  method: Vec<u8>,
+  target: Vec<u8>,
+  reason: Vec<u8>,
+  header_name: Vec<u8>,
+  header_value: Vec<u8>,
+  headers: HeaderMap,
+  head_length: usize,
+}
+
+impl Collected {
+  fn clear(&mut self) {
+    self.method.clear();
+    self.target.clear();
+    self.reason.clear();
+    self.header_name.clear();
+    self.header_value.clear();
+    self.headers.clear();
+    self.head_length = 0;
+  }
+
+  fn append(&mut self, field: fn(&mut Self) -> &mut Vec<u8>, data: &[u8]) -> Result<(), Error> {
+    self.head_length += data.len();
+    if self.head_length > MAXIMUM_HEAD_LENGTH {
+      return Err(invalid(llhttp_errno_t::HPE_USER, "Message head is too long"));
+    }
+
+    field(self).extend_from_slice(data);
+    Ok(())
+  }
+}
+
+struct DecoderCallbacks<K: MessageKind> {
+  collected: Collected,
+  events: Vec<Event<K::Head>>,
+  /// Address of the first byte of the input being parsed, for turning body
+  /// pointers into ranges.
+  input_address: usize,
+  skip_next_body: bool,
+  /// Why a callback failed, since llhttp only knows that one did.
+  failure: Option<Error>,
+}
+
+impl<K: MessageKind> DecoderCallbacks<K> {
+  fn flow(&mut self, result: Result<(), Error>) -> Flow {
+    match result {
+      Ok(()) => {
+        Flow::Continue
+      }
+      Err(error) => {
+        self.failure = Some(error);
+        Flow::Fail(c"Rejected by the message decoder")
+      }
+    }
+  }
+
+  fn add_header(&mut self) -> Result<(), Error> {
+    let collected = &mut self.collected;
+    if collected.headers.len() >= MAXIMUM_HEADERS {
+      return Err(invalid(llhttp_errno_t::HPE_USER, "Too many headers"));
+    }
+
+    let name = HeaderName::from_bytes(&collected.header_name)
+      .map_err(|_| invalid(llhttp_errno_t::HPE_INVALID_HEADER_TOKEN, "Invalid header name"))?;
+    let value = HeaderValue::from_bytes(&collected.header_value)
+      .map_err(|_| invalid(llhttp_errno_t::HPE_INVALID_HEADER_TOKEN, "Invalid header value"))?;
+
+    collected.headers.append(name, value);
+    collected.header_name.clear();
+    collected.header_value.clear();
+    Ok(())
+  }
+}
+
+impl<K: MessageKind> Callbacks for DecoderCallbacks<K> {
+  fn on_message_begin(&mut self, _parser: &ParserState<'_>) -> Flow {
+    self.collected.clear();
+    Flow::Continue
+  }
+
+  fn on_method(&mut self, _parser: &ParserState<'_>, data: &[u8]) -> Flow {
+    let result = self.collected.append(|collected| &mut collected.method, data);
+    self.flow(result)
+  }
+
+  fn on_url(&mut self, _parser: &ParserState<'_>, data: &[u8]) -> Flow {
+    let result = self.collected.append(|collected| &mut collected.target, data);
+    self.flow(result)
+  }
+
+  fn on_status(&mut self, _parser: &ParserState<'_>, data: &[u8]) -> Flow {
+    let result = self.collected.append(|collected| &mut collected.reason, data);
+    self.flow(result)
+  }
+
+  fn on_header_field(&mut self, _parser: &ParserState<'_>, data: &[u8]) -> Flow {
+    let result = self.collected.append(|collected| &mut collected.header_name, data);
+    self.flow(result)
+  }
+
+  fn on_header_value(&mut self, _parser: &ParserState<'_>, data: &[u8]) -> Flow {
+    let result = self.collected.append(|collected| &mut collected.header_value, data);
+    self.flow(result)
+  }
+
+  fn on_header_value_complete(&mut self, _parser: &ParserState<'_>) -> Flow {
+    let result = self.add_header();
+    self.flow(result)
+  }
+
+  fn on_headers_complete(&mut self, parser: &ParserState<'_>) -> Flow {
+    let head = match K::build_head(parser, &mut self.collected) {
+      Ok(head) => head,
+      Err(error) => return self.flow(Err(error)),
+    };
+
+    let skip_body = std::mem::take(&mut self.skip_next_body);
+    let framing = if skip_body {
+      BodyFraming::None
+    } else if parser.is_chunked() {
+      BodyFraming::Chunked
+    } else if let Some(length) = parser.get_content_length() {
+      match length {
+        0 => BodyFraming::None,
+        length => BodyFraming::ContentLength(length),
+      }
+    } else if parser.message_needs_eof() {
+      BodyFraming::UntilEof
+    } else {
+      BodyFraming::None
+    };
+
+    self.events.push(Event::Head {
+      head,
+      framing,
+      upgrade: parser.get_upgrade(),
+    });
+
+    if skip_body {
+      return Flow::SkipBody;
+    }
+
+    // Pausing gives the caller a chance to act on the head, like telling the
+    // response decoder to skip the body of a response to a HEAD request,
+    // before any of the body is decoded.
+    Flow::Pause
+  }
+
+  fn on_body(&mut self, _parser: &ParserState<'_>, data: &[u8]) -> Flow {
+    // llhttp ends the span of a body with no known length at the end of
+    // every input, even an empty one.
+    if data.is_empty() {
+      return Flow::Continue;
+    }
+
+    let start = data.as_ptr() as usize - self.input_address;
+    self.events.push(Event::Body(start..start + data.len()));
+    Flow::Continue
+  }
+
+  fn on_message_complete(&mut self, parser: &ParserState<'_>) -> Flow {
+    self.events.push(Event::MessageComplete {
+      keep_alive: parser.should_keep_alive(),
+    });
+
+    if parser.get_upgrade() {
+      // llhttp stops by itself with HPE_PAUSED_UPGRADE.
+      return Flow::Continue;
+    }
+
+    // One message at a time, so that the caller sees where each one ends.
+    Flow::Pause
+  }
+}
+
+/// Decodes a stream of requests or responses, as they arrive.
+pub struct Decoder<K: MessageKind> {
+  parser: Parser,
+  callbacks: DecoderCallbacks<K>,
+  upgraded: bool,
+  kind: PhantomData<K>,
+}
+
+pub type RequestDecoder = Decoder<Request>;
+pub type ResponseDecoder = Decoder<Response>;
+
+impl<K: MessageKind> Decoder<K> {
+  pub fn new() -> Self {
+    Self {
+      parser: K::new_parser(),
+      callbacks: DecoderCallbacks {
+        collected: Collected::default(),
+        events: Vec::new(),
+        input_address: 0,
+        skip_next_body: false,
+        failure: None,
+      },
+      upgraded: false,
+      kind: PhantomData,
+    }
+  }
+
+  /// Decodes as much of `data` as possible, stopping after each head and
+  /// each complete message. Call it again with the bytes it didn't consume,
+  /// even if there are none, before waiting for more input: a message
+  /// without a body completes without further input.
+  pub fn decode(&mut self, data: &[u8]) -> Result<Decoded<K::Head>, Error> {
+    if self.upgraded {
+      return Ok(Decoded { consumed: 0, events: Vec::new() });
+    }
+
+    self.callbacks.input_address = data.as_ptr() as usize;
+    let result = self.parser.parse(&mut self.callbacks, data);
+    let mut events = std::mem::take(&mut self.callbacks.events);
+
+    let consumed = match result.map_err(|error| self.explain(error))? {
+      Parsed::Done => {
+        data.len()
+      }
+      Parsed::Paused { offset } => {
+        self.parser.resume();
+        offset
+      }
+      Parsed::Upgraded { offset } => {
+        self.upgraded = true;
+        events.push(Event::Upgrade);
+        offset
+      }
+    };
+
+    Ok(Decoded { consumed, events })
+  }
+
+  /// Replaces the error llhttp reports for a failed callback with the reason
+  /// the callback failed.
+  fn explain(&mut self, error: Error) -> Error {
+    match self.callbacks.failure.take() {
+      Some(failure) => Error::new(failure.errno(), error.offset(), failure.reason()),
+      None => error,
+    }
+  }
+
+  /// Tells the decoder the connection was closed. Completes a message whose
+  /// body ends there and fails if a message was cut short.
+  pub fn finish(&mut self) -> Result<Vec<Event<K::Head>>, Error> {
+    if self.upgraded {
+      return Ok(Vec::new());
+    }
+
+    let result = self.parser.finish(&mut self.callbacks);
+    let events = std::mem::take(&mut self.callbacks.events);
+    result.map_err(|error| self.explain(error))?;
+
+    // on_message_complete pauses the parser.
+    self.parser.resume();
+    Ok(events)
+  }
+
+  /// Whether the connection switched protocols and the decoder stopped.
+  pub fn is_upgraded(&self) -> bool {
+    self.upgraded
+  }
+
+  /// Keeps decoding after an upgrade request that the server declined, by
+  /// answering it with anything but 101 Switching Protocols.
+  pub fn resume_after_upgrade(&mut self) {
+    if self.upgraded {
+      self.parser.resume_after_upgrade();
+      self.upgraded = false;
+    }
+  }
+}
+
+impl<K: MessageKind> Default for Decoder<K> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Decoder<Response> {
+  /// The next response has no body whatever its head says, because it
+  /// answers a HEAD request or a successful CONNECT request. Must be called
+  /// before its head is decoded.
+  pub fn skip_body_of_next_message(&mut self) {
+    self.callbacks.skip_next_body = true;
+  }
+}
+
+// SECTION: Reader.
+/// An `Event` whose body bytes are borrowed from a `MessageReader`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Item<'a, H> {
+  Head {
+    head: H,
+    framing: BodyFraming,
+    upgrade: bool,
+  },
+  Body(&'a [u8]),
+  MessageComplete {
+    keep_alive: bool,
+  },
+  Upgrade,
+}
+
+fn into_io_error(error: Error) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Reads messages from a connection, holding no more than one read's worth
+/// of a body in memory at a time.
+pub struct MessageReader<K: MessageKind, R> {
+  reader: R,
+  decoder: Decoder<K>,
+  buffer: Vec<u8>,
+  /// Start of the bytes in `buffer` that the decoder didn't consume yet.
+  start: usize,
+  pending: VecDeque<Event<K::Head>>,
+  /// The decoder was paused or got new input and may make progress without
+  /// more bytes from `reader`.
+  may_progress: bool,
+  reached_eof: bool,
+}
+
+pub type RequestReader<R> = MessageReader<Request, R>;
+pub type ResponseReader<R> = MessageReader<Response, R>;
+
+impl<K: MessageKind, R: Read> MessageReader<K, R> {
+  pub fn new(reader: R) -> Self {
+    Self {
+      reader,
+      decoder: Decoder::new(),
+      buffer: Vec::with_capacity(READ_BUFFER_LENGTH),
+      start: 0,
+      pending: VecDeque::new(),
+      may_progress: true,
+      reached_eof: false,
+    }
+  }
+
+  pub fn get_ref(&self) -> &R {
+    &self.reader
+  }
+
+  pub fn get_mut(&mut self) -> &mut R {
+    &mut self.reader
+  }
+
+  pub fn decoder_mut(&mut self) -> &mut Decoder<K> {
+    &mut self.decoder
+  }
+
+  /// See `Decoder::resume_after_upgrade`.
+  pub fn resume_after_upgrade(&mut self) {
+    if self.decoder.is_upgraded() {
+      self.decoder.resume_after_upgrade();
+      self.pending.retain(|event| !matches!(event, Event::Upgrade));
+      self.may_progress = true;
+    }
+  }
+
+  /// Bytes that were read but not decoded. After `Item::Upgrade`, these are
+  /// the first bytes of the new protocol.
+  pub fn buffered(&self) -> &[u8] {
+    &self.buffer[self.start..]
+  }
+
+  pub fn into_parts(mut self) -> (R, Vec<u8>) {
+    self.buffer.drain(..self.start);
+    (self.reader, self.buffer)
+  }
+
+  /// Returns the next item, or `None` if the connection was closed between
+  /// messages or switched protocols.
+  pub fn next_item(&mut self) -> io::Result<Option<Item<'_, K::Head>>> {
+    loop {
+      if let Some(event) = self.pending.pop_front() {
+        return Ok(Some(match event {
+          Event::Head { head, framing, upgrade } => Item::Head { head, framing, upgrade },
+          Event::Body(range) => Item::Body(&self.buffer[range]),
+          Event::MessageComplete { keep_alive } => Item::MessageComplete { keep_alive },
+          Event::Upgrade => Item::Upgrade,
+        }));
+      }
+
+      if self.decoder.is_upgraded() {
+        return Ok(None);
+      }
+
+      if self.may_progress {
+        // Pending body ranges point into `buffer`, which is only compacted
+        // once they've all been returned.
+        self.buffer.drain(..self.start);
+        self.start = 0;
+
+        let decoded = self.decoder.decode(&self.buffer).map_err(into_io_error)?;
+        self.start = decoded.consumed;
+        self.may_progress = !decoded.events.is_empty() && !self.decoder.is_upgraded();
+        self.pending.extend(decoded.events);
+        continue;
+      }
+
+      if self.reached_eof {
+        return Ok(None);
+      }
+
+      let length = self.buffer.len();
+      self.buffer.resize(length + READ_BUFFER_LENGTH, 0);
+      let read = match self.reader.read(&mut self.buffer[length..]) {
+        Ok(read) => read,
+        Err(error) => {
+          self.buffer.truncate(length);
+          return Err(error);
+        }
+      };
+      self.buffer.truncate(length + read);
+
+      if read > 0 {
+        self.may_progress = true;
+        continue;
+      }
+
+      self.reached_eof = true;
+      let events = self.decoder.finish().map_err(into_io_error)?;
+      self.pending.extend(events);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn decode_all<K: MessageKind>(decoder: &mut Decoder<K>, data: &[u8]) -> (Vec<Event<K::Head>>, Vec<u8>) {
+    let mut events = Vec::new();
+    let mut body = Vec::new();
+    let mut start = 0;
+
+    loop {
+      let decoded = decoder.decode(&data[start..]).unwrap();
+      let progressed = decoded.consumed > 0 || !decoded.events.is_empty();
+
+      for event in decoded.events {
+        if let Event::Body(range) = &event {
+          body.extend_from_slice(&data[start + range.start..start + range.end]);
+        }
+        events.push(event);
+      }
+
+      start += decoded.consumed;
+      if !progressed || decoder.is_upgraded() {
+        return (events, body);
+      }
+    }
+  }
+
+  #[test]
+  fn test_content_length_request() {
+    let mut decoder = RequestDecoder::new();
+    let (events, body) = decode_all(
+      &mut decoder,
+      b"POST /submit?x=1 HTTP/1.1\r\nHost: example.com\r\nContent-Length: 5\r\n\r\nhello",
+    );
+
+    let Event::Head { head, framing, upgrade } = &events[0] else {
+      panic!("expected a head, got {:?}", events[0]);
+    };
+    assert_eq!(head.method, Method::POST);
+    assert_eq!(head.target, "/submit?x=1");
+    assert_eq!(head.version, Version::HTTP_11);
+    assert_eq!(head.headers.get("host").unwrap(), "example.com");
+    assert_eq!(*framing, BodyFraming::ContentLength(5));
+    assert!(!upgrade);
+
+    assert_eq!(body, b"hello");
+    assert_eq!(events.last(), Some(&Event::MessageComplete { keep_alive: true }));
+  }
+
+  #[test]
+  fn test_chunked_response() {
+    let mut decoder = ResponseDecoder::new();
+    let (events, body) = decode_all(
+      &mut decoder,
+      b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n",
+    );
+
+    let Event::Head { head, framing, .. } = &events[0] else {
+      panic!("expected a head, got {:?}", events[0]);
+    };
+    assert_eq!(head.status, StatusCode::OK);
+    assert_eq!(head.reason, "OK");
+    assert_eq!(*framing, BodyFraming::Chunked);
+    assert_eq!(body, b"hello world");
+    assert_eq!(events.last(), Some(&Event::MessageComplete { keep_alive: true }));
+  }
+
+  #[test]
+  fn test_pipelined_requests_without_bodies() {
+    let mut decoder = RequestDecoder::new();
+    let (events, _) = decode_all(
+      &mut decoder,
+      b"GET /a HTTP/1.1\r\nHost: a\r\n\r\nGET /b HTTP/1.0\r\nHost: a\r\n\r\n",
+    );
+
+    let targets: Vec<_> = events
+      .iter()
+      .filter_map(|event| match event {
+        Event::Head { head, .. } => Some(head.target.as_str()),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(targets, ["/a", "/b"]);
+
+    let keep_alives: Vec<_> = events
+      .iter()
+      .filter_map(|event| match event {
+        Event::MessageComplete { keep_alive } => Some(*keep_alive),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(keep_alives, [true, false]);
+  }
+
+  #[test]
+  fn test_upgrade() {
+    let mut decoder = RequestDecoder::new();
+    let data = b"GET /chat HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\r\nframes";
+    let mut start = 0;
+    let mut events = Vec::new();
+
+    while !decoder.is_upgraded() {
+      let decoded = decoder.decode(&data[start..]).unwrap();
+      start += decoded.consumed;
+      events.extend(decoded.events);
+    }
+
+    assert!(matches!(events[0], Event::Head { upgrade: true, .. }));
+    assert_eq!(events.last(), Some(&Event::Upgrade));
+    assert_eq!(&data[start..], b"frames");
+  }
+
+  #[test]
+  fn test_response_to_head_request() {
+    let mut decoder = ResponseDecoder::new();
+    decoder.skip_body_of_next_message();
+    let (events, body) = decode_all(
+      &mut decoder,
+      b"HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\n",
+    );
+
+    assert!(matches!(events[0], Event::Head { framing: BodyFraming::None, .. }));
+    assert!(body.is_empty());
+    assert_eq!(events.last(), Some(&Event::MessageComplete { keep_alive: true }));
+  }
+
+  #[test]
+  fn test_body_until_eof() {
+    let mut reader = ResponseReader::new(&b"HTTP/1.1 200 OK\r\n\r\nuntil the end"[..]);
+
+    let Some(Item::Head { framing, .. }) = reader.next_item().unwrap() else {
+      panic!("expected a head");
+    };
+    assert_eq!(framing, BodyFraming::UntilEof);
+
+    let mut body = Vec::new();
+    loop {
+      match reader.next_item().unwrap() {
+        Some(Item::Body(bytes)) => body.extend_from_slice(bytes),
+        Some(Item::MessageComplete { keep_alive }) => {
+          assert!(!keep_alive);
+          break;
+        }
+        item => panic!("unexpected item {:?}", item),
+      }
+    }
+
+    assert_eq!(body, b"until the end");
+    assert!(reader.next_item().unwrap().is_none());
+  }
+
+  #[test]
+  fn test_reader_streams_body_in_pieces() {
+    /// Hands out its bytes a few at a time, like a slow connection.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+      fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let length = self.0.len().min(buffer.len()).min(3);
+        buffer[..length].copy_from_slice(&self.0[..length]);
+        self.0 = &self.0[length..];
+        Ok(length)
+      }
+    }
+
+    let mut reader = RequestReader::new(Trickle(
+      b"PUT /file HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\na\r\n0123456789\r\n0\r\n\r\n",
+    ));
+
+    assert!(matches!(reader.next_item().unwrap(), Some(Item::Head { framing: BodyFraming::Chunked, .. })));
+
+    let mut body = Vec::new();
+    while let Some(Item::Body(bytes)) = reader.next_item().unwrap() {
+      assert!(bytes.len() <= 3);
+      body.extend_from_slice(bytes);
+    }
+    assert_eq!(body, b"0123456789");
+    assert!(reader.next_item().unwrap().is_none());
+  }
+
+  #[test]
+  fn test_invalid_request() {
+    let mut decoder = RequestDecoder::new();
+    assert!(decoder.decode(b"GET / HTTP/1.1\r\nBad Header\r\n\r\n").is_err());
+  }
+
+  #[test]
+  fn test_too_many_headers() {
+    let mut request = b"GET / HTTP/1.1\r\n".to_vec();
+    for index in 0..=MAXIMUM_HEADERS {
+      request.extend_from_slice(format!("X-Header-{index}: value\r\n").as_bytes());
+    }
+    request.extend_from_slice(b"\r\n");
+
+    let error = RequestDecoder::new().decode(&request).unwrap_err();
+    assert_eq!(error.errno(), llhttp_errno_t::HPE_USER);
+    assert_eq!(error.reason(), "Too many headers");
+    assert!(error.offset() > 0 && error.offset() < request.len());
+  }
+}
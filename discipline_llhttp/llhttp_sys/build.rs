@@ -0,0 +1,14 @@
+// Compiles the llhttp C sources vendored in `vendor`, which are the release
+// build of the llhttp version in `../llhttp`. The bindings in `src/llhttp.rs`
+// are generated from `vendor/include/llhttp.h`.
+fn main() {
+  println!("cargo:rerun-if-changed=vendor");
+
+  cc::Build::new()
+    .file("vendor/src/api.c")
+    .file("vendor/src/http.c")
+    .file("vendor/src/llhttp.c")
+    .include("vendor/include")
+    .warnings(false)
+    .compile("llhttp");
+}